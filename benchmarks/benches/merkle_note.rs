@@ -21,7 +21,7 @@ pub fn decrypt_note_for_spender(c: &mut Criterion) {
                 );
 
                 let ekp = EphemeralKeyPair::new();
-                let value_commitment = ValueCommitment::new(note.value(), note.asset_generator());
+                let value_commitment = ValueCommitment::new(note.value(), note.asset_generator().into());
                 let merkle_note = MerkleNote::new(&spender_key, &note, &value_commitment, &ekp);
 
                 return (spender_key.outgoing_view_key().clone(), merkle_note);
@@ -52,7 +52,7 @@ pub fn decrypt_note_for_owner(c: &mut Criterion) {
                 );
 
                 let ekp = EphemeralKeyPair::new();
-                let value_commitment = ValueCommitment::new(note.value(), note.asset_generator());
+                let value_commitment = ValueCommitment::new(note.value(), note.asset_generator().into());
                 let merkle_note = MerkleNote::new(&spender_key, &note, &value_commitment, &ekp);
 
                 return (receiver_key.incoming_view_key().clone(), merkle_note);
@@ -49,7 +49,25 @@ export const TRANSACTION_PUBLIC_KEY_RANDOMNESS_LENGTH: number
 export const TRANSACTION_EXPIRATION_LENGTH: number
 export const TRANSACTION_FEE_LENGTH: number
 export const TRANSACTION_VERSION: number
-export function verifyTransactions(serializedTransactions: Array<Buffer>): boolean
+export function verifyTransactions(serializedTransactions: Array<Buffer>, activatedVersion?: number | undefined | null): boolean
+/** Maximum number of each description a transaction may contain */
+export interface TransactionLimits {
+  maxSpends: number
+  maxOutputs: number
+  maxMints: number
+  maxBurns: number
+}
+/**
+ * Limits on transactions read from peers, checked before the transaction
+ * is parsed
+ */
+export interface TransactionReadLimits {
+  maxSpends: number
+  maxOutputs: number
+  maxMints: number
+  maxBurns: number
+  maxBytes: number
+}
 export const enum LanguageCode {
   English = 0,
   ChineseSimplified = 1,
@@ -60,6 +78,10 @@ export const enum LanguageCode {
   Korean = 6,
   Spanish = 7
 }
+export const enum NetworkCode {
+  Mainnet = 0,
+  Testnet = 1
+}
 export interface Key {
   spendingKey: string
   viewKey: string
@@ -67,12 +89,17 @@ export interface Key {
   outgoingViewKey: string
   publicAddress: string
 }
-export function generateKey(): Key
+export function generateKey(network?: NetworkCode | undefined | null): Key
 export function spendingKeyToWords(privateKey: string, languageCode: LanguageCode): string
 export function wordsToSpendingKey(words: string, languageCode: LanguageCode): string
-export function generateKeyFromPrivateKey(privateKey: string): Key
+export function generateKeyFromPrivateKey(privateKey: string, network?: NetworkCode | undefined | null): Key
 export function initializeSapling(): void
 export function isValidPublicAddress(hexAddress: string): boolean
+/**
+ * Derive the public addresses of incoming view keys laid end to end in a
+ * buffer, returned end to end in the same order
+ */
+export function derivePublicAddresses(incomingViewKeys: Buffer): Buffer
 export class BoxKeyPair {
   constructor()
   static fromHex(secretHex: string): BoxKeyPair
@@ -153,9 +180,13 @@ export class Note {
 }
 export type NativeTransactionPosted = TransactionPosted
 export class TransactionPosted {
-  constructor(jsBytes: Buffer)
+  constructor(jsBytes: Buffer, limits?: TransactionReadLimits | undefined | null, network?: NetworkCode | undefined | null)
   serialize(): Buffer
-  verify(): boolean
+  /**
+   * Verify the transaction, accepting the versions up to the activated
+   * one, only the first version if it isn't given
+   */
+  verify(activatedVersion?: number | undefined | null): boolean
   notesLength(): number
   getNote(index: number): Buffer
   spendsLength(): number
@@ -167,7 +198,7 @@ export class TransactionPosted {
 }
 export type NativeTransaction = Transaction
 export class Transaction {
-  constructor(spenderHexKey: string)
+  constructor(spenderHexKey: string, limits?: TransactionLimits | undefined | null, network?: NetworkCode | undefined | null)
   /** Create a proof of a new note owned by the recipient in this transaction. */
   output(note: Note): void
   /** Spend the note owned by spender_hex_key at the given witness location. */
@@ -4,7 +4,8 @@
 
 use std::fmt::Display;
 
-use ironfish::keys::Language;
+use ironfish::keys::{self, Language, INCOMING_VIEW_KEY_SIZE, PUBLIC_ADDRESS_SIZE};
+use ironfish::NetworkKind;
 use ironfish::PublicAddress;
 use ironfish::SaplingKey;
 use napi::bindgen_prelude::*;
@@ -54,6 +55,26 @@ impl From<LanguageCode> for Language {
     }
 }
 
+// napi can't export NetworkKind either, for the same reason as Language
+#[napi]
+pub enum NetworkCode {
+    Mainnet,
+    Testnet,
+}
+impl From<NetworkCode> for NetworkKind {
+    fn from(item: NetworkCode) -> Self {
+        match item {
+            NetworkCode::Mainnet => NetworkKind::Mainnet,
+            NetworkCode::Testnet => NetworkKind::Testnet,
+        }
+    }
+}
+
+/// The network of an optional network argument, mainnet when it's left out
+pub(crate) fn network_kind(network: Option<NetworkCode>) -> NetworkKind {
+    network.map_or(NetworkKind::Mainnet, NetworkKind::from)
+}
+
 #[napi(object)]
 pub struct Key {
     pub spending_key: String,
@@ -64,8 +85,8 @@ pub struct Key {
 }
 
 #[napi]
-pub fn generate_key() -> Key {
-    let sapling_key = SaplingKey::generate_key();
+pub fn generate_key(network: Option<NetworkCode>) -> Key {
+    let sapling_key = SaplingKey::generate_key_for_network(network_kind(network));
 
     Key {
         spending_key: sapling_key.hex_spending_key(),
@@ -90,8 +111,12 @@ pub fn words_to_spending_key(words: String, language_code: LanguageCode) -> Resu
 }
 
 #[napi]
-pub fn generate_key_from_private_key(private_key: String) -> Result<Key> {
-    let sapling_key = SaplingKey::from_hex(&private_key).map_err(to_napi_err)?;
+pub fn generate_key_from_private_key(
+    private_key: String,
+    network: Option<NetworkCode>,
+) -> Result<Key> {
+    let sapling_key = SaplingKey::from_hex_for_network(&private_key, network_kind(network))
+        .map_err(to_napi_err)?;
 
     Ok(Key {
         spending_key: sapling_key.hex_spending_key(),
@@ -167,3 +192,14 @@ impl ThreadPoolHandler {
 pub fn is_valid_public_address(hex_address: String) -> bool {
     PublicAddress::from_hex(&hex_address).is_ok()
 }
+
+/// Derive the public addresses of incoming view keys laid end to end in a
+/// buffer, returned end to end in the same order
+#[napi]
+pub fn derive_public_addresses(incoming_view_keys: Buffer) -> Result<Buffer> {
+    let count = incoming_view_keys.len() / INCOMING_VIEW_KEY_SIZE;
+    let mut addresses = vec![0; count * PUBLIC_ADDRESS_SIZE];
+    keys::derive_public_addresses(&incoming_view_keys, &mut addresses).map_err(to_napi_err)?;
+
+    Ok(Buffer::from(addresses))
+}
@@ -5,6 +5,7 @@
 use ironfish::{
     assets::asset::ID_LENGTH as ASSET_ID_LENGTH,
     note::{AMOUNT_VALUE_SIZE, MEMO_SIZE, SCALAR_SIZE},
+    tree_position::NotePosition,
     ViewKey,
 };
 use napi::{bindgen_prelude::*, JsBuffer};
@@ -137,11 +138,11 @@ impl NativeNote {
     /// 'nullifier set', preventing double-spend.
     #[napi]
     pub fn nullifier(&self, owner_view_key: String, position: BigInt) -> Result<Buffer> {
-        let position_u64 = position.get_u64().1;
+        let position = NotePosition::new(position.get_u64().1);
 
         let view_key = ViewKey::from_hex(&owner_view_key).map_err(to_napi_err)?;
 
-        let nullifier: &[u8] = &self.note.nullifier(&view_key, position_u64).0;
+        let nullifier: &[u8] = &self.note.nullifier(&view_key, position).0;
 
         Ok(Buffer::from(nullifier))
     }
@@ -7,8 +7,10 @@ use std::convert::TryInto;
 
 use ironfish::assets::asset_identifier::AssetIdentifier;
 use ironfish::transaction::{
-    batch_verify_transactions, TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE,
-    TRANSACTION_PUBLIC_KEY_SIZE, TRANSACTION_SIGNATURE_SIZE,
+    batch_verify_transactions_for_version,
+    limits::{Limits, ReadLimits},
+    TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE, TRANSACTION_PUBLIC_KEY_SIZE,
+    TRANSACTION_SIGNATURE_SIZE,
 };
 use ironfish::{
     MerkleNoteHash, ProposedTransaction, PublicAddress, SaplingKey, Transaction,
@@ -20,7 +22,7 @@ use napi::{
 };
 use napi_derive::napi;
 
-use crate::to_napi_err;
+use crate::{network_kind, to_napi_err, NetworkCode};
 
 use super::note::NativeNote;
 use super::spend_proof::NativeSpendDescription;
@@ -54,10 +56,17 @@ pub const TRANSACTION_VERSION: u8 = TX_VERSION;
 #[napi]
 impl NativeTransactionPosted {
     #[napi(constructor)]
-    pub fn new(js_bytes: JsBuffer) -> Result<NativeTransactionPosted> {
+    pub fn new(
+        js_bytes: JsBuffer,
+        limits: Option<TransactionReadLimits>,
+        network: Option<NetworkCode>,
+    ) -> Result<NativeTransactionPosted> {
         let bytes = js_bytes.into_value()?;
+        let limits = limits.map(ReadLimits::from).unwrap_or_default();
 
-        let transaction = Transaction::read(bytes.as_ref()).map_err(to_napi_err)?;
+        let transaction =
+            Transaction::read_with_limits(bytes.as_ref(), network_kind(network), &limits)
+                .map_err(to_napi_err)?;
 
         Ok(NativeTransactionPosted { transaction })
     }
@@ -70,9 +79,14 @@ impl NativeTransactionPosted {
         Ok(Buffer::from(vec))
     }
 
+    /// Verify the transaction, accepting the versions up to the activated
+    /// one, only the first version if it isn't given
     #[napi]
-    pub fn verify(&self) -> bool {
-        match self.transaction.verify() {
+    pub fn verify(&self, activated_version: Option<u8>) -> bool {
+        match self
+            .transaction
+            .verify_for_version(activated_version.unwrap_or(TX_VERSION))
+        {
             Ok(_) => true,
             Err(_e) => false,
         }
@@ -167,6 +181,51 @@ impl NativeTransactionPosted {
     }
 }
 
+/// Maximum number of each description a transaction may contain
+#[napi(object)]
+pub struct TransactionLimits {
+    pub max_spends: u32,
+    pub max_outputs: u32,
+    pub max_mints: u32,
+    pub max_burns: u32,
+}
+
+impl From<TransactionLimits> for Limits {
+    fn from(limits: TransactionLimits) -> Self {
+        Limits {
+            max_spends: limits.max_spends as usize,
+            max_outputs: limits.max_outputs as usize,
+            max_mints: limits.max_mints as usize,
+            max_burns: limits.max_burns as usize,
+        }
+    }
+}
+
+/// Limits on transactions read from peers, checked before the transaction
+/// is parsed
+#[napi(object)]
+pub struct TransactionReadLimits {
+    pub max_spends: u32,
+    pub max_outputs: u32,
+    pub max_mints: u32,
+    pub max_burns: u32,
+    pub max_bytes: u32,
+}
+
+impl From<TransactionReadLimits> for ReadLimits {
+    fn from(limits: TransactionReadLimits) -> Self {
+        ReadLimits {
+            descriptions: Limits {
+                max_spends: limits.max_spends as usize,
+                max_outputs: limits.max_outputs as usize,
+                max_mints: limits.max_mints as usize,
+                max_burns: limits.max_burns as usize,
+            },
+            max_bytes: limits.max_bytes as usize,
+        }
+    }
+}
+
 #[napi(js_name = "Transaction")]
 pub struct NativeTransaction {
     transaction: ProposedTransaction,
@@ -175,10 +234,18 @@ pub struct NativeTransaction {
 #[napi]
 impl NativeTransaction {
     #[napi(constructor)]
-    pub fn new(spender_hex_key: String) -> Result<NativeTransaction> {
-        let spender_key = SaplingKey::from_hex(&spender_hex_key).map_err(to_napi_err)?;
+    pub fn new(
+        spender_hex_key: String,
+        limits: Option<TransactionLimits>,
+        network: Option<NetworkCode>,
+    ) -> Result<NativeTransaction> {
+        let spender_key = SaplingKey::from_hex_for_network(&spender_hex_key, network_kind(network))
+            .map_err(to_napi_err)?;
+        let limits = limits.map(Limits::from).unwrap_or_default();
+
         Ok(NativeTransaction {
-            transaction: ProposedTransaction::new(spender_key),
+            transaction: ProposedTransaction::with_limits(spender_key, limits)
+                .map_err(to_napi_err)?,
         })
     }
 
@@ -301,7 +368,10 @@ impl NativeTransaction {
 }
 
 #[napi]
-pub fn verify_transactions(serialized_transactions: Vec<JsBuffer>) -> Result<bool> {
+pub fn verify_transactions(
+    serialized_transactions: Vec<JsBuffer>,
+    activated_version: Option<u8>,
+) -> Result<bool> {
     let mut transactions: Vec<Transaction> = vec![];
 
     for tx_bytes in serialized_transactions {
@@ -312,5 +382,9 @@ pub fn verify_transactions(serialized_transactions: Vec<JsBuffer>) -> Result<boo
         }
     }
 
-    Ok(batch_verify_transactions(transactions.iter()).is_ok())
+    Ok(batch_verify_transactions_for_version(
+        transactions.iter(),
+        activated_version.unwrap_or(TX_VERSION),
+    )
+    .is_ok())
 }
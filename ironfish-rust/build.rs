@@ -132,5 +132,9 @@ fn prepare_sapling_params() {
 }
 
 fn main() {
-    prepare_sapling_params();
+    // With `params-fetch`, the parameters are downloaded at runtime and never
+    // bundled
+    if cfg!(not(feature = "params-fetch")) {
+        prepare_sapling_params();
+    }
 }
@@ -1,19 +1,52 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
-use crate::{errors::IronfishError, keys::PUBLIC_ADDRESS_SIZE, util::str_to_array, PublicAddress};
+use crate::{
+    errors::IronfishError, keys::PUBLIC_ADDRESS_SIZE, network::NetworkKind, util::str_to_array,
+    PublicAddress,
+};
 use byteorder::{ReadBytesExt, WriteBytesExt};
-use ironfish_zkp::constants::{ASSET_ID_LENGTH, ASSET_ID_PERSONALIZATION, GH_FIRST_BLOCK};
-use jubjub::{ExtendedPoint, SubgroupPoint};
+use ironfish_zkp::constants::{ASSET_ID_LENGTH, GH_FIRST_BLOCK};
 use std::io;
 
-use super::asset_identifier::AssetIdentifier;
+use super::{
+    asset_identifier::AssetIdentifier,
+    generators::{AssetGenerator, ValueCommitmentGenerator},
+};
 
 pub const NAME_LENGTH: usize = 32;
 pub const METADATA_LENGTH: usize = 96;
 pub const ASSET_LENGTH: usize = NAME_LENGTH + PUBLIC_ADDRESS_SIZE + METADATA_LENGTH + 1;
 pub const ID_LENGTH: usize = ASSET_ID_LENGTH;
 
+/// Size of the bytes hashed into an asset identifier, see
+/// [`derive_asset_id_preimage`]
+pub const ASSET_ID_PREIMAGE_LENGTH: usize = GH_FIRST_BLOCK.len() + ASSET_LENGTH;
+
+/// The exact bytes that are hashed, with BLAKE2s and the network's
+/// [`NetworkKind::asset_id_personalization`], into the identifier of an
+/// asset: the first block of the group hash, owner, name, metadata and nonce.
+pub fn derive_asset_id_preimage(
+    owner: &PublicAddress,
+    name: &[u8; NAME_LENGTH],
+    metadata: &[u8; METADATA_LENGTH],
+    nonce: u8,
+) -> [u8; ASSET_ID_PREIMAGE_LENGTH] {
+    let mut preimage = [0; ASSET_ID_PREIMAGE_LENGTH];
+    let (group_hash_block, rest) = preimage.split_at_mut(GH_FIRST_BLOCK.len());
+    let (owner_bytes, rest) = rest.split_at_mut(PUBLIC_ADDRESS_SIZE);
+    let (name_bytes, rest) = rest.split_at_mut(NAME_LENGTH);
+    let (metadata_bytes, nonce_byte) = rest.split_at_mut(METADATA_LENGTH);
+
+    group_hash_block.copy_from_slice(GH_FIRST_BLOCK);
+    owner_bytes.copy_from_slice(&owner.public_address());
+    name_bytes.copy_from_slice(name);
+    metadata_bytes.copy_from_slice(metadata);
+    nonce_byte[0] = nonce;
+
+    preimage
+}
+
 /// Describes all the fields necessary for creating and transacting with an
 /// asset on the Iron Fish network
 #[derive(Clone, Copy, Debug)]
@@ -37,6 +70,16 @@ pub struct Asset {
 impl Asset {
     /// Create a new AssetType from a public address, name, chain, and network
     pub fn new(owner: PublicAddress, name: &str, metadata: &str) -> Result<Asset, IronfishError> {
+        Asset::new_for_network(owner, name, metadata, NetworkKind::Mainnet)
+    }
+
+    /// Create a new AssetType whose identifier is derived for the given network
+    pub fn new_for_network(
+        owner: PublicAddress,
+        name: &str,
+        metadata: &str,
+        network: NetworkKind,
+    ) -> Result<Asset, IronfishError> {
         let trimmed_name = name.trim();
         if trimmed_name.is_empty() {
             return Err(IronfishError::InvalidData);
@@ -45,9 +88,22 @@ impl Asset {
         let name_bytes = str_to_array(trimmed_name);
         let metadata_bytes = str_to_array(metadata);
 
+        Asset::find_nonce_for_network(owner, name_bytes, metadata_bytes, network)
+    }
+
+    /// Create an asset from the exact name and metadata bytes, using the
+    /// first nonce that gives a valid identifier
+    pub(crate) fn find_nonce_for_network(
+        owner: PublicAddress,
+        name_bytes: [u8; NAME_LENGTH],
+        metadata_bytes: [u8; METADATA_LENGTH],
+        network: NetworkKind,
+    ) -> Result<Asset, IronfishError> {
         let mut nonce = 0u8;
         loop {
-            if let Ok(asset) = Asset::new_with_nonce(owner, name_bytes, metadata_bytes, nonce) {
+            if let Ok(asset) =
+                Asset::new_with_nonce_for_network(owner, name_bytes, metadata_bytes, nonce, network)
+            {
                 return Ok(asset);
             }
             nonce = nonce.checked_add(1).ok_or(IronfishError::RandomnessError)?;
@@ -59,18 +115,22 @@ impl Asset {
         name: [u8; NAME_LENGTH],
         metadata: [u8; METADATA_LENGTH],
         nonce: u8,
+    ) -> Result<Asset, IronfishError> {
+        Asset::new_with_nonce_for_network(owner, name, metadata, nonce, NetworkKind::Mainnet)
+    }
+
+    pub fn new_with_nonce_for_network(
+        owner: PublicAddress,
+        name: [u8; NAME_LENGTH],
+        metadata: [u8; METADATA_LENGTH],
+        nonce: u8,
+        network: NetworkKind,
     ) -> Result<Asset, IronfishError> {
         // Create the potential asset identifier from the asset info
         let asset_id_hash = blake2s_simd::Params::new()
             .hash_length(ASSET_ID_LENGTH)
-            .personal(ASSET_ID_PERSONALIZATION)
-            .to_state()
-            .update(GH_FIRST_BLOCK)
-            .update(&owner.public_address())
-            .update(&name)
-            .update(&metadata)
-            .update(std::slice::from_ref(&nonce))
-            .finalize();
+            .personal(network.asset_id_personalization())
+            .hash(&derive_asset_id_preimage(&owner, &name, &metadata, nonce));
 
         // Try creating an asset identifier from this hash
         let asset_id = AssetIdentifier::new(asset_id_hash.as_array().to_owned())?;
@@ -105,15 +165,23 @@ impl Asset {
         &self.id
     }
 
-    pub fn asset_generator(&self) -> ExtendedPoint {
+    pub fn asset_generator(&self) -> AssetGenerator {
         self.id.asset_generator()
     }
 
-    pub fn value_commitment_generator(&self) -> SubgroupPoint {
+    pub fn value_commitment_generator(&self) -> ValueCommitmentGenerator {
         self.id.value_commitment_generator()
     }
 
-    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Asset::read_for_network(reader, NetworkKind::Mainnet)
+    }
+
+    /// Load an asset, deriving its identifier for the given network
+    pub fn read_for_network<R: io::Read>(
+        mut reader: R,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
         let owner = PublicAddress::read(&mut reader)?;
 
         let mut name = [0; NAME_LENGTH];
@@ -124,7 +192,7 @@ impl Asset {
 
         let nonce = reader.read_u8()?;
 
-        Asset::new_with_nonce(owner, name, metadata, nonce)
+        Asset::new_with_nonce_for_network(owner, name, metadata, nonce, network)
     }
 
     /// Stow the bytes of this struct in the given writer.
@@ -136,13 +204,22 @@ impl Asset {
 
         Ok(())
     }
+
+    /// The serialized form of this asset, as written by [`Asset::write`],
+    /// without allocating.
+    pub fn to_bytes(&self) -> [u8; ASSET_LENGTH] {
+        let mut bytes = [0; ASSET_LENGTH];
+        self.write(&mut bytes[..]).unwrap();
+        bytes
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::{util::str_to_array, PublicAddress, SaplingKey};
+    use crate::{network::NetworkKind, util::str_to_array, PublicAddress, SaplingKey};
+    use ironfish_zkp::constants::GH_FIRST_BLOCK;
 
-    use super::Asset;
+    use super::{derive_asset_id_preimage, Asset};
 
     #[test]
     fn test_asset_new() {
@@ -210,4 +287,60 @@ mod test {
 
         assert!(asset_res.is_err());
     }
+
+    #[test]
+    fn test_asset_id_depends_on_network() {
+        let owner = SaplingKey::generate_key().public_address();
+
+        let mainnet_asset = Asset::new(owner, "name", "").unwrap();
+        let testnet_asset =
+            Asset::new_for_network(owner, "name", "", NetworkKind::Testnet).unwrap();
+        assert_ne!(mainnet_asset.id, testnet_asset.id);
+
+        let mut serialized = vec![];
+        testnet_asset.write(&mut serialized).unwrap();
+
+        let read_back = Asset::read_for_network(&serialized[..], NetworkKind::Testnet).unwrap();
+        assert_eq!(read_back.id, testnet_asset.id);
+    }
+
+    #[test]
+    fn test_asset_to_and_from_bytes() {
+        let owner = SaplingKey::generate_key().public_address();
+        let asset = Asset::new(owner, "name", "metadata").unwrap();
+
+        let mut serialized = vec![];
+        asset.write(&mut serialized).unwrap();
+        assert_eq!(&asset.to_bytes()[..], &serialized[..]);
+
+        let parsed = Asset::read(&asset.to_bytes()[..]).unwrap();
+        assert_eq!(parsed.id, asset.id);
+        assert_eq!(parsed.to_bytes(), asset.to_bytes());
+    }
+
+    #[test]
+    fn test_derive_asset_id_preimage() {
+        let owner = SaplingKey::generate_key().public_address();
+        let asset = Asset::new(owner, "name", "metadata").unwrap();
+
+        let preimage = derive_asset_id_preimage(&owner, &asset.name, &asset.metadata, asset.nonce);
+        assert_eq!(preimage.len(), 64 + 32 + 32 + 96 + 1);
+        assert_eq!(&preimage[..64], &GH_FIRST_BLOCK[..]);
+        assert_eq!(preimage[64..96], owner.public_address());
+        assert_eq!(preimage[96..128], asset.name);
+        assert_eq!(preimage[128..224], asset.metadata);
+        assert_eq!(preimage[224], asset.nonce);
+
+        // Hashing the preimage gives the identifier, on every network
+        for network in [NetworkKind::Mainnet, NetworkKind::Testnet] {
+            let asset = Asset::new_for_network(owner, "name", "metadata", network).unwrap();
+            let preimage =
+                derive_asset_id_preimage(&owner, &asset.name, &asset.metadata, asset.nonce);
+            let hash = blake2s_simd::Params::new()
+                .hash_length(32)
+                .personal(network.asset_id_personalization())
+                .hash(&preimage);
+            assert_eq!(hash.as_bytes(), asset.id.as_bytes());
+        }
+    }
 }
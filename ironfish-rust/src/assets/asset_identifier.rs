@@ -2,16 +2,21 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 use crate::errors::IronfishError;
-use group::cofactor::CofactorGroup;
+use bech32::{FromBase32, ToBase32, Variant};
 use ironfish_zkp::{constants::ASSET_ID_LENGTH, util::asset_hash_to_point};
-use jubjub::{ExtendedPoint, SubgroupPoint};
-use std::io;
+use std::{fmt, io, str::FromStr};
+
+use super::generators::{AssetGenerator, ValueCommitmentGenerator};
 
 pub const NATIVE_ASSET: AssetIdentifier = AssetIdentifier([
     81, 243, 58, 47, 20, 249, 39, 53, 229, 98, 220, 101, 138, 86, 57, 39, 157, 220, 163, 213, 7,
     154, 109, 18, 66, 178, 165, 136, 169, 203, 244, 76,
 ]);
 
+/// Human readable part of encoded asset ids, see [`AssetIdentifier`]'s
+/// `Display` implementation
+pub const ASSET_ID_PREFIX: &str = "ifasset";
+
 /// A convenience wrapper around an asset id byte-array, allowing us to push the
 /// error checking of the asset id validity to instantiation
 /// instead of when trying to get the generator point. This causes code relating
@@ -24,12 +29,12 @@ impl AssetIdentifier {
         byte_array.try_into()
     }
 
-    pub fn asset_generator(&self) -> ExtendedPoint {
-        asset_hash_to_point(&self.0).unwrap()
+    pub fn asset_generator(&self) -> AssetGenerator {
+        AssetGenerator::new(asset_hash_to_point(&self.0).unwrap())
     }
 
-    pub fn value_commitment_generator(&self) -> SubgroupPoint {
-        self.asset_generator().clear_cofactor()
+    pub fn value_commitment_generator(&self) -> ValueCommitmentGenerator {
+        self.asset_generator().value_commitment_generator()
     }
 
     pub fn as_bytes(&self) -> &[u8; ASSET_ID_LENGTH] {
@@ -62,17 +67,55 @@ impl TryFrom<[u8; ASSET_ID_LENGTH]> for AssetIdentifier {
     }
 }
 
+/// Asset ids are shown to users as bech32m strings starting with
+/// [`ASSET_ID_PREFIX`]. Unlike hex, the checksum catches mistyped or
+/// truncated ids: any four wrong characters are always detected.
+impl fmt::Display for AssetIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let encoded = bech32::encode(ASSET_ID_PREFIX, self.0.to_base32(), Variant::Bech32m)
+            .map_err(|_| fmt::Error)?;
+        f.write_str(&encoded)
+    }
+}
+
+impl FromStr for AssetIdentifier {
+    type Err = IronfishError;
+
+    /// Parse an encoded asset id. Fails with
+    /// [`IronfishError::InvalidAssetIdentifierEncoding`] if the prefix or the
+    /// checksum are wrong, which usually means the id was mistyped.
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (prefix, data, variant) =
+            bech32::decode(value).map_err(|_| IronfishError::InvalidAssetIdentifierEncoding)?;
+        if prefix != ASSET_ID_PREFIX || variant != Variant::Bech32m {
+            return Err(IronfishError::InvalidAssetIdentifierEncoding);
+        }
+
+        let bytes = Vec::<u8>::from_base32(&data)
+            .map_err(|_| IronfishError::InvalidAssetIdentifierEncoding)?;
+        let byte_array: [u8; ASSET_ID_LENGTH] = bytes
+            .try_into()
+            .map_err(|_| IronfishError::InvalidAssetIdentifierEncoding)?;
+
+        byte_array.try_into()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use group::cofactor::CofactorGroup;
     use ironfish_zkp::constants::NATIVE_VALUE_COMMITMENT_GENERATOR;
 
-    use crate::assets::asset_identifier::NATIVE_ASSET;
+    use crate::{
+        assets::asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+        errors::IronfishError,
+    };
+    use bech32::{ToBase32, Variant};
 
     #[test]
     fn test_asset_native_identifier() {
-        let asset_generator = NATIVE_ASSET.asset_generator();
-        let value_commitment_generator = NATIVE_ASSET.value_commitment_generator();
+        let asset_generator = NATIVE_ASSET.asset_generator().point();
+        let value_commitment_generator = NATIVE_ASSET.value_commitment_generator().point();
 
         assert_eq!(asset_generator.clear_cofactor(), value_commitment_generator);
 
@@ -81,4 +124,40 @@ mod test {
             NATIVE_VALUE_COMMITMENT_GENERATOR
         );
     }
+
+    #[test]
+    fn test_asset_identifier_encoding() {
+        let encoded = NATIVE_ASSET.to_string();
+        assert!(encoded.starts_with("ifasset1"));
+        assert_eq!(encoded.parse::<AssetIdentifier>().unwrap(), NATIVE_ASSET);
+        // Encoded ids are case insensitive, as long as they aren't mixed case
+        assert_eq!(
+            encoded.to_uppercase().parse::<AssetIdentifier>().unwrap(),
+            NATIVE_ASSET
+        );
+
+        // A single mistyped character is caught by the checksum
+        let mut typo = encoded.clone().into_bytes();
+        let last = typo.len() - 1;
+        typo[last] = if typo[last] == b'q' { b'p' } else { b'q' };
+        assert!(matches!(
+            String::from_utf8(typo).unwrap().parse::<AssetIdentifier>(),
+            Err(IronfishError::InvalidAssetIdentifierEncoding)
+        ));
+
+        // So are a dropped character and a wrong prefix
+        assert!(encoded[..encoded.len() - 1]
+            .parse::<AssetIdentifier>()
+            .is_err());
+        let other_prefix = bech32::encode(
+            "asset",
+            NATIVE_ASSET.as_bytes().to_base32(),
+            Variant::Bech32m,
+        )
+        .unwrap();
+        assert!(matches!(
+            other_prefix.parse::<AssetIdentifier>(),
+            Err(IronfishError::InvalidAssetIdentifierEncoding)
+        ));
+    }
 }
@@ -0,0 +1,333 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Owner-signed verification details of an asset, published off-chain.
+//!
+//! Anyone can create an asset with the name and metadata of a well-known
+//! one, so wallets can't tell assets apart by what they're called. An
+//! [`AssetAttestation`] lets the owner vouch for a website, the hash of an
+//! icon and a ticker. Wallets check it with
+//! [`AssetAttestation::verify_for_asset`] against the asset they found on
+//! chain, whose identifier commits to the owner, before displaying any of
+//! it: a copycat asset has another identifier, and only its own owner can
+//! attest to it.
+//!
+//! Ownership is proven the same way as for metadata updates, see
+//! [`super::metadata_update`], with a mint proof and a signature from the
+//! randomized spend authorizing key of the owner.
+
+use std::io;
+
+use bellman::groth16;
+use blake2b_simd::Params as Blake2b;
+use bls12_381::Bls12;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use group::GroupEncoding;
+use ironfish_zkp::redjubjub;
+
+use crate::{
+    errors::IronfishError,
+    keys::owner_authorization::{blank_signature, OwnerAuthorization},
+    network::NetworkKind,
+    personalization::ASSET_ATTESTATION_PERSONALIZATION,
+    SaplingKey,
+};
+
+use super::asset::Asset;
+
+pub const ASSET_ATTESTATION_VERSION: u8 = 1;
+
+/// Maximum length of the website of an attestation, in bytes
+pub const MAX_WEBSITE_LENGTH: usize = 255;
+
+/// Maximum length of the ticker of an attestation, in bytes
+pub const MAX_TICKER_LENGTH: usize = 16;
+
+/// Parameters used to build an [`AssetAttestation`]
+pub struct AssetAttestationBuilder {
+    /// Asset being attested to
+    pub asset: Asset,
+
+    /// Official website of the asset, such as `https://example.com`
+    pub website: String,
+
+    /// Hash of the official icon, so that wallets can check an icon they
+    /// downloaded
+    pub icon_hash: [u8; 32],
+
+    pub ticker: String,
+
+    /// Attestations with higher sequence numbers supersede lower ones
+    pub sequence: u64,
+}
+
+impl AssetAttestationBuilder {
+    pub fn new(
+        asset: Asset,
+        website: &str,
+        icon_hash: [u8; 32],
+        ticker: &str,
+        sequence: u64,
+    ) -> Self {
+        Self {
+            asset,
+            website: website.to_string(),
+            icon_hash,
+            ticker: ticker.to_string(),
+            sequence,
+        }
+    }
+
+    /// Prove ownership of the asset and sign the attestation. Fails if the
+    /// key doesn't own the asset, or if the website or ticker is too long.
+    pub fn build(&self, owner_key: &SaplingKey) -> Result<AssetAttestation, IronfishError> {
+        if owner_key.public_address() != self.asset.owner {
+            return Err(IronfishError::InvalidSigningKey);
+        }
+
+        if self.website.len() > MAX_WEBSITE_LENGTH || self.ticker.len() > MAX_TICKER_LENGTH {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let authorization = OwnerAuthorization::prove(owner_key)?;
+
+        let mut attestation = AssetAttestation {
+            proof: authorization.proof.clone(),
+            randomized_public_key: authorization.randomized_public_key.clone(),
+            asset: self.asset,
+            website: self.website.clone(),
+            icon_hash: self.icon_hash,
+            ticker: self.ticker.clone(),
+            sequence: self.sequence,
+            authorizing_signature: blank_signature()?,
+        };
+
+        attestation.authorizing_signature =
+            authorization.sign(owner_key, &attestation.signature_hash())?;
+
+        attestation.verify()?;
+
+        Ok(attestation)
+    }
+}
+
+/// Verification details of an asset, signed by its owner
+#[derive(Clone)]
+pub struct AssetAttestation {
+    /// Proof that the signer owns the asset
+    pub proof: groth16::Proof<Bls12>,
+
+    /// Randomized public key of the owner, which the proof is bound to and
+    /// which verifies the signature
+    pub randomized_public_key: redjubjub::PublicKey,
+
+    /// Asset being attested to
+    pub asset: Asset,
+
+    pub website: String,
+
+    pub icon_hash: [u8; 32],
+
+    pub ticker: String,
+
+    /// Attestations with higher sequence numbers supersede lower ones
+    pub sequence: u64,
+
+    /// Signature of the owner over every other field
+    pub authorizing_signature: redjubjub::Signature,
+}
+
+impl AssetAttestation {
+    /// Calculate a hash of the attestation. This is what the owner signs,
+    /// along with the randomized public key.
+    pub fn signature_hash(&self) -> [u8; 32] {
+        let mut hasher = Blake2b::new()
+            .hash_length(32)
+            .personal(ASSET_ATTESTATION_PERSONALIZATION)
+            .to_state();
+        hasher.update(&[ASSET_ATTESTATION_VERSION]);
+        hasher.update(&self.randomized_public_key.0.to_bytes());
+        hasher.update(&self.asset.to_bytes());
+        hasher.update(&[self.website.len() as u8]);
+        hasher.update(self.website.as_bytes());
+        hasher.update(&self.icon_hash);
+        hasher.update(&[self.ticker.len() as u8]);
+        hasher.update(self.ticker.as_bytes());
+        hasher.update(&self.sequence.to_le_bytes());
+
+        let mut hash_result = [0; 32];
+        hash_result[..].clone_from_slice(hasher.finalize().as_ref());
+        hash_result
+    }
+
+    /// Confirm that the attestation was proven and signed by the owner of
+    /// the asset it names.
+    pub fn verify(&self) -> Result<(), IronfishError> {
+        if self.website.len() > MAX_WEBSITE_LENGTH || self.ticker.len() > MAX_TICKER_LENGTH {
+            return Err(IronfishError::InvalidData);
+        }
+
+        OwnerAuthorization::verify(
+            &self.proof,
+            &self.randomized_public_key,
+            &self.asset.owner,
+            &self.signature_hash(),
+            &self.authorizing_signature,
+        )
+    }
+
+    /// Confirm that the attestation is about the given asset, as found on
+    /// chain, and was signed by its owner. Wallets should only display
+    /// attestations that pass this check.
+    pub fn verify_for_asset(&self, asset: &Asset) -> Result<(), IronfishError> {
+        if self.asset.id() != asset.id() {
+            return Err(IronfishError::InvalidAssetIdentifier);
+        }
+
+        self.verify()
+    }
+
+    /// Load an [`AssetAttestation`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Self::read_for_network(reader, NetworkKind::Mainnet)
+    }
+
+    /// Load an [`AssetAttestation`] whose asset belongs to the given network
+    pub fn read_for_network<R: io::Read>(
+        mut reader: R,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != ASSET_ATTESTATION_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let proof = groth16::Proof::read(&mut reader)?;
+        let randomized_public_key = redjubjub::PublicKey::read(&mut reader)?;
+        let asset = Asset::read_for_network(&mut reader, network)?;
+        let website = read_string(&mut reader, MAX_WEBSITE_LENGTH)?;
+
+        let mut icon_hash = [0; 32];
+        reader.read_exact(&mut icon_hash)?;
+
+        let ticker = read_string(&mut reader, MAX_TICKER_LENGTH)?;
+        let sequence = reader.read_u64::<LittleEndian>()?;
+        let authorizing_signature = redjubjub::Signature::read(&mut reader)?;
+
+        Ok(AssetAttestation {
+            proof,
+            randomized_public_key,
+            asset,
+            website,
+            icon_hash,
+            ticker,
+            sequence,
+            authorizing_signature,
+        })
+    }
+
+    /// Stow the bytes of this [`AssetAttestation`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(ASSET_ATTESTATION_VERSION)?;
+        self.proof.write(&mut writer)?;
+        writer.write_all(&self.randomized_public_key.0.to_bytes())?;
+        self.asset.write(&mut writer)?;
+        write_string(&mut writer, &self.website, MAX_WEBSITE_LENGTH)?;
+        writer.write_all(&self.icon_hash)?;
+        write_string(&mut writer, &self.ticker, MAX_TICKER_LENGTH)?;
+        writer.write_u64::<LittleEndian>(self.sequence)?;
+        self.authorizing_signature.write(&mut writer)?;
+
+        Ok(())
+    }
+}
+
+fn read_string<R: io::Read>(mut reader: R, max_length: usize) -> Result<String, IronfishError> {
+    let length = reader.read_u8()? as usize;
+    if length > max_length {
+        return Err(IronfishError::InvalidData);
+    }
+
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn write_string<W: io::Write>(
+    mut writer: W,
+    string: &str,
+    max_length: usize,
+) -> Result<(), IronfishError> {
+    if string.len() > max_length {
+        return Err(IronfishError::InvalidData);
+    }
+
+    writer.write_u8(string.len() as u8)?;
+    writer.write_all(string.as_bytes())?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{assets::asset::Asset, errors::IronfishError, SaplingKey};
+
+    use super::{AssetAttestation, AssetAttestationBuilder, MAX_TICKER_LENGTH};
+
+    #[test]
+    fn test_asset_attestation() {
+        let key = SaplingKey::generate_key();
+        let asset = Asset::new(key.public_address(), "Wrapped Coin", "").unwrap();
+
+        let builder =
+            AssetAttestationBuilder::new(asset, "https://coin.example", [3; 32], "WCOIN", 1);
+        let attestation = builder.build(&key).expect("owner can attest");
+
+        let mut serialized = vec![];
+        attestation.write(&mut serialized).unwrap();
+        let read_back = AssetAttestation::read(&serialized[..]).unwrap();
+        assert_eq!(read_back.website, "https://coin.example");
+        assert_eq!(read_back.ticker, "WCOIN");
+        read_back
+            .verify_for_asset(&asset)
+            .expect("attestation should verify against its asset");
+
+        // Changing any signed field invalidates the attestation
+        let mut tampered = read_back.clone();
+        tampered.website = "https://evil.example".to_string();
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = read_back.clone();
+        tampered.icon_hash = [4; 32];
+        assert!(tampered.verify().is_err());
+
+        // A copycat asset with the same name has another identifier
+        let copycat_owner = SaplingKey::generate_key();
+        let copycat = Asset::new(copycat_owner.public_address(), "Wrapped Coin", "").unwrap();
+        assert!(matches!(
+            read_back.verify_for_asset(&copycat),
+            Err(IronfishError::InvalidAssetIdentifier)
+        ));
+    }
+
+    #[test]
+    fn test_asset_attestation_requires_owner() {
+        let owner = SaplingKey::generate_key();
+        let other = SaplingKey::generate_key();
+        let asset = Asset::new(owner.public_address(), "name", "").unwrap();
+
+        let builder = AssetAttestationBuilder::new(asset, "https://example.com", [0; 32], "T", 1);
+        assert!(builder.build(&other).is_err());
+
+        let long_ticker = "T".repeat(MAX_TICKER_LENGTH + 1);
+        let builder =
+            AssetAttestationBuilder::new(asset, "https://example.com", [0; 32], &long_ticker, 1);
+        assert!(matches!(
+            builder.build(&owner),
+            Err(IronfishError::InvalidData)
+        ));
+    }
+}
@@ -0,0 +1,232 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Assets that wrap tokens from other chains.
+//!
+//! An asset identifier is derived from the owner, name and metadata of the
+//! asset, so two bridges that describe the same token with differently
+//! formatted metadata end up with two different assets. The helpers here
+//! pack a reference to the external token into the metadata in one canonical
+//! way. Given the same owner, every bridge derives the same asset for the
+//! same wrapped token, and anyone can recover the reference from the asset.
+//!
+//! The metadata of a bridged asset is laid out as:
+//!
+//! | bytes  | field                                  |
+//! |--------|----------------------------------------|
+//! | 4      | `BRIDGED_ASSET_PREFIX`                 |
+//! | 1      | `BRIDGED_ASSET_VERSION`                |
+//! | 8      | chain id, little endian                |
+//! | 1      | decimals                               |
+//! | 1      | length of the contract address         |
+//! | ≤ 81   | contract address, followed by zeros    |
+//!
+//! The name of the asset is the token symbol.
+
+use std::io::{Read, Write};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{errors::IronfishError, network::NetworkKind, util::str_to_array, PublicAddress};
+
+use super::asset::{Asset, METADATA_LENGTH, NAME_LENGTH};
+
+pub const BRIDGED_ASSET_PREFIX: &[u8; 4] = b"IFBR";
+
+pub const BRIDGED_ASSET_VERSION: u8 = 1;
+
+/// Space taken up by everything in the metadata besides the contract address
+const BRIDGED_ASSET_HEADER_SIZE: usize = 4 + 1 + 8 + 1 + 1;
+
+pub const MAX_CONTRACT_ADDRESS_LENGTH: usize = METADATA_LENGTH - BRIDGED_ASSET_HEADER_SIZE;
+
+/// The token on an external chain that a bridged asset wraps
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BridgedAssetReference {
+    /// Identifier of the external chain, e.g. the EIP-155 chain id
+    pub chain_id: u64,
+
+    /// Raw bytes of the token contract address on the external chain. Pass
+    /// the decoded address rather than its text form, so that differences in
+    /// case or prefixes don't lead to different assets.
+    pub contract_address: Vec<u8>,
+
+    /// Symbol of the token, used as the asset name
+    pub symbol: String,
+
+    /// Number of decimals of the token on the external chain
+    pub decimals: u8,
+}
+
+impl BridgedAssetReference {
+    /// Pack the reference into the canonical asset metadata. Fails if the
+    /// contract address doesn't fit.
+    pub fn to_metadata(&self) -> Result<[u8; METADATA_LENGTH], IronfishError> {
+        if self.contract_address.is_empty()
+            || self.contract_address.len() > MAX_CONTRACT_ADDRESS_LENGTH
+        {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let mut metadata = [0; METADATA_LENGTH];
+        let mut writer = &mut metadata[..];
+        writer.write_all(BRIDGED_ASSET_PREFIX)?;
+        writer.write_u8(BRIDGED_ASSET_VERSION)?;
+        writer.write_u64::<LittleEndian>(self.chain_id)?;
+        writer.write_u8(self.decimals)?;
+        writer.write_u8(self.contract_address.len() as u8)?;
+        writer.write_all(&self.contract_address)?;
+
+        Ok(metadata)
+    }
+
+    /// Recover the reference from an asset created by
+    /// [`create_bridged_asset`]. Fails if the asset wasn't packed the
+    /// canonical way.
+    pub fn from_asset(asset: &Asset) -> Result<Self, IronfishError> {
+        let mut reader = asset.metadata();
+
+        let mut prefix = [0; 4];
+        reader.read_exact(&mut prefix)?;
+        if &prefix != BRIDGED_ASSET_PREFIX || reader.read_u8()? != BRIDGED_ASSET_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let chain_id = reader.read_u64::<LittleEndian>()?;
+        let decimals = reader.read_u8()?;
+
+        let address_length = reader.read_u8()? as usize;
+        if address_length == 0 || address_length > MAX_CONTRACT_ADDRESS_LENGTH {
+            return Err(IronfishError::InvalidData);
+        }
+        let (contract_address, padding) = reader.split_at(address_length);
+        if padding.iter().any(|byte| *byte != 0) {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let symbol = std::str::from_utf8(asset.name())
+            .map_err(|_| IronfishError::InvalidData)?
+            .trim_end_matches('\0');
+        validate_symbol(symbol)?;
+
+        Ok(BridgedAssetReference {
+            chain_id,
+            contract_address: contract_address.to_vec(),
+            symbol: symbol.to_string(),
+            decimals,
+        })
+    }
+}
+
+/// Create the asset wrapping the given external token, with canonically
+/// packed metadata. The identifier still depends on the owner, so bridges
+/// need to agree on the owner to agree on the asset.
+pub fn create_bridged_asset(
+    owner: PublicAddress,
+    chain_id: u64,
+    contract_address: &[u8],
+    symbol: &str,
+    decimals: u8,
+) -> Result<Asset, IronfishError> {
+    create_bridged_asset_for_network(
+        owner,
+        chain_id,
+        contract_address,
+        symbol,
+        decimals,
+        NetworkKind::Mainnet,
+    )
+}
+
+/// Create the asset wrapping the given external token, deriving its
+/// identifier for the given network
+pub fn create_bridged_asset_for_network(
+    owner: PublicAddress,
+    chain_id: u64,
+    contract_address: &[u8],
+    symbol: &str,
+    decimals: u8,
+    network: NetworkKind,
+) -> Result<Asset, IronfishError> {
+    validate_symbol(symbol)?;
+
+    let reference = BridgedAssetReference {
+        chain_id,
+        contract_address: contract_address.to_vec(),
+        symbol: symbol.to_string(),
+        decimals,
+    };
+
+    Asset::find_nonce_for_network(
+        owner,
+        str_to_array(symbol),
+        reference.to_metadata()?,
+        network,
+    )
+}
+
+/// Symbols are used as is, so reject any that would be altered by how asset
+/// names are stored or trimmed
+fn validate_symbol(symbol: &str) -> Result<(), IronfishError> {
+    if symbol.is_empty()
+        || symbol.len() > NAME_LENGTH
+        || symbol.trim() != symbol
+        || symbol.contains('\0')
+    {
+        return Err(IronfishError::InvalidData);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{create_bridged_asset, BridgedAssetReference, MAX_CONTRACT_ADDRESS_LENGTH};
+    use crate::{assets::asset::Asset, SaplingKey};
+
+    const CONTRACT_ADDRESS: [u8; 20] = [
+        0xa0, 0xb8, 0x69, 0x91, 0xc6, 0x21, 0x8b, 0x36, 0xc1, 0xd1, 0x9d, 0x4a, 0x2e, 0x9e, 0xb0,
+        0xce, 0x36, 0x06, 0xeb, 0x48,
+    ];
+
+    #[test]
+    fn test_bridged_asset_is_deterministic() {
+        let owner = SaplingKey::generate_key().public_address();
+
+        let asset = create_bridged_asset(owner, 1, &CONTRACT_ADDRESS, "USDC", 6).unwrap();
+        let again = create_bridged_asset(owner, 1, &CONTRACT_ADDRESS, "USDC", 6).unwrap();
+        assert_eq!(asset.id, again.id);
+
+        let other_chain = create_bridged_asset(owner, 5, &CONTRACT_ADDRESS, "USDC", 6).unwrap();
+        assert_ne!(asset.id, other_chain.id);
+
+        let reference = BridgedAssetReference::from_asset(&asset).unwrap();
+        assert_eq!(
+            reference,
+            BridgedAssetReference {
+                chain_id: 1,
+                contract_address: CONTRACT_ADDRESS.to_vec(),
+                symbol: "USDC".to_string(),
+                decimals: 6,
+            }
+        );
+    }
+
+    #[test]
+    fn test_bridged_asset_validation() {
+        let owner = SaplingKey::generate_key().public_address();
+
+        assert!(create_bridged_asset(owner, 1, &[], "USDC", 6).is_err());
+        assert!(
+            create_bridged_asset(owner, 1, &[1; MAX_CONTRACT_ADDRESS_LENGTH + 1], "USDC", 6)
+                .is_err()
+        );
+        assert!(create_bridged_asset(owner, 1, &CONTRACT_ADDRESS, " USDC", 6).is_err());
+        assert!(create_bridged_asset(owner, 1, &CONTRACT_ADDRESS, "", 6).is_err());
+
+        // Assets that weren't packed canonically aren't bridged assets
+        let plain = Asset::new(owner, "USDC", "{ 'chain': 1 }").unwrap();
+        assert!(BridgedAssetReference::from_asset(&plain).is_err());
+    }
+}
@@ -0,0 +1,156 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Crate-owned wrappers around the curve points derived from an asset
+//! identifier. The underlying jubjub types are an implementation detail, so
+//! upgrading the curve crates doesn't break consumers of this crate. Use the
+//! byte encodings to move generators across that boundary.
+
+use std::io;
+
+use group::{cofactor::CofactorGroup, Group, GroupEncoding};
+use jubjub::{ExtendedPoint, SubgroupPoint};
+
+use crate::errors::IronfishError;
+
+pub const GENERATOR_SIZE: usize = 32;
+
+/// Point that the value of notes of an asset are committed to, before
+/// clearing the cofactor. Derived from the asset identifier.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AssetGenerator(ExtendedPoint);
+
+impl AssetGenerator {
+    pub(crate) fn new(point: ExtendedPoint) -> Self {
+        AssetGenerator(point)
+    }
+
+    pub(crate) fn point(&self) -> ExtendedPoint {
+        self.0
+    }
+
+    /// Decode an asset generator, rejecting encodings that aren't on the
+    /// curve or are of small order.
+    pub fn from_bytes(bytes: &[u8; GENERATOR_SIZE]) -> Result<Self, IronfishError> {
+        let point: ExtendedPoint =
+            Option::from(ExtendedPoint::from_bytes(bytes)).ok_or(IronfishError::InvalidData)?;
+
+        if point.is_small_order().into() {
+            return Err(IronfishError::IsSmallOrder);
+        }
+
+        Ok(AssetGenerator(point))
+    }
+
+    pub fn to_bytes(&self) -> [u8; GENERATOR_SIZE] {
+        self.0.to_bytes()
+    }
+
+    /// The generator used for value commitments, which is this generator
+    /// with the cofactor cleared.
+    pub fn value_commitment_generator(&self) -> ValueCommitmentGenerator {
+        ValueCommitmentGenerator(self.0.clear_cofactor())
+    }
+
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let mut bytes = [0; GENERATOR_SIZE];
+        reader.read_exact(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Stow the bytes of this struct in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.to_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// Generator of the prime order subgroup that value commitments for an asset
+/// are computed with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ValueCommitmentGenerator(SubgroupPoint);
+
+impl ValueCommitmentGenerator {
+    pub(crate) fn point(&self) -> SubgroupPoint {
+        self.0
+    }
+
+    /// Decode a value commitment generator, rejecting encodings that aren't
+    /// in the prime order subgroup, as well as the identity.
+    pub fn from_bytes(bytes: &[u8; GENERATOR_SIZE]) -> Result<Self, IronfishError> {
+        let point: SubgroupPoint =
+            Option::from(SubgroupPoint::from_bytes(bytes)).ok_or(IronfishError::InvalidData)?;
+
+        if point.is_identity().into() {
+            return Err(IronfishError::IsSmallOrder);
+        }
+
+        Ok(ValueCommitmentGenerator(point))
+    }
+
+    pub fn to_bytes(&self) -> [u8; GENERATOR_SIZE] {
+        self.0.to_bytes()
+    }
+
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let mut bytes = [0; GENERATOR_SIZE];
+        reader.read_exact(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    /// Stow the bytes of this struct in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.to_bytes())?;
+
+        Ok(())
+    }
+}
+
+// Benchmarks build value commitments directly, which needs the raw point
+#[cfg(feature = "benchmark")]
+impl From<AssetGenerator> for ExtendedPoint {
+    fn from(generator: AssetGenerator) -> Self {
+        generator.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ironfish_zkp::constants::NATIVE_VALUE_COMMITMENT_GENERATOR;
+
+    use super::{AssetGenerator, ValueCommitmentGenerator};
+    use crate::{assets::asset_identifier::NATIVE_ASSET, errors::IronfishError};
+
+    #[test]
+    fn test_generator_serialization() {
+        let generator = NATIVE_ASSET.asset_generator();
+        let read_back = AssetGenerator::from_bytes(&generator.to_bytes()).unwrap();
+        assert_eq!(read_back, generator);
+
+        let value_generator = generator.value_commitment_generator();
+        assert_eq!(value_generator.point(), NATIVE_VALUE_COMMITMENT_GENERATOR);
+
+        let mut serialized = vec![];
+        value_generator.write(&mut serialized).unwrap();
+        let read_back = ValueCommitmentGenerator::read(&serialized[..]).unwrap();
+        assert_eq!(read_back, value_generator);
+    }
+
+    #[test]
+    fn test_generator_validation() {
+        // The identity is of small order, and not a generator of anything
+        let mut identity = [0; 32];
+        identity[0] = 1;
+
+        assert!(matches!(
+            AssetGenerator::from_bytes(&identity),
+            Err(IronfishError::IsSmallOrder)
+        ));
+        assert!(matches!(
+            ValueCommitmentGenerator::from_bytes(&identity),
+            Err(IronfishError::IsSmallOrder)
+        ));
+    }
+}
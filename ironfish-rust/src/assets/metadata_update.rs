@@ -0,0 +1,235 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Owner-signed updates to the metadata of an existing asset.
+//!
+//! The metadata of an asset is part of its identifier, so it can't change
+//! without creating a new asset. An [`AssetMetadataUpdate`] instead publishes
+//! replacement metadata alongside the original asset, leaving the identifier
+//! alone. Wallets and explorers that understand updates display the metadata
+//! from the valid update with the highest sequence number; everything else
+//! keeps using the original metadata.
+//!
+//! Ownership is proven the same way as for mints, with a mint proof and a
+//! signature from the randomized spend authorizing key of the owner.
+
+use std::io;
+
+use bellman::groth16;
+use blake2b_simd::Params as Blake2b;
+use bls12_381::Bls12;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use group::GroupEncoding;
+use ironfish_zkp::redjubjub;
+
+use crate::{
+    errors::IronfishError,
+    keys::owner_authorization::{blank_signature, OwnerAuthorization},
+    network::NetworkKind,
+    personalization::METADATA_UPDATE_PERSONALIZATION,
+    util::str_to_array,
+    SaplingKey,
+};
+
+use super::asset::{Asset, METADATA_LENGTH};
+
+pub const METADATA_UPDATE_VERSION: u8 = 1;
+
+/// Parameters used to build an [`AssetMetadataUpdate`]
+pub struct MetadataUpdateBuilder {
+    /// Asset whose metadata is being replaced, with its original metadata
+    pub asset: Asset,
+
+    /// Replacement metadata
+    pub metadata: [u8; METADATA_LENGTH],
+
+    /// Updates with higher sequence numbers supersede lower ones
+    pub sequence: u64,
+}
+
+impl MetadataUpdateBuilder {
+    pub fn new(asset: Asset, metadata: &str, sequence: u64) -> Self {
+        Self {
+            asset,
+            metadata: str_to_array(metadata),
+            sequence,
+        }
+    }
+
+    /// Prove ownership of the asset and sign the update. Fails if the key
+    /// doesn't own the asset.
+    pub fn build(&self, owner_key: &SaplingKey) -> Result<AssetMetadataUpdate, IronfishError> {
+        if owner_key.public_address() != self.asset.owner {
+            return Err(IronfishError::InvalidSigningKey);
+        }
+
+        let authorization = OwnerAuthorization::prove(owner_key)?;
+
+        let mut update = AssetMetadataUpdate {
+            proof: authorization.proof.clone(),
+            randomized_public_key: authorization.randomized_public_key.clone(),
+            asset: self.asset,
+            metadata: self.metadata,
+            sequence: self.sequence,
+            authorizing_signature: blank_signature()?,
+        };
+
+        update.authorizing_signature = authorization.sign(owner_key, &update.signature_hash())?;
+
+        update.verify()?;
+
+        Ok(update)
+    }
+}
+
+/// A replacement for the metadata of an asset, authorized by its owner
+#[derive(Clone)]
+pub struct AssetMetadataUpdate {
+    /// Proof that the signer owns the asset
+    pub proof: groth16::Proof<Bls12>,
+
+    /// Randomized public key of the owner, which the proof is bound to and
+    /// which verifies the signature
+    pub randomized_public_key: redjubjub::PublicKey,
+
+    /// Asset being updated, with its original metadata
+    pub asset: Asset,
+
+    /// Replacement metadata
+    pub metadata: [u8; METADATA_LENGTH],
+
+    /// Updates with higher sequence numbers supersede lower ones
+    pub sequence: u64,
+
+    /// Signature of the owner over every other field
+    pub authorizing_signature: redjubjub::Signature,
+}
+
+impl AssetMetadataUpdate {
+    pub fn metadata(&self) -> &[u8] {
+        &self.metadata
+    }
+
+    /// Calculate a hash of the update. This is what the owner signs, along
+    /// with the randomized public key.
+    pub fn signature_hash(&self) -> [u8; 32] {
+        let mut hasher = Blake2b::new()
+            .hash_length(32)
+            .personal(METADATA_UPDATE_PERSONALIZATION)
+            .to_state();
+        hasher.update(&[METADATA_UPDATE_VERSION]);
+        hasher.update(&self.randomized_public_key.0.to_bytes());
+        hasher.update(&self.asset.to_bytes());
+        hasher.update(&self.metadata);
+        hasher.update(&self.sequence.to_le_bytes());
+
+        let mut hash_result = [0; 32];
+        hash_result[..].clone_from_slice(hasher.finalize().as_ref());
+        hash_result
+    }
+
+    /// Confirm that the update was proven and signed by the owner of the
+    /// asset.
+    pub fn verify(&self) -> Result<(), IronfishError> {
+        OwnerAuthorization::verify(
+            &self.proof,
+            &self.randomized_public_key,
+            &self.asset.owner,
+            &self.signature_hash(),
+            &self.authorizing_signature,
+        )
+    }
+
+    /// Load an [`AssetMetadataUpdate`] from a Read implementation (e.g:
+    /// socket, file)
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Self::read_for_network(reader, NetworkKind::Mainnet)
+    }
+
+    /// Load an [`AssetMetadataUpdate`] whose asset belongs to the given
+    /// network
+    pub fn read_for_network<R: io::Read>(
+        mut reader: R,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != METADATA_UPDATE_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let proof = groth16::Proof::read(&mut reader)?;
+        let randomized_public_key = redjubjub::PublicKey::read(&mut reader)?;
+        let asset = Asset::read_for_network(&mut reader, network)?;
+
+        let mut metadata = [0; METADATA_LENGTH];
+        reader.read_exact(&mut metadata[..])?;
+
+        let sequence = reader.read_u64::<LittleEndian>()?;
+        let authorizing_signature = redjubjub::Signature::read(&mut reader)?;
+
+        Ok(AssetMetadataUpdate {
+            proof,
+            randomized_public_key,
+            asset,
+            metadata,
+            sequence,
+            authorizing_signature,
+        })
+    }
+
+    /// Stow the bytes of this [`AssetMetadataUpdate`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(METADATA_UPDATE_VERSION)?;
+        self.proof.write(&mut writer)?;
+        writer.write_all(&self.randomized_public_key.0.to_bytes())?;
+        self.asset.write(&mut writer)?;
+        writer.write_all(&self.metadata)?;
+        writer.write_u64::<LittleEndian>(self.sequence)?;
+        self.authorizing_signature.write(&mut writer)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{assets::asset::Asset, util::str_to_array, SaplingKey};
+
+    use super::{AssetMetadataUpdate, MetadataUpdateBuilder};
+
+    #[test]
+    fn test_metadata_update() {
+        let key = SaplingKey::generate_key();
+        let asset = Asset::new(key.public_address(), "name", "https://old.example").unwrap();
+
+        let builder = MetadataUpdateBuilder::new(asset, "https://new.example", 1);
+        let update = builder.build(&key).expect("owner can update metadata");
+        assert_eq!(update.metadata, str_to_array("https://new.example"));
+        assert_eq!(update.asset.id, asset.id);
+
+        let mut serialized = vec![];
+        update.write(&mut serialized).unwrap();
+        let read_back = AssetMetadataUpdate::read(&serialized[..]).unwrap();
+        read_back.verify().expect("update should still verify");
+
+        // Changing any signed field invalidates the update
+        let mut tampered = read_back.clone();
+        tampered.sequence = 2;
+        assert!(tampered.verify().is_err());
+
+        let mut tampered = read_back;
+        tampered.metadata = str_to_array("https://evil.example");
+        assert!(tampered.verify().is_err());
+    }
+
+    #[test]
+    fn test_metadata_update_requires_owner() {
+        let owner = SaplingKey::generate_key();
+        let other = SaplingKey::generate_key();
+        let asset = Asset::new(owner.public_address(), "name", "").unwrap();
+
+        let builder = MetadataUpdateBuilder::new(asset, "new", 1);
+        assert!(builder.build(&other).is_err());
+    }
+}
@@ -3,3 +3,8 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 pub mod asset;
 pub mod asset_identifier;
+pub mod attestation;
+pub mod bridged;
+pub mod generators;
+pub mod metadata_update;
+pub mod registry;
@@ -0,0 +1,515 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Signed snapshots of every known asset, so that light clients can show the
+//! name, owner, metadata and supply of assets without trusting whichever
+//! server they downloaded them from.
+//!
+//! An [`AssetRegistrySnapshot`] lists the assets as of a block, sorted by
+//! identifier, and commits to them with a merkle root. Its
+//! [`RegistryCheckpoint`], the block sequence, asset count and root, is what
+//! gets signed. A client holding a trusted [`VerifyingKey`] can then check a
+//! whole snapshot against a [`SignedRegistryCheckpoint`], or check single
+//! assets with a [`RegistryInclusionProof`] without downloading the rest.
+
+use std::io;
+
+use blake2b_simd::Params as Blake2b;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    errors::IronfishError,
+    keys::{DomainSignature, SignatureDomain, SigningKey, VerifyingKey},
+    personalization::ASSET_REGISTRY_PERSONALIZATION,
+};
+
+use super::{asset::Asset, asset_identifier::AssetIdentifier};
+
+pub const ASSET_REGISTRY_VERSION: u8 = 1;
+
+/// Domain of the signatures over [`RegistryCheckpoint`]s
+pub const ASSET_REGISTRY_SIGNATURE_DOMAIN: &str = "ironfish/asset-registry-checkpoint/v1";
+
+/// Root of a registry without any assets
+pub const EMPTY_REGISTRY_ROOT: [u8; 32] = [0; 32];
+
+/// An asset and the amount of it in circulation
+#[derive(Clone, Copy, Debug)]
+pub struct RegistryEntry {
+    pub asset: Asset,
+    pub supply: u64,
+}
+
+impl RegistryEntry {
+    pub fn id(&self) -> &AssetIdentifier {
+        self.asset.id()
+    }
+
+    fn leaf_hash(&self) -> [u8; 32] {
+        hash(&[&[0], &self.asset.to_bytes(), &self.supply.to_le_bytes()])
+    }
+
+    /// Load a [`RegistryEntry`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let asset = Asset::read(&mut reader)?;
+        let supply = reader.read_u64::<LittleEndian>()?;
+
+        Ok(RegistryEntry { asset, supply })
+    }
+
+    /// Stow the bytes of this [`RegistryEntry`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        self.asset.write(&mut writer)?;
+        writer.write_u64::<LittleEndian>(self.supply)?;
+
+        Ok(())
+    }
+}
+
+fn hash(parts: &[&[u8]]) -> [u8; 32] {
+    let mut state = Blake2b::new()
+        .hash_length(32)
+        .personal(ASSET_REGISTRY_PERSONALIZATION)
+        .to_state();
+    for part in parts {
+        state.update(part);
+    }
+
+    let mut hash = [0; 32];
+    hash.copy_from_slice(state.finalize().as_bytes());
+    hash
+}
+
+/// Nodes are prefixed differently from leaves, so a node can't pass for an
+/// entry
+fn hash_node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    hash(&[&[1], left, right])
+}
+
+/// All levels of the merkle tree over the leaves, the leaves first and the
+/// root last. A node without a right sibling is hashed with itself.
+fn tree_levels(leaves: Vec<[u8; 32]>) -> Vec<Vec<[u8; 32]>> {
+    let mut levels = vec![leaves];
+    while levels[levels.len() - 1].len() > 1 {
+        let level = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| hash_node(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+            .collect();
+        levels.push(level);
+    }
+
+    levels
+}
+
+/// Number of siblings in the inclusion proofs of a registry of this many
+/// assets
+fn tree_depth(asset_count: u64) -> usize {
+    let mut depth = 0;
+    while (1u128 << depth) < asset_count as u128 {
+        depth += 1;
+    }
+    depth
+}
+
+/// Every known asset as of a block
+#[derive(Clone, Debug)]
+pub struct AssetRegistrySnapshot {
+    sequence: u64,
+
+    /// Sorted by asset identifier, without duplicates
+    entries: Vec<RegistryEntry>,
+}
+
+impl AssetRegistrySnapshot {
+    /// A snapshot of the registry at the block with the given sequence.
+    /// Fails if an asset is listed more than once.
+    pub fn new(sequence: u64, mut entries: Vec<RegistryEntry>) -> Result<Self, IronfishError> {
+        entries.sort_by(|a, b| a.id().as_bytes().cmp(b.id().as_bytes()));
+        if entries.windows(2).any(|pair| pair[0].id() == pair[1].id()) {
+            return Err(IronfishError::InvalidAssetRegistry);
+        }
+
+        Ok(AssetRegistrySnapshot { sequence, entries })
+    }
+
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    pub fn entries(&self) -> &[RegistryEntry] {
+        &self.entries
+    }
+
+    fn position(&self, asset_id: &AssetIdentifier) -> Option<usize> {
+        self.entries
+            .binary_search_by(|entry| entry.id().as_bytes().cmp(asset_id.as_bytes()))
+            .ok()
+    }
+
+    pub fn get(&self, asset_id: &AssetIdentifier) -> Option<&RegistryEntry> {
+        self.position(asset_id).map(|index| &self.entries[index])
+    }
+
+    fn levels(&self) -> Vec<Vec<[u8; 32]>> {
+        tree_levels(self.entries.iter().map(RegistryEntry::leaf_hash).collect())
+    }
+
+    /// The merkle root committing to every entry
+    pub fn root(&self) -> [u8; 32] {
+        match self.entries.is_empty() {
+            true => EMPTY_REGISTRY_ROOT,
+            false => self.levels().pop().unwrap()[0],
+        }
+    }
+
+    /// What gets signed to vouch for this snapshot
+    pub fn checkpoint(&self) -> RegistryCheckpoint {
+        RegistryCheckpoint {
+            sequence: self.sequence,
+            asset_count: self.entries.len() as u64,
+            root: self.root(),
+        }
+    }
+
+    /// Proof that the asset is in this snapshot, with its entry, for clients
+    /// that only need a few assets
+    pub fn inclusion_proof(&self, asset_id: &AssetIdentifier) -> Option<RegistryInclusionProof> {
+        let index = self.position(asset_id)?;
+
+        let levels = self.levels();
+        let siblings = levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| {
+                let position = index >> level;
+                *nodes.get(position ^ 1).unwrap_or(&nodes[position])
+            })
+            .collect();
+
+        Some(RegistryInclusionProof {
+            entry: self.entries[index],
+            index: index as u64,
+            siblings,
+        })
+    }
+
+    /// Load an [`AssetRegistrySnapshot`] from a Read implementation (e.g:
+    /// socket, file). Asset identifiers are derived for mainnet.
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != ASSET_REGISTRY_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let sequence = reader.read_u64::<LittleEndian>()?;
+        let asset_count = reader.read_u64::<LittleEndian>()?;
+
+        let mut entries = vec![];
+        for _ in 0..asset_count {
+            entries.push(RegistryEntry::read(&mut reader)?);
+        }
+
+        AssetRegistrySnapshot::new(sequence, entries)
+    }
+
+    /// Stow the bytes of this [`AssetRegistrySnapshot`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(ASSET_REGISTRY_VERSION)?;
+        writer.write_u64::<LittleEndian>(self.sequence)?;
+        writer.write_u64::<LittleEndian>(self.entries.len() as u64)?;
+        for entry in self.entries.iter() {
+            entry.write(&mut writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The commitment to a snapshot that gets signed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RegistryCheckpoint {
+    pub sequence: u64,
+    pub asset_count: u64,
+    pub root: [u8; 32],
+}
+
+impl RegistryCheckpoint {
+    /// Vouch for the snapshot this checkpoint commits to
+    pub fn sign(&self, key: &SigningKey) -> Result<SignedRegistryCheckpoint, IronfishError> {
+        Ok(SignedRegistryCheckpoint {
+            checkpoint: *self,
+            signature: key.sign(&signature_domain(), &self.to_bytes())?,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        // Writing to a vector can't fail
+        self.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    /// Load a [`RegistryCheckpoint`] from a Read implementation (e.g:
+    /// socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != ASSET_REGISTRY_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let sequence = reader.read_u64::<LittleEndian>()?;
+        let asset_count = reader.read_u64::<LittleEndian>()?;
+        let mut root = [0; 32];
+        reader.read_exact(&mut root)?;
+
+        Ok(RegistryCheckpoint {
+            sequence,
+            asset_count,
+            root,
+        })
+    }
+
+    /// Stow the bytes of this [`RegistryCheckpoint`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(ASSET_REGISTRY_VERSION)?;
+        writer.write_u64::<LittleEndian>(self.sequence)?;
+        writer.write_u64::<LittleEndian>(self.asset_count)?;
+        writer.write_all(&self.root)?;
+
+        Ok(())
+    }
+}
+
+fn signature_domain() -> SignatureDomain {
+    SignatureDomain::new(ASSET_REGISTRY_SIGNATURE_DOMAIN).expect("the domain is valid")
+}
+
+/// A [`RegistryCheckpoint`] and the signature vouching for it
+#[derive(Clone)]
+pub struct SignedRegistryCheckpoint {
+    pub checkpoint: RegistryCheckpoint,
+    pub signature: DomainSignature,
+}
+
+impl SignedRegistryCheckpoint {
+    /// Check that the checkpoint was signed by the given key
+    pub fn verify(&self, key: &VerifyingKey) -> Result<(), IronfishError> {
+        key.verify(
+            &signature_domain(),
+            &self.checkpoint.to_bytes(),
+            &self.signature,
+        )
+    }
+
+    /// Check that the key signed the checkpoint of this snapshot
+    pub fn verify_snapshot(
+        &self,
+        key: &VerifyingKey,
+        snapshot: &AssetRegistrySnapshot,
+    ) -> Result<(), IronfishError> {
+        self.verify(key)?;
+
+        if snapshot.checkpoint() != self.checkpoint {
+            return Err(IronfishError::InvalidAssetRegistry);
+        }
+
+        Ok(())
+    }
+
+    /// Check that the key signed a checkpoint including this entry, and
+    /// return the entry
+    pub fn verify_entry<'a>(
+        &self,
+        key: &VerifyingKey,
+        proof: &'a RegistryInclusionProof,
+    ) -> Result<&'a RegistryEntry, IronfishError> {
+        self.verify(key)?;
+        proof.verify(&self.checkpoint)?;
+
+        Ok(&proof.entry)
+    }
+
+    /// Load a [`SignedRegistryCheckpoint`] from a Read implementation (e.g:
+    /// socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let checkpoint = RegistryCheckpoint::read(&mut reader)?;
+        let signature = DomainSignature::read(&mut reader)?;
+
+        Ok(SignedRegistryCheckpoint {
+            checkpoint,
+            signature,
+        })
+    }
+
+    /// Stow the bytes of this [`SignedRegistryCheckpoint`] in the given
+    /// writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        self.checkpoint.write(&mut writer)?;
+        self.signature.write(&mut writer)?;
+
+        Ok(())
+    }
+}
+
+/// Proof that an entry is in the snapshot a [`RegistryCheckpoint`] commits
+/// to
+#[derive(Clone, Debug)]
+pub struct RegistryInclusionProof {
+    pub entry: RegistryEntry,
+
+    /// Position of the entry in the snapshot
+    pub index: u64,
+
+    /// Sibling at every level of the tree, the leaf's first
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl RegistryInclusionProof {
+    /// Check that the entry is in the checkpoint's snapshot
+    pub fn verify(&self, checkpoint: &RegistryCheckpoint) -> Result<(), IronfishError> {
+        if self.index >= checkpoint.asset_count
+            || self.siblings.len() != tree_depth(checkpoint.asset_count)
+        {
+            return Err(IronfishError::InvalidAssetRegistry);
+        }
+
+        let mut node = self.entry.leaf_hash();
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            node = match (self.index >> level) & 1 {
+                0 => hash_node(&node, sibling),
+                _ => hash_node(sibling, &node),
+            };
+        }
+
+        if node != checkpoint.root {
+            return Err(IronfishError::InvalidAssetRegistry);
+        }
+
+        Ok(())
+    }
+
+    /// Load a [`RegistryInclusionProof`] from a Read implementation (e.g:
+    /// socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let entry = RegistryEntry::read(&mut reader)?;
+        let index = reader.read_u64::<LittleEndian>()?;
+
+        // No registry needs more levels than there are bits in a count
+        let depth = reader.read_u8()?;
+        if depth > 64 {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let mut siblings = Vec::with_capacity(depth as usize);
+        for _ in 0..depth {
+            let mut sibling = [0; 32];
+            reader.read_exact(&mut sibling)?;
+            siblings.push(sibling);
+        }
+
+        Ok(RegistryInclusionProof {
+            entry,
+            index,
+            siblings,
+        })
+    }
+
+    /// Stow the bytes of this [`RegistryInclusionProof`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        self.entry.write(&mut writer)?;
+        writer.write_u64::<LittleEndian>(self.index)?;
+        writer.write_u8(self.siblings.len() as u8)?;
+        for sibling in self.siblings.iter() {
+            writer.write_all(sibling)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AssetRegistrySnapshot, RegistryEntry, RegistryInclusionProof, SignedRegistryCheckpoint,
+    };
+    use crate::{
+        assets::asset::Asset,
+        errors::IronfishError,
+        keys::{SaplingKey, SigningKey},
+    };
+
+    fn entries(count: u64) -> Vec<RegistryEntry> {
+        let owner = SaplingKey::generate_key().public_address();
+        (0..count)
+            .map(|supply| RegistryEntry {
+                asset: Asset::new(owner, &format!("asset {}", supply), "").unwrap(),
+                supply,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_registry_snapshot() {
+        let key = SigningKey::generate().unwrap();
+        let snapshot = AssetRegistrySnapshot::new(100, entries(5)).unwrap();
+        let signed = snapshot.checkpoint().sign(&key).unwrap();
+
+        let mut serialized = vec![];
+        snapshot.write(&mut serialized).unwrap();
+        let read_back = AssetRegistrySnapshot::read(&serialized[..]).unwrap();
+        assert_eq!(read_back.root(), snapshot.root());
+
+        let mut serialized = vec![];
+        signed.write(&mut serialized).unwrap();
+        let signed = SignedRegistryCheckpoint::read(&serialized[..]).unwrap();
+        signed
+            .verify_snapshot(&key.verifying_key(), &read_back)
+            .unwrap();
+
+        // Another signer, or a snapshot with a different supply, is rejected
+        assert!(signed
+            .verify_snapshot(&SigningKey::generate().unwrap().verifying_key(), &snapshot)
+            .is_err());
+        let mut tampered = snapshot.entries().to_vec();
+        tampered[0].supply += 1;
+        let tampered = AssetRegistrySnapshot::new(100, tampered).unwrap();
+        assert!(matches!(
+            signed.verify_snapshot(&key.verifying_key(), &tampered),
+            Err(IronfishError::InvalidAssetRegistry)
+        ));
+
+        // Duplicate assets aren't a valid snapshot
+        let mut duplicated = snapshot.entries().to_vec();
+        duplicated.push(duplicated[0]);
+        assert!(AssetRegistrySnapshot::new(100, duplicated).is_err());
+    }
+
+    #[test]
+    fn test_registry_inclusion_proofs() {
+        let key = SigningKey::generate().unwrap();
+        let snapshot = AssetRegistrySnapshot::new(7, entries(5)).unwrap();
+        let signed = snapshot.checkpoint().sign(&key).unwrap();
+
+        for entry in snapshot.entries() {
+            let proof = snapshot.inclusion_proof(entry.id()).unwrap();
+
+            let mut serialized = vec![];
+            proof.write(&mut serialized).unwrap();
+            let proof = RegistryInclusionProof::read(&serialized[..]).unwrap();
+
+            let verified = signed.verify_entry(&key.verifying_key(), &proof).unwrap();
+            assert_eq!(verified.supply, entry.supply);
+        }
+
+        let mut proof = snapshot
+            .inclusion_proof(snapshot.entries()[4].id())
+            .unwrap();
+        proof.entry.supply += 1;
+        assert!(proof.verify(&signed.checkpoint).is_err());
+        proof.entry.supply -= 1;
+        proof.index = 3;
+        assert!(proof.verify(&signed.checkpoint).is_err());
+    }
+}
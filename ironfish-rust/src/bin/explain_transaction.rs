@@ -0,0 +1,97 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Decode a posted transaction and print its [`Transaction::explain`] report
+//! as JSON. The report is built by the same code nodes use to read and verify
+//! transactions, so it shows exactly what the network sees.
+//!
+//! The transaction is read from stdin, hex or base64 encoded. Outputs are
+//! decrypted with any view keys passed on the command line.
+
+use std::{
+    env,
+    io::{self, Read},
+    process,
+};
+
+use ironfish::{
+    serializing::hex_to_vec_bytes, IncomingViewKey, NetworkKind, OutgoingViewKey, Transaction,
+};
+
+const USAGE: &str = "\
+usage: explain-transaction [options] < transaction
+
+Reads a hex or base64 encoded transaction from stdin and prints a JSON
+description of it.
+
+options:
+    --incoming-view-key <hex>    decrypt outputs received with this key
+    --outgoing-view-key <hex>    decrypt outputs sent with this key
+    --testnet                    read minted assets as testnet assets
+    -h, --help                   print this message";
+
+fn main() {
+    if let Err(message) = run() {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let mut incoming_view_keys = vec![];
+    let mut outgoing_view_keys = vec![];
+    let mut network = NetworkKind::Mainnet;
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--incoming-view-key" => {
+                let value = args.next().ok_or("--incoming-view-key needs a key")?;
+                let key = IncomingViewKey::from_hex(&value)
+                    .map_err(|e| format!("invalid incoming view key: {}", e))?;
+                incoming_view_keys.push(key);
+            }
+            "--outgoing-view-key" => {
+                let value = args.next().ok_or("--outgoing-view-key needs a key")?;
+                let key = OutgoingViewKey::from_hex(&value)
+                    .map_err(|e| format!("invalid outgoing view key: {}", e))?;
+                outgoing_view_keys.push(key);
+            }
+            "--testnet" => network = NetworkKind::Testnet,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(());
+            }
+            _ => return Err(format!("unknown argument {}\n\n{}", arg, USAGE)),
+        }
+    }
+
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .map_err(|e| format!("failed to read stdin: {}", e))?;
+    let bytes = decode(input.trim())?;
+
+    let transaction = Transaction::read_for_network(&bytes[..], network)
+        .map_err(|e| format!("invalid transaction: {}", e))?;
+    let report = transaction.explain(&incoming_view_keys, &outgoing_view_keys);
+
+    let json = serde_json::to_string_pretty(&report).map_err(|e| e.to_string())?;
+    println!("{}", json);
+
+    Ok(())
+}
+
+/// Transactions are given as hex, as printed by the node, or as base64.
+/// Base64 made up only of hex digits is read as hex, which in practice never
+/// happens for something as long as a transaction.
+fn decode(input: &str) -> Result<Vec<u8>, String> {
+    if input.is_empty() {
+        return Err("no transaction on stdin".to_string());
+    }
+
+    hex_to_vec_bytes(input)
+        .or_else(|_| base64::decode(input))
+        .map_err(|_| "transaction is neither hex nor base64".to_string())
+}
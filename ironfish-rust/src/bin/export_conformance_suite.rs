@@ -0,0 +1,33 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Write the [`ironfish::conformance`] test vectors to a directory, as JSON
+//! files that other implementations can test against.
+
+use std::{env, path::PathBuf, process};
+
+use ironfish::conformance::ConformanceSuite;
+
+const USAGE: &str = "\
+usage: export-conformance-suite <directory>
+
+Writes the conformance test vectors generated by this implementation to the
+given directory, creating it if needed.";
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let directory = match (args.next(), args.next()) {
+        (Some(arg), None) if arg != "-h" && arg != "--help" => PathBuf::from(arg),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let result = ConformanceSuite::generate().and_then(|suite| suite.write_to(&directory));
+    if let Err(error) = result {
+        eprintln!("error: failed to export the conformance suite: {}", error);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,360 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Proofs that a transaction is in a block, so that light clients holding
+//! only block headers can check a transaction they were sent without
+//! downloading the rest of the block.
+//!
+//! Every block header commits to the transactions of the block, in order,
+//! with the root of a merkle tree over their [`Transaction::hash`]es, built
+//! the way the chain builds it:
+//! - a block without transactions commits to the BLAKE3 hash of
+//!   [`TRANSACTION_ROOT_PERSONALIZATION`]
+//! - otherwise the hashes are padded with the BLAKE3 hash of a single zero
+//!   byte, up to the next power of two and to at least two of them
+//! - every node is the BLAKE3 hash of the personalization, its level as a
+//!   byte, the leaves being level 0, and its two children
+//!
+//! A [`TransactionInclusionProof`] holds the siblings on the path from a
+//! transaction to that root. Whether the header itself is on the best chain
+//! is for the client to check.
+
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    errors::IronfishError, personalization::TRANSACTION_ROOT_PERSONALIZATION, Transaction,
+};
+
+/// Size of a serialized block header, including the randomness
+pub const BLOCK_HEADER_SIZE: usize = 180;
+
+/// No block holds more transactions than an index fits, so no tree is deeper
+const MAX_PROOF_DEPTH: u8 = 32;
+
+/// Padding after the transaction hashes
+fn null_node() -> [u8; 32] {
+    *blake3::hash(&[0]).as_bytes()
+}
+
+fn hash_node(level: u8, left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(TRANSACTION_ROOT_PERSONALIZATION);
+    hasher.update(&[level]);
+    hasher.update(left);
+    hasher.update(right);
+    *hasher.finalize().as_bytes()
+}
+
+/// All levels of the merkle tree over the hashes, the padded leaves first
+/// and the root last. There has to be at least one hash.
+fn tree_levels(hashes: &[[u8; 32]]) -> Vec<Vec<[u8; 32]>> {
+    let size = hashes.len().max(2).next_power_of_two();
+    let mut leaves = hashes.to_vec();
+    leaves.resize(size, null_node());
+
+    let mut levels = vec![leaves];
+    while levels[levels.len() - 1].len() > 1 {
+        let level = (levels.len() - 1) as u8;
+        let nodes = levels[levels.len() - 1]
+            .chunks(2)
+            .map(|pair| hash_node(level, &pair[0], &pair[1]))
+            .collect();
+        levels.push(nodes);
+    }
+
+    levels
+}
+
+/// Root of the merkle tree over the given transaction hashes, in block order
+pub fn transaction_merkle_root(hashes: &[[u8; 32]]) -> [u8; 32] {
+    if hashes.is_empty() {
+        return *blake3::hash(TRANSACTION_ROOT_PERSONALIZATION).as_bytes();
+    }
+
+    tree_levels(hashes).pop().unwrap()[0]
+}
+
+/// The transaction commitment of a block holding the given transactions
+pub fn transaction_commitment(transactions: &[Transaction]) -> [u8; 32] {
+    let hashes: Vec<[u8; 32]> = transactions.iter().map(Transaction::hash).collect();
+    transaction_merkle_root(&hashes)
+}
+
+/// A serialized block header, as hashed into the block hash: the randomness
+/// followed by the fields the miner doesn't change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockHeader {
+    bytes: [u8; BLOCK_HEADER_SIZE],
+}
+
+impl BlockHeader {
+    pub fn new(bytes: [u8; BLOCK_HEADER_SIZE]) -> Self {
+        BlockHeader { bytes }
+    }
+
+    /// The block hash
+    pub fn hash(&self) -> [u8; 32] {
+        *blake3::hash(&self.bytes).as_bytes()
+    }
+
+    pub fn sequence(&self) -> u32 {
+        (&self.bytes[8..12]).read_u32::<LittleEndian>().unwrap()
+    }
+
+    pub fn previous_block_hash(&self) -> [u8; 32] {
+        self.field(12)
+    }
+
+    pub fn note_commitment(&self) -> [u8; 32] {
+        self.field(44)
+    }
+
+    pub fn transaction_commitment(&self) -> [u8; 32] {
+        self.field(76)
+    }
+
+    fn field(&self, offset: usize) -> [u8; 32] {
+        let mut field = [0; 32];
+        field.copy_from_slice(&self.bytes[offset..offset + 32]);
+        field
+    }
+
+    /// Load a [`BlockHeader`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let mut bytes = [0; BLOCK_HEADER_SIZE];
+        reader.read_exact(&mut bytes)?;
+
+        Ok(BlockHeader { bytes })
+    }
+
+    /// Stow the bytes of this [`BlockHeader`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.bytes)?;
+
+        Ok(())
+    }
+}
+
+/// Proof that a transaction is in the block whose header has a given
+/// transaction commitment
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransactionInclusionProof {
+    /// [`Transaction::hash`] of the transaction
+    pub transaction_hash: [u8; 32],
+
+    /// Position of the transaction in the block
+    pub index: u32,
+
+    /// Sibling at every level of the tree, the leaf's first
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl TransactionInclusionProof {
+    /// Proof that the transaction at the given index is among the given
+    /// transaction hashes, in block order
+    pub fn new(hashes: &[[u8; 32]], index: usize) -> Option<Self> {
+        let transaction_hash = *hashes.get(index)?;
+
+        let levels = tree_levels(hashes);
+        let siblings = levels[..levels.len() - 1]
+            .iter()
+            .enumerate()
+            .map(|(level, nodes)| nodes[(index >> level) ^ 1])
+            .collect();
+
+        Some(TransactionInclusionProof {
+            transaction_hash,
+            index: u32::try_from(index).ok()?,
+            siblings,
+        })
+    }
+
+    /// Proof that the transaction at the given index is in a block holding
+    /// the given transactions
+    pub fn from_transactions(transactions: &[Transaction], index: usize) -> Option<Self> {
+        let hashes: Vec<[u8; 32]> = transactions.iter().map(Transaction::hash).collect();
+        TransactionInclusionProof::new(&hashes, index)
+    }
+
+    /// The transaction commitment this proof leads to
+    pub fn root(&self) -> Result<[u8; 32], IronfishError> {
+        let depth = self.siblings.len();
+        if depth == 0 || depth > MAX_PROOF_DEPTH as usize || (self.index as u64) >> depth != 0 {
+            return Err(IronfishError::InvalidInclusionProof);
+        }
+
+        let mut node = self.transaction_hash;
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            node = match (self.index >> level) & 1 {
+                0 => hash_node(level as u8, &node, sibling),
+                _ => hash_node(level as u8, sibling, &node),
+            };
+        }
+
+        Ok(node)
+    }
+
+    /// Check that the transaction is in the block with the given transaction
+    /// commitment
+    pub fn verify(&self, transaction_commitment: &[u8; 32]) -> Result<(), IronfishError> {
+        if self.root()? != *transaction_commitment {
+            return Err(IronfishError::InvalidInclusionProof);
+        }
+
+        Ok(())
+    }
+
+    /// Check that the transaction is in the block with the given hash, whose
+    /// header is given
+    pub fn verify_header(
+        &self,
+        header: &BlockHeader,
+        block_hash: &[u8; 32],
+    ) -> Result<(), IronfishError> {
+        if header.hash() != *block_hash {
+            return Err(IronfishError::InvalidInclusionProof);
+        }
+
+        self.verify(&header.transaction_commitment())
+    }
+
+    /// Load a [`TransactionInclusionProof`] from a Read implementation (e.g:
+    /// socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let mut transaction_hash = [0; 32];
+        reader.read_exact(&mut transaction_hash)?;
+        let index = reader.read_u32::<LittleEndian>()?;
+
+        let depth = reader.read_u8()?;
+        if depth > MAX_PROOF_DEPTH {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let mut siblings = Vec::with_capacity(depth as usize);
+        for _ in 0..depth {
+            let mut sibling = [0; 32];
+            reader.read_exact(&mut sibling)?;
+            siblings.push(sibling);
+        }
+
+        Ok(TransactionInclusionProof {
+            transaction_hash,
+            index,
+            siblings,
+        })
+    }
+
+    /// Stow the bytes of this [`TransactionInclusionProof`] in the given
+    /// writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.transaction_hash)?;
+        writer.write_u32::<LittleEndian>(self.index)?;
+        writer.write_u8(u8::try_from(self.siblings.len())?)?;
+        for sibling in self.siblings.iter() {
+            writer.write_all(sibling)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        hash_node, null_node, transaction_merkle_root, BlockHeader, TransactionInclusionProof,
+        BLOCK_HEADER_SIZE,
+    };
+    use crate::{errors::IronfishError, personalization::TRANSACTION_ROOT_PERSONALIZATION};
+
+    fn hashes(count: u8) -> Vec<[u8; 32]> {
+        (0..count)
+            .map(|i| *blake3::hash(&[i, 1]).as_bytes())
+            .collect()
+    }
+
+    #[test]
+    fn test_transaction_merkle_root() {
+        assert_eq!(
+            transaction_merkle_root(&[]),
+            *blake3::hash(TRANSACTION_ROOT_PERSONALIZATION).as_bytes()
+        );
+
+        let h = hashes(3);
+        assert_eq!(
+            transaction_merkle_root(&h[..1]),
+            hash_node(0, &h[0], &null_node())
+        );
+        assert_eq!(transaction_merkle_root(&h[..2]), hash_node(0, &h[0], &h[1]));
+        assert_eq!(
+            transaction_merkle_root(&h),
+            hash_node(
+                1,
+                &hash_node(0, &h[0], &h[1]),
+                &hash_node(0, &h[2], &null_node())
+            )
+        );
+    }
+
+    #[test]
+    fn test_transaction_inclusion_proofs() {
+        for count in [1, 2, 3, 11] {
+            let h = hashes(count);
+            let root = transaction_merkle_root(&h);
+
+            for (index, hash) in h.iter().enumerate() {
+                let proof = TransactionInclusionProof::new(&h, index).unwrap();
+                assert_eq!(proof.transaction_hash, *hash);
+
+                let mut serialized = vec![];
+                proof.write(&mut serialized).unwrap();
+                let proof = TransactionInclusionProof::read(&serialized[..]).unwrap();
+                proof.verify(&root).unwrap();
+            }
+        }
+
+        let h = hashes(5);
+        assert!(TransactionInclusionProof::new(&h, 5).is_none());
+
+        let root = transaction_merkle_root(&h);
+        let mut proof = TransactionInclusionProof::new(&h, 2).unwrap();
+        proof.index = 3;
+        assert!(matches!(
+            proof.verify(&root),
+            Err(IronfishError::InvalidInclusionProof)
+        ));
+
+        // An index past the end of the tree would alias another position
+        proof.index = 2 + 8;
+        assert!(proof.verify(&root).is_err());
+        proof.index = 2;
+        proof.siblings.pop();
+        assert!(proof.verify(&root).is_err());
+    }
+
+    #[test]
+    fn test_verify_against_header() {
+        let h = hashes(4);
+        let root = transaction_merkle_root(&h);
+
+        let mut bytes = [7; BLOCK_HEADER_SIZE];
+        bytes[8..12].copy_from_slice(&42u32.to_le_bytes());
+        bytes[76..108].copy_from_slice(&root);
+        let header = BlockHeader::new(bytes);
+        assert_eq!(header.sequence(), 42);
+        assert_eq!(header.previous_block_hash(), [7; 32]);
+
+        let mut serialized = vec![];
+        header.write(&mut serialized).unwrap();
+        let header = BlockHeader::read(&serialized[..]).unwrap();
+
+        let proof = TransactionInclusionProof::new(&h, 1).unwrap();
+        proof.verify_header(&header, &header.hash()).unwrap();
+        assert!(proof.verify_header(&header, &[0; 32]).is_err());
+
+        let proof = TransactionInclusionProof::new(&hashes(3), 1).unwrap();
+        assert!(proof.verify_header(&header, &header.hash()).is_err());
+    }
+}
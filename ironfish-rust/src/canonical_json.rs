@@ -0,0 +1,240 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A canonical JSON encoding for reports, such as
+//! [`crate::transaction::explain::TransactionReport`], so that the same report
+//! is always encoded to the same bytes, whatever tool or platform produced
+//! it. Reports can then be hashed, signed and diffed byte for byte.
+//!
+//! The encoding is:
+//! - no whitespace between tokens
+//! - object keys sorted by their UTF-8 bytes, with no duplicates
+//! - integers in decimal, without sign for positive values, leading zeros,
+//!   fraction or exponent. There are no floating point numbers.
+//! - strings as UTF-8, escaping only `"`, `\` and control characters.
+//!   Control characters are escaped as `\b`, `\f`, `\n`, `\r`, `\t` or a
+//!   `\u00xx` escape with lowercase hex digits.
+//! - binary values as lowercase hex strings, as written by
+//!   [`crate::serializing::bytes_to_hex`]
+//!
+//! None of this depends on the locale, and it doesn't need the `serde`
+//! feature.
+
+use std::{collections::BTreeMap, fmt::Write};
+
+/// A value that can be encoded canonically
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CanonicalValue {
+    Null,
+    Bool(bool),
+    Unsigned(u128),
+    Signed(i128),
+    String(String),
+    Array(Vec<CanonicalValue>),
+    Object(BTreeMap<String, CanonicalValue>),
+}
+
+impl CanonicalValue {
+    /// The canonical encoding of this value
+    pub fn encode(&self) -> String {
+        let mut json = String::new();
+        self.encode_into(&mut json);
+        json
+    }
+
+    fn encode_into(&self, json: &mut String) {
+        match self {
+            CanonicalValue::Null => json.push_str("null"),
+            CanonicalValue::Bool(value) => json.push_str(if *value { "true" } else { "false" }),
+            CanonicalValue::Unsigned(value) => write!(json, "{}", value).unwrap(),
+            CanonicalValue::Signed(value) => write!(json, "{}", value).unwrap(),
+            CanonicalValue::String(value) => encode_string(value, json),
+            CanonicalValue::Array(values) => {
+                json.push('[');
+                for (index, value) in values.iter().enumerate() {
+                    if index > 0 {
+                        json.push(',');
+                    }
+                    value.encode_into(json);
+                }
+                json.push(']');
+            }
+            CanonicalValue::Object(fields) => {
+                json.push('{');
+                for (index, (key, value)) in fields.iter().enumerate() {
+                    if index > 0 {
+                        json.push(',');
+                    }
+                    encode_string(key, json);
+                    json.push(':');
+                    value.encode_into(json);
+                }
+                json.push('}');
+            }
+        }
+    }
+}
+
+fn encode_string(value: &str, json: &mut String) {
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\u{08}' => json.push_str("\\b"),
+            '\u{0c}' => json.push_str("\\f"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c < ' ' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+}
+
+impl From<bool> for CanonicalValue {
+    fn from(value: bool) -> Self {
+        CanonicalValue::Bool(value)
+    }
+}
+
+impl From<u8> for CanonicalValue {
+    fn from(value: u8) -> Self {
+        CanonicalValue::Unsigned(value.into())
+    }
+}
+
+impl From<u32> for CanonicalValue {
+    fn from(value: u32) -> Self {
+        CanonicalValue::Unsigned(value.into())
+    }
+}
+
+impl From<u64> for CanonicalValue {
+    fn from(value: u64) -> Self {
+        CanonicalValue::Unsigned(value.into())
+    }
+}
+
+impl From<usize> for CanonicalValue {
+    fn from(value: usize) -> Self {
+        CanonicalValue::Unsigned(value as u128)
+    }
+}
+
+impl From<u128> for CanonicalValue {
+    fn from(value: u128) -> Self {
+        CanonicalValue::Unsigned(value)
+    }
+}
+
+impl From<i64> for CanonicalValue {
+    fn from(value: i64) -> Self {
+        CanonicalValue::Signed(value.into())
+    }
+}
+
+impl From<&str> for CanonicalValue {
+    fn from(value: &str) -> Self {
+        CanonicalValue::String(value.to_string())
+    }
+}
+
+impl From<String> for CanonicalValue {
+    fn from(value: String) -> Self {
+        CanonicalValue::String(value)
+    }
+}
+
+impl<T: Into<CanonicalValue>> From<Option<T>> for CanonicalValue {
+    fn from(value: Option<T>) -> Self {
+        match value {
+            Some(value) => value.into(),
+            None => CanonicalValue::Null,
+        }
+    }
+}
+
+impl<T: Into<CanonicalValue>> From<Vec<T>> for CanonicalValue {
+    fn from(values: Vec<T>) -> Self {
+        CanonicalValue::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Builds the fields of a [`CanonicalValue::Object`]
+#[derive(Default)]
+pub struct CanonicalObject(BTreeMap<String, CanonicalValue>);
+
+impl CanonicalObject {
+    pub fn new() -> Self {
+        CanonicalObject::default()
+    }
+
+    /// Add a field. Keys must be unique, so adding a key twice panics.
+    pub fn field(mut self, key: &str, value: impl Into<CanonicalValue>) -> Self {
+        let previous = self.0.insert(key.to_string(), value.into());
+        assert!(previous.is_none(), "duplicate key {}", key);
+        self
+    }
+
+    pub fn build(self) -> CanonicalValue {
+        CanonicalValue::Object(self.0)
+    }
+}
+
+/// Types with a canonical JSON encoding
+pub trait ToCanonicalJson {
+    fn to_canonical_value(&self) -> CanonicalValue;
+
+    /// The canonical encoding of this value, see the [module
+    /// documentation](self)
+    fn to_canonical_json(&self) -> String {
+        self.to_canonical_value().encode()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CanonicalObject, CanonicalValue};
+
+    #[test]
+    fn test_canonical_encoding() {
+        let value = CanonicalObject::new()
+            .field("zeta", u64::MAX)
+            .field("Alpha", -42i64)
+            .field("alpha", "quote \" slash \\ tab \t bell \u{7} snow ☃")
+            .field("list", vec![1u32, 0, 7])
+            .field("none", None::<String>)
+            .field(
+                "nested",
+                CanonicalObject::new()
+                    .field("b", true)
+                    .field("a", u128::MAX)
+                    .build(),
+            )
+            .build();
+
+        // Golden encoding: keys sorted by bytes, so uppercase comes first
+        assert_eq!(
+            value.encode(),
+            concat!(
+                r#"{"Alpha":-42,"#,
+                r#""alpha":"quote \" slash \\ tab \t bell \u0007 snow ☃","#,
+                r#""list":[1,0,7],"#,
+                r#""nested":{"a":340282366920938463463374607431768211455,"b":true},"#,
+                r#""none":null,"#,
+                r#""zeta":18446744073709551615}"#
+            )
+        );
+
+        assert_eq!(CanonicalValue::from("\u{1f}").encode(), r#""\u001f""#);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_duplicate_keys() {
+        CanonicalObject::new().field("a", 1u32).field("a", 2u32);
+    }
+}
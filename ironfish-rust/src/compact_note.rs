@@ -0,0 +1,295 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Compact notes, holding only what light clients need to find their notes
+//! on the chain, so that syncing takes a fraction of the bandwidth of
+//! downloading whole blocks.
+//!
+//! A [`CompactNote`] keeps the note commitment, to match the note and to
+//! build the note tree, the ephemeral public key, to derive the shared
+//! secret, and the encrypted note without its memo. It drops the value
+//! commitment, the note encryption keys of the spender, the encrypted memo
+//! and the MAC tag, which takes it from
+//! [`MERKLE_NOTE_SIZE`](crate::merkle_note::MERKLE_NOTE_SIZE) bytes to
+//! [`COMPACT_NOTE_SIZE`].
+//!
+//! Notes are encrypted with a stream cipher, so any part of the ciphertext
+//! can be decrypted on its own. Without the MAC tag the decrypted note
+//! isn't authenticated, but it's only accepted if it matches the note
+//! commitment, which nobody can forge. The memo isn't part of the
+//! commitment: clients that need it fetch the whole note once they know
+//! it's theirs.
+
+use std::{io, ops::Range};
+
+use bls12_381::Scalar;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use chacha20::{
+    cipher::{NewCipher, StreamCipher, StreamCipherSeek},
+    ChaCha20, Key, Nonce,
+};
+use jubjub::SubgroupPoint;
+
+use crate::{
+    errors::IronfishError,
+    keys::IncomingViewKey,
+    merkle_note::{MerkleNote, EPHEMERAL_PUBLIC_KEY_SIZE, NOTE_COMMITMENT_SIZE},
+    metrics::record,
+    note::{Note, AMOUNT_VALUE_SIZE, ENCRYPTED_NOTE_SIZE, MEMO_SIZE, SCALAR_SIZE},
+    note_kem::{DefaultNoteKem, NoteKem},
+    serializing::read_scalar,
+    MerkleNoteHash, Transaction,
+};
+
+/// Where the memo is in a decrypted note, see [`Note::encrypt`]
+const MEMO_RANGE: Range<usize> =
+    SCALAR_SIZE + AMOUNT_VALUE_SIZE..SCALAR_SIZE + AMOUNT_VALUE_SIZE + MEMO_SIZE;
+
+/// Size of the encrypted note without its memo or MAC tag
+pub const COMPACT_CIPHERTEXT_SIZE: usize = ENCRYPTED_NOTE_SIZE - MEMO_SIZE;
+
+/// Size of a serialized [`CompactNote`]: note commitment, ephemeral public
+/// key and the compact ciphertext
+pub const COMPACT_NOTE_SIZE: usize =
+    NOTE_COMMITMENT_SIZE + EPHEMERAL_PUBLIC_KEY_SIZE + COMPACT_CIPHERTEXT_SIZE;
+
+/// Bytes of the ChaCha20 keystream that key the MAC of the AEAD notes are
+/// encrypted with, before the bytes that encrypt the note
+const MAC_KEY_BLOCK_SIZE: u64 = 64;
+
+/// What a light client needs of a [`MerkleNote`] to find out whether it's
+/// theirs. See the [module documentation](self).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactNote {
+    note_commitment: Scalar,
+    ephemeral_public_key: SubgroupPoint,
+    ciphertext: [u8; COMPACT_CIPHERTEXT_SIZE],
+}
+
+impl CompactNote {
+    pub fn from_merkle_note(merkle_note: &MerkleNote) -> Self {
+        let encrypted_note = &merkle_note.encrypted_note;
+        let mut ciphertext = [0; COMPACT_CIPHERTEXT_SIZE];
+        ciphertext[..MEMO_RANGE.start].copy_from_slice(&encrypted_note[..MEMO_RANGE.start]);
+        ciphertext[MEMO_RANGE.start..]
+            .copy_from_slice(&encrypted_note[MEMO_RANGE.end..ENCRYPTED_NOTE_SIZE]);
+
+        CompactNote {
+            note_commitment: merkle_note.note_commitment,
+            ephemeral_public_key: merkle_note.ephemeral_public_key,
+            ciphertext,
+        }
+    }
+
+    pub fn merkle_hash(&self) -> MerkleNoteHash {
+        MerkleNoteHash::new(self.note_commitment)
+    }
+
+    /// Decrypt the note with the owner's incoming view key, in the same time
+    /// whether or not the note belongs to the key, like
+    /// [`MerkleNote::decrypt_note_for_owner`]. The memo of the note isn't
+    /// in its compact form, so the note comes back with an empty memo.
+    pub fn decrypt_note_for_owner(
+        &self,
+        owner_view_key: &IncomingViewKey,
+    ) -> Result<Note, IronfishError> {
+        let shared_secret = DefaultNoteKem::decapsulate(owner_view_key, &self.ephemeral_public_key);
+        let plaintext = self.decrypt(&shared_secret);
+        let (note, is_valid) = Note::from_plaintext_constant_time(
+            owner_view_key.public_address(),
+            &plaintext,
+            self.note_commitment,
+        )?;
+
+        let is_valid = bool::from(is_valid);
+        record(|sink| sink.note_decryption_attempted(is_valid));
+
+        match is_valid {
+            true => Ok(note),
+            false => Err(IronfishError::InvalidDecryptionKey),
+        }
+    }
+
+    /// The note as decrypted with the shared secret, with a zeroed memo.
+    /// Note encryption uses ChaCha20Poly1305 with a zero nonce, which
+    /// encrypts the note from the second block of the keystream on.
+    fn decrypt(&self, shared_secret: &[u8; 32]) -> [u8; ENCRYPTED_NOTE_SIZE] {
+        let mut plaintext = [0; ENCRYPTED_NOTE_SIZE];
+        plaintext[..MEMO_RANGE.start].copy_from_slice(&self.ciphertext[..MEMO_RANGE.start]);
+        plaintext[MEMO_RANGE.end..].copy_from_slice(&self.ciphertext[MEMO_RANGE.start..]);
+
+        let mut cipher = ChaCha20::new(Key::from_slice(shared_secret), &Nonce::default());
+        cipher.seek(MAC_KEY_BLOCK_SIZE);
+        cipher.apply_keystream(&mut plaintext[..MEMO_RANGE.start]);
+        cipher.seek(MAC_KEY_BLOCK_SIZE + MEMO_RANGE.end as u64);
+        cipher.apply_keystream(&mut plaintext[MEMO_RANGE.end..]);
+
+        plaintext
+    }
+
+    /// Load a [`CompactNote`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let note_commitment = read_scalar(&mut reader)?;
+        let ephemeral_public_key = DefaultNoteKem::read_encapsulation(&mut reader)?;
+        let mut ciphertext = [0; COMPACT_CIPHERTEXT_SIZE];
+        reader.read_exact(&mut ciphertext)?;
+
+        Ok(CompactNote {
+            note_commitment,
+            ephemeral_public_key,
+            ciphertext,
+        })
+    }
+
+    /// Stow the bytes of this [`CompactNote`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.note_commitment.to_bytes())?;
+        DefaultNoteKem::write_encapsulation(&self.ephemeral_public_key, &mut writer)?;
+        writer.write_all(&self.ciphertext)?;
+
+        Ok(())
+    }
+}
+
+/// The compact notes of a block, in the order they're added to the note
+/// tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CompactBlock {
+    pub sequence: u32,
+    pub notes: Vec<CompactNote>,
+}
+
+impl CompactBlock {
+    pub fn from_transactions(sequence: u32, transactions: &[Transaction]) -> Self {
+        let notes = transactions
+            .iter()
+            .flat_map(Transaction::outputs)
+            .map(|output| CompactNote::from_merkle_note(&output.merkle_note()))
+            .collect();
+
+        CompactBlock { sequence, notes }
+    }
+
+    /// The notes of the block sent to the key, with their index in the block
+    pub fn decrypt_notes_for_owner(&self, owner_view_key: &IncomingViewKey) -> Vec<(usize, Note)> {
+        self.notes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, note)| {
+                let note = note.decrypt_note_for_owner(owner_view_key).ok()?;
+                Some((index, note))
+            })
+            .collect()
+    }
+
+    /// Load a [`CompactBlock`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let sequence = reader.read_u32::<LittleEndian>()?;
+        let count = reader.read_u32::<LittleEndian>()?;
+        let notes = (0..count)
+            .map(|_| CompactNote::read(&mut reader))
+            .collect::<Result<_, _>>()?;
+
+        Ok(CompactBlock { sequence, notes })
+    }
+
+    /// Stow the bytes of this [`CompactBlock`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        let count = u32::try_from(self.notes.len())?;
+        writer.write_u32::<LittleEndian>(self.sequence)?;
+        writer.write_u32::<LittleEndian>(count)?;
+        for note in &self.notes {
+            note.write(&mut writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CompactBlock, CompactNote, COMPACT_NOTE_SIZE};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::{EphemeralKeyPair, SaplingKey},
+        merkle_note::MerkleNote,
+        note::{Memo, Note},
+    };
+    use ironfish_zkp::primitives::ValueCommitment;
+
+    fn make_merkle_note(owner: &SaplingKey, memo: &str) -> (Note, MerkleNote) {
+        let spender_key = SaplingKey::generate_key();
+        let note = Note::new(
+            owner.public_address(),
+            42,
+            memo,
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+        let merkle_note = MerkleNote::new(
+            &spender_key,
+            &note,
+            &value_commitment,
+            &EphemeralKeyPair::new(),
+        );
+
+        (note, merkle_note)
+    }
+
+    #[test]
+    fn test_compact_note_decryption() {
+        let owner = SaplingKey::generate_key();
+        let (note, merkle_note) = make_merkle_note(&owner, "not in the compact note");
+        let compact = CompactNote::from_merkle_note(&merkle_note);
+        assert_eq!(compact.merkle_hash(), merkle_note.merkle_hash());
+
+        let decrypted = compact
+            .decrypt_note_for_owner(owner.incoming_view_key())
+            .unwrap();
+        assert_eq!(decrypted.value(), note.value());
+        assert_eq!(decrypted.sender(), note.sender());
+        assert_eq!(decrypted.commitment(), note.commitment());
+        assert_eq!(decrypted.memo(), Memo::default());
+
+        let other = SaplingKey::generate_key();
+        assert!(matches!(
+            compact.decrypt_note_for_owner(other.incoming_view_key()),
+            Err(IronfishError::InvalidDecryptionKey)
+        ));
+
+        // A tampered ciphertext doesn't match the commitment
+        let mut tampered = compact.clone();
+        tampered.ciphertext[33] ^= 1;
+        assert!(tampered
+            .decrypt_note_for_owner(owner.incoming_view_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_compact_block_serialization() {
+        let owner = SaplingKey::generate_key();
+        let other = SaplingKey::generate_key();
+        let notes = [
+            make_merkle_note(&other, "").1,
+            make_merkle_note(&owner, "").1,
+        ];
+        let block = CompactBlock {
+            sequence: 7,
+            notes: notes.iter().map(CompactNote::from_merkle_note).collect(),
+        };
+
+        let mut serialized = vec![];
+        block.write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), 8 + 2 * COMPACT_NOTE_SIZE);
+
+        let read = CompactBlock::read(&serialized[..]).unwrap();
+        assert_eq!(read, block);
+
+        let found = read.decrypt_notes_for_owner(owner.incoming_view_key());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].0, 1);
+    }
+}
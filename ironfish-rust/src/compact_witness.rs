@@ -0,0 +1,219 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A compact encoding for batches of witnesses, for remote provers and light
+//! clients that ship thousands of authentication paths at a time.
+//!
+//! Written one by one, every witness repeats its tree size and root hash and
+//! spends a whole byte on the side of each node. Witnesses in a batch are
+//! almost always for the same tree though, and neighbouring notes share
+//! most of their path near the root. The compact encoding:
+//! - lists each distinct tree size and root hash once, and has witnesses
+//!   refer to them by index
+//! - packs the side of every node of a path into a bit
+//! - leaves out any sibling equal to the sibling at the same level of the
+//!   previous witness for the same root, marked by another bit
+//!
+//! Witnesses come back in the order they were written, so writing them
+//! sorted by position leaves out the most siblings.
+
+use std::{collections::HashMap, io};
+
+use bls12_381::Scalar;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::PrimeField;
+use ironfish_zkp::constants::TREE_DEPTH;
+
+use crate::{
+    errors::IronfishError,
+    serializing::read_scalar,
+    tree_position::TreeSize,
+    witness::{Witness, WitnessNode},
+};
+
+pub const COMPACT_WITNESS_VERSION: u8 = 1;
+
+/// Bytes taken by one bit per level of a path
+const PATH_BITS_SIZE: usize = (TREE_DEPTH + 7) / 8;
+
+/// Stow the compact encoding of the witnesses in the given writer. Every
+/// witness needs a full authentication path.
+pub fn write_compact_witnesses<W: io::Write>(
+    witnesses: &[Witness],
+    mut writer: W,
+) -> Result<(), IronfishError> {
+    if witnesses
+        .iter()
+        .any(|witness| witness.auth_path.len() != TREE_DEPTH)
+    {
+        return Err(IronfishError::InconsistentWitness);
+    }
+
+    let mut roots: Vec<(TreeSize, Scalar)> = vec![];
+    let mut root_indices: HashMap<(TreeSize, [u8; 32]), u32> = HashMap::new();
+    let witness_roots = witnesses
+        .iter()
+        .map(|witness| {
+            let key = (witness.tree_size, witness.root_hash.to_repr());
+            let next_index = u32::try_from(roots.len())?;
+            let index = *root_indices.entry(key).or_insert_with(|| {
+                roots.push((witness.tree_size, witness.root_hash));
+                next_index
+            });
+            Ok(index)
+        })
+        .collect::<Result<Vec<u32>, IronfishError>>()?;
+
+    writer.write_u8(COMPACT_WITNESS_VERSION)?;
+
+    writer.write_u32::<LittleEndian>(u32::try_from(roots.len())?)?;
+    for (tree_size, root_hash) in roots.iter() {
+        writer.write_u32::<LittleEndian>(tree_size.as_u32())?;
+        writer.write_all(&root_hash.to_repr())?;
+    }
+
+    let mut previous: Vec<Option<&[WitnessNode<Scalar>]>> = vec![None; roots.len()];
+    writer.write_u32::<LittleEndian>(u32::try_from(witnesses.len())?)?;
+    for (witness, root_index) in witnesses.iter().zip(witness_roots) {
+        let mut sides = [0u8; PATH_BITS_SIZE];
+        let mut reused = [0u8; PATH_BITS_SIZE];
+        let mut siblings = vec![];
+
+        for (level, node) in witness.auth_path.iter().enumerate() {
+            let sibling = match node {
+                WitnessNode::Left(sibling) => sibling,
+                WitnessNode::Right(sibling) => {
+                    sides[level / 8] |= 1 << (level % 8);
+                    sibling
+                }
+            };
+
+            let is_reused = previous[root_index as usize]
+                .map_or(false, |path| sibling_of(&path[level]) == sibling);
+            match is_reused {
+                true => reused[level / 8] |= 1 << (level % 8),
+                false => siblings.push(sibling),
+            }
+        }
+        previous[root_index as usize] = Some(&witness.auth_path[..]);
+
+        writer.write_u32::<LittleEndian>(root_index)?;
+        writer.write_all(&sides)?;
+        writer.write_all(&reused)?;
+        for sibling in siblings {
+            writer.write_all(&sibling.to_repr())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Load witnesses written by [`write_compact_witnesses`] from a Read
+/// implementation (e.g: socket, file)
+pub fn read_compact_witnesses<R: io::Read>(mut reader: R) -> Result<Vec<Witness>, IronfishError> {
+    let version = reader.read_u8()?;
+    if version != COMPACT_WITNESS_VERSION {
+        return Err(IronfishError::InvalidData);
+    }
+
+    // Counts come from the sender, so nothing is reserved for them up front
+    let root_count = reader.read_u32::<LittleEndian>()?;
+    let mut roots = vec![];
+    for _ in 0..root_count {
+        let tree_size = TreeSize::new(reader.read_u32::<LittleEndian>()?);
+        let root_hash = read_scalar(&mut reader)?;
+        roots.push((tree_size, root_hash));
+    }
+
+    let mut previous: Vec<Option<usize>> = vec![None; roots.len()];
+    let witness_count = reader.read_u32::<LittleEndian>()?;
+    let mut witnesses: Vec<Witness> = vec![];
+    for _ in 0..witness_count {
+        let root_index = reader.read_u32::<LittleEndian>()? as usize;
+        let (tree_size, root_hash) = *roots.get(root_index).ok_or(IronfishError::InvalidData)?;
+
+        let mut sides = [0u8; PATH_BITS_SIZE];
+        reader.read_exact(&mut sides)?;
+        let mut reused = [0u8; PATH_BITS_SIZE];
+        reader.read_exact(&mut reused)?;
+
+        let mut auth_path = Vec::with_capacity(TREE_DEPTH);
+        for level in 0..TREE_DEPTH {
+            let sibling = match reused[level / 8] & (1 << (level % 8)) != 0 {
+                true => {
+                    let previous = previous[root_index].ok_or(IronfishError::InvalidData)?;
+                    *sibling_of(&witnesses[previous].auth_path[level])
+                }
+                false => read_scalar(&mut reader)?,
+            };
+
+            auth_path.push(match sides[level / 8] & (1 << (level % 8)) != 0 {
+                true => WitnessNode::Right(sibling),
+                false => WitnessNode::Left(sibling),
+            });
+        }
+
+        previous[root_index] = Some(witnesses.len());
+        witnesses.push(Witness {
+            tree_size,
+            root_hash,
+            auth_path,
+        });
+    }
+
+    Ok(witnesses)
+}
+
+fn sibling_of(node: &WitnessNode<Scalar>) -> &Scalar {
+    match node {
+        WitnessNode::Left(sibling) | WitnessNode::Right(sibling) => sibling,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_compact_witnesses, write_compact_witnesses};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, tree_position::NotePosition, witness::Witness,
+        MerkleNoteHash,
+    };
+    use bls12_381::Scalar;
+    use rand::{thread_rng, Rng};
+
+    /// Naive encoding: tree size, root hash, and a side byte and sibling per
+    /// level
+    const NAIVE_WITNESS_SIZE: usize = 4 + 32 + 32 * 33;
+
+    #[test]
+    fn test_compact_witnesses_round_trip() {
+        let leaves: Vec<MerkleNoteHash> = (0..100)
+            .map(|_| MerkleNoteHash(Scalar::from(thread_rng().gen::<u64>())))
+            .collect();
+        let positions: Vec<NotePosition> = (90..100).map(NotePosition::new).collect();
+        let mut witnesses = Witness::build_many(&leaves, &positions).unwrap();
+
+        // A witness for another tree in the middle of the batch
+        let address = SaplingKey::generate_key().public_address();
+        let note = Note::new(address, 1, "", NATIVE_ASSET, address);
+        witnesses.insert(5, make_fake_witness(&note));
+
+        let mut serialized = vec![];
+        write_compact_witnesses(&witnesses, &mut serialized).unwrap();
+        assert!(serialized.len() * 3 < witnesses.len() * NAIVE_WITNESS_SIZE);
+
+        let read_back = read_compact_witnesses(&serialized[..]).unwrap();
+        assert!(read_back == witnesses);
+
+        // Truncated batches are rejected rather than read short
+        assert!(read_compact_witnesses(&serialized[..serialized.len() - 1]).is_err());
+
+        let mut short = make_fake_witness(&note);
+        short.auth_path.pop();
+        assert!(matches!(
+            write_compact_witnesses(&[short], vec![]),
+            Err(IronfishError::InconsistentWitness)
+        ));
+    }
+}
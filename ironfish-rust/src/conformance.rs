@@ -0,0 +1,431 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Test vectors generated by this implementation, so that the TypeScript
+//! bindings and third party implementations can check themselves against it
+//! automatically.
+//!
+//! The inputs are derived from fixed seeds, so the suite is the same every
+//! time it's generated, and any change to it reflects a change in behavior.
+//! Every byte string is hex encoded, and scalars and points are in the same
+//! encoding transactions use. [`ConformanceSuite::write_to`] writes each
+//! section to its own JSON file:
+//!
+//! - `keys.json`: keys and addresses derived from spending keys, per network
+//! - `hashing.json`: merkle tree hashes, asset identifiers and nullifiers
+//! - `commitments.json`: note and value commitments
+//! - `encryption.json`: encrypted outputs, with the ephemeral key and value
+//!   commitment randomness used to create them
+
+use std::{fs, io, path::Path};
+
+use blake2b_simd::Params as Blake2b;
+use bls12_381::Scalar;
+use group::GroupEncoding;
+use ironfish_zkp::{constants::TREE_DEPTH, primitives::ValueCommitment};
+use serde::Serialize;
+
+use crate::{
+    assets::{
+        asset::Asset,
+        asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+    },
+    errors::IronfishError,
+    keys::{shared_secret, EphemeralKeyPair, SaplingKey},
+    merkle_note::MerkleNote,
+    network::NetworkKind,
+    note::Note,
+    serializing::bytes_to_hex,
+    tree_position::NotePosition,
+    MerkleNoteHash,
+};
+
+/// Incremented whenever the layout of the suite changes
+pub const CONFORMANCE_SUITE_VERSION: u8 = 1;
+
+const SEED_PERSONALIZATION: &[u8; 16] = b"IF_Conformance__";
+
+/// Number of vectors of each kind, and of keys per network
+const VECTORS_PER_KIND: u8 = 4;
+
+const NETWORKS: [NetworkKind; 2] = [NetworkKind::Mainnet, NetworkKind::Testnet];
+
+/// Keys derived from a single spending key on one network
+#[derive(Clone, Debug, Serialize)]
+pub struct KeyVector {
+    /// The network as serialized in keys and accounts
+    pub network: u8,
+    pub spending_key: String,
+    pub authorizing_key: String,
+    pub nullifier_deriving_key: String,
+    pub incoming_view_key: String,
+    pub outgoing_view_key: String,
+    pub public_address: String,
+    pub encoded_public_address: String,
+}
+
+/// Hash of two nodes at the given depth of the note commitment tree
+#[derive(Clone, Debug, Serialize)]
+pub struct MerkleHashVector {
+    pub depth: usize,
+    pub left: String,
+    pub right: String,
+    pub hash: String,
+}
+
+/// Identifier of an asset, from its owner, its padded name and metadata and
+/// the nonce found while deriving it
+#[derive(Clone, Debug, Serialize)]
+pub struct AssetIdentifierVector {
+    pub network: u8,
+    pub owner: String,
+    pub name: String,
+    pub metadata: String,
+    pub nonce: u8,
+    pub asset_id: String,
+    pub asset_generator: String,
+}
+
+/// The fields of a note, as hashed into its commitment and encrypted in
+/// outputs
+#[derive(Clone, Debug, Serialize)]
+pub struct NoteFields {
+    pub owner: String,
+    pub value: u64,
+    pub memo: String,
+    pub asset_id: String,
+    pub randomness: String,
+    pub sender: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NullifierVector {
+    /// The view key of the owner, as exported by wallets
+    pub view_key: String,
+    pub note: NoteFields,
+    pub position: u64,
+    pub nullifier: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct HashingVectors {
+    pub merkle_hashes: Vec<MerkleHashVector>,
+    pub asset_identifiers: Vec<AssetIdentifierVector>,
+    pub nullifiers: Vec<NullifierVector>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct NoteCommitmentVector {
+    pub note: NoteFields,
+    pub commitment: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ValueCommitmentVector {
+    pub value: u64,
+    pub randomness: String,
+    pub asset_id: String,
+    pub commitment: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct CommitmentVectors {
+    pub notes: Vec<NoteCommitmentVector>,
+    pub values: Vec<ValueCommitmentVector>,
+}
+
+/// An output encrypted by the sender for the owner of the note. The serialized
+/// merkle note includes the note encrypted to the owner and the encryption
+/// keys encrypted to the sender's outgoing view key.
+#[derive(Clone, Debug, Serialize)]
+pub struct NoteEncryptionVector {
+    pub sender_spending_key: String,
+    pub note: NoteFields,
+    pub ephemeral_secret_key: String,
+    pub value_commitment_randomness: String,
+    pub shared_secret: String,
+    pub merkle_note: String,
+}
+
+/// Every vector of the suite, on mainnet unless a vector names its network
+#[derive(Clone, Debug, Serialize)]
+pub struct ConformanceSuite {
+    pub version: u8,
+    pub keys: Vec<KeyVector>,
+    pub hashing: HashingVectors,
+    pub commitments: CommitmentVectors,
+    pub encryption: Vec<NoteEncryptionVector>,
+}
+
+/// Contents of each file written by [`ConformanceSuite::write_to`]
+#[derive(Serialize)]
+struct SuiteFile<'a, T: Serialize> {
+    version: u8,
+    vectors: &'a T,
+}
+
+impl ConformanceSuite {
+    /// Generate the suite. Only fails if this implementation can't derive
+    /// keys or assets from its own inputs.
+    pub fn generate() -> Result<Self, IronfishError> {
+        let mut keys = vec![];
+        for network in NETWORKS {
+            for index in 0..VECTORS_PER_KIND {
+                keys.push(key_vector(&spending_key(index, network)?));
+            }
+        }
+
+        let mut merkle_hashes = vec![];
+        let mut asset_identifiers = vec![];
+        let mut nullifiers = vec![];
+        let mut notes = vec![];
+        let mut values = vec![];
+        let mut encryption = vec![];
+
+        for index in 0..VECTORS_PER_KIND {
+            let depth = index as usize * 10 % TREE_DEPTH;
+            let left = scalar("merkle left", index);
+            let right = scalar("merkle right", index);
+            merkle_hashes.push(MerkleHashVector {
+                depth,
+                left: bytes_to_hex(&left.to_bytes()),
+                right: bytes_to_hex(&right.to_bytes()),
+                hash: bytes_to_hex(&MerkleNoteHash::combine_hash(depth, &left, &right).to_bytes()),
+            });
+
+            for network in NETWORKS {
+                let owner = spending_key(index, network)?.public_address();
+                let asset = Asset::new_for_network(
+                    owner,
+                    &format!("asset {}", index),
+                    &format!("conformance {}", index),
+                    network,
+                )?;
+                asset_identifiers.push(AssetIdentifierVector {
+                    network: network.as_u8(),
+                    owner: bytes_to_hex(&asset.owner()),
+                    name: bytes_to_hex(asset.name()),
+                    metadata: bytes_to_hex(asset.metadata()),
+                    nonce: asset.nonce(),
+                    asset_id: bytes_to_hex(asset.id().as_bytes()),
+                    asset_generator: bytes_to_hex(&asset.asset_generator().to_bytes()),
+                });
+            }
+
+            let owner_key = spending_key(index, NetworkKind::Mainnet)?;
+            let sender_key = spending_key(index + VECTORS_PER_KIND, NetworkKind::Mainnet)?;
+            let note = make_note(index, &owner_key, &sender_key)?;
+
+            let position = u64::from_le_bytes(seed("position", index)[..8].try_into().unwrap());
+            nullifiers.push(NullifierVector {
+                view_key: owner_key.view_key().hex_key(),
+                note: note_fields(&note),
+                position,
+                nullifier: bytes_to_hex(
+                    &note
+                        .nullifier(owner_key.view_key(), NotePosition::new(position))
+                        .0,
+                ),
+            });
+
+            notes.push(NoteCommitmentVector {
+                note: note_fields(&note),
+                commitment: bytes_to_hex(&note.commitment()),
+            });
+
+            let mut value_commitment =
+                ValueCommitment::new(note.value, note.asset_generator().point());
+            value_commitment.randomness = fr("value commitment randomness", index);
+            values.push(ValueCommitmentVector {
+                value: value_commitment.value,
+                randomness: bytes_to_hex(&value_commitment.randomness.to_bytes()),
+                asset_id: bytes_to_hex(note.asset_id.as_bytes()),
+                commitment: bytes_to_hex(&value_commitment.commitment().to_bytes()),
+            });
+
+            let ephemeral_keys = EphemeralKeyPair::from_secret(fr("ephemeral secret", index));
+            let merkle_note =
+                MerkleNote::new(&sender_key, &note, &value_commitment, &ephemeral_keys);
+            encryption.push(NoteEncryptionVector {
+                sender_spending_key: sender_key.hex_spending_key(),
+                note: note_fields(&note),
+                ephemeral_secret_key: bytes_to_hex(&ephemeral_keys.secret().to_bytes()),
+                value_commitment_randomness: bytes_to_hex(&value_commitment.randomness.to_bytes()),
+                shared_secret: bytes_to_hex(&shared_secret(
+                    ephemeral_keys.secret(),
+                    &note.owner.transmission_key,
+                    ephemeral_keys.public(),
+                )),
+                merkle_note: bytes_to_hex(&merkle_note.to_bytes()),
+            });
+        }
+
+        Ok(ConformanceSuite {
+            version: CONFORMANCE_SUITE_VERSION,
+            keys,
+            hashing: HashingVectors {
+                merkle_hashes,
+                asset_identifiers,
+                nullifiers,
+            },
+            commitments: CommitmentVectors { notes, values },
+            encryption,
+        })
+    }
+
+    /// Write each section of the suite to its own JSON file in the given
+    /// directory, creating it if needed
+    pub fn write_to(&self, directory: &Path) -> Result<(), IronfishError> {
+        fs::create_dir_all(directory)?;
+
+        write_file(directory, "keys.json", &self.keys)?;
+        write_file(directory, "hashing.json", &self.hashing)?;
+        write_file(directory, "commitments.json", &self.commitments)?;
+        write_file(directory, "encryption.json", &self.encryption)?;
+
+        Ok(())
+    }
+}
+
+fn write_file<T: Serialize>(
+    directory: &Path,
+    name: &str,
+    vectors: &T,
+) -> Result<(), IronfishError> {
+    let file = SuiteFile {
+        version: CONFORMANCE_SUITE_VERSION,
+        vectors,
+    };
+    let mut json = serde_json::to_vec_pretty(&file).map_err(io::Error::from)?;
+    json.push(b'\n');
+
+    fs::write(directory.join(name), json)?;
+
+    Ok(())
+}
+
+/// Bytes derived from the label and index, the source of every input
+fn seed(label: &str, index: u8) -> [u8; 64] {
+    let hash = Blake2b::new()
+        .hash_length(64)
+        .personal(SEED_PERSONALIZATION)
+        .to_state()
+        .update(label.as_bytes())
+        .update(&[index])
+        .finalize();
+
+    let mut bytes = [0; 64];
+    bytes.copy_from_slice(hash.as_bytes());
+    bytes
+}
+
+fn scalar(label: &str, index: u8) -> Scalar {
+    Scalar::from_bytes_wide(&seed(label, index))
+}
+
+fn fr(label: &str, index: u8) -> jubjub::Fr {
+    jubjub::Fr::from_bytes_wide(&seed(label, index))
+}
+
+fn spending_key(index: u8, network: NetworkKind) -> Result<SaplingKey, IronfishError> {
+    let mut spending_key = [0; 32];
+    spending_key.copy_from_slice(&seed("spending key", index)[..32]);
+
+    SaplingKey::new_for_network(spending_key, network)
+}
+
+/// A note of the native asset for even indices and of a custom asset for odd
+/// ones, with randomness from the seed
+fn make_note(
+    index: u8,
+    owner_key: &SaplingKey,
+    sender_key: &SaplingKey,
+) -> Result<Note, IronfishError> {
+    let asset_id: AssetIdentifier = match index % 2 {
+        0 => NATIVE_ASSET,
+        _ => *Asset::new(sender_key.public_address(), "conformance", "")?.id(),
+    };
+    let value = u64::from_le_bytes(seed("note value", index)[..8].try_into().unwrap());
+
+    let mut note = Note::new(
+        owner_key.public_address(),
+        value,
+        format!("conformance note {}", index).as_str(),
+        asset_id,
+        sender_key.public_address(),
+    );
+    note.randomness = fr("note randomness", index);
+
+    Ok(note)
+}
+
+fn note_fields(note: &Note) -> NoteFields {
+    NoteFields {
+        owner: note.owner.hex_public_address(),
+        value: note.value,
+        memo: bytes_to_hex(&note.memo.0),
+        asset_id: bytes_to_hex(note.asset_id.as_bytes()),
+        randomness: bytes_to_hex(&note.randomness.to_bytes()),
+        sender: note.sender.hex_public_address(),
+    }
+}
+
+fn key_vector(key: &SaplingKey) -> KeyVector {
+    let address = key.public_address();
+
+    KeyVector {
+        network: key.network().as_u8(),
+        spending_key: key.hex_spending_key(),
+        authorizing_key: bytes_to_hex(&key.view_key().authorizing_key.to_bytes()),
+        nullifier_deriving_key: bytes_to_hex(&key.view_key().nullifier_deriving_key.to_bytes()),
+        incoming_view_key: key.incoming_view_key().hex_key(),
+        outgoing_view_key: key.outgoing_view_key().hex_key(),
+        public_address: address.hex_public_address(),
+        encoded_public_address: address.encode(key.network()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{spending_key, ConformanceSuite, VECTORS_PER_KIND};
+    use crate::{merkle_note::MerkleNote, network::NetworkKind, serializing::hex_to_vec_bytes};
+
+    #[test]
+    fn test_suite_is_reproducible() {
+        let suite = ConformanceSuite::generate().unwrap();
+        let again = ConformanceSuite::generate().unwrap();
+        assert_eq!(
+            serde_json::to_string(&suite).unwrap(),
+            serde_json::to_string(&again).unwrap()
+        );
+
+        assert_eq!(suite.keys.len(), 2 * VECTORS_PER_KIND as usize);
+        assert_ne!(suite.keys[0].public_address, suite.keys[4].public_address);
+
+        // The owner can read every encrypted output
+        for (index, vector) in suite.encryption.iter().enumerate() {
+            let owner_key = spending_key(index as u8, NetworkKind::Mainnet).unwrap();
+            let bytes = hex_to_vec_bytes(&vector.merkle_note).unwrap();
+            let merkle_note = MerkleNote::read(&bytes[..]).unwrap();
+            let note = merkle_note
+                .decrypt_note_for_owner(owner_key.incoming_view_key())
+                .unwrap();
+            assert_eq!(note.value, vector.note.value);
+        }
+
+        let directory =
+            std::env::temp_dir().join(format!("ironfish-conformance-{}", std::process::id()));
+        suite.write_to(&directory).unwrap();
+        for name in [
+            "keys.json",
+            "hashing.json",
+            "commitments.json",
+            "encryption.json",
+        ] {
+            let contents = std::fs::read_to_string(directory.join(name)).unwrap();
+            assert!(contents.starts_with("{\n  \"version\": 1,"));
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
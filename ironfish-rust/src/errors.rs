@@ -8,6 +8,15 @@ use std::io;
 use std::num;
 use std::string;
 
+use crate::{
+    screening::ScreeningRejection,
+    transaction::{
+        anchors::AnchorRejection, issuance::MintRejection, signing_context::SpendingLimitViolation,
+    },
+    value_range::ValueError,
+    witness::WitnessCheckFailure,
+};
+
 /// Error type to handle all errors within the code and dependency-raised
 /// errors. This serves 2 purposes. The first is to keep a consistent error type
 /// in the code to reduce the cognitive load needed for using Result and Error
@@ -17,33 +26,68 @@ use std::string;
 pub enum IronfishError {
     BellmanSynthesis(bellman::SynthesisError),
     BellmanVerification(bellman::VerificationError),
+    BurnExceedsBalance,
+    BurnValueOverflow,
     CryptoBox(crypto_box::aead::Error),
+    ExtraDataTooLarge,
+    FeeTooLow,
     IllegalValue,
     InconsistentWitness,
     InvalidAssetIdentifier,
+    InvalidAssetIdentifierEncoding,
+    InvalidAssetRegistry,
     InvalidAuthorizingKey,
     InvalidBalance,
     InvalidCommitment,
+    InvalidConversion,
     InvalidData,
     InvalidDecryptionKey,
     InvalidDiversificationPoint,
     InvalidEntropy,
+    InvalidEqualityProof,
+    InvalidInclusionProof,
+    InvalidKeyShare,
     InvalidLanguageEncoding,
     InvalidMinersFeeTransaction,
     InvalidMnemonicString,
+    InvalidNetwork,
     InvalidNonceLength,
     InvalidNullifierDerivingKey,
+    InvalidParamsChecksum,
     InvalidPaymentAddress,
+    InvalidPaymentProof,
     InvalidPublicAddress,
+    InvalidSignatureDomain,
     InvalidSigningKey,
     InvalidTransaction,
     InvalidTransactionVersion,
+    InvalidTreeId,
+    InvalidTreeSnapshot,
+    InvalidValue(ValueError),
     InvalidViewingKey,
     InvalidWord,
     Io(io::Error),
     IsSmallOrder,
+    MemoForbidden,
+    MemoRequired,
+    MemoTooLarge,
+    OverlappingParameterSets,
+    ParamsDownloadFailed,
+    ProvingCancelled,
+    ProvingDeadlineMissed,
     RandomnessError,
+    RejectedAnchor(AnchorRejection),
+    RejectedMints(Vec<MintRejection>),
+    SaplingAlreadyLoaded,
+    ScreeningRejected(ScreeningRejection),
+    SpendingLimitExceeded(SpendingLimitViolation),
+    TooManyBurns,
+    TooManyMints,
+    TooManyOutputs,
+    TooManySpends,
+    TransactionTooLarge,
     TryFromInt(num::TryFromIntError),
+    UnknownScanClient,
     Utf8(string::FromUtf8Error),
     VerificationFailed,
 }
@@ -91,3 +135,15 @@ impl From<num::TryFromIntError> for IronfishError {
         IronfishError::TryFromInt(e)
     }
 }
+
+impl From<ValueError> for IronfishError {
+    fn from(e: ValueError) -> IronfishError {
+        IronfishError::InvalidValue(e)
+    }
+}
+
+impl From<WitnessCheckFailure> for IronfishError {
+    fn from(_: WitnessCheckFailure) -> IronfishError {
+        IronfishError::InconsistentWitness
+    }
+}
@@ -0,0 +1,139 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Well-known accounts and assets for integration tests, so that the wallet,
+//! the node, explorers and the bindings all test against the same ones.
+//!
+//! The spending key of a fixture account is the BLAKE2b-256 hash of its
+//! name, personalized with [`FIXTURE_PERSONALIZATION`], expanded for
+//! mainnet. Fixture assets are created with [`Asset::new`] by one of the
+//! accounts, from a fixed name and metadata. Any implementation can derive
+//! them from that description alone, and their keys, addresses and
+//! identifiers never change.
+//!
+//! Everyone knows these keys: never send anything of value to them.
+
+use blake2b_simd::Params as Blake2b;
+
+use crate::{
+    assets::{asset::Asset, asset_identifier::AssetIdentifier},
+    keys::{PublicAddress, SaplingKey, SPEND_KEY_SIZE},
+};
+
+pub use crate::personalization::FIXTURE_PERSONALIZATION;
+
+/// Names of the fixture accounts, in the order tests usually give them
+/// parts: a sender, a recipient and a third party
+pub const FIXTURE_ACCOUNTS: [&str; 3] = ["alice", "bob", "carol"];
+
+/// An asset created by a fixture account
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FixtureAsset {
+    /// Name of the fixture account that owns the asset
+    pub owner: &'static str,
+    pub name: &'static str,
+    pub metadata: &'static str,
+}
+
+/// The fixture assets, two of them owned by the same account for tests of
+/// transactions with several custom assets
+pub const FIXTURE_ASSETS: [FixtureAsset; 3] = [
+    FixtureAsset {
+        owner: "alice",
+        name: "Fixture Coin",
+        metadata: "",
+    },
+    FixtureAsset {
+        owner: "alice",
+        name: "Fixture Token",
+        metadata: "fixture metadata",
+    },
+    FixtureAsset {
+        owner: "bob",
+        name: "Fixture Bond",
+        metadata: "",
+    },
+];
+
+/// The spending key a fixture account with the given name would have. Only
+/// the names in [`FIXTURE_ACCOUNTS`] are shared, other names can be used
+/// for accounts of a single test suite.
+pub fn fixture_spending_key(name: &str) -> [u8; SPEND_KEY_SIZE] {
+    let mut spending_key = [0; SPEND_KEY_SIZE];
+    spending_key.copy_from_slice(
+        Blake2b::new()
+            .hash_length(SPEND_KEY_SIZE)
+            .personal(FIXTURE_PERSONALIZATION)
+            .hash(name.as_bytes())
+            .as_bytes(),
+    );
+
+    spending_key
+}
+
+/// The key of the fixture account with the given name
+pub fn fixture_key(name: &str) -> SaplingKey {
+    SaplingKey::new(fixture_spending_key(name)).expect("fixture spending keys are valid")
+}
+
+pub fn alice() -> SaplingKey {
+    fixture_key("alice")
+}
+
+pub fn bob() -> SaplingKey {
+    fixture_key("bob")
+}
+
+pub fn carol() -> SaplingKey {
+    fixture_key("carol")
+}
+
+impl FixtureAsset {
+    pub fn owner_address(&self) -> PublicAddress {
+        fixture_key(self.owner).public_address()
+    }
+
+    pub fn asset(&self) -> Asset {
+        Asset::new(self.owner_address(), self.name, self.metadata)
+            .expect("fixture assets have valid names")
+    }
+
+    pub fn id(&self) -> AssetIdentifier {
+        *self.asset().id()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{fixture_key, fixture_spending_key, FIXTURE_ACCOUNTS, FIXTURE_ASSETS};
+    use crate::serializing::bytes_to_hex;
+
+    #[test]
+    fn test_fixture_keys_are_stable() {
+        // Pinned, so that changing how fixtures are derived breaks this test
+        // rather than the integration tests of every other repository
+        let spending_keys = [
+            "060290039ec563f58e90372290c68bad657b659ba32b7acf20a91157b69640d1",
+            "93a6fbbcbf320163bc248c861a3392264a5b3a2dd6729b76bd744824c81849bc",
+            "40b64b3e98880ec1ad38baa7168b71b38f90b515ac15cf02efa1ca1aab842520",
+        ];
+        for (name, spending_key) in FIXTURE_ACCOUNTS.iter().zip(spending_keys) {
+            assert_eq!(bytes_to_hex(&fixture_spending_key(name)), spending_key);
+            assert_eq!(fixture_key(name).hex_spending_key(), spending_key);
+        }
+    }
+
+    #[test]
+    fn test_fixture_assets() {
+        for (i, fixture) in FIXTURE_ASSETS.iter().enumerate() {
+            let asset = fixture.asset();
+            assert_eq!(*asset.id(), fixture.id());
+            assert_eq!(asset.owner, fixture.owner_address());
+
+            for other in FIXTURE_ASSETS[i + 1..].iter() {
+                assert_ne!(fixture.id(), other.id());
+            }
+        }
+    }
+}
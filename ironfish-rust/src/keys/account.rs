@@ -0,0 +1,220 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use group::GroupEncoding;
+use jubjub::SubgroupPoint;
+
+use crate::{errors::IronfishError, network::NetworkKind, serializing::read_scalar};
+
+use super::{IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey, ViewKey, SPEND_KEY_SIZE};
+
+pub const ACCOUNT_EXPORT_VERSION: u8 = 1;
+
+/// Sequence of the genesis block, where scanning starts for accounts without a
+/// birthday
+pub const GENESIS_BLOCK_SEQUENCE: u32 = 1;
+
+/// Keys of an exported account. View only accounts can find and decrypt
+/// their notes, but can't spend them.
+#[derive(Clone)]
+pub enum AccountKeys {
+    Spending(SaplingKey),
+    View {
+        view_key: ViewKey,
+        incoming_view_key: IncomingViewKey,
+        outgoing_view_key: OutgoingViewKey,
+    },
+}
+
+/// An account as moved between wallets, with an optional birthday.
+///
+/// The birthday is the sequence of a block created before the account was,
+/// so nothing before it can hold notes for the account and rescans can start
+/// there instead of at genesis. It's a hint from whoever exported the
+/// account, and a wrong birthday only means missing notes from before it.
+#[derive(Clone)]
+pub struct AccountExport {
+    pub keys: AccountKeys,
+    pub network: NetworkKind,
+    pub birthday: Option<u32>,
+}
+
+impl AccountExport {
+    /// Export an account with its spending key
+    pub fn new(key: SaplingKey, birthday: Option<u32>) -> Self {
+        AccountExport {
+            network: key.network(),
+            keys: AccountKeys::Spending(key),
+            birthday,
+        }
+    }
+
+    /// Export only the view keys of an account
+    pub fn view_only(key: &SaplingKey, birthday: Option<u32>) -> Self {
+        AccountExport {
+            network: key.network(),
+            keys: AccountKeys::View {
+                view_key: key.view_key().clone(),
+                incoming_view_key: key.incoming_view_key().clone(),
+                outgoing_view_key: key.outgoing_view_key().clone(),
+            },
+            birthday,
+        }
+    }
+
+    pub fn is_view_only(&self) -> bool {
+        matches!(self.keys, AccountKeys::View { .. })
+    }
+
+    pub fn public_address(&self) -> PublicAddress {
+        match &self.keys {
+            AccountKeys::Spending(key) => key.public_address(),
+            AccountKeys::View {
+                incoming_view_key, ..
+            } => incoming_view_key.public_address(),
+        }
+    }
+
+    pub fn incoming_view_key(&self) -> &IncomingViewKey {
+        match &self.keys {
+            AccountKeys::Spending(key) => key.incoming_view_key(),
+            AccountKeys::View {
+                incoming_view_key, ..
+            } => incoming_view_key,
+        }
+    }
+
+    pub fn outgoing_view_key(&self) -> &OutgoingViewKey {
+        match &self.keys {
+            AccountKeys::Spending(key) => key.outgoing_view_key(),
+            AccountKeys::View {
+                outgoing_view_key, ..
+            } => outgoing_view_key,
+        }
+    }
+
+    pub fn birthday(&self) -> Option<u32> {
+        self.birthday
+    }
+
+    /// Sequence of the first block a rescan of this account has to look at
+    pub fn scan_start(&self) -> u32 {
+        self.birthday
+            .unwrap_or(GENESIS_BLOCK_SEQUENCE)
+            .max(GENESIS_BLOCK_SEQUENCE)
+    }
+
+    /// Whether the block with the given sequence could hold notes for this
+    /// account, according to its birthday
+    pub fn should_scan(&self, sequence: u32) -> bool {
+        sequence >= self.scan_start()
+    }
+
+    /// Load an [`AccountExport`] from a Read implementation (e.g: socket,
+    /// file). The view keys of view only accounts are checked against each
+    /// other.
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != ACCOUNT_EXPORT_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let network = NetworkKind::try_from(reader.read_u8()?)?;
+
+        let keys = match reader.read_u8()? {
+            0 => {
+                let mut spending_key = [0; SPEND_KEY_SIZE];
+                reader.read_exact(&mut spending_key)?;
+                AccountKeys::Spending(SaplingKey::new_for_network(spending_key, network)?)
+            }
+            1 => {
+                let authorizing_key =
+                    read_point(&mut reader, IronfishError::InvalidAuthorizingKey)?;
+                let nullifier_deriving_key =
+                    read_point(&mut reader, IronfishError::InvalidNullifierDerivingKey)?;
+                let incoming_view_key = IncomingViewKey {
+                    view_key: read_scalar(&mut reader)?,
+                };
+                let mut outgoing_view_key = OutgoingViewKey { view_key: [0; 32] };
+                reader.read_exact(&mut outgoing_view_key.view_key)?;
+
+                let expected_view_key =
+                    SaplingKey::hash_viewing_key(&authorizing_key, &nullifier_deriving_key)?;
+                if expected_view_key != incoming_view_key.view_key {
+                    return Err(IronfishError::InvalidViewingKey);
+                }
+
+                AccountKeys::View {
+                    view_key: ViewKey {
+                        authorizing_key,
+                        nullifier_deriving_key,
+                    },
+                    incoming_view_key,
+                    outgoing_view_key,
+                }
+            }
+            _ => return Err(IronfishError::InvalidData),
+        };
+
+        let birthday = match reader.read_u8()? {
+            0 => None,
+            1 => Some(reader.read_u32::<LittleEndian>()?),
+            _ => return Err(IronfishError::InvalidData),
+        };
+
+        Ok(AccountExport {
+            keys,
+            network,
+            birthday,
+        })
+    }
+
+    /// Stow the bytes of this [`AccountExport`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(ACCOUNT_EXPORT_VERSION)?;
+        writer.write_u8(self.network.as_u8())?;
+
+        match &self.keys {
+            AccountKeys::Spending(key) => {
+                writer.write_u8(0)?;
+                writer.write_all(&key.spending_key())?;
+            }
+            AccountKeys::View {
+                view_key,
+                incoming_view_key,
+                outgoing_view_key,
+            } => {
+                writer.write_u8(1)?;
+                writer.write_all(&view_key.to_bytes())?;
+                writer.write_all(&incoming_view_key.view_key.to_bytes())?;
+                writer.write_all(&outgoing_view_key.view_key)?;
+            }
+        }
+
+        match self.birthday {
+            None => writer.write_u8(0)?,
+            Some(birthday) => {
+                writer.write_u8(1)?;
+                writer.write_u32::<LittleEndian>(birthday)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Read a point of the prime order subgroup, failing with `invalid` if the
+/// bytes don't encode one
+fn read_point<R: io::Read>(
+    reader: &mut R,
+    invalid: IronfishError,
+) -> Result<SubgroupPoint, IronfishError> {
+    let mut bytes = [0; 32];
+    reader.read_exact(&mut bytes)?;
+
+    Option::from(SubgroupPoint::from_bytes(&bytes)).ok_or(invalid)
+}
@@ -0,0 +1,165 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! View keys scoped to a single asset.
+//!
+//! Every account can derive a separate key for each asset, with its own
+//! address. Notes of the asset are sent to, and change is kept at, the asset
+//! address, so they're encrypted to the asset key rather than to the account.
+//! The view keys of the asset key can be handed to an auditor, who can then
+//! read the flows of that asset without being able to derive the account's
+//! keys, or the keys of any other asset.
+//!
+//! An asset key is a full spending key, and it can hold other assets too,
+//! like the native coins it pays fees with. An [`AssetViewKey`] only
+//! decrypts notes of its asset, so those stay hidden from the auditor.
+
+use super::{IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey, SPEND_KEY_SIZE};
+use crate::{
+    assets::asset_identifier::AssetIdentifier,
+    errors::IronfishError,
+    note::Note,
+    personalization::ASSET_KEY_PERSONALIZATION,
+    serializing::{bytes_to_hex, hex_to_vec_bytes},
+    MerkleNote,
+};
+use blake2b_simd::Params as Blake2b;
+
+use std::io;
+
+/// Size of a serialized [`AssetViewKey`]: asset id, incoming view key and
+/// outgoing view key.
+pub const ASSET_VIEW_KEY_SIZE: usize = 32 + 32 + 32;
+
+impl SaplingKey {
+    /// Derive the key that holds the given asset for this account. The
+    /// derivation is one way, so neither the asset key nor its view keys
+    /// reveal anything about this key.
+    pub fn derive_asset_key(
+        &self,
+        asset_id: &AssetIdentifier,
+    ) -> Result<SaplingKey, IronfishError> {
+        let mut spending_key = [0; SPEND_KEY_SIZE];
+        spending_key.copy_from_slice(
+            Blake2b::new()
+                .hash_length(SPEND_KEY_SIZE)
+                .personal(ASSET_KEY_PERSONALIZATION)
+                .to_state()
+                .update(&self.spending_key())
+                .update(asset_id.as_bytes())
+                .finalize()
+                .as_bytes(),
+        );
+
+        SaplingKey::new_for_network(spending_key, self.network())
+    }
+
+    /// The address that notes of the given asset should be sent to, so that
+    /// they can be disclosed with [`SaplingKey::asset_view_key`].
+    pub fn asset_address(
+        &self,
+        asset_id: &AssetIdentifier,
+    ) -> Result<PublicAddress, IronfishError> {
+        Ok(self.derive_asset_key(asset_id)?.public_address())
+    }
+
+    /// The view key that discloses notes of the given asset, and only those.
+    pub fn asset_view_key(
+        &self,
+        asset_id: &AssetIdentifier,
+    ) -> Result<AssetViewKey, IronfishError> {
+        let asset_key = self.derive_asset_key(asset_id)?;
+
+        Ok(AssetViewKey {
+            asset_id: *asset_id,
+            incoming_view_key: asset_key.incoming_view_key().clone(),
+            outgoing_view_key: asset_key.outgoing_view_key().clone(),
+        })
+    }
+}
+
+/// Key that allows someone to view the notes of a single asset that an
+/// account received and sent, derived with [`SaplingKey::asset_view_key`].
+#[derive(Clone)]
+pub struct AssetViewKey {
+    asset_id: AssetIdentifier,
+    incoming_view_key: IncomingViewKey,
+    outgoing_view_key: OutgoingViewKey,
+}
+
+impl AssetViewKey {
+    /// Load an asset view key from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let asset_id = AssetIdentifier::read(&mut reader)?;
+        let incoming_view_key = IncomingViewKey::read(&mut reader)?;
+        let mut outgoing_view_key = [0; 32];
+        reader.read_exact(&mut outgoing_view_key)?;
+
+        Ok(AssetViewKey {
+            asset_id,
+            incoming_view_key,
+            outgoing_view_key: OutgoingViewKey {
+                view_key: outgoing_view_key,
+            },
+        })
+    }
+
+    /// Stow the bytes of this [`AssetViewKey`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        self.asset_id.write(&mut writer)?;
+        writer.write_all(&self.incoming_view_key.view_key.to_bytes())?;
+        writer.write_all(&self.outgoing_view_key.view_key)?;
+
+        Ok(())
+    }
+
+    /// Load a key from a string of hexadecimal digits
+    pub fn from_hex(value: &str) -> Result<Self, IronfishError> {
+        let bytes = hex_to_vec_bytes(value).map_err(|_| IronfishError::InvalidViewingKey)?;
+        if bytes.len() != ASSET_VIEW_KEY_SIZE {
+            return Err(IronfishError::InvalidViewingKey);
+        }
+
+        Self::read(&bytes[..])
+    }
+
+    /// Key as hexadecimal, for readability.
+    pub fn hex_key(&self) -> String {
+        let mut bytes = Vec::with_capacity(ASSET_VIEW_KEY_SIZE);
+        self.write(&mut bytes).unwrap();
+        bytes_to_hex(&bytes)
+    }
+
+    pub fn asset_id(&self) -> &AssetIdentifier {
+        &self.asset_id
+    }
+
+    /// The asset address notes are sent to
+    pub fn public_address(&self) -> PublicAddress {
+        self.incoming_view_key.public_address()
+    }
+
+    /// Decrypt a note of this key's asset that was sent to the asset address.
+    pub fn decrypt_note_for_owner(&self, merkle_note: &MerkleNote) -> Result<Note, IronfishError> {
+        let note = merkle_note.decrypt_note_for_owner(&self.incoming_view_key)?;
+        self.check_asset(note)
+    }
+
+    /// Decrypt a note of this key's asset that was sent from the asset
+    /// address.
+    pub fn decrypt_note_for_spender(
+        &self,
+        merkle_note: &MerkleNote,
+    ) -> Result<Note, IronfishError> {
+        let note = merkle_note.decrypt_note_for_spender(&self.outgoing_view_key)?;
+        self.check_asset(note)
+    }
+
+    fn check_asset(&self, note: Note) -> Result<Note, IronfishError> {
+        match note.asset_id == self.asset_id {
+            true => Ok(note),
+            false => Err(IronfishError::InvalidDecryptionKey),
+        }
+    }
+}
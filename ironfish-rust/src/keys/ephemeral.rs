@@ -1,9 +1,9 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
-use ff::Field;
 use ironfish_zkp::constants::PUBLIC_KEY_GENERATOR;
-use rand::thread_rng;
+
+use crate::{errors::IronfishError, randomness::random_scalar};
 
 /// Diffie Hellman key exchange pair as used in note encryption.
 ///
@@ -16,9 +16,20 @@ pub struct EphemeralKeyPair {
 }
 
 impl EphemeralKeyPair {
+    /// A fresh key pair. Panics if the randomness source fails, see
+    /// [`EphemeralKeyPair::try_new`].
     pub fn new() -> Self {
-        let secret = jubjub::Fr::random(thread_rng());
+        Self::try_new().expect("the randomness source failed")
+    }
+
+    /// A fresh key pair, failing if the randomness source does.
+    pub fn try_new() -> Result<Self, IronfishError> {
+        Ok(Self::from_secret(random_scalar()?))
+    }
 
+    /// The key pair with the given secret, for reproducing an encryption such
+    /// as in test vectors. Secrets must never be reused across notes.
+    pub fn from_secret(secret: jubjub::Fr) -> Self {
         Self {
             secret,
             public: PUBLIC_KEY_GENERATOR * secret,
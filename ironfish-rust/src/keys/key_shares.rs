@@ -0,0 +1,513 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Shamir secret sharing of spending keys, for keys kept in cold storage by
+//! several custodians, any `threshold` of whom can recover the key while
+//! fewer learn nothing about it.
+//!
+//! Every byte of the spending key is shared on its own, as the constant term
+//! of a random polynomial over GF(2^8) of degree `threshold - 1`, and each
+//! share holds the evaluations of the 32 polynomials at its index.
+//!
+//! Shares can be refreshed without recovering the key: each custodian
+//! taking part hands the others evaluations of a random polynomial whose
+//! constant term is zero, see [`KeyShare::refresh_updates`], and adding them
+//! all to a share, with [`KeyShare::apply_updates`], moves it to the next
+//! epoch. The key is unchanged, but shares of different epochs can't be
+//! combined, so shares stolen before a refresh are worthless once the
+//! custodians have refreshed theirs, as long as fewer than `threshold` were
+//! stolen in any one epoch.
+//!
+//! Shares carry a fingerprint of the key, a hash of its spending key, which
+//! tells shares of different keys apart and is checked against the
+//! recovered key. It can confirm a guess of the key, which is no easier than
+//! finding the key itself.
+
+use std::io;
+
+use blake2b_simd::Params as Blake2b;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rand::RngCore;
+
+use crate::{
+    errors::IronfishError, network::NetworkKind, personalization::KEY_SHARE_PERSONALIZATION,
+    randomness::with_rng,
+};
+
+use super::{SaplingKey, SPEND_KEY_SIZE};
+
+/// Serialization version of [`KeyShare`] and [`ShareUpdate`], bumped
+/// whenever the format changes
+pub const KEY_SHARE_VERSION: u8 = 1;
+
+pub const KEY_FINGERPRINT_SIZE: usize = 8;
+
+/// Size of a serialized [`KeyShare`]: version, network, fingerprint,
+/// threshold, index, epoch and the shared bytes
+pub const KEY_SHARE_SIZE: usize = 1 + 1 + KEY_FINGERPRINT_SIZE + 1 + 1 + 4 + SPEND_KEY_SIZE;
+
+/// Size of a serialized [`ShareUpdate`]: version, fingerprint, epoch, the
+/// index of the share it's from and the one it's for, and the update
+pub const SHARE_UPDATE_SIZE: usize = 1 + KEY_FINGERPRINT_SIZE + 4 + 1 + 1 + SPEND_KEY_SIZE;
+
+/// One custodian's share of a spending key
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyShare {
+    network: NetworkKind,
+    fingerprint: [u8; KEY_FINGERPRINT_SIZE],
+
+    /// Number of shares needed to recover the key
+    threshold: u8,
+
+    /// Where the polynomials are evaluated, never zero
+    index: u8,
+
+    /// Number of times the share was refreshed
+    epoch: u32,
+
+    value: [u8; SPEND_KEY_SIZE],
+}
+
+/// What one custodian adds to the share of another to refresh it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShareUpdate {
+    fingerprint: [u8; KEY_FINGERPRINT_SIZE],
+    epoch: u32,
+
+    /// Index of the share of the custodian who made the update
+    from: u8,
+
+    /// Index of the share the update is for
+    index: u8,
+
+    delta: [u8; SPEND_KEY_SIZE],
+}
+
+/// Split the spending key into `count` shares, any `threshold` of which
+/// recover it. Fails with [`IronfishError::InvalidKeyShare`] unless
+/// `2 <= threshold <= count`.
+pub fn split_spending_key(
+    key: &SaplingKey,
+    threshold: u8,
+    count: u8,
+) -> Result<Vec<KeyShare>, IronfishError> {
+    if threshold < 2 || threshold > count {
+        return Err(IronfishError::InvalidKeyShare);
+    }
+
+    let polynomials = random_polynomials(key.spending_key(), threshold)?;
+    let fingerprint = key_fingerprint(&key.spending_key());
+
+    Ok((1..=count)
+        .map(|index| KeyShare {
+            network: key.network(),
+            fingerprint,
+            threshold,
+            index,
+            epoch: 0,
+            value: evaluate(&polynomials, index),
+        })
+        .collect())
+}
+
+/// Recover the spending key from at least `threshold` shares of the same
+/// epoch. Fails with [`IronfishError::InvalidKeyShare`] if there are too
+/// few, if they don't belong together, or if the recovered key doesn't
+/// match their fingerprint.
+pub fn recover_spending_key(shares: &[KeyShare]) -> Result<SaplingKey, IronfishError> {
+    let first = shares.first().ok_or(IronfishError::InvalidKeyShare)?;
+    if shares.len() < usize::from(first.threshold) {
+        return Err(IronfishError::InvalidKeyShare);
+    }
+
+    for (i, share) in shares.iter().enumerate() {
+        let belongs = share.network == first.network
+            && share.fingerprint == first.fingerprint
+            && share.threshold == first.threshold
+            && share.epoch == first.epoch;
+        let is_repeated = shares[..i].iter().any(|other| other.index == share.index);
+        if !belongs || is_repeated {
+            return Err(IronfishError::InvalidKeyShare);
+        }
+    }
+
+    let spending_key = interpolate_at_zero(shares);
+    if key_fingerprint(&spending_key) != first.fingerprint {
+        return Err(IronfishError::InvalidKeyShare);
+    }
+
+    SaplingKey::new_for_network(spending_key, first.network)
+}
+
+impl KeyShare {
+    pub fn network(&self) -> NetworkKind {
+        self.network
+    }
+
+    pub fn fingerprint(&self) -> &[u8; KEY_FINGERPRINT_SIZE] {
+        &self.fingerprint
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn epoch(&self) -> u32 {
+        self.epoch
+    }
+
+    /// Updates for the shares with the given indexes, one for each, to
+    /// refresh them with. Every custodian taking part makes updates for all
+    /// of those taking part, itself included, and each applies the updates
+    /// it receives. A share missing the update of one of them recovers
+    /// another key, which [`recover_spending_key`] rejects.
+    pub fn refresh_updates(&self, indexes: &[u8]) -> Result<Vec<ShareUpdate>, IronfishError> {
+        for (i, index) in indexes.iter().enumerate() {
+            if *index == 0 || indexes[..i].contains(index) {
+                return Err(IronfishError::InvalidKeyShare);
+            }
+        }
+
+        let polynomials = random_polynomials([0; SPEND_KEY_SIZE], self.threshold)?;
+
+        Ok(indexes
+            .iter()
+            .map(|&index| ShareUpdate {
+                fingerprint: self.fingerprint,
+                epoch: self.epoch,
+                from: self.index,
+                index,
+                delta: evaluate(&polynomials, index),
+            })
+            .collect())
+    }
+
+    /// The share of the next epoch, adding the updates of every custodian
+    /// that took part in the refresh. Fails with
+    /// [`IronfishError::InvalidKeyShare`] if an update is for another share
+    /// or epoch, or if two are from the same custodian.
+    pub fn apply_updates(&self, updates: &[ShareUpdate]) -> Result<KeyShare, IronfishError> {
+        if updates.is_empty() {
+            return Err(IronfishError::InvalidKeyShare);
+        }
+
+        let mut value = self.value;
+        for (i, update) in updates.iter().enumerate() {
+            let is_for_this_share = update.fingerprint == self.fingerprint
+                && update.epoch == self.epoch
+                && update.index == self.index;
+            let is_repeated = updates[..i].iter().any(|other| other.from == update.from);
+            if !is_for_this_share || is_repeated {
+                return Err(IronfishError::InvalidKeyShare);
+            }
+
+            for (byte, delta) in value.iter_mut().zip(update.delta) {
+                *byte ^= delta;
+            }
+        }
+
+        let epoch = self
+            .epoch
+            .checked_add(1)
+            .ok_or(IronfishError::InvalidKeyShare)?;
+
+        Ok(KeyShare {
+            epoch,
+            value,
+            ..self.clone()
+        })
+    }
+
+    /// Load a [`KeyShare`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        if reader.read_u8()? != KEY_SHARE_VERSION {
+            return Err(IronfishError::InvalidKeyShare);
+        }
+
+        let network = NetworkKind::try_from(reader.read_u8()?)?;
+        let mut fingerprint = [0; KEY_FINGERPRINT_SIZE];
+        reader.read_exact(&mut fingerprint)?;
+        let threshold = reader.read_u8()?;
+        let index = reader.read_u8()?;
+        let epoch = reader.read_u32::<LittleEndian>()?;
+        let mut value = [0; SPEND_KEY_SIZE];
+        reader.read_exact(&mut value)?;
+
+        if threshold < 2 || index == 0 {
+            return Err(IronfishError::InvalidKeyShare);
+        }
+
+        Ok(KeyShare {
+            network,
+            fingerprint,
+            threshold,
+            index,
+            epoch,
+            value,
+        })
+    }
+
+    /// Stow the bytes of this [`KeyShare`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(KEY_SHARE_VERSION)?;
+        writer.write_u8(self.network.as_u8())?;
+        writer.write_all(&self.fingerprint)?;
+        writer.write_u8(self.threshold)?;
+        writer.write_u8(self.index)?;
+        writer.write_u32::<LittleEndian>(self.epoch)?;
+        writer.write_all(&self.value)?;
+
+        Ok(())
+    }
+}
+
+impl ShareUpdate {
+    /// Index of the share this update is for
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    /// Index of the share of the custodian who made this update
+    pub fn sender(&self) -> u8 {
+        self.from
+    }
+
+    /// Load a [`ShareUpdate`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        if reader.read_u8()? != KEY_SHARE_VERSION {
+            return Err(IronfishError::InvalidKeyShare);
+        }
+
+        let mut fingerprint = [0; KEY_FINGERPRINT_SIZE];
+        reader.read_exact(&mut fingerprint)?;
+        let epoch = reader.read_u32::<LittleEndian>()?;
+        let from = reader.read_u8()?;
+        let index = reader.read_u8()?;
+        let mut delta = [0; SPEND_KEY_SIZE];
+        reader.read_exact(&mut delta)?;
+
+        if from == 0 || index == 0 {
+            return Err(IronfishError::InvalidKeyShare);
+        }
+
+        Ok(ShareUpdate {
+            fingerprint,
+            epoch,
+            from,
+            index,
+            delta,
+        })
+    }
+
+    /// Stow the bytes of this [`ShareUpdate`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(KEY_SHARE_VERSION)?;
+        writer.write_all(&self.fingerprint)?;
+        writer.write_u32::<LittleEndian>(self.epoch)?;
+        writer.write_u8(self.from)?;
+        writer.write_u8(self.index)?;
+        writer.write_all(&self.delta)?;
+
+        Ok(())
+    }
+}
+
+fn key_fingerprint(spending_key: &[u8; SPEND_KEY_SIZE]) -> [u8; KEY_FINGERPRINT_SIZE] {
+    let mut fingerprint = [0; KEY_FINGERPRINT_SIZE];
+    fingerprint.copy_from_slice(
+        Blake2b::new()
+            .hash_length(KEY_FINGERPRINT_SIZE)
+            .personal(KEY_SHARE_PERSONALIZATION)
+            .hash(spending_key)
+            .as_bytes(),
+    );
+
+    fingerprint
+}
+
+/// Coefficients of the polynomials sharing each byte of `secret`, lowest
+/// degree first, the constant terms being the bytes of the secret
+fn random_polynomials(
+    secret: [u8; SPEND_KEY_SIZE],
+    threshold: u8,
+) -> Result<Vec<[u8; SPEND_KEY_SIZE]>, IronfishError> {
+    let mut coefficients = vec![secret];
+    for _ in 1..threshold {
+        let mut coefficient = [0; SPEND_KEY_SIZE];
+        with_rng(|rng| rng.fill_bytes(&mut coefficient))?;
+        coefficients.push(coefficient);
+    }
+
+    Ok(coefficients)
+}
+
+/// The polynomials of every byte at `x`
+fn evaluate(coefficients: &[[u8; SPEND_KEY_SIZE]], x: u8) -> [u8; SPEND_KEY_SIZE] {
+    let mut value = [0; SPEND_KEY_SIZE];
+    for coefficient in coefficients.iter().rev() {
+        for (byte, term) in value.iter_mut().zip(coefficient) {
+            *byte = gf_mul(*byte, x) ^ term;
+        }
+    }
+
+    value
+}
+
+/// The constant terms of the polynomials through the shares, by Lagrange
+/// interpolation
+fn interpolate_at_zero(shares: &[KeyShare]) -> [u8; SPEND_KEY_SIZE] {
+    let mut secret = [0; SPEND_KEY_SIZE];
+    for share in shares {
+        // Subtraction is addition in GF(2^8)
+        let mut weight = 1;
+        for other in shares.iter().filter(|other| other.index != share.index) {
+            weight = gf_mul(
+                weight,
+                gf_mul(other.index, gf_inverse(other.index ^ share.index)),
+            );
+        }
+
+        for (byte, value) in secret.iter_mut().zip(share.value) {
+            *byte ^= gf_mul(weight, value);
+        }
+    }
+
+    secret
+}
+
+/// Multiplication in GF(2^8) modulo the polynomial of AES, in the same time
+/// for every operand
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+
+    product
+}
+
+/// The inverse in GF(2^8) of a non zero element, `a^254`
+fn gf_inverse(a: u8) -> u8 {
+    let mut inverse = 1;
+    let mut power = a;
+    for bit in 0..8 {
+        if (254 >> bit) & 1 == 1 {
+            inverse = gf_mul(inverse, power);
+        }
+        power = gf_mul(power, power);
+    }
+
+    inverse
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        gf_inverse, gf_mul, recover_spending_key, split_spending_key, KeyShare, ShareUpdate,
+        KEY_SHARE_SIZE, SHARE_UPDATE_SIZE,
+    };
+    use crate::{errors::IronfishError, keys::SaplingKey};
+
+    #[test]
+    fn test_gf_arithmetic() {
+        for a in 1..=255 {
+            assert_eq!(gf_mul(a, gf_inverse(a)), 1);
+        }
+        // From FIPS 197
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+    }
+
+    #[test]
+    fn test_split_and_recover() {
+        let key = SaplingKey::generate_key();
+        let shares = split_spending_key(&key, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        for subset in [&shares[..3], &shares[2..], &shares[..]] {
+            let recovered = recover_spending_key(subset).unwrap();
+            assert_eq!(recovered.spending_key(), key.spending_key());
+        }
+        let mixed = [shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(
+            recover_spending_key(&mixed).unwrap().spending_key(),
+            key.spending_key()
+        );
+
+        assert!(matches!(
+            recover_spending_key(&shares[..2]),
+            Err(IronfishError::InvalidKeyShare)
+        ));
+        let repeated = [shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(recover_spending_key(&repeated).is_err());
+
+        // Shares of another key don't combine
+        let other = split_spending_key(&SaplingKey::generate_key(), 3, 5).unwrap();
+        let foreign = [shares[0].clone(), shares[1].clone(), other[2].clone()];
+        assert!(recover_spending_key(&foreign).is_err());
+
+        assert!(split_spending_key(&key, 1, 5).is_err());
+        assert!(split_spending_key(&key, 4, 3).is_err());
+
+        let mut serialized = vec![];
+        shares[1].write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), KEY_SHARE_SIZE);
+        assert_eq!(KeyShare::read(&serialized[..]).unwrap(), shares[1]);
+    }
+
+    #[test]
+    fn test_refresh_shares() {
+        let key = SaplingKey::generate_key();
+        let shares = split_spending_key(&key, 2, 3).unwrap();
+        let indexes: Vec<u8> = shares.iter().map(KeyShare::index).collect();
+
+        // Every custodian makes an update for each, and sends it over
+        let updates: Vec<Vec<ShareUpdate>> = shares
+            .iter()
+            .map(|share| share.refresh_updates(&indexes).unwrap())
+            .collect();
+        let mut serialized = vec![];
+        updates[0][1].write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), SHARE_UPDATE_SIZE);
+        assert_eq!(ShareUpdate::read(&serialized[..]).unwrap(), updates[0][1]);
+
+        let refreshed: Vec<KeyShare> = shares
+            .iter()
+            .enumerate()
+            .map(|(i, share)| {
+                let received: Vec<ShareUpdate> =
+                    updates.iter().map(|from| from[i].clone()).collect();
+                share.apply_updates(&received).unwrap()
+            })
+            .collect();
+
+        assert_ne!(refreshed[0], shares[0]);
+        assert_eq!(refreshed[0].epoch(), 1);
+        assert_eq!(
+            recover_spending_key(&refreshed[1..])
+                .unwrap()
+                .spending_key(),
+            key.spending_key()
+        );
+
+        // Shares of different epochs don't combine
+        let stale = [shares[0].clone(), refreshed[1].clone()];
+        assert!(matches!(
+            recover_spending_key(&stale),
+            Err(IronfishError::InvalidKeyShare)
+        ));
+
+        // Updates only apply to the share they're for, once each
+        assert!(shares[0].apply_updates(&updates[0][1..2]).is_err());
+        let twice = [updates[0][0].clone(), updates[0][0].clone()];
+        assert!(shares[0].apply_updates(&twice).is_err());
+        assert!(refreshed[0].apply_updates(&updates[0][..1]).is_err());
+    }
+}
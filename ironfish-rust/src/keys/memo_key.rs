@@ -0,0 +1,59 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Memo keys let senders find their own transactions again without keeping
+//! their hashes.
+//!
+//! The sender numbers their transactions, and builds each one with the memo
+//! key of its number, derived from their outgoing view key. The memo key
+//! sets the randomness of the transaction's randomized public key, which is
+//! public, so it can be indexed. Later, the sender recomputes the tags of
+//! their transaction numbers and looks them up in the index. To anyone
+//! without the sender's keys, tags look like any other randomized public key.
+
+use super::{OutgoingViewKey, ViewKey};
+use crate::personalization::MEMO_KEY_PERSONALIZATION;
+use blake2b_simd::Params as Blake2b;
+use group::GroupEncoding;
+use ironfish_zkp::{constants::SPENDING_KEY_GENERATOR, redjubjub};
+
+/// The randomness of the randomized public key of one of a sender's
+/// transactions. Each memo key must only be used for a single transaction;
+/// transactions built with the same memo key share their tag and are linked.
+#[derive(Clone)]
+pub struct MemoKey {
+    pub(crate) randomness: jubjub::Fr,
+}
+
+impl OutgoingViewKey {
+    /// The memo key of the sender's transaction with the given number.
+    pub fn memo_key(&self, index: u64) -> MemoKey {
+        let mut hash_result = [0; 64];
+        hash_result.copy_from_slice(
+            Blake2b::new()
+                .hash_length(64)
+                .personal(MEMO_KEY_PERSONALIZATION)
+                .to_state()
+                .update(&self.view_key)
+                .update(&index.to_le_bytes())
+                .finalize()
+                .as_bytes(),
+        );
+
+        MemoKey {
+            randomness: jubjub::Fr::from_bytes_wide(&hash_result),
+        }
+    }
+}
+
+impl MemoKey {
+    /// The tag of the transaction the owner of the given view key built with
+    /// this memo key, as returned by [`crate::Transaction::memo_tag`].
+    pub fn tag(&self, view_key: &ViewKey) -> [u8; 32] {
+        redjubjub::PublicKey(view_key.authorizing_key.into())
+            .randomize(self.randomness, SPENDING_KEY_GENERATOR)
+            .0
+            .to_bytes()
+    }
+}
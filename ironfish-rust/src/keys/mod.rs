@@ -3,34 +3,48 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::errors::IronfishError;
+use crate::network::NetworkKind;
+use crate::primitives::{crh_ivk, prf_expand};
+use crate::randomness::expect_rng;
 use crate::serializing::{bytes_to_hex, hex_to_bytes, read_scalar};
 
 pub use bip39::Language;
 use bip39::Mnemonic;
-use blake2b_simd::Params as Blake2b;
-use blake2s_simd::Params as Blake2s;
+use byteorder::{ReadBytesExt, WriteBytesExt};
 use group::GroupEncoding;
-use ironfish_zkp::constants::{
-    CRH_IVK_PERSONALIZATION, PROOF_GENERATION_KEY_GENERATOR, SPENDING_KEY_GENERATOR,
-};
+use ironfish_zkp::constants::{PROOF_GENERATION_KEY_GENERATOR, SPENDING_KEY_GENERATOR};
 use ironfish_zkp::ProofGenerationKey;
 use jubjub::SubgroupPoint;
 use rand::prelude::*;
 
 use std::io;
 
+mod account;
+pub use account::*;
+mod asset_view_key;
+pub use asset_view_key::*;
 mod ephemeral;
 pub use ephemeral::EphemeralKeyPair;
+mod key_shares;
+pub use key_shares::*;
+mod memo_key;
+pub use memo_key::*;
+pub(crate) mod owner_authorization;
 mod public_address;
 pub use public_address::*;
+mod rotation;
+pub use rotation::*;
+mod signing;
+pub use signing::*;
+mod view_key_set;
+pub use view_key_set::*;
 mod view_keys;
 pub use view_keys::*;
+pub mod zcash;
 
 #[cfg(test)]
 mod test;
 
-const EXPANDED_SPEND_BLAKE2_KEY: &[u8; 16] = b"Iron Fish Money ";
-
 pub const SPEND_KEY_SIZE: usize = 32;
 
 /// A single private key generates multiple other key parts that can
@@ -72,24 +86,36 @@ pub struct SaplingKey {
     /// transmission key. This key allows the receiver of a note to decrypt its
     /// contents. Derived from view_key contents, this is materialized for convenience
     pub(crate) incoming_viewing_key: IncomingViewKey,
+
+    /// The network this key was expanded for.
+    pub(crate) network: NetworkKind,
 }
 
 impl SaplingKey {
-    /// Construct a new key from an array of bytes
+    /// Construct a new mainnet key from an array of bytes
     pub fn new(spending_key: [u8; SPEND_KEY_SIZE]) -> Result<Self, IronfishError> {
+        Self::new_for_network(spending_key, NetworkKind::Mainnet)
+    }
+
+    /// Construct a new key for the given network from an array of bytes. The
+    /// same bytes produce unrelated keys on each network.
+    pub fn new_for_network(
+        spending_key: [u8; SPEND_KEY_SIZE],
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
         let spend_authorizing_key =
-            jubjub::Fr::from_bytes_wide(&Self::convert_key(spending_key, 0));
+            jubjub::Fr::from_bytes_wide(&Self::convert_key(spending_key, 0, network));
 
         if spend_authorizing_key == jubjub::Fr::zero() {
             return Err(IronfishError::IllegalValue);
         }
 
         let proof_authorizing_key =
-            jubjub::Fr::from_bytes_wide(&Self::convert_key(spending_key, 1));
+            jubjub::Fr::from_bytes_wide(&Self::convert_key(spending_key, 1, network));
 
         let mut outgoing_viewing_key = [0; SPEND_KEY_SIZE];
         outgoing_viewing_key[0..SPEND_KEY_SIZE]
-            .clone_from_slice(&Self::convert_key(spending_key, 2)[0..SPEND_KEY_SIZE]);
+            .clone_from_slice(&Self::convert_key(spending_key, 2, network)[0..SPEND_KEY_SIZE]);
         let outgoing_viewing_key = OutgoingViewKey {
             view_key: outgoing_viewing_key,
         };
@@ -110,21 +136,37 @@ impl SaplingKey {
             outgoing_viewing_key,
             view_key,
             incoming_viewing_key,
+            network,
         })
     }
 
-    /// Load a new key from a Read implementation (e.g: socket, file)
+    /// Load a new mainnet key from a Read implementation (e.g: socket, file)
     pub fn read<R: io::Read>(reader: &mut R) -> Result<Self, IronfishError> {
         let mut spending_key = [0; SPEND_KEY_SIZE];
         reader.read_exact(&mut spending_key)?;
         Self::new(spending_key)
     }
 
-    /// Load a key from a string of hexadecimal digits
+    /// Load a key written by [`SaplingKey::write_with_network`] from a Read
+    /// implementation (e.g: socket, file), on the network written along with
+    /// it
+    pub fn read_for_network<R: io::Read>(reader: &mut R) -> Result<Self, IronfishError> {
+        let network = NetworkKind::try_from(reader.read_u8()?)?;
+        let mut spending_key = [0; SPEND_KEY_SIZE];
+        reader.read_exact(&mut spending_key)?;
+        Self::new_for_network(spending_key, network)
+    }
+
+    /// Load a mainnet key from a string of hexadecimal digits
     pub fn from_hex(value: &str) -> Result<Self, IronfishError> {
+        Self::from_hex_for_network(value, NetworkKind::Mainnet)
+    }
+
+    /// Load a key for the given network from a string of hexadecimal digits
+    pub fn from_hex_for_network(value: &str, network: NetworkKind) -> Result<Self, IronfishError> {
         match hex_to_bytes(value) {
             Err(_) => Err(IronfishError::InvalidPaymentAddress),
-            Ok(bytes) => Self::new(bytes),
+            Ok(bytes) => Self::new_for_network(bytes, network),
         }
     }
 
@@ -134,9 +176,14 @@ impl SaplingKey {
     /// first time.
     /// Note that unlike `new`, this function always successfully returns a value.
     pub fn generate_key() -> Self {
-        let spending_key: [u8; SPEND_KEY_SIZE] = random();
+        Self::generate_key_for_network(NetworkKind::Mainnet)
+    }
+
+    /// Generate a new random secret key for the given network.
+    pub fn generate_key_for_network(network: NetworkKind) -> Self {
+        let spending_key: [u8; SPEND_KEY_SIZE] = expect_rng(|rng| rng.gen());
         loop {
-            if let Ok(key) = Self::new(spending_key) {
+            if let Ok(key) = Self::new_for_network(spending_key, network) {
                 return key;
             }
         }
@@ -147,7 +194,8 @@ impl SaplingKey {
         PublicAddress::from_key(self)
     }
 
-    // Write a bytes representation of this key to the provided stream
+    // Write a bytes representation of this key to the provided stream. The
+    // network isn't part of it, see `write_with_network`
     pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
         let num_bytes_written = writer.write(&self.spending_key)?;
         if num_bytes_written != SPEND_KEY_SIZE {
@@ -157,13 +205,21 @@ impl SaplingKey {
         Ok(())
     }
 
+    /// Stow the bytes of this key, preceded by its network, in the given
+    /// writer. Read them back with [`SaplingKey::read_for_network`].
+    pub fn write_with_network<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(self.network.as_u8())?;
+        self.write(writer)
+    }
+
     /// Retrieve the private spending key
     pub fn spending_key(&self) -> [u8; SPEND_KEY_SIZE] {
         self.spending_key
     }
 
     /// Private spending key as hexadecimal. This is slightly
-    /// more human readable.
+    /// more human readable. The network isn't part of it, so it has to be
+    /// loaded back with [`SaplingKey::from_hex_for_network`] off mainnet.
     pub fn hex_spending_key(&self) -> String {
         bytes_to_hex(&self.spending_key)
     }
@@ -174,19 +230,34 @@ impl SaplingKey {
     /// a seed. This isn't strictly necessary for private key, but view keys
     /// will need a direct mapping. The private key could still be generated
     /// using bip-32 and bip-39 if desired.
+    ///
+    /// The words only hold the spending key, as bip-39 has no room for the
+    /// network: off mainnet they have to be loaded back with
+    /// [`SaplingKey::from_words_for_network`].
     pub fn to_words(&self, language: Language) -> Result<Mnemonic, IronfishError> {
         Mnemonic::from_entropy(&self.spending_key, language)
             .map_err(|_| IronfishError::InvalidEntropy)
     }
 
-    /// Takes a bip-39 phrase as a string and turns it into a SaplingKey instance
+    /// Takes a bip-39 phrase as a string and turns it into a mainnet
+    /// SaplingKey instance
     pub fn from_words(words: String, language: Language) -> Result<Self, IronfishError> {
+        Self::from_words_for_network(words, language, NetworkKind::Mainnet)
+    }
+
+    /// Takes a bip-39 phrase as a string and turns it into a SaplingKey
+    /// instance for the given network
+    pub fn from_words_for_network(
+        words: String,
+        language: Language,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
         let mnemonic = Mnemonic::from_phrase(&words, language)
             .map_err(|_| IronfishError::InvalidMnemonicString)?;
         let bytes = mnemonic.entropy();
         let mut byte_arr = [0; SPEND_KEY_SIZE];
         byte_arr.clone_from_slice(&bytes[0..SPEND_KEY_SIZE]);
-        Self::new(byte_arr)
+        Self::new_for_network(byte_arr, network)
     }
 
     /// Retrieve the publicly visible outgoing viewing key
@@ -204,6 +275,19 @@ impl SaplingKey {
         &self.view_key
     }
 
+    /// Retrieve the network this key was expanded for
+    pub fn network(&self) -> NetworkKind {
+        self.network
+    }
+
+    /// Retrieve the keys that prove spends of this account's notes, `ak` and
+    /// `nsk`, for a prover such as [`crate::transaction::spends::prove_spend`].
+    /// Whoever holds them can tell which nullifiers are this account's, but
+    /// can't sign spends.
+    pub fn proof_generation_key(&self) -> ProofGenerationKey {
+        self.sapling_proof_generation_key()
+    }
+
     /// Adapter to convert this key to a proof generation key for use in
     /// sapling functions
     pub(crate) fn sapling_proof_generation_key(&self) -> ProofGenerationKey {
@@ -223,17 +307,13 @@ impl SaplingKey {
     ///  *  `spending_key` The 32 byte spending key
     ///  *  `modifier` a byte to add to tweak the hash for each of the three
     ///     values
-    fn convert_key(spending_key: [u8; SPEND_KEY_SIZE], modifier: u8) -> [u8; 64] {
-        let mut hasher = Blake2b::new()
-            .hash_length(64)
-            .personal(EXPANDED_SPEND_BLAKE2_KEY)
-            .to_state();
-
-        hasher.update(&spending_key);
-        hasher.update(&[modifier]);
-        let mut hash_result = [0; 64];
-        hash_result[0..64].clone_from_slice(&hasher.finalize().as_ref()[0..64]);
-        hash_result
+    ///  *  `network` the network whose personalization is used
+    fn convert_key(
+        spending_key: [u8; SPEND_KEY_SIZE],
+        modifier: u8,
+        network: NetworkKind,
+    ) -> [u8; 64] {
+        prf_expand(&spending_key, modifier, network)
     }
 
     /// Helper method to construct the viewing key from the authorizing key
@@ -245,21 +325,10 @@ impl SaplingKey {
         authorizing_key: &SubgroupPoint,
         nullifier_deriving_key: &SubgroupPoint,
     ) -> Result<jubjub::Fr, IronfishError> {
-        let mut view_key_contents = [0; 64];
-        view_key_contents[0..32].copy_from_slice(&authorizing_key.to_bytes());
-        view_key_contents[32..64].copy_from_slice(&nullifier_deriving_key.to_bytes());
-        // let mut hasher = Blake2s::with_params(32, &[], &[], CRH_IVK_PERSONALIZATION);
-
-        let mut hash_result = [0; 32];
-        hash_result.copy_from_slice(
-            Blake2s::new()
-                .hash_length(32)
-                .personal(CRH_IVK_PERSONALIZATION)
-                .hash(&view_key_contents)
-                .as_bytes(),
+        let hash_result = crh_ivk(
+            &authorizing_key.to_bytes(),
+            &nullifier_deriving_key.to_bytes(),
         );
-        // Drop the last five bits, so it can be interpreted as a scalar.
-        hash_result[31] &= 0b0000_0111;
         if hash_result == [0; 32] {
             return Err(IronfishError::InvalidViewingKey);
         }
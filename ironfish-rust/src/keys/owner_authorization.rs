@@ -0,0 +1,152 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use bellman::groth16;
+use bls12_381::{Bls12, Scalar};
+use group::{Curve, GroupEncoding};
+use ironfish_zkp::{constants::SPENDING_KEY_GENERATOR, proofs::MintAsset, redjubjub};
+use jubjub::ExtendedPoint;
+
+use crate::{
+    errors::IronfishError,
+    randomness::{random_scalar, with_rng},
+    sapling_bls12::SAPLING,
+};
+
+use super::{PublicAddress, SaplingKey};
+
+/// Proof that the signer knows the keys behind a public address, the same
+/// way the owner of an asset proves it for a mint: a mint proof bound to a
+/// randomized public key of the account, which then signs the message it
+/// authorizes. Nothing about the keys is revealed besides the address.
+pub(crate) struct OwnerAuthorization {
+    pub(crate) proof: groth16::Proof<Bls12>,
+
+    /// Randomized public key of the owner, which the proof is bound to and
+    /// which verifies the signature
+    pub(crate) randomized_public_key: redjubjub::PublicKey,
+
+    public_key_randomness: jubjub::Fr,
+}
+
+impl OwnerAuthorization {
+    /// Prove ownership of the public address of the given key, with a fresh
+    /// randomized public key
+    pub(crate) fn prove(owner_key: &SaplingKey) -> Result<Self, IronfishError> {
+        let public_key_randomness = random_scalar()?;
+        let randomized_public_key = redjubjub::PublicKey(owner_key.view_key.authorizing_key.into())
+            .randomize(public_key_randomness, SPENDING_KEY_GENERATOR);
+
+        let circuit = MintAsset {
+            proof_generation_key: Some(owner_key.sapling_proof_generation_key()),
+            public_key_randomness: Some(public_key_randomness),
+        };
+        let params = SAPLING.mint_params.load()?;
+        let proof = with_rng(|rng| groth16::create_random_proof(circuit, &*params, rng))??;
+
+        Ok(OwnerAuthorization {
+            proof,
+            randomized_public_key,
+            public_key_randomness,
+        })
+    }
+
+    /// Sign the hash of the authorized message with the key this proved
+    /// ownership for
+    pub(crate) fn sign(
+        &self,
+        owner_key: &SaplingKey,
+        signature_hash: &[u8; 32],
+    ) -> Result<redjubjub::Signature, IronfishError> {
+        sign_randomized(owner_key, self.public_key_randomness, signature_hash)
+    }
+
+    /// Confirm that the proof and the signature over the hash of the
+    /// authorized message were made by the owner of the given address
+    pub(crate) fn verify(
+        proof: &groth16::Proof<Bls12>,
+        randomized_public_key: &redjubjub::PublicKey,
+        owner: &PublicAddress,
+        signature_hash: &[u8; 32],
+        signature: &redjubjub::Signature,
+    ) -> Result<(), IronfishError> {
+        if randomized_public_key.0.is_small_order().into() {
+            return Err(IronfishError::IsSmallOrder);
+        }
+
+        groth16::verify_proof(
+            &SAPLING.mint_verifying_key,
+            proof,
+            &public_inputs(randomized_public_key, owner),
+        )?;
+
+        verify_randomized(randomized_public_key, signature_hash, signature)
+    }
+}
+
+/// Placeholder for the signature of a message that's being built, until the
+/// rest of the message can be hashed
+pub(crate) fn blank_signature() -> Result<redjubjub::Signature, IronfishError> {
+    let buf = [0u8; 64];
+    Ok(redjubjub::Signature::read(&mut buf.as_ref())?)
+}
+
+/// Sign a hash with the spend authorizing key of the given account,
+/// randomized with the given randomness, along with the randomized public
+/// key
+pub(crate) fn sign_randomized(
+    key: &SaplingKey,
+    public_key_randomness: jubjub::Fr,
+    signature_hash: &[u8; 32],
+) -> Result<redjubjub::Signature, IronfishError> {
+    let randomized_public_key = redjubjub::PublicKey(key.view_key.authorizing_key.into())
+        .randomize(public_key_randomness, SPENDING_KEY_GENERATOR);
+    let data_to_be_signed = data_to_be_signed(&randomized_public_key, signature_hash);
+
+    let private_key = redjubjub::PrivateKey(key.spend_authorizing_key);
+    let randomized_private_key = private_key.randomize(public_key_randomness);
+    with_rng(|rng| randomized_private_key.sign(&data_to_be_signed, rng, SPENDING_KEY_GENERATOR))
+}
+
+/// Check a signature made by [`sign_randomized`]. The randomized public key
+/// is expected to have been checked for small order already.
+pub(crate) fn verify_randomized(
+    randomized_public_key: &redjubjub::PublicKey,
+    signature_hash: &[u8; 32],
+    signature: &redjubjub::Signature,
+) -> Result<(), IronfishError> {
+    let data_to_be_signed = data_to_be_signed(randomized_public_key, signature_hash);
+    if !randomized_public_key.verify(&data_to_be_signed, signature, SPENDING_KEY_GENERATOR) {
+        return Err(IronfishError::VerificationFailed);
+    }
+
+    Ok(())
+}
+
+fn data_to_be_signed(
+    randomized_public_key: &redjubjub::PublicKey,
+    signature_hash: &[u8; 32],
+) -> [u8; 64] {
+    let mut data_to_be_signed = [0; 64];
+    data_to_be_signed[..32].copy_from_slice(&randomized_public_key.0.to_bytes());
+    data_to_be_signed[32..].copy_from_slice(signature_hash);
+    data_to_be_signed
+}
+
+fn public_inputs(
+    randomized_public_key: &redjubjub::PublicKey,
+    owner: &PublicAddress,
+) -> [Scalar; 4] {
+    let mut public_inputs = [Scalar::zero(); 4];
+
+    let randomized_public_key_point = randomized_public_key.0.to_affine();
+    public_inputs[0] = randomized_public_key_point.get_u();
+    public_inputs[1] = randomized_public_key_point.get_v();
+
+    let owner_public_address_point = ExtendedPoint::from(owner.transmission_key).to_affine();
+    public_inputs[2] = owner_public_address_point.get_u();
+    public_inputs[3] = owner_public_address_point.get_v();
+
+    public_inputs
+}
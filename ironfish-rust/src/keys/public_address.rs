@@ -4,17 +4,64 @@
 
 use crate::{
     errors::IronfishError,
-    serializing::{bytes_to_hex, hex_to_bytes},
+    network::NetworkKind,
+    serializing::{bytes_to_hex, hex_to_bytes, hex_to_vec_bytes},
 };
 use group::GroupEncoding;
 use ironfish_zkp::constants::PUBLIC_KEY_GENERATOR;
-use jubjub::SubgroupPoint;
+use jubjub::{AffinePoint, ExtendedPoint, SubgroupPoint};
 
-use std::{convert::TryInto, io};
+use std::{convert::TryInto, fmt, io};
 
-use super::{IncomingViewKey, SaplingKey};
+use super::{IncomingViewKey, SaplingKey, INCOMING_VIEW_KEY_SIZE};
 pub const PUBLIC_ADDRESS_SIZE: usize = 32;
 
+/// Separates the network prefix from the hex address in encoded addresses
+const ADDRESS_HRP_SEPARATOR: char = '_';
+
+/// What's wrong with something that was meant to be a public address, from
+/// [`PublicAddress::parse`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AddressDefect {
+    /// Not made of pairs of hexadecimal digits
+    InvalidHex,
+
+    /// Not [`PUBLIC_ADDRESS_SIZE`] bytes long
+    WrongLength { length: usize },
+
+    /// Encodes a point in more than one way: the coordinate is larger than
+    /// the field, or the sign of a zero coordinate is negative
+    NonCanonical,
+
+    /// No point of the curve has this encoding
+    NotOnCurve,
+
+    /// A point of small order, which would reveal what's sent to it
+    SmallOrder,
+
+    /// A point of the curve outside of the subgroup keys are in
+    NotInSubgroup,
+}
+
+impl fmt::Display for AddressDefect {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AddressDefect::InvalidHex => write!(f, "address is not hexadecimal"),
+            AddressDefect::WrongLength { length } => write!(
+                f,
+                "address is {} bytes long instead of {}",
+                length, PUBLIC_ADDRESS_SIZE
+            ),
+            AddressDefect::NonCanonical => write!(f, "address is not canonically encoded"),
+            AddressDefect::NotOnCurve => write!(f, "address is not a point of the curve"),
+            AddressDefect::SmallOrder => write!(f, "address is a point of small order"),
+            AddressDefect::NotInSubgroup => {
+                write!(f, "address is not a point of the prime order subgroup")
+            }
+        }
+    }
+}
+
 /// The address to which funds can be sent, stored as a public
 /// transmission key. Using the incoming_viewing_key allows
 /// the creation of a unique public addresses without revealing the viewing key.
@@ -34,6 +81,67 @@ impl PublicAddress {
         Ok(PublicAddress { transmission_key })
     }
 
+    /// Initialize a public address from bytes of any length, saying what's
+    /// wrong with them if they aren't an address. [`PublicAddress::new`]
+    /// accepts the same addresses, but fails alike for all of them.
+    pub fn parse(bytes: &[u8]) -> Result<PublicAddress, AddressDefect> {
+        let bytes: &[u8; PUBLIC_ADDRESS_SIZE] =
+            bytes.try_into().map_err(|_| AddressDefect::WrongLength {
+                length: bytes.len(),
+            })?;
+
+        if !PublicAddress::is_valid_quick(bytes) {
+            return Err(AddressDefect::NonCanonical);
+        }
+
+        // Decoding with the rules from before ZIP 216 only fails if the
+        // point isn't on the curve, since the coordinate is canonical
+        let point =
+            Option::<AffinePoint>::from(AffinePoint::from_bytes_pre_zip216_compatibility(*bytes))
+                .ok_or(AddressDefect::NotOnCurve)?;
+        if bool::from(AffinePoint::from_bytes(*bytes).is_none()) {
+            return Err(AddressDefect::NonCanonical);
+        }
+
+        let point = ExtendedPoint::from(point);
+        if bool::from(point.is_small_order()) {
+            return Err(AddressDefect::SmallOrder);
+        }
+
+        let transmission_key = Option::<SubgroupPoint>::from(SubgroupPoint::from_bytes(bytes))
+            .ok_or(AddressDefect::NotInSubgroup)?;
+
+        Ok(PublicAddress { transmission_key })
+    }
+
+    /// [`PublicAddress::parse`] for an address in hex form
+    pub fn parse_hex(value: &str) -> Result<PublicAddress, AddressDefect> {
+        let bytes = hex_to_vec_bytes(value).map_err(|_| AddressDefect::InvalidHex)?;
+
+        PublicAddress::parse(&bytes)
+    }
+
+    /// A cheap check of the length and encoding of an address, to reject
+    /// mistyped addresses as they are entered. Addresses that pass it may
+    /// still not be points of the curve, which only [`PublicAddress::parse`]
+    /// can tell.
+    pub fn is_valid_quick(bytes: &[u8]) -> bool {
+        let bytes: &[u8; PUBLIC_ADDRESS_SIZE] = match bytes.try_into() {
+            Ok(bytes) => bytes,
+            Err(_) => return false,
+        };
+
+        // The top bit is the sign of the other coordinate
+        let mut coordinate = *bytes;
+        coordinate[PUBLIC_ADDRESS_SIZE - 1] &= 0x7f;
+        jubjub::Fq::from_bytes(&coordinate).is_some().into()
+    }
+
+    /// Same as [`PublicAddress::public_address`].
+    pub fn to_bytes(&self) -> [u8; PUBLIC_ADDRESS_SIZE] {
+        self.public_address()
+    }
+
     /// Load a public address from a Read implementation (e.g: socket, file)
     pub fn read<R: io::Read>(reader: &mut R) -> Result<Self, IronfishError> {
         let mut address_bytes = [0; PUBLIC_ADDRESS_SIZE];
@@ -73,6 +181,32 @@ impl PublicAddress {
         bytes_to_hex(&self.public_address())
     }
 
+    /// Encode the public address with the prefix of the given network, for
+    /// example `iron_<64 hex characters>` on mainnet.
+    pub fn encode(&self, network: NetworkKind) -> String {
+        format!(
+            "{}{}{}",
+            network.address_hrp(),
+            ADDRESS_HRP_SEPARATOR,
+            self.hex_public_address()
+        )
+    }
+
+    /// Decode a public address produced by [`PublicAddress::encode`], failing
+    /// with [`IronfishError::InvalidNetwork`] if it belongs to another
+    /// network.
+    pub fn decode(value: &str, network: NetworkKind) -> Result<Self, IronfishError> {
+        let (hrp, hex) = value
+            .split_once(ADDRESS_HRP_SEPARATOR)
+            .ok_or(IronfishError::InvalidPublicAddress)?;
+
+        if hrp != network.address_hrp() {
+            return Err(IronfishError::InvalidNetwork);
+        }
+
+        Self::from_hex(hex)
+    }
+
     /// Store the bytes of this public address in the given writer.
     pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
         writer.write_all(&self.public_address())?;
@@ -107,9 +241,42 @@ impl std::cmp::PartialEq for PublicAddress {
     }
 }
 
+/// Derive the address of every incoming view key in `view_keys`, which holds
+/// the keys end to end, writing the addresses end to end in the same order
+/// into `addresses`. This lets bindings derive any number of addresses in a
+/// single call rather than one call per address.
+pub fn derive_public_addresses(
+    view_keys: &[u8],
+    addresses: &mut [u8],
+) -> Result<(), IronfishError> {
+    if view_keys.len() % INCOMING_VIEW_KEY_SIZE != 0
+        || addresses.len() != view_keys.len() / INCOMING_VIEW_KEY_SIZE * PUBLIC_ADDRESS_SIZE
+    {
+        return Err(IronfishError::InvalidData);
+    }
+
+    for (view_key, address) in view_keys
+        .chunks_exact(INCOMING_VIEW_KEY_SIZE)
+        .zip(addresses.chunks_exact_mut(PUBLIC_ADDRESS_SIZE))
+    {
+        let view_key = IncomingViewKey::read(&mut &view_key[..])
+            .map_err(|_| IronfishError::InvalidViewingKey)?;
+        address.copy_from_slice(&view_key.public_address().public_address());
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
-    use crate::{keys::PUBLIC_ADDRESS_SIZE, PublicAddress, SaplingKey};
+    use super::{derive_public_addresses, AddressDefect};
+    use crate::{
+        errors::IronfishError,
+        keys::{INCOMING_VIEW_KEY_SIZE, PUBLIC_ADDRESS_SIZE},
+        PublicAddress, SaplingKey,
+    };
+    use group::GroupEncoding;
+    use jubjub::{AffinePoint, ExtendedPoint};
 
     #[test]
     fn public_address_validation() {
@@ -122,10 +289,87 @@ mod test {
         PublicAddress::from_hex(good_address).expect("returns a valid public address");
     }
 
+    #[test]
+    fn public_address_defects() {
+        let address = SaplingKey::generate_key().public_address();
+        let bytes = address.public_address();
+        assert_eq!(PublicAddress::parse(&bytes).unwrap(), address);
+        assert_eq!(
+            PublicAddress::parse_hex(&address.hex_public_address()).unwrap(),
+            address
+        );
+        assert!(PublicAddress::is_valid_quick(&bytes));
+
+        let parse = |bytes: &[u8]| PublicAddress::parse(bytes).unwrap_err();
+        assert_eq!(
+            parse(&bytes[1..]),
+            AddressDefect::WrongLength { length: 31 }
+        );
+        assert_eq!(
+            PublicAddress::parse_hex("not hex"),
+            Err(AddressDefect::InvalidHex)
+        );
+
+        // A coordinate larger than the field
+        assert!(!PublicAddress::is_valid_quick(&[0xff; 32]));
+        assert_eq!(parse(&[0xff; 32]), AddressDefect::NonCanonical);
+
+        // The identity, with a zero coordinate of negative sign
+        let mut identity = [0; PUBLIC_ADDRESS_SIZE];
+        identity[0] = 1;
+        assert_eq!(parse(&identity), AddressDefect::SmallOrder);
+        identity[PUBLIC_ADDRESS_SIZE - 1] = 0x80;
+        assert!(PublicAddress::is_valid_quick(&identity));
+        assert_eq!(parse(&identity), AddressDefect::NonCanonical);
+
+        // There is no point with a coordinate of 2
+        let mut off_curve = [0; PUBLIC_ADDRESS_SIZE];
+        off_curve[0] = 2;
+        assert!(PublicAddress::is_valid_quick(&off_curve));
+        assert_eq!(parse(&off_curve), AddressDefect::NotOnCurve);
+
+        // The point of order 2, and the address shifted by it
+        let order_two = AffinePoint::from_bytes((-jubjub::Fq::from(1u64)).to_bytes()).unwrap();
+        assert_eq!(parse(&order_two.to_bytes()), AddressDefect::SmallOrder);
+        let shifted =
+            ExtendedPoint::from(address.transmission_key) + ExtendedPoint::from(order_two);
+        assert_eq!(parse(&shifted.to_bytes()), AddressDefect::NotInSubgroup);
+
+        // Every defect is rejected by the plain constructor
+        assert!(PublicAddress::new(&shifted.to_bytes()).is_err());
+    }
+
     #[test]
     fn public_address_generation() {
         let sapling_key = SaplingKey::generate_key();
         let public_address = sapling_key.public_address();
         assert_eq!(public_address.public_address().len(), PUBLIC_ADDRESS_SIZE);
     }
+
+    #[test]
+    fn test_derive_public_addresses() {
+        let keys: Vec<SaplingKey> = (0..3).map(|_| SaplingKey::generate_key()).collect();
+        let view_keys: Vec<u8> = keys
+            .iter()
+            .flat_map(|key| key.incoming_view_key().view_key.to_bytes())
+            .collect();
+
+        let mut addresses = vec![0; keys.len() * PUBLIC_ADDRESS_SIZE];
+        derive_public_addresses(&view_keys, &mut addresses).unwrap();
+        for (key, address) in keys.iter().zip(addresses.chunks(PUBLIC_ADDRESS_SIZE)) {
+            assert_eq!(address, key.public_address().public_address());
+        }
+
+        assert!(matches!(
+            derive_public_addresses(&view_keys, &mut addresses[1..]),
+            Err(IronfishError::InvalidData)
+        ));
+        assert!(matches!(
+            derive_public_addresses(
+                &[0xff; INCOMING_VIEW_KEY_SIZE],
+                &mut [0; PUBLIC_ADDRESS_SIZE]
+            ),
+            Err(IronfishError::InvalidViewingKey)
+        ));
+    }
 }
@@ -0,0 +1,143 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::io;
+
+use bellman::groth16;
+use blake2b_simd::Params as Blake2b;
+use bls12_381::Bls12;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use group::GroupEncoding;
+use ironfish_zkp::redjubjub;
+
+use crate::{errors::IronfishError, personalization::ACCOUNT_ROTATION_PERSONALIZATION};
+
+use super::{
+    owner_authorization::{blank_signature, OwnerAuthorization},
+    PublicAddress, SaplingKey,
+};
+
+pub const ACCOUNT_ROTATION_VERSION: u8 = 1;
+
+/// An attestation from the owner of an account that funds for it should go
+/// to a new account from now on, so services holding the old public address
+/// (e.g. whitelisted withdrawal addresses) can migrate to the new one.
+///
+/// The owner proves knowledge of the keys behind the old address with a mint
+/// proof, the same way asset ownership is proven, and signs both addresses
+/// with the randomized spend authorizing key. Nothing about the old keys is
+/// revealed besides the old address itself.
+#[derive(Clone)]
+pub struct AccountRotation {
+    /// Proof that the signer owns the old address
+    pub proof: groth16::Proof<Bls12>,
+
+    /// Randomized public key of the old account, which the proof is bound to
+    /// and which verifies the signature
+    pub randomized_public_key: redjubjub::PublicKey,
+
+    /// Address being retired
+    pub old_address: PublicAddress,
+
+    /// Address replacing it
+    pub new_address: PublicAddress,
+
+    /// Signature of the old account over every other field
+    pub authorizing_signature: redjubjub::Signature,
+}
+
+impl AccountRotation {
+    /// Attest that `old_key`'s account is replaced by the account at
+    /// `new_address`.
+    pub fn new(old_key: &SaplingKey, new_address: PublicAddress) -> Result<Self, IronfishError> {
+        let old_address = old_key.public_address();
+        if old_address == new_address {
+            return Err(IronfishError::InvalidPublicAddress);
+        }
+
+        let authorization = OwnerAuthorization::prove(old_key)?;
+
+        let mut rotation = AccountRotation {
+            proof: authorization.proof.clone(),
+            randomized_public_key: authorization.randomized_public_key.clone(),
+            old_address,
+            new_address,
+            authorizing_signature: blank_signature()?,
+        };
+
+        rotation.authorizing_signature = authorization.sign(old_key, &rotation.signature_hash())?;
+
+        rotation.verify()?;
+
+        Ok(rotation)
+    }
+
+    /// Calculate a hash of the attestation. This is what the old account
+    /// signs, along with the randomized public key.
+    pub fn signature_hash(&self) -> [u8; 32] {
+        let mut hasher = Blake2b::new()
+            .hash_length(32)
+            .personal(ACCOUNT_ROTATION_PERSONALIZATION)
+            .to_state();
+        hasher.update(&[ACCOUNT_ROTATION_VERSION]);
+        hasher.update(&self.randomized_public_key.0.to_bytes());
+        hasher.update(&self.old_address.to_bytes());
+        hasher.update(&self.new_address.to_bytes());
+
+        let mut hash_result = [0; 32];
+        hash_result[..].clone_from_slice(hasher.finalize().as_ref());
+        hash_result
+    }
+
+    /// Confirm that the attestation was proven and signed by the owner of
+    /// the old address.
+    pub fn verify(&self) -> Result<(), IronfishError> {
+        if self.old_address == self.new_address {
+            return Err(IronfishError::InvalidPublicAddress);
+        }
+
+        OwnerAuthorization::verify(
+            &self.proof,
+            &self.randomized_public_key,
+            &self.old_address,
+            &self.signature_hash(),
+            &self.authorizing_signature,
+        )
+    }
+
+    /// Load an [`AccountRotation`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != ACCOUNT_ROTATION_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let proof = groth16::Proof::read(&mut reader)?;
+        let randomized_public_key = redjubjub::PublicKey::read(&mut reader)?;
+        let old_address = PublicAddress::read(&mut reader)?;
+        let new_address = PublicAddress::read(&mut reader)?;
+        let authorizing_signature = redjubjub::Signature::read(&mut reader)?;
+
+        Ok(AccountRotation {
+            proof,
+            randomized_public_key,
+            old_address,
+            new_address,
+            authorizing_signature,
+        })
+    }
+
+    /// Stow the bytes of this [`AccountRotation`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(ACCOUNT_ROTATION_VERSION)?;
+        self.proof.write(&mut writer)?;
+        writer.write_all(&self.randomized_public_key.0.to_bytes())?;
+        self.old_address.write(&mut writer)?;
+        self.new_address.write(&mut writer)?;
+        self.authorizing_signature.write(&mut writer)?;
+
+        Ok(())
+    }
+}
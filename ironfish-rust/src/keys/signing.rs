@@ -0,0 +1,247 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::io;
+
+use blake2b_simd::Params as Blake2b;
+use ff::Field;
+use group::GroupEncoding;
+use ironfish_zkp::{constants::SPENDING_KEY_GENERATOR, redjubjub};
+
+use crate::{
+    errors::IronfishError,
+    personalization::DOMAIN_SIGNATURE_PERSONALIZATION,
+    randomness::{random_scalar, with_rng},
+    serializing::read_scalar,
+};
+
+/// Longest context string of a [`SignatureDomain`], in bytes
+pub const MAX_SIGNATURE_DOMAIN_LENGTH: usize = 64;
+
+pub const DOMAIN_SIGNATURE_SIZE: usize = 64;
+
+/// What a signature is for, such as `"myapp/login-challenge/v1"`.
+///
+/// Every signature made with [`SigningKey::sign`] commits to its domain, so a
+/// signature made for one purpose never verifies for another, even if the
+/// signed messages are the same bytes. Give every kind of message its own
+/// domain, and change the domain whenever the format of the messages changes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SignatureDomain(&'static str);
+
+impl SignatureDomain {
+    /// Domains are fixed strings chosen by the protocol using them, never
+    /// input. They can't be empty or longer than
+    /// [`MAX_SIGNATURE_DOMAIN_LENGTH`].
+    pub fn new(context: &'static str) -> Result<Self, IronfishError> {
+        if context.is_empty() || context.len() > MAX_SIGNATURE_DOMAIN_LENGTH {
+            return Err(IronfishError::InvalidSignatureDomain);
+        }
+
+        Ok(SignatureDomain(context))
+    }
+
+    pub fn context(&self) -> &'static str {
+        self.0
+    }
+}
+
+/// A RedJubjub key for signing messages outside of transactions.
+///
+/// These keys are deliberately separate from the spend authorizing key of an
+/// account: a signing key can only sign messages in a [`SignatureDomain`],
+/// so it can't be tricked into authorizing a spend, and leaking one doesn't
+/// put any funds at risk.
+#[derive(Clone)]
+pub struct SigningKey(jubjub::Fr);
+
+impl SigningKey {
+    pub fn generate() -> Result<Self, IronfishError> {
+        Ok(SigningKey(random_scalar()?))
+    }
+
+    pub fn verifying_key(&self) -> VerifyingKey {
+        VerifyingKey(redjubjub::PublicKey(
+            (SPENDING_KEY_GENERATOR * self.0).into(),
+        ))
+    }
+
+    /// Sign a message in the given domain
+    pub fn sign(
+        &self,
+        domain: &SignatureDomain,
+        message: &[u8],
+    ) -> Result<DomainSignature, IronfishError> {
+        let data_to_be_signed = data_to_be_signed(&self.verifying_key(), domain, message);
+
+        Ok(DomainSignature(with_rng(|rng| {
+            redjubjub::PrivateKey(self.0).sign(&data_to_be_signed, rng, SPENDING_KEY_GENERATOR)
+        })?))
+    }
+
+    /// Load a [`SigningKey`] from a Read implementation (e.g: socket, file).
+    /// Zero isn't a valid key.
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        let scalar: jubjub::Fr = read_scalar(reader)?;
+        if scalar.is_zero().into() {
+            return Err(IronfishError::InvalidSigningKey);
+        }
+
+        Ok(SigningKey(scalar))
+    }
+
+    /// Stow the bytes of this [`SigningKey`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.0.to_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// The public half of a [`SigningKey`]
+#[derive(Clone)]
+pub struct VerifyingKey(redjubjub::PublicKey);
+
+impl VerifyingKey {
+    /// Check a signature over a message in the given domain
+    pub fn verify(
+        &self,
+        domain: &SignatureDomain,
+        message: &[u8],
+        signature: &DomainSignature,
+    ) -> Result<(), IronfishError> {
+        let data_to_be_signed = data_to_be_signed(self, domain, message);
+
+        if !self
+            .0
+            .verify(&data_to_be_signed, &signature.0, SPENDING_KEY_GENERATOR)
+        {
+            return Err(IronfishError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] {
+        self.0 .0.to_bytes()
+    }
+
+    /// Load a [`VerifyingKey`] from a Read implementation (e.g: socket,
+    /// file). Keys of small order are rejected, since anyone could forge
+    /// signatures for them.
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        let key = redjubjub::PublicKey::read(reader)?;
+        if key.0.is_small_order().into() {
+            return Err(IronfishError::IsSmallOrder);
+        }
+
+        Ok(VerifyingKey(key))
+    }
+
+    /// Stow the bytes of this [`VerifyingKey`] in the given writer.
+    pub fn write<W: io::Write>(&self, writer: W) -> Result<(), IronfishError> {
+        self.0.write(writer)?;
+
+        Ok(())
+    }
+}
+
+/// A signature made by [`SigningKey::sign`]
+#[derive(Clone)]
+pub struct DomainSignature(redjubjub::Signature);
+
+impl DomainSignature {
+    /// Load a [`DomainSignature`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Ok(DomainSignature(redjubjub::Signature::read(reader)?))
+    }
+
+    /// Stow the bytes of this [`DomainSignature`] in the given writer.
+    pub fn write<W: io::Write>(&self, writer: W) -> Result<(), IronfishError> {
+        self.0.write(writer)?;
+
+        Ok(())
+    }
+}
+
+/// The verifying key followed by a hash of the domain and message, the same
+/// layout transactions sign with their randomized public key
+fn data_to_be_signed(
+    verifying_key: &VerifyingKey,
+    domain: &SignatureDomain,
+    message: &[u8],
+) -> [u8; 64] {
+    let context = domain.context().as_bytes();
+    let hash = Blake2b::new()
+        .hash_length(32)
+        .personal(DOMAIN_SIGNATURE_PERSONALIZATION)
+        .to_state()
+        .update(&[context.len() as u8])
+        .update(context)
+        .update(message)
+        .finalize();
+
+    let mut data_to_be_signed = [0; 64];
+    data_to_be_signed[..32].copy_from_slice(&verifying_key.to_bytes());
+    data_to_be_signed[32..].copy_from_slice(hash.as_bytes());
+    data_to_be_signed
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        DomainSignature, SignatureDomain, SigningKey, VerifyingKey, DOMAIN_SIGNATURE_SIZE,
+    };
+    use crate::errors::IronfishError;
+
+    #[test]
+    fn test_domain_signatures() {
+        let login = SignatureDomain::new("ironfish-test/login/v1").unwrap();
+        let attestation = SignatureDomain::new("ironfish-test/attestation/v1").unwrap();
+        assert!(SignatureDomain::new("").is_err());
+
+        let key = SigningKey::generate().unwrap();
+        let verifying_key = key.verifying_key();
+        let signature = key.sign(&login, b"nonce 42").unwrap();
+        verifying_key
+            .verify(&login, b"nonce 42", &signature)
+            .unwrap();
+
+        // Signatures don't carry over to other messages, domains or keys
+        assert!(matches!(
+            verifying_key.verify(&login, b"nonce 43", &signature),
+            Err(IronfishError::VerificationFailed)
+        ));
+        assert!(verifying_key
+            .verify(&attestation, b"nonce 42", &signature)
+            .is_err());
+        assert!(SigningKey::generate()
+            .unwrap()
+            .verifying_key()
+            .verify(&login, b"nonce 42", &signature)
+            .is_err());
+
+        let mut serialized = vec![];
+        signature.write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), DOMAIN_SIGNATURE_SIZE);
+        let signature = DomainSignature::read(&serialized[..]).unwrap();
+
+        let mut serialized = vec![];
+        key.write(&mut serialized).unwrap();
+        let key = SigningKey::read(&serialized[..]).unwrap();
+
+        let mut serialized = vec![];
+        key.verifying_key().write(&mut serialized).unwrap();
+        let verifying_key = VerifyingKey::read(&serialized[..]).unwrap();
+        verifying_key
+            .verify(&login, b"nonce 42", &signature)
+            .unwrap();
+
+        assert!(matches!(
+            SigningKey::read(&[0; 32][..]),
+            Err(IronfishError::InvalidSigningKey)
+        ));
+    }
+}
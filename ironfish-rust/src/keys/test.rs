@@ -2,10 +2,20 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::keys::{ephemeral::EphemeralKeyPair, PUBLIC_ADDRESS_SIZE};
+use crate::{
+    assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+    errors::IronfishError,
+    keys::{ephemeral::EphemeralKeyPair, PUBLIC_ADDRESS_SIZE, SPEND_KEY_SIZE},
+    network::NetworkKind,
+    note::Note,
+    MerkleNote,
+};
 
-use super::{shared_secret, PublicAddress, SaplingKey};
+use super::{
+    shared_secret, AccountExport, AccountRotation, AssetViewKey, PublicAddress, SaplingKey,
+};
 use group::Curve;
+use ironfish_zkp::primitives::ValueCommitment;
 use jubjub::ExtendedPoint;
 
 #[test]
@@ -95,6 +105,23 @@ fn test_serialization() {
     )
 }
 
+#[test]
+fn test_serialization_with_network() {
+    let key = SaplingKey::generate_key_for_network(NetworkKind::Testnet);
+    let mut serialized_key = vec![];
+    key.write_with_network(&mut serialized_key).unwrap();
+    assert_eq!(serialized_key.len(), 1 + SPEND_KEY_SIZE);
+
+    let read_back_key = SaplingKey::read_for_network(&mut serialized_key.as_ref()).unwrap();
+    assert_eq!(read_back_key.network(), NetworkKind::Testnet);
+    assert_eq!(read_back_key.public_address(), key.public_address());
+
+    // The bare bytes after the network are the untagged serialization
+    let mut bare_key = vec![];
+    key.write(&mut bare_key).unwrap();
+    assert_eq!(bare_key, serialized_key[1..]);
+}
+
 #[test]
 fn test_hex_conversion() {
     let key = SaplingKey::generate_key();
@@ -104,6 +131,12 @@ fn test_hex_conversion() {
     let second_key = SaplingKey::from_hex(&hex).unwrap();
     assert_eq!(second_key.spending_key, key.spending_key);
 
+    let testnet_key = SaplingKey::generate_key_for_network(NetworkKind::Testnet);
+    let second_key =
+        SaplingKey::from_hex_for_network(&testnet_key.hex_spending_key(), NetworkKind::Testnet)
+            .unwrap();
+    assert_eq!(second_key.public_address(), testnet_key.public_address());
+
     let address = key.public_address();
     let hex = address.hex_public_address();
     assert_eq!(hex.len(), 2 * PUBLIC_ADDRESS_SIZE);
@@ -130,7 +163,168 @@ fn test_from_and_to_words() {
     assert_eq!(words_for_bytes, words);
 
     // Convert from words
-    let key =
-        SaplingKey::from_words(words, bip39::Language::English).expect("key should be created");
+    let key = SaplingKey::from_words(words.clone(), bip39::Language::English)
+        .expect("key should be created");
     assert_eq!(key.spending_key, key_bytes);
+    assert_eq!(key.network(), NetworkKind::Mainnet);
+
+    let testnet_key =
+        SaplingKey::from_words_for_network(words, bip39::Language::English, NetworkKind::Testnet)
+            .expect("key should be created");
+    assert_eq!(testnet_key.spending_key, key_bytes);
+    assert_eq!(testnet_key.network(), NetworkKind::Testnet);
+}
+
+#[test]
+fn test_network_key_derivation() {
+    let mainnet_key = SaplingKey::generate_key();
+    assert_eq!(mainnet_key.network(), NetworkKind::Mainnet);
+
+    let testnet_key =
+        SaplingKey::new_for_network(mainnet_key.spending_key, NetworkKind::Testnet).unwrap();
+    assert_eq!(testnet_key.network(), NetworkKind::Testnet);
+    assert_eq!(testnet_key.spending_key, mainnet_key.spending_key);
+    assert_ne!(testnet_key.public_address(), mainnet_key.public_address());
+    assert_ne!(
+        testnet_key.outgoing_view_key().view_key,
+        mainnet_key.outgoing_view_key().view_key
+    );
+}
+
+#[test]
+fn test_network_address_encoding() {
+    let address = SaplingKey::generate_key().public_address();
+
+    let encoded = address.encode(NetworkKind::Testnet);
+    assert!(encoded.starts_with("tiron_"));
+    assert_eq!(
+        PublicAddress::decode(&encoded, NetworkKind::Testnet).unwrap(),
+        address
+    );
+
+    assert!(matches!(
+        PublicAddress::decode(&encoded, NetworkKind::Mainnet),
+        Err(IronfishError::InvalidNetwork)
+    ));
+    assert!(PublicAddress::decode(&address.hex_public_address(), NetworkKind::Mainnet).is_err());
+}
+
+#[test]
+fn test_account_rotation() {
+    let old_key = SaplingKey::generate_key();
+    let new_address = SaplingKey::generate_key().public_address();
+
+    let rotation = AccountRotation::new(&old_key, new_address).unwrap();
+    assert_eq!(rotation.old_address, old_key.public_address());
+    assert_eq!(rotation.new_address, new_address);
+
+    let mut serialized = vec![];
+    rotation.write(&mut serialized).unwrap();
+    let read_back = AccountRotation::read(&serialized[..]).unwrap();
+    read_back.verify().expect("rotation should still verify");
+
+    // Redirecting the rotation to another address invalidates it
+    let mut tampered = read_back.clone();
+    tampered.new_address = SaplingKey::generate_key().public_address();
+    assert!(matches!(
+        tampered.verify(),
+        Err(IronfishError::VerificationFailed)
+    ));
+
+    // Claiming someone else's address fails the proof
+    let mut tampered = read_back;
+    tampered.old_address = SaplingKey::generate_key().public_address();
+    assert!(tampered.verify().is_err());
+
+    assert!(matches!(
+        AccountRotation::new(&old_key, old_key.public_address()),
+        Err(IronfishError::InvalidPublicAddress)
+    ));
+}
+
+#[test]
+fn test_account_export_birthday() {
+    let key = SaplingKey::generate_key_for_network(NetworkKind::Testnet);
+
+    let export = AccountExport::new(key.clone(), Some(1234));
+    let mut serialized = vec![];
+    export.write(&mut serialized).unwrap();
+    let read_back = AccountExport::read(&serialized[..]).unwrap();
+    assert_eq!(read_back.birthday(), Some(1234));
+    assert_eq!(read_back.network, NetworkKind::Testnet);
+    assert_eq!(read_back.public_address(), key.public_address());
+    assert!(!read_back.should_scan(1233));
+    assert!(read_back.should_scan(1234));
+
+    let view_only = AccountExport::view_only(&key, None);
+    let mut serialized = vec![];
+    view_only.write(&mut serialized).unwrap();
+    let read_back = AccountExport::read(&serialized[..]).unwrap();
+    assert!(read_back.is_view_only());
+    assert_eq!(read_back.birthday(), None);
+    assert_eq!(read_back.scan_start(), 1);
+    assert_eq!(read_back.public_address(), key.public_address());
+
+    // The incoming view key has to match the rest of the view keys
+    let ivk_start = 1 + 1 + 1 + 64;
+    serialized[ivk_start] ^= 1;
+    assert!(AccountExport::read(&serialized[..]).is_err());
+}
+
+#[test]
+fn test_asset_view_key() {
+    let business_key = SaplingKey::generate_key();
+    let stablecoin = *Asset::new(business_key.public_address(), "stablecoin", "")
+        .unwrap()
+        .id();
+    let asset_key = business_key.derive_asset_key(&stablecoin).unwrap();
+    let auditor_key = business_key.asset_view_key(&stablecoin).unwrap();
+
+    // Derivation is deterministic, and unique to the asset
+    assert_eq!(
+        business_key.asset_address(&stablecoin).unwrap(),
+        auditor_key.public_address()
+    );
+    assert_eq!(asset_key.public_address(), auditor_key.public_address());
+    assert_ne!(
+        business_key.asset_address(&NATIVE_ASSET).unwrap(),
+        auditor_key.public_address()
+    );
+    assert_ne!(business_key.public_address(), auditor_key.public_address());
+
+    let encrypt = |spender_key: &SaplingKey, owner: PublicAddress, asset_id| {
+        let note = Note::new(owner, 10, "", asset_id, spender_key.public_address());
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+        MerkleNote::new(
+            spender_key,
+            &note,
+            &value_commitment,
+            &EphemeralKeyPair::new(),
+        )
+    };
+
+    let customer_key = SaplingKey::generate_key();
+
+    // Stablecoins received at, and sent from, the asset address are disclosed
+    let received = encrypt(&customer_key, auditor_key.public_address(), stablecoin);
+    let note = auditor_key.decrypt_note_for_owner(&received).unwrap();
+    assert_eq!(note.asset_id, stablecoin);
+    let sent = encrypt(&asset_key, customer_key.public_address(), stablecoin);
+    assert!(auditor_key.decrypt_note_for_spender(&sent).is_ok());
+
+    // Native coins are not, even when they're held by the asset key to pay fees
+    let fees = encrypt(&customer_key, auditor_key.public_address(), NATIVE_ASSET);
+    assert!(matches!(
+        auditor_key.decrypt_note_for_owner(&fees),
+        Err(IronfishError::InvalidDecryptionKey)
+    ));
+    let holdings = encrypt(&customer_key, business_key.public_address(), NATIVE_ASSET);
+    assert!(auditor_key.decrypt_note_for_owner(&holdings).is_err());
+    let spent = encrypt(&business_key, customer_key.public_address(), stablecoin);
+    assert!(auditor_key.decrypt_note_for_spender(&spent).is_err());
+
+    let read_back = AssetViewKey::from_hex(&auditor_key.hex_key()).unwrap();
+    assert_eq!(read_back.asset_id(), &stablecoin);
+    assert_eq!(read_back.public_address(), auditor_key.public_address());
+    assert!(AssetViewKey::from_hex(&auditor_key.hex_key()[2..]).is_err());
 }
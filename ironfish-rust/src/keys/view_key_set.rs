@@ -0,0 +1,216 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Trial decryption of notes with many incoming view keys at once, for
+//! wallets holding many accounts, or the keys of several epochs of a
+//! rotated account, that scan a single stream of notes.
+//!
+//! Every key multiplies the same ephemeral public key of a note, so the
+//! small multiples of that point are computed once per note, and each key
+//! then needs a quarter of the additions of a plain multiplication. The
+//! addresses of the keys are derived once, when they're added to the set.
+
+use group::GroupEncoding;
+use jubjub::{ExtendedNielsPoint, ExtendedPoint, SubgroupPoint};
+use subtle::{ConditionallySelectable, ConstantTimeEq};
+
+use crate::{errors::IronfishError, merkle_note::MerkleNote, metrics::record, note::Note};
+
+use super::{hash_shared_point, IncomingViewKey, PublicAddress};
+
+/// Bits of the scalar handled by every addition
+const WINDOW_BITS: usize = 4;
+
+/// An ephemeral public key with its multiples from 0 to 15, to compute the
+/// shared secrets of many keys with it
+pub struct PreparedEphemeralKey {
+    bytes: [u8; 32],
+    multiples: [ExtendedNielsPoint; 1 << WINDOW_BITS],
+}
+
+impl PreparedEphemeralKey {
+    pub fn new(ephemeral_public_key: &SubgroupPoint) -> Self {
+        let point = ExtendedPoint::from(*ephemeral_public_key).to_niels();
+
+        let mut multiples = [ExtendedNielsPoint::identity(); 1 << WINDOW_BITS];
+        let mut multiple = ExtendedPoint::identity();
+        for entry in multiples.iter_mut().skip(1) {
+            multiple = &multiple + &point;
+            *entry = multiple.to_niels();
+        }
+
+        PreparedEphemeralKey {
+            bytes: ephemeral_public_key.to_bytes(),
+            multiples,
+        }
+    }
+
+    /// The shared secret of the key with this ephemeral public key, the same
+    /// as [`IncomingViewKey::shared_secret`]
+    pub(crate) fn shared_secret(&self, key: &IncomingViewKey) -> [u8; 32] {
+        hash_shared_point(&self.multiply(&key.view_key).to_bytes(), &self.bytes)
+    }
+
+    /// Fixed window multiplication, in the same time for every scalar
+    fn multiply(&self, scalar: &jubjub::Fr) -> ExtendedPoint {
+        let mut product = ExtendedPoint::identity();
+        for byte in scalar.to_bytes().iter().rev() {
+            for window in [byte >> WINDOW_BITS, byte & 0x0f] {
+                for _ in 0..WINDOW_BITS {
+                    product = product.double();
+                }
+                product = &product + &self.select(window);
+            }
+        }
+
+        product
+    }
+
+    /// The multiple at the given index, reading every one of them so that
+    /// the index can't be told from memory accesses
+    fn select(&self, index: u8) -> ExtendedNielsPoint {
+        let mut selected = ExtendedNielsPoint::identity();
+        for (i, multiple) in self.multiples.iter().enumerate() {
+            selected.conditional_assign(multiple, (i as u8).ct_eq(&index));
+        }
+
+        selected
+    }
+}
+
+/// Incoming view keys to trial decrypt notes with, each with its address
+#[derive(Clone, Default)]
+pub struct IncomingViewKeySet {
+    keys: Vec<(IncomingViewKey, PublicAddress)>,
+}
+
+impl IncomingViewKeySet {
+    pub fn new() -> Self {
+        IncomingViewKeySet::default()
+    }
+
+    /// Add a key, returning its index in the set
+    pub fn push(&mut self, key: IncomingViewKey) -> usize {
+        let address = key.public_address();
+        self.keys.push((key, address));
+        self.keys.len() - 1
+    }
+
+    pub fn get(&self, index: usize) -> Option<&IncomingViewKey> {
+        self.keys.get(index).map(|(key, _)| key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Decrypt the note with whichever key in the set it was sent to,
+    /// returning the index of that key with the note. Every key is tried,
+    /// even after one matched, so this takes the same time whichever key,
+    /// if any, the note belongs to; see
+    /// [`MerkleNote::decrypt_note_for_owner`].
+    pub fn decrypt_note(&self, merkle_note: &MerkleNote) -> Result<(usize, Note), IronfishError> {
+        let prepared = PreparedEphemeralKey::new(&merkle_note.ephemeral_public_key);
+
+        let mut decrypted = None;
+        for (index, (key, address)) in self.keys.iter().enumerate() {
+            let shared_secret = prepared.shared_secret(key);
+            let (note, is_valid) = Note::decrypt_constant_time(
+                merkle_note.aead_version,
+                *address,
+                &shared_secret,
+                &merkle_note.encrypted_note,
+                merkle_note.note_commitment,
+            )?;
+
+            let is_valid = bool::from(is_valid);
+            record(|sink| sink.note_decryption_attempted(is_valid));
+
+            if is_valid && decrypted.is_none() {
+                decrypted = Some((index, note));
+            }
+        }
+
+        decrypted.ok_or(IronfishError::InvalidDecryptionKey)
+    }
+}
+
+impl FromIterator<IncomingViewKey> for IncomingViewKeySet {
+    fn from_iter<I: IntoIterator<Item = IncomingViewKey>>(keys: I) -> Self {
+        let mut set = IncomingViewKeySet::new();
+        for key in keys {
+            set.push(key);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{IncomingViewKeySet, PreparedEphemeralKey};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::{EphemeralKeyPair, SaplingKey},
+        merkle_note::MerkleNote,
+        note::Note,
+    };
+    use ironfish_zkp::primitives::ValueCommitment;
+
+    #[test]
+    fn test_prepared_shared_secret() {
+        let ephemeral = EphemeralKeyPair::new();
+        let prepared = PreparedEphemeralKey::new(ephemeral.public());
+
+        for _ in 0..8 {
+            let key = SaplingKey::generate_key();
+            assert_eq!(
+                prepared.shared_secret(key.incoming_view_key()),
+                key.incoming_view_key().shared_secret(ephemeral.public())
+            );
+        }
+    }
+
+    #[test]
+    fn test_decrypt_note_with_key_set() {
+        let spender_key = SaplingKey::generate_key();
+        let keys: Vec<SaplingKey> = (0..4).map(|_| SaplingKey::generate_key()).collect();
+
+        let note = Note::new(
+            keys[2].public_address(),
+            42,
+            "epochs",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+        let merkle_note = MerkleNote::new(
+            &spender_key,
+            &note,
+            &value_commitment,
+            &EphemeralKeyPair::new(),
+        );
+
+        let set: IncomingViewKeySet = keys
+            .iter()
+            .map(|key| key.incoming_view_key().clone())
+            .collect();
+        let (index, decrypted) = set.decrypt_note(&merkle_note).unwrap();
+        assert_eq!(index, 2);
+        assert_eq!(decrypted.commitment(), note.commitment());
+
+        let others: IncomingViewKeySet = [0, 1, 3]
+            .iter()
+            .map(|&i| keys[i].incoming_view_key().clone())
+            .collect();
+        assert!(matches!(
+            others.decrypt_note(&merkle_note),
+            Err(IronfishError::InvalidDecryptionKey)
+        ));
+    }
+}
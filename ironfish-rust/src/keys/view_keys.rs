@@ -15,16 +15,16 @@
 use super::PublicAddress;
 use crate::{
     errors::IronfishError,
+    primitives::kdf_sapling,
     serializing::{bytes_to_hex, hex_to_bytes, read_scalar},
 };
 use bip39::{Language, Mnemonic};
-use blake2b_simd::Params as Blake2b;
 use group::GroupEncoding;
 use jubjub::SubgroupPoint;
 
 use std::io;
 
-const DIFFIE_HELLMAN_PERSONALIZATION: &[u8; 16] = b"Iron Fish shared";
+pub const INCOMING_VIEW_KEY_SIZE: usize = 32;
 
 /// Key that allows someone to view a transaction that you have received.
 ///
@@ -209,19 +209,14 @@ pub(crate) fn shared_secret(
     other_public_key: &SubgroupPoint,
     reference_public_key: &SubgroupPoint,
 ) -> [u8; 32] {
-    let shared_secret = (other_public_key * secret_key).to_bytes();
-    let reference_bytes = reference_public_key.to_bytes();
-
-    let mut hasher = Blake2b::new()
-        .hash_length(32)
-        .personal(DIFFIE_HELLMAN_PERSONALIZATION)
-        .to_state();
-
-    hasher.update(&shared_secret);
-    hasher.update(&reference_bytes);
-    let mut hash_result = [0; 32];
-    hash_result[..].clone_from_slice(hasher.finalize().as_ref());
-    hash_result
+    let shared_point = (other_public_key * secret_key).to_bytes();
+    hash_shared_point(&shared_point, &reference_public_key.to_bytes())
+}
+
+/// Hash the Diffie Hellman shared point with the reference public key into
+/// the final shared secret, see [`shared_secret`]
+pub(crate) fn hash_shared_point(shared_point: &[u8; 32], reference_bytes: &[u8; 32]) -> [u8; 32] {
+    kdf_sapling(shared_point, reference_bytes)
 }
 
 #[cfg(test)]
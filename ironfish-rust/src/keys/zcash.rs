@@ -0,0 +1,261 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Import of Zcash Sapling keys, for tooling that migrates users holding
+//! Sapling-era keys.
+//!
+//! Iron Fish keys are not interchangeable with Zcash keys. Both expand a
+//! 32 byte spending key into `ask`, `nsk` and `ovk` with BLAKE2b, but Iron Fish
+//! uses its own personalization, has no diversified addresses, and derives
+//! the incoming view key and nullifiers differently. An expanded Zcash key
+//! also can't be turned back into the spending key that produced it.
+//!
+//! The only sound mapping is therefore a one-way derivation: the Zcash key
+//! material is hashed, under a personalization reserved for this purpose, into
+//! a fresh Iron Fish spending key. The same Zcash key always yields the same
+//! Iron Fish key, so users only need to back up their Zcash key, but the two
+//! keys control unrelated accounts and funds are never shared between chains.
+//!
+//! The hash input is a tag byte followed by the key material:
+//!  *  `0x00 || sk` for a raw 32 byte Zcash spending key
+//!  *  `0x01 || ask || nsk || ovk || dk` for a ZIP 32 extended spending key
+//!
+//! Depth, parent fingerprint, child index and chain code of an extended key
+//! are not part of the input, since they describe the key's position in its
+//! derivation tree rather than the key itself.
+
+use super::{SaplingKey, SPEND_KEY_SIZE};
+use crate::{
+    errors::IronfishError, network::NetworkKind, personalization::ZCASH_IMPORT_PERSONALIZATION,
+};
+
+use blake2b_simd::Params as Blake2b;
+use byteorder::{LittleEndian, ReadBytesExt};
+use ff::PrimeField;
+
+use std::io;
+
+const ZCASH_SPENDING_KEY_TAG: u8 = 0;
+const ZCASH_EXTENDED_SPENDING_KEY_TAG: u8 = 1;
+
+pub const ZCASH_EXTENDED_SPENDING_KEY_SIZE: usize = 169;
+
+/// A ZIP 32 Sapling extended spending key, in its 169 byte binary encoding.
+/// This is the payload of a bech32 encoded `secret-extended-key-main` string.
+pub struct ZcashExtendedSpendingKey {
+    pub depth: u8,
+    pub parent_fvk_tag: [u8; 4],
+    pub child_index: u32,
+    pub chain_code: [u8; 32],
+    ask: jubjub::Fr,
+    nsk: jubjub::Fr,
+    ovk: [u8; 32],
+    dk: [u8; 32],
+}
+
+impl ZcashExtendedSpendingKey {
+    /// Load an extended spending key from a Read implementation. Fails if
+    /// `ask` or `nsk` are not canonical scalars, or if `ask` is zero.
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let depth = reader.read_u8()?;
+        let mut parent_fvk_tag = [0; 4];
+        reader.read_exact(&mut parent_fvk_tag)?;
+        let child_index = reader.read_u32::<LittleEndian>()?;
+        let mut chain_code = [0; 32];
+        reader.read_exact(&mut chain_code)?;
+
+        let ask = read_canonical_scalar(&mut reader)?;
+        if ask == jubjub::Fr::zero() {
+            return Err(IronfishError::InvalidSigningKey);
+        }
+        let nsk = read_canonical_scalar(&mut reader)?;
+
+        let mut ovk = [0; 32];
+        reader.read_exact(&mut ovk)?;
+        let mut dk = [0; 32];
+        reader.read_exact(&mut dk)?;
+
+        Ok(ZcashExtendedSpendingKey {
+            depth,
+            parent_fvk_tag,
+            child_index,
+            chain_code,
+            ask,
+            nsk,
+            ovk,
+            dk,
+        })
+    }
+
+    pub fn from_bytes(
+        bytes: &[u8; ZCASH_EXTENDED_SPENDING_KEY_SIZE],
+    ) -> Result<Self, IronfishError> {
+        Self::read(&bytes[..])
+    }
+}
+
+impl SaplingKey {
+    /// Derive an Iron Fish key from a raw 32 byte Zcash Sapling spending key.
+    /// See the [module documentation](crate::keys::zcash) for how the two relate.
+    pub fn from_zcash_spending_key(
+        zcash_spending_key: [u8; 32],
+    ) -> Result<SaplingKey, IronfishError> {
+        SaplingKey::from_zcash_spending_key_for_network(zcash_spending_key, NetworkKind::Mainnet)
+    }
+
+    /// Derive an Iron Fish key for the given network from a raw 32 byte
+    /// Zcash Sapling spending key. The spending key is the same on every
+    /// network, the addresses aren't.
+    pub fn from_zcash_spending_key_for_network(
+        zcash_spending_key: [u8; 32],
+        network: NetworkKind,
+    ) -> Result<SaplingKey, IronfishError> {
+        SaplingKey::new_for_network(
+            derive_imported_key(ZCASH_SPENDING_KEY_TAG, &[&zcash_spending_key]),
+            network,
+        )
+    }
+
+    /// Derive an Iron Fish key from a ZIP 32 Zcash Sapling extended spending
+    /// key. See the [module documentation](crate::keys::zcash) for how the two
+    /// relate.
+    pub fn from_zcash_extended_spending_key(
+        zcash_key: &ZcashExtendedSpendingKey,
+    ) -> Result<SaplingKey, IronfishError> {
+        SaplingKey::from_zcash_extended_spending_key_for_network(zcash_key, NetworkKind::Mainnet)
+    }
+
+    /// Derive an Iron Fish key for the given network from a ZIP 32 Zcash
+    /// Sapling extended spending key
+    pub fn from_zcash_extended_spending_key_for_network(
+        zcash_key: &ZcashExtendedSpendingKey,
+        network: NetworkKind,
+    ) -> Result<SaplingKey, IronfishError> {
+        SaplingKey::new_for_network(
+            derive_imported_key(
+                ZCASH_EXTENDED_SPENDING_KEY_TAG,
+                &[
+                    zcash_key.ask.to_repr().as_ref(),
+                    zcash_key.nsk.to_repr().as_ref(),
+                    &zcash_key.ovk,
+                    &zcash_key.dk,
+                ],
+            ),
+            network,
+        )
+    }
+}
+
+fn derive_imported_key(tag: u8, parts: &[&[u8]]) -> [u8; SPEND_KEY_SIZE] {
+    let mut hasher = Blake2b::new()
+        .hash_length(SPEND_KEY_SIZE)
+        .personal(ZCASH_IMPORT_PERSONALIZATION)
+        .to_state();
+
+    hasher.update(&[tag]);
+    for part in parts {
+        hasher.update(part);
+    }
+
+    let mut spending_key = [0; SPEND_KEY_SIZE];
+    spending_key.copy_from_slice(hasher.finalize().as_bytes());
+    spending_key
+}
+
+fn read_canonical_scalar<R: io::Read>(mut reader: R) -> Result<jubjub::Fr, IronfishError> {
+    let mut repr = [0; 32];
+    reader.read_exact(&mut repr)?;
+
+    Option::from(jubjub::Fr::from_repr(repr)).ok_or(IronfishError::InvalidData)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ZcashExtendedSpendingKey, ZCASH_EXTENDED_SPENDING_KEY_SIZE};
+    use crate::{NetworkKind, SaplingKey};
+    use ff::Field;
+    use rand::{thread_rng, Rng};
+
+    fn random_extended_key_bytes() -> [u8; ZCASH_EXTENDED_SPENDING_KEY_SIZE] {
+        let mut bytes = [0; ZCASH_EXTENDED_SPENDING_KEY_SIZE];
+        thread_rng().fill(&mut bytes[..]);
+        bytes[41..73].copy_from_slice(&jubjub::Fr::random(thread_rng()).to_bytes());
+        bytes[73..105].copy_from_slice(&jubjub::Fr::random(thread_rng()).to_bytes());
+        bytes
+    }
+
+    #[test]
+    fn test_zcash_spending_key_import_is_deterministic() {
+        let zcash_key: [u8; 32] = thread_rng().gen();
+
+        let key = SaplingKey::from_zcash_spending_key(zcash_key).unwrap();
+        let key2 = SaplingKey::from_zcash_spending_key(zcash_key).unwrap();
+        assert_eq!(key.spending_key(), key2.spending_key());
+
+        // The imported key is domain separated from using the bytes directly
+        let direct_key = SaplingKey::new(zcash_key).unwrap();
+        assert_ne!(key.spending_key(), direct_key.spending_key());
+        assert_ne!(key.public_address(), direct_key.public_address());
+    }
+
+    #[test]
+    fn test_zcash_extended_spending_key_import() {
+        let bytes = random_extended_key_bytes();
+        let zcash_key = ZcashExtendedSpendingKey::from_bytes(&bytes).unwrap();
+        let key = SaplingKey::from_zcash_extended_spending_key(&zcash_key).unwrap();
+
+        // Changing the position in the derivation tree doesn't change the key
+        let mut moved_bytes = bytes;
+        moved_bytes[0] = moved_bytes[0].wrapping_add(1);
+        moved_bytes[9..41].copy_from_slice(&[7; 32]);
+        let moved_key = ZcashExtendedSpendingKey::from_bytes(&moved_bytes).unwrap();
+        let key2 = SaplingKey::from_zcash_extended_spending_key(&moved_key).unwrap();
+        assert_eq!(key.spending_key(), key2.spending_key());
+
+        // Changing the key material does
+        let mut other_bytes = bytes;
+        other_bytes[137] ^= 1;
+        let other_key = ZcashExtendedSpendingKey::from_bytes(&other_bytes).unwrap();
+        let key3 = SaplingKey::from_zcash_extended_spending_key(&other_key).unwrap();
+        assert_ne!(key.spending_key(), key3.spending_key());
+    }
+
+    #[test]
+    fn test_zcash_import_for_network() {
+        let zcash_key: [u8; 32] = thread_rng().gen();
+        let mainnet_key = SaplingKey::from_zcash_spending_key(zcash_key).unwrap();
+        assert_eq!(mainnet_key.network(), NetworkKind::Mainnet);
+
+        let testnet_key =
+            SaplingKey::from_zcash_spending_key_for_network(zcash_key, NetworkKind::Testnet)
+                .unwrap();
+        assert_eq!(testnet_key.network(), NetworkKind::Testnet);
+        assert_eq!(testnet_key.spending_key(), mainnet_key.spending_key());
+        assert_ne!(testnet_key.public_address(), mainnet_key.public_address());
+
+        let zcash_key = ZcashExtendedSpendingKey::from_bytes(&random_extended_key_bytes()).unwrap();
+        let mainnet_key = SaplingKey::from_zcash_extended_spending_key(&zcash_key).unwrap();
+        let testnet_key = SaplingKey::from_zcash_extended_spending_key_for_network(
+            &zcash_key,
+            NetworkKind::Testnet,
+        )
+        .unwrap();
+        assert_eq!(testnet_key.network(), NetworkKind::Testnet);
+        assert_eq!(testnet_key.spending_key(), mainnet_key.spending_key());
+    }
+
+    #[test]
+    fn test_zcash_extended_spending_key_validation() {
+        let mut bytes = random_extended_key_bytes();
+        bytes[41..73].copy_from_slice(&[0xff; 32]);
+        assert!(ZcashExtendedSpendingKey::from_bytes(&bytes).is_err());
+
+        let mut bytes = random_extended_key_bytes();
+        bytes[41..73].copy_from_slice(&[0; 32]);
+        assert!(ZcashExtendedSpendingKey::from_bytes(&bytes).is_err());
+
+        let bytes = random_extended_key_bytes();
+        assert!(ZcashExtendedSpendingKey::read(&bytes[..100]).is_err());
+    }
+}
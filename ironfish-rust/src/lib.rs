@@ -4,25 +4,66 @@
 use bellman::groth16;
 use bls12_381::Bls12;
 
+#[cfg(feature = "params-fetch")]
+use std::path::Path;
+
 pub mod assets;
+pub mod block_inclusion;
+pub mod canonical_json;
+pub mod compact_note;
+pub mod compact_witness;
+#[cfg(feature = "conformance")]
+pub mod conformance;
 pub mod errors;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 pub mod keys;
+pub mod memo_template;
 pub mod merkle_note;
 pub mod merkle_note_hash;
+pub mod metrics;
 pub mod mining;
 pub mod nacl;
+pub mod network;
 pub mod note;
+pub mod note_kem;
+pub mod nullifier_filter;
+pub mod parameter_sets;
+#[cfg(feature = "params-fetch")]
+pub mod params_fetch;
+pub mod payment_proof;
+pub mod personalization;
+pub mod primitives;
+pub mod proof_system;
+pub mod proving_params;
+pub mod randomness;
+pub mod rerandomization;
 pub mod rolling_filter;
 pub mod sapling_bls12;
+pub mod sapling_snapshot;
+pub mod scan_cursor;
+pub mod scan_server;
+pub mod screening;
 pub mod serializing;
 pub mod signal_catcher;
+pub mod spendable_note;
 pub mod transaction;
+pub mod tree_position;
+pub mod tree_snapshot;
 pub mod util;
+pub mod value_range;
+pub mod wallet_backup;
+pub mod wire;
 pub mod witness;
+#[cfg(any(test, feature = "testing"))]
+pub mod witness_fuzzing;
+use errors::IronfishError;
+use proving_params::ProvingParams;
 pub use {
     keys::{IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey, ViewKey},
     merkle_note::MerkleNote,
     merkle_note_hash::MerkleNoteHash,
+    network::NetworkKind,
     note::Note,
     transaction::{
         outputs::OutputDescription, spends::SpendDescription, ProposedTransaction, Transaction,
@@ -43,11 +84,12 @@ pub use ironfish_zkp::primitives::ValueCommitment;
 // Though the *_params have a verifying key on them, they are not the prepared verifying keys,
 // so we store the prepared keys separately at the time of loading the params.
 //
-// The values are all loaded from a file in serialized form.
+// The proving keys of each circuit are only parsed when a proof of it is built, see
+// `proving_params`, so processes that only verify never hold them in memory.
 pub struct Sapling {
-    spend_params: groth16::Parameters<Bls12>,
-    output_params: groth16::Parameters<Bls12>,
-    mint_params: groth16::Parameters<Bls12>,
+    spend_params: ProvingParams,
+    output_params: ProvingParams,
+    mint_params: ProvingParams,
     spend_verifying_key: groth16::PreparedVerifyingKey<Bls12>,
     output_verifying_key: groth16::PreparedVerifyingKey<Bls12>,
     mint_verifying_key: groth16::PreparedVerifyingKey<Bls12>,
@@ -56,6 +98,7 @@ pub struct Sapling {
 impl Sapling {
     /// Initialize a Sapling instance and prepare for proving. Load the parameters from files
     /// at a known location (`$OUT_DIR/sapling_params`).
+    #[cfg(not(feature = "params-fetch"))]
     pub fn load() -> Self {
         let spend_bytes = include_bytes!(concat!(
             env!("OUT_DIR"),
@@ -70,10 +113,68 @@ impl Sapling {
             "/sapling_params/sapling-mint.params"
         ));
 
-        let spend_params = Sapling::load_params(&spend_bytes[..]);
-        let output_params = Sapling::load_params(&output_bytes[..]);
-        let mint_params = Sapling::load_params(&mint_bytes[..]);
+        let spend_params = ProvingParams::from_bundled(spend_bytes).unwrap();
+        let output_params = ProvingParams::from_bundled(output_bytes).unwrap();
+        let mint_params = ProvingParams::from_bundled(mint_bytes).unwrap();
+
+        Sapling::from_proving_params(spend_params, output_params, mint_params)
+    }
+
+    /// Initialize a Sapling instance and prepare for proving. The parameters
+    /// aren't bundled with the `params-fetch` feature, so they're loaded from
+    /// the cache directory of [`params_fetch::ParamsFetcher`], downloading
+    /// them first if they're missing.
+    #[cfg(feature = "params-fetch")]
+    pub fn load() -> Self {
+        let fetcher = params_fetch::ParamsFetcher::new().unwrap();
+        Sapling::load_from_dir(fetcher.fetch_all().unwrap()).unwrap()
+    }
+
+    /// Initialize a Sapling instance from parameter files in the given
+    /// directory, such as one populated by `params_fetch::ParamsFetcher`.
+    /// The files are expected to have the same names as the bundled ones,
+    /// and fail with [`IronfishError::InvalidParamsChecksum`] unless they
+    /// match the same SHA-512 checksums. They're memory-mapped, and must not
+    /// change while the instance is alive.
+    #[cfg(feature = "params-fetch")]
+    pub fn load_from_dir(dir: &Path) -> Result<Self, IronfishError> {
+        let map_file = |name: &str| {
+            ProvingParams::map_file(&dir.join(name), &params_fetch::expected_checksum(name)?)
+        };
+        let spend_params = map_file("sapling-spend.params")?;
+        let output_params = map_file("sapling-output.params")?;
+        let mint_params = map_file("sapling-mint.params")?;
+
+        Ok(Sapling::from_proving_params(
+            spend_params,
+            output_params,
+            mint_params,
+        ))
+    }
+
+    /// Prove with the given, already parsed, parameters, keeping the prepared
+    /// verifying keys of these. The parameters must have the same verifying
+    /// keys.
+    fn with_params(
+        self,
+        spend_params: groth16::Parameters<Bls12>,
+        output_params: groth16::Parameters<Bls12>,
+        mint_params: groth16::Parameters<Bls12>,
+    ) -> Self {
+        Sapling {
+            spend_params: ProvingParams::from_params(spend_params),
+            output_params: ProvingParams::from_params(output_params),
+            mint_params: ProvingParams::from_params(mint_params),
+            ..self
+        }
+    }
 
+    /// Prepare the verifying keys for the given parameters.
+    fn from_proving_params(
+        spend_params: ProvingParams,
+        output_params: ProvingParams,
+        mint_params: ProvingParams,
+    ) -> Self {
         let spend_vk = groth16::prepare_verifying_key(&spend_params.vk);
         let output_vk = groth16::prepare_verifying_key(&output_params.vk);
         let mint_vk = groth16::prepare_verifying_key(&mint_params.vk);
@@ -88,12 +189,12 @@ impl Sapling {
         }
     }
 
-    /// Load sapling parameters from a provided filename. The parameters are huge and take a
-    /// couple seconds to load. They primarily contain the "toxic waste" for a specific sapling
-    /// curve.
-    ///
-    /// NOTE: If this is stupidly slow for you, try compiling in --release mode
-    fn load_params(bytes: &[u8]) -> groth16::Parameters<Bls12> {
-        groth16::Parameters::read(bytes, false).unwrap()
+    /// Drop the parsed proving keys of every circuit, to give back their
+    /// memory until the next proof. Proving keys that were restored from a
+    /// snapshot can't be parsed again, so they're kept.
+    pub fn release_proving_params(&self) {
+        self.spend_params.release();
+        self.output_params.release();
+        self.mint_params.release();
     }
 }
@@ -0,0 +1,236 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Memos made of typed fields, so that a merchant receiving a payment can
+//! read the invoice it pays without guessing at free text.
+//!
+//! A structured memo starts with [`TEMPLATE_MARKER`], a byte that never
+//! starts UTF-8 text, so it can't be mistaken for a text memo, and then the
+//! version of the layout. The fields that are set follow, in the order of
+//! their tags, each as its tag then its value; the rest of the memo is
+//! zeros. There is a single encoding of every [`MemoTemplate`], and
+//! [`MemoTemplate::parse`] rejects any other.
+
+use std::str;
+
+use crate::{
+    errors::IronfishError,
+    note::{Memo, MEMO_SIZE},
+};
+
+/// First byte of a structured memo
+pub const TEMPLATE_MARKER: u8 = 0xff;
+
+/// Version of the layout of the fields, bumped whenever it changes
+pub const TEMPLATE_VERSION: u8 = 1;
+
+/// Bytes taken by the marker and the version
+const HEADER_SIZE: usize = 2;
+
+const INVOICE_ID_TAG: u8 = 1;
+const CATEGORY_TAG: u8 = 2;
+const TEXT_TAG: u8 = 3;
+
+/// The fields of a structured memo. Each of them is optional, but they have
+/// to fit in [`MEMO_SIZE`] bytes together: with the other two fields set,
+/// the text can be 16 bytes long.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoTemplate {
+    /// The invoice the payment settles
+    pub invoice_id: Option<u64>,
+
+    /// What the payment is for, in codes agreed on by the two sides
+    pub category: Option<u16>,
+
+    pub text: Option<String>,
+}
+
+impl MemoTemplate {
+    pub fn new() -> Self {
+        MemoTemplate::default()
+    }
+
+    /// Whether the memo was written from a template, whether or not it's a
+    /// valid one
+    pub fn is_template(memo: &Memo) -> bool {
+        memo.0[0] == TEMPLATE_MARKER
+    }
+
+    /// Pack the fields into a memo. Fails with
+    /// [`IronfishError::MemoTooLarge`] if they don't fit.
+    pub fn to_memo(&self) -> Result<Memo, IronfishError> {
+        let mut bytes = vec![TEMPLATE_MARKER, TEMPLATE_VERSION];
+
+        if let Some(invoice_id) = self.invoice_id {
+            bytes.push(INVOICE_ID_TAG);
+            bytes.extend_from_slice(&invoice_id.to_le_bytes());
+        }
+        if let Some(category) = self.category {
+            bytes.push(CATEGORY_TAG);
+            bytes.extend_from_slice(&category.to_le_bytes());
+        }
+        if let Some(text) = &self.text {
+            let length = u8::try_from(text.len()).map_err(|_| IronfishError::MemoTooLarge)?;
+            bytes.push(TEXT_TAG);
+            bytes.push(length);
+            bytes.extend_from_slice(text.as_bytes());
+        }
+
+        if bytes.len() > MEMO_SIZE {
+            return Err(IronfishError::MemoTooLarge);
+        }
+
+        let mut memo = [0; MEMO_SIZE];
+        memo[..bytes.len()].copy_from_slice(&bytes);
+
+        Ok(Memo(memo))
+    }
+
+    /// Read the fields of a memo written by [`MemoTemplate::to_memo`].
+    /// Fails with [`IronfishError::InvalidData`] for any other memo,
+    /// including text memos and structured memos of another version.
+    pub fn parse(memo: &Memo) -> Result<Self, IronfishError> {
+        if memo.0[..HEADER_SIZE] != [TEMPLATE_MARKER, TEMPLATE_VERSION] {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let mut template = MemoTemplate::new();
+        let mut last_tag = 0;
+        let mut rest = &memo.0[HEADER_SIZE..];
+
+        while let Some((&tag, value)) = rest.split_first() {
+            // Zeros fill the memo once the fields end
+            if tag == 0 {
+                if value.iter().any(|&byte| byte != 0) {
+                    return Err(IronfishError::InvalidData);
+                }
+                break;
+            }
+            if tag <= last_tag {
+                return Err(IronfishError::InvalidData);
+            }
+            last_tag = tag;
+
+            rest = match tag {
+                INVOICE_ID_TAG => {
+                    let (invoice_id, rest) = split(value, 8)?;
+                    template.invoice_id = Some(u64::from_le_bytes(invoice_id.try_into().unwrap()));
+                    rest
+                }
+                CATEGORY_TAG => {
+                    let (category, rest) = split(value, 2)?;
+                    template.category = Some(u16::from_le_bytes(category.try_into().unwrap()));
+                    rest
+                }
+                TEXT_TAG => {
+                    let (length, rest) = split(value, 1)?;
+                    let (text, rest) = split(rest, usize::from(length[0]))?;
+                    let text = str::from_utf8(text).map_err(|_| IronfishError::InvalidData)?;
+                    template.text = Some(text.to_string());
+                    rest
+                }
+                _ => return Err(IronfishError::InvalidData),
+            };
+        }
+
+        Ok(template)
+    }
+}
+
+/// The first `size` bytes, and what follows them
+fn split(bytes: &[u8], size: usize) -> Result<(&[u8], &[u8]), IronfishError> {
+    match bytes.len() >= size {
+        true => Ok(bytes.split_at(size)),
+        false => Err(IronfishError::InvalidData),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoTemplate;
+    use crate::{
+        errors::IronfishError,
+        note::{Memo, MEMO_SIZE},
+    };
+
+    #[test]
+    fn test_memo_template_round_trip() {
+        let template = MemoTemplate {
+            invoice_id: Some(0x0102_0304_0506_0708),
+            category: Some(7),
+            text: Some("order #1234 ok".to_string()),
+        };
+        let memo = template.to_memo().unwrap();
+        assert!(MemoTemplate::is_template(&memo));
+        assert_eq!(MemoTemplate::parse(&memo).unwrap(), template);
+
+        let empty = MemoTemplate::new().to_memo().unwrap();
+        assert_eq!(MemoTemplate::parse(&empty).unwrap(), MemoTemplate::new());
+
+        // Text memos aren't structured
+        let text = Memo::from("invoice 1234");
+        assert!(!MemoTemplate::is_template(&text));
+        assert!(matches!(
+            MemoTemplate::parse(&text),
+            Err(IronfishError::InvalidData)
+        ));
+        assert!(MemoTemplate::parse(&Memo::default()).is_err());
+    }
+
+    #[test]
+    fn test_memo_template_size() {
+        let mut template = MemoTemplate {
+            invoice_id: Some(1),
+            category: Some(2),
+            text: Some("x".repeat(16)),
+        };
+        let memo = template.to_memo().unwrap();
+        assert_eq!(memo.0[MEMO_SIZE - 1], b'x');
+
+        template.text = Some("x".repeat(17));
+        assert!(matches!(
+            template.to_memo(),
+            Err(IronfishError::MemoTooLarge)
+        ));
+    }
+
+    #[test]
+    fn test_memo_template_is_canonical() {
+        let template = MemoTemplate {
+            invoice_id: Some(5),
+            category: Some(9),
+            text: None,
+        };
+        let memo = template.to_memo().unwrap();
+
+        let mutated = |offset: usize, byte: u8| {
+            let mut bytes = memo.0;
+            bytes[offset] = byte;
+            MemoTemplate::parse(&Memo(bytes))
+        };
+
+        // Another version
+        assert!(mutated(1, 2).is_err());
+        // Fields out of order or repeated
+        assert!(mutated(2, 2).is_err());
+        assert!(mutated(11, 1).is_err());
+        // Unknown tag
+        assert!(mutated(11, 4).is_err());
+        // Bytes after the fields
+        assert!(mutated(MEMO_SIZE - 1, 1).is_err());
+
+        // Text that isn't UTF-8, or runs past the end of the memo
+        let text = MemoTemplate {
+            text: Some("ab".to_string()),
+            ..MemoTemplate::new()
+        };
+        let memo = text.to_memo().unwrap();
+        let mut bytes = memo.0;
+        bytes[4] = 0xff;
+        assert!(MemoTemplate::parse(&Memo(bytes)).is_err());
+        let mut bytes = memo.0;
+        bytes[3] = 30;
+        assert!(MemoTemplate::parse(&Memo(bytes)).is_err());
+    }
+}
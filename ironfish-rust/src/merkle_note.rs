@@ -2,30 +2,71 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use crate::{errors::IronfishError, keys::EphemeralKeyPair, serializing::read_point};
+use crate::{
+    errors::IronfishError,
+    keys::EphemeralKeyPair,
+    metrics::record,
+    note_kem::{DefaultNoteKem, NoteKem},
+    primitives::prf_ock,
+    serializing::{read_point, read_point_constant_time},
+};
 
 /// Implement a merkle note to store all the values that need to go into a merkle tree.
 /// A tree containing these values can serve as a snapshot of the entire chain.
 use super::{
     keys::{shared_secret, IncomingViewKey, OutgoingViewKey, PublicAddress, SaplingKey},
     note::{Note, ENCRYPTED_NOTE_SIZE},
-    serializing::{aead, read_scalar},
+    serializing::{
+        aead::{self, AeadVersion},
+        read_scalar,
+    },
+    transaction::TRANSACTION_VERSION,
+    tree_position::NotePosition,
     witness::{WitnessNode, WitnessTrait},
     MerkleNoteHash,
 };
 
-use blake2b_simd::Params as Blake2b;
 use bls12_381::Scalar;
-use ff::PrimeField;
+use ff::{Field, PrimeField};
 use group::GroupEncoding;
-use ironfish_zkp::primitives::ValueCommitment;
+use ironfish_zkp::{constants::PUBLIC_KEY_GENERATOR, primitives::ValueCommitment};
 use jubjub::{ExtendedPoint, SubgroupPoint};
 
-use std::{convert::TryInto, io};
+use std::{convert::TryInto, io, ops::Range};
 
 pub const ENCRYPTED_SHARED_KEY_SIZE: usize = 64;
 
+/// Size of the note encryption keys, encrypted for the spender with their
+/// MAC. Referred to as `out_ciphertext` in the literature.
 pub const NOTE_ENCRYPTION_KEY_SIZE: usize = ENCRYPTED_SHARED_KEY_SIZE + aead::MAC_SIZE;
+
+pub const VALUE_COMMITMENT_SIZE: usize = 32;
+pub const NOTE_COMMITMENT_SIZE: usize = 32;
+pub const EPHEMERAL_PUBLIC_KEY_SIZE: usize = 32;
+
+/// Size of the note encrypted for its owner with its MAC. Referred to as
+/// `enc_ciphertext` in the literature.
+pub const ENCRYPTED_NOTE_CIPHERTEXT_SIZE: usize = ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE;
+
+/// Size of a serialized [`MerkleNote`]: value commitment, note commitment,
+/// ephemeral public key, encrypted note and encrypted note keys.
+pub const MERKLE_NOTE_SIZE: usize = VALUE_COMMITMENT_SIZE
+    + NOTE_COMMITMENT_SIZE
+    + EPHEMERAL_PUBLIC_KEY_SIZE
+    + ENCRYPTED_NOTE_CIPHERTEXT_SIZE
+    + NOTE_ENCRYPTION_KEY_SIZE;
+
+/// Where each part is in a serialized [`MerkleNote`], see [`MerkleNoteParts`]
+pub const VALUE_COMMITMENT_RANGE: Range<usize> = 0..VALUE_COMMITMENT_SIZE;
+pub const NOTE_COMMITMENT_RANGE: Range<usize> =
+    VALUE_COMMITMENT_RANGE.end..VALUE_COMMITMENT_RANGE.end + NOTE_COMMITMENT_SIZE;
+pub const EPHEMERAL_PUBLIC_KEY_RANGE: Range<usize> =
+    NOTE_COMMITMENT_RANGE.end..NOTE_COMMITMENT_RANGE.end + EPHEMERAL_PUBLIC_KEY_SIZE;
+pub const ENCRYPTED_NOTE_RANGE: Range<usize> =
+    EPHEMERAL_PUBLIC_KEY_RANGE.end..EPHEMERAL_PUBLIC_KEY_RANGE.end + ENCRYPTED_NOTE_CIPHERTEXT_SIZE;
+pub const NOTE_ENCRYPTION_KEYS_RANGE: Range<usize> =
+    ENCRYPTED_NOTE_RANGE.end..ENCRYPTED_NOTE_RANGE.end + NOTE_ENCRYPTION_KEY_SIZE;
+
 /// The note encryption keys are used to allow the spender to
 /// read notes that they have themselves have spent.
 /// In the case of miner notes, the note is created out of thin air
@@ -37,8 +78,6 @@ pub const NOTE_ENCRYPTION_KEY_SIZE: usize = ENCRYPTED_SHARED_KEY_SIZE + aead::MA
 /// to be on the first transaction in a block.
 pub const NOTE_ENCRYPTION_MINER_KEYS: &[u8; NOTE_ENCRYPTION_KEY_SIZE] =
     b"Iron Fish note encryption miner key000000000000000000000000000000000000000000000";
-const SHARED_KEY_PERSONALIZATION: &[u8; 16] = b"Iron Fish Keyenc";
-
 #[derive(Clone)]
 pub struct MerkleNote {
     /// Randomized value commitment. Sometimes referred to as
@@ -55,13 +94,18 @@ pub struct MerkleNote {
     pub(crate) ephemeral_public_key: SubgroupPoint,
 
     /// note as encrypted by the diffie hellman public key
-    pub(crate) encrypted_note: [u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
+    pub(crate) encrypted_note: [u8; ENCRYPTED_NOTE_CIPHERTEXT_SIZE],
 
     /// Keys used to encrypt the note. These are stored in encrypted format
     /// using the spender's outgoing viewing key, and allow the spender to
     /// decrypt it. The receiver (owner) doesn't need these, as they can decrypt
     /// the note directly using their incoming viewing key.
     pub(crate) note_encryption_keys: [u8; NOTE_ENCRYPTION_KEY_SIZE],
+
+    /// Scheme the note and the note encryption keys are encrypted with. It
+    /// isn't serialized, it's implied by the version of the transaction
+    /// holding the note, see [`AeadVersion::for_transaction_version`].
+    pub(crate) aead_version: AeadVersion,
 }
 
 impl PartialEq for MerkleNote {
@@ -78,6 +122,25 @@ impl MerkleNote {
         value_commitment: &ValueCommitment,
         diffie_hellman_keys: &EphemeralKeyPair,
     ) -> MerkleNote {
+        MerkleNote::new_for_version(
+            spender_key,
+            note,
+            value_commitment,
+            diffie_hellman_keys,
+            TRANSACTION_VERSION,
+        )
+    }
+
+    /// Encrypt the note as it's encrypted in transactions of the given
+    /// version
+    pub fn new_for_version(
+        spender_key: &SaplingKey,
+        note: &Note,
+        value_commitment: &ValueCommitment,
+        diffie_hellman_keys: &EphemeralKeyPair,
+        version: u8,
+    ) -> MerkleNote {
+        let aead_version = AeadVersion::for_transaction_version(version);
         let secret_key = diffie_hellman_keys.secret();
         let public_key = diffie_hellman_keys.public();
 
@@ -92,13 +155,14 @@ impl MerkleNote {
             public_key,
         );
         let note_encryption_keys: [u8; NOTE_ENCRYPTION_KEY_SIZE] =
-            aead::encrypt(&encryption_key, &key_bytes).unwrap();
+            aead::encrypt_with_version(aead_version, &encryption_key, &key_bytes).unwrap();
 
         Self::construct(
             note,
             value_commitment,
             diffie_hellman_keys,
             note_encryption_keys,
+            aead_version,
         )
     }
 
@@ -108,6 +172,7 @@ impl MerkleNote {
         note: &Note,
         value_commitment: &ValueCommitment,
         diffie_hellman_keys: &EphemeralKeyPair,
+        version: u8,
     ) -> MerkleNote {
         let note_encryption_keys = *NOTE_ENCRYPTION_MINER_KEYS;
 
@@ -116,6 +181,7 @@ impl MerkleNote {
             value_commitment,
             diffie_hellman_keys,
             note_encryption_keys,
+            AeadVersion::for_transaction_version(version),
         )
     }
 
@@ -127,32 +193,38 @@ impl MerkleNote {
         value_commitment: &ValueCommitment,
         diffie_hellman_keys: &EphemeralKeyPair,
         note_encryption_keys: [u8; NOTE_ENCRYPTION_KEY_SIZE],
+        aead_version: AeadVersion,
     ) -> MerkleNote {
-        let secret_key = diffie_hellman_keys.secret();
-        let public_key = diffie_hellman_keys.public();
-
-        let encrypted_note = note.encrypt(&shared_secret(
-            secret_key,
-            &note.owner.transmission_key,
-            public_key,
-        ));
+        let (ephemeral_public_key, shared_secret) =
+            DefaultNoteKem::encapsulate(diffie_hellman_keys, &note.owner);
+        let encrypted_note = note.encrypt_with_version(aead_version, &shared_secret);
 
         MerkleNote {
             value_commitment: value_commitment.commitment().into(),
             note_commitment: note.commitment_point(),
-            ephemeral_public_key: (*public_key),
+            ephemeral_public_key,
             encrypted_note,
             note_encryption_keys,
+            aead_version,
         }
     }
 
     /// Load a MerkleNote from the given stream
-    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        MerkleNote::read_for_version(reader, TRANSACTION_VERSION)
+    }
+
+    /// Load a MerkleNote of a transaction of the given version from the
+    /// given stream
+    pub fn read_for_version<R: io::Read>(
+        mut reader: R,
+        version: u8,
+    ) -> Result<Self, IronfishError> {
         let value_commitment = read_point(&mut reader)?;
         let note_commitment = read_scalar(&mut reader)?;
-        let ephemeral_public_key = read_point(&mut reader)?;
+        let ephemeral_public_key = DefaultNoteKem::read_encapsulation(&mut reader)?;
 
-        let mut encrypted_note = [0; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE];
+        let mut encrypted_note = [0; ENCRYPTED_NOTE_CIPHERTEXT_SIZE];
         reader.read_exact(&mut encrypted_note[..])?;
         let mut note_encryption_keys = [0; NOTE_ENCRYPTION_KEY_SIZE];
         reader.read_exact(&mut note_encryption_keys[..])?;
@@ -163,34 +235,97 @@ impl MerkleNote {
             ephemeral_public_key,
             encrypted_note,
             note_encryption_keys,
+            aead_version: AeadVersion::for_transaction_version(version),
         })
     }
 
     pub fn write<W: io::Write>(&self, writer: &mut W) -> Result<(), IronfishError> {
         writer.write_all(&self.value_commitment.to_bytes())?;
         writer.write_all(&self.note_commitment.to_bytes())?;
-        writer.write_all(&self.ephemeral_public_key.to_bytes())?;
+        DefaultNoteKem::write_encapsulation(&self.ephemeral_public_key, &mut *writer)?;
         writer.write_all(&self.encrypted_note)?;
         writer.write_all(&self.note_encryption_keys)?;
 
         Ok(())
     }
 
+    /// The serialized form of this MerkleNote, as written by
+    /// [`MerkleNote::write`], without allocating.
+    pub fn to_bytes(&self) -> [u8; MERKLE_NOTE_SIZE] {
+        let mut bytes = [0; MERKLE_NOTE_SIZE];
+        self.write(&mut &mut bytes[..]).unwrap();
+        bytes
+    }
+
     pub fn merkle_hash(&self) -> MerkleNoteHash {
         MerkleNoteHash::new(self.note_commitment)
     }
 
+    /// Scheme the note is encrypted with
+    pub fn aead_version(&self) -> AeadVersion {
+        self.aead_version
+    }
+
+    /// Decrypt the note with the owner's incoming view key. This is trial
+    /// decryption of notes that are usually someone else's, so it takes the
+    /// same time whether or not the note belongs to the key; see
+    /// [`Note::decrypt_constant_time`].
     pub fn decrypt_note_for_owner(
         &self,
         owner_view_key: &IncomingViewKey,
     ) -> Result<Note, IronfishError> {
-        let shared_secret = owner_view_key.shared_secret(&self.ephemeral_public_key);
-        let note =
-            Note::from_owner_encrypted(owner_view_key, &shared_secret, &self.encrypted_note)?;
-        note.verify_commitment(self.note_commitment)?;
+        let shared_secret = DefaultNoteKem::decapsulate(owner_view_key, &self.ephemeral_public_key);
+        let (note, is_valid) = Note::decrypt_constant_time(
+            self.aead_version,
+            owner_view_key.public_address(),
+            &shared_secret,
+            &self.encrypted_note,
+            self.note_commitment,
+        )?;
+
+        let is_valid = bool::from(is_valid);
+        record(|sink| sink.note_decryption_attempted(is_valid));
+
+        match is_valid {
+            true => Ok(note),
+            false => Err(IronfishError::InvalidDecryptionKey),
+        }
+    }
+
+    /// Decrypt the note with the ephemeral secret it was encrypted with and
+    /// the address it was sent to. This is for senders that kept the secret,
+    /// see [`crate::ProposedTransaction::output_ephemeral_secrets`], and works
+    /// for miner's fee notes too, which can't be decrypted by a spender.
+    pub fn decrypt_note_with_ephemeral_secret(
+        &self,
+        owner: &PublicAddress,
+        ephemeral_secret: &jubjub::Fr,
+    ) -> Result<Note, IronfishError> {
+        if PUBLIC_KEY_GENERATOR * ephemeral_secret != self.ephemeral_public_key {
+            return Err(IronfishError::InvalidDecryptionKey);
+        }
+
+        let shared_key = shared_secret(
+            ephemeral_secret,
+            &owner.transmission_key,
+            &self.ephemeral_public_key,
+        );
+        let note = Note::from_spender_encrypted(
+            self.aead_version,
+            owner.transmission_key,
+            &shared_key,
+            &self.encrypted_note,
+        )?;
+        if note.commitment_point() != self.note_commitment {
+            return Err(IronfishError::InvalidDecryptionKey);
+        }
+
         Ok(note)
     }
 
+    /// Decrypt the note with the spender's outgoing view key, taking the
+    /// same time whether or not the key sent it, like
+    /// [`MerkleNote::decrypt_note_for_owner`].
     pub fn decrypt_note_for_spender(
         &self,
         spender_key: &OutgoingViewKey,
@@ -202,15 +337,68 @@ impl MerkleNote {
             &self.ephemeral_public_key,
         );
 
-        let note_encryption_keys: [u8; ENCRYPTED_SHARED_KEY_SIZE] =
-            aead::decrypt(&encryption_key, &self.note_encryption_keys)?;
+        let (note_encryption_keys, keys_authenticated) =
+            aead::decrypt_constant_time::<ENCRYPTED_SHARED_KEY_SIZE>(
+                self.aead_version,
+                &encryption_key,
+                &self.note_encryption_keys,
+            )?;
+
+        let mut transmission_key_bytes = [0; 32];
+        transmission_key_bytes.copy_from_slice(&note_encryption_keys[..32]);
+        let (transmission_key, transmission_key_is_valid) =
+            read_point_constant_time(&transmission_key_bytes, &PUBLIC_KEY_GENERATOR);
+
+        let mut secret_key_bytes = [0; 32];
+        secret_key_bytes.copy_from_slice(&note_encryption_keys[32..]);
+        let secret_key = jubjub::Fr::from_repr(secret_key_bytes);
+        let secret_key_is_valid = secret_key.is_some();
+        let secret_key = secret_key.unwrap_or(jubjub::Fr::zero());
+
+        let shared_key = shared_secret(&secret_key, &transmission_key, &self.ephemeral_public_key);
+        let (note, note_is_valid) = Note::decrypt_constant_time(
+            self.aead_version,
+            PublicAddress { transmission_key },
+            &shared_key,
+            &self.encrypted_note,
+            self.note_commitment,
+        )?;
+
+        let is_valid =
+            keys_authenticated & transmission_key_is_valid & secret_key_is_valid & note_is_valid;
+
+        let is_valid = bool::from(is_valid);
+        record(|sink| sink.note_decryption_attempted(is_valid));
+
+        match is_valid {
+            true => Ok(note),
+            false => Err(IronfishError::InvalidDecryptionKey),
+        }
+    }
+
+    /// Decrypt the note encryption keys with the spender's outgoing view key,
+    /// returning the owner's transmission key and the ephemeral secret key
+    /// that was used to encrypt the note.
+    pub(crate) fn decrypt_note_encryption_keys(
+        &self,
+        spender_key: &OutgoingViewKey,
+    ) -> Result<(SubgroupPoint, jubjub::Fr), IronfishError> {
+        let encryption_key = calculate_key_for_encryption_keys(
+            spender_key,
+            &self.value_commitment,
+            &self.note_commitment,
+            &self.ephemeral_public_key,
+        );
+
+        let note_encryption_keys: [u8; ENCRYPTED_SHARED_KEY_SIZE] = aead::decrypt_with_version(
+            self.aead_version,
+            &encryption_key,
+            &self.note_encryption_keys,
+        )?;
         let transmission_key = PublicAddress::load_transmission_key(&note_encryption_keys[..32])?;
         let secret_key = read_scalar(&note_encryption_keys[32..])?;
-        let shared_key = shared_secret(&secret_key, &transmission_key, &self.ephemeral_public_key);
-        let note =
-            Note::from_spender_encrypted(transmission_key, &shared_key, &self.encrypted_note)?;
-        note.verify_commitment(self.note_commitment)?;
-        Ok(note)
+
+        Ok((transmission_key, secret_key))
     }
 }
 
@@ -233,14 +421,48 @@ pub(crate) fn sapling_auth_path(witness: &dyn WitnessTrait) -> Vec<Option<(Scala
 /// on an assumption that the tree is complete and binary. And I didn't feel
 /// like making Witness a trait since it's otherwise very simple.
 /// So this hacky function gets to live here.
-pub(crate) fn position(witness: &dyn WitnessTrait) -> u64 {
-    let mut pos = 0;
-    for (i, element) in witness.get_auth_path().iter().enumerate() {
-        if let WitnessNode::Right(_) = element {
-            pos |= 1 << i;
+pub(crate) fn position(witness: &dyn WitnessTrait) -> NotePosition {
+    NotePosition::from_auth_path(&witness.get_auth_path())
+}
+
+/// The parts of a serialized [`MerkleNote`], borrowed from its bytes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MerkleNoteParts<'a> {
+    pub value_commitment: &'a [u8; VALUE_COMMITMENT_SIZE],
+    pub note_commitment: &'a [u8; NOTE_COMMITMENT_SIZE],
+    pub ephemeral_public_key: &'a [u8; EPHEMERAL_PUBLIC_KEY_SIZE],
+    pub encrypted_note: &'a [u8; ENCRYPTED_NOTE_CIPHERTEXT_SIZE],
+    pub note_encryption_keys: &'a [u8; NOTE_ENCRYPTION_KEY_SIZE],
+}
+
+impl<'a> MerkleNoteParts<'a> {
+    /// Split the bytes of a [`MerkleNote`] as written by [`MerkleNote::write`].
+    /// The parts aren't checked to be valid points or scalars.
+    pub fn split(bytes: &'a [u8; MERKLE_NOTE_SIZE]) -> Self {
+        MerkleNoteParts {
+            value_commitment: part(bytes, VALUE_COMMITMENT_RANGE),
+            note_commitment: part(bytes, NOTE_COMMITMENT_RANGE),
+            ephemeral_public_key: part(bytes, EPHEMERAL_PUBLIC_KEY_RANGE),
+            encrypted_note: part(bytes, ENCRYPTED_NOTE_RANGE),
+            note_encryption_keys: part(bytes, NOTE_ENCRYPTION_KEYS_RANGE),
         }
     }
-    pos
+
+    /// Put the parts back together in the layout of [`MerkleNote::write`]
+    pub fn compose(&self) -> [u8; MERKLE_NOTE_SIZE] {
+        let mut bytes = [0; MERKLE_NOTE_SIZE];
+        bytes[VALUE_COMMITMENT_RANGE].copy_from_slice(self.value_commitment);
+        bytes[NOTE_COMMITMENT_RANGE].copy_from_slice(self.note_commitment);
+        bytes[EPHEMERAL_PUBLIC_KEY_RANGE].copy_from_slice(self.ephemeral_public_key);
+        bytes[ENCRYPTED_NOTE_RANGE].copy_from_slice(self.encrypted_note);
+        bytes[NOTE_ENCRYPTION_KEYS_RANGE].copy_from_slice(self.note_encryption_keys);
+        bytes
+    }
+}
+
+fn part<const SIZE: usize>(bytes: &[u8; MERKLE_NOTE_SIZE], range: Range<usize>) -> &[u8; SIZE] {
+    // The ranges are constant and within the note
+    bytes[range].try_into().unwrap()
 }
 
 /// Calculate the key used to encrypt the shared keys for an [`crate::outputs::OutputDescription`].
@@ -258,30 +480,28 @@ fn calculate_key_for_encryption_keys(
     note_commitment: &Scalar,
     public_key: &SubgroupPoint,
 ) -> [u8; 32] {
-    let mut key_input = [0u8; 128];
-    key_input[0..32].copy_from_slice(&outgoing_view_key.view_key);
-    key_input[32..64].copy_from_slice(&value_commitment.to_bytes());
-    key_input[64..96].copy_from_slice(note_commitment.to_repr().as_ref());
-    key_input[96..128].copy_from_slice(&public_key.to_bytes());
-
-    Blake2b::new()
-        .hash_length(32)
-        .personal(SHARED_KEY_PERSONALIZATION)
-        .hash(&key_input)
-        .as_bytes()
-        .try_into()
-        .expect("has has incorrect length")
+    prf_ock(
+        &outgoing_view_key.view_key,
+        &value_commitment.to_bytes(),
+        &note_commitment.to_repr(),
+        &public_key.to_bytes(),
+    )
 }
 
 #[cfg(test)]
 mod test {
     use super::MerkleNote;
     use super::NOTE_ENCRYPTION_MINER_KEYS;
+    use super::{MerkleNoteParts, EPHEMERAL_PUBLIC_KEY_RANGE, MERKLE_NOTE_SIZE};
     use crate::assets::asset_identifier::NATIVE_ASSET;
+    use crate::errors::IronfishError;
     use crate::keys::EphemeralKeyPair;
+    use crate::serializing::aead::AeadVersion;
+    use crate::transaction::{EXTRA_DATA_TRANSACTION_VERSION, TRANSACTION_VERSION};
     use crate::{keys::SaplingKey, note::Note};
 
     use bls12_381::Scalar;
+    use group::GroupEncoding;
     use ironfish_zkp::primitives::ValueCommitment;
     use rand::prelude::*;
 
@@ -300,7 +520,7 @@ mod test {
         );
         let diffie_hellman_keys = EphemeralKeyPair::new();
 
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
 
         let merkle_note =
             MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
@@ -326,10 +546,14 @@ mod test {
         );
         let diffie_hellman_keys = EphemeralKeyPair::new();
 
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
 
-        let merkle_note =
-            MerkleNote::new_for_miners_fee(&note, &value_commitment, &diffie_hellman_keys);
+        let merkle_note = MerkleNote::new_for_miners_fee(
+            &note,
+            &value_commitment,
+            &diffie_hellman_keys,
+            TRANSACTION_VERSION,
+        );
 
         assert_eq!(
             &merkle_note.note_encryption_keys,
@@ -337,6 +561,44 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_merkle_note_versions() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let note = Note::new(
+            receiver_key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+
+        for version in [TRANSACTION_VERSION, EXTRA_DATA_TRANSACTION_VERSION] {
+            let merkle_note = MerkleNote::new_for_version(
+                &spender_key,
+                &note,
+                &value_commitment,
+                &EphemeralKeyPair::new(),
+                version,
+            );
+            assert_eq!(
+                merkle_note.aead_version(),
+                AeadVersion::for_transaction_version(version)
+            );
+
+            let read_back =
+                MerkleNote::read_for_version(&merkle_note.to_bytes()[..], version).unwrap();
+            assert_eq!(read_back.aead_version(), merkle_note.aead_version());
+            read_back
+                .decrypt_note_for_owner(receiver_key.incoming_view_key())
+                .unwrap();
+            read_back
+                .decrypt_note_for_spender(spender_key.outgoing_view_key())
+                .unwrap();
+        }
+    }
+
     #[test]
     fn test_view_key_encryption() {
         let spender_key = SaplingKey::generate_key();
@@ -350,7 +612,7 @@ mod test {
         );
         let diffie_hellman_keys = EphemeralKeyPair::new();
 
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
 
         let merkle_note =
             MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
@@ -383,7 +645,7 @@ mod test {
         );
         let diffie_hellman_keys = EphemeralKeyPair::new();
 
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
 
         let merkle_note =
             MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
@@ -408,7 +670,7 @@ mod test {
         );
         let diffie_hellman_keys = EphemeralKeyPair::new();
 
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
 
         let mut merkle_note =
             MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
@@ -429,4 +691,173 @@ mod test {
             .decrypt_note_for_spender(spender_key.outgoing_view_key())
             .is_err());
     }
+
+    #[test]
+    fn test_trial_decryption() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let third_party_key = SaplingKey::generate_key();
+
+        let note = Note::new(
+            receiver_key.public_address(),
+            42,
+            "trial",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+        let merkle_note = MerkleNote::new(
+            &spender_key,
+            &note,
+            &value_commitment,
+            &EphemeralKeyPair::new(),
+        );
+
+        let decrypted = merkle_note
+            .decrypt_note_for_owner(receiver_key.incoming_view_key())
+            .unwrap();
+        assert_eq!(decrypted.commitment(), note.commitment());
+        assert_eq!(decrypted.memo(), note.memo());
+        let decrypted = merkle_note
+            .decrypt_note_for_spender(spender_key.outgoing_view_key())
+            .unwrap();
+        assert_eq!(decrypted.commitment(), note.commitment());
+
+        assert!(matches!(
+            merkle_note.decrypt_note_for_owner(third_party_key.incoming_view_key()),
+            Err(IronfishError::InvalidDecryptionKey)
+        ));
+        assert!(matches!(
+            merkle_note.decrypt_note_for_spender(third_party_key.outgoing_view_key()),
+            Err(IronfishError::InvalidDecryptionKey)
+        ));
+
+        // A note that decrypts but doesn't match its commitment is rejected
+        // the same way
+        let mut wrong_commitment = merkle_note.clone();
+        wrong_commitment.note_commitment = Scalar::one();
+        assert!(matches!(
+            wrong_commitment.decrypt_note_for_owner(receiver_key.incoming_view_key()),
+            Err(IronfishError::InvalidDecryptionKey)
+        ));
+    }
+
+    /// Welch's t statistic between two samples, as used by dudect to tell
+    /// whether two classes of inputs take different time
+    fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+        let mean = |x: &[f64]| x.iter().sum::<f64>() / x.len() as f64;
+        let variance = |x: &[f64], m: f64| {
+            x.iter().map(|v| (v - m) * (v - m)).sum::<f64>() / (x.len() - 1) as f64
+        };
+
+        let (mean_a, mean_b) = (mean(a), mean(b));
+        let (var_a, var_b) = (variance(a, mean_a), variance(b, mean_b));
+
+        (mean_a - mean_b) / (var_a / a.len() as f64 + var_b / b.len() as f64).sqrt()
+    }
+
+    #[test]
+    #[ignore = "timing is noisy on shared machines, run with --ignored when idle"]
+    fn test_trial_decryption_timing() {
+        const SAMPLES: usize = 2000;
+
+        let spender_key = SaplingKey::generate_key();
+        let scanner_key = SaplingKey::generate_key();
+        let other_key = SaplingKey::generate_key();
+
+        let make_note = |owner: &SaplingKey| {
+            let note = Note::new(
+                owner.public_address(),
+                42,
+                "",
+                NATIVE_ASSET,
+                spender_key.public_address(),
+            );
+            let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+            MerkleNote::new(
+                &spender_key,
+                &note,
+                &value_commitment,
+                &EphemeralKeyPair::new(),
+            )
+        };
+        let own_note = make_note(&scanner_key);
+        let other_note = make_note(&other_key);
+
+        // Interleave the two classes in random order so drift in the
+        // machine's speed affects both equally
+        let mut rng = thread_rng();
+        let mut own_timings = Vec::with_capacity(SAMPLES);
+        let mut other_timings = Vec::with_capacity(SAMPLES);
+        while own_timings.len() < SAMPLES || other_timings.len() < SAMPLES {
+            let own = rng.gen::<bool>();
+            let merkle_note = if own { &own_note } else { &other_note };
+
+            let start = std::time::Instant::now();
+            let result = merkle_note.decrypt_note_for_owner(scanner_key.incoming_view_key());
+            let elapsed = start.elapsed().as_nanos() as f64;
+            assert_eq!(result.is_ok(), own);
+
+            match own {
+                true if own_timings.len() < SAMPLES => own_timings.push(elapsed),
+                false if other_timings.len() < SAMPLES => other_timings.push(elapsed),
+                _ => {}
+            }
+        }
+
+        // Like dudect, drop the slowest measurements, which are mostly
+        // interrupts and scheduling rather than the code being measured
+        let crop = |timings: &mut Vec<f64>| {
+            timings.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            timings.truncate(SAMPLES * 9 / 10);
+        };
+        crop(&mut own_timings);
+        crop(&mut other_timings);
+
+        // dudect treats |t| above 10 as a certain leak
+        let t = welch_t(&own_timings, &other_timings);
+        assert!(t.abs() < 10.0, "decryption time leaks ownership, t = {}", t);
+    }
+
+    #[test]
+    fn test_merkle_note_parts() {
+        let spender_key = SaplingKey::generate_key();
+        let note = Note::new(
+            spender_key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let diffie_hellman_keys = EphemeralKeyPair::new();
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+        let merkle_note =
+            MerkleNote::new(&spender_key, &note, &value_commitment, &diffie_hellman_keys);
+
+        let bytes = merkle_note.to_bytes();
+        let parts = MerkleNoteParts::split(&bytes);
+        assert_eq!(
+            parts.ephemeral_public_key,
+            &merkle_note.ephemeral_public_key.to_bytes()
+        );
+        assert_eq!(
+            &bytes[EPHEMERAL_PUBLIC_KEY_RANGE],
+            &diffie_hellman_keys.public().to_bytes()
+        );
+        assert_eq!(parts.encrypted_note, &merkle_note.encrypted_note);
+        assert_eq!(
+            parts.note_encryption_keys,
+            &merkle_note.note_encryption_keys
+        );
+        assert_eq!(parts.compose(), bytes);
+
+        // Composing from parts doesn't check them, so reading is what does
+        let garbage = [0xff; 32];
+        let parts = MerkleNoteParts {
+            ephemeral_public_key: &garbage,
+            ..parts
+        };
+        assert_eq!(parts.compose().len(), MERKLE_NOTE_SIZE);
+        assert!(MerkleNote::read(&parts.compose()[..]).is_err());
+    }
 }
@@ -0,0 +1,190 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Counters and timings from the verification and decryption hot paths.
+//!
+//! Nothing is recorded until a [`MetricsSink`] is installed with
+//! [`set_metrics_sink`]. Node operators can implement the sink on top of
+//! their metrics library, for example to export Prometheus counters and
+//! histograms. Sinks are called on the thread doing the work, so they should
+//! only update counters and never block.
+
+use lazy_static::lazy_static;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+/// The circuit a proof is for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Circuit {
+    Spend,
+    Output,
+    Mint,
+}
+
+/// Receives the events of the crate's hot paths. Every method does nothing by
+/// default, so sinks only implement the events they export.
+pub trait MetricsSink: Send + Sync {
+    /// Proofs of the circuit were verified, either one at a time or as a
+    /// batch of `count` proofs, in `elapsed`.
+    fn proofs_verified(&self, _circuit: Circuit, _count: usize, _elapsed: Duration) {}
+
+    /// A proof of the circuit, or a batch containing one, was invalid.
+    fn proof_verification_failed(&self, _circuit: Circuit) {}
+
+    /// A batch of `count` signatures was verified in `elapsed`.
+    fn signatures_verified(&self, _count: usize, _elapsed: Duration) {}
+
+    /// An authorizing or binding signature, or a batch containing one, was
+    /// invalid.
+    fn signature_verification_failed(&self) {}
+
+    /// A note was trial decrypted, and either belonged to the key or not.
+    fn note_decryption_attempted(&self, _decrypted: bool) {}
+}
+
+lazy_static! {
+    static ref SINK: RwLock<Option<Arc<dyn MetricsSink>>> = RwLock::new(None);
+}
+
+/// Checked before taking the lock, so recording stays cheap without a sink
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Send metrics to the given sink from now on, replacing any previous one.
+pub fn set_metrics_sink(sink: Arc<dyn MetricsSink>) {
+    *SINK.write().unwrap() = Some(sink);
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Stop sending metrics.
+pub fn clear_metrics_sink() {
+    ENABLED.store(false, Ordering::SeqCst);
+    *SINK.write().unwrap() = None;
+}
+
+/// Call the installed sink, if there is one.
+pub(crate) fn record<F: FnOnce(&dyn MetricsSink)>(event: F) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let Some(sink) = SINK.read().unwrap().as_ref() {
+        event(sink.as_ref());
+    }
+}
+
+/// Record the outcome of verifying proofs of the circuit, which took
+/// `elapsed`.
+pub(crate) fn record_proofs<T, E>(
+    circuit: Circuit,
+    count: usize,
+    elapsed: Duration,
+    result: &Result<T, E>,
+) {
+    record(|sink| match result {
+        Ok(_) => sink.proofs_verified(circuit, count, elapsed),
+        Err(_) => sink.proof_verification_failed(circuit),
+    });
+}
+
+/// Record the outcome of verifying `count` signatures, which took `elapsed`.
+pub(crate) fn record_signatures(count: usize, elapsed: Duration, is_valid: bool) {
+    record(|sink| match is_valid {
+        true => sink.signatures_verified(count, elapsed),
+        false => sink.signature_verification_failed(),
+    });
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clear_metrics_sink, record, set_metrics_sink, Circuit, MetricsSink};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, keys::SaplingKey, note::Note,
+        test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    #[derive(Default)]
+    struct Counts {
+        spend_proofs: usize,
+        signatures: usize,
+        decrypted: usize,
+        not_decrypted: usize,
+    }
+
+    #[derive(Default)]
+    struct TestSink(Mutex<Counts>);
+
+    impl MetricsSink for TestSink {
+        fn proofs_verified(&self, circuit: Circuit, count: usize, _elapsed: Duration) {
+            if circuit == Circuit::Spend {
+                self.0.lock().unwrap().spend_proofs += count;
+            }
+        }
+
+        fn signatures_verified(&self, count: usize, _elapsed: Duration) {
+            self.0.lock().unwrap().signatures += count;
+        }
+
+        fn note_decryption_attempted(&self, decrypted: bool) {
+            let mut counts = self.0.lock().unwrap();
+            match decrypted {
+                true => counts.decrypted += 1,
+                false => counts.not_decrypted += 1,
+            }
+        }
+    }
+
+    #[test]
+    fn test_metrics_sink() {
+        let key = SaplingKey::generate_key();
+        let other_key = SaplingKey::generate_key();
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(in_note, &witness).unwrap();
+        let transaction = proposed.post(None, 1).unwrap();
+
+        let sink = Arc::new(TestSink::default());
+        set_metrics_sink(sink.clone());
+
+        // Other tests may run while the sink is installed, so only check
+        // lower bounds
+        transaction.verify().unwrap();
+        let merkle_note = transaction.outputs()[0].merkle_note();
+        merkle_note
+            .decrypt_note_for_owner(key.incoming_view_key())
+            .unwrap();
+        assert!(merkle_note
+            .decrypt_note_for_owner(other_key.incoming_view_key())
+            .is_err());
+
+        {
+            let counts = sink.0.lock().unwrap();
+            assert!(counts.spend_proofs >= 1);
+            // The spend authorizing signature and the binding signature
+            assert!(counts.signatures >= 2);
+            assert!(counts.decrypted >= 1);
+            assert!(counts.not_decrypted >= 1);
+        }
+
+        clear_metrics_sink();
+        let mut called = false;
+        record(|_| called = true);
+        assert!(!called);
+    }
+}
@@ -3,21 +3,24 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crypto_box::{
-    aead::{generic_array::GenericArray, Aead, AeadCore},
-    rand_core::OsRng,
+    aead::{generic_array::GenericArray, Aead},
     PublicKey, SalsaBox, SecretKey,
 };
 use rand::RngCore;
 
-use crate::errors::IronfishError;
+use crate::{
+    errors::IronfishError,
+    randomness::{expect_rng, with_rng},
+};
 
 pub const KEY_LENGTH: usize = crypto_box::KEY_SIZE;
 pub const NONCE_LENGTH: usize = 24;
 
 pub fn new_secret_key() -> SecretKey {
-    let mut rng = crypto_box::rand_core::OsRng;
+    let mut bytes = [0; KEY_LENGTH];
+    expect_rng(|rng| rng.fill_bytes(&mut bytes));
 
-    SecretKey::generate(&mut rng)
+    SecretKey::from(bytes)
 }
 
 pub fn bytes_to_secret_key(bytes: [u8; KEY_LENGTH]) -> SecretKey {
@@ -26,7 +29,7 @@ pub fn bytes_to_secret_key(bytes: [u8; KEY_LENGTH]) -> SecretKey {
 
 pub fn random_bytes(bytes_length: usize) -> Vec<u8> {
     let mut rand_bytes = vec![0; bytes_length];
-    OsRng.fill_bytes(&mut rand_bytes);
+    expect_rng(|rng| rng.fill_bytes(&mut rand_bytes));
 
     rand_bytes
 }
@@ -36,12 +39,12 @@ pub fn box_message(
     sender_secret_key: [u8; KEY_LENGTH],
     recipient_public_key: [u8; KEY_LENGTH],
 ) -> Result<(Vec<u8>, Vec<u8>), IronfishError> {
-    let mut rng = OsRng;
-
     let sender: SecretKey = SecretKey::from(sender_secret_key);
     let recipient: PublicKey = PublicKey::from(recipient_public_key);
 
-    let nonce = SalsaBox::generate_nonce(&mut rng);
+    let mut nonce = [0; NONCE_LENGTH];
+    with_rng(|rng| rng.fill_bytes(&mut nonce))?;
+    let nonce = GenericArray::from(nonce);
 
     let key_box = SalsaBox::new(&recipient, &sender);
 
@@ -0,0 +1,71 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use ironfish_zkp::constants::ASSET_ID_PERSONALIZATION;
+
+use crate::errors::IronfishError;
+
+/// The network that keys, addresses and assets are derived for.
+///
+/// Each network uses its own personalization when expanding spending keys
+/// and deriving asset identifiers, so the same spending key controls
+/// unrelated addresses on each network and an asset created on testnet never
+/// shares an identifier with one on mainnet. Encoded addresses carry a
+/// network prefix so they can't be pasted into a wallet for the wrong network.
+///
+/// Personalizations that are fixed in the circuits, such as the one used to
+/// derive the incoming view key, are the same on every network.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum NetworkKind {
+    #[default]
+    Mainnet,
+    Testnet,
+}
+
+impl NetworkKind {
+    /// Personalization used to expand a spending key into `ask`, `nsk` and
+    /// `ovk`.
+    pub fn spending_key_personalization(&self) -> &'static [u8; 16] {
+        match self {
+            NetworkKind::Mainnet => b"Iron Fish Money ",
+            NetworkKind::Testnet => b"IronFishTestnet ",
+        }
+    }
+
+    /// Personalization used to hash asset info into an asset identifier.
+    pub fn asset_id_personalization(&self) -> &'static [u8; 8] {
+        match self {
+            NetworkKind::Mainnet => ASSET_ID_PERSONALIZATION,
+            NetworkKind::Testnet => b"ironfTA_",
+        }
+    }
+
+    /// Human readable prefix of encoded public addresses.
+    pub fn address_hrp(&self) -> &'static str {
+        match self {
+            NetworkKind::Mainnet => "iron",
+            NetworkKind::Testnet => "tiron",
+        }
+    }
+
+    /// Byte identifying the network in serialized keys and accounts.
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            NetworkKind::Mainnet => 0,
+            NetworkKind::Testnet => 1,
+        }
+    }
+}
+
+impl TryFrom<u8> for NetworkKind {
+    type Error = IronfishError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            0 => Ok(NetworkKind::Mainnet),
+            1 => Ok(NetworkKind::Testnet),
+            _ => Err(IronfishError::InvalidNetwork),
+        }
+    }
+}
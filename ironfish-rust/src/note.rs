@@ -3,27 +3,38 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use crate::{
-    assets::asset_identifier::AssetIdentifier, errors::IronfishError, keys::PUBLIC_ADDRESS_SIZE,
-    util::str_to_array, ViewKey,
+    assets::{
+        asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+        generators::AssetGenerator,
+    },
+    errors::IronfishError,
+    keys::PUBLIC_ADDRESS_SIZE,
+    primitives::prf_nf,
+    randomness::random_scalar,
+    tree_position::NotePosition,
+    util::str_to_array,
+    ViewKey,
 };
 
 use super::{
     keys::{IncomingViewKey, PublicAddress},
-    serializing::{aead, read_scalar},
+    serializing::{
+        aead::{self, AeadVersion},
+        read_point_constant_time, read_scalar, select_bytes,
+    },
 };
-use blake2s_simd::Params as Blake2sParams;
 use bls12_381::Scalar;
 use byteorder::{ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
-use ff::{Field, PrimeField};
+use ff::PrimeField;
 use group::{Curve, GroupEncoding};
 use ironfish_zkp::{
-    constants::{ASSET_ID_LENGTH, NULLIFIER_POSITION_GENERATOR, PRF_NF_PERSONALIZATION},
-    util::commitment_full_point,
+    constants::{ASSET_ID_LENGTH, NULLIFIER_POSITION_GENERATOR},
+    util::{asset_hash_to_point_ct, commitment_full_point},
     Nullifier,
 };
 use jubjub::SubgroupPoint;
-use rand::thread_rng;
-use std::{fmt, io, io::Read};
+use std::{fmt, io, io::Read, ops::Range};
+use subtle::{Choice, ConstantTimeEq};
 pub const ENCRYPTED_NOTE_SIZE: usize =
     SCALAR_SIZE + MEMO_SIZE + AMOUNT_VALUE_SIZE + ASSET_ID_LENGTH + PUBLIC_ADDRESS_SIZE;
 //   8  value
@@ -97,7 +108,8 @@ pub struct Note {
 }
 
 impl<'a> Note {
-    /// Construct a new Note.
+    /// Construct a new Note. Panics if the randomness source fails, see
+    /// [`Note::try_new`].
     pub fn new(
         owner: PublicAddress,
         value: u64,
@@ -105,16 +117,27 @@ impl<'a> Note {
         asset_id: AssetIdentifier,
         sender: PublicAddress,
     ) -> Self {
-        let randomness: jubjub::Fr = jubjub::Fr::random(thread_rng());
+        Self::try_new(owner, value, memo, asset_id, sender).expect("the randomness source failed")
+    }
+
+    /// Construct a new Note, failing if the randomness source does.
+    pub fn try_new(
+        owner: PublicAddress,
+        value: u64,
+        memo: impl Into<Memo>,
+        asset_id: AssetIdentifier,
+        sender: PublicAddress,
+    ) -> Result<Self, IronfishError> {
+        let randomness = random_scalar()?;
 
-        Self {
+        Ok(Self {
             owner,
             asset_id,
             value,
             randomness,
             memo: memo.into(),
             sender,
-        }
+        })
     }
 
     /// Read a note from the given stream IN PLAINTEXT.
@@ -168,14 +191,19 @@ impl<'a> Note {
     /// using a shared secret derived from the owner's public key.
     ///
     /// This function allows the owner to decrypt the note using the derived
-    /// shared secret and their own view key.
+    /// shared secret and their own view key. The note is expected to be
+    /// encrypted with [`AeadVersion::ChaCha20Poly1305`], as the notes of
+    /// every transaction version so far are.
     pub fn from_owner_encrypted(
         owner_view_key: &'a IncomingViewKey,
         shared_secret: &[u8; 32],
         encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
     ) -> Result<Self, IronfishError> {
-        let (randomness, asset_id, value, memo, sender) =
-            Note::decrypt_note_parts(shared_secret, encrypted_bytes)?;
+        let (randomness, asset_id, value, memo, sender) = Note::decrypt_note_parts(
+            AeadVersion::ChaCha20Poly1305,
+            shared_secret,
+            encrypted_bytes,
+        )?;
         let owner = owner_view_key.public_address();
 
         Ok(Note {
@@ -198,12 +226,13 @@ impl<'a> Note {
     /// This function allows the owner to decrypt the note using the derived
     /// shared secret and their own view key.
     pub(crate) fn from_spender_encrypted(
+        version: AeadVersion,
         transmission_key: SubgroupPoint,
         shared_secret: &[u8; 32],
         encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
     ) -> Result<Self, IronfishError> {
         let (randomness, asset_id, value, memo, sender) =
-            Note::decrypt_note_parts(shared_secret, encrypted_bytes)?;
+            Note::decrypt_note_parts(version, shared_secret, encrypted_bytes)?;
 
         let owner = PublicAddress { transmission_key };
 
@@ -217,6 +246,91 @@ impl<'a> Note {
         })
     }
 
+    /// Decrypt a note sent to `owner` and check it against its commitment,
+    /// taking the same time whether or not it succeeds.
+    ///
+    /// Scanning tries to decrypt every note on the chain, and the ciphertexts
+    /// are chosen by whoever sent them. [`Note::from_owner_encrypted`] stops
+    /// at the first check that fails, so timing it tells an observer which
+    /// notes a scanner could read. Here every step runs no matter what: a
+    /// failed MAC tag or a malformed field is swapped for a placeholder, and
+    /// the checks are combined into a [`Choice`] that is only acted on once
+    /// all the work is done. When the choice isn't set, the note is made of
+    /// placeholders and must be thrown away.
+    ///
+    /// The Pedersen hash over the note contents still uses table lookups
+    /// indexed by the contents, which this doesn't hide.
+    pub(crate) fn decrypt_constant_time(
+        version: AeadVersion,
+        owner: PublicAddress,
+        shared_secret: &[u8; 32],
+        encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
+        commitment: Scalar,
+    ) -> Result<(Self, Choice), IronfishError> {
+        let (plaintext, authenticated) = aead::decrypt_constant_time::<ENCRYPTED_NOTE_SIZE>(
+            version,
+            shared_secret,
+            encrypted_bytes,
+        )?;
+        let (note, is_valid) = Note::from_plaintext_constant_time(owner, &plaintext, commitment)?;
+
+        Ok((note, authenticated & is_valid))
+    }
+
+    /// Read a decrypted note sent to `owner` and check it against its
+    /// commitment, in the same time whether or not it succeeds, like
+    /// [`Note::decrypt_constant_time`] once the MAC tag is checked.
+    pub(crate) fn from_plaintext_constant_time(
+        owner: PublicAddress,
+        plaintext: &[u8; ENCRYPTED_NOTE_SIZE],
+        commitment: Scalar,
+    ) -> Result<(Self, Choice), IronfishError> {
+        let mut reader = plaintext[..].as_ref();
+
+        let mut randomness_bytes = [0; SCALAR_SIZE];
+        reader.read_exact(&mut randomness_bytes)?;
+        let randomness = jubjub::Fr::from_repr(randomness_bytes);
+        let randomness_is_valid = randomness.is_some();
+        let randomness = randomness.unwrap_or(jubjub::Fr::zero());
+
+        let value = reader.read_u64::<LittleEndian>()?;
+
+        let mut memo = Memo::default();
+        reader.read_exact(&mut memo.0)?;
+
+        let mut asset_id_bytes = [0; ASSET_ID_LENGTH];
+        reader.read_exact(&mut asset_id_bytes)?;
+        let asset_id_is_valid = asset_hash_to_point_ct(&asset_id_bytes).is_some();
+        // The selected identifier is always valid, so this never fails
+        let asset_id = AssetIdentifier::new(select_bytes(
+            NATIVE_ASSET.as_bytes(),
+            &asset_id_bytes,
+            asset_id_is_valid,
+        ))?;
+
+        let mut sender_bytes = [0; PUBLIC_ADDRESS_SIZE];
+        reader.read_exact(&mut sender_bytes)?;
+        let (sender, sender_is_valid) =
+            read_point_constant_time(&sender_bytes, &owner.transmission_key);
+
+        let note = Note {
+            owner,
+            asset_id,
+            value,
+            randomness,
+            memo,
+            sender: PublicAddress {
+                transmission_key: sender,
+            },
+        };
+        let commitment_matches = note.commitment_point().ct_eq(&commitment);
+
+        let is_valid =
+            randomness_is_valid & asset_id_is_valid & sender_is_valid & commitment_matches;
+
+        Ok((note, is_valid))
+    }
+
     pub fn value(&self) -> u64 {
         self.value
     }
@@ -229,7 +343,7 @@ impl<'a> Note {
         self.owner
     }
 
-    pub fn asset_generator(&self) -> jubjub::ExtendedPoint {
+    pub fn asset_generator(&self) -> AssetGenerator {
         self.asset_id.asset_generator()
     }
 
@@ -245,6 +359,17 @@ impl<'a> Note {
     /// the tree. Only someone with the incoming viewing key for the note can
     /// actually read the contents.
     pub fn encrypt(&self, shared_secret: &[u8; 32]) -> [u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE] {
+        self.encrypt_with_version(AeadVersion::ChaCha20Poly1305, shared_secret)
+    }
+
+    /// Encrypt the note like [`Note::encrypt`], with the given scheme. See
+    /// [`AeadVersion::for_transaction_version`] for the scheme of the notes
+    /// of a transaction.
+    pub fn encrypt_with_version(
+        &self,
+        version: AeadVersion,
+        shared_secret: &[u8; 32],
+    ) -> [u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE] {
         let mut bytes_to_encrypt = [0; ENCRYPTED_NOTE_SIZE];
 
         let mut index = 0;
@@ -267,13 +392,13 @@ impl<'a> Note {
 
         bytes_to_encrypt[index..].copy_from_slice(&self.sender.public_address());
 
-        aead::encrypt(shared_secret, &bytes_to_encrypt).unwrap()
+        aead::encrypt_with_version(version, shared_secret, &bytes_to_encrypt).unwrap()
     }
 
     /// Computes the note commitment, returning the full point.
     fn commitment_full_point(&self) -> jubjub::SubgroupPoint {
         commitment_full_point(
-            self.asset_generator(),
+            self.asset_generator().point(),
             self.value,
             self.owner.transmission_key,
             self.randomness,
@@ -286,23 +411,37 @@ impl<'a> Note {
     /// The nullifier is a series of bytes that is published by the note owner
     /// only at the time the note is spent. This key is collected in a massive
     /// 'nullifier set', preventing double-spend.
-    pub fn nullifier(&self, view_key: &ViewKey, position: u64) -> Nullifier {
+    pub fn nullifier(&self, view_key: &ViewKey, position: NotePosition) -> Nullifier {
         // Compute rho = cm + position.G
         let rho = self.commitment_full_point()
-            + (NULLIFIER_POSITION_GENERATOR * jubjub::Fr::from(position));
-
-        // Compute nf = BLAKE2s(nk | rho)
-        Nullifier::from_slice(
-            Blake2sParams::new()
-                .hash_length(32)
-                .personal(PRF_NF_PERSONALIZATION)
-                .to_state()
-                .update(&view_key.nullifier_deriving_key.to_bytes())
-                .update(&rho.to_bytes())
-                .finalize()
-                .as_bytes(),
-        )
-        .unwrap()
+            + (NULLIFIER_POSITION_GENERATOR * jubjub::Fr::from(position.as_u64()));
+
+        derive_nullifier(&view_key.nullifier_deriving_key, &rho)
+    }
+
+    /// Compute the nullifiers this note would have at each of the given
+    /// positions, for recovering a note whose position in the tree was lost.
+    /// Comparing them with the nullifier set shows whether the note was
+    /// spent, and a match gives the position back. Consecutive positions
+    /// share the commitment and a single point addition, so this is much
+    /// cheaper than calling [`Note::nullifier`] for each position.
+    pub fn nullifiers_for_positions(
+        &self,
+        view_key: &ViewKey,
+        positions: Range<NotePosition>,
+    ) -> impl Iterator<Item = (NotePosition, Nullifier)> {
+        let start = positions.start.as_u64();
+        let end = positions.end.as_u64();
+
+        let nullifier_deriving_key = view_key.nullifier_deriving_key;
+        let mut rho =
+            self.commitment_full_point() + (NULLIFIER_POSITION_GENERATOR * jubjub::Fr::from(start));
+
+        (start..end).map(move |position| {
+            let nullifier = derive_nullifier(&nullifier_deriving_key, &rho);
+            rho += NULLIFIER_POSITION_GENERATOR;
+            (NotePosition::new(position), nullifier)
+        })
     }
 
     /// Get the commitment hash for this note. This encapsulates all the values
@@ -335,11 +474,12 @@ impl<'a> Note {
     }
 
     fn decrypt_note_parts(
+        version: AeadVersion,
         shared_secret: &[u8; 32],
         encrypted_bytes: &[u8; ENCRYPTED_NOTE_SIZE + aead::MAC_SIZE],
     ) -> Result<(jubjub::Fr, AssetIdentifier, u64, Memo, PublicAddress), IronfishError> {
         let plaintext_bytes: [u8; ENCRYPTED_NOTE_SIZE] =
-            aead::decrypt(shared_secret, encrypted_bytes)?;
+            aead::decrypt_with_version(version, shared_secret, encrypted_bytes)?;
 
         let mut reader = plaintext_bytes[..].as_ref();
 
@@ -356,14 +496,53 @@ impl<'a> Note {
     }
 }
 
+/// Compute nf = BLAKE2s(nk | rho), see [`prf_nf`]
+fn derive_nullifier(nullifier_deriving_key: &SubgroupPoint, rho: &SubgroupPoint) -> Nullifier {
+    Nullifier::from_slice(&prf_nf(&nullifier_deriving_key.to_bytes(), &rho.to_bytes())).unwrap()
+}
+
 #[cfg(test)]
 mod test {
     use super::{Memo, Note};
     use crate::{
         assets::asset_identifier::NATIVE_ASSET,
         keys::{shared_secret, EphemeralKeyPair, SaplingKey},
+        serializing::aead::AeadVersion,
+        tree_position::NotePosition,
     };
 
+    #[test]
+    fn test_nullifiers_for_positions() {
+        let owner_key = SaplingKey::generate_key();
+        let note = Note::new(
+            owner_key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            owner_key.public_address(),
+        );
+
+        let nullifiers: Vec<_> = note
+            .nullifiers_for_positions(
+                owner_key.view_key(),
+                NotePosition::new(1000)..NotePosition::new(1010),
+            )
+            .collect();
+        assert_eq!(nullifiers.len(), 10);
+        for (position, nullifier) in nullifiers {
+            assert_eq!(nullifier, note.nullifier(owner_key.view_key(), position));
+        }
+
+        assert_eq!(
+            note.nullifiers_for_positions(
+                owner_key.view_key(),
+                NotePosition::new(5)..NotePosition::new(5)
+            )
+            .count(),
+            0
+        );
+    }
+
     #[test]
     fn test_plaintext_serialization() {
         let owner_key: SaplingKey = SaplingKey::generate_key();
@@ -432,6 +611,7 @@ mod test {
         );
 
         let spender_decrypted = Note::from_spender_encrypted(
+            AeadVersion::ChaCha20Poly1305,
             note.owner.transmission_key,
             &public_shared_secret,
             &encryption_result,
@@ -0,0 +1,189 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The key agreement step of note encryption, as a key encapsulation
+//! mechanism, so that another scheme can be introduced alongside the current
+//! one.
+//!
+//! The sender of a note encapsulates a fresh shared secret to the recipient,
+//! and encrypts the note under it; the recipient decapsulates the secret
+//! with their incoming view key. Today that's the Diffie Hellman exchange
+//! on Jubjub described at [`crate::keys::shared_secret`], whose
+//! encapsulation is the note's ephemeral public key. A Diffie Hellman
+//! exchange falls to a quantum computer, so a hybrid scheme, hashing the
+//! Diffie Hellman secret together with the secret of a lattice KEM such as
+//! Kyber, would be added as another [`NoteKem`] with its own
+//! [`NoteCiphertextVersion`].
+//!
+//! The version isn't in the serialized note: it's implied by the version of
+//! the transaction holding it, see
+//! [`NoteCiphertextVersion::for_transaction_version`], so switching schemes
+//! is a protocol upgrade. Every transaction version so far uses
+//! [`NoteCiphertextVersion::V1`].
+
+use std::io;
+
+use group::GroupEncoding;
+use jubjub::SubgroupPoint;
+
+use crate::{
+    errors::IronfishError,
+    keys::{shared_secret, EphemeralKeyPair, IncomingViewKey, PublicAddress},
+    merkle_note::{EPHEMERAL_PUBLIC_KEY_SIZE, MERKLE_NOTE_SIZE},
+    serializing::read_point,
+};
+
+/// Layout of the encrypted parts of a note, which depends on the scheme that
+/// agreed on the key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NoteCiphertextVersion {
+    /// A Jubjub ephemeral public key, see [`DiffieHellmanKem`], followed by
+    /// the encrypted note and the encrypted note encryption keys
+    V1,
+}
+
+impl NoteCiphertextVersion {
+    /// Layout of the notes in transactions of the given version
+    pub fn for_transaction_version(_version: u8) -> Self {
+        NoteCiphertextVersion::V1
+    }
+
+    /// Size of the encapsulated shared secret
+    pub fn encapsulation_size(self) -> usize {
+        match self {
+            NoteCiphertextVersion::V1 => EPHEMERAL_PUBLIC_KEY_SIZE,
+        }
+    }
+
+    /// Size of a serialized note with this layout
+    pub fn merkle_note_size(self) -> usize {
+        match self {
+            NoteCiphertextVersion::V1 => MERKLE_NOTE_SIZE,
+        }
+    }
+}
+
+/// A key encapsulation mechanism for note encryption
+pub trait NoteKem {
+    /// Ciphertext layout of notes encrypted with this scheme
+    const VERSION: NoteCiphertextVersion;
+
+    /// The randomness the sender picks for each note
+    type EphemeralSecret;
+
+    /// What the recipient's secret is encapsulated to
+    type PublicKey;
+
+    /// What the recipient decapsulates with
+    type SecretKey;
+
+    /// What's stored in the note for the recipient to decapsulate
+    type Encapsulation;
+
+    /// The encapsulation of a shared secret to the recipient, with that
+    /// secret
+    fn encapsulate(
+        ephemeral_secret: &Self::EphemeralSecret,
+        recipient: &Self::PublicKey,
+    ) -> (Self::Encapsulation, [u8; 32]);
+
+    /// The shared secret in the encapsulation, for notes sent to the key.
+    /// For any other note it's a secret that fails to decrypt it, so this
+    /// has to take the same time either way.
+    fn decapsulate(key: &Self::SecretKey, encapsulation: &Self::Encapsulation) -> [u8; 32];
+
+    /// Load an encapsulation from a Read implementation (e.g: socket, file)
+    fn read_encapsulation<R: io::Read>(reader: R) -> Result<Self::Encapsulation, IronfishError>;
+
+    /// Stow the bytes of the encapsulation in the given writer.
+    fn write_encapsulation<W: io::Write>(
+        encapsulation: &Self::Encapsulation,
+        writer: W,
+    ) -> Result<(), IronfishError>;
+}
+
+/// The Diffie Hellman exchange on Jubjub notes have always used
+pub struct DiffieHellmanKem;
+
+impl NoteKem for DiffieHellmanKem {
+    const VERSION: NoteCiphertextVersion = NoteCiphertextVersion::V1;
+
+    type EphemeralSecret = EphemeralKeyPair;
+    type PublicKey = PublicAddress;
+    type SecretKey = IncomingViewKey;
+    type Encapsulation = SubgroupPoint;
+
+    fn encapsulate(
+        ephemeral_secret: &EphemeralKeyPair,
+        recipient: &PublicAddress,
+    ) -> (SubgroupPoint, [u8; 32]) {
+        let public_key = ephemeral_secret.public();
+        let secret = shared_secret(
+            ephemeral_secret.secret(),
+            &recipient.transmission_key,
+            public_key,
+        );
+
+        (*public_key, secret)
+    }
+
+    fn decapsulate(key: &IncomingViewKey, encapsulation: &SubgroupPoint) -> [u8; 32] {
+        key.shared_secret(encapsulation)
+    }
+
+    fn read_encapsulation<R: io::Read>(reader: R) -> Result<SubgroupPoint, IronfishError> {
+        read_point(reader)
+    }
+
+    fn write_encapsulation<W: io::Write>(
+        encapsulation: &SubgroupPoint,
+        mut writer: W,
+    ) -> Result<(), IronfishError> {
+        writer.write_all(&encapsulation.to_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// The scheme new notes are encrypted with
+pub type DefaultNoteKem = DiffieHellmanKem;
+
+#[cfg(test)]
+mod test {
+    use super::{DefaultNoteKem, NoteCiphertextVersion, NoteKem};
+    use crate::keys::{EphemeralKeyPair, SaplingKey};
+
+    #[test]
+    fn test_diffie_hellman_kem() {
+        let recipient = SaplingKey::generate_key();
+        let ephemeral = EphemeralKeyPair::new();
+
+        let (encapsulation, secret) =
+            DefaultNoteKem::encapsulate(&ephemeral, &recipient.public_address());
+        assert_eq!(encapsulation, *ephemeral.public());
+
+        let mut serialized = vec![];
+        DefaultNoteKem::write_encapsulation(&encapsulation, &mut serialized).unwrap();
+        assert_eq!(
+            serialized.len(),
+            DefaultNoteKem::VERSION.encapsulation_size()
+        );
+        let encapsulation = DefaultNoteKem::read_encapsulation(&serialized[..]).unwrap();
+
+        assert_eq!(
+            DefaultNoteKem::decapsulate(recipient.incoming_view_key(), &encapsulation),
+            secret
+        );
+        let other = SaplingKey::generate_key();
+        assert_ne!(
+            DefaultNoteKem::decapsulate(other.incoming_view_key(), &encapsulation),
+            secret
+        );
+
+        assert_eq!(
+            NoteCiphertextVersion::for_transaction_version(1),
+            DefaultNoteKem::VERSION
+        );
+    }
+}
@@ -0,0 +1,235 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ironfish_zkp::Nullifier;
+use rand::RngCore;
+use xxhash_rust::xxh3::xxh3_64_with_seed;
+
+use crate::{errors::IronfishError, randomness::expect_rng};
+
+const MAX_HASH_FUNC_COUNT: u32 = 50;
+
+/// A Bloom filter over nullifiers, for light clients that can't hold the full
+/// nullifier set but want to cheaply rule out double spends.
+///
+/// Unlike [`crate::rolling_filter::RollingFilter`], entries are never evicted:
+/// nullifiers are added as blocks are synced and the filter only grows more
+/// full. A negative answer is definitive, while a positive answer means the
+/// nullifier has been seen with probability `1 - false_positive_rate`, and
+/// should be confirmed against a full node before rejecting a transaction.
+#[derive(Clone)]
+pub struct NullifierFilter {
+    hash_func_count: u32,
+    tweak: u32,
+    entries: u64,
+    data: Vec<u64>,
+}
+
+impl NullifierFilter {
+    /// Create a filter sized to hold `expected_entries` nullifiers with the
+    /// given false positive rate. Adding more nullifiers than expected still
+    /// works, but the false positive rate rises above the configured one.
+    pub fn new(expected_entries: u64, false_positive_rate: f64) -> Self {
+        Self::with_tweak(
+            expected_entries,
+            false_positive_rate,
+            expect_rng(|rng| rng.next_u32()),
+        )
+    }
+
+    /// Like [`NullifierFilter::new`], but with a fixed salt for the hash
+    /// functions. Filters can only be merged if they share the same tweak
+    /// and size, so peers that build filters independently need to agree on
+    /// one.
+    pub fn with_tweak(expected_entries: u64, false_positive_rate: f64, tweak: u32) -> Self {
+        let expected_entries = expected_entries.max(1) as f64;
+        let log_rate = false_positive_rate.clamp(f64::MIN_POSITIVE, 0.5).ln();
+        let ln_2 = 2.0_f64.ln();
+
+        let filter_bits = (-expected_entries * log_rate / (ln_2 * ln_2)).ceil();
+        let data_size = ((filter_bits / 64.0).ceil() as usize).max(1);
+
+        let hash_func_count = ((data_size as f64 * 64.0 / expected_entries * ln_2).round() as u32)
+            .clamp(1, MAX_HASH_FUNC_COUNT);
+
+        NullifierFilter {
+            hash_func_count,
+            tweak,
+            entries: 0,
+            data: vec![0; data_size],
+        }
+    }
+
+    fn bit_position(&self, nullifier: &Nullifier, n_hash_num: u32) -> (usize, u64) {
+        let seed = n_hash_num as u64 * 0xFBA4C795 + self.tweak as u64;
+        let h = xxh3_64_with_seed(&nullifier.0, seed);
+
+        let total_bits = self.data.len() as u128 * 64;
+        let bit = ((h as u128 * total_bits) >> 64) as u64;
+
+        ((bit / 64) as usize, bit % 64)
+    }
+
+    pub fn add(&mut self, nullifier: &Nullifier) {
+        for n in 0..self.hash_func_count {
+            let (word, bit) = self.bit_position(nullifier, n);
+            self.data[word] |= 1 << bit;
+        }
+
+        self.entries += 1;
+    }
+
+    /// Returns false if the nullifier has definitely not been added to the
+    /// filter, and true if it probably has.
+    pub fn contains(&self, nullifier: &Nullifier) -> bool {
+        (0..self.hash_func_count).all(|n| {
+            let (word, bit) = self.bit_position(nullifier, n);
+            (self.data[word] >> bit) & 1 == 1
+        })
+    }
+
+    /// Number of nullifiers added to the filter, including through merges.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Estimate the current false positive rate from the fraction of bits
+    /// that are set.
+    pub fn estimated_false_positive_rate(&self) -> f64 {
+        let set_bits: u32 = self.data.iter().map(|word| word.count_ones()).sum();
+        let fill_ratio = set_bits as f64 / (self.data.len() as f64 * 64.0);
+
+        fill_ratio.powi(self.hash_func_count as i32)
+    }
+
+    /// Add every nullifier in `other` to this filter. Both filters must have
+    /// been created with the same size and tweak.
+    pub fn merge(&mut self, other: &NullifierFilter) -> Result<(), IronfishError> {
+        if self.hash_func_count != other.hash_func_count
+            || self.tweak != other.tweak
+            || self.data.len() != other.data.len()
+        {
+            return Err(IronfishError::InvalidData);
+        }
+
+        for (word, other_word) in self.data.iter_mut().zip(other.data.iter()) {
+            *word |= other_word;
+        }
+        self.entries += other.entries;
+
+        Ok(())
+    }
+
+    /// Load a [`NullifierFilter`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let hash_func_count = reader.read_u32::<LittleEndian>()?;
+        if !(1..=MAX_HASH_FUNC_COUNT).contains(&hash_func_count) {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let tweak = reader.read_u32::<LittleEndian>()?;
+        let entries = reader.read_u64::<LittleEndian>()?;
+
+        let data_size = reader.read_u32::<LittleEndian>()?;
+        if data_size == 0 {
+            return Err(IronfishError::InvalidData);
+        }
+
+        // Don't trust the length to preallocate, the data is read one word
+        // at a time so a bogus length fails on the first missing word
+        let mut data = vec![];
+        for _ in 0..data_size {
+            data.push(reader.read_u64::<LittleEndian>()?);
+        }
+
+        Ok(NullifierFilter {
+            hash_func_count,
+            tweak,
+            entries,
+            data,
+        })
+    }
+
+    /// Stow the bytes of this [`NullifierFilter`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u32::<LittleEndian>(self.hash_func_count)?;
+        writer.write_u32::<LittleEndian>(self.tweak)?;
+        writer.write_u64::<LittleEndian>(self.entries)?;
+        writer.write_u32::<LittleEndian>(u32::try_from(self.data.len())?)?;
+        for word in self.data.iter() {
+            writer.write_u64::<LittleEndian>(*word)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use ironfish_zkp::Nullifier;
+    use rand::{thread_rng, Rng};
+
+    use super::NullifierFilter;
+
+    fn random_nullifier() -> Nullifier {
+        Nullifier(thread_rng().gen())
+    }
+
+    #[test]
+    fn test_nullifier_filter() {
+        let mut filter = NullifierFilter::new(1_000, 0.001);
+
+        let nullifiers: Vec<Nullifier> = (0..1_000).map(|_| random_nullifier()).collect();
+        for nullifier in nullifiers.iter() {
+            filter.add(nullifier);
+        }
+
+        // No false negatives
+        assert!(nullifiers.iter().all(|n| filter.contains(n)));
+        assert_eq!(filter.entries(), 1_000);
+
+        // The configured rate is 1 in 1000, so allow some slack to keep the
+        // test from being flaky
+        let false_positives = (0..10_000)
+            .filter(|_| filter.contains(&random_nullifier()))
+            .count();
+        assert!(false_positives < 50);
+        assert!(filter.estimated_false_positive_rate() < 0.005);
+    }
+
+    #[test]
+    fn test_nullifier_filter_serialization() {
+        let mut filter = NullifierFilter::new(100, 0.01);
+        let nullifier = random_nullifier();
+        filter.add(&nullifier);
+
+        let mut serialized = vec![];
+        filter.write(&mut serialized).unwrap();
+
+        let read_back = NullifierFilter::read(&serialized[..]).unwrap();
+        assert!(read_back.contains(&nullifier));
+        assert_eq!(read_back.entries(), 1);
+
+        assert!(NullifierFilter::read(&serialized[..serialized.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_nullifier_filter_merge() {
+        let mut filter = NullifierFilter::with_tweak(100, 0.01, 7);
+        let mut other = NullifierFilter::with_tweak(100, 0.01, 7);
+
+        let nullifier = random_nullifier();
+        other.add(&nullifier);
+
+        filter.merge(&other).unwrap();
+        assert!(filter.contains(&nullifier));
+        assert_eq!(filter.entries(), 1);
+
+        let incompatible = NullifierFilter::with_tweak(100, 0.01, 8);
+        assert!(filter.merge(&incompatible).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Earlier generations of the circuit parameters, kept loaded alongside
+//! [`SAPLING`] so that transactions proven before a hard fork changed the
+//! circuits still verify during the transition window.
+//!
+//! A fork that changes the circuits ships new parameters and a new
+//! transaction version. [`SAPLING`] holds the new parameters, which
+//! transactions are proven with, and the parameters of the old circuits are
+//! registered with [`register_parameter_set`] for the transaction versions
+//! they proved. Posted transactions are then verified with the parameters
+//! [`parameters_for_version`] picks by their version, in blocks mixing both
+//! generations too; versions no parameter set is registered for use
+//! [`SAPLING`]. Verifiers accept the versions parameter sets are registered
+//! for besides the ones transactions can be built in, so old versions the
+//! current code no longer builds stay valid while their parameters are
+//! registered. Once the window closes, [`unregister_parameter_set`] drops
+//! the old parameters.
+//!
+//! Only verification of posted transactions looks at parameter sets: proofs
+//! are always built, and checked as they're built, with [`SAPLING`]. A
+//! backend installed with the `experimental-proof-systems` feature verifies
+//! every version its own way.
+
+use std::{
+    ops::RangeInclusive,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{errors::IronfishError, sapling_bls12::SAPLING, Sapling};
+
+/// Parameters that verify the transactions of some versions
+struct ParameterSet {
+    versions: RangeInclusive<u8>,
+    sapling: Arc<Sapling>,
+}
+
+lazy_static! {
+    static ref PARAMETER_SETS: RwLock<Vec<ParameterSet>> = RwLock::new(vec![]);
+}
+
+/// Verify the transactions of the given versions with the given parameters
+/// from now on, instead of [`SAPLING`]. Fails with
+/// [`IronfishError::OverlappingParameterSets`] if some of the versions already
+/// have parameters registered for them.
+pub fn register_parameter_set(
+    versions: RangeInclusive<u8>,
+    sapling: Arc<Sapling>,
+) -> Result<(), IronfishError> {
+    let mut parameter_sets = PARAMETER_SETS.write().unwrap();
+    if parameter_sets
+        .iter()
+        .any(|set| set.versions.start() <= versions.end() && versions.start() <= set.versions.end())
+    {
+        return Err(IronfishError::OverlappingParameterSets);
+    }
+
+    parameter_sets.push(ParameterSet { versions, sapling });
+
+    Ok(())
+}
+
+/// Go back to verifying the transactions of the versions of the parameter
+/// set covering the given version with [`SAPLING`], returning the parameters
+/// that were registered for them, if any
+pub fn unregister_parameter_set(version: u8) -> Option<Arc<Sapling>> {
+    let mut parameter_sets = PARAMETER_SETS.write().unwrap();
+    let index = parameter_sets
+        .iter()
+        .position(|set| set.versions.contains(&version))?;
+
+    Some(parameter_sets.remove(index).sapling)
+}
+
+/// Whether parameters are registered for transactions of the given version,
+/// which makes them verifiable even when they're outside the versions
+/// transactions can be built in
+pub fn has_parameter_set(version: u8) -> bool {
+    PARAMETER_SETS
+        .read()
+        .unwrap()
+        .iter()
+        .any(|set| set.versions.contains(&version))
+}
+
+/// The parameters transactions of the given version are verified with.
+/// Cloned out of the lock, so that registering parameters doesn't wait for
+/// verifications in progress.
+pub fn parameters_for_version(version: u8) -> Arc<Sapling> {
+    PARAMETER_SETS
+        .read()
+        .unwrap()
+        .iter()
+        .find(|set| set.versions.contains(&version))
+        .map_or_else(|| SAPLING.clone(), |set| set.sapling.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::{
+        has_parameter_set, parameters_for_version, register_parameter_set, unregister_parameter_set,
+    };
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        metrics::Circuit, note::Note, proof_system::Groth16, sapling_bls12::SAPLING,
+        test_util::make_fake_witness, ProposedTransaction, Sapling, TRANSACTION_VERSION,
+    };
+
+    // Versions no other test builds transactions of, as parameter sets are
+    // shared by every test
+    const OLD_VERSIONS: u8 = 200;
+
+    #[test]
+    fn test_parameter_sets() {
+        // A generation whose spend circuit is another one
+        let Sapling {
+            spend_params,
+            output_params,
+            mint_params,
+            ..
+        } = Sapling::load();
+        let old = Arc::new(Sapling::from_proving_params(
+            output_params,
+            spend_params,
+            mint_params,
+        ));
+
+        register_parameter_set(OLD_VERSIONS..=OLD_VERSIONS + 1, old.clone()).unwrap();
+        assert!(matches!(
+            register_parameter_set(OLD_VERSIONS + 1..=OLD_VERSIONS + 2, old.clone()),
+            Err(IronfishError::OverlappingParameterSets)
+        ));
+        assert!(Arc::ptr_eq(&parameters_for_version(OLD_VERSIONS + 1), &old));
+        assert!(has_parameter_set(OLD_VERSIONS));
+        assert!(!has_parameter_set(OLD_VERSIONS + 2));
+        assert!(Arc::ptr_eq(
+            &parameters_for_version(TRANSACTION_VERSION),
+            &*SAPLING
+        ));
+
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let mut proposed = ProposedTransaction::new(key);
+        proposed
+            .add_spend(note.clone(), &make_fake_witness(&note))
+            .unwrap();
+        let transaction = proposed.post(None, 1).unwrap();
+        let spend = &transaction.spends()[0];
+        let public_inputs = spend.public_inputs(transaction.randomized_public_key());
+
+        let verify = |version| {
+            Groth16::verify_with(
+                &parameters_for_version(version),
+                Circuit::Spend,
+                &spend.proof,
+                &public_inputs,
+            )
+        };
+        verify(TRANSACTION_VERSION).unwrap();
+        assert!(verify(OLD_VERSIONS).is_err());
+
+        assert!(unregister_parameter_set(OLD_VERSIONS).is_some());
+        assert!(unregister_parameter_set(OLD_VERSIONS).is_none());
+        verify(OLD_VERSIONS).unwrap();
+    }
+}
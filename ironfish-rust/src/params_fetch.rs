@@ -0,0 +1,232 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Runtime download and caching of the Sapling parameter files.
+//!
+//! With this feature the parameters aren't bundled in the binary: they're
+//! fetched on first use into a per-user cache directory, and then loaded
+//! with [`crate::Sapling::load_from_dir`], which [`crate::Sapling::load`]
+//! does on its own. Downloads are resumed from a `.part`
+//! file if a previous attempt was interrupted, and every file is checked
+//! against the same SHA-512 checksums the build script uses, both once it's
+//! downloaded and whenever it's loaded.
+
+use crate::{errors::IronfishError, serializing::hex_to_bytes};
+
+use reqwest::{blocking::Client, header::RANGE, StatusCode};
+use sha2::{Digest, Sha512};
+
+use std::{
+    env, fs, io,
+    path::{Path, PathBuf},
+};
+
+pub const PARAM_FILES: [&str; 3] = [
+    "sapling-mint.params",
+    "sapling-output.params",
+    "sapling-spend.params",
+];
+
+pub const DEFAULT_MIRRORS: &[&str] =
+    &["https://github.com/iron-fish/ironfish/raw/master/ironfish-rust/src/sapling_params"];
+
+const CHECKSUMS: &str = include_str!("sapling_params/params-sha512.txt");
+
+/// Downloads parameter files from a list of mirrors into a cache directory.
+/// Mirrors are tried in order until one of them serves a file matching its
+/// checksum.
+pub struct ParamsFetcher {
+    mirrors: Vec<String>,
+    cache_dir: PathBuf,
+    client: Client,
+}
+
+impl ParamsFetcher {
+    /// Create a fetcher using the default mirrors and the platform cache
+    /// directory. Fails if no cache directory can be determined for this
+    /// platform, in which case [`ParamsFetcher::with_cache_dir`] should be
+    /// used.
+    pub fn new() -> Result<Self, IronfishError> {
+        let cache_dir = default_cache_dir().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "no cache directory for platform")
+        })?;
+
+        Ok(Self::with_cache_dir(cache_dir))
+    }
+
+    /// Create a fetcher using the default mirrors and the given cache
+    /// directory.
+    pub fn with_cache_dir(cache_dir: impl Into<PathBuf>) -> Self {
+        ParamsFetcher {
+            mirrors: DEFAULT_MIRRORS.iter().map(|m| m.to_string()).collect(),
+            cache_dir: cache_dir.into(),
+            client: Client::new(),
+        }
+    }
+
+    /// Replace the list of mirrors. Each mirror is a base URL that the file
+    /// names in [`PARAM_FILES`] are appended to.
+    pub fn set_mirrors(&mut self, mirrors: Vec<String>) {
+        self.mirrors = mirrors;
+    }
+
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Make sure every parameter file is present and valid in the cache
+    /// directory, downloading any that are missing or corrupt. Returns the
+    /// cache directory, suitable for [`crate::Sapling::load_from_dir`].
+    pub fn fetch_all(&self) -> Result<&Path, IronfishError> {
+        for name in PARAM_FILES.iter() {
+            self.fetch(name)?;
+        }
+
+        Ok(&self.cache_dir)
+    }
+
+    /// Make sure a single parameter file is present and valid in the cache
+    /// directory, returning its path.
+    pub fn fetch(&self, name: &str) -> Result<PathBuf, IronfishError> {
+        let checksum = expected_checksum(name)?;
+        let destination = self.cache_dir.join(name);
+
+        if destination.exists() {
+            if file_matches_checksum(&destination, &checksum)? {
+                return Ok(destination);
+            }
+            fs::remove_file(&destination)?;
+        }
+
+        fs::create_dir_all(&self.cache_dir)?;
+        let partial = self.cache_dir.join(format!("{}.part", name));
+
+        for mirror in self.mirrors.iter() {
+            let url = format!("{}/{}", mirror.trim_end_matches('/'), name);
+
+            if self.download(&url, &partial).is_err() {
+                continue;
+            }
+
+            if file_matches_checksum(&partial, &checksum)? {
+                fs::rename(&partial, &destination)?;
+                return Ok(destination);
+            }
+
+            // The partial file can't be trusted anymore, so the next mirror
+            // has to start from scratch
+            fs::remove_file(&partial)?;
+        }
+
+        Err(IronfishError::ParamsDownloadFailed)
+    }
+
+    /// Download `url` into `partial`, resuming from the end of the file if
+    /// it already contains data.
+    fn download(&self, url: &str, partial: &Path) -> Result<(), IronfishError> {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(partial)?;
+        let offset = file.metadata()?.len();
+
+        let mut request = self.client.get(url);
+        if offset > 0 {
+            request = request.header(RANGE, format!("bytes={}-", offset));
+        }
+
+        let mut response = request
+            .send()
+            .map_err(|_| IronfishError::ParamsDownloadFailed)?;
+
+        match response.status() {
+            StatusCode::PARTIAL_CONTENT => {}
+            // The server doesn't support ranges, so start over
+            StatusCode::OK => file.set_len(0)?,
+            // The file was already fully downloaded
+            StatusCode::RANGE_NOT_SATISFIABLE => return Ok(()),
+            _ => return Err(IronfishError::ParamsDownloadFailed),
+        }
+
+        response
+            .copy_to(&mut file)
+            .map_err(|_| IronfishError::ParamsDownloadFailed)?;
+
+        Ok(())
+    }
+}
+
+/// The platform-appropriate directory to cache parameter files in:
+///  *  `%LOCALAPPDATA%\ironfish\sapling_params` on Windows
+///  *  `~/Library/Caches/ironfish/sapling_params` on macOS
+///  *  `$XDG_CACHE_HOME/ironfish/sapling_params` (or `~/.cache`) elsewhere
+pub fn default_cache_dir() -> Option<PathBuf> {
+    let base = if cfg!(target_os = "windows") {
+        env::var_os("LOCALAPPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        env::var_os("HOME").map(|home| PathBuf::from(home).join("Library").join("Caches"))
+    } else {
+        env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+    };
+
+    base.map(|dir| dir.join("ironfish").join("sapling_params"))
+}
+
+/// Look up the SHA-512 checksum of a parameter file.
+pub fn expected_checksum(name: &str) -> Result<[u8; 64], IronfishError> {
+    for line in CHECKSUMS.lines() {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() == 2 && parts[1] == name {
+            return hex_to_bytes(parts[0]);
+        }
+    }
+
+    Err(IronfishError::InvalidData)
+}
+
+fn file_matches_checksum(path: &Path, checksum: &[u8; 64]) -> Result<bool, IronfishError> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha512::new();
+    io::copy(&mut file, &mut hasher)?;
+
+    Ok(hasher.finalize().as_slice() == checksum)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{expected_checksum, file_matches_checksum, ParamsFetcher, PARAM_FILES};
+    use std::path::Path;
+
+    #[test]
+    fn test_expected_checksums() {
+        for name in PARAM_FILES.iter() {
+            expected_checksum(name).expect("should have a checksum for every file");
+        }
+
+        assert!(expected_checksum("sapling-unknown.params").is_err());
+    }
+
+    #[test]
+    fn test_bundled_params_match_checksums() {
+        let params_dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/sapling_params"));
+
+        for name in PARAM_FILES.iter() {
+            let checksum = expected_checksum(name).unwrap();
+            assert!(file_matches_checksum(&params_dir.join(name), &checksum).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_fetch_uses_valid_cache() {
+        // Point the cache at the params in the source tree, which are already
+        // valid, so no download is attempted
+        let params_dir = Path::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/sapling_params"));
+        let mut fetcher = ParamsFetcher::with_cache_dir(params_dir);
+        fetcher.set_mirrors(vec![]);
+
+        assert_eq!(fetcher.fetch_all().unwrap(), params_dir);
+    }
+}
@@ -0,0 +1,548 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Payment proofs allow the sender of a note to convince a third party that a
+//! given output on the chain was sent to a specific address, with a specific
+//! value and memo, without handing over their outgoing view key.
+//!
+//! The proof discloses the ephemeral secret key that was used to encrypt the
+//! note. Only the creator of the output knows this key, and knowing it is
+//! enough to re-derive the shared secret for the recipient's address and
+//! decrypt the note. The verifier then checks that the decrypted note matches
+//! the commitment published on the chain, which binds the owner, value and
+//! asset of the note.
+//!
+//! Proofs can also be stored off chain as a [`PaymentDisclosure`], encrypted
+//! to the sender's outgoing view key and optionally to an auditor, so that
+//! payments can be proven later without revealing the sender's history.
+
+use crate::{
+    errors::IronfishError,
+    keys::{shared_secret, EphemeralKeyPair, IncomingViewKey, OutgoingViewKey, PublicAddress},
+    merkle_note::MerkleNote,
+    note::{Memo, Note},
+    personalization::PAYMENT_DISCLOSURE_PERSONALIZATION,
+    serializing::{aead, read_point, read_scalar},
+    transaction::Transaction,
+};
+
+use blake2b_simd::Params as Blake2b;
+use bls12_381::Scalar;
+use byteorder::{ReadBytesExt, WriteBytesExt};
+use group::GroupEncoding;
+use ironfish_zkp::constants::PUBLIC_KEY_GENERATOR;
+use jubjub::SubgroupPoint;
+
+use std::io;
+
+pub const PAYMENT_PROOF_SIZE: usize = 96;
+
+pub const ENCRYPTED_PAYMENT_PROOF_SIZE: usize = PAYMENT_PROOF_SIZE + aead::MAC_SIZE;
+
+/// Disclosure of the values needed to decrypt a single output, produced by
+/// the sender of that output.
+#[derive(Clone)]
+pub struct PaymentProof {
+    /// Commitment of the note this proof is about. Used to find the output on
+    /// the chain and to make sure the proof is checked against the right one.
+    pub(crate) note_commitment: Scalar,
+
+    /// Address the sender claims to have paid.
+    pub(crate) recipient: PublicAddress,
+
+    /// Secret part of the ephemeral diffie-hellman key-pair used to encrypt
+    /// the note. Referred to as `esk` in the literature.
+    pub(crate) ephemeral_secret_key: jubjub::Fr,
+}
+
+impl PaymentProof {
+    /// Create a payment proof for an output that was created with the given
+    /// outgoing view key.
+    pub fn new(
+        merkle_note: &MerkleNote,
+        outgoing_view_key: &OutgoingViewKey,
+    ) -> Result<Self, IronfishError> {
+        let (transmission_key, ephemeral_secret_key) =
+            merkle_note.decrypt_note_encryption_keys(outgoing_view_key)?;
+
+        Ok(PaymentProof {
+            note_commitment: merkle_note.note_commitment,
+            recipient: PublicAddress { transmission_key },
+            ephemeral_secret_key,
+        })
+    }
+
+    /// Load a [`PaymentProof`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let note_commitment = read_scalar(&mut reader)?;
+        let recipient = PublicAddress::read(&mut reader)?;
+        let ephemeral_secret_key = read_scalar(&mut reader)?;
+
+        Ok(PaymentProof {
+            note_commitment,
+            recipient,
+            ephemeral_secret_key,
+        })
+    }
+
+    /// Stow the bytes of this [`PaymentProof`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.note_commitment.to_bytes())?;
+        self.recipient.write(&mut writer)?;
+        writer.write_all(&self.ephemeral_secret_key.to_bytes())?;
+
+        Ok(())
+    }
+
+    pub fn note_commitment(&self) -> [u8; 32] {
+        self.note_commitment.to_bytes()
+    }
+
+    pub fn recipient(&self) -> PublicAddress {
+        self.recipient
+    }
+
+    /// Check this proof against the output found on the chain, returning the
+    /// note that was sent to the recipient if the proof is valid.
+    pub fn verify(&self, merkle_note: &MerkleNote) -> Result<Note, IronfishError> {
+        if self.note_commitment != merkle_note.note_commitment {
+            return Err(IronfishError::InvalidPaymentProof);
+        }
+
+        if PUBLIC_KEY_GENERATOR * self.ephemeral_secret_key != merkle_note.ephemeral_public_key {
+            return Err(IronfishError::InvalidPaymentProof);
+        }
+
+        let shared_key = shared_secret(
+            &self.ephemeral_secret_key,
+            &self.recipient.transmission_key,
+            &merkle_note.ephemeral_public_key,
+        );
+        let note = Note::from_spender_encrypted(
+            merkle_note.aead_version,
+            self.recipient.transmission_key,
+            &shared_key,
+            &merkle_note.encrypted_note,
+        )
+        .map_err(|_| IronfishError::InvalidPaymentProof)?;
+
+        note.verify_commitment(merkle_note.note_commitment)
+            .map_err(|_| IronfishError::InvalidPaymentProof)?;
+
+        Ok(note)
+    }
+
+    /// Check that the output was sent to `recipient` with the given `memo`.
+    /// This is the check a merchant performs to resolve an "I paid you"
+    /// dispute.
+    pub fn verify_payment(
+        &self,
+        merkle_note: &MerkleNote,
+        recipient: &PublicAddress,
+        memo: &Memo,
+    ) -> Result<Note, IronfishError> {
+        let note = self.verify(merkle_note)?;
+
+        if note.owner != *recipient || note.memo != *memo {
+            return Err(IronfishError::InvalidPaymentProof);
+        }
+
+        Ok(note)
+    }
+}
+
+/// A [`PaymentProof`] for a single output, encrypted so that only the sender
+/// and, optionally, an auditor chosen by the sender can recover it. The
+/// sender can keep these alongside their wallet, or hand them to the auditor,
+/// without disclosing their outgoing view key.
+#[derive(Clone)]
+pub struct PaymentDisclosure {
+    /// Commitment of the note the proof is about
+    pub(crate) note_commitment: Scalar,
+
+    /// The proof, encrypted with a key derived from the sender's outgoing
+    /// view key and the note commitment
+    pub(crate) sender_ciphertext: [u8; ENCRYPTED_PAYMENT_PROOF_SIZE],
+
+    /// The proof, encrypted to the auditor's public address using a fresh
+    /// ephemeral key
+    pub(crate) auditor_ciphertext: Option<(SubgroupPoint, [u8; ENCRYPTED_PAYMENT_PROOF_SIZE])>,
+}
+
+impl PaymentDisclosure {
+    /// Encrypt a payment proof to the sender's outgoing view key and, if
+    /// given, to an auditor's public address.
+    pub fn new(
+        proof: &PaymentProof,
+        outgoing_view_key: &OutgoingViewKey,
+        auditor: Option<&PublicAddress>,
+    ) -> Result<Self, IronfishError> {
+        let mut plaintext = [0; PAYMENT_PROOF_SIZE];
+        proof.write(&mut plaintext[..])?;
+
+        let sender_key = sender_disclosure_key(outgoing_view_key, &proof.note_commitment);
+        let sender_ciphertext = aead::encrypt(&sender_key, &plaintext)?;
+
+        let auditor_ciphertext = match auditor {
+            Some(auditor) => {
+                let ephemeral_keys = EphemeralKeyPair::try_new()?;
+                let shared_key = shared_secret(
+                    ephemeral_keys.secret(),
+                    &auditor.transmission_key,
+                    ephemeral_keys.public(),
+                );
+                let auditor_key = auditor_disclosure_key(&shared_key, &proof.note_commitment);
+
+                Some((
+                    *ephemeral_keys.public(),
+                    aead::encrypt(&auditor_key, &plaintext)?,
+                ))
+            }
+            None => None,
+        };
+
+        Ok(PaymentDisclosure {
+            note_commitment: proof.note_commitment,
+            sender_ciphertext,
+            auditor_ciphertext,
+        })
+    }
+
+    /// Load a [`PaymentDisclosure`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let note_commitment = read_scalar(&mut reader)?;
+
+        let mut sender_ciphertext = [0; ENCRYPTED_PAYMENT_PROOF_SIZE];
+        reader.read_exact(&mut sender_ciphertext)?;
+
+        let auditor_ciphertext = match reader.read_u8()? {
+            0 => None,
+            1 => {
+                let ephemeral_public_key = read_point(&mut reader)?;
+                let mut ciphertext = [0; ENCRYPTED_PAYMENT_PROOF_SIZE];
+                reader.read_exact(&mut ciphertext)?;
+
+                Some((ephemeral_public_key, ciphertext))
+            }
+            _ => return Err(IronfishError::InvalidData),
+        };
+
+        Ok(PaymentDisclosure {
+            note_commitment,
+            sender_ciphertext,
+            auditor_ciphertext,
+        })
+    }
+
+    /// Stow the bytes of this [`PaymentDisclosure`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.note_commitment.to_bytes())?;
+        writer.write_all(&self.sender_ciphertext)?;
+
+        match &self.auditor_ciphertext {
+            Some((ephemeral_public_key, ciphertext)) => {
+                writer.write_u8(1)?;
+                writer.write_all(&ephemeral_public_key.to_bytes())?;
+                writer.write_all(ciphertext)?;
+            }
+            None => writer.write_u8(0)?,
+        }
+
+        Ok(())
+    }
+
+    pub fn note_commitment(&self) -> [u8; 32] {
+        self.note_commitment.to_bytes()
+    }
+
+    pub fn has_auditor(&self) -> bool {
+        self.auditor_ciphertext.is_some()
+    }
+
+    /// Recover the payment proof with the sender's outgoing view key.
+    pub fn decrypt_for_sender(
+        &self,
+        outgoing_view_key: &OutgoingViewKey,
+    ) -> Result<PaymentProof, IronfishError> {
+        let sender_key = sender_disclosure_key(outgoing_view_key, &self.note_commitment);
+        let plaintext: [u8; PAYMENT_PROOF_SIZE] =
+            aead::decrypt(&sender_key, &self.sender_ciphertext)?;
+
+        self.read_proof(&plaintext)
+    }
+
+    /// Recover the payment proof with the auditor's incoming view key.
+    pub fn decrypt_for_auditor(
+        &self,
+        incoming_view_key: &IncomingViewKey,
+    ) -> Result<PaymentProof, IronfishError> {
+        let (ephemeral_public_key, ciphertext) = self
+            .auditor_ciphertext
+            .as_ref()
+            .ok_or(IronfishError::InvalidDecryptionKey)?;
+
+        let shared_key = incoming_view_key.shared_secret(ephemeral_public_key);
+        let auditor_key = auditor_disclosure_key(&shared_key, &self.note_commitment);
+        let plaintext: [u8; PAYMENT_PROOF_SIZE] = aead::decrypt(&auditor_key, ciphertext)?;
+
+        self.read_proof(&plaintext)
+    }
+
+    fn read_proof(
+        &self,
+        plaintext: &[u8; PAYMENT_PROOF_SIZE],
+    ) -> Result<PaymentProof, IronfishError> {
+        let proof = PaymentProof::read(&plaintext[..])?;
+
+        // The commitment is authenticated as part of the key, but check it
+        // anyway so a disclosure can't be used for some other output
+        if proof.note_commitment != self.note_commitment {
+            return Err(IronfishError::InvalidPaymentProof);
+        }
+
+        Ok(proof)
+    }
+}
+
+impl Transaction {
+    /// Create a [`PaymentDisclosure`] for every output of this transaction
+    /// that was created with the given outgoing view key, in output order.
+    /// Outputs that weren't created with this key, such as miners fee
+    /// outputs, produce `None`.
+    pub fn payment_disclosures(
+        &self,
+        outgoing_view_key: &OutgoingViewKey,
+        auditor: Option<&PublicAddress>,
+    ) -> Result<Vec<Option<PaymentDisclosure>>, IronfishError> {
+        let mut disclosures = Vec::with_capacity(self.outputs().len());
+
+        for output in self.iter_outputs() {
+            let disclosure = match PaymentProof::new(&output.merkle_note, outgoing_view_key) {
+                Ok(proof) => Some(PaymentDisclosure::new(&proof, outgoing_view_key, auditor)?),
+                Err(_) => None,
+            };
+
+            disclosures.push(disclosure);
+        }
+
+        Ok(disclosures)
+    }
+}
+
+fn sender_disclosure_key(
+    outgoing_view_key: &OutgoingViewKey,
+    note_commitment: &Scalar,
+) -> [u8; 32] {
+    disclosure_key(0, &outgoing_view_key.view_key, note_commitment)
+}
+
+fn auditor_disclosure_key(shared_key: &[u8; 32], note_commitment: &Scalar) -> [u8; 32] {
+    disclosure_key(1, shared_key, note_commitment)
+}
+
+fn disclosure_key(tag: u8, secret: &[u8; 32], note_commitment: &Scalar) -> [u8; 32] {
+    let mut key = [0; 32];
+    key.copy_from_slice(
+        Blake2b::new()
+            .hash_length(32)
+            .personal(PAYMENT_DISCLOSURE_PERSONALIZATION)
+            .to_state()
+            .update(&[tag])
+            .update(secret)
+            .update(&note_commitment.to_bytes())
+            .finalize()
+            .as_bytes(),
+    );
+    key
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PaymentDisclosure, PaymentProof, PAYMENT_PROOF_SIZE};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        keys::{EphemeralKeyPair, SaplingKey},
+        merkle_note::MerkleNote,
+        note::{Memo, Note},
+        test_util::make_fake_witness,
+        ProposedTransaction,
+    };
+    use ironfish_zkp::primitives::ValueCommitment;
+
+    fn build_merkle_note(spender_key: &SaplingKey, receiver_key: &SaplingKey) -> MerkleNote {
+        let note = Note::new(
+            receiver_key.public_address(),
+            42,
+            "invoice 1234",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let diffie_hellman_keys = EphemeralKeyPair::new();
+        let value_commitment = ValueCommitment::new(note.value, note.asset_generator().point());
+
+        MerkleNote::new(spender_key, &note, &value_commitment, &diffie_hellman_keys)
+    }
+
+    #[test]
+    fn test_payment_proof_round_trip() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let merkle_note = build_merkle_note(&spender_key, &receiver_key);
+
+        let proof = PaymentProof::new(&merkle_note, spender_key.outgoing_view_key())
+            .expect("should be able to create a payment proof");
+
+        let mut serialized = vec![];
+        proof.write(&mut serialized).expect("should serialize");
+        assert_eq!(serialized.len(), PAYMENT_PROOF_SIZE);
+
+        let read_back = PaymentProof::read(&serialized[..]).expect("should deserialize");
+        let note = read_back
+            .verify_payment(
+                &merkle_note,
+                &receiver_key.public_address(),
+                &Memo::from("invoice 1234"),
+            )
+            .expect("proof should verify");
+
+        assert_eq!(note.value, 42);
+        assert_eq!(note.sender, spender_key.public_address());
+    }
+
+    #[test]
+    fn test_payment_proof_wrong_details() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let third_party_key = SaplingKey::generate_key();
+        let merkle_note = build_merkle_note(&spender_key, &receiver_key);
+
+        let proof = PaymentProof::new(&merkle_note, spender_key.outgoing_view_key()).unwrap();
+
+        assert!(proof
+            .verify_payment(
+                &merkle_note,
+                &third_party_key.public_address(),
+                &Memo::from("invoice 1234"),
+            )
+            .is_err());
+        assert!(proof
+            .verify_payment(
+                &merkle_note,
+                &receiver_key.public_address(),
+                &Memo::from("invoice 9999"),
+            )
+            .is_err());
+
+        // A proof can't be checked against some other output
+        let other_merkle_note = build_merkle_note(&spender_key, &receiver_key);
+        assert!(proof.verify(&other_merkle_note).is_err());
+
+        // A forged recipient fails to decrypt the note
+        let mut forged = proof;
+        forged.recipient = third_party_key.public_address();
+        assert!(forged.verify(&merkle_note).is_err());
+    }
+
+    #[test]
+    fn test_payment_proof_requires_outgoing_view_key() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let merkle_note = build_merkle_note(&spender_key, &receiver_key);
+
+        assert!(PaymentProof::new(&merkle_note, receiver_key.outgoing_view_key()).is_err());
+    }
+
+    #[test]
+    fn test_payment_disclosure() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let auditor_key = SaplingKey::generate_key();
+        let merkle_note = build_merkle_note(&spender_key, &receiver_key);
+
+        let proof = PaymentProof::new(&merkle_note, spender_key.outgoing_view_key()).unwrap();
+        let disclosure = PaymentDisclosure::new(
+            &proof,
+            spender_key.outgoing_view_key(),
+            Some(&auditor_key.public_address()),
+        )
+        .expect("should be able to create a disclosure");
+
+        let mut serialized = vec![];
+        disclosure.write(&mut serialized).unwrap();
+        let disclosure = PaymentDisclosure::read(&serialized[..]).unwrap();
+        assert!(disclosure.has_auditor());
+
+        let sender_proof = disclosure
+            .decrypt_for_sender(spender_key.outgoing_view_key())
+            .expect("sender should be able to open the disclosure");
+        assert_eq!(sender_proof.verify(&merkle_note).unwrap().value, 42);
+
+        let auditor_proof = disclosure
+            .decrypt_for_auditor(auditor_key.incoming_view_key())
+            .expect("auditor should be able to open the disclosure");
+        assert_eq!(auditor_proof.recipient(), receiver_key.public_address());
+
+        // Nobody else can
+        assert!(disclosure
+            .decrypt_for_sender(receiver_key.outgoing_view_key())
+            .is_err());
+        assert!(disclosure
+            .decrypt_for_auditor(receiver_key.incoming_view_key())
+            .is_err());
+
+        // Without an auditor only the sender can open it
+        let private =
+            PaymentDisclosure::new(&proof, spender_key.outgoing_view_key(), None).unwrap();
+        assert!(!private.has_auditor());
+        assert!(private
+            .decrypt_for_auditor(auditor_key.incoming_view_key())
+            .is_err());
+    }
+
+    #[test]
+    fn test_transaction_payment_disclosures() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+
+        let in_note = Note::new(
+            spender_key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            receiver_key.public_address(),
+        );
+        let out_note = Note::new(
+            receiver_key.public_address(),
+            40,
+            "",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        let mut transaction = ProposedTransaction::new(spender_key.clone());
+        transaction.add_spend(in_note, &witness).unwrap();
+        transaction.add_output(out_note).unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        let disclosures = posted
+            .payment_disclosures(spender_key.outgoing_view_key(), None)
+            .unwrap();
+        assert_eq!(disclosures.len(), posted.outputs().len());
+
+        for (disclosure, output) in disclosures.iter().zip(posted.iter_outputs()) {
+            let proof = disclosure
+                .as_ref()
+                .expect("spender created every output")
+                .decrypt_for_sender(spender_key.outgoing_view_key())
+                .unwrap();
+            assert!(proof.verify(&output.merkle_note).is_ok());
+        }
+
+        let disclosures = posted
+            .payment_disclosures(receiver_key.outgoing_view_key(), None)
+            .unwrap();
+        assert!(disclosures.iter().all(|d| d.is_none()));
+    }
+}
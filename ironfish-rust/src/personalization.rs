@@ -0,0 +1,86 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The personalization of every hash the protocol uses, so that auditors and
+//! other implementations can check hashing domains byte for byte.
+//!
+//! BLAKE2s personalizations are 8 bytes and BLAKE2b ones are 16, except for
+//! the signature hash. Personalizations that differ per network, for
+//! expanding spending keys and deriving asset identifiers, are returned by
+//! [`crate::NetworkKind::spending_key_personalization`] and
+//! [`crate::NetworkKind::asset_id_personalization`]; the constant
+//! [`ASSET_ID_PERSONALIZATION`] is the mainnet one. See
+//! [`crate::assets::asset::derive_asset_id_preimage`] for what's hashed into
+//! an asset identifier.
+
+/// BLAKE2s personalizations fixed in the circuits
+pub use ironfish_zkp::constants::{
+    ASSET_ID_PERSONALIZATION, CRH_IVK_PERSONALIZATION, PRF_NF_PERSONALIZATION,
+    VALUE_COMMITMENT_GENERATOR_PERSONALIZATION,
+};
+
+/// BLAKE2b personalization of the hash signed by the spends, mints and
+/// binding signature of a transaction
+pub const SIGNATURE_HASH_PERSONALIZATION: &[u8; 8] = b"IFsighsh";
+
+/// BLAKE2b personalization for the Diffie Hellman shared secret that notes
+/// are encrypted with
+pub const DIFFIE_HELLMAN_PERSONALIZATION: &[u8; 16] = b"Iron Fish shared";
+
+/// BLAKE2b personalization for the key that encrypts a note's keys to the
+/// sender's outgoing view key
+pub const SHARED_KEY_PERSONALIZATION: &[u8; 16] = b"Iron Fish Keyenc";
+
+/// BLAKE2b personalization for deriving the spending key of an account's
+/// per-asset key
+pub const ASSET_KEY_PERSONALIZATION: &[u8; 16] = b"Iron Fish AsstKy";
+
+/// BLAKE2b personalization for deriving memo keys from outgoing view keys
+pub const MEMO_KEY_PERSONALIZATION: &[u8; 16] = b"Iron Fish MemoKy";
+
+/// BLAKE2b personalization for deriving an Iron Fish spending key from an
+/// imported Zcash spending key
+pub const ZCASH_IMPORT_PERSONALIZATION: &[u8; 16] = b"IF_ZcashSkImport";
+
+/// BLAKE2b personalization of the hash signed by an account rotation
+pub const ACCOUNT_ROTATION_PERSONALIZATION: &[u8; 16] = b"IF_AccountRotate";
+
+/// BLAKE2b personalization of the hash signed by an asset metadata update
+pub const METADATA_UPDATE_PERSONALIZATION: &[u8; 16] = b"IF_AssetMetaUpdt";
+
+/// BLAKE2b personalization for the key that encrypts a payment proof to an
+/// auditor
+pub const PAYMENT_DISCLOSURE_PERSONALIZATION: &[u8; 16] = b"IF_PayDisclosure";
+
+/// BLAKE2b personalization of the challenge of a proof that two value
+/// commitments are to the same value and asset
+pub const COMMITMENT_EQUALITY_PERSONALIZATION: &[u8; 16] = b"IF_CvEqualityPrf";
+
+/// BLAKE2b personalization of the hash signed by an asset attestation
+pub const ASSET_ATTESTATION_PERSONALIZATION: &[u8; 16] = b"IF_AssetAttestat";
+
+/// BLAKE2b personalization of the hash signed by a burn receipt
+pub const BURN_RECEIPT_PERSONALIZATION: &[u8; 16] = b"IF_BurnReceipt__";
+
+/// BLAKE2b personalization of the hash of a domain separated message signed
+/// with a [`crate::keys::SigningKey`]
+pub const DOMAIN_SIGNATURE_PERSONALIZATION: &[u8; 16] = b"IF_DomainSigHash";
+
+/// BLAKE2b personalization of the nodes of the merkle tree over an
+/// [`crate::assets::registry::AssetRegistrySnapshot`]
+pub const ASSET_REGISTRY_PERSONALIZATION: &[u8; 16] = b"IF_AssetRegistry";
+
+/// BLAKE2b personalization for deriving the spending keys of the test
+/// accounts of the `fixtures` module, which only exists with the `fixtures`
+/// feature
+pub const FIXTURE_PERSONALIZATION: &[u8; 16] = b"IF_FixtureSeedKy";
+
+/// BLAKE2b personalization of the fingerprint of a spending key carried by
+/// its shares, see [`crate::keys::KeyShare`]
+pub const KEY_SHARE_PERSONALIZATION: &[u8; 16] = b"IF_KeyShareGroup";
+
+/// BLAKE3 prefix of the nodes of the merkle tree over the transactions of a
+/// block, see [`crate::block_inclusion::transaction_merkle_root`]. It's
+/// hashed as data, BLAKE3 having no personalization.
+pub const TRANSACTION_ROOT_PERSONALIZATION: &[u8; 26] = b"IRON_FISH_TRANSACTION_ROOT";
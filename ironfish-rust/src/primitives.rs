@@ -0,0 +1,185 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Every pseudorandom function and key derivation function the protocol
+//! uses, named after the function of the Sapling protocol specification
+//! each one instantiates, so that reviewers can check the implementation
+//! against the specification one function at a time.
+//!
+//! These work on bytes rather than points and scalars: they're the exact
+//! hashes keys and notes are built with, and the rest of the crate calls
+//! them. How their outputs are read, as a scalar or a key, is up to the
+//! caller. The tests pin each of them to a test vector.
+//!
+//! Iron Fish uses its own personalizations, see [`crate::personalization`],
+//! except for `CRH^ivk`, which the circuits fix to the one of Zcash.
+
+use blake2b_simd::Params as Blake2b;
+use blake2s_simd::Params as Blake2s;
+
+use crate::{
+    network::NetworkKind,
+    personalization::{
+        CRH_IVK_PERSONALIZATION, DIFFIE_HELLMAN_PERSONALIZATION, PRF_NF_PERSONALIZATION,
+        SHARED_KEY_PERSONALIZATION,
+    },
+};
+
+/// `PRF^expand(sk, t)`: BLAKE2b-512 of the spending key then the modifier,
+/// personalized per network. The modifiers are 0 for `ask`, 1 for `nsk` and
+/// 2 for `ovk`, which is the first 32 bytes of the output.
+pub fn prf_expand(spending_key: &[u8; 32], modifier: u8, network: NetworkKind) -> [u8; 64] {
+    let mut output = [0; 64];
+    output.copy_from_slice(
+        Blake2b::new()
+            .hash_length(64)
+            .personal(network.spending_key_personalization())
+            .to_state()
+            .update(spending_key)
+            .update(&[modifier])
+            .finalize()
+            .as_bytes(),
+    );
+
+    output
+}
+
+/// `CRH^ivk(ak, nk)`: BLAKE2s-256 of the encodings of the authorizing key
+/// and the nullifier deriving key, truncated to 251 bits so that it's a
+/// valid encoding of a Jubjub scalar, the incoming view key
+pub fn crh_ivk(authorizing_key: &[u8; 32], nullifier_deriving_key: &[u8; 32]) -> [u8; 32] {
+    let mut output = [0; 32];
+    output.copy_from_slice(
+        Blake2s::new()
+            .hash_length(32)
+            .personal(CRH_IVK_PERSONALIZATION)
+            .to_state()
+            .update(authorizing_key)
+            .update(nullifier_deriving_key)
+            .finalize()
+            .as_bytes(),
+    );
+    output[31] &= 0b0000_0111;
+
+    output
+}
+
+/// `KDF^Sapling(sharedSecret, epk)`: BLAKE2b-256 of the encodings of the
+/// Diffie Hellman shared point and the ephemeral public key, the key notes
+/// are encrypted with
+pub fn kdf_sapling(shared_point: &[u8; 32], ephemeral_public_key: &[u8; 32]) -> [u8; 32] {
+    let mut output = [0; 32];
+    output.copy_from_slice(
+        Blake2b::new()
+            .hash_length(32)
+            .personal(DIFFIE_HELLMAN_PERSONALIZATION)
+            .to_state()
+            .update(shared_point)
+            .update(ephemeral_public_key)
+            .finalize()
+            .as_bytes(),
+    );
+
+    output
+}
+
+/// `PRF^ock(ovk, cv, cm, epk)`: BLAKE2b-256 of the outgoing view key and the
+/// encodings of the value commitment, the note commitment and the ephemeral
+/// public key of an output, the key its note encryption keys are encrypted
+/// with for the spender
+pub fn prf_ock(
+    outgoing_view_key: &[u8; 32],
+    value_commitment: &[u8; 32],
+    note_commitment: &[u8; 32],
+    ephemeral_public_key: &[u8; 32],
+) -> [u8; 32] {
+    let mut output = [0; 32];
+    output.copy_from_slice(
+        Blake2b::new()
+            .hash_length(32)
+            .personal(SHARED_KEY_PERSONALIZATION)
+            .to_state()
+            .update(outgoing_view_key)
+            .update(value_commitment)
+            .update(note_commitment)
+            .update(ephemeral_public_key)
+            .finalize()
+            .as_bytes(),
+    );
+
+    output
+}
+
+/// `PRF^nf(nk, rho)`: BLAKE2s-256 of the encodings of the nullifier deriving
+/// key and of `rho`, the note commitment point moved by the position of the
+/// note, which is the nullifier of the note
+pub fn prf_nf(nullifier_deriving_key: &[u8; 32], rho: &[u8; 32]) -> [u8; 32] {
+    let mut output = [0; 32];
+    output.copy_from_slice(
+        Blake2s::new()
+            .hash_length(32)
+            .personal(PRF_NF_PERSONALIZATION)
+            .to_state()
+            .update(nullifier_deriving_key)
+            .update(rho)
+            .finalize()
+            .as_bytes(),
+    );
+
+    output
+}
+
+#[cfg(test)]
+mod test {
+    use super::{crh_ivk, kdf_sapling, prf_expand, prf_nf, prf_ock};
+    use crate::{keys::SaplingKey, network::NetworkKind, serializing::bytes_to_hex};
+
+    const SPENDING_KEY: [u8; 32] = [
+        0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24,
+        25, 26, 27, 28, 29, 30, 31,
+    ];
+
+    #[test]
+    fn test_prf_expand_vectors() {
+        let vectors = [
+            (0, NetworkKind::Mainnet, "aefa70ba51a0b61f26d982b16416540e8f68d91febe08be0e59ca51b79d424922ee420f4e47cbf583ab5791e35d16644b9c24c77533e839a06dfbe8e7b63815f"),
+            (1, NetworkKind::Mainnet, "f41fc60dce4af29076baeedeeeab9705656d5be455442ec0df0c11858629af4a2589ca42b82d5611a326e87d55384aeab4504121b3cc7f2acaa6a52c5ae33933"),
+            (2, NetworkKind::Mainnet, "d72009a761ff450daec3b43d8d265576198697ccf17b530fad80684755538211b1f01710a71ca72c9b629acdb90ef048b41e8fadc293cfc7640a477d71518fc0"),
+            (0, NetworkKind::Testnet, "e272b943d22892f0a03f4f4172c66df4ce4d5be88479892e0340ea00b88ec7dd7ed41342244c9ca3e63205bf9ab298413da8a1c7259225acbf001b87a6b5a1e8"),
+        ];
+        for (modifier, network, expected) in vectors {
+            assert_eq!(
+                bytes_to_hex(&prf_expand(&SPENDING_KEY, modifier, network)),
+                expected
+            );
+        }
+
+        // The outgoing view key is the start of the expansion with modifier 2
+        let key = SaplingKey::new(SPENDING_KEY).unwrap();
+        assert_eq!(
+            key.outgoing_view_key().hex_key(),
+            "d72009a761ff450daec3b43d8d265576198697ccf17b530fad80684755538211"
+        );
+    }
+
+    #[test]
+    fn test_hash_vectors() {
+        assert_eq!(
+            bytes_to_hex(&crh_ivk(&[1; 32], &[2; 32])),
+            "3be11f28737a278ee3f149e29fdb7636688f2796c0cf2fccd6570a713b640202"
+        );
+        assert_eq!(
+            bytes_to_hex(&kdf_sapling(&[3; 32], &[4; 32])),
+            "1b913d3b99a8edd0a15e87875b131f72e78b4af283db700b9a59a3fdda6defa4"
+        );
+        assert_eq!(
+            bytes_to_hex(&prf_ock(&[5; 32], &[6; 32], &[7; 32], &[8; 32])),
+            "744044df956ba738964aa9af4efc09e4de80f2964572bc47f3c726c32373570b"
+        );
+        assert_eq!(
+            bytes_to_hex(&prf_nf(&[9; 32], &[10; 32])),
+            "e193ac839839480a85ff6772c9f94181611fbf3a4fb626758e9eb180c3146099"
+        );
+    }
+}
@@ -0,0 +1,335 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The backend that proves and verifies the spend, output and mint circuits.
+//!
+//! Transactions are always proven with [`Groth16`] over the Sapling
+//! parameters, which is what the network accepts. With the experimental
+//! `experimental-proof-systems` feature, a research fork can install another
+//! [`ProofSystem`] with [`set_proof_system`], for example one built on PLONK
+//! or Halo2 from the same circuit witnesses, and keep using everything else
+//! in this crate: transaction assembly, keys, note encryption and
+//! serialization.
+//!
+//! Proofs are still carried, and serialized, in the shape of a Groth16 proof
+//! of three points. A backend whose proofs don't fit that shape has to change
+//! the description formats as well, which this hook deliberately leaves
+//! alone so that the wire format can't change by accident.
+
+use std::{sync::Arc, time::Instant};
+
+use bellman::groth16::{self, batch::Verifier};
+use bls12_381::{Bls12, Scalar};
+use ironfish_zkp::proofs::{MintAsset, Output, Spend};
+use lazy_static::lazy_static;
+
+#[cfg(feature = "experimental-proof-systems")]
+use std::sync::RwLock;
+
+use crate::{
+    errors::IronfishError,
+    metrics::{record_proofs, Circuit},
+    parameter_sets::parameters_for_version,
+    randomness::with_rng,
+    sapling_bls12::SAPLING,
+    Sapling,
+};
+
+/// A proof of one of the circuits
+pub type Proof = groth16::Proof<Bls12>;
+
+/// Proves and verifies the circuits of transactions. The circuits carry every
+/// witness of the statement, and `public_inputs` are the inputs of the
+/// statement in the order [`crate::transaction::spends::SpendDescription`]
+/// and the other descriptions lay them out.
+pub trait ProofSystem: Send + Sync {
+    fn prove_spend(&self, circuit: Spend) -> Result<Proof, IronfishError>;
+
+    fn prove_output(&self, circuit: Output) -> Result<Proof, IronfishError>;
+
+    fn prove_mint(&self, circuit: MintAsset) -> Result<Proof, IronfishError>;
+
+    fn verify(
+        &self,
+        circuit: Circuit,
+        proof: &Proof,
+        public_inputs: &[Scalar],
+    ) -> Result<(), IronfishError>;
+
+    /// Verify many proofs of the circuit at once. Only has to say whether
+    /// all of them are valid, not which one isn't. Verifies them one at a
+    /// time by default.
+    fn verify_batch(
+        &self,
+        circuit: Circuit,
+        proofs: &[(&Proof, Vec<Scalar>)],
+    ) -> Result<(), IronfishError> {
+        for (proof, public_inputs) in proofs {
+            self.verify(circuit, proof, public_inputs)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The proof system of the Iron Fish network: Groth16 with the Sapling
+/// parameters
+pub struct Groth16;
+
+impl ProofSystem for Groth16 {
+    fn prove_spend(&self, circuit: Spend) -> Result<Proof, IronfishError> {
+        let params = SAPLING.spend_params.load()?;
+        Ok(with_rng(|rng| {
+            groth16::create_random_proof(circuit, &*params, rng)
+        })??)
+    }
+
+    fn prove_output(&self, circuit: Output) -> Result<Proof, IronfishError> {
+        let params = SAPLING.output_params.load()?;
+        Ok(with_rng(|rng| {
+            groth16::create_random_proof(circuit, &*params, rng)
+        })??)
+    }
+
+    fn prove_mint(&self, circuit: MintAsset) -> Result<Proof, IronfishError> {
+        let params = SAPLING.mint_params.load()?;
+        Ok(with_rng(|rng| {
+            groth16::create_random_proof(circuit, &*params, rng)
+        })??)
+    }
+
+    fn verify(
+        &self,
+        circuit: Circuit,
+        proof: &Proof,
+        public_inputs: &[Scalar],
+    ) -> Result<(), IronfishError> {
+        Groth16::verify_with(&SAPLING, circuit, proof, public_inputs)
+    }
+
+    fn verify_batch(
+        &self,
+        circuit: Circuit,
+        proofs: &[(&Proof, Vec<Scalar>)],
+    ) -> Result<(), IronfishError> {
+        Groth16::verify_batch_with(&SAPLING, circuit, proofs)
+    }
+}
+
+impl Groth16 {
+    /// Verify a proof with the verifying key of the given parameters
+    pub(crate) fn verify_with(
+        sapling: &Sapling,
+        circuit: Circuit,
+        proof: &Proof,
+        public_inputs: &[Scalar],
+    ) -> Result<(), IronfishError> {
+        let verifying_key = match circuit {
+            Circuit::Spend => &sapling.spend_verifying_key,
+            Circuit::Output => &sapling.output_verifying_key,
+            Circuit::Mint => &sapling.mint_verifying_key,
+        };
+
+        Ok(groth16::verify_proof(verifying_key, proof, public_inputs)?)
+    }
+
+    /// Verify many proofs at once with the verifying key of the given
+    /// parameters
+    pub(crate) fn verify_batch_with(
+        sapling: &Sapling,
+        circuit: Circuit,
+        proofs: &[(&Proof, Vec<Scalar>)],
+    ) -> Result<(), IronfishError> {
+        let verifying_key = match circuit {
+            Circuit::Spend => &sapling.spend_params.vk,
+            Circuit::Output => &sapling.output_params.vk,
+            Circuit::Mint => &sapling.mint_params.vk,
+        };
+
+        let mut verifier = Verifier::<Bls12>::new();
+        for (proof, public_inputs) in proofs {
+            verifier.queue((*proof, &public_inputs[..]));
+        }
+
+        Ok(with_rng(|rng| verifier.verify(rng, verifying_key))??)
+    }
+}
+
+lazy_static! {
+    static ref GROTH16: Arc<dyn ProofSystem> = Arc::new(Groth16);
+}
+
+#[cfg(feature = "experimental-proof-systems")]
+lazy_static! {
+    static ref PROOF_SYSTEM: RwLock<Option<Arc<dyn ProofSystem>>> = RwLock::new(None);
+}
+
+/// Prove and verify with the given backend from now on, replacing
+/// [`Groth16`]. Transactions proven with anything else won't be accepted by
+/// the Iron Fish network.
+#[cfg(feature = "experimental-proof-systems")]
+pub fn set_proof_system(proof_system: Arc<dyn ProofSystem>) {
+    *PROOF_SYSTEM.write().unwrap() = Some(proof_system);
+}
+
+/// Go back to proving and verifying with [`Groth16`].
+#[cfg(feature = "experimental-proof-systems")]
+pub fn clear_proof_system() {
+    *PROOF_SYSTEM.write().unwrap() = None;
+}
+
+/// The backend installed instead of [`Groth16`], if any. Cloned out of the
+/// lock, so that setting a backend doesn't wait for proofs in progress.
+#[cfg(feature = "experimental-proof-systems")]
+fn installed_proof_system() -> Option<Arc<dyn ProofSystem>> {
+    PROOF_SYSTEM.read().unwrap().clone()
+}
+
+#[cfg(not(feature = "experimental-proof-systems"))]
+fn installed_proof_system() -> Option<Arc<dyn ProofSystem>> {
+    None
+}
+
+/// The backend in use
+pub(crate) fn proof_system() -> Arc<dyn ProofSystem> {
+    installed_proof_system().unwrap_or_else(|| GROTH16.clone())
+}
+
+/// Verify a proof with the backend in use, recording the outcome
+pub(crate) fn verify_proof(
+    circuit: Circuit,
+    proof: &Proof,
+    public_inputs: &[Scalar],
+) -> Result<(), IronfishError> {
+    let start = Instant::now();
+    let result = proof_system().verify(circuit, proof, public_inputs);
+    record_proofs(circuit, 1, start.elapsed(), &result);
+
+    result
+}
+
+/// Verify a proof of a posted transaction of the given version like
+/// [`verify_proof`], with [`Groth16`] over the parameters of the version,
+/// see [`crate::parameter_sets`]
+pub(crate) fn verify_proof_for_version(
+    version: u8,
+    circuit: Circuit,
+    proof: &Proof,
+    public_inputs: &[Scalar],
+) -> Result<(), IronfishError> {
+    let start = Instant::now();
+    let result = match installed_proof_system() {
+        Some(proof_system) => proof_system.verify(circuit, proof, public_inputs),
+        None => Groth16::verify_with(
+            &parameters_for_version(version),
+            circuit,
+            proof,
+            public_inputs,
+        ),
+    };
+    record_proofs(circuit, 1, start.elapsed(), &result);
+
+    result
+}
+
+/// Verify a batch of proofs of posted transactions of the given version,
+/// recording the outcome, see [`verify_proof_for_version`]
+pub(crate) fn verify_proofs(
+    version: u8,
+    circuit: Circuit,
+    proofs: &[(&Proof, Vec<Scalar>)],
+) -> Result<(), IronfishError> {
+    let start = Instant::now();
+    let result = match installed_proof_system() {
+        Some(proof_system) => proof_system.verify_batch(circuit, proofs),
+        None => Groth16::verify_batch_with(&parameters_for_version(version), circuit, proofs),
+    };
+    record_proofs(circuit, proofs.len(), start.elapsed(), &result);
+
+    result
+}
+
+#[cfg(all(test, feature = "experimental-proof-systems"))]
+mod test {
+    use super::{clear_proof_system, set_proof_system, Groth16, Proof, ProofSystem};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::SaplingKey,
+        metrics::Circuit,
+        note::Note,
+        test_util::make_fake_witness,
+        transaction::{batch_verify_transactions, ProposedTransaction},
+    };
+    use bls12_381::Scalar;
+    use ironfish_zkp::proofs::{MintAsset, Output, Spend};
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    /// Counts the proofs passing through, leaving the proving to Groth16 so
+    /// that transactions built by other tests meanwhile stay valid
+    #[derive(Default)]
+    struct Counting {
+        proven: AtomicUsize,
+        verified: AtomicUsize,
+    }
+
+    impl ProofSystem for Counting {
+        fn prove_spend(&self, circuit: Spend) -> Result<Proof, IronfishError> {
+            self.proven.fetch_add(1, Ordering::SeqCst);
+            Groth16.prove_spend(circuit)
+        }
+
+        fn prove_output(&self, circuit: Output) -> Result<Proof, IronfishError> {
+            self.proven.fetch_add(1, Ordering::SeqCst);
+            Groth16.prove_output(circuit)
+        }
+
+        fn prove_mint(&self, circuit: MintAsset) -> Result<Proof, IronfishError> {
+            self.proven.fetch_add(1, Ordering::SeqCst);
+            Groth16.prove_mint(circuit)
+        }
+
+        fn verify(
+            &self,
+            circuit: Circuit,
+            proof: &Proof,
+            public_inputs: &[Scalar],
+        ) -> Result<(), IronfishError> {
+            self.verified.fetch_add(1, Ordering::SeqCst);
+            Groth16.verify(circuit, proof, public_inputs)
+        }
+    }
+
+    #[test]
+    fn test_custom_proof_system() {
+        let counting = Arc::new(Counting::default());
+        set_proof_system(counting.clone());
+
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(note, &witness).unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        // Batches fall back to verifying one proof at a time
+        batch_verify_transactions([&posted]).unwrap();
+        clear_proof_system();
+
+        // A spend and a change output, each checked once when built and
+        // once more in the batch
+        assert!(counting.proven.load(Ordering::SeqCst) >= 2);
+        assert!(counting.verified.load(Ordering::SeqCst) >= 4);
+    }
+}
@@ -0,0 +1,189 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Proving parameters that are only parsed when they're needed.
+//!
+//! Parsed, the spend, output and mint proving keys take around 1GB, which is
+//! more than mobile and embedded wallets can keep resident. [`ProvingParams`]
+//! holds on to the serialized parameters instead, either bundled in the
+//! binary or, with the `params-fetch` feature, memory-mapped from a file
+//! checked against its pinned checksum, so the OS only pages them in while
+//! they're read. The verifying key at the start of the parameters is small
+//! and needed to verify anything, so it's parsed up front. The rest is parsed
+//! the first time a proof of that circuit is built, and can be dropped again
+//! with [`ProvingParams::release`] once the process is done proving.
+
+use bellman::groth16;
+use bls12_381::Bls12;
+
+#[cfg(feature = "params-fetch")]
+use sha2::{Digest, Sha512};
+
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "params-fetch")]
+use std::{fs, path::Path};
+
+use crate::errors::IronfishError;
+
+/// Where the serialized parameters of a circuit are kept while they aren't
+/// parsed
+enum ParamsSource {
+    #[cfg(not(feature = "params-fetch"))]
+    Bundled(&'static [u8]),
+    #[cfg(feature = "params-fetch")]
+    Mapped(memmap2::Mmap),
+}
+
+impl ParamsSource {
+    fn bytes(&self) -> &[u8] {
+        match self {
+            #[cfg(not(feature = "params-fetch"))]
+            ParamsSource::Bundled(bytes) => *bytes,
+            #[cfg(feature = "params-fetch")]
+            ParamsSource::Mapped(map) => &map[..],
+        }
+    }
+}
+
+/// The parameters of a single circuit, with the proving key parsed on demand
+pub struct ProvingParams {
+    pub(crate) vk: groth16::VerifyingKey<Bls12>,
+    source: Option<ParamsSource>,
+    loaded: Mutex<Option<Arc<groth16::Parameters<Bls12>>>>,
+}
+
+impl ProvingParams {
+    /// Use parameters bundled in the binary, which are trusted and parsed
+    /// without validating the points.
+    #[cfg(not(feature = "params-fetch"))]
+    pub(crate) fn from_bundled(bytes: &'static [u8]) -> Result<Self, IronfishError> {
+        ProvingParams::from_source(ParamsSource::Bundled(bytes))
+    }
+
+    /// Memory-map the parameters in the given file, failing with
+    /// [`IronfishError::InvalidParamsChecksum`] unless they have the given
+    /// SHA-512 checksum. Like the bundled ones, they're parsed without
+    /// validating the points, so the checksum is what makes them trusted. The
+    /// file must not be modified while these parameters are alive.
+    #[cfg(feature = "params-fetch")]
+    pub(crate) fn map_file(path: &Path, checksum: &[u8; 64]) -> Result<Self, IronfishError> {
+        let file = fs::File::open(path)?;
+        // SAFETY: parameter files are only ever written whole, by
+        // `params_fetch`, before they are loaded
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        // The verifying key is parsed from the very bytes that were hashed
+        if Sha512::digest(&map[..]).as_slice() != checksum {
+            return Err(IronfishError::InvalidParamsChecksum);
+        }
+
+        ProvingParams::from_source(ParamsSource::Mapped(map))
+    }
+
+    /// Use parameters that were already parsed. Since there's nothing to
+    /// parse them from again, they're never released.
+    pub(crate) fn from_params(params: groth16::Parameters<Bls12>) -> Self {
+        ProvingParams {
+            vk: params.vk.clone(),
+            source: None,
+            loaded: Mutex::new(Some(Arc::new(params))),
+        }
+    }
+
+    fn from_source(source: ParamsSource) -> Result<Self, IronfishError> {
+        let vk = groth16::VerifyingKey::read(source.bytes())?;
+
+        Ok(ProvingParams {
+            vk,
+            source: Some(source),
+            loaded: Mutex::new(None),
+        })
+    }
+
+    /// Get the proving parameters, parsing them if this is the first use
+    /// since they were loaded or released. Parsing takes a couple seconds.
+    pub fn load(&self) -> Result<Arc<groth16::Parameters<Bls12>>, IronfishError> {
+        let mut loaded = self.loaded.lock().unwrap();
+
+        if let Some(params) = loaded.as_ref() {
+            return Ok(params.clone());
+        }
+
+        let source = self
+            .source
+            .as_ref()
+            .expect("parameters without a source are always loaded");
+        let params = Arc::new(groth16::Parameters::read(source.bytes(), false)?);
+        *loaded = Some(params.clone());
+
+        Ok(params)
+    }
+
+    /// Whether the proving parameters are currently parsed and resident
+    pub fn is_loaded(&self) -> bool {
+        self.loaded.lock().unwrap().is_some()
+    }
+
+    /// Drop the parsed proving parameters, if they can be parsed again. Proofs
+    /// that are being built keep their own reference until they finish.
+    pub fn release(&self) {
+        if self.source.is_some() {
+            *self.loaded.lock().unwrap() = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ProvingParams;
+
+    #[cfg(not(feature = "params-fetch"))]
+    const SPEND_PARAMS: &[u8] = include_bytes!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/src/sapling_params/sapling-spend.params"
+    ));
+
+    #[cfg(not(feature = "params-fetch"))]
+    #[test]
+    fn test_load_and_release() {
+        let params = ProvingParams::from_bundled(SPEND_PARAMS).unwrap();
+        assert!(!params.is_loaded());
+
+        let loaded = params.load().unwrap();
+        assert!(params.is_loaded());
+        assert!(loaded.vk == params.vk);
+
+        params.release();
+        assert!(!params.is_loaded());
+        // Reloading parses the same parameters
+        assert!(params.load().unwrap() == loaded);
+
+        // Already parsed parameters can't be parsed again, so stay loaded
+        let parsed = ProvingParams::from_params((*loaded).clone());
+        parsed.release();
+        assert!(parsed.is_loaded());
+    }
+
+    #[cfg(feature = "params-fetch")]
+    #[test]
+    fn test_map_file() {
+        use crate::{errors::IronfishError, params_fetch::expected_checksum};
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/sapling_params/sapling-output.params"
+        );
+        let checksum = expected_checksum("sapling-output.params").unwrap();
+        let params = ProvingParams::map_file(path.as_ref(), &checksum).unwrap();
+        assert!(!params.is_loaded());
+        params.load().unwrap();
+
+        // The parameters of another circuit don't match the checksum
+        let spend_checksum = expected_checksum("sapling-spend.params").unwrap();
+        assert!(matches!(
+            ProvingParams::map_file(path.as_ref(), &spend_checksum),
+            Err(IronfishError::InvalidParamsChecksum)
+        ));
+    }
+}
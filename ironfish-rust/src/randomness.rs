@@ -0,0 +1,306 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Where the crate draws its randomness from.
+//!
+//! Spending keys, note and commitment randomness, the randomness of proofs
+//! and signatures, and the salts of backups are all drawn from the
+//! [`RandomnessSource`] in use. That's the operating system,
+//! [`OsRandomness`], unless another source is installed for the whole
+//! process with [`set_randomness_source`], for example one backed by a
+//! hardware security module, or for a single call with
+//! [`with_randomness_source`].
+//!
+//! A source can fail, when the device behind it goes away for instance.
+//! Whatever was drawn from a failed source is discarded: posting a
+//! transaction, proving and signing return
+//! [`IronfishError::RandomnessError`], while the constructors that have
+//! never returned an error, such as [`crate::Note::new`] or
+//! [`crate::SaplingKey::generate_key`], panic.
+
+use std::{
+    cell::RefCell,
+    io,
+    sync::{Arc, RwLock},
+};
+
+use lazy_static::lazy_static;
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+
+#[cfg(any(test, feature = "testing"))]
+use rand::{rngs::StdRng, SeedableRng};
+#[cfg(any(test, feature = "testing"))]
+use std::sync::Mutex;
+
+use crate::errors::IronfishError;
+
+/// Provides the randomness of every secret the crate picks. Has to be a
+/// cryptographically secure generator.
+pub trait RandomnessSource: Send + Sync {
+    /// Fill `dest` entirely with random bytes, or fail
+    fn try_fill_bytes(&self, dest: &mut [u8]) -> Result<(), IronfishError>;
+}
+
+/// The random number generator of the operating system
+pub struct OsRandomness;
+
+impl RandomnessSource for OsRandomness {
+    fn try_fill_bytes(&self, dest: &mut [u8]) -> Result<(), IronfishError> {
+        OsRng
+            .try_fill_bytes(dest)
+            .map_err(|_| IronfishError::RandomnessError)
+    }
+}
+
+/// A generator seeded with a fixed number, to reproduce the keys,
+/// transactions and proofs of a test. Anyone who knows the seed knows every
+/// secret drawn from it, so this is only for tests.
+#[cfg(any(test, feature = "testing"))]
+pub struct SeededRandomness {
+    rng: Mutex<StdRng>,
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl SeededRandomness {
+    pub fn new(seed: u64) -> Self {
+        SeededRandomness {
+            rng: Mutex::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+impl RandomnessSource for SeededRandomness {
+    fn try_fill_bytes(&self, dest: &mut [u8]) -> Result<(), IronfishError> {
+        self.rng.lock().unwrap().fill_bytes(dest);
+
+        Ok(())
+    }
+}
+
+lazy_static! {
+    static ref OS_RANDOMNESS: Arc<dyn RandomnessSource> = Arc::new(OsRandomness);
+    static ref RANDOMNESS_SOURCE: RwLock<Option<Arc<dyn RandomnessSource>>> = RwLock::new(None);
+}
+
+thread_local! {
+    static THREAD_RANDOMNESS_SOURCE: RefCell<Option<Arc<dyn RandomnessSource>>> =
+        RefCell::new(None);
+}
+
+/// Draw all randomness from the given source from now on, replacing
+/// [`OsRandomness`].
+pub fn set_randomness_source(source: Arc<dyn RandomnessSource>) {
+    *RANDOMNESS_SOURCE.write().unwrap() = Some(source);
+}
+
+/// Go back to drawing randomness from [`OsRandomness`].
+pub fn clear_randomness_source() {
+    *RANDOMNESS_SOURCE.write().unwrap() = None;
+}
+
+/// Run `f` drawing randomness from the given source, on this thread only.
+/// Other threads keep using the source of the process meanwhile, including
+/// those `f` hands work to, such as the ones
+/// [`crate::transaction::factory::TransactionFactory`] posts on.
+pub fn with_randomness_source<T>(source: Arc<dyn RandomnessSource>, f: impl FnOnce() -> T) -> T {
+    /// Puts back the previous source, even if `f` panics
+    struct Restore(Option<Arc<dyn RandomnessSource>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            THREAD_RANDOMNESS_SOURCE.with(|source| *source.borrow_mut() = previous);
+        }
+    }
+
+    let _restore = Restore(THREAD_RANDOMNESS_SOURCE.with(|current| current.replace(Some(source))));
+
+    f()
+}
+
+/// The source in use on this thread. Cloned out of the lock, so that
+/// setting a source doesn't wait for a slow one.
+fn randomness_source() -> Arc<dyn RandomnessSource> {
+    if let Some(source) = THREAD_RANDOMNESS_SOURCE.with(|source| source.borrow().clone()) {
+        return source;
+    }
+
+    if let Some(source) = RANDOMNESS_SOURCE.read().unwrap().as_ref() {
+        return source.clone();
+    }
+
+    OS_RANDOMNESS.clone()
+}
+
+/// An [`RngCore`] over the source in use, for the generic code of the
+/// proving and signing libraries. Those can't be handed an error, so a
+/// failure of the source fills the bytes with zeros and is remembered, and
+/// [`with_rng`] turns it into an error once they're done.
+pub(crate) struct SourceRng {
+    source: Arc<dyn RandomnessSource>,
+    failed: bool,
+}
+
+impl RngCore for SourceRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        self.fill_bytes(&mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        self.fill_bytes(&mut bytes);
+        u64::from_le_bytes(bytes)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        if self.source.try_fill_bytes(dest).is_err() {
+            dest.fill(0);
+            self.failed = true;
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.source.try_fill_bytes(dest).map_err(|_| {
+            self.failed = true;
+            rand::Error::new(io::Error::new(
+                io::ErrorKind::Other,
+                "the randomness source failed",
+            ))
+        })
+    }
+}
+
+impl CryptoRng for SourceRng {}
+
+/// Run `f` with a generator drawing from the source in use, discarding
+/// what it returns if the source failed along the way.
+pub(crate) fn with_rng<T>(f: impl FnOnce(&mut SourceRng) -> T) -> Result<T, IronfishError> {
+    let mut rng = SourceRng {
+        source: randomness_source(),
+        failed: false,
+    };
+    let result = f(&mut rng);
+
+    if rng.failed {
+        return Err(IronfishError::RandomnessError);
+    }
+
+    Ok(result)
+}
+
+/// [`with_rng`], for constructors that can't return an error. Panics if the
+/// source failed.
+pub(crate) fn expect_rng<T>(f: impl FnOnce(&mut SourceRng) -> T) -> T {
+    with_rng(f).expect("the randomness source failed")
+}
+
+/// A uniformly random scalar of the Jubjub field
+pub(crate) fn random_scalar() -> Result<jubjub::Fr, IronfishError> {
+    let mut bytes = [0; 64];
+    with_rng(|rng| rng.fill_bytes(&mut bytes))?;
+
+    Ok(jubjub::Fr::from_bytes_wide(&bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        random_scalar, with_randomness_source, with_rng, RandomnessSource, SeededRandomness,
+    };
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::{SaplingKey, SigningKey},
+        note::Note,
+        rerandomization::rerandomize_commitment,
+        test_util::make_fake_witness,
+        transaction::{limits::Limits, ProposedTransaction},
+    };
+    use ironfish_zkp::primitives::ValueCommitment;
+    use rand::RngCore;
+    use std::sync::Arc;
+
+    struct Failing;
+
+    impl RandomnessSource for Failing {
+        fn try_fill_bytes(&self, _dest: &mut [u8]) -> Result<(), IronfishError> {
+            Err(IronfishError::RandomnessError)
+        }
+    }
+
+    #[test]
+    fn test_seeded_randomness_is_reproducible() {
+        let draw = || {
+            with_randomness_source(Arc::new(SeededRandomness::new(7)), || {
+                (random_scalar().unwrap(), SaplingKey::generate_key())
+            })
+        };
+
+        let (scalar, key) = draw();
+        let (scalar2, key2) = draw();
+        assert_eq!(scalar, scalar2);
+        assert_eq!(key.spending_key(), key2.spending_key());
+
+        // The source is put back afterwards
+        assert_ne!(random_scalar().unwrap(), scalar);
+    }
+
+    #[test]
+    fn test_failing_source_is_an_error() {
+        let result = with_randomness_source(Arc::new(Failing), || {
+            with_rng(|rng| {
+                let mut bytes = [1; 32];
+                rng.fill_bytes(&mut bytes);
+                bytes
+            })
+        });
+        assert!(matches!(result, Err(IronfishError::RandomnessError)));
+    }
+
+    #[test]
+    fn test_posting_with_failing_source() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(note, &witness).unwrap();
+
+        let result = with_randomness_source(Arc::new(Failing), || transaction.post(None, 1));
+        assert!(matches!(result, Err(IronfishError::RandomnessError)));
+
+        // It can still be posted once the source recovers
+        transaction.post(None, 1).unwrap();
+    }
+
+    #[test]
+    fn test_constructors_with_failing_source() {
+        let key = SaplingKey::generate_key();
+        let commitment = ValueCommitment::new(42, NATIVE_ASSET.asset_generator().point());
+
+        with_randomness_source(Arc::new(Failing), || {
+            assert!(matches!(
+                ProposedTransaction::with_limits(key.clone(), Limits::UNLIMITED),
+                Err(IronfishError::RandomnessError)
+            ));
+            assert!(matches!(
+                SigningKey::generate(),
+                Err(IronfishError::RandomnessError)
+            ));
+            assert!(matches!(
+                rerandomize_commitment(&commitment.commitment()),
+                Err(IronfishError::RandomnessError)
+            ));
+        });
+    }
+}
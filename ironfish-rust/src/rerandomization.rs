@@ -0,0 +1,204 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Re-randomization of value commitments, for protocols built on top of Iron
+//! Fish such as atomic swaps and auctions.
+//!
+//! A value commitment is `value * G_asset + r * R`, where `R` is the value
+//! commitment randomness generator. Adding `delta * R` to it gives a new
+//! commitment to the same value of the same asset that can't be linked to the
+//! original one. The [`EqualityProof`] convinces anyone who has both
+//! commitments of that, without revealing the value, the asset or `delta`: it
+//! is a Schnorr proof of knowledge of `delta` such that the difference of the
+//! commitments is `delta * R`, made non-interactive with the Fiat-Shamir
+//! transform.
+
+use std::io;
+
+use blake2b_simd::Params as Blake2b;
+use group::GroupEncoding;
+use ironfish_zkp::{constants::VALUE_COMMITMENT_RANDOMNESS_GENERATOR, primitives::ValueCommitment};
+use jubjub::SubgroupPoint;
+
+use crate::{
+    errors::IronfishError,
+    personalization::COMMITMENT_EQUALITY_PERSONALIZATION,
+    randomness::random_scalar,
+    serializing::{read_point, read_scalar},
+};
+
+pub const EQUALITY_PROOF_SIZE: usize = 64;
+
+/// Proof that two value commitments commit to the same value of the same
+/// asset.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EqualityProof {
+    /// Commitment to the prover's nonce, `k * R`
+    nonce_commitment: SubgroupPoint,
+
+    /// `k + c * delta`, where `c` is the challenge
+    response: jubjub::Fr,
+}
+
+impl EqualityProof {
+    /// Prove that `rerandomized` is `original + delta * R`.
+    pub fn new(
+        original: &SubgroupPoint,
+        rerandomized: &SubgroupPoint,
+        delta: &jubjub::Fr,
+    ) -> Result<Self, IronfishError> {
+        let nonce = random_scalar()?;
+        let nonce_commitment = VALUE_COMMITMENT_RANDOMNESS_GENERATOR * nonce;
+        let challenge = challenge(original, rerandomized, &nonce_commitment);
+
+        Ok(EqualityProof {
+            nonce_commitment,
+            response: nonce + challenge * delta,
+        })
+    }
+
+    /// Check that both commitments are to the same value of the same asset.
+    pub fn verify(
+        &self,
+        original: &SubgroupPoint,
+        rerandomized: &SubgroupPoint,
+    ) -> Result<(), IronfishError> {
+        let challenge = challenge(original, rerandomized, &self.nonce_commitment);
+        let difference = rerandomized - original;
+
+        if VALUE_COMMITMENT_RANDOMNESS_GENERATOR * self.response
+            != self.nonce_commitment + difference * challenge
+        {
+            return Err(IronfishError::InvalidEqualityProof);
+        }
+
+        Ok(())
+    }
+
+    /// Load an [`EqualityProof`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let nonce_commitment = read_point(&mut reader)?;
+        let response = read_scalar(&mut reader)?;
+
+        Ok(EqualityProof {
+            nonce_commitment,
+            response,
+        })
+    }
+
+    /// Stow the bytes of this [`EqualityProof`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_all(&self.nonce_commitment.to_bytes())?;
+        writer.write_all(&self.response.to_bytes())?;
+
+        Ok(())
+    }
+}
+
+/// The Fiat-Shamir challenge, binding both commitments and the nonce
+/// commitment
+fn challenge(
+    original: &SubgroupPoint,
+    rerandomized: &SubgroupPoint,
+    nonce_commitment: &SubgroupPoint,
+) -> jubjub::Fr {
+    let mut hash_result = [0; 64];
+    hash_result.copy_from_slice(
+        Blake2b::new()
+            .hash_length(64)
+            .personal(COMMITMENT_EQUALITY_PERSONALIZATION)
+            .to_state()
+            .update(&VALUE_COMMITMENT_RANDOMNESS_GENERATOR.to_bytes())
+            .update(&original.to_bytes())
+            .update(&rerandomized.to_bytes())
+            .update(&nonce_commitment.to_bytes())
+            .finalize()
+            .as_bytes(),
+    );
+
+    jubjub::Fr::from_bytes_wide(&hash_result)
+}
+
+/// Re-randomize a value commitment, returning the opening of the new
+/// commitment and the proof that it commits to the same value and asset as
+/// the original one.
+pub fn rerandomize(
+    value_commitment: &ValueCommitment,
+) -> Result<(ValueCommitment, EqualityProof), IronfishError> {
+    let delta = random_scalar()?;
+    let rerandomized = ValueCommitment {
+        value: value_commitment.value,
+        randomness: value_commitment.randomness + delta,
+        asset_generator: value_commitment.asset_generator,
+    };
+
+    let proof = EqualityProof::new(
+        &value_commitment.commitment(),
+        &rerandomized.commitment(),
+        &delta,
+    )?;
+
+    Ok((rerandomized, proof))
+}
+
+/// Re-randomize a commitment whose opening isn't known, returning the new
+/// commitment, the randomness that was added to it and the proof. Whoever
+/// holds the opening of the original commitment can open the new one by
+/// adding the returned randomness to theirs.
+pub fn rerandomize_commitment(
+    commitment: &SubgroupPoint,
+) -> Result<(SubgroupPoint, jubjub::Fr, EqualityProof), IronfishError> {
+    let delta = random_scalar()?;
+    let rerandomized = commitment + VALUE_COMMITMENT_RANDOMNESS_GENERATOR * delta;
+    let proof = EqualityProof::new(commitment, &rerandomized, &delta)?;
+
+    Ok((rerandomized, delta, proof))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{rerandomize, rerandomize_commitment, EqualityProof, EQUALITY_PROOF_SIZE};
+    use crate::{assets::asset_identifier::NATIVE_ASSET, errors::IronfishError};
+    use ironfish_zkp::primitives::ValueCommitment;
+
+    #[test]
+    fn test_rerandomize() {
+        let generator = NATIVE_ASSET.asset_generator().point();
+        let value_commitment = ValueCommitment::new(42, generator);
+        let original = value_commitment.commitment();
+
+        let (rerandomized, proof) = rerandomize(&value_commitment).unwrap();
+        assert_eq!(rerandomized.value, 42);
+        assert_ne!(rerandomized.commitment(), original);
+        proof.verify(&original, &rerandomized.commitment()).unwrap();
+
+        let mut serialized = vec![];
+        proof.write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), EQUALITY_PROOF_SIZE);
+        let read_back = EqualityProof::read(&serialized[..]).unwrap();
+        assert_eq!(read_back, proof);
+
+        // A commitment to another value fails the proof
+        let other = ValueCommitment {
+            value: 43,
+            ..rerandomized
+        };
+        assert!(matches!(
+            proof.verify(&original, &other.commitment()),
+            Err(IronfishError::InvalidEqualityProof)
+        ));
+
+        // Without the opening, the new randomness opens the new commitment
+        let (commitment, delta, proof) = rerandomize_commitment(&original).unwrap();
+        proof.verify(&original, &commitment).unwrap();
+        let opened = ValueCommitment {
+            value: 42,
+            randomness: value_commitment.randomness + delta,
+            asset_generator: generator,
+        };
+        assert_eq!(opened.commitment(), commitment);
+        assert!(proof.verify(&commitment, &original).is_err());
+    }
+}
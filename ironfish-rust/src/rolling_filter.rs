@@ -4,9 +4,11 @@
 
 use std::num::Wrapping;
 
-use rand::{thread_rng, RngCore};
+use rand::RngCore;
 use xxhash_rust::xxh3::xxh3_64_with_seed;
 
+use crate::randomness::expect_rng;
+
 pub struct RollingFilter {
     entries: u32,         // entries currently in this generation
     generation: i32,      // current generation
@@ -33,7 +35,7 @@ impl RollingFilter {
         let data_size = ((filter_bits + 63) / 64) << 1;
         let data = vec![0; data_size as usize];
 
-        let tweak = thread_rng().next_u32();
+        let tweak = expect_rng(|rng| rng.next_u32());
 
         Self {
             entries_per_generation,
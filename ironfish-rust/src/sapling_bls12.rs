@@ -3,9 +3,12 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 pub use bls12_381::Scalar;
 use lazy_static::lazy_static;
-use std::sync::Arc;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
 
-use crate::Sapling;
+use crate::{errors::IronfishError, Sapling};
 
 // Loads the Sapling object once when dereferenced,
 // then reuses the reference on future calls.
@@ -13,6 +16,23 @@ lazy_static! {
     pub static ref SAPLING: Arc<Sapling> = Arc::new(load());
 }
 
+static PRELOADED: Mutex<Option<Sapling>> = Mutex::new(None);
+static LOADED: AtomicBool = AtomicBool::new(false);
+
+/// Use an already loaded Sapling instance, such as one restored from a
+/// snapshot, as [`SAPLING`] instead of loading the bundled parameters. Fails
+/// if [`SAPLING`] has already been used.
+pub fn preload(sapling: Sapling) -> Result<(), IronfishError> {
+    let mut preloaded = PRELOADED.lock().unwrap();
+    if LOADED.load(Ordering::SeqCst) {
+        return Err(IronfishError::SaplingAlreadyLoaded);
+    }
+
+    *preloaded = Some(sapling);
+
+    Ok(())
+}
+
 /// Load a sapling object configured to a BLS12 jubjub curve. This is currently
 /// the only pairing for which a jubjub curve has been defined, and is the
 /// default implementation.
@@ -20,5 +40,8 @@ lazy_static! {
 /// Provided as a convenience method so clients don't have to depend
 /// explicitly on zcash_primitives just to define a JubjubBls12 point.
 fn load() -> Sapling {
-    Sapling::load()
+    let mut preloaded = PRELOADED.lock().unwrap();
+    LOADED.store(true, Ordering::SeqCst);
+
+    preloaded.take().unwrap_or_else(Sapling::load)
 }
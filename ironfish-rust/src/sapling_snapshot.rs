@@ -0,0 +1,277 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Snapshots of a loaded [`Sapling`] proving context.
+//!
+//! The first proof of a process spends seconds decoding the proving keys of
+//! the bundled parameters one point after another. A snapshot stores the
+//! points of every proving key of the spend, output and mint circuits in
+//! their own fixed size sections, so restoring decodes all of them in
+//! parallel, and the restored context is ready to prove right away. Short
+//! lived processes, such as CLI invocations, can write a snapshot once and
+//! restore it on every start, either from a file, a memory-mapped file or a
+//! slice, and install it with [`crate::sapling_bls12::preload`] before the
+//! first proof.
+//!
+//! Snapshots only hold proving keys. The verifying keys, prepared, always
+//! come from the trusted parameters of [`Sapling::load`], the bundled ones
+//! or, with the `params-fetch` feature, the fetched ones checked against
+//! their pinned checksums, and a snapshot only restores if it was written
+//! for their verifying keys, so a snapshot can't change which proofs
+//! verify. The BLAKE3 digest of the body only catches corrupt snapshots:
+//! anyone can recompute it, so it's no reason to trust the points, which are
+//! decoded without validation like the bundled ones.
+
+use crate::{errors::IronfishError, Sapling};
+
+use bellman::groth16;
+use bls12_381::{Bls12, G1Affine, G2Affine};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rayon::prelude::*;
+
+use std::{fs, io, path::Path, sync::Arc};
+
+const SNAPSHOT_MAGIC: &[u8; 8] = b"IFSapSnp";
+const SNAPSHOT_VERSION: u8 = 2;
+
+/// Magic, version, and digest of the body
+const SNAPSHOT_HEADER_SIZE: usize = SNAPSHOT_MAGIC.len() + 1 + blake3::OUT_LEN;
+
+const G1_SIZE: usize = 96;
+const G2_SIZE: usize = 192;
+
+impl Sapling {
+    /// Write a snapshot of this proving context to the given writer. Only
+    /// snapshots of the parameters of [`Sapling::load`] can be restored.
+    pub fn write_snapshot<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        let mut body = vec![];
+        for params in [&self.spend_params, &self.output_params, &self.mint_params] {
+            let params = params.load()?;
+
+            body.extend_from_slice(verifying_key_digest(&params.vk)?.as_bytes());
+            write_g1_section(&mut body, &params.h)?;
+            write_g1_section(&mut body, &params.l)?;
+            write_g1_section(&mut body, &params.a)?;
+            write_g1_section(&mut body, &params.b_g1)?;
+            write_g2_section(&mut body, &params.b_g2)?;
+        }
+
+        writer.write_all(SNAPSHOT_MAGIC)?;
+        writer.write_u8(SNAPSHOT_VERSION)?;
+        writer.write_all(blake3::hash(&body).as_bytes())?;
+        writer.write_all(&body)?;
+
+        Ok(())
+    }
+
+    /// Restore a proving context from a snapshot written by
+    /// [`Sapling::write_snapshot`].
+    pub fn read_snapshot<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+
+        Self::restore_snapshot(&bytes)
+    }
+
+    /// Restore a proving context from a memory-mapped snapshot file. The file
+    /// is only read while restoring.
+    pub fn map_snapshot(path: &Path) -> Result<Self, IronfishError> {
+        let file = fs::File::open(path)?;
+        // SAFETY: the mapping is dropped before this returns, and snapshots
+        // are only ever written whole by `write_snapshot`
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+
+        Self::restore_snapshot(&map[..])
+    }
+
+    /// Restore a proving context from the bytes of a snapshot. Fails with
+    /// [`IronfishError::InvalidParamsChecksum`] if the snapshot is corrupt or
+    /// wasn't written for the parameters of [`Sapling::load`].
+    pub fn restore_snapshot(bytes: &[u8]) -> Result<Self, IronfishError> {
+        if bytes.len() < SNAPSHOT_HEADER_SIZE
+            || !bytes.starts_with(SNAPSHOT_MAGIC)
+            || bytes[SNAPSHOT_MAGIC.len()] != SNAPSHOT_VERSION
+        {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let digest = &bytes[SNAPSHOT_MAGIC.len() + 1..SNAPSHOT_HEADER_SIZE];
+        let body = &bytes[SNAPSHOT_HEADER_SIZE..];
+        if blake3::hash(body).as_bytes() != digest {
+            return Err(IronfishError::InvalidParamsChecksum);
+        }
+
+        // Only the verifying keys of the trusted parameters are parsed here
+        let trusted = Sapling::load();
+
+        let mut reader = body;
+        let spend_params = read_proving_key(&mut reader, &trusted.spend_params.vk)?;
+        let output_params = read_proving_key(&mut reader, &trusted.output_params.vk)?;
+        let mint_params = read_proving_key(&mut reader, &trusted.mint_params.vk)?;
+        if !reader.is_empty() {
+            return Err(IronfishError::InvalidData);
+        }
+
+        Ok(trusted.with_params(spend_params, output_params, mint_params))
+    }
+}
+
+fn verifying_key_digest(vk: &groth16::VerifyingKey<Bls12>) -> Result<blake3::Hash, IronfishError> {
+    let mut vk_bytes = vec![];
+    vk.write(&mut vk_bytes)?;
+
+    Ok(blake3::hash(&vk_bytes))
+}
+
+fn write_g1_section(body: &mut Vec<u8>, points: &[G1Affine]) -> Result<(), IronfishError> {
+    body.write_u64::<LittleEndian>(points.len() as u64)?;
+    for point in points {
+        body.extend_from_slice(&point.to_uncompressed());
+    }
+
+    Ok(())
+}
+
+fn write_g2_section(body: &mut Vec<u8>, points: &[G2Affine]) -> Result<(), IronfishError> {
+    body.write_u64::<LittleEndian>(points.len() as u64)?;
+    for point in points {
+        body.extend_from_slice(&point.to_uncompressed());
+    }
+
+    Ok(())
+}
+
+/// Read the proving key of a circuit written for the given verifying key
+fn read_proving_key(
+    reader: &mut &[u8],
+    vk: &groth16::VerifyingKey<Bls12>,
+) -> Result<groth16::Parameters<Bls12>, IronfishError> {
+    let mut digest = [0; blake3::OUT_LEN];
+    io::Read::read_exact(reader, &mut digest)?;
+    if verifying_key_digest(vk)? != blake3::Hash::from(digest) {
+        return Err(IronfishError::InvalidParamsChecksum);
+    }
+
+    let h = read_section(reader, G1_SIZE)?;
+    let l = read_section(reader, G1_SIZE)?;
+    let a = read_section(reader, G1_SIZE)?;
+    let b_g1 = read_section(reader, G1_SIZE)?;
+    let b_g2 = read_section(reader, G2_SIZE)?;
+
+    // Bellman refuses the point at infinity in h and l, even unchecked
+    Ok(groth16::Parameters {
+        vk: vk.clone(),
+        h: Arc::new(decode_g1(h, false)?),
+        l: Arc::new(decode_g1(l, false)?),
+        a: Arc::new(decode_g1(a, true)?),
+        b_g1: Arc::new(decode_g1(b_g1, true)?),
+        b_g2: Arc::new(decode_g2(b_g2)?),
+    })
+}
+
+fn read_section<'a>(reader: &mut &'a [u8], point_size: usize) -> Result<&'a [u8], IronfishError> {
+    let count = usize::try_from(reader.read_u64::<LittleEndian>()?)?;
+    let length = count
+        .checked_mul(point_size)
+        .ok_or(IronfishError::InvalidData)?;
+    if length > reader.len() {
+        return Err(IronfishError::InvalidData);
+    }
+
+    let (section, rest) = reader.split_at(length);
+    *reader = rest;
+
+    Ok(section)
+}
+
+fn decode_g1(section: &[u8], allow_identity: bool) -> Result<Vec<G1Affine>, IronfishError> {
+    section
+        .par_chunks_exact(G1_SIZE)
+        .map(|bytes| {
+            let point: Option<G1Affine> =
+                G1Affine::from_uncompressed_unchecked(bytes.try_into().unwrap()).into();
+            point
+                .filter(|point| allow_identity || !bool::from(point.is_identity()))
+                .ok_or(IronfishError::InvalidData)
+        })
+        .collect()
+}
+
+fn decode_g2(section: &[u8]) -> Result<Vec<G2Affine>, IronfishError> {
+    section
+        .par_chunks_exact(G2_SIZE)
+        .map(|bytes| {
+            Option::from(G2Affine::from_uncompressed_unchecked(
+                bytes.try_into().unwrap(),
+            ))
+            .ok_or(IronfishError::InvalidData)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{errors::IronfishError, sapling_bls12::SAPLING, Sapling};
+
+    use super::SNAPSHOT_HEADER_SIZE;
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let mut snapshot = vec![];
+        SAPLING.write_snapshot(&mut snapshot).unwrap();
+
+        let restored = Sapling::read_snapshot(&snapshot[..]).unwrap();
+        assert!(restored.spend_params.load().unwrap() == SAPLING.spend_params.load().unwrap());
+        assert!(restored.output_params.load().unwrap() == SAPLING.output_params.load().unwrap());
+        assert!(restored.mint_params.load().unwrap() == SAPLING.mint_params.load().unwrap());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_corruption() {
+        let mut snapshot = vec![];
+        SAPLING.write_snapshot(&mut snapshot).unwrap();
+
+        let mut corrupt = snapshot.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 1;
+        assert!(Sapling::restore_snapshot(&corrupt).is_err());
+
+        let mut wrong_version = snapshot.clone();
+        wrong_version[8] += 1;
+        assert!(Sapling::restore_snapshot(&wrong_version).is_err());
+
+        assert!(Sapling::restore_snapshot(&snapshot[..40]).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_rejects_other_parameters() {
+        // A snapshot of other parameters, with a digest that matches its body
+        let Sapling {
+            spend_params,
+            output_params,
+            mint_params,
+            ..
+        } = Sapling::load();
+        let swapped = Sapling::from_proving_params(output_params, spend_params, mint_params);
+
+        let mut snapshot = vec![];
+        swapped.write_snapshot(&mut snapshot).unwrap();
+        assert!(matches!(
+            Sapling::restore_snapshot(&snapshot),
+            Err(IronfishError::InvalidParamsChecksum)
+        ));
+
+        // Neither can the verifying key digest in the body be rewritten
+        let mut snapshot = vec![];
+        SAPLING.write_snapshot(&mut snapshot).unwrap();
+        snapshot[SNAPSHOT_HEADER_SIZE] ^= 1;
+        let digest = blake3::hash(&snapshot[SNAPSHOT_HEADER_SIZE..]);
+        snapshot[SNAPSHOT_HEADER_SIZE - blake3::OUT_LEN..SNAPSHOT_HEADER_SIZE]
+            .copy_from_slice(digest.as_bytes());
+        assert!(matches!(
+            Sapling::restore_snapshot(&snapshot),
+            Err(IronfishError::InvalidParamsChecksum)
+        ));
+    }
+}
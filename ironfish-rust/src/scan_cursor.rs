@@ -0,0 +1,231 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    errors::IronfishError,
+    keys::AccountExport,
+    tree_position::{NotePosition, TreeSize},
+};
+
+/// Serialization version of [`ScanCursor`], bumped whenever the format changes
+pub const SCAN_CURSOR_VERSION: u8 = 1;
+
+/// How far a rescan of an account got, so it can be persisted and resumed
+/// after a restart instead of starting over from the account's birthday.
+///
+/// The cursor points into the block being scanned: the notes of the block are
+/// trial decrypted in order, and the cursor counts how many were already
+/// looked at and which of those belonged to the account. Those notes only
+/// need to be stored by the wallet once the whole block is scanned, with
+/// [`ScanCursor::finish_block`], so a scan can be interrupted at any note.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScanCursor {
+    /// Sequence of the block being scanned
+    sequence: u32,
+
+    /// Hash of the last block that was completely scanned, if any. A resumed
+    /// scan should check it's still on the main chain, and start over from
+    /// an earlier cursor if it was reorganized away.
+    previous_block_hash: Option<[u8; 32]>,
+
+    /// Position in the note tree of the first note of the block
+    note_tree_size: u64,
+
+    /// Number of notes of the block that were already trial decrypted
+    notes_scanned: u32,
+
+    /// Positions of the notes of the block that decrypted for the account
+    decrypted_positions: Vec<NotePosition>,
+}
+
+impl ScanCursor {
+    /// Cursor at the start of the block with the given sequence, whose first
+    /// note is at position `note_tree_size` in the note tree.
+    pub fn new(sequence: u32, note_tree_size: TreeSize) -> Self {
+        ScanCursor {
+            sequence,
+            previous_block_hash: None,
+            note_tree_size: note_tree_size.as_u64(),
+            notes_scanned: 0,
+            decrypted_positions: vec![],
+        }
+    }
+
+    /// Cursor at the first block that could hold notes for the account. See
+    /// [`AccountExport::scan_start`].
+    pub fn for_account(account: &AccountExport, note_tree_size: TreeSize) -> Self {
+        ScanCursor::new(account.scan_start(), note_tree_size)
+    }
+
+    pub fn sequence(&self) -> u32 {
+        self.sequence
+    }
+
+    pub fn previous_block_hash(&self) -> Option<&[u8; 32]> {
+        self.previous_block_hash.as_ref()
+    }
+
+    /// Position in the note tree of the next note to trial decrypt. Notes of
+    /// the block before it were already scanned when resuming.
+    pub fn next_position(&self) -> NotePosition {
+        NotePosition::new(self.note_tree_size + u64::from(self.notes_scanned))
+    }
+
+    /// Positions of the notes of the current block that were decrypted so far
+    pub fn decrypted_positions(&self) -> &[NotePosition] {
+        &self.decrypted_positions
+    }
+
+    /// Record that the note at [`ScanCursor::next_position`] was trial
+    /// decrypted, and whether it belonged to the account.
+    pub fn note_scanned(&mut self, decrypted: bool) {
+        if decrypted {
+            self.decrypted_positions.push(self.next_position());
+        }
+        self.notes_scanned += 1;
+    }
+
+    /// Move on to the next block once every note of the block with the given
+    /// hash was scanned, returning the positions of the notes that belonged to
+    /// the account.
+    pub fn finish_block(&mut self, block_hash: [u8; 32]) -> Vec<NotePosition> {
+        self.note_tree_size = self.next_position().as_u64();
+        self.sequence += 1;
+        self.previous_block_hash = Some(block_hash);
+        self.notes_scanned = 0;
+
+        std::mem::take(&mut self.decrypted_positions)
+    }
+
+    /// Load a [`ScanCursor`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != SCAN_CURSOR_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let sequence = reader.read_u32::<LittleEndian>()?;
+        let previous_block_hash = match reader.read_u8()? {
+            0 => None,
+            1 => {
+                let mut hash = [0; 32];
+                reader.read_exact(&mut hash)?;
+                Some(hash)
+            }
+            _ => return Err(IronfishError::InvalidData),
+        };
+        let note_tree_size = reader.read_u64::<LittleEndian>()?;
+        let notes_scanned = reader.read_u32::<LittleEndian>()?;
+
+        let cursor = ScanCursor {
+            sequence,
+            previous_block_hash,
+            note_tree_size,
+            notes_scanned,
+            decrypted_positions: vec![],
+        };
+
+        let decrypted_count = reader.read_u32::<LittleEndian>()?;
+        if decrypted_count > notes_scanned {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let mut decrypted_positions = Vec::with_capacity(decrypted_count as usize);
+        for _ in 0..decrypted_count {
+            let position = NotePosition::new(reader.read_u64::<LittleEndian>()?);
+            if position.as_u64() < note_tree_size || position >= cursor.next_position() {
+                return Err(IronfishError::InvalidData);
+            }
+            decrypted_positions.push(position);
+        }
+
+        Ok(ScanCursor {
+            decrypted_positions,
+            ..cursor
+        })
+    }
+
+    /// Stow the bytes of this [`ScanCursor`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(SCAN_CURSOR_VERSION)?;
+        writer.write_u32::<LittleEndian>(self.sequence)?;
+        match &self.previous_block_hash {
+            None => writer.write_u8(0)?,
+            Some(hash) => {
+                writer.write_u8(1)?;
+                writer.write_all(hash)?;
+            }
+        }
+        writer.write_u64::<LittleEndian>(self.note_tree_size)?;
+        writer.write_u32::<LittleEndian>(self.notes_scanned)?;
+
+        writer.write_u32::<LittleEndian>(u32::try_from(self.decrypted_positions.len())?)?;
+        for position in self.decrypted_positions.iter() {
+            writer.write_u64::<LittleEndian>(position.as_u64())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ScanCursor;
+    use crate::{
+        errors::IronfishError,
+        keys::{AccountExport, SaplingKey, GENESIS_BLOCK_SEQUENCE},
+        tree_position::{NotePosition, TreeSize},
+    };
+
+    #[test]
+    fn test_scan_cursor_resume() {
+        let key = SaplingKey::generate_key();
+        let account = AccountExport::new(key.clone(), Some(1000));
+        assert_eq!(
+            ScanCursor::for_account(&account, TreeSize::EMPTY).sequence(),
+            1000
+        );
+
+        let account = AccountExport::new(key, None);
+        let mut cursor = ScanCursor::for_account(&account, TreeSize::EMPTY);
+        assert_eq!(cursor.sequence(), GENESIS_BLOCK_SEQUENCE);
+
+        cursor.note_scanned(false);
+        cursor.note_scanned(true);
+        assert_eq!(cursor.finish_block([1; 32]), vec![NotePosition::new(1)]);
+        assert_eq!(cursor.next_position(), NotePosition::new(2));
+
+        cursor.note_scanned(true);
+        cursor.note_scanned(false);
+        cursor.note_scanned(true);
+
+        // Interrupted in the middle of the block
+        let mut serialized = vec![];
+        cursor.write(&mut serialized).unwrap();
+        let mut resumed = ScanCursor::read(&serialized[..]).unwrap();
+        assert_eq!(resumed, cursor);
+        assert_eq!(resumed.sequence(), GENESIS_BLOCK_SEQUENCE + 1);
+        assert_eq!(resumed.previous_block_hash(), Some(&[1; 32]));
+        assert_eq!(resumed.next_position(), NotePosition::new(5));
+
+        resumed.note_scanned(true);
+        assert_eq!(
+            resumed.finish_block([2; 32]),
+            [2, 4, 5].map(NotePosition::new)
+        );
+        assert!(resumed.decrypted_positions().is_empty());
+
+        // Decrypted positions have to be among the scanned notes
+        let last = serialized.len() - 8;
+        serialized[last..].copy_from_slice(&5u64.to_le_bytes());
+        assert!(matches!(
+            ScanCursor::read(&serialized[..]),
+            Err(IronfishError::InvalidData)
+        ));
+    }
+}
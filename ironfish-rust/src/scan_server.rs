@@ -0,0 +1,495 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Trial decryption shared fairly between the clients of a light wallet
+//! server.
+//!
+//! Each client registers its incoming view keys and submits the notes it
+//! wants scanned. The server takes turns between the clients with notes
+//! waiting, trial decrypting at most [`ScanQuota::notes_per_turn`] notes of
+//! each per turn, so a client catching up on years of notes doesn't hold up
+//! one that only wants the latest block. Independently of the other
+//! clients, each one may scan at most [`ScanQuota::notes_per_second`] notes
+//! on average, in bursts of up to [`ScanQuota::burst`].
+//!
+//! Nothing runs in the background: the server scans when
+//! [`ScanServer::run_turn`] or [`ScanServer::run_until`] is called, and
+//! reports the notes that decrypted to a callback. An async server calls
+//! [`ScanServer::run_until`] with a time budget and yields to its runtime
+//! between calls, so scanning never blocks it for longer than one turn.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    errors::IronfishError, keys::IncomingViewKeySet, merkle_note::MerkleNote, note::Note,
+    tree_position::NotePosition,
+};
+
+const NANOS_PER_SECOND: u128 = 1_000_000_000;
+
+/// How much trial decryption each client of a [`ScanServer`] gets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScanQuota {
+    /// Notes scanned for a client before moving on to the next one
+    pub notes_per_turn: usize,
+
+    /// Notes a client may have scanned per second on average, or zero for
+    /// no more than [`ScanQuota::burst`]
+    pub notes_per_second: u64,
+
+    /// Notes a client that waited long enough may have scanned at once
+    pub burst: u64,
+}
+
+impl Default for ScanQuota {
+    fn default() -> Self {
+        ScanQuota {
+            notes_per_turn: 64,
+            notes_per_second: 2_000,
+            burst: 20_000,
+        }
+    }
+}
+
+/// A client registered with a [`ScanServer`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientId(u64);
+
+/// A note that decrypted with one of the keys of a client
+pub struct ScanMatch {
+    /// Where the note was submitted as being in the note tree
+    pub position: NotePosition,
+
+    /// Index of the key the note was sent to, in the client's
+    /// [`IncomingViewKeySet`]
+    pub key_index: usize,
+
+    pub note: Note,
+}
+
+/// Why [`ScanServer::run_until`] returned
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScanStatus {
+    /// Every submitted note was scanned
+    Idle,
+
+    /// Notes are waiting, but only for clients that used up their quota.
+    /// More can be scanned once [`ScanServer::next_allowance`] has passed.
+    Throttled,
+
+    /// The caller asked to yield, with notes still waiting
+    Yielded,
+}
+
+struct Client {
+    keys: IncomingViewKeySet,
+    pending: VecDeque<(NotePosition, MerkleNote)>,
+
+    /// Notes the client may have scanned right now
+    allowance: u64,
+
+    /// When the allowance was last topped up
+    refilled_at: Instant,
+}
+
+impl Client {
+    /// Top up the allowance with what the client earned since it was last
+    /// topped up
+    fn refill(&mut self, quota: &ScanQuota, now: Instant) {
+        if self.allowance >= quota.burst {
+            self.refilled_at = now;
+            return;
+        }
+        if quota.notes_per_second == 0 {
+            return;
+        }
+
+        let elapsed = now.saturating_duration_since(self.refilled_at);
+        let earned = elapsed.as_nanos() * u128::from(quota.notes_per_second) / NANOS_PER_SECOND;
+        let earned = u64::try_from(earned).unwrap_or(u64::MAX);
+        if earned == 0 {
+            return;
+        }
+
+        self.allowance = self.allowance.saturating_add(earned).min(quota.burst);
+        self.refilled_at = match self.allowance >= quota.burst {
+            true => now,
+            // Keep the fraction of a note earned since
+            false => self.refilled_at + time_to_earn(quota, earned),
+        };
+    }
+
+    /// How long until the client may scan another note
+    fn next_allowance(&self, quota: &ScanQuota, now: Instant) -> Option<Duration> {
+        if self.allowance > 0 {
+            return Some(Duration::ZERO);
+        }
+        if quota.notes_per_second == 0 {
+            return None;
+        }
+
+        let earned_at = self.refilled_at + time_to_earn(quota, 1);
+        Some(earned_at.saturating_duration_since(now))
+    }
+}
+
+/// How long a client takes to earn the given number of notes, rounded up to
+/// the nanosecond so that a quota of over a thousand notes per second doesn't
+/// round down to nothing
+fn time_to_earn(quota: &ScanQuota, notes: u64) -> Duration {
+    let rate = u128::from(quota.notes_per_second);
+    let nanos = (u128::from(notes) * NANOS_PER_SECOND + rate - 1) / rate;
+
+    Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+}
+
+/// Trial decrypts the notes of many clients in turns, within a quota per
+/// client. See the [module documentation](self).
+pub struct ScanServer {
+    quota: ScanQuota,
+    clients: HashMap<ClientId, Client>,
+
+    /// Clients with notes waiting, in the order their turns come
+    turns: VecDeque<ClientId>,
+
+    next_id: u64,
+}
+
+impl ScanServer {
+    pub fn new(quota: ScanQuota) -> Self {
+        ScanServer {
+            quota,
+            clients: HashMap::new(),
+            turns: VecDeque::new(),
+            next_id: 0,
+        }
+    }
+
+    pub fn quota(&self) -> &ScanQuota {
+        &self.quota
+    }
+
+    /// Register a client scanning with the given keys. It starts with a full
+    /// [`ScanQuota::burst`] of allowance.
+    pub fn add_client(&mut self, keys: IncomingViewKeySet) -> ClientId {
+        let id = ClientId(self.next_id);
+        self.next_id += 1;
+
+        self.clients.insert(
+            id,
+            Client {
+                keys,
+                pending: VecDeque::new(),
+                allowance: self.quota.burst,
+                refilled_at: Instant::now(),
+            },
+        );
+
+        id
+    }
+
+    /// Forget the client and the notes it had waiting, returning whether it
+    /// was registered
+    pub fn remove_client(&mut self, id: ClientId) -> bool {
+        self.turns.retain(|&turn| turn != id);
+        self.clients.remove(&id).is_some()
+    }
+
+    /// Queue notes to be trial decrypted for the client, each with its
+    /// position in the note tree. Fails with
+    /// [`IronfishError::UnknownScanClient`] if the client isn't registered.
+    pub fn submit<I: IntoIterator<Item = (NotePosition, MerkleNote)>>(
+        &mut self,
+        id: ClientId,
+        notes: I,
+    ) -> Result<(), IronfishError> {
+        let client = self
+            .clients
+            .get_mut(&id)
+            .ok_or(IronfishError::UnknownScanClient)?;
+
+        let was_waiting = !client.pending.is_empty();
+        client.pending.extend(notes);
+        if !was_waiting && !client.pending.is_empty() {
+            self.turns.push_back(id);
+        }
+
+        Ok(())
+    }
+
+    /// Number of notes waiting to be scanned for the client, if it's
+    /// registered
+    pub fn pending(&self, id: ClientId) -> Option<usize> {
+        self.clients.get(&id).map(|client| client.pending.len())
+    }
+
+    /// How long until a client with notes waiting may scan again: zero if
+    /// one can right away, `None` if nothing is waiting or the clients that
+    /// have notes waiting will never earn more allowance.
+    pub fn next_allowance(&self) -> Option<Duration> {
+        let now = Instant::now();
+        self.turns
+            .iter()
+            .filter_map(|id| self.clients[id].next_allowance(&self.quota, now))
+            .min()
+    }
+
+    /// Give every client with notes waiting one turn, calling `on_match` for
+    /// each note that decrypted. Returns the number of notes scanned. Notes
+    /// that don't decrypt with any of the client's keys, whatever the
+    /// reason, are dropped.
+    pub fn run_turn(&mut self, mut on_match: impl FnMut(ClientId, ScanMatch)) -> usize {
+        let now = Instant::now();
+        let mut scanned = 0;
+
+        for _ in 0..self.turns.len() {
+            let id = match self.turns.pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let client = self.clients.get_mut(&id).unwrap();
+            client.refill(&self.quota, now);
+
+            let allowance = usize::try_from(client.allowance).unwrap_or(usize::MAX);
+            let count = self
+                .quota
+                .notes_per_turn
+                .min(allowance)
+                .min(client.pending.len());
+            for (position, merkle_note) in client.pending.drain(..count) {
+                scanned += 1;
+                client.allowance -= 1;
+
+                if let Ok((key_index, note)) = client.keys.decrypt_note(&merkle_note) {
+                    on_match(
+                        id,
+                        ScanMatch {
+                            position,
+                            key_index,
+                            note,
+                        },
+                    );
+                }
+            }
+
+            if !client.pending.is_empty() {
+                self.turns.push_back(id);
+            }
+        }
+
+        scanned
+    }
+
+    /// Run turns until every note is scanned, the clients with notes waiting
+    /// are out of allowance, or `should_yield` returns true. It's asked
+    /// after every turn, so a turn of every client is the longest this runs
+    /// past it.
+    pub fn run_until(
+        &mut self,
+        mut should_yield: impl FnMut() -> bool,
+        mut on_match: impl FnMut(ClientId, ScanMatch),
+    ) -> ScanStatus {
+        loop {
+            if self.turns.is_empty() {
+                return ScanStatus::Idle;
+            }
+            if self.run_turn(&mut on_match) == 0 {
+                return ScanStatus::Throttled;
+            }
+            if self.turns.is_empty() {
+                return ScanStatus::Idle;
+            }
+            if should_yield() {
+                return ScanStatus::Yielded;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ScanQuota, ScanServer, ScanStatus};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::{EphemeralKeyPair, IncomingViewKeySet, SaplingKey},
+        merkle_note::MerkleNote,
+        note::Note,
+        tree_position::NotePosition,
+    };
+    use ironfish_zkp::primitives::ValueCommitment;
+    use std::time::{Duration, Instant};
+
+    fn make_notes(sender: &SaplingKey, owner: &SaplingKey, count: u64) -> Vec<MerkleNote> {
+        (0..count)
+            .map(|value| {
+                let note = Note::new(
+                    owner.public_address(),
+                    value,
+                    "",
+                    NATIVE_ASSET,
+                    sender.public_address(),
+                );
+                let value_commitment =
+                    ValueCommitment::new(note.value, note.asset_generator().point());
+                MerkleNote::new(sender, &note, &value_commitment, &EphemeralKeyPair::new())
+            })
+            .collect()
+    }
+
+    fn key_set(key: &SaplingKey) -> IncomingViewKeySet {
+        [key.incoming_view_key().clone()].into_iter().collect()
+    }
+
+    fn positioned(notes: Vec<MerkleNote>) -> impl Iterator<Item = (NotePosition, MerkleNote)> {
+        notes
+            .into_iter()
+            .enumerate()
+            .map(|(i, note)| (NotePosition::new(i as u64), note))
+    }
+
+    #[test]
+    fn test_clients_take_turns() {
+        let sender = SaplingKey::generate_key();
+        let busy_key = SaplingKey::generate_key();
+        let light_key = SaplingKey::generate_key();
+
+        let mut server = ScanServer::new(ScanQuota {
+            notes_per_turn: 2,
+            ..ScanQuota::default()
+        });
+        let busy = server.add_client(key_set(&busy_key));
+        let light = server.add_client(key_set(&light_key));
+
+        server
+            .submit(busy, positioned(make_notes(&sender, &busy_key, 6)))
+            .unwrap();
+        server
+            .submit(light, positioned(make_notes(&sender, &light_key, 2)))
+            .unwrap();
+
+        // The light client is done after the first turn, despite the busy
+        // one submitting first
+        let mut matches = vec![];
+        assert_eq!(
+            server.run_turn(|id, found| matches.push((id, found.position))),
+            4
+        );
+        assert_eq!(server.pending(light), Some(0));
+        assert_eq!(server.pending(busy), Some(4));
+        assert_eq!(
+            matches,
+            [
+                (busy, NotePosition::new(0)),
+                (busy, NotePosition::new(1)),
+                (light, NotePosition::new(0)),
+                (light, NotePosition::new(1)),
+            ]
+        );
+
+        let mut turns = 0;
+        let status = server.run_until(
+            || {
+                turns += 1;
+                true
+            },
+            |_, _| {},
+        );
+        assert_eq!(status, ScanStatus::Yielded);
+        assert_eq!(turns, 1);
+        assert_eq!(server.run_until(|| false, |_, _| {}), ScanStatus::Idle);
+        assert_eq!(server.pending(busy), Some(0));
+
+        // Notes for someone else don't match
+        server
+            .submit(light, positioned(make_notes(&sender, &busy_key, 1)))
+            .unwrap();
+        let mut found = 0;
+        server.run_turn(|_, _| found += 1);
+        assert_eq!(found, 0);
+
+        assert!(server.remove_client(light));
+        assert!(matches!(
+            server.submit(light, vec![]),
+            Err(IronfishError::UnknownScanClient)
+        ));
+    }
+
+    #[test]
+    fn test_client_quota() {
+        let sender = SaplingKey::generate_key();
+        let greedy_key = SaplingKey::generate_key();
+        let other_key = SaplingKey::generate_key();
+
+        let mut server = ScanServer::new(ScanQuota {
+            notes_per_turn: 10,
+            notes_per_second: 0,
+            burst: 3,
+        });
+        let greedy = server.add_client(key_set(&greedy_key));
+        let other = server.add_client(key_set(&other_key));
+
+        server
+            .submit(greedy, positioned(make_notes(&sender, &greedy_key, 5)))
+            .unwrap();
+        assert_eq!(server.run_until(|| false, |_, _| {}), ScanStatus::Throttled);
+        assert_eq!(server.pending(greedy), Some(2));
+        assert_eq!(server.next_allowance(), None);
+
+        // Another client isn't held up by the throttled one
+        server
+            .submit(other, positioned(make_notes(&sender, &other_key, 1)))
+            .unwrap();
+        let mut matches = vec![];
+        server.run_until(|| false, |id, _| matches.push(id));
+        assert_eq!(matches, [other]);
+
+        // Allowance is earned back over time, up to the burst
+        server.quota.notes_per_second = 1;
+        let client = server.clients.get_mut(&greedy).unwrap();
+        client.refilled_at = Instant::now() - Duration::from_secs(1);
+        assert_eq!(server.next_allowance(), Some(Duration::ZERO));
+        assert_eq!(server.run_turn(|_, _| {}), 1);
+        assert_eq!(server.pending(greedy), Some(1));
+        assert!(server.next_allowance().unwrap() > Duration::ZERO);
+
+        let client = server.clients.get_mut(&greedy).unwrap();
+        client.refilled_at = Instant::now() - Duration::from_secs(60);
+        assert_eq!(server.run_until(|| false, |_, _| {}), ScanStatus::Idle);
+        assert_eq!(server.clients[&greedy].allowance, 2);
+    }
+
+    #[test]
+    fn test_client_quota_above_a_thousand_per_second() {
+        let key = SaplingKey::generate_key();
+        let mut server = ScanServer::new(ScanQuota::default());
+        let id = server.add_client(key_set(&key));
+        server
+            .submit(id, positioned(make_notes(&key, &key, 1)))
+            .unwrap();
+
+        // At the default 2000 notes per second, a note is earned every half
+        // a millisecond
+        let quota = server.quota;
+        let client = server.clients.get_mut(&id).unwrap();
+        let now = Instant::now();
+        client.allowance = 0;
+        client.refilled_at = now;
+        assert_eq!(
+            client.next_allowance(&quota, now),
+            Some(Duration::from_micros(500))
+        );
+        client.refill(&quota, now + Duration::from_micros(499));
+        assert_eq!(client.allowance, 0);
+        client.refill(&quota, now + Duration::from_micros(750));
+        assert_eq!(client.allowance, 1);
+        assert_eq!(
+            client.next_allowance(&quota, now + Duration::from_micros(750)),
+            Some(Duration::ZERO)
+        );
+    }
+}
@@ -0,0 +1,170 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Screening of recipients before a transaction is proven.
+//!
+//! Integrators that have to enforce a policy on who they pay, such as
+//! sanctions screening, can install a [`ScreeningHook`] with
+//! [`set_screening_hook`]. Every output of every transaction is then passed
+//! to the hook before any proof is built, including change and miner's fee
+//! outputs, and a rejected output fails the transaction with
+//! [`IronfishError::ScreeningRejected`]. Nothing is screened until a hook is
+//! installed.
+
+use lazy_static::lazy_static;
+use std::{
+    fmt,
+    sync::{Arc, RwLock},
+};
+
+use crate::{assets::asset_identifier::AssetIdentifier, errors::IronfishError, PublicAddress};
+
+/// Decides whether a transaction may pay a recipient.
+pub trait ScreeningHook: Send + Sync {
+    /// Check that `value` of the asset may be sent to the recipient, or
+    /// return the reason it may not.
+    fn screen(
+        &self,
+        recipient: &PublicAddress,
+        asset_id: &AssetIdentifier,
+        value: u64,
+    ) -> Result<(), String>;
+}
+
+/// An output that a [`ScreeningHook`] rejected
+#[derive(Clone)]
+pub struct ScreeningRejection {
+    pub recipient: PublicAddress,
+    pub asset_id: AssetIdentifier,
+    pub value: u64,
+
+    /// The reason given by the hook
+    pub reason: String,
+}
+
+impl fmt::Debug for ScreeningRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScreeningRejection")
+            .field("recipient", &self.recipient.hex_public_address())
+            .field("asset_id", &self.asset_id)
+            .field("value", &self.value)
+            .field("reason", &self.reason)
+            .finish()
+    }
+}
+
+lazy_static! {
+    static ref HOOK: RwLock<Option<Arc<dyn ScreeningHook>>> = RwLock::new(None);
+}
+
+/// Screen the outputs of every transaction with the given hook from now on,
+/// replacing any previous one.
+pub fn set_screening_hook(hook: Arc<dyn ScreeningHook>) {
+    *HOOK.write().unwrap() = Some(hook);
+}
+
+/// Stop screening outputs.
+pub fn clear_screening_hook() {
+    *HOOK.write().unwrap() = None;
+}
+
+/// Pass an output to the installed hook, if there is one.
+pub(crate) fn screen_output(
+    recipient: &PublicAddress,
+    asset_id: &AssetIdentifier,
+    value: u64,
+) -> Result<(), IronfishError> {
+    let hook = match HOOK.read().unwrap().as_ref() {
+        Some(hook) => hook.clone(),
+        None => return Ok(()),
+    };
+
+    hook.screen(recipient, asset_id, value).map_err(|reason| {
+        IronfishError::ScreeningRejected(ScreeningRejection {
+            recipient: *recipient,
+            asset_id: *asset_id,
+            value,
+            reason,
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clear_screening_hook, set_screening_hook, ScreeningHook};
+    use crate::{
+        assets::asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::Note,
+        test_util::make_fake_witness,
+        transaction::ProposedTransaction,
+        PublicAddress,
+    };
+    use std::sync::Arc;
+
+    /// Rejects a single address, so other tests running while it's installed
+    /// aren't affected
+    struct Blocklist(PublicAddress);
+
+    impl ScreeningHook for Blocklist {
+        fn screen(
+            &self,
+            recipient: &PublicAddress,
+            _asset_id: &AssetIdentifier,
+            _value: u64,
+        ) -> Result<(), String> {
+            match recipient.public_address() == self.0.public_address() {
+                true => Err("blocked".to_string()),
+                false => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_screening_hook() {
+        let key = SaplingKey::generate_key();
+        let blocked_key = SaplingKey::generate_key();
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+        let out_note = Note::new(
+            blocked_key.public_address(),
+            40,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+
+        set_screening_hook(Arc::new(Blocklist(blocked_key.public_address())));
+
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(in_note.clone(), &witness).unwrap();
+        proposed.add_output(out_note.clone()).unwrap();
+        match proposed.post(None, 1) {
+            Err(IronfishError::ScreeningRejected(rejection)) => {
+                assert_eq!(rejection.reason, "blocked");
+                assert_eq!(rejection.value, 40);
+                assert_eq!(rejection.asset_id, NATIVE_ASSET);
+            }
+            _ => panic!("the output should have been rejected"),
+        }
+
+        // Change back to the spender is screened too, and allowed
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(in_note.clone(), &witness).unwrap();
+        proposed.post(None, 1).unwrap();
+
+        clear_screening_hook();
+        let mut proposed = ProposedTransaction::new(key);
+        proposed.add_spend(in_note, &witness).unwrap();
+        proposed.add_output(out_note).unwrap();
+        proposed.post(None, 1).unwrap();
+    }
+}
@@ -5,55 +5,233 @@
 use crate::errors::IronfishError;
 use chacha20poly1305::aead::{AeadInPlace, NewAead};
 use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use subtle::{Choice, ConstantTimeEq};
 
+/// Size of the MAC tag appended by [`AeadVersion::ChaCha20Poly1305`], which
+/// is the size used in the layout of encrypted notes and keys.
 pub const MAC_SIZE: usize = 16;
 
+/// The authenticated encryption scheme used for notes and note keys.
+///
+/// Encrypted notes in merkle notes don't carry their version, it's implied
+/// by the version of the transaction, see
+/// [`AeadVersion::for_transaction_version`]. Data encrypted with [`seal`]
+/// starts with the version byte instead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AeadVersion {
+    /// ChaCha20Poly1305 keyed with the shared secret. The nonce and
+    /// associated data are zeroed, which is safe because every key is only
+    /// ever used to encrypt one message.
+    ChaCha20Poly1305 = 1,
+}
+
+impl AeadVersion {
+    /// The scheme that notes of a transaction with the given version are
+    /// encrypted with. Every transaction version so far uses
+    /// [`AeadVersion::ChaCha20Poly1305`], like it uses the note layout of
+    /// [`crate::note_kem::NoteCiphertextVersion::V1`].
+    pub fn for_transaction_version(_version: u8) -> Self {
+        AeadVersion::ChaCha20Poly1305
+    }
+
+    /// Size of the MAC tag appended to the ciphertext
+    pub fn mac_size(&self) -> usize {
+        match self {
+            AeadVersion::ChaCha20Poly1305 => MAC_SIZE,
+        }
+    }
+
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl TryFrom<u8> for AeadVersion {
+    type Error = IronfishError;
+
+    fn try_from(byte: u8) -> Result<Self, Self::Error> {
+        match byte {
+            1 => Ok(AeadVersion::ChaCha20Poly1305),
+            _ => Err(IronfishError::InvalidData),
+        }
+    }
+}
+
 /// Encrypt the plaintext using the given key, and append the MAC tag to the
 /// end of the output array to be decrypted and checked in one step below.
 ///
-/// This is just a facade around the ChaCha20Poly1305 struct. The nonce and
-/// associated data are zeroed.
+/// This uses [`AeadVersion::ChaCha20Poly1305`], which all current note and
+/// key layouts are built around.
 pub(crate) fn encrypt<const SIZE: usize>(
     key: &[u8; 32],
     plaintext: &[u8],
 ) -> Result<[u8; SIZE], IronfishError> {
+    encrypt_with_version(AeadVersion::ChaCha20Poly1305, key, plaintext)
+}
+
+/// Decrypt the encrypted text using the given key and ciphertext, also checking
+/// that the mac tag is correct.
+///
+/// This uses [`AeadVersion::ChaCha20Poly1305`], see [`encrypt`].
+pub(crate) fn decrypt<const SIZE: usize>(
+    key: &[u8; 32],
+    ciphertext: &[u8],
+) -> Result<[u8; SIZE], IronfishError> {
+    decrypt_with_version(AeadVersion::ChaCha20Poly1305, key, ciphertext)
+}
+
+/// Decrypt like [`decrypt`], taking the same time whether or not the MAC tag
+/// matches. [`decrypt`] only applies the keystream once the tag checks out;
+/// this always applies it, so trial decryption of notes meant for someone
+/// else doesn't finish early. Whether the tag matched is returned as a
+/// [`Choice`] instead of an error, and the plaintext is garbage when it
+/// didn't.
+pub(crate) fn decrypt_constant_time<const SIZE: usize>(
+    version: AeadVersion,
+    key: &[u8; 32],
+    ciphertext: &[u8],
+) -> Result<([u8; SIZE], Choice), IronfishError> {
+    if ciphertext.len() != SIZE + version.mac_size() {
+        return Err(IronfishError::InvalidData);
+    }
+
+    let cipher = match version {
+        AeadVersion::ChaCha20Poly1305 => ChaCha20Poly1305::new(Key::from_slice(key)),
+    };
+
+    // ChaCha20 is a stream cipher, so encrypting the ciphertext applies the
+    // same keystream that decrypting it would. The tag that comes out is
+    // over the plaintext and of no use.
+    let mut plaintext = [0u8; SIZE];
+    plaintext.copy_from_slice(&ciphertext[..SIZE]);
+    cipher
+        .encrypt_in_place_detached(&Nonce::default(), &[], &mut plaintext)
+        .map_err(|_| IronfishError::InvalidDecryptionKey)?;
+
+    // Encrypting the plaintext gives back the ciphertext along with the tag
+    // it should have been sent with
+    let mut reencrypted = plaintext;
+    let expected_tag = cipher
+        .encrypt_in_place_detached(&Nonce::default(), &[], &mut reencrypted)
+        .map_err(|_| IronfishError::InvalidDecryptionKey)?;
+
+    Ok((
+        plaintext,
+        expected_tag.as_slice().ct_eq(&ciphertext[SIZE..]),
+    ))
+}
+
+/// Encrypt the plaintext with the given scheme, appending the MAC tag. `SIZE`
+/// must be the plaintext length plus [`AeadVersion::mac_size`].
+pub fn encrypt_with_version<const SIZE: usize>(
+    version: AeadVersion,
+    key: &[u8; 32],
+    plaintext: &[u8],
+) -> Result<[u8; SIZE], IronfishError> {
+    if plaintext.len() + version.mac_size() != SIZE {
+        return Err(IronfishError::InvalidData);
+    }
+
     let mut encrypted_output = [0u8; SIZE];
     encrypted_output[..plaintext.len()].copy_from_slice(plaintext);
 
-    let encryptor = ChaCha20Poly1305::new(Key::from_slice(key));
+    match version {
+        AeadVersion::ChaCha20Poly1305 => {
+            let encryptor = ChaCha20Poly1305::new(Key::from_slice(key));
 
-    let tag = encryptor
-        .encrypt_in_place_detached(
-            &Nonce::default(),
-            &[],
-            &mut encrypted_output[..plaintext.len()],
-        )
-        .map_err(|_| IronfishError::InvalidSigningKey)?;
-    encrypted_output[plaintext.len()..].copy_from_slice(&tag);
+            let tag = encryptor
+                .encrypt_in_place_detached(
+                    &Nonce::default(),
+                    &[],
+                    &mut encrypted_output[..plaintext.len()],
+                )
+                .map_err(|_| IronfishError::InvalidSigningKey)?;
+            encrypted_output[plaintext.len()..].copy_from_slice(&tag);
+        }
+    }
 
     Ok(encrypted_output)
 }
 
-/// Decrypt the encrypted text using the given key and ciphertext, also checking
-/// that the mac tag is correct.
-
-pub(crate) fn decrypt<const SIZE: usize>(
+/// Decrypt ciphertext produced by [`encrypt_with_version`] with the same
+/// scheme, checking the MAC tag.
+pub fn decrypt_with_version<const SIZE: usize>(
+    version: AeadVersion,
     key: &[u8; 32],
     ciphertext: &[u8],
 ) -> Result<[u8; SIZE], IronfishError> {
-    let decryptor = ChaCha20Poly1305::new(Key::from_slice(key));
+    if ciphertext.len() != SIZE + version.mac_size() {
+        return Err(IronfishError::InvalidData);
+    }
 
     let mut plaintext = [0u8; SIZE];
     plaintext.copy_from_slice(&ciphertext[..SIZE]);
 
-    decryptor
-        .decrypt_in_place_detached(
-            &Nonce::default(),
-            &[],
-            &mut plaintext,
-            ciphertext[SIZE..].into(),
-        )
-        .map_err(|_| IronfishError::InvalidDecryptionKey)?;
+    match version {
+        AeadVersion::ChaCha20Poly1305 => {
+            let decryptor = ChaCha20Poly1305::new(Key::from_slice(key));
+
+            decryptor
+                .decrypt_in_place_detached(
+                    &Nonce::default(),
+                    &[],
+                    &mut plaintext,
+                    ciphertext[SIZE..].into(),
+                )
+                .map_err(|_| IronfishError::InvalidDecryptionKey)?;
+        }
+    }
+
+    Ok(plaintext)
+}
+
+/// Encrypt the plaintext with the given scheme, prefixed with the version
+/// byte so that [`open`] knows how to decrypt it.
+pub fn seal(
+    version: AeadVersion,
+    key: &[u8; 32],
+    plaintext: &[u8],
+) -> Result<Vec<u8>, IronfishError> {
+    let mut sealed = Vec::with_capacity(1 + plaintext.len() + version.mac_size());
+    sealed.push(version.as_u8());
+    sealed.extend_from_slice(plaintext);
+
+    match version {
+        AeadVersion::ChaCha20Poly1305 => {
+            let encryptor = ChaCha20Poly1305::new(Key::from_slice(key));
+
+            let tag = encryptor
+                .encrypt_in_place_detached(&Nonce::default(), &[], &mut sealed[1..])
+                .map_err(|_| IronfishError::InvalidSigningKey)?;
+            sealed.extend_from_slice(&tag);
+        }
+    }
+
+    Ok(sealed)
+}
+
+/// Decrypt data produced by [`seal`], using the scheme named by its version
+/// byte.
+pub fn open(key: &[u8; 32], sealed: &[u8]) -> Result<Vec<u8>, IronfishError> {
+    let (version, ciphertext) = sealed.split_first().ok_or(IronfishError::InvalidData)?;
+    let version = AeadVersion::try_from(*version)?;
+
+    let plaintext_size = ciphertext
+        .len()
+        .checked_sub(version.mac_size())
+        .ok_or(IronfishError::InvalidData)?;
+    let (ciphertext, tag) = ciphertext.split_at(plaintext_size);
+    let mut plaintext = ciphertext.to_vec();
+
+    match version {
+        AeadVersion::ChaCha20Poly1305 => {
+            let decryptor = ChaCha20Poly1305::new(Key::from_slice(key));
+
+            decryptor
+                .decrypt_in_place_detached(&Nonce::default(), &[], &mut plaintext, tag.into())
+                .map_err(|_| IronfishError::InvalidDecryptionKey)?;
+        }
+    }
 
     Ok(plaintext)
 }
@@ -62,9 +240,16 @@ pub(crate) fn decrypt<const SIZE: usize>(
 mod test {
     use rand::Rng;
 
-    use crate::{note::ENCRYPTED_NOTE_SIZE, serializing::aead};
+    use crate::{
+        note::ENCRYPTED_NOTE_SIZE,
+        note_kem::NoteCiphertextVersion,
+        serializing::aead,
+        transaction::{
+            EXTRA_DATA_TRANSACTION_VERSION, TRANSACTION_VERSION, TREE_ID_TRANSACTION_VERSION,
+        },
+    };
 
-    use super::{decrypt, encrypt};
+    use super::{decrypt, decrypt_constant_time, encrypt, open, seal, AeadVersion};
 
     #[test]
     fn test_aead_facade() {
@@ -79,5 +264,72 @@ mod test {
         let decrypted_plaintext: [u8; ENCRYPTED_NOTE_SIZE] =
             decrypt(key, &encrypted_text[..]).expect("Should successfully decrypt plaintext");
         assert_eq!(decrypted_plaintext, plaintext);
+
+        let (decrypted_plaintext, authenticated) = decrypt_constant_time::<ENCRYPTED_NOTE_SIZE>(
+            AeadVersion::ChaCha20Poly1305,
+            key,
+            &encrypted_text[..],
+        )
+        .unwrap();
+        assert!(bool::from(authenticated));
+        assert_eq!(decrypted_plaintext, plaintext);
+
+        let other_key = b"another very very secret key....";
+        let (_, authenticated) = decrypt_constant_time::<ENCRYPTED_NOTE_SIZE>(
+            AeadVersion::ChaCha20Poly1305,
+            other_key,
+            &encrypted_text[..],
+        )
+        .unwrap();
+        assert!(!bool::from(authenticated));
+
+        let mut tampered = encrypted_text;
+        tampered[SIZE - 1] ^= 1;
+        let (_, authenticated) = decrypt_constant_time::<ENCRYPTED_NOTE_SIZE>(
+            AeadVersion::ChaCha20Poly1305,
+            key,
+            &tampered[..],
+        )
+        .unwrap();
+        assert!(!bool::from(authenticated));
+    }
+
+    #[test]
+    fn test_sealed_versions() {
+        let key = b"an example very very secret key.";
+        let plaintext = b"some note plaintext";
+
+        let sealed = seal(AeadVersion::ChaCha20Poly1305, key, plaintext).unwrap();
+        assert_eq!(sealed[0], AeadVersion::ChaCha20Poly1305.as_u8());
+        assert_eq!(open(key, &sealed).unwrap(), plaintext);
+
+        // The versioned layout is the unversioned one behind a version byte
+        let unversioned: [u8; 19 + aead::MAC_SIZE] = encrypt(key, plaintext).unwrap();
+        assert_eq!(&sealed[1..], &unversioned[..]);
+
+        let mut unknown_version = sealed.clone();
+        unknown_version[0] = 0;
+        assert!(open(key, &unknown_version).is_err());
+
+        let mut tampered = sealed;
+        tampered[1] ^= 1;
+        assert!(open(key, &tampered).is_err());
+
+        // Notes of every transaction version use the same scheme, as they
+        // use the same layout
+        for version in [
+            TRANSACTION_VERSION,
+            TREE_ID_TRANSACTION_VERSION,
+            EXTRA_DATA_TRANSACTION_VERSION,
+        ] {
+            assert_eq!(
+                AeadVersion::for_transaction_version(version),
+                AeadVersion::ChaCha20Poly1305
+            );
+            assert_eq!(
+                NoteCiphertextVersion::for_transaction_version(version),
+                NoteCiphertextVersion::V1
+            );
+        }
     }
 }
@@ -13,6 +13,8 @@ use crate::errors::IronfishError;
 /// can be a bit clunky if you're just working with bytearrays.
 use ff::PrimeField;
 use group::GroupEncoding;
+use jubjub::SubgroupPoint;
+use subtle::{Choice, ConditionallySelectable};
 
 use std::io;
 
@@ -32,6 +34,35 @@ pub(crate) fn read_point<G: GroupEncoding, R: io::Read>(mut reader: R) -> Result
     Option::from(G::from_bytes(&point_repr)).ok_or(IronfishError::InvalidData)
 }
 
+/// Decode a point without branching on the bytes, for points that are
+/// secret. Invalid encodings decode to `placeholder`, and the choice says
+/// whether the bytes were valid.
+pub(crate) fn read_point_constant_time(
+    bytes: &[u8; 32],
+    placeholder: &SubgroupPoint,
+) -> (SubgroupPoint, Choice) {
+    let is_valid = SubgroupPoint::from_bytes(bytes).is_some();
+    let bytes = select_bytes(&placeholder.to_bytes(), bytes, is_valid);
+
+    // The selected bytes always decode, so this never falls back
+    let point = Option::from(SubgroupPoint::from_bytes(&bytes)).unwrap_or(*placeholder);
+
+    (point, is_valid)
+}
+
+/// `b` if `choice` is set and `a` otherwise, without branching on `choice`
+pub(crate) fn select_bytes<const SIZE: usize>(
+    a: &[u8; SIZE],
+    b: &[u8; SIZE],
+    choice: Choice,
+) -> [u8; SIZE] {
+    let mut selected = [0; SIZE];
+    for ((selected, a), b) in selected.iter_mut().zip(a).zip(b) {
+        *selected = u8::conditional_select(a, b, choice);
+    }
+    selected
+}
+
 /// Output the bytes as a hexadecimal String
 pub fn bytes_to_hex(bytes: &[u8]) -> String {
     let mut hex: Vec<u8> = vec![0; bytes.len() * 2];
@@ -61,6 +92,18 @@ pub fn hex_to_bytes<const SIZE: usize>(hex: &str) -> Result<[u8; SIZE], Ironfish
     Ok(bytes)
 }
 
+/// Output the hexadecimal String as bytes, for hex of any length
+pub fn hex_to_vec_bytes(hex: &str) -> Result<Vec<u8>, IronfishError> {
+    if hex.len() % 2 != 0 {
+        return Err(IronfishError::InvalidData);
+    }
+
+    hex.as_bytes()
+        .chunks_exact(2)
+        .map(|hex| Ok(hex_to_u8(hex[0])? << 4 | hex_to_u8(hex[1])?))
+        .collect()
+}
+
 #[inline]
 fn hex_to_u8(char: u8) -> Result<u8, IronfishError> {
     match char {
@@ -73,7 +116,7 @@ fn hex_to_u8(char: u8) -> Result<u8, IronfishError> {
 
 #[cfg(test)]
 mod test {
-    use crate::serializing::{bytes_to_hex, hex_to_bytes};
+    use crate::serializing::{bytes_to_hex, hex_to_bytes, hex_to_vec_bytes};
 
     #[test]
     fn hex_serde() {
@@ -97,5 +140,9 @@ mod test {
 
         assert_eq!(HEX_LOWER, hex);
         assert_eq!(HEX_LOWER, lower_hex);
+
+        hex_to_vec_bytes(INVALID_HEX).expect_err("invalid hex should throw an error");
+        assert_eq!(hex_to_vec_bytes(HEX_STRING).unwrap(), bytes.to_vec());
+        assert!(hex_to_vec_bytes("").unwrap().is_empty());
     }
 }
@@ -0,0 +1,213 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::io;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ff::PrimeField;
+use ironfish_zkp::constants::TREE_DEPTH;
+
+use crate::{
+    errors::IronfishError,
+    note::Note,
+    serializing::read_scalar,
+    tree_position::{NotePosition, TreeSize},
+    witness::{Witness, WitnessNode, WitnessTrait},
+    MerkleNoteHash, ProposedTransaction,
+};
+
+/// Serialization version of [`SpendableNoteRecord`], bumped whenever the
+/// format changes so records exported by older wallets can still be detected.
+pub const SPENDABLE_NOTE_RECORD_VERSION: u8 = 1;
+
+/// An unspent note together with everything needed to spend it, so that
+/// wallets can move their notes to cold storage or another device without
+/// having to rescan the chain.
+///
+/// Witnesses go stale as the note tree grows, but a stale witness is still
+/// valid against its own root hash, so a record can be spent as long as that
+/// root is recent enough to be accepted by the network.
+pub struct SpendableNoteRecord {
+    pub note: Note,
+
+    /// Position of the note in the note tree
+    pub position: NotePosition,
+
+    pub witness: Witness,
+
+    /// Confirmations of the note when the record was exported. This is only
+    /// informational, it's never checked against the chain.
+    pub confirmations: u32,
+}
+
+impl SpendableNoteRecord {
+    /// Create a record, checking that the witness proves the note is at the
+    /// given position in the tree with the witness's root hash.
+    pub fn new(
+        note: Note,
+        position: NotePosition,
+        witness: Witness,
+        confirmations: u32,
+    ) -> Result<Self, IronfishError> {
+        let record = SpendableNoteRecord {
+            note,
+            position,
+            witness,
+            confirmations,
+        };
+        record.check_witness()?;
+
+        Ok(record)
+    }
+
+    fn check_witness(&self) -> Result<(), IronfishError> {
+        if self.witness.auth_path.len() != TREE_DEPTH
+            || NotePosition::from_auth_path(&self.witness.auth_path) != self.position
+            || !self
+                .witness
+                .verify(&MerkleNoteHash::new(self.note.commitment_point()))
+        {
+            return Err(IronfishError::InconsistentWitness);
+        }
+
+        Ok(())
+    }
+
+    /// Load a [`SpendableNoteRecord`] from a Read implementation (e.g: socket,
+    /// file). The witness is checked against the note, so a corrupt record is
+    /// rejected instead of failing later when proving.
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != SPENDABLE_NOTE_RECORD_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let note = Note::read(&mut reader)?;
+        let position = NotePosition::new(reader.read_u64::<LittleEndian>()?);
+        let confirmations = reader.read_u32::<LittleEndian>()?;
+
+        let tree_size = TreeSize::new(reader.read_u32::<LittleEndian>()?);
+        let root_hash = read_scalar(&mut reader)?;
+
+        let mut auth_path = Vec::with_capacity(TREE_DEPTH);
+        for _ in 0..TREE_DEPTH {
+            let is_right = reader.read_u8()?;
+            let sibling = read_scalar(&mut reader)?;
+
+            auth_path.push(match is_right {
+                0 => WitnessNode::Left(sibling),
+                1 => WitnessNode::Right(sibling),
+                _ => return Err(IronfishError::InvalidData),
+            });
+        }
+
+        let witness = Witness {
+            tree_size,
+            root_hash,
+            auth_path,
+        };
+
+        SpendableNoteRecord::new(note, position, witness, confirmations)
+    }
+
+    /// Stow the bytes of this [`SpendableNoteRecord`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        self.check_witness()?;
+
+        writer.write_u8(SPENDABLE_NOTE_RECORD_VERSION)?;
+        self.note.write(&mut writer)?;
+        writer.write_u64::<LittleEndian>(self.position.as_u64())?;
+        writer.write_u32::<LittleEndian>(self.confirmations)?;
+
+        writer.write_u32::<LittleEndian>(self.witness.tree_size.as_u32())?;
+        writer.write_all(&self.witness.root_hash.to_repr())?;
+
+        for node in self.witness.auth_path.iter() {
+            let (is_right, sibling) = match node {
+                WitnessNode::Left(sibling) => (0, sibling),
+                WitnessNode::Right(sibling) => (1, sibling),
+            };
+
+            writer.write_u8(is_right)?;
+            writer.write_all(&sibling.to_repr())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ProposedTransaction {
+    /// Spend the note in an exported record. See
+    /// [`ProposedTransaction::add_spend`].
+    pub fn add_spendable_note(
+        &mut self,
+        record: &SpendableNoteRecord,
+    ) -> Result<(), IronfishError> {
+        self.add_spend(record.note.clone(), &record.witness)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpendableNoteRecord;
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, tree_position::NotePosition, ProposedTransaction,
+    };
+
+    #[test]
+    fn test_spendable_note_record_round_trip() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+        let position = NotePosition::from_auth_path(&witness.auth_path);
+
+        let record = SpendableNoteRecord::new(note, position, witness, 12).unwrap();
+
+        let mut serialized = vec![];
+        record.write(&mut serialized).unwrap();
+
+        let read_back = SpendableNoteRecord::read(&serialized[..]).unwrap();
+        assert_eq!(read_back.note.commitment(), record.note.commitment());
+        assert_eq!(read_back.position, position);
+        assert_eq!(read_back.confirmations, 12);
+        assert!(read_back.witness == record.witness);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spendable_note(&read_back).unwrap();
+
+        // Flipping a bit of the root hash breaks the witness
+        let root_offset = serialized.len() - 33 * 32 - 32;
+        serialized[root_offset] ^= 1;
+        assert!(matches!(
+            SpendableNoteRecord::read(&serialized[..]),
+            Err(IronfishError::InconsistentWitness | IronfishError::InvalidData)
+        ));
+    }
+
+    #[test]
+    fn test_spendable_note_record_wrong_position() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+        let position = NotePosition::from_auth_path(&witness.auth_path);
+
+        assert!(matches!(
+            SpendableNoteRecord::new(note, NotePosition::new(position.as_u64() ^ 1), witness, 0),
+            Err(IronfishError::InconsistentWitness)
+        ));
+    }
+}
@@ -3,9 +3,12 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use super::{
+    assets::asset_identifier::NATIVE_ASSET,
+    keys::SaplingKey,
     note::Note,
+    tree_position::TreeSize,
     witness::{Witness, WitnessNode},
-    MerkleNoteHash,
+    MerkleNoteHash, ProposedTransaction, Transaction,
 };
 use bls12_381::Scalar;
 use ironfish_zkp::constants::TREE_DEPTH;
@@ -29,10 +32,40 @@ pub fn make_fake_witness(note: &Note) -> Witness {
     Witness {
         auth_path: witness_auth_path,
         root_hash,
-        tree_size: 1400,
+        tree_size: TreeSize::new(1400),
     }
 }
 
+/// Fee paid by [`make_posted_transaction`], the difference between its spend
+/// and its output
+pub const POSTED_TRANSACTION_FEE: u64 = 2;
+
+/// Post a transaction with a spend and an output of the native asset, proven
+/// against a fake witness.
+pub fn make_posted_transaction() -> Transaction {
+    let key = SaplingKey::generate_key();
+    let in_note = Note::new(
+        key.public_address(),
+        40 + POSTED_TRANSACTION_FEE,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let out_note = Note::new(
+        key.public_address(),
+        40,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut proposed = ProposedTransaction::new(key);
+    proposed.add_spend(in_note, &witness).unwrap();
+    proposed.add_output(out_note).unwrap();
+    proposed.post(None, POSTED_TRANSACTION_FEE).unwrap()
+}
+
 /// Helper function to calculate a root hash given an authentication path from
 /// a specific child_hash.
 ///
@@ -0,0 +1,254 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Checks on the anchors of a transaction's spends before they're proven.
+//!
+//! Every spend proves its note is in the tree with the root hash of its
+//! witness. Nodes only accept roots the tree had recently, so a transaction
+//! with a witness that's fallen too far behind is rejected, but only after
+//! all of its proofs were built. An [`AnchorPolicy`] catches that up front,
+//! along with witnesses that disagree about what the tree looked like at the
+//! same size, which means one of them is corrupt or from another chain.
+
+use std::fmt;
+
+use bls12_381::Scalar;
+
+use crate::{errors::IronfishError, tree_position::TreeSize, witness::TreeId};
+
+use super::{spends::SpendBuilder, ProposedTransaction};
+
+/// How stale the anchors of a transaction may be
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AnchorPolicy {
+    /// Size of the tree as the node currently sees it. Anchors are aged
+    /// against it if set, otherwise against the newest anchor of the
+    /// transaction's spends in the same tree.
+    pub current_tree_size: Option<TreeSize>,
+
+    /// How many leaves an anchor may trail the current tree by
+    pub max_age: u32,
+
+    /// Roots accepted whatever their age, for example roots the node is
+    /// known to still accept
+    pub allowed_roots: Vec<Scalar>,
+}
+
+impl AnchorPolicy {
+    /// Every spend has to be anchored to the same root
+    pub fn same_root() -> Self {
+        AnchorPolicy::default()
+    }
+
+    /// Anchors may trail the newest one by up to `max_age` leaves
+    pub fn with_max_age(max_age: u32) -> Self {
+        AnchorPolicy {
+            max_age,
+            ..AnchorPolicy::default()
+        }
+    }
+
+    /// Accept this root whatever its age
+    pub fn allow_root(&mut self, root_hash: Scalar) {
+        self.allowed_roots.push(root_hash);
+    }
+
+    /// Check the anchors of the given spends, reporting the first spend with
+    /// an anchor this policy doesn't accept.
+    pub(crate) fn check(&self, spends: &[SpendBuilder]) -> Result<(), AnchorRejection> {
+        for (index, spend) in spends.iter().enumerate() {
+            let same_tree = spends.iter().filter(|other| other.tree_id == spend.tree_id);
+
+            // Witnesses of the same tree at the same size must agree on its
+            // root, allowed or not
+            if let Some(other) = same_tree.clone().find(|other| {
+                other.tree_size == spend.tree_size && other.root_hash != spend.root_hash
+            }) {
+                return Err(AnchorRejection {
+                    spend: index,
+                    reason: AnchorRejectionReason::ConflictingRoot {
+                        tree_id: spend.tree_id,
+                        tree_size: TreeSize::new(other.tree_size),
+                    },
+                });
+            }
+
+            if self.allowed_roots.contains(&spend.root_hash) {
+                continue;
+            }
+
+            let tree_size = TreeSize::new(spend.tree_size);
+            let current = match self.current_tree_size {
+                Some(current) => current,
+                None => same_tree
+                    .map(|other| TreeSize::new(other.tree_size))
+                    .max()
+                    .unwrap_or(tree_size),
+            };
+
+            if tree_size > current {
+                return Err(AnchorRejection {
+                    spend: index,
+                    reason: AnchorRejectionReason::AheadOfTree {
+                        tree_size,
+                        current_tree_size: current,
+                    },
+                });
+            }
+
+            let age = current.as_u32() - tree_size.as_u32();
+            if age > self.max_age {
+                return Err(AnchorRejection {
+                    spend: index,
+                    reason: AnchorRejectionReason::TooOld {
+                        age,
+                        max_age: self.max_age,
+                    },
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A spend whose anchor an [`AnchorPolicy`] doesn't accept
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnchorRejection {
+    /// Index of the spend, in the order spends were added
+    pub spend: usize,
+
+    pub reason: AnchorRejectionReason,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AnchorRejectionReason {
+    /// The anchor trails the current tree by more leaves than allowed
+    TooOld { age: u32, max_age: u32 },
+
+    /// The witness is from a larger tree than the node knows of
+    AheadOfTree {
+        tree_size: TreeSize,
+        current_tree_size: TreeSize,
+    },
+
+    /// Another spend has a different root for the tree at the same size
+    ConflictingRoot {
+        tree_id: TreeId,
+        tree_size: TreeSize,
+    },
+}
+
+impl fmt::Display for AnchorRejection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "spend {}: {:?}", self.spend, self.reason)
+    }
+}
+
+impl ProposedTransaction {
+    /// Check the anchors of the spends added so far against the anchor
+    /// policy, if any. Posting does this before building any proofs.
+    pub fn check_anchors(&self) -> Result<(), IronfishError> {
+        match &self.anchor_policy {
+            Some(policy) => policy
+                .check(&self.spends)
+                .map_err(IronfishError::RejectedAnchor),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AnchorPolicy, AnchorRejection, AnchorRejectionReason};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, tree_position::TreeSize, ProposedTransaction,
+    };
+    use bls12_381::Scalar;
+
+    fn add_spend(
+        transaction: &mut ProposedTransaction,
+        key: &SaplingKey,
+        tree_size: u32,
+        root: u64,
+    ) {
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let mut witness = make_fake_witness(&note);
+        witness.tree_size = TreeSize::new(tree_size);
+        witness.root_hash = Scalar::from(root);
+
+        transaction.add_spend(note, &witness).unwrap();
+    }
+
+    fn rejection(transaction: &ProposedTransaction) -> AnchorRejection {
+        match transaction.check_anchors() {
+            Err(IronfishError::RejectedAnchor(rejection)) => rejection,
+            result => panic!("expected a rejected anchor, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_anchor_policy() {
+        let key = SaplingKey::generate_key();
+        let mut transaction = ProposedTransaction::new(key.clone());
+        add_spend(&mut transaction, &key, 100, 1);
+        add_spend(&mut transaction, &key, 90, 2);
+
+        // Without a policy, anything goes until the node rejects it
+        transaction.check_anchors().unwrap();
+
+        transaction.set_anchor_policy(Some(AnchorPolicy::same_root()));
+        assert_eq!(
+            rejection(&transaction),
+            AnchorRejection {
+                spend: 1,
+                reason: AnchorRejectionReason::TooOld {
+                    age: 10,
+                    max_age: 0
+                },
+            }
+        );
+
+        transaction.set_anchor_policy(Some(AnchorPolicy::with_max_age(10)));
+        transaction.check_anchors().unwrap();
+
+        // Aged against the node's tree instead of the newest spend
+        let mut policy = AnchorPolicy::with_max_age(10);
+        policy.current_tree_size = Some(TreeSize::new(105));
+        transaction.set_anchor_policy(Some(policy.clone()));
+        assert_eq!(rejection(&transaction).spend, 1);
+
+        policy.allow_root(Scalar::from(2));
+        transaction.set_anchor_policy(Some(policy.clone()));
+        transaction.check_anchors().unwrap();
+
+        policy.current_tree_size = Some(TreeSize::new(95));
+        transaction.set_anchor_policy(Some(policy));
+        assert!(matches!(
+            rejection(&transaction),
+            AnchorRejection {
+                spend: 0,
+                reason: AnchorRejectionReason::AheadOfTree { .. }
+            }
+        ));
+
+        // Two roots for the tree at the same size are never compatible
+        add_spend(&mut transaction, &key, 90, 3);
+        transaction.set_anchor_policy(Some(AnchorPolicy::with_max_age(10)));
+        assert!(matches!(
+            rejection(&transaction),
+            AnchorRejection {
+                spend: 1,
+                reason: AnchorRejectionReason::ConflictingRoot { .. }
+            }
+        ));
+    }
+}
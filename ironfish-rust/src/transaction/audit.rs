@@ -0,0 +1,314 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Re-verification of a whole chain, for explorers and exchanges auditing
+//! the blocks they've already accepted.
+//!
+//! A [`ChainAudit`] verifies the proofs and signatures of many blocks in a
+//! single batch, which is much faster than verifying block by block, and
+//! still reports on every block: when a batch fails, its blocks are
+//! verified again one transaction at a time to find the ones at fault. The
+//! blocks of a batch are held until it's verified, so the batch size bounds
+//! the memory an audit takes.
+
+use std::mem;
+
+use crate::{errors::IronfishError, network::NetworkKind};
+
+use super::{block_verifier::BlockVerifier, Transaction, TRANSACTION_VERSION};
+
+/// The transactions of a block, as serialized in it
+pub struct SerializedBlock {
+    pub sequence: u32,
+    pub transactions: Vec<Vec<u8>>,
+}
+
+/// The outcome of the audit of one block
+#[derive(Debug)]
+pub struct BlockReport {
+    pub sequence: u32,
+
+    /// Number of transactions in the block
+    pub transactions: usize,
+
+    /// The first invalid transaction of the block, if any
+    pub failure: Option<TransactionFailure>,
+}
+
+impl BlockReport {
+    pub fn is_valid(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// Why a transaction of an audited block is invalid
+#[derive(Debug)]
+pub struct TransactionFailure {
+    /// Index of the transaction in the block
+    pub index: usize,
+    pub error: IronfishError,
+}
+
+/// Totals of a finished audit
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AuditSummary {
+    pub blocks: usize,
+    pub transactions: usize,
+    pub invalid_blocks: usize,
+
+    /// Number of batches verified, not counting the verification of the
+    /// blocks of failed batches one by one
+    pub batches: usize,
+}
+
+/// A block of the batch being queued: its transactions once read, or the
+/// report of a block that couldn't be read
+enum QueuedBlock {
+    Read {
+        sequence: u32,
+        transactions: Vec<Transaction>,
+    },
+    Reported(BlockReport),
+}
+
+/// Verifies blocks in batches of at least a given size, and reports on each
+/// of them in order. See the [module documentation](self).
+pub struct ChainAudit {
+    network: NetworkKind,
+
+    /// Latest transaction version the audited chain activated
+    activated_version: u8,
+
+    /// Serialized bytes of transactions to queue before verifying the batch
+    batch_size: usize,
+
+    verifier: BlockVerifier,
+    queued: Vec<QueuedBlock>,
+    queued_size: usize,
+
+    /// A transaction failed its checks while being queued, so the batch is
+    /// missing some of its proofs and has to be verified block by block
+    tainted: bool,
+
+    summary: AuditSummary,
+}
+
+impl ChainAudit {
+    pub fn new(network: NetworkKind, batch_size: usize) -> Self {
+        ChainAudit {
+            network,
+            activated_version: TRANSACTION_VERSION,
+            batch_size,
+            verifier: BlockVerifier::unbounded(),
+            queued: vec![],
+            queued_size: 0,
+            tainted: false,
+            summary: AuditSummary::default(),
+        }
+    }
+
+    /// Accept the transaction versions up to `activated_version`, instead of
+    /// only [`TRANSACTION_VERSION`]
+    pub fn with_activated_version(mut self, activated_version: u8) -> Self {
+        self.activated_version = activated_version;
+        self.verifier = BlockVerifier::unbounded().with_activated_version(activated_version);
+        self
+    }
+
+    /// Queue the block, verifying the batch once it's large enough. Reports
+    /// are passed to `on_report` in the order the blocks were added, once
+    /// the batch holding them is verified.
+    pub fn add_block(&mut self, block: SerializedBlock, on_report: impl FnMut(BlockReport)) {
+        self.queued_size += block.transactions.iter().map(Vec::len).sum::<usize>();
+        let queued = self.queue(block);
+        self.queued.push(queued);
+
+        if self.queued_size >= self.batch_size {
+            self.flush(on_report);
+        }
+    }
+
+    /// Verify the last batch and report on its blocks
+    pub fn finish(mut self, on_report: impl FnMut(BlockReport)) -> AuditSummary {
+        self.flush(on_report);
+        self.summary
+    }
+
+    fn queue(&mut self, block: SerializedBlock) -> QueuedBlock {
+        let count = block.transactions.len();
+        let mut transactions = Vec::with_capacity(count);
+
+        for (index, serialized) in block.transactions.iter().enumerate() {
+            let queued = Transaction::read_for_network(&serialized[..], self.network).and_then(
+                |transaction| {
+                    self.verifier.add_transaction(&transaction)?;
+                    Ok(transaction)
+                },
+            );
+
+            match queued {
+                Ok(transaction) => transactions.push(transaction),
+                Err(error) => {
+                    self.tainted = true;
+                    return QueuedBlock::Reported(BlockReport {
+                        sequence: block.sequence,
+                        transactions: count,
+                        failure: Some(TransactionFailure { index, error }),
+                    });
+                }
+            }
+        }
+
+        QueuedBlock::Read {
+            sequence: block.sequence,
+            transactions,
+        }
+    }
+
+    fn flush(&mut self, mut on_report: impl FnMut(BlockReport)) {
+        if self.queued.is_empty() {
+            return;
+        }
+
+        let verifier = mem::replace(
+            &mut self.verifier,
+            BlockVerifier::unbounded().with_activated_version(self.activated_version),
+        );
+        let batch_is_valid = verifier.finish().is_ok() && !self.tainted;
+        self.summary.batches += 1;
+        self.queued_size = 0;
+        self.tainted = false;
+
+        for queued in mem::take(&mut self.queued) {
+            let report = match queued {
+                QueuedBlock::Reported(report) => report,
+                QueuedBlock::Read {
+                    sequence,
+                    transactions,
+                } => BlockReport {
+                    sequence,
+                    transactions: transactions.len(),
+                    failure: match batch_is_valid {
+                        true => None,
+                        false => first_failure(&transactions, self.activated_version),
+                    },
+                },
+            };
+
+            self.summary.blocks += 1;
+            self.summary.transactions += report.transactions;
+            if !report.is_valid() {
+                self.summary.invalid_blocks += 1;
+            }
+            on_report(report);
+        }
+    }
+}
+
+/// Verify the transactions one at a time, returning the first that fails
+fn first_failure(
+    transactions: &[Transaction],
+    activated_version: u8,
+) -> Option<TransactionFailure> {
+    transactions
+        .iter()
+        .enumerate()
+        .find_map(|(index, transaction)| {
+            let mut verifier = BlockVerifier::unbounded().with_activated_version(activated_version);
+            verifier
+                .add_transaction(transaction)
+                .and_then(|_| verifier.finish())
+                .err()
+                .map(|error| TransactionFailure { index, error })
+        })
+}
+
+/// Audit every block, in batches of at least `batch_size` serialized bytes,
+/// passing the report of each block to `on_report` in order
+pub fn audit_chain<I: IntoIterator<Item = SerializedBlock>>(
+    blocks: I,
+    network: NetworkKind,
+    batch_size: usize,
+    mut on_report: impl FnMut(BlockReport),
+) -> AuditSummary {
+    let mut audit = ChainAudit::new(network, batch_size);
+    for block in blocks {
+        audit.add_block(block, &mut on_report);
+    }
+
+    audit.finish(on_report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{audit_chain, AuditSummary, SerializedBlock};
+    use crate::{
+        errors::IronfishError, network::NetworkKind, test_util::make_posted_transaction,
+        Transaction,
+    };
+
+    fn serialize(transaction: &Transaction) -> Vec<u8> {
+        let mut serialized = vec![];
+        transaction.write(&mut serialized).unwrap();
+        serialized
+    }
+
+    #[test]
+    fn test_audit_chain() {
+        let valid = serialize(&make_posted_transaction());
+        let mut invalid = make_posted_transaction();
+        invalid.fee += 1;
+        let invalid = serialize(&invalid);
+
+        let blocks = || {
+            vec![
+                SerializedBlock {
+                    sequence: 1,
+                    transactions: vec![valid.clone()],
+                },
+                SerializedBlock {
+                    sequence: 2,
+                    transactions: vec![valid.clone(), invalid.clone()],
+                },
+                SerializedBlock {
+                    sequence: 3,
+                    transactions: vec![vec![0; 3]],
+                },
+                SerializedBlock {
+                    sequence: 4,
+                    transactions: vec![valid.clone(), valid.clone()],
+                },
+            ]
+        };
+
+        // The whole chain at once, and a block at a time
+        for (batch_size, batches) in [(usize::MAX, 1), (1, 4)] {
+            let mut reports = vec![];
+            let summary = audit_chain(blocks(), NetworkKind::Mainnet, batch_size, |report| {
+                reports.push(report)
+            });
+            assert_eq!(
+                summary,
+                AuditSummary {
+                    blocks: 4,
+                    transactions: 6,
+                    invalid_blocks: 2,
+                    batches,
+                }
+            );
+
+            let sequences: Vec<u32> = reports.iter().map(|report| report.sequence).collect();
+            assert_eq!(sequences, [1, 2, 3, 4]);
+            assert!(reports[0].is_valid());
+            assert!(reports[3].is_valid());
+
+            let failure = reports[1].failure.as_ref().unwrap();
+            assert_eq!(failure.index, 1);
+            assert!(matches!(failure.error, IronfishError::VerificationFailed));
+            let failure = reports[2].failure.as_ref().unwrap();
+            assert_eq!(failure.index, 0);
+            assert!(matches!(failure.error, IronfishError::Io(_)));
+        }
+    }
+}
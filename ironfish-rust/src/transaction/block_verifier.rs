@@ -0,0 +1,303 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Batch verification of the transactions of a block in bounded memory.
+//!
+//! [`super::batch_verify_transactions`] queues the proofs and signatures of
+//! every transaction before verifying any of them, so its memory grows with
+//! the block. A [`BlockVerifier`] takes transactions one at a time and
+//! verifies what it has queued whenever the queue reaches its memory limit,
+//! so a validator with little memory can verify the largest blocks, at the
+//! cost of smaller batches. Transactions are only borrowed while they're
+//! added, and [`BlockVerifier::add_serialized`] reads them straight from the
+//! block, so nothing but the queue has to be held.
+//!
+//! As with [`super::batch_verify_transactions`], a failure doesn't say which
+//! transaction is invalid, and the block is only valid once
+//! [`BlockVerifier::finish`] succeeds.
+
+use std::{collections::BTreeMap, io, mem};
+
+use bellman::groth16;
+use bls12_381::{Bls12, Scalar};
+use ironfish_zkp::redjubjub::{PublicKey, Signature};
+use jubjub::ExtendedPoint;
+
+use crate::{
+    errors::IronfishError,
+    metrics::Circuit,
+    network::NetworkKind,
+    randomness::with_rng,
+    value_range::{check_posted_value, ValueContext},
+};
+
+use super::{
+    calculate_value_balance, is_verifiable_version, signature_batch::SignatureBatch,
+    utils::verify_proof_batch, Transaction, TRANSACTION_VERSION,
+};
+
+/// Memory taken by a queued proof, besides its public inputs
+const QUEUED_PROOF_SIZE: usize = mem::size_of::<(groth16::Proof<Bls12>, Vec<Scalar>)>();
+
+/// Memory taken by a queued signature, with its key and message
+const QUEUED_SIGNATURE_SIZE: usize = mem::size_of::<PublicKey>() + 64 + mem::size_of::<Signature>();
+
+/// Proofs of one circuit waiting to be verified together, by the version of
+/// their transaction, which picks the parameters they're verified with
+#[derive(Default)]
+struct QueuedProofs {
+    proofs: BTreeMap<u8, Vec<(groth16::Proof<Bls12>, Vec<Scalar>)>>,
+}
+
+impl QueuedProofs {
+    /// Queue the proof, returning the memory it takes
+    fn queue(&mut self, version: u8, proof: &groth16::Proof<Bls12>, inputs: &[Scalar]) -> usize {
+        self.proofs
+            .entry(version)
+            .or_default()
+            .push((proof.clone(), inputs.to_vec()));
+        QUEUED_PROOF_SIZE + mem::size_of_val(inputs)
+    }
+
+    fn verify(&mut self, circuit: Circuit) -> Result<(), IronfishError> {
+        for (version, proofs) in mem::take(&mut self.proofs) {
+            let (proofs, inputs): (Vec<_>, Vec<_>) = proofs.into_iter().unzip();
+            let batch: Vec<_> = proofs.iter().zip(inputs).collect();
+
+            verify_proof_batch(version, circuit, &batch)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct BlockVerifier {
+    /// Memory the queue may take before it's verified, in bytes
+    memory_limit: usize,
+
+    /// Memory the queue takes now, as estimated from what's queued
+    queued_memory: usize,
+
+    spend_proofs: QueuedProofs,
+    output_proofs: QueuedProofs,
+    mint_proofs: QueuedProofs,
+    signatures: SignatureBatch,
+
+    /// Latest transaction version the node activated
+    activated_version: u8,
+
+    flushes: usize,
+}
+
+impl BlockVerifier {
+    /// A verifier whose queue takes at most about `memory_limit` bytes. The
+    /// queue is verified as soon as it reaches the limit, even halfway
+    /// through a transaction.
+    pub fn new(memory_limit: usize) -> Self {
+        BlockVerifier {
+            memory_limit,
+            queued_memory: 0,
+            spend_proofs: QueuedProofs::default(),
+            output_proofs: QueuedProofs::default(),
+            mint_proofs: QueuedProofs::default(),
+            signatures: SignatureBatch::default(),
+            activated_version: TRANSACTION_VERSION,
+            flushes: 0,
+        }
+    }
+
+    /// Accept the transaction versions up to `activated_version`, instead of
+    /// only [`TRANSACTION_VERSION`]
+    pub fn with_activated_version(mut self, activated_version: u8) -> Self {
+        self.activated_version = activated_version;
+        self
+    }
+
+    /// A verifier that queues everything until [`BlockVerifier::finish`]
+    pub fn unbounded() -> Self {
+        BlockVerifier::new(usize::MAX)
+    }
+
+    /// Check the structure of the transaction, and queue its proofs and
+    /// signatures
+    pub fn add_transaction(&mut self, transaction: &Transaction) -> Result<(), IronfishError> {
+        if !is_verifiable_version(transaction.version, self.activated_version) {
+            return Err(IronfishError::InvalidTransactionVersion);
+        }
+
+        // Context to accumulate a signature of all the spends and outputs and
+        // guarantee they are part of this transaction, unmodified.
+        let mut binding_verification_key = ExtendedPoint::identity();
+
+        let hash_to_verify_signature = transaction.transaction_signature_hash();
+
+        for spend in transaction.spends.iter() {
+            spend.partial_verify()?;
+
+            let public_inputs = spend.public_inputs(transaction.randomized_public_key());
+            let size = self
+                .spend_proofs
+                .queue(transaction.version, &spend.proof, &public_inputs);
+            self.queued(size)?;
+
+            binding_verification_key += spend.value_commitment;
+
+            self.signatures.queue_authorizing(
+                transaction.randomized_public_key(),
+                &hash_to_verify_signature,
+                &spend.authorizing_signature,
+            )?;
+            self.queued(QUEUED_SIGNATURE_SIZE)?;
+        }
+
+        for output in transaction.outputs.iter() {
+            output.partial_verify()?;
+
+            let public_inputs = output.public_inputs(transaction.randomized_public_key());
+            let size = self
+                .output_proofs
+                .queue(transaction.version, &output.proof, &public_inputs);
+            self.queued(size)?;
+
+            binding_verification_key -= output.merkle_note.value_commitment;
+        }
+
+        for mint in transaction.mints.iter() {
+            mint.partial_verify()?;
+            check_posted_value(mint.value, ValueContext::Mint)?;
+
+            let public_inputs = mint.public_inputs(transaction.randomized_public_key());
+            let size = self
+                .mint_proofs
+                .queue(transaction.version, &mint.proof, &public_inputs);
+            self.queued(size)?;
+
+            self.signatures.queue_authorizing(
+                transaction.randomized_public_key(),
+                &hash_to_verify_signature,
+                &mint.authorizing_signature,
+            )?;
+            self.queued(QUEUED_SIGNATURE_SIZE)?;
+        }
+
+        for burn in transaction.burns.iter() {
+            check_posted_value(burn.value, ValueContext::Burn)?;
+        }
+
+        let value_balance = calculate_value_balance(
+            &binding_verification_key,
+            transaction.fee,
+            &transaction.mints,
+            &transaction.burns,
+        )?;
+        self.signatures.queue_binding(
+            value_balance,
+            &hash_to_verify_signature,
+            &transaction.binding_signature,
+        );
+        self.queued(QUEUED_SIGNATURE_SIZE)
+    }
+
+    /// Read the next transaction of a block from the given reader, and add
+    /// it. The transaction is dropped once its proofs and signatures are
+    /// queued.
+    pub fn add_serialized<R: io::Read>(
+        &mut self,
+        reader: R,
+        network: NetworkKind,
+    ) -> Result<(), IronfishError> {
+        let transaction = Transaction::read_for_network(reader, network)?;
+        self.add_transaction(&transaction)
+    }
+
+    /// Verify everything queued so far, emptying the queue
+    pub fn flush(&mut self) -> Result<(), IronfishError> {
+        self.queued_memory = 0;
+        self.flushes += 1;
+
+        self.spend_proofs.verify(Circuit::Spend)?;
+        self.output_proofs.verify(Circuit::Output)?;
+        self.mint_proofs.verify(Circuit::Mint)?;
+        let signatures = mem::take(&mut self.signatures);
+        with_rng(|rng| signatures.verify(rng))??;
+
+        Ok(())
+    }
+
+    /// Verify what's left in the queue. Every transaction added is valid if
+    /// this succeeds.
+    pub fn finish(mut self) -> Result<(), IronfishError> {
+        self.flush()
+    }
+
+    /// How many times the queue was verified, including by
+    /// [`BlockVerifier::finish`]
+    pub fn flushes(&self) -> usize {
+        self.flushes
+    }
+
+    fn queued(&mut self, size: usize) -> Result<(), IronfishError> {
+        self.queued_memory = self.queued_memory.saturating_add(size);
+        if self.queued_memory >= self.memory_limit {
+            self.flush()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BlockVerifier, QUEUED_PROOF_SIZE};
+    use crate::{
+        errors::IronfishError, network::NetworkKind, test_util::make_posted_transaction,
+        transaction::batch_verify_transactions,
+    };
+
+    #[test]
+    fn test_block_verifier_memory_limit() {
+        let transactions = [
+            make_posted_transaction(),
+            make_posted_transaction(),
+            make_posted_transaction(),
+        ];
+
+        let mut unbounded = BlockVerifier::unbounded();
+        for transaction in transactions.iter() {
+            unbounded.add_transaction(transaction).unwrap();
+        }
+        assert_eq!(unbounded.flushes(), 0);
+        unbounded.finish().unwrap();
+
+        // A limit below a single proof verifies every item on its own
+        let mut bounded = BlockVerifier::new(QUEUED_PROOF_SIZE);
+        for transaction in transactions.iter() {
+            let mut serialized = vec![];
+            transaction.write(&mut serialized).unwrap();
+            bounded
+                .add_serialized(&serialized[..], NetworkKind::Mainnet)
+                .unwrap();
+        }
+        assert!(bounded.flushes() >= 2 * transactions.len());
+        bounded.finish().unwrap();
+
+        batch_verify_transactions(transactions.iter()).unwrap();
+    }
+
+    #[test]
+    fn test_block_verifier_rejects_invalid_transaction() {
+        let valid = make_posted_transaction();
+        let mut invalid = make_posted_transaction();
+        invalid.fee += 1;
+
+        for limit in [QUEUED_PROOF_SIZE, usize::MAX] {
+            let mut verifier = BlockVerifier::new(limit);
+            verifier.add_transaction(&valid).unwrap();
+            let result = verifier
+                .add_transaction(&invalid)
+                .and_then(|_| verifier.finish());
+            assert!(matches!(result, Err(IronfishError::VerificationFailed)));
+        }
+    }
+}
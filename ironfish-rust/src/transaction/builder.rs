@@ -0,0 +1,331 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A typed front end to [`ProposedTransaction`] that walks through the stages
+//! of building a transaction. Descriptions are added while [`Building`],
+//! change is added to make the transaction [`Balanced`], the proofs are
+//! built to make it [`Proven`], and finally it's [`Signed`]. Each stage only
+//! has the methods that make sense in it, so calling them out of order, like
+//! adding a spend after signing or proving before adding change, doesn't
+//! compile.
+//!
+//! [`ProposedTransaction`] remains the dynamic API, for callers such as the
+//! FFI bindings that can't carry the stage in a type. It runs every stage at
+//! once when posting. [`TransactionBuilder::from_proposed`] and
+//! [`TransactionBuilder::into_proposed`] move between the two while
+//! building.
+//!
+//! ```compile_fail
+//! use ironfish::{transaction::builder::TransactionBuilder, SaplingKey};
+//!
+//! let builder = TransactionBuilder::new(SaplingKey::generate_key());
+//! // Change has to be added before proving
+//! builder.prove();
+//! ```
+
+use crate::{
+    assets::{asset::Asset, asset_identifier::AssetIdentifier},
+    errors::IronfishError,
+    keys::{MemoKey, PublicAddress, SaplingKey},
+    note::Note,
+    witness::WitnessTrait,
+};
+
+use super::{
+    conversions::Conversion, issuance::MintRequest, memo_policy::MemoPolicy, ProposedTransaction,
+    Transaction, UnsignedDescriptions,
+};
+
+/// Descriptions can still be added
+pub struct Building;
+
+/// Change has been added, so that spends and mints pay for exactly the
+/// outputs, burns and fee
+pub struct Balanced;
+
+/// Every description is proven, and the hash they're signed over is known
+pub struct Proven {
+    descriptions: UnsignedDescriptions,
+}
+
+/// Signed and ready to be posted
+pub struct Signed {
+    transaction: Transaction,
+}
+
+/// A [`ProposedTransaction`] that tracks the stage it's in, `S`, in its type
+pub struct TransactionBuilder<S> {
+    proposed: ProposedTransaction,
+    stage: S,
+}
+
+impl<S> TransactionBuilder<S> {
+    /// Get the expiration sequence for this transaction
+    pub fn expiration(&self) -> u32 {
+        self.proposed.expiration()
+    }
+}
+
+impl TransactionBuilder<Building> {
+    pub fn new(spender_key: SaplingKey) -> Self {
+        TransactionBuilder::from_proposed(ProposedTransaction::new(spender_key))
+    }
+
+    /// Continue building a transaction that was started with the dynamic API
+    pub fn from_proposed(proposed: ProposedTransaction) -> Self {
+        TransactionBuilder {
+            proposed,
+            stage: Building,
+        }
+    }
+
+    /// Continue building with the dynamic API
+    pub fn into_proposed(self) -> ProposedTransaction {
+        self.proposed
+    }
+
+    /// Spend the note owned by the spender key at the given witness location.
+    pub fn add_spend(
+        &mut self,
+        note: Note,
+        witness: &dyn WitnessTrait,
+    ) -> Result<(), IronfishError> {
+        self.proposed.add_spend(note, witness)
+    }
+
+    /// Create a proof of a new note owned by the recipient in this
+    /// transaction.
+    pub fn add_output(&mut self, note: Note) -> Result<(), IronfishError> {
+        self.proposed.add_output(note)
+    }
+
+    /// See [`ProposedTransaction::add_output_with_ephemeral_secret`]
+    pub fn add_output_with_ephemeral_secret(
+        &mut self,
+        note: Note,
+        ephemeral_secret: jubjub::Fr,
+    ) -> Result<(), IronfishError> {
+        self.proposed
+            .add_output_with_ephemeral_secret(note, ephemeral_secret)
+    }
+
+    pub fn add_mint(&mut self, asset: Asset, value: u64) -> Result<(), IronfishError> {
+        self.proposed.add_mint(asset, value)
+    }
+
+    /// See [`ProposedTransaction::add_mint_to`]
+    pub fn add_mint_to(
+        &mut self,
+        recipient: PublicAddress,
+        asset: Asset,
+        value: u64,
+    ) -> Result<(), IronfishError> {
+        self.proposed.add_mint_to(recipient, asset, value)
+    }
+
+    /// See [`ProposedTransaction::add_mints`]
+    pub fn add_mints(&mut self, requests: &[MintRequest]) -> Result<(), IronfishError> {
+        self.proposed.add_mints(requests)
+    }
+
+    pub fn add_burn(&mut self, asset_id: AssetIdentifier, value: u64) -> Result<(), IronfishError> {
+        self.proposed.add_burn(asset_id, value)
+    }
+
+    /// See [`ProposedTransaction::add_conversion`]
+    pub fn add_conversion(
+        &mut self,
+        conversion: &Conversion,
+        recipient: Option<PublicAddress>,
+    ) -> Result<(), IronfishError> {
+        self.proposed.add_conversion(conversion, recipient)
+    }
+
+    /// Set the sequence to expire the transaction from the mempool.
+    pub fn set_expiration(&mut self, sequence: u32) {
+        self.proposed.set_expiration(sequence)
+    }
+
+    /// See [`ProposedTransaction::set_memo_key`]
+    pub fn set_memo_key(&mut self, memo_key: &MemoKey) {
+        self.proposed.set_memo_key(memo_key)
+    }
+
+    /// See [`ProposedTransaction::set_memo_policy`]
+    pub fn set_memo_policy(&mut self, policy: Option<MemoPolicy>) {
+        self.proposed.set_memo_policy(policy)
+    }
+
+    /// Add change for everything spends and mints leave over after the
+    /// outputs, burns and fee, and sort the descriptions into their
+    /// canonical order, see [`super::ordering`]. Change goes back to the
+    /// spender unless `change_goes_to` is set.
+    pub fn balance(
+        mut self,
+        change_goes_to: Option<PublicAddress>,
+        intended_transaction_fee: u64,
+    ) -> Result<TransactionBuilder<Balanced>, IronfishError> {
+        self.proposed.check_burn_balances()?;
+        self.proposed
+            .add_change_notes(change_goes_to, intended_transaction_fee)?;
+        self.proposed.sort_descriptions();
+
+        Ok(TransactionBuilder {
+            proposed: self.proposed,
+            stage: Balanced,
+        })
+    }
+
+    /// Balance a miners fee transaction, which creates the value of its
+    /// single output instead of taking change. See
+    /// [`ProposedTransaction::post_miners_fee`].
+    pub fn balance_miners_fee(mut self) -> Result<TransactionBuilder<Balanced>, IronfishError> {
+        self.proposed.check_miners_fee()?;
+        for output in &mut self.proposed.outputs {
+            output.set_is_miners_fee();
+        }
+
+        Ok(TransactionBuilder {
+            proposed: self.proposed,
+            stage: Balanced,
+        })
+    }
+}
+
+impl TransactionBuilder<Balanced> {
+    /// The fee the transaction pays to the miner, negative for miners fee
+    /// transactions
+    pub fn fee(&self) -> i64 {
+        *self.proposed.value_balances.fee()
+    }
+
+    /// Build the proof of every description
+    pub fn prove(self) -> Result<TransactionBuilder<Proven>, IronfishError> {
+        let descriptions = self.proposed.prove()?;
+
+        Ok(TransactionBuilder {
+            proposed: self.proposed,
+            stage: Proven { descriptions },
+        })
+    }
+}
+
+impl TransactionBuilder<Proven> {
+    /// The hash the spends, mints and binding signature sign, which is also
+    /// the hash of the posted transaction
+    pub fn signature_hash(&self) -> [u8; 32] {
+        self.stage.descriptions.signature_hash
+    }
+
+    /// Sign the spends and mints, and bind the descriptions together
+    pub fn sign(self) -> Result<TransactionBuilder<Signed>, IronfishError> {
+        let transaction = self.proposed.sign(self.stage.descriptions)?;
+
+        Ok(TransactionBuilder {
+            proposed: self.proposed,
+            stage: Signed { transaction },
+        })
+    }
+}
+
+impl TransactionBuilder<Signed> {
+    pub fn transaction(&self) -> &Transaction {
+        &self.stage.transaction
+    }
+
+    pub fn into_transaction(self) -> Transaction {
+        self.stage.transaction
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TransactionBuilder;
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+
+    #[test]
+    fn test_builder_stages() {
+        let key = SaplingKey::generate_key();
+        let receiver = SaplingKey::generate_key().public_address();
+
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        // Start with the dynamic API and finish with the typed one
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(in_note, &witness).unwrap();
+        let mut builder = TransactionBuilder::from_proposed(proposed);
+        builder
+            .add_output(Note::new(
+                receiver,
+                40,
+                "",
+                NATIVE_ASSET,
+                key.public_address(),
+            ))
+            .unwrap();
+        builder.set_expiration(10);
+
+        let balanced = builder.balance(None, 1).unwrap();
+        assert_eq!(balanced.fee(), 1);
+
+        let proven = balanced.prove().unwrap();
+        let signature_hash = proven.signature_hash();
+
+        let transaction = proven.sign().unwrap().into_transaction();
+        transaction.verify().unwrap();
+        assert_eq!(transaction.transaction_signature_hash(), signature_hash);
+        assert_eq!(transaction.expiration(), 10);
+        // The payment and the change
+        assert_eq!(transaction.outputs().len(), 2);
+
+        // Outputs worth more than the spends can't be balanced
+        let mut builder = TransactionBuilder::new(key.clone());
+        builder
+            .add_output(Note::new(
+                receiver,
+                40,
+                "",
+                NATIVE_ASSET,
+                key.public_address(),
+            ))
+            .unwrap();
+        assert!(matches!(
+            builder.balance(None, 0),
+            Err(IronfishError::InvalidBalance)
+        ));
+    }
+
+    #[test]
+    fn test_builder_miners_fee() {
+        let key = SaplingKey::generate_key();
+
+        let mut builder = TransactionBuilder::new(key.clone());
+        builder
+            .add_output(Note::new(
+                key.public_address(),
+                20,
+                "",
+                NATIVE_ASSET,
+                key.public_address(),
+            ))
+            .unwrap();
+
+        let balanced = builder.balance_miners_fee().unwrap();
+        assert_eq!(balanced.fee(), -20);
+
+        let transaction = balanced.prove().unwrap().sign().unwrap().into_transaction();
+        transaction.verify().unwrap();
+        assert_eq!(transaction.fee(), -20);
+    }
+}
@@ -0,0 +1,338 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Receipts for burns, for bridges to show the light client or committee of
+//! another chain that value was destroyed on Iron Fish before releasing it
+//! there.
+//!
+//! A [`BurnReceipt`] carries the transaction, which burn of it the receipt
+//! is for, and the block the transaction is in: its header and a
+//! [`TransactionInclusionProof`] of the transaction's hash in it. It's signed
+//! with the randomized spend authorizing key the transaction itself was
+//! signed with, which only its spender can produce. That takes the
+//! randomness of the transaction's randomized public key, which the spender
+//! has while the [`ProposedTransaction`] is around, or can derive again if
+//! they built the transaction with a [`MemoKey`].
+//!
+//! [`BurnReceipt::verify_for_block`] needs nothing but the receipt and the
+//! hash of a block the verifier accepts, such as a light client following
+//! the headers of Iron Fish. Whether the transaction is valid is up to the
+//! chain that included it: the verifier doesn't check its proofs.
+
+use std::io;
+
+use blake2b_simd::Params as Blake2b;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use group::GroupEncoding;
+use ironfish_zkp::{constants::SPENDING_KEY_GENERATOR, redjubjub};
+
+use crate::{
+    assets::asset_identifier::AssetIdentifier,
+    block_inclusion::{BlockHeader, TransactionInclusionProof},
+    errors::IronfishError,
+    keys::{
+        owner_authorization::{blank_signature, sign_randomized, verify_randomized},
+        MemoKey, SaplingKey,
+    },
+    network::NetworkKind,
+    personalization::BURN_RECEIPT_PERSONALIZATION,
+};
+
+use super::{burns::BurnDescription, ProposedTransaction, Transaction};
+
+pub const BURN_RECEIPT_VERSION: u8 = 1;
+
+/// Proof that the spender of a transaction in a block burned value, signed
+/// by the spender. See the [module documentation](self).
+#[derive(Clone)]
+pub struct BurnReceipt {
+    /// Transaction the burn is in
+    pub transaction: Transaction,
+
+    /// Position of the burn among the burns of the transaction
+    pub burn_index: u32,
+
+    /// Header of the block the transaction is in
+    pub header: BlockHeader,
+
+    /// Proof that the transaction is in the block
+    pub inclusion_proof: TransactionInclusionProof,
+
+    /// Signature with the randomized public key of the transaction over
+    /// every other field
+    pub authorizing_signature: redjubjub::Signature,
+}
+
+impl BurnReceipt {
+    /// Sign a receipt for the burn at the given index of the transaction,
+    /// with the spender key and the randomness of the transaction's
+    /// randomized public key. Fails with [`IronfishError::InvalidSigningKey`]
+    /// if they didn't sign the transaction.
+    fn sign(
+        spender_key: &SaplingKey,
+        public_key_randomness: jubjub::Fr,
+        transaction: &Transaction,
+        burn_index: usize,
+        header: BlockHeader,
+        inclusion_proof: TransactionInclusionProof,
+    ) -> Result<BurnReceipt, IronfishError> {
+        let randomized_public_key =
+            redjubjub::PublicKey(spender_key.view_key.authorizing_key.into())
+                .randomize(public_key_randomness, SPENDING_KEY_GENERATOR);
+        if randomized_public_key.0.to_bytes() != transaction.randomized_public_key().0.to_bytes() {
+            return Err(IronfishError::InvalidSigningKey);
+        }
+
+        let mut receipt = BurnReceipt {
+            transaction: transaction.clone(),
+            burn_index: u32::try_from(burn_index)?,
+            header,
+            inclusion_proof,
+            authorizing_signature: blank_signature()?,
+        };
+
+        receipt.authorizing_signature = sign_randomized(
+            spender_key,
+            public_key_randomness,
+            &receipt.signature_hash()?,
+        )?;
+
+        receipt.verify_for_block(&header.hash())?;
+
+        Ok(receipt)
+    }
+
+    /// Sign a receipt for a burn of a transaction built with the given memo
+    /// key, see [`ProposedTransaction::set_memo_key`], once it's in a block
+    pub fn sign_with_memo_key(
+        spender_key: &SaplingKey,
+        memo_key: &MemoKey,
+        transaction: &Transaction,
+        burn_index: usize,
+        header: BlockHeader,
+        inclusion_proof: TransactionInclusionProof,
+    ) -> Result<BurnReceipt, IronfishError> {
+        BurnReceipt::sign(
+            spender_key,
+            memo_key.randomness,
+            transaction,
+            burn_index,
+            header,
+            inclusion_proof,
+        )
+    }
+
+    /// The burn the receipt is for. Fails with [`IronfishError::InvalidData`]
+    /// if the transaction has no burn at the index of the receipt.
+    pub fn burn(&self) -> Result<&BurnDescription, IronfishError> {
+        self.transaction
+            .burns()
+            .get(self.burn_index as usize)
+            .ok_or(IronfishError::InvalidData)
+    }
+
+    pub fn asset_id(&self) -> Result<AssetIdentifier, IronfishError> {
+        Ok(self.burn()?.asset_id)
+    }
+
+    pub fn value(&self) -> Result<u64, IronfishError> {
+        Ok(self.burn()?.value)
+    }
+
+    /// [`Transaction::hash`] of the transaction the burn is in
+    pub fn transaction_hash(&self) -> [u8; 32] {
+        self.transaction.hash()
+    }
+
+    /// Calculate a hash of the receipt. This is what the spender signs,
+    /// along with the randomized public key of the transaction.
+    pub fn signature_hash(&self) -> Result<[u8; 32], IronfishError> {
+        let burn = self.burn()?;
+
+        let mut hasher = Blake2b::new()
+            .hash_length(32)
+            .personal(BURN_RECEIPT_PERSONALIZATION)
+            .to_state();
+        hasher.update(&[BURN_RECEIPT_VERSION]);
+        hasher.update(&self.transaction_hash());
+        hasher.update(&self.burn_index.to_le_bytes());
+        hasher.update(burn.asset_id.as_bytes());
+        hasher.update(&burn.value.to_le_bytes());
+        hasher.update(&self.header.hash());
+
+        let mut hash_result = [0; 32];
+        hash_result[..].clone_from_slice(hasher.finalize().as_ref());
+        Ok(hash_result)
+    }
+
+    /// Confirm that the transaction burned the value of the receipt, that
+    /// it's in the block with the given hash, and that its spender signed the
+    /// receipt
+    pub fn verify_for_block(&self, block_hash: &[u8; 32]) -> Result<(), IronfishError> {
+        if self.inclusion_proof.transaction_hash != self.transaction_hash() {
+            return Err(IronfishError::InvalidInclusionProof);
+        }
+        self.inclusion_proof
+            .verify_header(&self.header, block_hash)?;
+
+        let randomized_public_key = self.transaction.randomized_public_key();
+        if randomized_public_key.0.is_small_order().into() {
+            return Err(IronfishError::IsSmallOrder);
+        }
+
+        verify_randomized(
+            randomized_public_key,
+            &self.signature_hash()?,
+            &self.authorizing_signature,
+        )
+    }
+
+    /// Load a [`BurnReceipt`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Self::read_for_network(reader, NetworkKind::Mainnet)
+    }
+
+    /// Load a [`BurnReceipt`] whose transaction belongs to the given network
+    pub fn read_for_network<R: io::Read>(
+        mut reader: R,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != BURN_RECEIPT_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let transaction = Transaction::read_for_network(&mut reader, network)?;
+        let burn_index = reader.read_u32::<LittleEndian>()?;
+        let header = BlockHeader::read(&mut reader)?;
+        let inclusion_proof = TransactionInclusionProof::read(&mut reader)?;
+        let authorizing_signature = redjubjub::Signature::read(&mut reader)?;
+
+        Ok(BurnReceipt {
+            transaction,
+            burn_index,
+            header,
+            inclusion_proof,
+            authorizing_signature,
+        })
+    }
+
+    /// Stow the bytes of this [`BurnReceipt`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(BURN_RECEIPT_VERSION)?;
+        self.transaction.write(&mut writer)?;
+        writer.write_u32::<LittleEndian>(self.burn_index)?;
+        self.header.write(&mut writer)?;
+        self.inclusion_proof.write(&mut writer)?;
+        self.authorizing_signature.write(&mut writer)?;
+
+        Ok(())
+    }
+}
+
+impl ProposedTransaction {
+    /// Sign a receipt for the burn at the given index of the transaction
+    /// this posted, once it's in a block, see [`BurnReceipt`]
+    pub fn burn_receipt(
+        &self,
+        transaction: &Transaction,
+        burn_index: usize,
+        header: BlockHeader,
+        inclusion_proof: TransactionInclusionProof,
+    ) -> Result<BurnReceipt, IronfishError> {
+        BurnReceipt::sign(
+            &self.spender_key,
+            self.public_key_randomness,
+            transaction,
+            burn_index,
+            header,
+            inclusion_proof,
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BurnReceipt;
+    use crate::{
+        assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+        block_inclusion::{BlockHeader, TransactionInclusionProof, BLOCK_HEADER_SIZE},
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::Note,
+        test_util::make_fake_witness,
+        ProposedTransaction, Transaction,
+    };
+
+    /// The header of a block holding only the given transaction
+    fn make_block(transaction: &Transaction) -> (BlockHeader, TransactionInclusionProof) {
+        let inclusion_proof =
+            TransactionInclusionProof::from_transactions(&[transaction.clone()], 0).unwrap();
+
+        let mut bytes = [0; BLOCK_HEADER_SIZE];
+        bytes[76..108].copy_from_slice(&inclusion_proof.root().unwrap());
+
+        (BlockHeader::new(bytes), inclusion_proof)
+    }
+
+    #[test]
+    fn test_burn_receipt() {
+        let key = SaplingKey::generate_key();
+        let asset = Asset::new(key.public_address(), "Bridged Coin", "").unwrap();
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+
+        let mut proposed = ProposedTransaction::new(key);
+        proposed
+            .add_spend(note.clone(), &make_fake_witness(&note))
+            .unwrap();
+        proposed.add_mint(asset, 5).unwrap();
+        proposed.add_burn(*asset.id(), 2).unwrap();
+        let transaction = proposed.post(None, 1).unwrap();
+        let (header, inclusion_proof) = make_block(&transaction);
+
+        let receipt = proposed
+            .burn_receipt(&transaction, 0, header, inclusion_proof.clone())
+            .unwrap();
+
+        let mut serialized = vec![];
+        receipt.write(&mut serialized).unwrap();
+        let receipt = BurnReceipt::read(&serialized[..]).unwrap();
+        assert_eq!(receipt.asset_id().unwrap(), *asset.id());
+        assert_eq!(receipt.value().unwrap(), 2);
+        receipt.verify_for_block(&header.hash()).unwrap();
+        assert!(matches!(
+            receipt.verify_for_block(&[0; 32]),
+            Err(IronfishError::InvalidInclusionProof)
+        ));
+
+        // The signature covers the burn the receipt is for
+        let mut tampered = receipt.clone();
+        tampered.transaction.burns[0].value = 3;
+        let (header, inclusion_proof) = make_block(&tampered.transaction);
+        tampered.header = header;
+        tampered.inclusion_proof = inclusion_proof;
+        assert!(matches!(
+            tampered.verify_for_block(&header.hash()),
+            Err(IronfishError::VerificationFailed)
+        ));
+
+        // Only the spender of the transaction can sign for its burns
+        let (header, inclusion_proof) = make_block(&transaction);
+        let other = ProposedTransaction::new(SaplingKey::generate_key());
+        assert!(matches!(
+            other.burn_receipt(&transaction, 0, header, inclusion_proof.clone()),
+            Err(IronfishError::InvalidSigningKey)
+        ));
+        assert!(matches!(
+            proposed.burn_receipt(&transaction, 1, header, inclusion_proof),
+            Err(IronfishError::InvalidData)
+        ));
+    }
+}
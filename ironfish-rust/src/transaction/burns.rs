@@ -6,7 +6,13 @@ use std::io;
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
-use crate::{assets::asset_identifier::AssetIdentifier, errors::IronfishError};
+use crate::{
+    assets::{asset::ID_LENGTH, asset_identifier::AssetIdentifier},
+    errors::IronfishError,
+};
+
+/// Size of a serialized [`BurnDescription`]: asset identifier and value.
+pub const BURN_DESCRIPTION_SIZE: usize = ID_LENGTH + 8;
 
 /// Parameters used to build a burn description
 pub struct BurnBuilder {
@@ -42,6 +48,14 @@ pub struct BurnDescription {
 }
 
 impl BurnDescription {
+    pub fn asset_id(&self) -> &AssetIdentifier {
+        &self.asset_id
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
     /// Write the signature of this proof to the provided writer.
     ///
     /// The signature is used by the transaction to calculate the signature
@@ -70,6 +84,14 @@ impl BurnDescription {
 
         Ok(())
     }
+
+    /// The serialized form of this [`BurnDescription`], as written by
+    /// [`BurnDescription::write`], without allocating.
+    pub fn to_bytes(&self) -> [u8; BURN_DESCRIPTION_SIZE] {
+        let mut bytes = [0; BURN_DESCRIPTION_SIZE];
+        self.write(&mut bytes[..]).unwrap();
+        bytes
+    }
 }
 
 #[cfg(test)]
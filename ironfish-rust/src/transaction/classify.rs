@@ -0,0 +1,152 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Classification of the notes an account can decrypt in a transaction, so
+//! that wallets don't count change coming back to the account as income.
+//!
+//! A note the account can decrypt with its incoming view key is its own, and
+//! one it can decrypt with its outgoing view key was created by it. Notes
+//! that are both went from the account to itself: they're change if the
+//! transaction also pays someone else or burns something, and self-transfers
+//! otherwise, for example when consolidating notes.
+
+use crate::{
+    keys::{IncomingViewKey, OutgoingViewKey},
+    note::Note,
+};
+
+use super::Transaction;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum NoteKind {
+    /// Sent to the account by someone else
+    Received,
+
+    /// Sent by the account to someone else
+    Sent,
+
+    /// Returned to the account by a transaction that pays someone else
+    Change,
+
+    /// Sent by the account to itself, in a transaction that pays no one else
+    SelfTransfer,
+}
+
+#[derive(Clone, Debug)]
+pub struct ClassifiedNote {
+    /// Index of the output the note was decrypted from
+    pub output: usize,
+    pub note: Note,
+    pub kind: NoteKind,
+}
+
+impl Transaction {
+    /// Decrypt every output the account with the given view keys can read,
+    /// and classify the notes. Outputs it can't read are left out.
+    pub fn classify_notes(
+        &self,
+        incoming_view_key: &IncomingViewKey,
+        outgoing_view_key: &OutgoingViewKey,
+    ) -> Vec<ClassifiedNote> {
+        let mut notes = vec![];
+        for (output, description) in self.outputs.iter().enumerate() {
+            let merkle_note = &description.merkle_note;
+            let received = merkle_note.decrypt_note_for_owner(incoming_view_key).ok();
+            let sent = merkle_note.decrypt_note_for_spender(outgoing_view_key).ok();
+
+            // Notes from the account to itself are sorted out below
+            let (note, kind) = match (received, sent) {
+                (Some(note), None) => (note, NoteKind::Received),
+                (None, Some(note)) => (note, NoteKind::Sent),
+                (Some(note), Some(_)) => (note, NoteKind::SelfTransfer),
+                (None, None) => continue,
+            };
+
+            notes.push(ClassifiedNote { output, note, kind });
+        }
+
+        let pays_out =
+            !self.burns.is_empty() || notes.iter().any(|note| note.kind == NoteKind::Sent);
+        if pays_out {
+            for note in notes.iter_mut() {
+                if note.kind == NoteKind::SelfTransfer {
+                    note.kind = NoteKind::Change;
+                }
+            }
+        }
+
+        notes
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ClassifiedNote, NoteKind};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, keys::SaplingKey, note::Note,
+        test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+
+    fn kinds(notes: &[ClassifiedNote]) -> Vec<(NoteKind, u64)> {
+        notes
+            .iter()
+            .map(|note| (note.kind, note.note.value()))
+            .collect()
+    }
+
+    #[test]
+    fn test_classify_notes() {
+        let key = SaplingKey::generate_key();
+        let receiver = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            receiver.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut transaction = ProposedTransaction::new(key.clone());
+        transaction.add_spend(note.clone(), &witness).unwrap();
+        transaction
+            .add_output(Note::new(
+                receiver.public_address(),
+                40,
+                "",
+                NATIVE_ASSET,
+                key.public_address(),
+            ))
+            .unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        let notes = posted.classify_notes(key.incoming_view_key(), key.outgoing_view_key());
+        assert_eq!(kinds(&notes), [(NoteKind::Sent, 40), (NoteKind::Change, 1)]);
+
+        let notes =
+            posted.classify_notes(receiver.incoming_view_key(), receiver.outgoing_view_key());
+        assert_eq!(kinds(&notes), [(NoteKind::Received, 40)]);
+
+        // Paying only itself, the change is part of the self-transfer
+        let mut transaction = ProposedTransaction::new(key.clone());
+        transaction.add_spend(note, &witness).unwrap();
+        transaction
+            .add_output(Note::new(
+                key.public_address(),
+                20,
+                "",
+                NATIVE_ASSET,
+                key.public_address(),
+            ))
+            .unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        let notes = posted.classify_notes(key.incoming_view_key(), key.outgoing_view_key());
+        assert_eq!(
+            kinds(&notes),
+            [(NoteKind::SelfTransfer, 20), (NoteKind::SelfTransfer, 21)]
+        );
+    }
+}
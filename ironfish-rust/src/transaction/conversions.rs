@@ -0,0 +1,176 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Conversions burn some of one asset and mint another in the same
+//! transaction, for example to migrate holders of a token to its
+//! replacement. Both happen or neither does, so a migration can't be left
+//! with the old tokens burned and the new ones never minted.
+
+use crate::{
+    assets::{asset::Asset, asset_identifier::AssetIdentifier},
+    errors::IronfishError,
+    PublicAddress,
+};
+
+use super::ProposedTransaction;
+
+/// Burn `burn_value` of one asset and mint `mint_value` of another
+#[derive(Clone, Copy, Debug)]
+pub struct Conversion {
+    pub burn_asset_id: AssetIdentifier,
+    pub burn_value: u64,
+
+    /// Asset minted in exchange, which the spender has to own
+    pub mint_asset: Asset,
+    pub mint_value: u64,
+}
+
+impl Conversion {
+    pub fn new(
+        burn_asset_id: AssetIdentifier,
+        burn_value: u64,
+        mint_asset: Asset,
+        mint_value: u64,
+    ) -> Self {
+        Conversion {
+            burn_asset_id,
+            burn_value,
+            mint_asset,
+            mint_value,
+        }
+    }
+
+    /// Check that the owner of the given address can make this conversion:
+    /// it has to own the minted asset, which has to be another asset than
+    /// the burned one, and both values have to be positive.
+    pub fn check(&self, owner: &PublicAddress) -> Result<(), IronfishError> {
+        if self.mint_asset.owner != *owner {
+            return Err(IronfishError::InvalidSigningKey);
+        }
+
+        if *self.mint_asset.id() == self.burn_asset_id
+            || self.burn_value == 0
+            || self.mint_value == 0
+        {
+            return Err(IronfishError::InvalidConversion);
+        }
+
+        Ok(())
+    }
+}
+
+impl ProposedTransaction {
+    /// Add the burn and the mint of a conversion, sending the minted value to
+    /// `recipient`, or to the change if there's none. Either both are added
+    /// or nothing is. The burned value still has to be spent, which is
+    /// checked when posting.
+    pub fn add_conversion(
+        &mut self,
+        conversion: &Conversion,
+        recipient: Option<PublicAddress>,
+    ) -> Result<(), IronfishError> {
+        conversion.check(&self.spender_key.public_address())?;
+
+        // Check every limit first, so that the conversion fits as a whole
+        self.limits.check_burns(self.burns.len() + 1)?;
+        self.limits.check_mints(self.mints.len() + 1)?;
+        if recipient.is_some() {
+            self.limits.check_outputs(self.outputs.len() + 1)?;
+        }
+
+        self.add_burn(conversion.burn_asset_id, conversion.burn_value)?;
+
+        let minted = match recipient {
+            Some(recipient) => {
+                self.add_mint_to(recipient, conversion.mint_asset, conversion.mint_value)
+            }
+            None => self.add_mint(conversion.mint_asset, conversion.mint_value),
+        };
+
+        if let Err(error) = minted {
+            // Undo the burn, which was checked to fit when it was added
+            self.burns.pop();
+            self.value_balances
+                .add(&conversion.burn_asset_id, conversion.burn_value as i64)?;
+            return Err(error);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Conversion;
+    use crate::{
+        assets::asset::Asset,
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::Note,
+        test_util::make_fake_witness,
+        transaction::{limits::Limits, ProposedTransaction},
+    };
+
+    #[test]
+    fn test_conversion() {
+        let issuer = SaplingKey::generate_key();
+        let holder = SaplingKey::generate_key().public_address();
+        let old_asset = Asset::new(issuer.public_address(), "old coin", "").unwrap();
+        let new_asset = Asset::new(issuer.public_address(), "new coin", "").unwrap();
+
+        let note = Note::new(
+            issuer.public_address(),
+            10,
+            "",
+            *old_asset.id(),
+            issuer.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let conversion = Conversion::new(*old_asset.id(), 10, new_asset, 20);
+        let mut transaction = ProposedTransaction::new(issuer.clone());
+        transaction.add_spend(note.clone(), &witness).unwrap();
+        transaction
+            .add_conversion(&conversion, Some(holder))
+            .unwrap();
+
+        let posted = transaction.post(None, 0).unwrap();
+        posted.verify().unwrap();
+        assert_eq!(posted.burns().len(), 1);
+        assert_eq!(posted.mints().len(), 1);
+        // Only the minted note, as all of the old asset is burned
+        assert_eq!(posted.outputs().len(), 1);
+
+        // Only the owner of the new asset can convert to it
+        let mut transaction = ProposedTransaction::new(SaplingKey::generate_key());
+        assert!(matches!(
+            transaction.add_conversion(&conversion, None),
+            Err(IronfishError::InvalidSigningKey)
+        ));
+
+        let same_asset = Conversion::new(*new_asset.id(), 10, new_asset, 20);
+        let mut transaction = ProposedTransaction::new(issuer.clone());
+        assert!(matches!(
+            transaction.add_conversion(&same_asset, None),
+            Err(IronfishError::InvalidConversion)
+        ));
+
+        // Nothing is added when the mint doesn't fit
+        let mut transaction = ProposedTransaction::with_limits(
+            issuer,
+            Limits {
+                max_mints: 0,
+                ..Limits::UNLIMITED
+            },
+        )
+        .unwrap();
+        assert!(matches!(
+            transaction.add_conversion(&conversion, None),
+            Err(IronfishError::TooManyMints)
+        ));
+        transaction.add_spend(note, &witness).unwrap();
+        let posted = transaction.post(None, 0).unwrap();
+        assert!(posted.burns().is_empty());
+    }
+}
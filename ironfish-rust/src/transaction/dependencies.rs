@@ -0,0 +1,208 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Relations between a set of transactions, for mempool and block assembly.
+//!
+//! Two transactions conflict when they publish the same nullifier, since only
+//! one of them can ever be mined. A transaction depends on another when it
+//! spends a note the other creates, so it can't be mined before it. Spends
+//! don't reveal which note they consume, so dependencies are only found for
+//! spends whose notes the caller knows, like a wallet's own transactions.
+
+use std::collections::{BTreeSet, HashMap};
+
+use ironfish_zkp::Nullifier;
+
+use super::Transaction;
+use crate::errors::IronfishError;
+
+/// Two transactions that spend the same note
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    /// Index of the earlier of the two transactions
+    pub first: usize,
+    pub second: usize,
+    pub nullifier: Nullifier,
+}
+
+/// Conflicts and dependencies between transactions, which are referred to by
+/// their index in the slice the graph was built from.
+pub struct DependencyGraph {
+    conflicts: Vec<Conflict>,
+
+    /// Indices of the transactions each transaction depends on
+    dependencies: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph {
+    /// Build the graph of the given transactions. `spent_notes` holds the
+    /// nullifiers of spends whose notes the caller decrypted, with the
+    /// commitment of each of those notes.
+    pub fn new(transactions: &[Transaction], spent_notes: &[(Nullifier, [u8; 32])]) -> Self {
+        let mut conflicts = vec![];
+        let mut spenders: HashMap<[u8; 32], usize> = HashMap::new();
+        for (index, transaction) in transactions.iter().enumerate() {
+            for spend in transaction.iter_spends() {
+                let nullifier = spend.nullifier();
+                match spenders.get(&nullifier.0) {
+                    Some(first) if *first != index => conflicts.push(Conflict {
+                        first: *first,
+                        second: index,
+                        nullifier,
+                    }),
+                    Some(_) => (),
+                    None => {
+                        spenders.insert(nullifier.0, index);
+                    }
+                }
+            }
+        }
+
+        let mut creators: HashMap<[u8; 32], usize> = HashMap::new();
+        for (index, transaction) in transactions.iter().enumerate() {
+            for output in transaction.iter_outputs() {
+                let commitment = output.merkle_note().merkle_hash().0.to_bytes();
+                creators.insert(commitment, index);
+            }
+        }
+
+        let spent_notes: HashMap<[u8; 32], [u8; 32]> = spent_notes
+            .iter()
+            .map(|(nullifier, commitment)| (nullifier.0, *commitment))
+            .collect();
+
+        let dependencies = transactions
+            .iter()
+            .enumerate()
+            .map(|(index, transaction)| {
+                let found: BTreeSet<usize> = transaction
+                    .iter_spends()
+                    .filter_map(|spend| spent_notes.get(&spend.nullifier().0))
+                    .filter_map(|commitment| creators.get(commitment).copied())
+                    .filter(|creator| *creator != index)
+                    .collect();
+                found.into_iter().collect()
+            })
+            .collect();
+
+        DependencyGraph {
+            conflicts,
+            dependencies,
+        }
+    }
+
+    /// Every pair of transactions that spend the same note. At most one
+    /// transaction of each pair can be included in a block.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Whether the transaction conflicts with any other
+    pub fn has_conflict(&self, index: usize) -> bool {
+        self.conflicts
+            .iter()
+            .any(|conflict| conflict.first == index || conflict.second == index)
+    }
+
+    /// Indices of the transactions that create notes the given transaction
+    /// spends, in increasing order. They have to be mined first.
+    pub fn dependencies(&self, index: usize) -> &[usize] {
+        &self.dependencies[index]
+    }
+
+    /// Indices of every transaction, ordered so each comes after the
+    /// transactions it depends on. Independent transactions keep their
+    /// relative order, so callers can pass transactions in priority order.
+    ///
+    /// Fails if the dependencies are circular, which can only happen if the
+    /// spent notes given to [`DependencyGraph::new`] were wrong.
+    pub fn ordering(&self) -> Result<Vec<usize>, IronfishError> {
+        let mut remaining: Vec<usize> = self.dependencies.iter().map(Vec::len).collect();
+        let mut dependents = vec![vec![]; self.dependencies.len()];
+        for (index, dependencies) in self.dependencies.iter().enumerate() {
+            for dependency in dependencies {
+                dependents[*dependency].push(index);
+            }
+        }
+
+        let mut ready: BTreeSet<usize> = (0..remaining.len())
+            .filter(|index| remaining[*index] == 0)
+            .collect();
+        let mut ordering = Vec::with_capacity(remaining.len());
+        while let Some(index) = ready.pop_first() {
+            ordering.push(index);
+            for dependent in dependents[index].iter() {
+                remaining[*dependent] -= 1;
+                if remaining[*dependent] == 0 {
+                    ready.insert(*dependent);
+                }
+            }
+        }
+
+        if ordering.len() != remaining.len() {
+            return Err(IronfishError::InvalidData);
+        }
+
+        Ok(ordering)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::DependencyGraph;
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, keys::SaplingKey, note::Note,
+        test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+
+    #[test]
+    fn test_dependency_graph() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(note.clone(), &witness).unwrap();
+        let first = proposed.post(None, 1).unwrap();
+
+        // Spends the same note
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(note, &witness).unwrap();
+        let double_spend = proposed.post(None, 2).unwrap();
+
+        // Spends the change of the first transaction
+        let change = first.outputs()[0]
+            .merkle_note()
+            .decrypt_note_for_owner(key.incoming_view_key())
+            .unwrap();
+        let change_witness = make_fake_witness(&change);
+        let mut proposed = ProposedTransaction::new(key.clone());
+        proposed.add_spend(change.clone(), &change_witness).unwrap();
+        let dependent = proposed.post(None, 1).unwrap();
+
+        let spent_notes = [(dependent.spends()[0].nullifier(), change.commitment())];
+        let transactions = vec![dependent, double_spend, first];
+        let graph = DependencyGraph::new(&transactions, &spent_notes);
+
+        assert_eq!(graph.conflicts().len(), 1);
+        assert_eq!(graph.conflicts()[0].first, 1);
+        assert_eq!(graph.conflicts()[0].second, 2);
+        assert!(!graph.has_conflict(0));
+
+        assert_eq!(graph.dependencies(0), &[2]);
+        assert!(graph.dependencies(2).is_empty());
+        assert_eq!(graph.ordering().unwrap(), vec![1, 2, 0]);
+
+        // Without knowing the spent note, the dependency can't be found
+        let graph = DependencyGraph::new(&transactions, &[]);
+        assert!(graph.dependencies(0).is_empty());
+        assert_eq!(graph.ordering().unwrap(), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,499 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A structured, human oriented description of a [`Transaction`], for use in
+//! CLIs and block explorers. Enable the `serde` feature to serialize reports,
+//! or use [`ToCanonicalJson`] for an encoding that can be hashed or signed.
+
+use std::collections::BTreeMap;
+
+use group::GroupEncoding;
+
+use crate::{
+    assets::asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+    canonical_json::{CanonicalObject, CanonicalValue, ToCanonicalJson},
+    keys::{IncomingViewKey, OutgoingViewKey},
+    merkle_note::MerkleNote,
+    note::Note,
+    serializing::bytes_to_hex,
+};
+
+use super::{verification::VerifyLevel, Transaction};
+
+/// How an output was decrypted while explaining a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum DecryptedWith {
+    /// Decrypted with an incoming view key: the note was received.
+    IncomingViewKey,
+
+    /// Decrypted with an outgoing view key: the note was sent.
+    OutgoingViewKey,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct NoteReport {
+    pub owner: String,
+    pub sender: String,
+    pub value: u64,
+    pub asset_id: String,
+    pub memo: String,
+    pub decrypted_with: DecryptedWith,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SpendReport {
+    pub nullifier: String,
+    pub root_hash: String,
+    pub tree_size: u32,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OutputReport {
+    pub note_commitment: String,
+
+    /// The decrypted note, if any of the supplied view keys could read it.
+    pub note: Option<NoteReport>,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct MintReport {
+    pub asset_id: String,
+    pub asset_name: String,
+    pub asset_owner: String,
+    pub value: u64,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct BurnReport {
+    pub asset_id: String,
+    pub value: u64,
+}
+
+/// Change in value for a single asset. Minted and burned amounts are public;
+/// received and sent only include outputs that could be decrypted.
+#[derive(Clone, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct AssetDelta {
+    pub asset_id: String,
+    pub minted: u128,
+    pub burned: u128,
+    pub received: u128,
+    pub sent: u128,
+}
+
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TransactionReport {
+    pub version: u8,
+    pub fee: i64,
+    pub expiration: u32,
+    pub randomized_public_key: String,
+    pub signature_hash: String,
+    pub spends: Vec<SpendReport>,
+    pub outputs: Vec<OutputReport>,
+    pub mints: Vec<MintReport>,
+    pub burns: Vec<BurnReport>,
+
+    /// Sorted by asset identifier, with the native asset always present.
+    pub asset_deltas: Vec<AssetDelta>,
+
+    pub verified: bool,
+
+    /// The reason verification failed, if it did.
+    pub verification_error: Option<String>,
+}
+
+impl Transaction {
+    /// Build a [`TransactionReport`] describing this transaction. Outputs are
+    /// decrypted with the first of the given keys that can read them, and
+    /// the transaction is fully verified.
+    pub fn explain(
+        &self,
+        incoming_view_keys: &[IncomingViewKey],
+        outgoing_view_keys: &[OutgoingViewKey],
+    ) -> TransactionReport {
+        let mut asset_deltas = BTreeMap::new();
+        delta_for(&mut asset_deltas, &NATIVE_ASSET);
+
+        let spends = self
+            .spends
+            .iter()
+            .map(|spend| SpendReport {
+                nullifier: bytes_to_hex(&spend.nullifier.0),
+                root_hash: bytes_to_hex(&spend.root_hash.to_bytes()),
+                tree_size: spend.tree_size,
+            })
+            .collect();
+
+        let mut outputs = Vec::with_capacity(self.outputs.len());
+        for output in self.outputs.iter() {
+            let decrypted =
+                decrypt_output(&output.merkle_note, incoming_view_keys, outgoing_view_keys);
+
+            let note = decrypted.map(|(note, decrypted_with)| {
+                let delta = delta_for(&mut asset_deltas, &note.asset_id);
+                match decrypted_with {
+                    DecryptedWith::IncomingViewKey => delta.received += note.value as u128,
+                    DecryptedWith::OutgoingViewKey => delta.sent += note.value as u128,
+                }
+
+                NoteReport {
+                    owner: note.owner.hex_public_address(),
+                    sender: note.sender.hex_public_address(),
+                    value: note.value,
+                    asset_id: bytes_to_hex(note.asset_id.as_bytes()),
+                    memo: trim_nulls(&note.memo.0),
+                    decrypted_with,
+                }
+            });
+
+            outputs.push(OutputReport {
+                note_commitment: bytes_to_hex(&output.merkle_note.note_commitment.to_bytes()),
+                note,
+            });
+        }
+
+        let mut mints = Vec::with_capacity(self.mints.len());
+        for mint in self.mints.iter() {
+            delta_for(&mut asset_deltas, mint.asset.id()).minted += mint.value as u128;
+
+            mints.push(MintReport {
+                asset_id: bytes_to_hex(mint.asset.id().as_bytes()),
+                asset_name: trim_nulls(mint.asset.name()),
+                asset_owner: bytes_to_hex(&mint.asset.owner()),
+                value: mint.value,
+            });
+        }
+
+        let mut burns = Vec::with_capacity(self.burns.len());
+        for burn in self.burns.iter() {
+            delta_for(&mut asset_deltas, &burn.asset_id).burned += burn.value as u128;
+
+            burns.push(BurnReport {
+                asset_id: bytes_to_hex(burn.asset_id.as_bytes()),
+                value: burn.value,
+            });
+        }
+
+        let verification = self.verify_with_level(VerifyLevel::Full);
+
+        TransactionReport {
+            version: self.version,
+            fee: self.fee,
+            expiration: self.expiration,
+            randomized_public_key: bytes_to_hex(&self.randomized_public_key.0.to_bytes()),
+            signature_hash: bytes_to_hex(&self.transaction_signature_hash()),
+            spends,
+            outputs,
+            mints,
+            burns,
+            asset_deltas: asset_deltas.into_values().collect(),
+            verified: verification.is_ok(),
+            verification_error: verification.err().map(|e| e.to_string()),
+        }
+    }
+}
+
+impl ToCanonicalJson for DecryptedWith {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        match self {
+            DecryptedWith::IncomingViewKey => "IncomingViewKey".into(),
+            DecryptedWith::OutgoingViewKey => "OutgoingViewKey".into(),
+        }
+    }
+}
+
+impl ToCanonicalJson for NoteReport {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("owner", self.owner.as_str())
+            .field("sender", self.sender.as_str())
+            .field("value", self.value)
+            .field("asset_id", self.asset_id.as_str())
+            .field("memo", self.memo.as_str())
+            .field("decrypted_with", self.decrypted_with.to_canonical_value())
+            .build()
+    }
+}
+
+impl ToCanonicalJson for SpendReport {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("nullifier", self.nullifier.as_str())
+            .field("root_hash", self.root_hash.as_str())
+            .field("tree_size", self.tree_size)
+            .build()
+    }
+}
+
+impl ToCanonicalJson for OutputReport {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("note_commitment", self.note_commitment.as_str())
+            .field(
+                "note",
+                self.note.as_ref().map(ToCanonicalJson::to_canonical_value),
+            )
+            .build()
+    }
+}
+
+impl ToCanonicalJson for MintReport {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("asset_id", self.asset_id.as_str())
+            .field("asset_name", self.asset_name.as_str())
+            .field("asset_owner", self.asset_owner.as_str())
+            .field("value", self.value)
+            .build()
+    }
+}
+
+impl ToCanonicalJson for BurnReport {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("asset_id", self.asset_id.as_str())
+            .field("value", self.value)
+            .build()
+    }
+}
+
+impl ToCanonicalJson for AssetDelta {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("asset_id", self.asset_id.as_str())
+            .field("minted", self.minted)
+            .field("burned", self.burned)
+            .field("received", self.received)
+            .field("sent", self.sent)
+            .build()
+    }
+}
+
+impl ToCanonicalJson for TransactionReport {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("version", self.version)
+            .field("fee", self.fee)
+            .field("expiration", self.expiration)
+            .field("randomized_public_key", self.randomized_public_key.as_str())
+            .field("signature_hash", self.signature_hash.as_str())
+            .field("spends", canonical_values(&self.spends))
+            .field("outputs", canonical_values(&self.outputs))
+            .field("mints", canonical_values(&self.mints))
+            .field("burns", canonical_values(&self.burns))
+            .field("asset_deltas", canonical_values(&self.asset_deltas))
+            .field("verified", self.verified)
+            .field("verification_error", self.verification_error.clone())
+            .build()
+    }
+}
+
+fn canonical_values<T: ToCanonicalJson>(values: &[T]) -> Vec<CanonicalValue> {
+    values
+        .iter()
+        .map(ToCanonicalJson::to_canonical_value)
+        .collect()
+}
+
+fn delta_for<'a>(
+    asset_deltas: &'a mut BTreeMap<[u8; 32], AssetDelta>,
+    asset_id: &AssetIdentifier,
+) -> &'a mut AssetDelta {
+    asset_deltas
+        .entry(*asset_id.as_bytes())
+        .or_insert_with(|| AssetDelta {
+            asset_id: bytes_to_hex(asset_id.as_bytes()),
+            ..AssetDelta::default()
+        })
+}
+
+fn decrypt_output(
+    merkle_note: &MerkleNote,
+    incoming_view_keys: &[IncomingViewKey],
+    outgoing_view_keys: &[OutgoingViewKey],
+) -> Option<(Note, DecryptedWith)> {
+    for key in incoming_view_keys {
+        if let Ok(note) = merkle_note.decrypt_note_for_owner(key) {
+            return Some((note, DecryptedWith::IncomingViewKey));
+        }
+    }
+
+    for key in outgoing_view_keys {
+        if let Ok(note) = merkle_note.decrypt_note_for_spender(key) {
+            return Some((note, DecryptedWith::OutgoingViewKey));
+        }
+    }
+
+    None
+}
+
+/// Fixed size text fields are padded with zeroes, which are noise in a report.
+pub(super) fn trim_nulls(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\0')
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        AssetDelta, BurnReport, DecryptedWith, NoteReport, OutputReport, SpendReport,
+        TransactionReport,
+    };
+    use crate::{
+        assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+        canonical_json::ToCanonicalJson,
+        keys::SaplingKey,
+        note::Note,
+        serializing::bytes_to_hex,
+        test_util::make_fake_witness,
+        transaction::ProposedTransaction,
+    };
+
+    #[test]
+    fn test_explain_transaction() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+        let third_party_key = SaplingKey::generate_key();
+
+        let in_note = Note::new(
+            spender_key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            third_party_key.public_address(),
+        );
+        let out_note = Note::new(
+            receiver_key.public_address(),
+            40,
+            "for the pizza",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+        let asset = Asset::new(spender_key.public_address(), "Testcoin", "").unwrap();
+
+        let mut transaction = ProposedTransaction::new(spender_key.clone());
+        transaction.add_spend(in_note, &witness).unwrap();
+        transaction.add_output(out_note).unwrap();
+        transaction.add_mint(asset, 5).unwrap();
+        transaction.add_burn(asset.id, 2).unwrap();
+        transaction.set_expiration(10);
+
+        let posted = transaction.post(None, 1).unwrap();
+
+        // The receiver can only read their own output
+        let report = posted.explain(&[receiver_key.incoming_view_key().clone()], &[]);
+        assert!(report.verified);
+        assert!(report.verification_error.is_none());
+        assert_eq!(report.fee, 1);
+        assert_eq!(report.expiration, 10);
+        assert_eq!(report.spends.len(), 1);
+        assert_eq!(report.outputs.len(), 3);
+        assert_eq!(report.mints[0].asset_name, "Testcoin");
+        assert_eq!(report.burns[0].value, 2);
+
+        let decrypted: Vec<_> = report
+            .outputs
+            .iter()
+            .filter_map(|o| o.note.as_ref())
+            .collect();
+        assert_eq!(decrypted.len(), 1);
+        assert_eq!(decrypted[0].value, 40);
+        assert_eq!(decrypted[0].memo, "for the pizza");
+        assert_eq!(decrypted[0].decrypted_with, DecryptedWith::IncomingViewKey);
+
+        // The spender can read everything they sent
+        let report = posted.explain(&[], &[spender_key.outgoing_view_key().clone()]);
+        assert!(report.outputs.iter().all(|o| o.note.is_some()));
+
+        let native_delta = report
+            .asset_deltas
+            .iter()
+            .find(|d| d.asset_id == bytes_to_hex(NATIVE_ASSET.as_bytes()))
+            .unwrap();
+        assert_eq!(native_delta.sent, 41);
+
+        let asset_delta = report
+            .asset_deltas
+            .iter()
+            .find(|d| d.asset_id == bytes_to_hex(asset.id().as_bytes()))
+            .unwrap();
+        assert_eq!(asset_delta.minted, 5);
+        assert_eq!(asset_delta.burned, 2);
+        assert_eq!(asset_delta.sent, 3);
+    }
+
+    #[test]
+    fn test_canonical_report_golden() {
+        let report = TransactionReport {
+            version: 2,
+            fee: -5,
+            expiration: 0,
+            randomized_public_key: "0a".repeat(2),
+            signature_hash: "ff".to_string(),
+            spends: vec![SpendReport {
+                nullifier: "01".to_string(),
+                root_hash: "02".to_string(),
+                tree_size: 1400,
+            }],
+            outputs: vec![
+                OutputReport {
+                    note_commitment: "03".to_string(),
+                    note: None,
+                },
+                OutputReport {
+                    note_commitment: "04".to_string(),
+                    note: Some(NoteReport {
+                        owner: "05".to_string(),
+                        sender: "06".to_string(),
+                        value: 40,
+                        asset_id: "07".to_string(),
+                        memo: "rent \"march\"\n".to_string(),
+                        decrypted_with: DecryptedWith::OutgoingViewKey,
+                    }),
+                },
+            ],
+            mints: vec![],
+            burns: vec![BurnReport {
+                asset_id: "07".to_string(),
+                value: 2,
+            }],
+            asset_deltas: vec![AssetDelta {
+                asset_id: "07".to_string(),
+                minted: 0,
+                burned: 2,
+                received: 0,
+                sent: u64::MAX as u128 + 1,
+            }],
+            verified: false,
+            verification_error: Some("InvalidSpendSignature".to_string()),
+        };
+
+        assert_eq!(
+            report.to_canonical_json(),
+            concat!(
+                r#"{"asset_deltas":[{"asset_id":"07","burned":2,"minted":0,"received":0,"#,
+                r#""sent":18446744073709551616}],"#,
+                r#""burns":[{"asset_id":"07","value":2}],"#,
+                r#""expiration":0,"fee":-5,"mints":[],"#,
+                r#""outputs":[{"note":null,"note_commitment":"03"},"#,
+                r#"{"note":{"asset_id":"07","decrypted_with":"OutgoingViewKey","#,
+                r#""memo":"rent \"march\"\n","owner":"05","sender":"06","value":40},"#,
+                r#""note_commitment":"04"}],"#,
+                r#""randomized_public_key":"0a0a","signature_hash":"ff","#,
+                r#""spends":[{"nullifier":"01","root_hash":"02","tree_size":1400}],"#,
+                r#""verification_error":"InvalidSpendSignature","verified":false,"#,
+                r#""version":2}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,298 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Auxiliary data attached to a transaction as key-value entries, so that
+//! new features can carry their own data without changing the transaction
+//! format again.
+//!
+//! Transactions carry the entries from [`EXTRA_DATA_TRANSACTION_VERSION`]
+//! on, between the burns and the binding signature, and the signature hash
+//! covers them. Verifiers don't interpret entries, so one whose key they
+//! don't know is accepted and left alone: a feature that gives meaning to a
+//! new key only restricts which transactions are valid, as a soft fork.
+//!
+//! Entries are written sorted by key, each key at most once, so a set of
+//! entries has a single encoding:
+//! - the number of entries, as a u16
+//! - for every entry, its key and the length of its value as u16s, followed
+//!   by the value
+
+use std::{collections::BTreeMap, io};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::errors::IronfishError;
+
+use super::{Transaction, EXTRA_DATA_TRANSACTION_VERSION};
+
+/// Maximum size of the serialized extra data of a transaction, in bytes
+pub const MAX_EXTRA_DATA_SIZE: usize = 1024;
+
+/// Bytes taken by the number of entries
+pub(crate) const ENTRY_COUNT_SIZE: usize = 2;
+
+/// Bytes taken by the key and length of an entry
+pub(crate) const ENTRY_HEADER_SIZE: usize = 4;
+
+/// A value that can be stored in [`ExtraData`] under its own key. Every
+/// feature using extra data implements this for its value, with a key no
+/// other feature uses.
+pub trait ExtraDataEntry: Sized {
+    const KEY: u16;
+
+    /// Stow the bytes of this entry in the given writer.
+    fn write<W: io::Write>(&self, writer: W) -> Result<(), IronfishError>;
+
+    /// Load an entry from the bytes written by [`ExtraDataEntry::write`].
+    /// All of them have to be read.
+    fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError>;
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtraData {
+    entries: BTreeMap<u16, Vec<u8>>,
+}
+
+impl ExtraData {
+    pub fn new() -> Self {
+        ExtraData::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The keys of the entries, in ascending order
+    pub fn keys(&self) -> impl Iterator<Item = u16> + '_ {
+        self.entries.keys().copied()
+    }
+
+    /// Store the entry, replacing any entry of the same type. Fails with
+    /// [`IronfishError::ExtraDataTooLarge`], leaving the extra data as it
+    /// was, if it wouldn't fit.
+    pub fn insert<T: ExtraDataEntry>(&mut self, entry: &T) -> Result<(), IronfishError> {
+        let mut value = vec![];
+        entry.write(&mut value)?;
+        self.insert_raw(T::KEY, value)
+    }
+
+    /// The entry of the given type, if there's one
+    pub fn get<T: ExtraDataEntry>(&self) -> Result<Option<T>, IronfishError> {
+        let mut value = match self.get_raw(T::KEY) {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+
+        let entry = T::read(&mut value)?;
+        if !value.is_empty() {
+            return Err(IronfishError::InvalidData);
+        }
+
+        Ok(Some(entry))
+    }
+
+    /// Remove the entry of the given type, returning whether there was one
+    pub fn remove<T: ExtraDataEntry>(&mut self) -> bool {
+        self.entries.remove(&T::KEY).is_some()
+    }
+
+    /// Store an uninterpreted value under the given key, see
+    /// [`ExtraData::insert`]
+    pub fn insert_raw(&mut self, key: u16, value: Vec<u8>) -> Result<(), IronfishError> {
+        let replaced = self.entries.get(&key).map_or(0, |value| entry_size(value));
+        let size = (self.serialized_size() - replaced).checked_add(entry_size(&value));
+        match size {
+            Some(size) if size <= MAX_EXTRA_DATA_SIZE => {
+                self.entries.insert(key, value);
+                Ok(())
+            }
+            _ => Err(IronfishError::ExtraDataTooLarge),
+        }
+    }
+
+    /// The uninterpreted value stored under the given key
+    pub fn get_raw(&self, key: u16) -> Option<&[u8]> {
+        self.entries.get(&key).map(|value| &value[..])
+    }
+
+    /// Size in bytes of these entries, as written by [`ExtraData::write`]
+    pub fn serialized_size(&self) -> usize {
+        ENTRY_COUNT_SIZE
+            + self
+                .entries
+                .values()
+                .map(|value| entry_size(value))
+                .sum::<usize>()
+    }
+
+    /// Load [`ExtraData`] from a Read implementation (e.g: socket, file),
+    /// refusing to read more than [`MAX_EXTRA_DATA_SIZE`] bytes.
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let count = read_entry_count(&mut reader)?;
+        let mut size = ENTRY_COUNT_SIZE;
+        let mut entries = BTreeMap::new();
+        let mut previous_key = None;
+
+        for _ in 0..count {
+            let (key, length) = read_entry_header(&mut reader, &mut size)?;
+            if previous_key.map_or(false, |previous| key <= previous) {
+                return Err(IronfishError::InvalidData);
+            }
+            previous_key = Some(key);
+
+            let mut value = vec![0; length];
+            reader.read_exact(&mut value)?;
+            entries.insert(key, value);
+        }
+
+        Ok(ExtraData { entries })
+    }
+
+    /// Stow the bytes of this [`ExtraData`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u16::<LittleEndian>(u16::try_from(self.entries.len())?)?;
+        for (key, value) in self.entries.iter() {
+            writer.write_u16::<LittleEndian>(*key)?;
+            writer.write_u16::<LittleEndian>(u16::try_from(value.len())?)?;
+            writer.write_all(value)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn entry_size(value: &[u8]) -> usize {
+    ENTRY_HEADER_SIZE + value.len()
+}
+
+/// Read the number of entries, the first [`ENTRY_COUNT_SIZE`] bytes of the
+/// extra data
+pub(crate) fn read_entry_count<R: io::Read>(mut reader: R) -> Result<u16, IronfishError> {
+    Ok(reader.read_u16::<LittleEndian>()?)
+}
+
+/// Read the key and the length of the value of the next entry, adding the
+/// size of the entry to `size`, the bytes of extra data read so far. Fails
+/// with [`IronfishError::ExtraDataTooLarge`] if the entry doesn't fit.
+pub(crate) fn read_entry_header<R: io::Read>(
+    mut reader: R,
+    size: &mut usize,
+) -> Result<(u16, usize), IronfishError> {
+    let key = reader.read_u16::<LittleEndian>()?;
+    let length = reader.read_u16::<LittleEndian>()? as usize;
+
+    *size += ENTRY_HEADER_SIZE + length;
+    if *size > MAX_EXTRA_DATA_SIZE {
+        return Err(IronfishError::ExtraDataTooLarge);
+    }
+
+    Ok((key, length))
+}
+
+/// Whether transactions of the given version carry extra data
+pub(crate) fn has_extra_data(version: u8) -> bool {
+    version >= EXTRA_DATA_TRANSACTION_VERSION
+}
+
+impl Transaction {
+    /// The auxiliary data attached to this transaction, empty for versions
+    /// before [`EXTRA_DATA_TRANSACTION_VERSION`]
+    pub fn extra_data(&self) -> &ExtraData {
+        &self.extra_data
+    }
+
+    /// Bytes taken by the extra data in this transaction's serialization
+    pub(crate) fn extra_data_size(&self) -> usize {
+        match has_extra_data(self.version) {
+            true => self.extra_data.serialized_size(),
+            false => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ExtraData, ExtraDataEntry, MAX_EXTRA_DATA_SIZE};
+    use crate::errors::IronfishError;
+    use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+    use std::io;
+
+    #[derive(Debug, PartialEq)]
+    struct Invoice(u64);
+
+    impl ExtraDataEntry for Invoice {
+        const KEY: u16 = 7;
+
+        fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+            writer.write_u64::<LittleEndian>(self.0)?;
+            Ok(())
+        }
+
+        fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+            Ok(Invoice(reader.read_u64::<LittleEndian>()?))
+        }
+    }
+
+    #[test]
+    fn test_extra_data_round_trip() {
+        let mut extra_data = ExtraData::new();
+        assert_eq!(extra_data.get::<Invoice>().unwrap(), None);
+
+        extra_data.insert(&Invoice(42)).unwrap();
+        extra_data.insert_raw(3, b"unknown".to_vec()).unwrap();
+        assert_eq!(extra_data.get::<Invoice>().unwrap(), Some(Invoice(42)));
+        assert_eq!(extra_data.keys().collect::<Vec<_>>(), [3, 7]);
+
+        let mut serialized = vec![];
+        extra_data.write(&mut serialized).unwrap();
+        assert_eq!(serialized.len(), extra_data.serialized_size());
+        assert_eq!(
+            hex::encode(&serialized),
+            "020003000700756e6b6e6f776e070008002a00000000000000"
+        );
+
+        let read_back = ExtraData::read(&serialized[..]).unwrap();
+        assert_eq!(read_back, extra_data);
+
+        // Keys out of order, or repeated, would give the same entries
+        // another encoding
+        let swapped = hex::decode("02000700000003000000").unwrap();
+        assert!(matches!(
+            ExtraData::read(&swapped[..]),
+            Err(IronfishError::InvalidData)
+        ));
+
+        // A value of the wrong length for its type can't be read as it
+        extra_data.insert_raw(Invoice::KEY, vec![1, 2, 3]).unwrap();
+        assert!(extra_data.get::<Invoice>().is_err());
+        assert!(extra_data.remove::<Invoice>());
+        assert_eq!(extra_data.keys().collect::<Vec<_>>(), [3]);
+    }
+
+    #[test]
+    fn test_extra_data_size_limit() {
+        let mut extra_data = ExtraData::new();
+        let fits = MAX_EXTRA_DATA_SIZE - extra_data.serialized_size() - 4;
+        extra_data.insert_raw(1, vec![0; fits]).unwrap();
+        assert_eq!(extra_data.serialized_size(), MAX_EXTRA_DATA_SIZE);
+
+        assert!(matches!(
+            extra_data.insert_raw(2, vec![]),
+            Err(IronfishError::ExtraDataTooLarge)
+        ));
+        assert_eq!(extra_data.keys().collect::<Vec<_>>(), [1]);
+
+        // Replacing an entry only counts the new value
+        extra_data.insert_raw(1, vec![0; fits - 4]).unwrap();
+        extra_data.insert_raw(2, vec![]).unwrap();
+
+        let mut serialized = vec![];
+        extra_data.write(&mut serialized).unwrap();
+        serialized[2 + 2] += 1;
+        assert!(matches!(
+            ExtraData::read(&serialized[..]),
+            Err(IronfishError::ExtraDataTooLarge)
+        ));
+    }
+}
@@ -0,0 +1,487 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Bulk creation of transactions for services that pay out many requests at
+//! once, such as exchange withdrawals.
+//!
+//! A [`TransactionFactory`] owns a pool of spendable notes. Each run takes a
+//! queue of [`PaymentRequest`]s, packs as many of them as fit within the
+//! limits into each transaction, funds every transaction from the pool and
+//! proves them all in parallel. A request that can't be paid doesn't hold up
+//! the rest of the queue: it gets its own [`PaymentStatus`], and if posting a
+//! transaction fails, its requests are retried one per transaction so that a
+//! single bad request only fails itself.
+
+use std::collections::HashMap;
+
+use rayon::prelude::*;
+
+use crate::{
+    assets::asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+    errors::IronfishError,
+    note::{Note, MEMO_SIZE},
+    spendable_note::SpendableNoteRecord,
+    PublicAddress, SaplingKey,
+};
+
+use super::{
+    fee::{DescriptionCounts, FeePolicy},
+    limits::Limits,
+    ProposedTransaction, Transaction,
+};
+
+/// A payment to make, identified by the caller's own id
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaymentRequest {
+    pub id: u64,
+    pub recipient: PublicAddress,
+    pub amount: u64,
+    pub asset_id: AssetIdentifier,
+    pub memo: String,
+}
+
+/// What became of a [`PaymentRequest`]
+#[derive(Debug)]
+pub enum PaymentStatus {
+    /// Paid by the transaction at this index of [`FactoryRun::transactions`]
+    Posted { transaction: usize },
+
+    /// Not paid. The notes set aside for it are back in the pool.
+    Failed(IronfishError),
+}
+
+#[derive(Debug)]
+pub struct PaymentResult {
+    pub request_id: u64,
+    pub status: PaymentStatus,
+}
+
+/// The outcome of [`TransactionFactory::run`]
+pub struct FactoryRun {
+    pub transactions: Vec<Transaction>,
+
+    /// One result for every request, in the order of the queue
+    pub results: Vec<PaymentResult>,
+}
+
+/// Requests paid by one transaction and the notes funding them
+struct Batch {
+    requests: Vec<usize>,
+    notes: Vec<usize>,
+    fee: u64,
+}
+
+/// Creates transactions for queues of payment requests, funded from a shared
+/// pool of notes
+pub struct TransactionFactory {
+    spender_key: SaplingKey,
+    notes: Vec<SpendableNoteRecord>,
+    fee_policy: Box<dyn FeePolicy>,
+    limits: Limits,
+    max_payments_per_transaction: usize,
+    change_goes_to: Option<PublicAddress>,
+    expiration: u32,
+}
+
+impl TransactionFactory {
+    pub fn new(spender_key: SaplingKey, fee_policy: Box<dyn FeePolicy>) -> Self {
+        TransactionFactory {
+            spender_key,
+            notes: vec![],
+            fee_policy,
+            limits: Limits::UNLIMITED,
+            max_payments_per_transaction: usize::MAX,
+            change_goes_to: None,
+            expiration: 0,
+        }
+    }
+
+    /// Keep every transaction within the limits of the network
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Pay at most this many requests in a single transaction, for example
+    /// to keep each one quick to prove
+    pub fn set_max_payments_per_transaction(&mut self, max: usize) {
+        self.max_payments_per_transaction = max.max(1);
+    }
+
+    /// Send change here instead of back to the spender
+    pub fn set_change_goes_to(&mut self, change_goes_to: Option<PublicAddress>) {
+        self.change_goes_to = change_goes_to;
+    }
+
+    /// Expiration sequence of every transaction created
+    pub fn set_expiration(&mut self, sequence: u32) {
+        self.expiration = sequence;
+    }
+
+    /// Add a note to the pool the requests are funded from
+    pub fn add_note(&mut self, record: SpendableNoteRecord) {
+        self.notes.push(record);
+    }
+
+    /// Notes in the pool that haven't been spent by a run yet
+    pub fn notes(&self) -> &[SpendableNoteRecord] {
+        &self.notes
+    }
+
+    /// Pay as many of the requests as possible, in as few transactions as
+    /// the limits allow. Requests are packed in the order of the queue, and
+    /// notes are spent largest first.
+    ///
+    /// Notes spent by the posted transactions leave the pool. Their change
+    /// doesn't join it, since it can't be spent until it's on the chain and
+    /// has a witness.
+    pub fn run(&mut self, requests: &[PaymentRequest]) -> FactoryRun {
+        let mut statuses: Vec<Option<PaymentStatus>> = requests.iter().map(|_| None).collect();
+        let mut reserved = vec![false; self.notes.len()];
+
+        let mut order: Vec<usize> = (0..self.notes.len()).collect();
+        order.sort_by(|a, b| {
+            self.notes[*b]
+                .note
+                .value()
+                .cmp(&self.notes[*a].note.value())
+        });
+
+        let mut pending = vec![];
+        for (index, request) in requests.iter().enumerate() {
+            match request.memo.len() > MEMO_SIZE {
+                true => statuses[index] = Some(PaymentStatus::Failed(IronfishError::InvalidData)),
+                false => pending.push(index),
+            }
+        }
+
+        let mut transactions = vec![];
+        let mut max_payments = self.max_payments_per_transaction;
+        while !pending.is_empty() {
+            let batches = self.plan(
+                requests,
+                &pending,
+                max_payments,
+                &order,
+                &mut reserved,
+                &mut statuses,
+            );
+            pending = vec![];
+
+            for (batch, posted) in batches.iter().zip(self.post(requests, &batches)) {
+                match posted {
+                    Ok(transaction) => {
+                        for &index in batch.requests.iter() {
+                            statuses[index] = Some(PaymentStatus::Posted {
+                                transaction: transactions.len(),
+                            });
+                        }
+                        transactions.push(transaction);
+                    }
+                    Err(error) => {
+                        for &index in batch.notes.iter() {
+                            reserved[index] = false;
+                        }
+
+                        match batch.requests.as_slice() {
+                            &[index] => statuses[index] = Some(PaymentStatus::Failed(error)),
+                            _ => pending.extend(batch.requests.iter().copied()),
+                        }
+                    }
+                }
+            }
+
+            // Whatever failed together is retried alone
+            pending.sort_unstable();
+            max_payments = 1;
+        }
+
+        let mut index = 0;
+        self.notes.retain(|_| {
+            index += 1;
+            !reserved[index - 1]
+        });
+
+        let results = requests
+            .iter()
+            .zip(statuses)
+            .map(|(request, status)| PaymentResult {
+                request_id: request.id,
+                status: status.expect("every request is posted or failed"),
+            })
+            .collect();
+
+        FactoryRun {
+            transactions,
+            results,
+        }
+    }
+
+    /// Pack the pending requests into batches, reserving the notes that
+    /// fund each of them. Requests that can't be funded even on their own
+    /// are failed.
+    fn plan(
+        &self,
+        requests: &[PaymentRequest],
+        pending: &[usize],
+        max_payments: usize,
+        order: &[usize],
+        reserved: &mut [bool],
+        statuses: &mut [Option<PaymentStatus>],
+    ) -> Vec<Batch> {
+        let mut batches = vec![];
+        let mut current: Option<Batch> = None;
+
+        for &index in pending {
+            if let Some(batch) = current.as_mut() {
+                if batch.requests.len() < max_payments {
+                    let mut candidate = batch.requests.clone();
+                    candidate.push(index);
+
+                    if let Ok((notes, fee)) = self.fund(requests, &candidate, order, reserved) {
+                        *batch = Batch {
+                            requests: candidate,
+                            notes,
+                            fee,
+                        };
+                        continue;
+                    }
+                }
+
+                let batch = current.take().unwrap();
+                for &note in batch.notes.iter() {
+                    reserved[note] = true;
+                }
+                batches.push(batch);
+            }
+
+            match self.fund(requests, &[index], order, reserved) {
+                Ok((notes, fee)) => {
+                    current = Some(Batch {
+                        requests: vec![index],
+                        notes,
+                        fee,
+                    })
+                }
+                Err(error) => statuses[index] = Some(PaymentStatus::Failed(error)),
+            }
+        }
+
+        if let Some(batch) = current {
+            for &note in batch.notes.iter() {
+                reserved[note] = true;
+            }
+            batches.push(batch);
+        }
+
+        batches
+    }
+
+    /// Choose unreserved notes paying for the given requests and the fee of
+    /// the transaction, returning them with the fee. The fee assumes a
+    /// change note for every asset, so it can be a little higher than the
+    /// policy requires.
+    fn fund(
+        &self,
+        requests: &[PaymentRequest],
+        indices: &[usize],
+        order: &[usize],
+        reserved: &[bool],
+    ) -> Result<(Vec<usize>, u64), IronfishError> {
+        let mut amounts: HashMap<AssetIdentifier, u64> = HashMap::new();
+        amounts.insert(NATIVE_ASSET, 0);
+        for &index in indices {
+            let amount = amounts.entry(requests[index].asset_id).or_insert(0);
+            *amount = amount
+                .checked_add(requests[index].amount)
+                .ok_or(IronfishError::InvalidBalance)?;
+        }
+
+        let outputs = indices.len() + amounts.len();
+        self.limits.check_outputs(outputs)?;
+
+        let mut counts = DescriptionCounts {
+            spends: 0,
+            outputs,
+            mints: 0,
+            burns: 0,
+        };
+        let mut fee = self.fee_policy.minimum_fee(&counts);
+
+        // Paying the fee can take more notes, which raises the fee, so repeat
+        // until the notes chosen cover their own fee
+        loop {
+            let mut notes = vec![];
+            for (asset_id, amount) in amounts.iter() {
+                let mut needed = *amount as u128;
+                if *asset_id == NATIVE_ASSET {
+                    needed += fee as u128;
+                }
+
+                let mut funded = 0u128;
+                for &note in order {
+                    if funded >= needed {
+                        break;
+                    }
+
+                    let record = &self.notes[note];
+                    if !reserved[note] && record.note.asset_id() == *asset_id {
+                        funded += record.note.value() as u128;
+                        notes.push(note);
+                    }
+                }
+
+                if funded < needed {
+                    return Err(IronfishError::InvalidBalance);
+                }
+            }
+
+            self.limits.check_spends(notes.len())?;
+
+            counts.spends = notes.len();
+            let required = self.fee_policy.minimum_fee(&counts);
+            if required <= fee {
+                return Ok((notes, fee));
+            }
+            fee = required;
+        }
+    }
+
+    /// Build and prove the transactions of the batches in parallel
+    fn post(
+        &self,
+        requests: &[PaymentRequest],
+        batches: &[Batch],
+    ) -> Vec<Result<Transaction, IronfishError>> {
+        let spender_key = &self.spender_key;
+        let notes = &self.notes;
+        let limits = self.limits;
+        let change_goes_to = self.change_goes_to;
+        let expiration = self.expiration;
+
+        batches
+            .par_iter()
+            .map(|batch| {
+                let sender = spender_key.public_address();
+                let mut transaction =
+                    ProposedTransaction::with_limits(spender_key.clone(), limits)?;
+
+                for &note in batch.notes.iter() {
+                    transaction.add_spendable_note(&notes[note])?;
+                }
+
+                for &index in batch.requests.iter() {
+                    let request = &requests[index];
+                    transaction.add_output(Note::try_new(
+                        request.recipient,
+                        request.amount,
+                        request.memo.as_str(),
+                        request.asset_id,
+                        sender,
+                    )?)?;
+                }
+
+                transaction.set_expiration(expiration);
+                transaction.post(change_goes_to, batch.fee)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PaymentRequest, PaymentStatus, TransactionFactory};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::Note,
+        spendable_note::SpendableNoteRecord,
+        test_util::make_fake_witness,
+        transaction::fee::{FeePolicy, MinimumFeeRate},
+        tree_position::NotePosition,
+    };
+
+    fn spendable_note(key: &SaplingKey, value: u64) -> SpendableNoteRecord {
+        let note = Note::new(
+            key.public_address(),
+            value,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+        SpendableNoteRecord {
+            note,
+            position: NotePosition::new(0),
+            witness,
+            confirmations: 1,
+        }
+    }
+
+    #[test]
+    fn test_factory_run() {
+        let key = SaplingKey::generate_key();
+        let recipient = SaplingKey::generate_key().public_address();
+        let policy = MinimumFeeRate { fee_per_weight: 1 };
+
+        let mut factory = TransactionFactory::new(key.clone(), Box::new(policy));
+        factory.set_max_payments_per_transaction(2);
+        for value in [10, 30, 20] {
+            factory.add_note(spendable_note(&key, value));
+        }
+
+        let request = |id, amount, memo: &str| PaymentRequest {
+            id,
+            recipient,
+            amount,
+            asset_id: NATIVE_ASSET,
+            memo: memo.to_string(),
+        };
+        let requests = [
+            request(1, 5, "withdrawal 1"),
+            request(2, 10, "withdrawal 2"),
+            request(3, 1000, "too much"),
+            request(4, 1, &"x".repeat(33)),
+            request(5, 8, "withdrawal 5"),
+        ];
+
+        let run = factory.run(&requests);
+
+        // The first two share a transaction, the fifth is in the next one
+        assert_eq!(run.transactions.len(), 2);
+        assert_eq!(run.transactions[0].spends().len(), 1);
+        assert_eq!(run.transactions[0].outputs().len(), 3);
+        for transaction in run.transactions.iter() {
+            transaction.check_fee(&policy).unwrap();
+            assert!(policy.minimum_fee(&transaction.description_counts()) > 0);
+        }
+
+        let ids: Vec<u64> = run.results.iter().map(|result| result.request_id).collect();
+        assert_eq!(ids, [1, 2, 3, 4, 5]);
+        assert!(matches!(
+            run.results[0].status,
+            PaymentStatus::Posted { transaction: 0 }
+        ));
+        assert!(matches!(
+            run.results[1].status,
+            PaymentStatus::Posted { transaction: 0 }
+        ));
+        assert!(matches!(
+            run.results[2].status,
+            PaymentStatus::Failed(IronfishError::InvalidBalance)
+        ));
+        assert!(matches!(
+            run.results[3].status,
+            PaymentStatus::Failed(IronfishError::InvalidData)
+        ));
+        assert!(matches!(
+            run.results[4].status,
+            PaymentStatus::Posted { transaction: 1 }
+        ));
+
+        // The two largest notes were spent
+        assert_eq!(factory.notes().len(), 1);
+        assert_eq!(factory.notes()[0].note.value(), 10);
+    }
+}
@@ -0,0 +1,278 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Fault injection for testing that verification rejects tampered
+//! transactions.
+//!
+//! Each [`Fault`] names one field of a posted transaction, such as a spend
+//! proof or the binding signature. Injecting it flips a single bit of that
+//! field in the serialized transaction and reads the result back, just as a
+//! node would a transaction from a peer. A fault is caught if the tampered
+//! bytes fail to parse or the parsed transaction fails to verify.
+//!
+//! [`assert_faults_rejected`] runs every fault that applies to a transaction,
+//! so a protocol change that stops checking a field fails the tests that use
+//! it instead of silently weakening verification.
+
+use std::ops::Range;
+
+use crate::{errors::IronfishError, network::NetworkKind};
+
+use super::{
+    burns::BURN_DESCRIPTION_SIZE,
+    mints::MINT_DESCRIPTION_SIZE,
+    outputs::{OUTPUT_DESCRIPTION_SIZE, PROOF_SIZE},
+    spends::SPEND_DESCRIPTION_SIZE,
+    Transaction, TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE, TRANSACTION_HEADER_SIZE,
+    TRANSACTION_PUBLIC_KEY_SIZE, TRANSACTION_SIGNATURE_SIZE,
+};
+
+/// Offset of the fee in a serialized transaction, after the version and the
+/// four description counts
+const FEE_OFFSET: usize = 1 + 4 * 8;
+
+const RANDOMIZED_PUBLIC_KEY_OFFSET: usize =
+    FEE_OFFSET + TRANSACTION_FEE_SIZE + TRANSACTION_EXPIRATION_SIZE;
+
+/// A field of a posted transaction that a fault can be injected into. The
+/// index selects the description the field belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    Fee,
+    RandomizedPublicKey,
+    BindingSignature,
+    SpendProof(usize),
+    SpendValueCommitment(usize),
+    /// Merkle root of the note commitment tree the spent note is in
+    SpendAnchor(usize),
+    /// Size of the tree at the anchor
+    SpendTreeSize(usize),
+    SpendNullifier(usize),
+    SpendSignature(usize),
+    OutputProof(usize),
+    OutputValueCommitment(usize),
+    OutputNoteCommitment(usize),
+    MintProof(usize),
+    MintSignature(usize),
+}
+
+impl Fault {
+    /// Every fault that can be injected into the given transaction
+    pub fn all_for(transaction: &Transaction) -> Vec<Fault> {
+        let mut faults = vec![
+            Fault::Fee,
+            Fault::RandomizedPublicKey,
+            Fault::BindingSignature,
+        ];
+
+        for index in 0..transaction.spends.len() {
+            faults.extend([
+                Fault::SpendProof(index),
+                Fault::SpendValueCommitment(index),
+                Fault::SpendAnchor(index),
+                Fault::SpendTreeSize(index),
+                Fault::SpendNullifier(index),
+                Fault::SpendSignature(index),
+            ]);
+        }
+
+        for index in 0..transaction.outputs.len() {
+            faults.extend([
+                Fault::OutputProof(index),
+                Fault::OutputValueCommitment(index),
+                Fault::OutputNoteCommitment(index),
+            ]);
+        }
+
+        for index in 0..transaction.mints.len() {
+            faults.extend([Fault::MintProof(index), Fault::MintSignature(index)]);
+        }
+
+        faults
+    }
+
+    /// The bytes of the serialized transaction holding this field, or
+    /// `None` if the transaction has no such description
+    pub fn byte_range(&self, transaction: &Transaction) -> Option<Range<usize>> {
+        let spends_start = TRANSACTION_HEADER_SIZE;
+        let outputs_start = spends_start + transaction.spends.len() * SPEND_DESCRIPTION_SIZE;
+        let mints_start = outputs_start + transaction.outputs.len() * OUTPUT_DESCRIPTION_SIZE;
+        let burns_start = mints_start + transaction.mints.len() * MINT_DESCRIPTION_SIZE;
+        let signature_start = burns_start + transaction.burns.len() * BURN_DESCRIPTION_SIZE;
+
+        let proof_size = PROOF_SIZE as usize;
+
+        let spend = |index: usize, start: usize, size: usize| {
+            (index < transaction.spends.len()).then(|| {
+                let offset = spends_start + index * SPEND_DESCRIPTION_SIZE + start;
+                offset..offset + size
+            })
+        };
+        let output = |index: usize, start: usize, size: usize| {
+            (index < transaction.outputs.len()).then(|| {
+                let offset = outputs_start + index * OUTPUT_DESCRIPTION_SIZE + start;
+                offset..offset + size
+            })
+        };
+        let mint = |index: usize, start: usize, size: usize| {
+            (index < transaction.mints.len()).then(|| {
+                let offset = mints_start + index * MINT_DESCRIPTION_SIZE + start;
+                offset..offset + size
+            })
+        };
+
+        match *self {
+            Fault::Fee => Some(FEE_OFFSET..FEE_OFFSET + TRANSACTION_FEE_SIZE),
+            Fault::RandomizedPublicKey => Some(
+                RANDOMIZED_PUBLIC_KEY_OFFSET
+                    ..RANDOMIZED_PUBLIC_KEY_OFFSET + TRANSACTION_PUBLIC_KEY_SIZE,
+            ),
+            Fault::BindingSignature => {
+                Some(signature_start..signature_start + TRANSACTION_SIGNATURE_SIZE)
+            }
+            Fault::SpendProof(index) => spend(index, 0, proof_size),
+            Fault::SpendValueCommitment(index) => spend(index, proof_size, 32),
+            Fault::SpendAnchor(index) => spend(index, proof_size + 32, 32),
+            Fault::SpendTreeSize(index) => spend(index, proof_size + 64, 4),
+            Fault::SpendNullifier(index) => spend(index, proof_size + 68, 32),
+            Fault::SpendSignature(index) => spend(
+                index,
+                SPEND_DESCRIPTION_SIZE - TRANSACTION_SIGNATURE_SIZE,
+                TRANSACTION_SIGNATURE_SIZE,
+            ),
+            Fault::OutputProof(index) => output(index, 0, proof_size),
+            Fault::OutputValueCommitment(index) => output(index, proof_size, 32),
+            Fault::OutputNoteCommitment(index) => output(index, proof_size + 32, 32),
+            Fault::MintProof(index) => mint(index, 0, proof_size),
+            Fault::MintSignature(index) => mint(
+                index,
+                MINT_DESCRIPTION_SIZE - TRANSACTION_SIGNATURE_SIZE,
+                TRANSACTION_SIGNATURE_SIZE,
+            ),
+        }
+    }
+}
+
+/// Serialize the transaction with one bit of the faulted field flipped. `bit`
+/// counts from the first bit of the field and wraps around its size.
+pub fn inject_fault(
+    transaction: &Transaction,
+    fault: Fault,
+    bit: usize,
+) -> Result<Vec<u8>, IronfishError> {
+    let range = fault
+        .byte_range(transaction)
+        .ok_or(IronfishError::InvalidData)?;
+
+    let mut serialized = vec![];
+    transaction.write(&mut serialized)?;
+
+    let bit = bit % (range.len() * 8);
+    serialized[range.start + bit / 8] ^= 1 << (bit % 8);
+
+    Ok(serialized)
+}
+
+/// Whether the transaction with the given fault injected is refused, either
+/// because it can't be read or because it doesn't verify
+pub fn is_fault_rejected(
+    transaction: &Transaction,
+    fault: Fault,
+    bit: usize,
+    network: NetworkKind,
+) -> Result<bool, IronfishError> {
+    let serialized = inject_fault(transaction, fault, bit)?;
+
+    let rejected = match Transaction::read_for_network(&serialized[..], network) {
+        Err(_) => true,
+        Ok(tampered) => tampered.verify().is_err(),
+    };
+
+    Ok(rejected)
+}
+
+/// Panic unless the transaction verifies as given, and every fault that
+/// applies to it is rejected when flipping the first, last or a middle bit of
+/// the field.
+pub fn assert_faults_rejected(transaction: &Transaction, network: NetworkKind) {
+    transaction
+        .verify()
+        .expect("faults can only be tested against a valid transaction");
+
+    for fault in Fault::all_for(transaction) {
+        let bits = fault.byte_range(transaction).unwrap().len() * 8;
+
+        for bit in [0, bits / 2 + 1, bits - 1] {
+            let rejected = is_fault_rejected(transaction, fault, bit, network)
+                .expect("fault should apply to the transaction");
+            assert!(
+                rejected,
+                "{:?} with bit {} flipped was accepted",
+                fault, bit
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assert_faults_rejected, inject_fault, is_fault_rejected, Fault};
+    use crate::{
+        assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+        keys::SaplingKey,
+        network::NetworkKind,
+        note::Note,
+        test_util::make_fake_witness,
+        transaction::ProposedTransaction,
+    };
+
+    #[test]
+    fn test_faults_are_rejected() {
+        let key = SaplingKey::generate_key();
+        let receiver = SaplingKey::generate_key().public_address();
+
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+        let out_note = Note::new(receiver, 40, "", NATIVE_ASSET, key.public_address());
+        let asset = Asset::new(key.public_address(), "Testcoin", "").unwrap();
+
+        let mut proposed = ProposedTransaction::new(key);
+        proposed.add_spend(in_note, &witness).unwrap();
+        proposed.add_output(out_note).unwrap();
+        proposed.add_mint(asset, 5).unwrap();
+        let transaction = proposed.post(None, 1).unwrap();
+
+        let faults = Fault::all_for(&transaction);
+        assert!(faults.contains(&Fault::SpendAnchor(0)));
+        assert!(!faults.contains(&Fault::SpendAnchor(1)));
+        assert!(Fault::SpendAnchor(1).byte_range(&transaction).is_none());
+
+        assert_faults_rejected(&transaction, NetworkKind::Mainnet);
+
+        // Injecting a fault changes a single bit of the transaction
+        let mut original = vec![];
+        transaction.write(&mut original).unwrap();
+        let tampered = inject_fault(&transaction, Fault::Fee, 3).unwrap();
+        let differing = tampered
+            .iter()
+            .zip(original.iter())
+            .filter(|(a, b)| a != b)
+            .count();
+        assert_eq!(tampered.len(), original.len());
+        assert_eq!(differing, 1);
+
+        // Bits wrap around the size of the field
+        assert_eq!(
+            inject_fault(&transaction, Fault::Fee, 3).unwrap(),
+            inject_fault(&transaction, Fault::Fee, 3 + 64).unwrap()
+        );
+        assert!(is_fault_rejected(&transaction, Fault::Fee, 3, NetworkKind::Mainnet).unwrap());
+    }
+}
@@ -0,0 +1,345 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Fee rates and mempool priority. These live here, rather than in each node
+//! implementation, so that every node orders transactions the same way.
+//!
+//! The minimum fee is a consensus rule, so like [`super::limits::Limits`] it's
+//! set by the node for the network and upgrade it runs, as a [`FeePolicy`].
+
+use std::cmp::Ordering;
+
+use super::{
+    burns::BURN_DESCRIPTION_SIZE, mints::MINT_DESCRIPTION_SIZE, outputs::OUTPUT_DESCRIPTION_SIZE,
+    spends::SPEND_DESCRIPTION_SIZE, ProposedTransaction, Transaction, TRANSACTION_HEADER_SIZE,
+    TRANSACTION_SIGNATURE_SIZE,
+};
+use crate::{
+    assets::asset_identifier::NATIVE_ASSET,
+    errors::IronfishError,
+    keys::PublicAddress,
+    value_range::{check_value, ValueContext},
+};
+
+/// Fee rates are expressed in ore per this many bytes of serialized
+/// transaction.
+pub const FEE_RATE_BYTES: u64 = 1000;
+
+/// The cost of a transaction to the network, used to order transactions
+/// competing for space in the mempool and in blocks.
+///
+/// Priorities compare by fee rate first, then by absolute fee, and finally
+/// favor the smaller transaction. Fee rates are compared exactly rather than
+/// after rounding, so two transactions only compare equal if they have the
+/// same fee and size.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeePriority {
+    pub fee: u64,
+    pub size: u64,
+}
+
+impl FeePriority {
+    /// Fee rate in ore per [`FEE_RATE_BYTES`], rounded down.
+    pub fn fee_rate(&self) -> u64 {
+        ((self.fee as u128 * FEE_RATE_BYTES as u128) / self.size.max(1) as u128) as u64
+    }
+}
+
+impl Ord for FeePriority {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let rate = self.fee as u128 * other.size as u128;
+        let other_rate = other.fee as u128 * self.size as u128;
+
+        rate.cmp(&other_rate)
+            .then(self.fee.cmp(&other.fee))
+            .then(other.size.cmp(&self.size))
+    }
+}
+
+impl PartialOrd for FeePriority {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Size in bytes of a serialized transaction with the given number of each
+/// description, e.g. to estimate the fee of a transaction before posting it.
+pub fn serialized_size_for(spends: usize, outputs: usize, mints: usize, burns: usize) -> usize {
+    TRANSACTION_HEADER_SIZE
+        + spends * SPEND_DESCRIPTION_SIZE
+        + outputs * OUTPUT_DESCRIPTION_SIZE
+        + mints * MINT_DESCRIPTION_SIZE
+        + burns * BURN_DESCRIPTION_SIZE
+        + TRANSACTION_SIGNATURE_SIZE
+}
+
+/// The number of each kind of description in a transaction, which is what
+/// fee policies put a price on
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DescriptionCounts {
+    pub spends: usize,
+    pub outputs: usize,
+    pub mints: usize,
+    pub burns: usize,
+}
+
+impl DescriptionCounts {
+    /// Size in bytes of a serialized transaction with these descriptions
+    pub fn serialized_size(&self) -> usize {
+        serialized_size_for(self.spends, self.outputs, self.mints, self.burns)
+    }
+}
+
+/// The fee rules of a network. Both [`super::ProposedTransaction`], when
+/// posting, and [`Transaction::check_fee`], when verifying, price a
+/// transaction with the policy they're given.
+///
+/// By default the minimum fee is the fee per weight for the weight of the
+/// transaction, which is its serialized size, unless the mix of descriptions
+/// is allowed to go without any fee.
+pub trait FeePolicy {
+    /// Fee in ore per [`FEE_RATE_BYTES`] units of weight.
+    fn fee_per_weight(&self) -> u64;
+
+    /// How much a transaction with these descriptions weighs.
+    fn weight(&self, counts: &DescriptionCounts) -> u64 {
+        counts.serialized_size() as u64
+    }
+
+    /// Whether a transaction with these descriptions may pay no fee at all,
+    /// for example to let consolidation transactions through for free.
+    fn allows_zero_fee(&self, _counts: &DescriptionCounts) -> bool {
+        false
+    }
+
+    /// The smallest fee a transaction with these descriptions has to pay.
+    fn minimum_fee(&self, counts: &DescriptionCounts) -> u64 {
+        if self.allows_zero_fee(counts) {
+            return 0;
+        }
+
+        // Rounded up, so no transaction pays less than the fee per weight
+        let cost = self.weight(counts) as u128 * self.fee_per_weight() as u128;
+        let fee = (cost + FEE_RATE_BYTES as u128 - 1) / FEE_RATE_BYTES as u128;
+        fee.try_into().unwrap_or(u64::MAX)
+    }
+}
+
+/// Any fee goes, including none. This is how transactions were priced before
+/// fee policies.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoMinimumFee;
+
+impl FeePolicy for NoMinimumFee {
+    fn fee_per_weight(&self) -> u64 {
+        0
+    }
+}
+
+/// Every transaction pays at least the given fee per [`FEE_RATE_BYTES`] of
+/// serialized transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MinimumFeeRate {
+    pub fee_per_weight: u64,
+}
+
+impl FeePolicy for MinimumFeeRate {
+    fn fee_per_weight(&self) -> u64 {
+        self.fee_per_weight
+    }
+}
+
+impl Transaction {
+    /// The number of each kind of description in this transaction
+    pub fn description_counts(&self) -> DescriptionCounts {
+        DescriptionCounts {
+            spends: self.spends.len(),
+            outputs: self.outputs.len(),
+            mints: self.mints.len(),
+            burns: self.burns.len(),
+        }
+    }
+
+    /// Check that a posted transaction, for example one received from a
+    /// peer, pays the minimum fee of the policy. Miner's fee transactions
+    /// have a negative fee and always fail this check.
+    pub fn check_fee(&self, policy: &dyn FeePolicy) -> Result<(), IronfishError> {
+        let minimum_fee = policy.minimum_fee(&self.description_counts());
+        match u64::try_from(self.fee) {
+            Ok(fee) if fee >= minimum_fee => Ok(()),
+            _ => Err(IronfishError::FeeTooLow),
+        }
+    }
+
+    /// Size of this transaction in bytes, as written by
+    /// [`Transaction::write`], computed without serializing it.
+    pub fn serialized_size(&self) -> usize {
+        self.description_counts().serialized_size() + self.extra_data_size()
+    }
+
+    /// The priority of this transaction. Negative fees, as in miner's fee
+    /// transactions, count as no fee at all.
+    pub fn priority(&self) -> FeePriority {
+        FeePriority {
+            fee: self.fee.max(0) as u64,
+            size: self.serialized_size() as u64,
+        }
+    }
+
+    /// Fee rate in ore per [`FEE_RATE_BYTES`], rounded down.
+    pub fn fee_rate(&self) -> u64 {
+        self.priority().fee_rate()
+    }
+}
+
+impl ProposedTransaction {
+    /// The smallest fee the policy allows for this transaction. Posting adds
+    /// a change output for each asset with a balance left over, and the
+    /// native change is counted even if the fee ends up using all of it, so
+    /// this may be slightly more than the posted transaction needs.
+    pub fn minimum_fee(&self, policy: &dyn FeePolicy) -> u64 {
+        policy.minimum_fee(&self.posted_description_counts(0))
+    }
+
+    /// Post the transaction like [`ProposedTransaction::post`], but fail with
+    /// [`IronfishError::FeeTooLow`], before changing anything, if the fee is
+    /// less than the policy requires of the posted transaction.
+    pub fn post_with_fee_policy(
+        &mut self,
+        change_goes_to: Option<PublicAddress>,
+        intended_transaction_fee: u64,
+        policy: &dyn FeePolicy,
+    ) -> Result<Transaction, IronfishError> {
+        let fee = check_value(intended_transaction_fee, ValueContext::Fee)?;
+        let counts = self.posted_description_counts(fee);
+        if intended_transaction_fee < policy.minimum_fee(&counts) {
+            return Err(IronfishError::FeeTooLow);
+        }
+
+        self.post(change_goes_to, intended_transaction_fee)
+    }
+
+    /// The descriptions the transaction will have once posted with the given
+    /// fee, including change
+    fn posted_description_counts(&self, fee: i64) -> DescriptionCounts {
+        let change_outputs = self
+            .value_balances
+            .iter()
+            .filter(|(asset_id, value)| match **asset_id == NATIVE_ASSET {
+                true => **value > fee,
+                false => **value > 0,
+            })
+            .count();
+
+        DescriptionCounts {
+            spends: self.spends.len(),
+            outputs: self.outputs.len() + change_outputs,
+            mints: self.mints.len(),
+            burns: self.burns.len(),
+        }
+    }
+}
+
+/// Order transactions from highest to lowest priority, e.g. for
+/// `transactions.sort_by(compare_by_priority)`.
+pub fn compare_by_priority(a: &Transaction, b: &Transaction) -> Ordering {
+    b.priority().cmp(&a.priority())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{DescriptionCounts, FeePolicy, FeePriority, MinimumFeeRate, NoMinimumFee};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+
+    /// Charges by the output, and lets transactions without outputs through
+    /// for free
+    struct PerOutput;
+
+    impl FeePolicy for PerOutput {
+        fn fee_per_weight(&self) -> u64 {
+            1000
+        }
+
+        fn weight(&self, counts: &DescriptionCounts) -> u64 {
+            counts.outputs as u64
+        }
+
+        fn allows_zero_fee(&self, counts: &DescriptionCounts) -> bool {
+            counts.outputs == 0
+        }
+    }
+
+    #[test]
+    fn test_fee_priority_ordering() {
+        let cheap = FeePriority { fee: 1, size: 1000 };
+        let expensive = FeePriority {
+            fee: 10,
+            size: 1000,
+        };
+        assert!(expensive > cheap);
+        assert_eq!(cheap.fee_rate(), 1);
+        assert_eq!(expensive.fee_rate(), 10);
+
+        // Same rate once rounded, but the exact rate is higher
+        let slightly_better = FeePriority { fee: 1, size: 999 };
+        assert_eq!(slightly_better.fee_rate(), 1);
+        assert!(slightly_better > cheap);
+
+        // Same exact rate, the higher fee wins
+        let same_rate = FeePriority { fee: 2, size: 2000 };
+        assert!(same_rate > cheap);
+        assert_eq!(cheap.cmp(&cheap), std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_fee_policy() {
+        let counts = DescriptionCounts {
+            spends: 1,
+            outputs: 2,
+            ..DescriptionCounts::default()
+        };
+        assert_eq!(NoMinimumFee.minimum_fee(&counts), 0);
+        assert_eq!(PerOutput.minimum_fee(&counts), 2);
+        assert_eq!(PerOutput.minimum_fee(&DescriptionCounts::default()), 0);
+
+        // Rounded up to whole ore
+        let size = counts.serialized_size() as u64;
+        let rate = MinimumFeeRate { fee_per_weight: 1 };
+        assert_eq!(rate.minimum_fee(&counts), (size + 999) / 1000);
+        let rate = MinimumFeeRate {
+            fee_per_weight: 1000,
+        };
+        assert_eq!(rate.minimum_fee(&counts), size);
+
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        // The spend leaves change, which is an output
+        let mut proposed = ProposedTransaction::new(key);
+        proposed.add_spend(note, &witness).unwrap();
+        assert_eq!(proposed.minimum_fee(&PerOutput), 1);
+        assert!(matches!(
+            proposed.post_with_fee_policy(None, 0, &PerOutput),
+            Err(IronfishError::FeeTooLow)
+        ));
+
+        let posted = proposed.post_with_fee_policy(None, 1, &PerOutput).unwrap();
+        assert_eq!(posted.outputs().len(), 1);
+        posted.check_fee(&PerOutput).unwrap();
+        assert!(matches!(
+            posted.check_fee(&MinimumFeeRate { fee_per_weight: 10 }),
+            Err(IronfishError::FeeTooLow)
+        ));
+    }
+}
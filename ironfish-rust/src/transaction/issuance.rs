@@ -0,0 +1,210 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Mints of many assets in a single transaction, for issuers that create
+//! assets in bulk.
+//!
+//! Every mint of a transaction is authorized by the spender key, signing
+//! with the randomized public key the whole transaction shares, so a batch
+//! needs no more keys than a single mint. [`ProposedTransaction::add_mints`]
+//! checks every mint of the batch before adding any, and reports each one
+//! that can't be added, so an issuer can fix them all at once rather than one
+//! failed attempt at a time.
+
+use crate::{
+    assets::{asset::Asset, asset_identifier::AssetIdentifier},
+    errors::IronfishError,
+    note::Note,
+    value_range::{check_value, ValueContext},
+    PublicAddress,
+};
+
+use super::{mints::MintBuilder, outputs::OutputBuilder, ProposedTransaction};
+
+/// One mint of a batch
+#[derive(Clone, Copy, Debug)]
+pub struct MintRequest {
+    pub asset: Asset,
+    pub value: u64,
+
+    /// Who the minted value goes to, as with
+    /// [`ProposedTransaction::add_mint_to`], or the change if nobody
+    pub recipient: Option<PublicAddress>,
+}
+
+impl MintRequest {
+    pub fn new(asset: Asset, value: u64, recipient: Option<PublicAddress>) -> Self {
+        MintRequest {
+            asset,
+            value,
+            recipient,
+        }
+    }
+}
+
+/// A mint of a batch that can't be added, in
+/// [`IronfishError::RejectedMints`]
+#[derive(Debug)]
+pub struct MintRejection {
+    /// Index of the mint in the batch
+    pub index: usize,
+    pub asset_id: AssetIdentifier,
+
+    /// What [`ProposedTransaction::add_mint`] would fail with for this mint,
+    /// or [`IronfishError::InvalidSigningKey`] if the spender doesn't own
+    /// the asset
+    pub error: IronfishError,
+}
+
+impl ProposedTransaction {
+    /// Add every mint of the batch, or none of them. Fails with
+    /// [`IronfishError::TooManyMints`] or [`IronfishError::TooManyOutputs`]
+    /// if the batch doesn't fit in the limits, and with
+    /// [`IronfishError::RejectedMints`] listing every mint that can't be
+    /// added otherwise.
+    pub fn add_mints(&mut self, requests: &[MintRequest]) -> Result<(), IronfishError> {
+        let recipients = requests
+            .iter()
+            .filter(|request| request.recipient.is_some())
+            .count();
+        self.limits.check_mints(self.mints.len() + requests.len())?;
+        self.limits.check_outputs(self.outputs.len() + recipients)?;
+
+        let owner = self.spender_key.public_address();
+        let mut value_balances = self.value_balances.clone();
+        let mut mints = Vec::with_capacity(requests.len());
+        let mut outputs = Vec::with_capacity(recipients);
+        let mut rejections = vec![];
+
+        for (index, request) in requests.iter().enumerate() {
+            let asset_id = *request.asset.id();
+            let checked = match request.asset.owner == owner {
+                true => check_value(request.value, ValueContext::Mint)
+                    .map_err(IronfishError::from)
+                    .and_then(|value| value_balances.add(&asset_id, value)),
+                false => Err(IronfishError::InvalidSigningKey),
+            };
+
+            let output = checked.and_then(|_| match request.recipient {
+                Some(recipient) => {
+                    let note = Note::try_new(recipient, request.value, "", asset_id, owner)?;
+                    let value = check_value(request.value, ValueContext::Note)?;
+                    value_balances.subtract(&asset_id, value)?;
+                    OutputBuilder::new(note).map(Some)
+                }
+                None => Ok(None),
+            });
+
+            match output {
+                Ok(output) => {
+                    mints.push(MintBuilder::new(request.asset, request.value));
+                    outputs.extend(output);
+                }
+                Err(error) => rejections.push(MintRejection {
+                    index,
+                    asset_id,
+                    error,
+                }),
+            }
+        }
+
+        if !rejections.is_empty() {
+            return Err(IronfishError::RejectedMints(rejections));
+        }
+
+        self.value_balances = value_balances;
+        self.mints.extend(mints);
+        self.outputs.extend(outputs);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MintRequest;
+    use crate::{
+        assets::asset::Asset,
+        errors::IronfishError,
+        keys::SaplingKey,
+        transaction::{limits::Limits, ProposedTransaction},
+        value_range::ValueError,
+    };
+
+    #[test]
+    fn test_add_mints() {
+        let issuer = SaplingKey::generate_key();
+        let holder = SaplingKey::generate_key().public_address();
+        let assets: Vec<Asset> = (0..3)
+            .map(|i| Asset::new(issuer.public_address(), &format!("coin {}", i), "").unwrap())
+            .collect();
+
+        let requests = [
+            MintRequest::new(assets[0], 10, Some(holder)),
+            MintRequest::new(assets[1], 20, None),
+            MintRequest::new(assets[2], 30, Some(holder)),
+        ];
+        let mut transaction = ProposedTransaction::new(issuer);
+        transaction.add_mints(&requests).unwrap();
+
+        let posted = transaction.post(None, 0).unwrap();
+        posted.verify().unwrap();
+        assert_eq!(posted.mints().len(), 3);
+        // Two minted notes, and the change of the asset minted to nobody
+        assert_eq!(posted.outputs().len(), 3);
+    }
+
+    #[test]
+    fn test_add_mints_rejections() {
+        let issuer = SaplingKey::generate_key();
+        let other = SaplingKey::generate_key();
+        let asset = Asset::new(issuer.public_address(), "coin", "").unwrap();
+        let foreign = Asset::new(other.public_address(), "other coin", "").unwrap();
+
+        let requests = [
+            MintRequest::new(asset, 10, None),
+            MintRequest::new(foreign, 10, None),
+            MintRequest::new(asset, 0, Some(other.public_address())),
+        ];
+        let mut transaction = ProposedTransaction::new(issuer.clone());
+        let rejections = match transaction.add_mints(&requests) {
+            Err(IronfishError::RejectedMints(rejections)) => rejections,
+            _ => panic!("expected the mints to be rejected"),
+        };
+
+        assert_eq!(rejections.len(), 2);
+        assert_eq!(rejections[0].index, 1);
+        assert_eq!(rejections[0].asset_id, *foreign.id());
+        assert!(matches!(
+            rejections[0].error,
+            IronfishError::InvalidSigningKey
+        ));
+        assert_eq!(rejections[1].index, 2);
+        assert!(matches!(
+            rejections[1].error,
+            IronfishError::InvalidValue(ValueError::Zero { .. })
+        ));
+
+        // Nothing was added
+        assert!(transaction.mints.is_empty());
+        assert!(transaction.outputs.is_empty());
+        transaction.add_mints(&requests[..1]).unwrap();
+        assert_eq!(transaction.mints.len(), 1);
+
+        // The batch has to fit as a whole
+        let mut transaction = ProposedTransaction::with_limits(
+            issuer,
+            Limits {
+                max_mints: 1,
+                ..Limits::UNLIMITED
+            },
+        )
+        .unwrap();
+        transaction.add_mints(&requests[..1]).unwrap();
+        assert!(matches!(
+            transaction.add_mints(&requests[..1]),
+            Err(IronfishError::TooManyMints)
+        ));
+    }
+}
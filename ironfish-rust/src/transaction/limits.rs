@@ -0,0 +1,158 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::errors::IronfishError;
+
+use super::{
+    burns::BURN_DESCRIPTION_SIZE,
+    extra_data::{has_extra_data, MAX_EXTRA_DATA_SIZE},
+    mints::MINT_DESCRIPTION_SIZE,
+    outputs::OUTPUT_DESCRIPTION_SIZE,
+    spends::SPEND_DESCRIPTION_SIZE,
+    Transaction, TRANSACTION_HEADER_SIZE, TRANSACTION_SIGNATURE_SIZE, TREE_ID_TRANSACTION_VERSION,
+};
+
+/// Maximum number of each kind of description a single transaction may
+/// contain. These are consensus rules, so they are set by the node for the
+/// network and hard fork it is running rather than hardcoded here. The
+/// default places no limit on any description.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub max_spends: usize,
+    pub max_outputs: usize,
+    pub max_mints: usize,
+    pub max_burns: usize,
+}
+
+impl Limits {
+    pub const UNLIMITED: Limits = Limits {
+        max_spends: usize::MAX,
+        max_outputs: usize::MAX,
+        max_mints: usize::MAX,
+        max_burns: usize::MAX,
+    };
+
+    pub(crate) fn check_spends(&self, count: usize) -> Result<(), IronfishError> {
+        match count > self.max_spends {
+            true => Err(IronfishError::TooManySpends),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_outputs(&self, count: usize) -> Result<(), IronfishError> {
+        match count > self.max_outputs {
+            true => Err(IronfishError::TooManyOutputs),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_mints(&self, count: usize) -> Result<(), IronfishError> {
+        match count > self.max_mints {
+            true => Err(IronfishError::TooManyMints),
+            false => Ok(()),
+        }
+    }
+
+    pub(crate) fn check_burns(&self, count: usize) -> Result<(), IronfishError> {
+        match count > self.max_burns {
+            true => Err(IronfishError::TooManyBurns),
+            false => Ok(()),
+        }
+    }
+
+    /// Check that a posted transaction, for example one received from a
+    /// peer, is within these limits.
+    pub fn check_transaction(&self, transaction: &Transaction) -> Result<(), IronfishError> {
+        self.check_spends(transaction.spends.len())?;
+        self.check_outputs(transaction.outputs.len())?;
+        self.check_mints(transaction.mints.len())?;
+        self.check_burns(transaction.burns.len())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::UNLIMITED
+    }
+}
+
+/// Limits enforced while reading a transaction from an untrusted source,
+/// such as a peer. The description counts are checked as soon as the header
+/// is read, before anything is allocated for the descriptions, so a header
+/// claiming billions of spends is refused instead of reserved for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReadLimits {
+    pub descriptions: Limits,
+
+    /// Maximum size of the serialized transaction, in bytes
+    pub max_bytes: usize,
+}
+
+impl ReadLimits {
+    pub const UNLIMITED: ReadLimits = ReadLimits {
+        descriptions: Limits::UNLIMITED,
+        max_bytes: usize::MAX,
+    };
+
+    /// Check the description counts read from the header of a transaction of
+    /// the given version, along with the largest size of the transaction
+    /// they add up to.
+    pub(crate) fn check_header(
+        &self,
+        version: u8,
+        num_spends: u64,
+        num_outputs: u64,
+        num_mints: u64,
+        num_burns: u64,
+    ) -> Result<(), IronfishError> {
+        let num_spends = usize::try_from(num_spends).map_err(|_| IronfishError::TooManySpends)?;
+        let num_outputs =
+            usize::try_from(num_outputs).map_err(|_| IronfishError::TooManyOutputs)?;
+        let num_mints = usize::try_from(num_mints).map_err(|_| IronfishError::TooManyMints)?;
+        let num_burns = usize::try_from(num_burns).map_err(|_| IronfishError::TooManyBurns)?;
+
+        self.descriptions.check_spends(num_spends)?;
+        self.descriptions.check_outputs(num_outputs)?;
+        self.descriptions.check_mints(num_mints)?;
+        self.descriptions.check_burns(num_burns)?;
+
+        // Spends carry the id of their tree from the tree id version on
+        let spend_size = match version >= TREE_ID_TRANSACTION_VERSION {
+            true => SPEND_DESCRIPTION_SIZE + 1,
+            false => SPEND_DESCRIPTION_SIZE,
+        };
+
+        // The extra data, its entry count included, takes up to
+        // MAX_EXTRA_DATA_SIZE bytes, and can't be known from the header
+        let extra_data_size = match has_extra_data(version) {
+            true => MAX_EXTRA_DATA_SIZE,
+            false => 0,
+        };
+
+        // Every description has a fixed size, so the header determines the
+        // size of the whole transaction, up to its extra data
+        let size = [
+            (num_spends, spend_size),
+            (num_outputs, OUTPUT_DESCRIPTION_SIZE),
+            (num_mints, MINT_DESCRIPTION_SIZE),
+            (num_burns, BURN_DESCRIPTION_SIZE),
+        ]
+        .iter()
+        .try_fold(
+            TRANSACTION_HEADER_SIZE + TRANSACTION_SIGNATURE_SIZE + extra_data_size,
+            |total, (count, size)| count.checked_mul(*size)?.checked_add(total),
+        );
+
+        match size {
+            Some(size) if size <= self.max_bytes => Ok(()),
+            _ => Err(IronfishError::TransactionTooLarge),
+        }
+    }
+}
+
+impl Default for ReadLimits {
+    fn default() -> Self {
+        ReadLimits::UNLIMITED
+    }
+}
@@ -0,0 +1,155 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! What the outputs of a transaction may carry as a memo, for integrators
+//! with rules of their own: an exchange crediting deposits by memo requires
+//! one on every payment, while a wallet may want every note to carry a memo,
+//! so that the ones that do don't stand out once decrypted.
+//!
+//! A [`MemoPolicy`] applies to the outputs added with
+//! [`ProposedTransaction::add_output`] and
+//! [`ProposedTransaction::add_output_with_ephemeral_secret`], as they're
+//! added. Change and minted notes are the spender's own and aren't checked.
+//! A memo is empty when all its bytes are zero, as with [`Memo::default`].
+
+use rand::RngCore;
+
+use crate::{
+    errors::IronfishError,
+    note::{Memo, Note},
+    randomness::with_rng,
+};
+
+use super::ProposedTransaction;
+
+/// Makes the memo of an output added without one
+pub type MemoGenerator = fn(&Note) -> Result<Memo, IronfishError>;
+
+#[derive(Clone, Copy)]
+pub enum MemoPolicy {
+    /// Outputs without a memo fail with [`IronfishError::MemoRequired`]
+    Required,
+
+    /// Outputs with a memo fail with [`IronfishError::MemoForbidden`]
+    Forbidden,
+
+    /// Outputs without a memo get the one the generator makes for them
+    DefaultTo(MemoGenerator),
+}
+
+impl MemoPolicy {
+    /// Outputs without a memo get a random one, see [`random_memo`]
+    pub fn random_padding() -> Self {
+        MemoPolicy::DefaultTo(random_memo)
+    }
+
+    /// The note as the policy lets it be added
+    pub(crate) fn apply(&self, mut note: Note) -> Result<Note, IronfishError> {
+        let is_empty = note.memo == Memo::default();
+
+        match self {
+            MemoPolicy::Required if is_empty => Err(IronfishError::MemoRequired),
+            MemoPolicy::Forbidden if !is_empty => Err(IronfishError::MemoForbidden),
+            MemoPolicy::DefaultTo(generator) if is_empty => {
+                note.memo = generator(&note)?;
+                Ok(note)
+            }
+            _ => Ok(note),
+        }
+    }
+}
+
+/// A memo of random bytes, which tells nothing about the note or the wallet
+/// that made it
+pub fn random_memo(_note: &Note) -> Result<Memo, IronfishError> {
+    let mut memo = Memo::default();
+    with_rng(|rng| rng.fill_bytes(&mut memo.0))?;
+
+    Ok(memo)
+}
+
+impl ProposedTransaction {
+    /// Check the memos of the outputs added from now on, and fill in the
+    /// ones left empty, see [`MemoPolicy`]
+    pub fn set_memo_policy(&mut self, policy: Option<MemoPolicy>) {
+        self.memo_policy = policy;
+    }
+
+    pub(super) fn apply_memo_policy(&self, note: Note) -> Result<Note, IronfishError> {
+        match &self.memo_policy {
+            Some(policy) => policy.apply(note),
+            None => Ok(note),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::MemoPolicy;
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::{Memo, Note},
+        test_util::make_fake_witness,
+        ProposedTransaction,
+    };
+
+    #[test]
+    fn test_memo_policy() {
+        let key = SaplingKey::generate_key();
+        let recipient = SaplingKey::generate_key().public_address();
+        let note = |memo: &str| Note::new(recipient, 1, memo, NATIVE_ASSET, key.public_address());
+
+        let mut transaction = ProposedTransaction::new(key.clone());
+        transaction.add_output(note("")).unwrap();
+
+        transaction.set_memo_policy(Some(MemoPolicy::Required));
+        assert!(matches!(
+            transaction.add_output(note("")),
+            Err(IronfishError::MemoRequired)
+        ));
+        transaction.add_output(note("deposit 42")).unwrap();
+
+        transaction.set_memo_policy(Some(MemoPolicy::Forbidden));
+        assert!(matches!(
+            transaction.add_output(note("deposit 42")),
+            Err(IronfishError::MemoForbidden)
+        ));
+        transaction.add_output(note("")).unwrap();
+
+        // Rejected outputs aren't added
+        assert_eq!(transaction.outputs.len(), 3);
+
+        transaction.set_memo_policy(Some(MemoPolicy::random_padding()));
+        transaction.add_output(note("")).unwrap();
+        transaction.add_output(note("deposit 42")).unwrap();
+        assert_ne!(transaction.outputs[3].note.memo(), Memo::default());
+        assert_eq!(transaction.outputs[4].note.memo(), Memo::from("deposit 42"));
+
+        let fixed = MemoPolicy::DefaultTo(|_| Ok(Memo::from("from wallet")));
+        transaction.set_memo_policy(Some(fixed));
+        transaction.add_output(note("")).unwrap();
+        assert_eq!(
+            transaction.outputs[5].note.memo(),
+            Memo::from("from wallet")
+        );
+
+        // Change is left alone
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.set_memo_policy(Some(MemoPolicy::Required));
+        transaction
+            .add_spend(note.clone(), &make_fake_witness(&note))
+            .unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+        assert_eq!(posted.outputs().len(), 1);
+    }
+}
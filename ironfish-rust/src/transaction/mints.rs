@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use std::io;
+use std::{io, time::Instant};
 
 use bellman::groth16;
 use bls12_381::{Bls12, Scalar};
@@ -14,11 +14,26 @@ use ironfish_zkp::{
     redjubjub::{self, Signature},
 };
 use jubjub::ExtendedPoint;
-use rand::thread_rng;
 
-use crate::{assets::asset::Asset, errors::IronfishError, sapling_bls12::SAPLING, SaplingKey};
+use crate::{
+    assets::{
+        asset::{Asset, ASSET_LENGTH},
+        asset_identifier::AssetIdentifier,
+    },
+    errors::IronfishError,
+    metrics::record_signatures,
+    network::NetworkKind,
+    proof_system::proof_system,
+    randomness::with_rng,
+    PublicAddress, SaplingKey,
+};
+
+use super::{outputs::PROOF_SIZE, utils::verify_mint_proof, TRANSACTION_SIGNATURE_SIZE};
 
-use super::utils::verify_mint_proof;
+/// Size of a serialized [`MintDescription`]: proof, asset, value and
+/// authorizing signature.
+pub const MINT_DESCRIPTION_SIZE: usize =
+    PROOF_SIZE as usize + ASSET_LENGTH + 8 + TRANSACTION_SIGNATURE_SIZE;
 
 /// Parameters used to build a circuit that verifies an asset can be minted with
 /// a given key
@@ -46,7 +61,7 @@ impl MintBuilder {
             public_key_randomness: Some(*public_key_randomness),
         };
 
-        let proof = groth16::create_random_proof(circuit, &SAPLING.mint_params, &mut thread_rng())?;
+        let proof = proof_system().prove_mint(circuit)?;
 
         let blank_signature = {
             let buf = [0u8; 64];
@@ -109,11 +124,9 @@ impl UnsignedMintDescription {
         data_to_be_signed[..32].copy_from_slice(&randomized_public_key.0.to_bytes());
         data_to_be_signed[32..].copy_from_slice(&signature_hash[..]);
 
-        self.description.authorizing_signature = randomized_private_key.sign(
-            &data_to_be_signed,
-            &mut thread_rng(),
-            SPENDING_KEY_GENERATOR,
-        );
+        self.description.authorizing_signature = with_rng(|rng| {
+            randomized_private_key.sign(&data_to_be_signed, rng, SPENDING_KEY_GENERATOR)
+        })?;
 
         Ok(self.description)
     }
@@ -139,6 +152,23 @@ pub struct MintDescription {
 }
 
 impl MintDescription {
+    pub fn asset(&self) -> &Asset {
+        &self.asset
+    }
+
+    pub fn asset_id(&self) -> &AssetIdentifier {
+        self.asset.id()
+    }
+
+    pub fn value(&self) -> u64 {
+        self.value
+    }
+
+    /// The owner of the asset, the only address allowed to mint it
+    pub fn owner(&self) -> PublicAddress {
+        self.asset.owner
+    }
+
     /// Verify that the signature on this proof is signing the provided input
     /// with the randomized_public_key on this proof.
     pub fn verify_signature(
@@ -153,11 +183,15 @@ impl MintDescription {
         data_to_be_signed[..32].copy_from_slice(&randomized_public_key.0.to_bytes());
         data_to_be_signed[32..].copy_from_slice(&signature_hash_value[..]);
 
-        if !randomized_public_key.verify(
+        let start = Instant::now();
+        let is_valid = randomized_public_key.verify(
             &data_to_be_signed,
             &self.authorizing_signature,
             SPENDING_KEY_GENERATOR,
-        ) {
+        );
+        record_signatures(1, start.elapsed(), is_valid);
+
+        if !is_valid {
             return Err(IronfishError::VerificationFailed);
         }
 
@@ -220,9 +254,17 @@ impl MintDescription {
         Ok(())
     }
 
-    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Self::read_for_network(reader, NetworkKind::Mainnet)
+    }
+
+    /// Load a [`MintDescription`] whose asset belongs to the given network
+    pub fn read_for_network<R: io::Read>(
+        mut reader: R,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
         let proof = groth16::Proof::read(&mut reader)?;
-        let asset = Asset::read(&mut reader)?;
+        let asset = Asset::read_for_network(&mut reader, network)?;
         let value = reader.read_u64::<LittleEndian>()?;
         let authorizing_signature = redjubjub::Signature::read(&mut reader)?;
 
@@ -241,6 +283,14 @@ impl MintDescription {
 
         Ok(())
     }
+
+    /// The serialized form of this [`MintDescription`], as written by
+    /// [`MintDescription::write`], without allocating.
+    pub fn to_bytes(&self) -> [u8; MINT_DESCRIPTION_SIZE] {
+        let mut bytes = [0; MINT_DESCRIPTION_SIZE];
+        self.write(&mut bytes[..]).unwrap();
+        bytes
+    }
 }
 
 #[cfg(test)]
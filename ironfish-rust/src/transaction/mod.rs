@@ -2,7 +2,6 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
-use ff::Field;
 use outputs::OutputBuilder;
 use spends::{SpendBuilder, UnsignedSpendDescription};
 use value_balances::ValueBalances;
@@ -13,20 +12,23 @@ use crate::{
         asset_identifier::{AssetIdentifier, NATIVE_ASSET},
     },
     errors::IronfishError,
-    keys::{PublicAddress, SaplingKey},
+    keys::{EphemeralKeyPair, MemoKey, PublicAddress, SaplingKey},
+    metrics::{record_signatures, Circuit},
+    network::NetworkKind,
     note::Note,
-    sapling_bls12::SAPLING,
-    witness::WitnessTrait,
+    parameter_sets::has_parameter_set,
+    personalization::SIGNATURE_HASH_PERSONALIZATION,
+    randomness::{random_scalar, with_rng},
+    screening::screen_output,
+    value_range::{check_posted_value, check_value, ValueContext},
+    witness::{TreeId, WitnessTrait},
     OutputDescription, SpendDescription,
 };
 
-use bellman::groth16::batch::Verifier;
 use blake2b_simd::Params as Blake2b;
-use bls12_381::Bls12;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use group::GroupEncoding;
 use jubjub::ExtendedPoint;
-use rand::{rngs::OsRng, thread_rng};
 
 use ironfish_zkp::{
     constants::{
@@ -37,34 +39,96 @@ use ironfish_zkp::{
 };
 
 use std::{
+    collections::HashMap,
     io::{self, Write},
     iter,
     slice::Iter,
+    time::Instant,
 };
 
 use self::{
+    anchors::AnchorPolicy,
+    block_verifier::BlockVerifier,
     burns::{BurnBuilder, BurnDescription},
+    extra_data::{has_extra_data, ExtraData},
+    limits::{Limits, ReadLimits},
+    memo_policy::MemoPolicy,
     mints::{MintBuilder, MintDescription, UnsignedMintDescription},
+    utils::verify_posted_proof,
+    verification::{FailedComponent, VerificationFailure, VerifiedChecks, VerifyLevel},
 };
 
+pub mod anchors;
+pub mod audit;
+pub mod block_verifier;
+pub mod builder;
+pub mod burn_receipt;
 pub mod burns;
+pub mod classify;
+pub mod conversions;
+pub mod dependencies;
+pub mod explain;
+pub mod extra_data;
+pub mod factory;
+#[cfg(any(test, feature = "testing"))]
+pub mod fault_injection;
+pub mod fee;
+pub mod issuance;
+pub mod limits;
+pub mod memo_policy;
 pub mod mints;
+pub mod ordering;
 pub mod outputs;
+pub mod payment_export;
+pub mod privacy;
+pub mod scheduler;
+mod signature_batch;
+pub mod signing_context;
 pub mod spends;
+pub mod splitting;
+pub mod streaming;
+pub mod template;
 mod utils;
+pub mod verification;
+pub mod verification_cache;
 
 #[cfg(test)]
 mod tests;
 mod value_balances;
 
-const SIGNATURE_HASH_PERSONALIZATION: &[u8; 8] = b"IFsighsh";
 const TRANSACTION_SIGNATURE_VERSION: &[u8; 1] = &[0];
 pub const TRANSACTION_VERSION: u8 = 1;
+
+/// First transaction version whose spends name the tree their anchor belongs
+/// to. Spends in earlier versions always anchor to [`TreeId::NOTES`].
+pub const TREE_ID_TRANSACTION_VERSION: u8 = 2;
+
+/// First transaction version carrying [`ExtraData`], after the burns
+pub const EXTRA_DATA_TRANSACTION_VERSION: u8 = 3;
 pub const TRANSACTION_SIGNATURE_SIZE: usize = 64;
 pub const TRANSACTION_PUBLIC_KEY_SIZE: usize = 32;
 pub const TRANSACTION_EXPIRATION_SIZE: usize = 4;
 pub const TRANSACTION_FEE_SIZE: usize = 8;
 
+/// Version, the number of spends, outputs, mints and burns, fee, expiration
+/// and randomized public key
+pub(crate) const TRANSACTION_HEADER_SIZE: usize =
+    1 + 4 * 8 + TRANSACTION_FEE_SIZE + TRANSACTION_EXPIRATION_SIZE + TRANSACTION_PUBLIC_KEY_SIZE;
+
+/// Whether posted transactions of the given version can be verified by a
+/// node that activated the versions up to `activated_version`: the versions
+/// transactions can be built in, and the ones parameters are registered for,
+/// see [`crate::parameter_sets`], as long as they've been activated. Every
+/// level of verification gates on this, so they all accept the same
+/// versions. Verifiers only accept [`TRANSACTION_VERSION`] unless the node
+/// passes the version its network activated, so later versions can't be
+/// used before the hard fork that introduces them.
+pub(crate) fn is_verifiable_version(version: u8, activated_version: u8) -> bool {
+    version <= activated_version
+        && ((TRANSACTION_VERSION..=EXTRA_DATA_TRANSACTION_VERSION).contains(&version)
+            || has_parameter_set(version))
+}
+
 /// A collection of spend and output proofs that can be signed and verified.
 /// In general, all the spent values should add up to all the output values.
 ///
@@ -105,6 +169,19 @@ pub struct ProposedTransaction {
     /// not expire.
     expiration: u32,
 
+    /// Maximum number of each description this transaction may contain,
+    /// enforced as descriptions are added.
+    limits: Limits,
+
+    /// Checked against the anchors of the spends before any proof is built
+    anchor_policy: Option<AnchorPolicy>,
+
+    /// Checked against the memos of outputs as they're added
+    memo_policy: Option<MemoPolicy>,
+
+    /// Auxiliary data, only carried from [`EXTRA_DATA_TRANSACTION_VERSION`] on
+    extra_data: ExtraData,
+
     /// The key used to sign the transaction and any descriptions that need
     /// signed.
     spender_key: SaplingKey,
@@ -119,8 +196,22 @@ pub struct ProposedTransaction {
 }
 
 impl ProposedTransaction {
+    /// Create a transaction without limits. Panics if the randomness source
+    /// fails, see [`ProposedTransaction::with_limits`] to handle that.
     pub fn new(spender_key: SaplingKey) -> ProposedTransaction {
-        ProposedTransaction {
+        ProposedTransaction::with_limits(spender_key, Limits::default())
+            .expect("the randomness source failed")
+    }
+
+    /// Create a transaction that can't grow beyond the given limits. Change
+    /// notes are added as outputs when posting, so leave room for them.
+    /// Fails with [`IronfishError::RandomnessError`] if the randomness source
+    /// fails.
+    pub fn with_limits(
+        spender_key: SaplingKey,
+        limits: Limits,
+    ) -> Result<ProposedTransaction, IronfishError> {
+        Ok(ProposedTransaction {
             version: TRANSACTION_VERSION,
             spends: vec![],
             outputs: vec![],
@@ -128,9 +219,13 @@ impl ProposedTransaction {
             burns: vec![],
             value_balances: ValueBalances::new(),
             expiration: 0,
+            limits,
+            anchor_policy: None,
+            memo_policy: None,
+            extra_data: ExtraData::new(),
             spender_key,
-            public_key_randomness: jubjub::Fr::random(thread_rng()),
-        }
+            public_key_randomness: random_scalar()?,
+        })
     }
 
     /// Spend the note owned by spender_key at the given witness location.
@@ -139,10 +234,18 @@ impl ProposedTransaction {
         note: Note,
         witness: &dyn WitnessTrait,
     ) -> Result<(), IronfishError> {
-        self.value_balances
-            .add(note.asset_id(), note.value().try_into()?)?;
+        self.limits.check_spends(self.spends.len() + 1)?;
+
+        // Other trees can only be named once transactions carry tree ids
+        if witness.tree_id() != TreeId::NOTES && self.version < TREE_ID_TRANSACTION_VERSION {
+            return Err(IronfishError::InvalidTransactionVersion);
+        }
 
-        self.spends.push(SpendBuilder::new(note, witness));
+        let value = check_value(note.value(), ValueContext::Note)?;
+        let spend = SpendBuilder::new(note, witness)?;
+        self.value_balances.add(spend.note.asset_id(), value)?;
+
+        self.spends.push(spend);
 
         Ok(())
     }
@@ -150,24 +253,88 @@ impl ProposedTransaction {
     /// Create a proof of a new note owned by the recipient in this
     /// transaction.
     pub fn add_output(&mut self, note: Note) -> Result<(), IronfishError> {
+        let note = self.apply_memo_policy(note)?;
+        self.push_output(OutputBuilder::new(note)?)
+    }
+
+    /// Create an output of the note encrypted with the given ephemeral
+    /// secret, as exported by [`ProposedTransaction::output_ephemeral_secrets`],
+    /// so that the encrypted note comes out exactly as it was sent before.
+    ///
+    /// Only ever use a secret again with the very note it was exported for:
+    /// two different notes to the same recipient encrypted with the same
+    /// secret share their encryption key, which reveals both.
+    pub fn add_output_with_ephemeral_secret(
+        &mut self,
+        note: Note,
+        ephemeral_secret: jubjub::Fr,
+    ) -> Result<(), IronfishError> {
+        let note = self.apply_memo_policy(note)?;
+        let ephemeral_keys = EphemeralKeyPair::from_secret(ephemeral_secret);
+        self.push_output(OutputBuilder::with_ephemeral_keys(note, ephemeral_keys)?)
+    }
+
+    fn push_output(&mut self, output: OutputBuilder) -> Result<(), IronfishError> {
+        self.limits.check_outputs(self.outputs.len() + 1)?;
+
+        let value = check_value(output.note.value(), ValueContext::Note)?;
         self.value_balances
-            .subtract(note.asset_id(), note.value().try_into()?)?;
+            .subtract(output.note.asset_id(), value)?;
 
-        self.outputs.push(OutputBuilder::new(note));
+        self.outputs.push(output);
 
         Ok(())
     }
 
     pub fn add_mint(&mut self, asset: Asset, value: u64) -> Result<(), IronfishError> {
-        self.value_balances.add(asset.id(), value.try_into()?)?;
+        self.limits.check_mints(self.mints.len() + 1)?;
+
+        let signed_value = check_value(value, ValueContext::Mint)?;
+        self.value_balances.add(asset.id(), signed_value)?;
 
         self.mints.push(MintBuilder::new(asset, value));
 
         Ok(())
     }
 
+    /// Mint the given value of the asset straight to the recipient, with an
+    /// output of the newly created value in the same transaction. The memo of
+    /// the output is empty, use [`ProposedTransaction::add_mint`] and
+    /// [`ProposedTransaction::add_output`] for anything else.
+    pub fn add_mint_to(
+        &mut self,
+        recipient: PublicAddress,
+        asset: Asset,
+        value: u64,
+    ) -> Result<(), IronfishError> {
+        // Check both limits first, so a mint is never added without its output
+        self.limits.check_mints(self.mints.len() + 1)?;
+        self.limits.check_outputs(self.outputs.len() + 1)?;
+
+        let note = Note::try_new(
+            recipient,
+            value,
+            "",
+            *asset.id(),
+            self.spender_key.public_address(),
+        )?;
+
+        self.add_mint(asset, value)?;
+        self.push_output(OutputBuilder::new(note)?)
+    }
+
     pub fn add_burn(&mut self, asset_id: AssetIdentifier, value: u64) -> Result<(), IronfishError> {
-        self.value_balances.subtract(&asset_id, value.try_into()?)?;
+        self.limits.check_burns(self.burns.len() + 1)?;
+
+        // Burns of the same asset are summed into a single value balance, so
+        // the total has to fit rather than each burn on its own
+        self.burned_value(&asset_id)
+            .checked_add(value)
+            .filter(|total| i64::try_from(*total).is_ok())
+            .ok_or(IronfishError::BurnValueOverflow)?;
+
+        let signed_value = check_value(value, ValueContext::Burn)?;
+        self.value_balances.subtract(&asset_id, signed_value)?;
 
         self.burns.push(BurnBuilder::new(asset_id, value));
 
@@ -189,13 +356,29 @@ impl ProposedTransaction {
         change_goes_to: Option<PublicAddress>,
         intended_transaction_fee: u64,
     ) -> Result<Transaction, IronfishError> {
+        self.check_burn_balances()?;
+        self.add_change_notes(change_goes_to, intended_transaction_fee)?;
+        self.sort_descriptions();
+
+        self._partial_post()
+    }
+
+    /// Add a change note for every asset that spends and mints leave a
+    /// balance of, keeping the fee in the native asset. Fails if the outputs,
+    /// burns and fee are worth more than that.
+    fn add_change_notes(
+        &mut self,
+        change_goes_to: Option<PublicAddress>,
+        intended_transaction_fee: u64,
+    ) -> Result<(), IronfishError> {
+        let fee = check_value(intended_transaction_fee, ValueContext::Fee)?;
         let mut change_notes = vec![];
 
         for (asset_id, value) in self.value_balances.iter() {
             let is_native_asset = asset_id == &NATIVE_ASSET;
 
             let change_amount = match is_native_asset {
-                true => *value - i64::try_from(intended_transaction_fee)?,
+                true => *value - fee,
                 false => *value,
             };
 
@@ -205,23 +388,23 @@ impl ProposedTransaction {
             if change_amount > 0 {
                 let change_address =
                     change_goes_to.unwrap_or_else(|| self.spender_key.public_address());
-                let change_note = Note::new(
+                let change_note = Note::try_new(
                     change_address,
                     change_amount as u64, // we checked it was positive
                     "",
                     *asset_id,
                     self.spender_key.public_address(),
-                );
+                )?;
 
                 change_notes.push(change_note);
             }
         }
 
         for change_note in change_notes {
-            self.add_output(change_note)?;
+            self.push_output(OutputBuilder::new(change_note)?)?;
         }
 
-        self._partial_post()
+        Ok(())
     }
 
     /// Special case for posting a miners fee transaction. Miner fee transactions
@@ -230,6 +413,13 @@ impl ProposedTransaction {
     /// a miner would not accept such a transaction unless it was explicitly set
     /// as the miners fee.
     pub fn post_miners_fee(&mut self) -> Result<Transaction, IronfishError> {
+        self.check_miners_fee()?;
+        self.post_miners_fee_unchecked()
+    }
+
+    /// Make sure the transaction has the shape of a miners fee transaction: a
+    /// single output and nothing else
+    fn check_miners_fee(&self) -> Result<(), IronfishError> {
         if !self.spends.is_empty()
             || self.outputs.len() != 1
             || !self.mints.is_empty()
@@ -237,7 +427,8 @@ impl ProposedTransaction {
         {
             return Err(IronfishError::InvalidMinersFeeTransaction);
         }
-        self.post_miners_fee_unchecked()
+
+        Ok(())
     }
 
     /// Do not call this directly -- see post_miners_fee.
@@ -259,8 +450,130 @@ impl ProposedTransaction {
         self.expiration = sequence;
     }
 
+    /// The transaction serialization version
+    pub fn version(&self) -> u8 {
+        self.version
+    }
+
+    /// Build the transaction in the given format version, which has to
+    /// carry everything already added to it
+    pub fn set_version(&mut self, version: u8) -> Result<(), IronfishError> {
+        let names_trees = self
+            .spends
+            .iter()
+            .any(|spend| spend.tree_id != TreeId::NOTES);
+
+        if !(TRANSACTION_VERSION..=EXTRA_DATA_TRANSACTION_VERSION).contains(&version)
+            || (names_trees && version < TREE_ID_TRANSACTION_VERSION)
+            || (!self.extra_data.is_empty() && !has_extra_data(version))
+        {
+            return Err(IronfishError::InvalidTransactionVersion);
+        }
+
+        self.version = version;
+        Ok(())
+    }
+
+    /// Attach the auxiliary data to the transaction, which has to be of
+    /// [`EXTRA_DATA_TRANSACTION_VERSION`] or later, see
+    /// [`ProposedTransaction::set_version`]
+    pub fn set_extra_data(&mut self, extra_data: ExtraData) -> Result<(), IronfishError> {
+        if !extra_data.is_empty() && !has_extra_data(self.version) {
+            return Err(IronfishError::InvalidTransactionVersion);
+        }
+
+        self.extra_data = extra_data;
+        Ok(())
+    }
+
+    /// Refuse to prove spends whose anchors the policy doesn't accept, see
+    /// [`ProposedTransaction::check_anchors`]
+    pub fn set_anchor_policy(&mut self, policy: Option<AnchorPolicy>) {
+        self.anchor_policy = policy;
+    }
+
+    /// Build the transaction with the given memo key, derived from the
+    /// spender's outgoing view key, so that the spender can find it again by
+    /// its [`Transaction::memo_tag`].
+    pub fn set_memo_key(&mut self, memo_key: &MemoKey) {
+        self.public_key_randomness = memo_key.randomness;
+    }
+
+    /// The ephemeral secret each output's note is encrypted with, in the
+    /// order of the posted transaction's outputs. Change is only added when
+    /// posting, so this should be called afterwards to include it.
+    ///
+    /// A sender that keeps these can decrypt what they sent with
+    /// [`crate::MerkleNote::decrypt_note_with_ephemeral_secret`], or rebuild
+    /// an output with [`ProposedTransaction::add_output_with_ephemeral_secret`].
+    /// They're as sensitive as the notes they encrypt.
+    pub fn output_ephemeral_secrets(&self) -> Vec<jubjub::Fr> {
+        self.outputs
+            .iter()
+            .map(|output| *output.ephemeral_secret())
+            .collect()
+    }
+
+    /// Get the description limits this transaction is built against
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Total value of the given asset burned so far in this transaction
+    fn burned_value(&self, asset_id: &AssetIdentifier) -> u64 {
+        // Can't overflow, since totals are checked as burns are added
+        self.burns
+            .iter()
+            .filter(|burn| burn.asset_id == *asset_id)
+            .map(|burn| burn.value)
+            .sum()
+    }
+
+    /// Make sure no asset is burned for more than was spent and minted of it
+    /// in this transaction. This would otherwise only surface as a generic
+    /// balance error.
+    fn check_burn_balances(&self) -> Result<(), IronfishError> {
+        for burn in self.burns.iter() {
+            let spent: u128 = self
+                .spends
+                .iter()
+                .filter(|spend| spend.note.asset_id == burn.asset_id)
+                .map(|spend| spend.note.value as u128)
+                .sum();
+            let minted: u128 = self
+                .mints
+                .iter()
+                .filter(|mint| *mint.asset.id() == burn.asset_id)
+                .map(|mint| mint.value as u128)
+                .sum();
+
+            if self.burned_value(&burn.asset_id) as u128 > spent + minted {
+                return Err(IronfishError::BurnExceedsBalance);
+            }
+        }
+
+        Ok(())
+    }
+
     // Post transaction without much validation.
     fn _partial_post(&self) -> Result<Transaction, IronfishError> {
+        let descriptions = self.prove()?;
+        self.sign(descriptions)
+    }
+
+    /// Build the proof of every description, and the hash the descriptions
+    /// are signed over
+    fn prove(&self) -> Result<UnsignedDescriptions, IronfishError> {
+        self.check_anchors()?;
+
+        for output in &self.outputs {
+            screen_output(
+                &output.note.owner,
+                output.note.asset_id(),
+                output.note.value,
+            )?;
+        }
+
         // Generate randomized public key
 
         // The public key after randomization has been applied. This is used
@@ -286,6 +599,7 @@ impl ProposedTransaction {
                 &self.spender_key,
                 &self.public_key_randomness,
                 &randomized_public_key,
+                self.version,
             )?);
         }
 
@@ -311,6 +625,28 @@ impl ProposedTransaction {
             &burn_descriptions,
         );
 
+        Ok(UnsignedDescriptions {
+            spends: unsigned_spends,
+            outputs: output_descriptions,
+            mints: unsigned_mints,
+            burns: burn_descriptions,
+            randomized_public_key,
+            signature_hash: data_to_sign,
+        })
+    }
+
+    /// Sign proven descriptions, along with the binding signature that ties
+    /// them to this transaction
+    fn sign(&self, descriptions: UnsignedDescriptions) -> Result<Transaction, IronfishError> {
+        let UnsignedDescriptions {
+            spends: mut unsigned_spends,
+            outputs: output_descriptions,
+            mints: mut unsigned_mints,
+            burns: burn_descriptions,
+            randomized_public_key,
+            signature_hash: data_to_sign,
+        } = descriptions;
+
         // Create and verify binding signature keys
         let (binding_signature_private_key, binding_signature_public_key) =
             self.binding_signature_keys(&unsigned_mints, &burn_descriptions)?;
@@ -343,6 +679,7 @@ impl ProposedTransaction {
             burns: burn_descriptions,
             binding_signature,
             randomized_public_key,
+            extra_data: self.extra_data.clone(),
         })
     }
 
@@ -381,7 +718,7 @@ impl ProposedTransaction {
         for spend in spends {
             spend
                 .description
-                .serialize_signature_fields(&mut hasher)
+                .serialize_signature_fields(&mut hasher, self.version)
                 .unwrap();
         }
 
@@ -399,6 +736,10 @@ impl ProposedTransaction {
             burn.serialize_signature_fields(&mut hasher).unwrap();
         }
 
+        if has_extra_data(self.version) {
+            self.extra_data.write(&mut hasher).unwrap();
+        }
+
         let mut hash_result = [0; 32];
         hash_result[..].clone_from_slice(hasher.finalize().as_ref());
         hash_result
@@ -419,11 +760,13 @@ impl ProposedTransaction {
         data_to_be_signed[TRANSACTION_PUBLIC_KEY_SIZE..]
             .copy_from_slice(transaction_signature_hash);
 
-        Ok(private_key.sign(
-            &data_to_be_signed,
-            &mut OsRng,
-            VALUE_COMMITMENT_RANDOMNESS_GENERATOR,
-        ))
+        with_rng(|rng| {
+            private_key.sign(
+                &data_to_be_signed,
+                rng,
+                VALUE_COMMITMENT_RANDOMNESS_GENERATOR,
+            )
+        })
     }
 
     fn binding_signature_keys(
@@ -485,6 +828,17 @@ impl ProposedTransaction {
     }
 }
 
+/// The descriptions of a [`ProposedTransaction`] once their proofs are built,
+/// waiting for the signatures over `signature_hash`
+struct UnsignedDescriptions {
+    spends: Vec<UnsignedSpendDescription>,
+    outputs: Vec<OutputDescription>,
+    mints: Vec<UnsignedMintDescription>,
+    burns: Vec<BurnDescription>,
+    randomized_public_key: redjubjub::PublicKey,
+    signature_hash: [u8; 32],
+}
+
 /// A transaction that has been published and can be read by anyone, not storing
 /// any of the working data or private keys used in creating the proofs.
 ///
@@ -526,35 +880,59 @@ pub struct Transaction {
     /// `rk` in the literature Calculated from the authorizing key and
     /// the public_key_randomness.
     randomized_public_key: redjubjub::PublicKey,
+
+    /// Auxiliary data, empty before [`EXTRA_DATA_TRANSACTION_VERSION`]
+    extra_data: ExtraData,
 }
 
 impl Transaction {
     /// Load a Transaction from a Read implementation (e.g: socket, file)
     /// This is the main entry-point when reconstructing a serialized transaction
     /// for verifying.
-    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        Self::read_for_network(reader, NetworkKind::Mainnet)
+    }
+
+    /// Load a Transaction whose minted assets belong to the given network.
+    /// Asset identifiers are derived per network, so reading a transaction
+    /// for the wrong network produces one that fails verification.
+    pub fn read_for_network<R: io::Read>(
+        reader: R,
+        network: NetworkKind,
+    ) -> Result<Self, IronfishError> {
+        Self::read_with_limits(reader, network, &ReadLimits::UNLIMITED)
+    }
+
+    /// Load a Transaction from an untrusted source, such as a peer, failing
+    /// as soon as the header shows it exceeds the given limits.
+    pub fn read_with_limits<R: io::Read>(
+        mut reader: R,
+        network: NetworkKind,
+        limits: &ReadLimits,
+    ) -> Result<Self, IronfishError> {
         let version = reader.read_u8()?;
         let num_spends = reader.read_u64::<LittleEndian>()?;
         let num_outputs = reader.read_u64::<LittleEndian>()?;
         let num_mints = reader.read_u64::<LittleEndian>()?;
         let num_burns = reader.read_u64::<LittleEndian>()?;
+        limits.check_header(version, num_spends, num_outputs, num_mints, num_burns)?;
         let fee = reader.read_i64::<LittleEndian>()?;
         let expiration = reader.read_u32::<LittleEndian>()?;
         let randomized_public_key = redjubjub::PublicKey::read(&mut reader)?;
 
         let mut spends = Vec::with_capacity(num_spends as usize);
         for _ in 0..num_spends {
-            spends.push(SpendDescription::read(&mut reader)?);
+            spends.push(SpendDescription::read_for_version(&mut reader, version)?);
         }
 
         let mut outputs = Vec::with_capacity(num_outputs as usize);
         for _ in 0..num_outputs {
-            outputs.push(OutputDescription::read(&mut reader)?);
+            outputs.push(OutputDescription::read_for_version(&mut reader, version)?);
         }
 
         let mut mints = Vec::with_capacity(num_mints as usize);
         for _ in 0..num_mints {
-            mints.push(MintDescription::read(&mut reader)?);
+            mints.push(MintDescription::read_for_network(&mut reader, network)?);
         }
 
         let mut burns = Vec::with_capacity(num_burns as usize);
@@ -562,6 +940,11 @@ impl Transaction {
             burns.push(BurnDescription::read(&mut reader)?);
         }
 
+        let extra_data = match has_extra_data(version) {
+            true => ExtraData::read(&mut reader)?,
+            false => ExtraData::new(),
+        };
+
         let binding_signature = Signature::read(&mut reader)?;
 
         Ok(Transaction {
@@ -574,6 +957,7 @@ impl Transaction {
             binding_signature,
             expiration,
             randomized_public_key,
+            extra_data,
         })
     }
 
@@ -590,7 +974,7 @@ impl Transaction {
         writer.write_all(&self.randomized_public_key.0.to_bytes())?;
 
         for spend in self.spends.iter() {
-            spend.write(&mut writer)?;
+            spend.write_for_version(&mut writer, self.version)?;
         }
 
         for output in self.outputs.iter() {
@@ -605,6 +989,10 @@ impl Transaction {
             burns.write(&mut writer)?;
         }
 
+        if has_extra_data(self.version) {
+            self.extra_data.write(&mut writer)?;
+        }
+
         self.binding_signature.write(&mut writer)?;
 
         Ok(())
@@ -620,7 +1008,206 @@ impl Transaction {
     ///     containing those proofs (and only those proofs)
     ///
     pub fn verify(&self) -> Result<(), IronfishError> {
-        batch_verify_transactions(iter::once(self))
+        self.verify_for_version(TRANSACTION_VERSION)
+    }
+
+    /// Validate the transaction like [`Transaction::verify`], accepting the
+    /// versions up to `activated_version`
+    pub fn verify_for_version(&self, activated_version: u8) -> Result<(), IronfishError> {
+        batch_verify_transactions_for_version(iter::once(self), activated_version)
+    }
+
+    /// Validate the transaction up to the given [`VerifyLevel`], returning
+    /// the checks that were performed. Lower levels skip the proofs (and
+    /// possibly the signatures), so a transaction that passes them is not
+    /// necessarily valid; see [`VerifyLevel`].
+    pub fn verify_with_level(&self, level: VerifyLevel) -> Result<VerifiedChecks, IronfishError> {
+        self.verify_with_level_for_version(level, TRANSACTION_VERSION)
+    }
+
+    /// Validate the transaction like [`Transaction::verify_with_level`],
+    /// accepting the versions up to `activated_version`
+    pub fn verify_with_level_for_version(
+        &self,
+        level: VerifyLevel,
+        activated_version: u8,
+    ) -> Result<VerifiedChecks, IronfishError> {
+        if level == VerifyLevel::Full {
+            self.verify_for_version(activated_version)?;
+
+            return Ok(VerifiedChecks {
+                structure: true,
+                signatures: true,
+                proofs: true,
+            });
+        }
+
+        self.verify_structure(activated_version)?;
+        let mut checks = VerifiedChecks {
+            structure: true,
+            ..VerifiedChecks::default()
+        };
+
+        if level >= VerifyLevel::Signatures {
+            self.verify_signatures()?;
+            checks.signatures = true;
+        }
+
+        Ok(checks)
+    }
+
+    /// Validate the transaction without stopping at the first failure,
+    /// returning every check that failed. An empty list means the
+    /// transaction is valid.
+    ///
+    /// Each proof is verified on its own rather than in a batch, so this is
+    /// much slower than [`Transaction::verify`]. It's meant for debugging
+    /// transactions built by other implementations, not for validating
+    /// blocks.
+    pub fn verify_collect(&self) -> Vec<VerificationFailure> {
+        self.verify_collect_for_version(TRANSACTION_VERSION)
+    }
+
+    /// Collect the failed checks like [`Transaction::verify_collect`],
+    /// accepting the versions up to `activated_version`
+    pub fn verify_collect_for_version(&self, activated_version: u8) -> Vec<VerificationFailure> {
+        let mut failures = vec![];
+        let mut check = |component, result: Result<(), IronfishError>| {
+            if let Err(error) = result {
+                failures.push(VerificationFailure { component, error });
+            }
+        };
+
+        if !is_verifiable_version(self.version, activated_version) {
+            check(
+                FailedComponent::Version,
+                Err(IronfishError::InvalidTransactionVersion),
+            );
+        }
+
+        let mut binding_verification_key = ExtendedPoint::identity();
+
+        let hash_to_verify_signature = self.transaction_signature_hash();
+
+        for (index, spend) in self.spends.iter().enumerate() {
+            let component = FailedComponent::Spend(index);
+            check(component, spend.partial_verify());
+
+            let public_inputs = spend.public_inputs(self.randomized_public_key());
+            check(
+                component,
+                verify_posted_proof(self.version, Circuit::Spend, &spend.proof, &public_inputs),
+            );
+
+            check(
+                component,
+                spend.verify_signature(&hash_to_verify_signature, self.randomized_public_key()),
+            );
+
+            binding_verification_key += spend.value_commitment;
+        }
+
+        for (index, output) in self.outputs.iter().enumerate() {
+            let component = FailedComponent::Output(index);
+            check(component, output.partial_verify());
+
+            let public_inputs = output.public_inputs(self.randomized_public_key());
+            check(
+                component,
+                verify_posted_proof(self.version, Circuit::Output, &output.proof, &public_inputs),
+            );
+
+            binding_verification_key -= output.merkle_note.value_commitment;
+        }
+
+        for (index, mint) in self.mints.iter().enumerate() {
+            let component = FailedComponent::Mint(index);
+            check(component, mint.partial_verify());
+            check(
+                component,
+                check_posted_value(mint.value, ValueContext::Mint)
+                    .map(|_| ())
+                    .map_err(IronfishError::from),
+            );
+
+            let public_inputs = mint.public_inputs(self.randomized_public_key());
+            check(
+                component,
+                verify_posted_proof(self.version, Circuit::Mint, &mint.proof, &public_inputs),
+            );
+
+            check(
+                component,
+                mint.verify_signature(&hash_to_verify_signature, self.randomized_public_key()),
+            );
+        }
+
+        for (index, burn) in self.burns.iter().enumerate() {
+            check(
+                FailedComponent::Burn(index),
+                check_posted_value(burn.value, ValueContext::Burn)
+                    .map(|_| ())
+                    .map_err(IronfishError::from),
+            );
+        }
+
+        check(
+            FailedComponent::BindingSignature,
+            self.verify_binding_signature(&binding_verification_key),
+        );
+
+        failures
+    }
+
+    /// Check the transaction version and every description for anything
+    /// besides its proof and signature.
+    fn verify_structure(&self, activated_version: u8) -> Result<(), IronfishError> {
+        if !is_verifiable_version(self.version, activated_version) {
+            return Err(IronfishError::InvalidTransactionVersion);
+        }
+
+        for spend in self.spends.iter() {
+            spend.partial_verify()?;
+        }
+
+        for output in self.outputs.iter() {
+            output.partial_verify()?;
+        }
+
+        for mint in self.mints.iter() {
+            mint.partial_verify()?;
+            check_posted_value(mint.value, ValueContext::Mint)?;
+        }
+
+        for burn in self.burns.iter() {
+            check_posted_value(burn.value, ValueContext::Burn)?;
+        }
+
+        Ok(())
+    }
+
+    /// Check the spend and mint signatures as well as the binding signature,
+    /// without verifying any of the proofs.
+    fn verify_signatures(&self) -> Result<(), IronfishError> {
+        let mut binding_verification_key = ExtendedPoint::identity();
+
+        let hash_to_verify_signature = self.transaction_signature_hash();
+
+        for spend in self.spends.iter() {
+            binding_verification_key += spend.value_commitment;
+
+            spend.verify_signature(&hash_to_verify_signature, self.randomized_public_key())?;
+        }
+
+        for output in self.outputs.iter() {
+            binding_verification_key -= output.merkle_note.value_commitment;
+        }
+
+        for mint in self.mints.iter() {
+            mint.verify_signature(&hash_to_verify_signature, self.randomized_public_key())?;
+        }
+
+        self.verify_binding_signature(&binding_verification_key)
     }
 
     /// Get an iterator over the spends in this transaction. Each spend
@@ -642,14 +1229,60 @@ impl Transaction {
         &self.outputs
     }
 
+    /// Get an iterator over the mints in this transaction, by reference
+    pub fn iter_mints(&self) -> Iter<MintDescription> {
+        self.mints.iter()
+    }
+
     pub fn mints(&self) -> &Vec<MintDescription> {
         &self.mints
     }
 
+    /// Get an iterator over the burns in this transaction, by reference
+    pub fn iter_burns(&self) -> Iter<BurnDescription> {
+        self.burns.iter()
+    }
+
     pub fn burns(&self) -> &Vec<BurnDescription> {
         &self.burns
     }
 
+    /// Total value minted of each asset in this transaction
+    pub fn minted_by_asset(&self) -> HashMap<AssetIdentifier, u128> {
+        let mut totals = HashMap::new();
+        for mint in self.mints.iter() {
+            *totals.entry(*mint.asset_id()).or_insert(0) += mint.value as u128;
+        }
+        totals
+    }
+
+    /// Total value burned of each asset in this transaction
+    pub fn burned_by_asset(&self) -> HashMap<AssetIdentifier, u128> {
+        let mut totals = HashMap::new();
+        for burn in self.burns.iter() {
+            *totals.entry(burn.asset_id).or_insert(0) += burn.value as u128;
+        }
+        totals
+    }
+
+    /// Total value of the given asset minted in this transaction
+    pub fn minted_value(&self, asset_id: &AssetIdentifier) -> u128 {
+        self.mints
+            .iter()
+            .filter(|mint| mint.asset_id() == asset_id)
+            .map(|mint| mint.value as u128)
+            .sum()
+    }
+
+    /// Total value of the given asset burned in this transaction
+    pub fn burned_value(&self, asset_id: &AssetIdentifier) -> u128 {
+        self.burns
+            .iter()
+            .filter(|burn| burn.asset_id == *asset_id)
+            .map(|burn| burn.value as u128)
+            .sum()
+    }
+
     /// Get the transaction fee for this transaction. Miners should generally
     /// expect this to be positive (or they would lose money mining it!).
     /// The miners_fee transaction would be a special case.
@@ -672,6 +1305,21 @@ impl Transaction {
         &self.randomized_public_key
     }
 
+    /// Hash of the whole serialized transaction. Unlike
+    /// [`Transaction::transaction_signature_hash`], it covers the proofs and
+    /// signatures, so two transactions with the same hash are identical.
+    pub fn hash(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        self.write(&mut hasher).unwrap();
+        *hasher.finalize().as_bytes()
+    }
+
+    /// The tag to index this transaction by, which the spender can recompute
+    /// with [`MemoKey::tag`] if the transaction was built with a memo key.
+    pub fn memo_tag(&self) -> [u8; 32] {
+        self.randomized_public_key.0.to_bytes()
+    }
+
     /// Calculate a hash of the transaction data. This hash was signed by the
     /// private keys when the transaction was constructed, and will now be
     /// reconstructed to verify the signature.
@@ -689,7 +1337,9 @@ impl Transaction {
             .unwrap();
 
         for spend in self.spends.iter() {
-            spend.serialize_signature_fields(&mut hasher).unwrap();
+            spend
+                .serialize_signature_fields(&mut hasher, self.version)
+                .unwrap();
         }
 
         for output in self.outputs.iter() {
@@ -704,6 +1354,10 @@ impl Transaction {
             burn.serialize_signature_fields(&mut hasher).unwrap();
         }
 
+        if has_extra_data(self.version) {
+            self.extra_data.write(&mut hasher).unwrap();
+        }
+
         let mut hash_result = [0; 32];
         hash_result[..].clone_from_slice(hasher.finalize().as_ref());
         hash_result
@@ -722,11 +1376,15 @@ impl Transaction {
         data_to_verify_signature[..32].copy_from_slice(&value_balance.to_bytes());
         data_to_verify_signature[32..].copy_from_slice(&self.transaction_signature_hash());
 
-        if !redjubjub::PublicKey(value_balance).verify(
+        let start = Instant::now();
+        let is_valid = redjubjub::PublicKey(value_balance).verify(
             &data_to_verify_signature,
             &self.binding_signature,
             VALUE_COMMITMENT_RANDOMNESS_GENERATOR,
-        ) {
+        );
+        record_signatures(1, start.elapsed(), is_valid);
+
+        if !is_valid {
             return Err(IronfishError::VerificationFailed);
         }
 
@@ -769,79 +1427,39 @@ fn calculate_value_balance(
     let mut value_balance_point = binding_verification_key - fee_point;
 
     for mint in mints {
-        let mint_generator = mint.asset.value_commitment_generator();
+        let mint_generator = mint.asset.value_commitment_generator().point();
         value_balance_point += mint_generator * jubjub::Fr::from(mint.value);
     }
 
     for burn in burns {
-        let burn_generator = burn.asset_id.value_commitment_generator();
+        let burn_generator = burn.asset_id.value_commitment_generator().point();
         value_balance_point -= burn_generator * jubjub::Fr::from(burn.value);
     }
 
     Ok(value_balance_point)
 }
 
+/// Verify many transactions, such as the ones in a block, at once. The proofs
+/// of each circuit and the signatures are checked in batches, which is much
+/// faster than verifying each transaction, but a failure doesn't say which
+/// transaction is invalid. See [`BlockVerifier`] to verify them in bounded
+/// memory.
 pub fn batch_verify_transactions<'a>(
     transactions: impl IntoIterator<Item = &'a Transaction>,
 ) -> Result<(), IronfishError> {
-    let mut spend_verifier = Verifier::<Bls12>::new();
-    let mut output_verifier = Verifier::<Bls12>::new();
-    let mut mint_verifier = Verifier::<Bls12>::new();
+    batch_verify_transactions_for_version(transactions, TRANSACTION_VERSION)
+}
 
+/// Verify many transactions at once like [`batch_verify_transactions`],
+/// accepting the versions up to `activated_version`
+pub fn batch_verify_transactions_for_version<'a>(
+    transactions: impl IntoIterator<Item = &'a Transaction>,
+    activated_version: u8,
+) -> Result<(), IronfishError> {
+    let mut verifier = BlockVerifier::unbounded().with_activated_version(activated_version);
     for transaction in transactions {
-        // Currently only support version 1 transactions, the version
-        // field is here for future updates
-        if transaction.version != TRANSACTION_VERSION {
-            return Err(IronfishError::InvalidTransactionVersion);
-        }
-
-        // Context to accumulate a signature of all the spends and outputs and
-        // guarantee they are part of this transaction, unmodified.
-        let mut binding_verification_key = ExtendedPoint::identity();
-
-        let hash_to_verify_signature = transaction.transaction_signature_hash();
-
-        for spend in transaction.spends.iter() {
-            spend.partial_verify()?;
-
-            let public_inputs = spend.public_inputs(transaction.randomized_public_key());
-            spend_verifier.queue((&spend.proof, &public_inputs[..]));
-
-            binding_verification_key += spend.value_commitment;
-
-            spend.verify_signature(
-                &hash_to_verify_signature,
-                transaction.randomized_public_key(),
-            )?;
-        }
-
-        for output in transaction.outputs.iter() {
-            output.partial_verify()?;
-
-            let public_inputs = output.public_inputs(transaction.randomized_public_key());
-            output_verifier.queue((&output.proof, &public_inputs[..]));
-
-            binding_verification_key -= output.merkle_note.value_commitment;
-        }
-
-        for mint in transaction.mints.iter() {
-            mint.partial_verify()?;
-
-            let public_inputs = mint.public_inputs(transaction.randomized_public_key());
-            mint_verifier.queue((&mint.proof, &public_inputs[..]));
-
-            mint.verify_signature(
-                &hash_to_verify_signature,
-                transaction.randomized_public_key(),
-            )?;
-        }
-
-        transaction.verify_binding_signature(&binding_verification_key)?;
+        verifier.add_transaction(transaction)?;
     }
 
-    spend_verifier.verify(&mut OsRng, &SAPLING.spend_params.vk)?;
-    output_verifier.verify(&mut OsRng, &SAPLING.output_params.vk)?;
-    mint_verifier.verify(&mut OsRng, &SAPLING.mint_params.vk)?;
-
-    Ok(())
+    verifier.finish()
 }
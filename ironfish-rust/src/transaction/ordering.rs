@@ -0,0 +1,163 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Canonical ordering of the descriptions of a transaction.
+//!
+//! Descriptions are signed in the order they're in, so the order a wallet
+//! adds them in shows in every transaction it posts: the payment first and
+//! the change last, say, tells which software built the transaction and
+//! which output is the change. Posting sorts each kind of description by
+//! what it makes public, spends by nullifier, outputs by note commitment,
+//! mints and burns by asset identifier and value, which looks like a random
+//! order and is the same whichever wallet built the transaction.
+//!
+//! The order isn't a consensus rule, transactions in any order are valid.
+//! Once posted the order can't be changed either, since the signature hash
+//! covers it. [`is_canonically_ordered`] checks a posted transaction, for
+//! tests of wallets built on this crate for instance.
+
+use crate::assets::asset_identifier::AssetIdentifier;
+
+use super::{ProposedTransaction, Transaction};
+
+/// Mints and burns are sorted by asset, then by value
+fn asset_key(asset_id: &AssetIdentifier, value: u64) -> ([u8; 32], u64) {
+    (*asset_id.as_bytes(), value)
+}
+
+fn is_sorted_by_key<T, K: Ord>(items: &[T], key: impl Fn(&T) -> K) -> bool {
+    items.windows(2).all(|pair| key(&pair[0]) <= key(&pair[1]))
+}
+
+/// Whether the descriptions of the transaction are in the order
+/// [`ProposedTransaction::sort_descriptions`] puts them in
+pub fn is_canonically_ordered(transaction: &Transaction) -> bool {
+    is_sorted_by_key(&transaction.spends, |spend| spend.nullifier().0)
+        && is_sorted_by_key(&transaction.outputs, |output| {
+            output.merkle_note.note_commitment.to_bytes()
+        })
+        && is_sorted_by_key(&transaction.mints, |mint| {
+            asset_key(mint.asset_id(), mint.value())
+        })
+        && is_sorted_by_key(&transaction.burns, |burn| {
+            asset_key(burn.asset_id(), burn.value())
+        })
+}
+
+impl ProposedTransaction {
+    /// Sort the descriptions added so far into the canonical order. Posting
+    /// does this once change is added, so this only has to be called to see
+    /// the order beforehand.
+    pub fn sort_descriptions(&mut self) {
+        let view_key = &self.spender_key.view_key;
+        self.spends
+            .sort_by_cached_key(|spend| spend.note.nullifier(view_key, spend.witness_position).0);
+        self.outputs
+            .sort_by_cached_key(|output| output.note.commitment());
+        self.mints
+            .sort_by_key(|mint| asset_key(mint.asset.id(), mint.value));
+        self.burns
+            .sort_by_key(|burn| asset_key(&burn.asset_id, burn.value));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::is_canonically_ordered;
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, transaction::ProposedTransaction, Transaction,
+    };
+
+    fn notes(key: &SaplingKey, values: &[u64]) -> Vec<Note> {
+        values
+            .iter()
+            .map(|&value| {
+                Note::new(
+                    key.public_address(),
+                    value,
+                    "",
+                    NATIVE_ASSET,
+                    key.public_address(),
+                )
+            })
+            .collect()
+    }
+
+    /// Propose spending `spends` into `outputs`, added in the given order,
+    /// with a fee that leaves no change
+    fn propose(key: &SaplingKey, spends: &[Note], outputs: &[Note]) -> ProposedTransaction {
+        let mut proposed = ProposedTransaction::new(key.clone());
+        for note in spends {
+            proposed
+                .add_spend(note.clone(), &make_fake_witness(note))
+                .unwrap();
+        }
+        for note in outputs {
+            proposed.add_output(note.clone()).unwrap();
+        }
+        proposed
+    }
+
+    #[test]
+    fn test_posting_order_is_independent_of_insertion_order() {
+        let key = SaplingKey::generate_key();
+        let spends = notes(&key, &[10, 20]);
+        let outputs = notes(&key, &[5, 6, 7]);
+        let fee = 12;
+
+        let posted = propose(&key, &spends, &outputs).post(None, fee).unwrap();
+        posted.verify().unwrap();
+        assert!(is_canonically_ordered(&posted));
+
+        let reversed_spends: Vec<_> = spends.iter().rev().cloned().collect();
+        let reversed_outputs: Vec<_> = outputs.iter().rev().cloned().collect();
+        let reversed = propose(&key, &reversed_spends, &reversed_outputs)
+            .post(None, fee)
+            .unwrap();
+        assert!(is_canonically_ordered(&reversed));
+
+        let nullifiers = |transaction: &Transaction| -> Vec<_> {
+            transaction
+                .spends()
+                .iter()
+                .map(|spend| spend.nullifier())
+                .collect()
+        };
+        let commitments = |transaction: &Transaction| -> Vec<_> {
+            transaction
+                .outputs()
+                .iter()
+                .map(|output| output.merkle_note().merkle_hash())
+                .collect()
+        };
+        assert_eq!(nullifiers(&posted), nullifiers(&reversed));
+        assert_eq!(commitments(&posted), commitments(&reversed));
+    }
+
+    #[test]
+    fn test_reordering_posted_descriptions_invalidates_the_transaction() {
+        let key = SaplingKey::generate_key();
+        let spends = notes(&key, &[10]);
+        let mut outputs = notes(&key, &[3, 4]);
+
+        // Added in the reverse of the canonical order, and posted without
+        // sorting: any order is valid
+        outputs.sort_by_key(|note| std::cmp::Reverse(note.commitment()));
+        let proposed = propose(&key, &spends, &outputs);
+        let mut transaction = proposed._partial_post().unwrap();
+        transaction.verify().unwrap();
+        assert!(!is_canonically_ordered(&transaction));
+
+        // But the order is signed, so it can't be changed afterwards
+        let signature_hash = transaction.transaction_signature_hash();
+        transaction.outputs.swap(0, 1);
+        assert!(is_canonically_ordered(&transaction));
+        assert_ne!(transaction.transaction_signature_hash(), signature_hash);
+        assert!(matches!(
+            transaction.verify(),
+            Err(IronfishError::VerificationFailed)
+        ));
+    }
+}
@@ -5,9 +5,9 @@
 use crate::{
     errors::IronfishError,
     keys::{EphemeralKeyPair, SaplingKey},
-    merkle_note::MerkleNote,
+    merkle_note::{MerkleNote, MERKLE_NOTE_SIZE},
     note::Note,
-    sapling_bls12::SAPLING,
+    proof_system::proof_system,
 };
 
 use bellman::groth16;
@@ -15,11 +15,13 @@ use bls12_381::{Bls12, Scalar};
 use group::Curve;
 use ironfish_zkp::{primitives::ValueCommitment, proofs::Output, redjubjub};
 use jubjub::ExtendedPoint;
-use rand::thread_rng;
 
 use std::io;
 
-use super::utils::verify_output_proof;
+use super::{
+    utils::{new_value_commitment, verify_output_proof},
+    TRANSACTION_VERSION,
+};
 
 /// Parameters used when constructing proof that a new note exists. The owner
 /// of this note is the recipient of funds in a transaction. The note is signed
@@ -36,20 +38,43 @@ pub struct OutputBuilder {
     /// transaction. Not used directly here, but passed down into the
     /// [`MerkleNote`].
     is_miners_fee: bool,
+
+    /// Diffie Hellman keys the note is encrypted with. They're picked up
+    /// front, rather than when building the proof, so the secret can be
+    /// exported. See [`super::ProposedTransaction::output_ephemeral_secrets`].
+    ephemeral_keys: EphemeralKeyPair,
 }
 
 pub const PROOF_SIZE: u32 = 192;
 
+/// Size of a serialized [`OutputDescription`]: proof and merkle note.
+pub const OUTPUT_DESCRIPTION_SIZE: usize = PROOF_SIZE as usize + MERKLE_NOTE_SIZE;
+
 impl OutputBuilder {
     /// Create a new [`OutputBuilder`] attempting to create a note.
-    pub(crate) fn new(note: Note) -> Self {
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+    pub(crate) fn new(note: Note) -> Result<Self, IronfishError> {
+        OutputBuilder::with_ephemeral_keys(note, EphemeralKeyPair::try_new()?)
+    }
+
+    /// Create a new [`OutputBuilder`] that encrypts the note with the given
+    /// Diffie Hellman keys.
+    pub(crate) fn with_ephemeral_keys(
+        note: Note,
+        ephemeral_keys: EphemeralKeyPair,
+    ) -> Result<Self, IronfishError> {
+        let value_commitment = new_value_commitment(note.value, note.asset_generator().point())?;
 
-        Self {
+        Ok(Self {
             note,
             value_commitment,
             is_miners_fee: false,
-        }
+            ephemeral_keys,
+        })
+    }
+
+    /// The secret of the Diffie Hellman keys the note is encrypted with
+    pub(crate) fn ephemeral_secret(&self) -> &jubjub::Fr {
+        self.ephemeral_keys.secret()
     }
 
     /// Sets the `is_miners_fee` flag to true, indicating that this output is to
@@ -78,8 +103,9 @@ impl OutputBuilder {
         spender_key: &SaplingKey,
         public_key_randomness: &jubjub::Fr,
         randomized_public_key: &redjubjub::PublicKey,
+        version: u8,
     ) -> Result<OutputDescription, IronfishError> {
-        let diffie_hellman_keys = EphemeralKeyPair::new();
+        let diffie_hellman_keys = &self.ephemeral_keys;
 
         let circuit = Output {
             value_commitment: Some(self.value_commitment.clone()),
@@ -91,16 +117,21 @@ impl OutputBuilder {
             ar: Some(*public_key_randomness),
         };
 
-        let proof =
-            groth16::create_random_proof(circuit, &SAPLING.output_params, &mut thread_rng())?;
+        let proof = proof_system().prove_output(circuit)?;
         let merkle_note = if self.is_miners_fee {
-            MerkleNote::new_for_miners_fee(&self.note, &self.value_commitment, &diffie_hellman_keys)
+            MerkleNote::new_for_miners_fee(
+                &self.note,
+                &self.value_commitment,
+                diffie_hellman_keys,
+                version,
+            )
         } else {
-            MerkleNote::new(
+            MerkleNote::new_for_version(
                 spender_key,
                 &self.note,
                 &self.value_commitment,
-                &diffie_hellman_keys,
+                diffie_hellman_keys,
+                version,
             )
         };
 
@@ -136,9 +167,18 @@ impl OutputDescription {
     /// Load an [`OutputDescription`] from a Read implementation( e.g: socket, file)
     /// This is the main entry-point when reconstructing a serialized
     /// transaction.
-    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        OutputDescription::read_for_version(reader, TRANSACTION_VERSION)
+    }
+
+    /// Load an [`OutputDescription`] of a transaction of the given version,
+    /// whose note is encrypted as notes of that version are
+    pub fn read_for_version<R: io::Read>(
+        mut reader: R,
+        version: u8,
+    ) -> Result<Self, IronfishError> {
         let proof = groth16::Proof::read(&mut reader)?;
-        let merkle_note = MerkleNote::read(&mut reader)?;
+        let merkle_note = MerkleNote::read_for_version(&mut reader, version)?;
 
         Ok(OutputDescription { proof, merkle_note })
     }
@@ -148,6 +188,14 @@ impl OutputDescription {
         self.serialize_signature_fields(writer)
     }
 
+    /// The serialized form of this [`OutputDescription`], as written by
+    /// [`OutputDescription::write`], without allocating.
+    pub fn to_bytes(&self) -> [u8; OUTPUT_DESCRIPTION_SIZE] {
+        let mut bytes = [0; OUTPUT_DESCRIPTION_SIZE];
+        self.write(&mut bytes[..]).unwrap();
+        bytes
+    }
+
     /// A function to encapsulate any verification besides the proof itself.
     /// This allows us to abstract away the details and make it easier to work
     /// with. Note that this does not verify the proof, that happens in the
@@ -219,9 +267,11 @@ impl OutputDescription {
 mod test {
     use super::{OutputBuilder, OutputDescription};
     use crate::{
-        assets::asset_identifier::NATIVE_ASSET, keys::SaplingKey,
-        merkle_note::NOTE_ENCRYPTION_MINER_KEYS, note::Note,
-        transaction::utils::verify_output_proof,
+        assets::asset_identifier::NATIVE_ASSET,
+        keys::SaplingKey,
+        merkle_note::NOTE_ENCRYPTION_MINER_KEYS,
+        note::Note,
+        transaction::{utils::verify_output_proof, TRANSACTION_VERSION},
     };
     use ff::{Field, PrimeField};
     use group::Curve;
@@ -247,11 +297,16 @@ mod test {
             spender_key.public_address(),
         );
 
-        let mut output = OutputBuilder::new(note);
+        let mut output = OutputBuilder::new(note).unwrap();
         output.set_is_miners_fee();
 
         let proof = output
-            .build(&spender_key, &public_key_randomness, &randomized_public_key)
+            .build(
+                &spender_key,
+                &public_key_randomness,
+                &randomized_public_key,
+                TRANSACTION_VERSION,
+            )
             .expect("should be able to build output proof");
 
         assert_eq!(
@@ -277,9 +332,14 @@ mod test {
             spender_key.public_address(),
         );
 
-        let output = OutputBuilder::new(note);
+        let output = OutputBuilder::new(note).unwrap();
         let proof = output
-            .build(&spender_key, &public_key_randomness, &randomized_public_key)
+            .build(
+                &spender_key,
+                &public_key_randomness,
+                &randomized_public_key,
+                TRANSACTION_VERSION,
+            )
             .expect("should be able to build output proof");
 
         assert_ne!(
@@ -305,9 +365,14 @@ mod test {
             spender_key.public_address(),
         );
 
-        let output = OutputBuilder::new(note);
+        let output = OutputBuilder::new(note).unwrap();
         let proof = output
-            .build(&spender_key, &public_key_randomness, &randomized_public_key)
+            .build(
+                &spender_key,
+                &public_key_randomness,
+                &randomized_public_key,
+                TRANSACTION_VERSION,
+            )
             .expect("Should be able to build output proof");
         verify_output_proof(&proof.proof, &proof.public_inputs(&randomized_public_key))
             .expect("proof should check out");
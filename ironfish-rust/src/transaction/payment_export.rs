@@ -0,0 +1,202 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Export of the payments an account sent, for accounting and tax reporting.
+//!
+//! Payments are found by decrypting every output of the given transactions
+//! with the account's outgoing view key. Change is left out: any note the
+//! account sent to its own address is treated as change, so payments to
+//! itself don't appear either. Enable the `serde` feature to serialize
+//! payments, or write them as CSV with [`write_payments_csv`].
+
+use std::io;
+
+use crate::{
+    canonical_json::{CanonicalObject, CanonicalValue, ToCanonicalJson},
+    errors::IronfishError,
+    keys::OutgoingViewKey,
+    serializing::bytes_to_hex,
+};
+
+use super::{explain::trim_nulls, Transaction};
+
+/// Columns of the CSV written by [`write_payments_csv`], in order
+pub const PAYMENTS_CSV_HEADER: &str =
+    "transaction_hash,output_index,recipient,asset_id,amount,memo";
+
+/// A single note sent to someone else
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct OutgoingPayment {
+    /// Signature hash of the transaction, which nodes use as its hash
+    pub transaction_hash: String,
+
+    /// Position of the note among the outputs of the transaction
+    pub output_index: usize,
+
+    pub recipient: String,
+    pub asset_id: String,
+
+    /// Value of the note, in the smallest unit of the asset
+    pub amount: u64,
+
+    pub memo: String,
+}
+
+impl ToCanonicalJson for OutgoingPayment {
+    fn to_canonical_value(&self) -> CanonicalValue {
+        CanonicalObject::new()
+            .field("transaction_hash", self.transaction_hash.as_str())
+            .field("output_index", self.output_index)
+            .field("recipient", self.recipient.as_str())
+            .field("asset_id", self.asset_id.as_str())
+            .field("amount", self.amount)
+            .field("memo", self.memo.as_str())
+            .build()
+    }
+}
+
+/// Every payment sent with the given outgoing view key in the given
+/// transactions, in the order of the transactions and their outputs
+pub fn outgoing_payments<'a, I>(
+    outgoing_view_key: &OutgoingViewKey,
+    transactions: I,
+) -> Vec<OutgoingPayment>
+where
+    I: IntoIterator<Item = &'a Transaction>,
+{
+    let mut payments = vec![];
+
+    for transaction in transactions {
+        let transaction_hash = bytes_to_hex(&transaction.transaction_signature_hash());
+
+        for (output_index, output) in transaction.outputs.iter().enumerate() {
+            let note = match output
+                .merkle_note
+                .decrypt_note_for_spender(outgoing_view_key)
+            {
+                Ok(note) => note,
+                Err(_) => continue,
+            };
+
+            if note.owner == note.sender {
+                continue;
+            }
+
+            payments.push(OutgoingPayment {
+                transaction_hash: transaction_hash.clone(),
+                output_index,
+                recipient: note.owner.hex_public_address(),
+                asset_id: bytes_to_hex(note.asset_id.as_bytes()),
+                amount: note.value,
+                memo: trim_nulls(&note.memo.0),
+            });
+        }
+    }
+
+    payments
+}
+
+/// Write the payments as CSV, starting with [`PAYMENTS_CSV_HEADER`]. Memos
+/// are quoted when they contain commas, quotes or line breaks.
+pub fn write_payments_csv<W: io::Write>(
+    payments: &[OutgoingPayment],
+    mut writer: W,
+) -> Result<(), IronfishError> {
+    writeln!(writer, "{}", PAYMENTS_CSV_HEADER)?;
+
+    for payment in payments {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{}",
+            payment.transaction_hash,
+            payment.output_index,
+            payment.recipient,
+            payment.asset_id,
+            payment.amount,
+            csv_field(&payment.memo)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(|c| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{outgoing_payments, write_payments_csv, PAYMENTS_CSV_HEADER};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, keys::SaplingKey, note::Note,
+        serializing::bytes_to_hex, test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+
+    #[test]
+    fn test_outgoing_payments() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+
+        let in_note = Note::new(
+            spender_key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+        let out_note = Note::new(
+            receiver_key.public_address(),
+            40,
+            "invoice 7, \"rent\"",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+
+        let mut proposed = ProposedTransaction::new(spender_key.clone());
+        proposed.add_spend(in_note, &witness).unwrap();
+        proposed.add_output(out_note).unwrap();
+        // Leaves 1 ore of change
+        let transaction = proposed.post(None, 1).unwrap();
+        assert_eq!(transaction.outputs().len(), 2);
+
+        // The change isn't a payment
+        let payments = outgoing_payments(spender_key.outgoing_view_key(), [&transaction]);
+        assert_eq!(payments.len(), 1);
+        assert_eq!(
+            payments[0].transaction_hash,
+            bytes_to_hex(&transaction.transaction_signature_hash())
+        );
+        assert_eq!(
+            payments[0].recipient,
+            receiver_key.public_address().hex_public_address()
+        );
+        assert_eq!(payments[0].amount, 40);
+        assert_eq!(payments[0].memo, "invoice 7, \"rent\"");
+
+        // Nothing was sent by the receiver
+        assert!(outgoing_payments(receiver_key.outgoing_view_key(), [&transaction]).is_empty());
+
+        let mut csv = vec![];
+        write_payments_csv(&payments, &mut csv).unwrap();
+        let csv = String::from_utf8(csv).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(lines[0], PAYMENTS_CSV_HEADER);
+        assert_eq!(
+            lines[1],
+            format!(
+                "{},{},{},{},40,\"invoice 7, \"\"rent\"\"\"",
+                payments[0].transaction_hash,
+                payments[0].output_index,
+                payments[0].recipient,
+                bytes_to_hex(NATIVE_ASSET.as_bytes())
+            )
+        );
+    }
+}
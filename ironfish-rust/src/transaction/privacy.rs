@@ -0,0 +1,156 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Heuristics that flag transactions whose shape leaks information despite
+//! the encrypted notes. None of these make a transaction invalid; they're
+//! meant to be shown to the user, or acted on by the wallet, before posting.
+
+use std::collections::BTreeMap;
+
+use crate::keys::PublicAddress;
+
+use super::ProposedTransaction;
+
+/// Values that are a multiple of this many ore (0.01 $IRON) are considered
+/// round
+pub const ROUND_VALUE_UNIT: u64 = 1_000_000;
+
+/// Fees with more significant digits than this stand out from the fees
+/// wallets usually pick
+pub const MAX_FEE_SIGNIFICANT_DIGITS: u32 = 2;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrivacyFinding {
+    /// One note is spent to pay one round value. The change, if any, is then
+    /// easy to tell apart from the payment, and so is the amount paid.
+    SingleSpendSingleRoundOutput { value: u64 },
+
+    /// Several outputs pay the same address, linking them together. Indices
+    /// are in the order the outputs were added.
+    AddressReuse {
+        address: PublicAddress,
+        output_indices: Vec<usize>,
+    },
+
+    /// The fee is unusual enough to fingerprint the wallet or user that
+    /// picked it.
+    DistinctiveFee { fee: u64 },
+}
+
+impl ProposedTransaction {
+    /// Look for patterns in the descriptions added so far, and the fee that
+    /// is going to be used to post, that degrade the privacy of the
+    /// transaction. Change notes aren't known until posting, so they aren't
+    /// considered.
+    pub fn analyze_privacy(&self, intended_transaction_fee: u64) -> Vec<PrivacyFinding> {
+        let mut findings = vec![];
+
+        if self.spends.len() == 1 && self.outputs.len() == 1 {
+            let value = self.outputs[0].note.value;
+            if value > 0 && value % ROUND_VALUE_UNIT == 0 {
+                findings.push(PrivacyFinding::SingleSpendSingleRoundOutput { value });
+            }
+        }
+
+        let mut outputs_by_owner: BTreeMap<[u8; 32], Vec<usize>> = BTreeMap::new();
+        for (index, output) in self.outputs.iter().enumerate() {
+            outputs_by_owner
+                .entry(output.note.owner.public_address())
+                .or_default()
+                .push(index);
+        }
+        for output_indices in outputs_by_owner.into_values() {
+            if output_indices.len() > 1 {
+                findings.push(PrivacyFinding::AddressReuse {
+                    address: self.outputs[output_indices[0]].note.owner,
+                    output_indices,
+                });
+            }
+        }
+
+        if significant_digits(intended_transaction_fee) > MAX_FEE_SIGNIFICANT_DIGITS {
+            findings.push(PrivacyFinding::DistinctiveFee {
+                fee: intended_transaction_fee,
+            });
+        }
+
+        findings
+    }
+}
+
+fn significant_digits(mut value: u64) -> u32 {
+    if value == 0 {
+        return 0;
+    }
+
+    while value % 10 == 0 {
+        value /= 10;
+    }
+
+    value.to_string().len() as u32
+}
+
+#[cfg(test)]
+mod test {
+    use super::{significant_digits, PrivacyFinding, ROUND_VALUE_UNIT};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, keys::SaplingKey, note::Note,
+        test_util::make_fake_witness, ProposedTransaction,
+    };
+
+    #[test]
+    fn test_significant_digits() {
+        assert_eq!(significant_digits(0), 0);
+        assert_eq!(significant_digits(1), 1);
+        assert_eq!(significant_digits(25_000), 2);
+        assert_eq!(significant_digits(123_457), 6);
+    }
+
+    #[test]
+    fn test_analyze_privacy() {
+        let spender_key = SaplingKey::generate_key();
+        let receiver_key = SaplingKey::generate_key();
+
+        let in_note = Note::new(
+            spender_key.public_address(),
+            10 * ROUND_VALUE_UNIT + 7,
+            "",
+            NATIVE_ASSET,
+            receiver_key.public_address(),
+        );
+        let out_note = Note::new(
+            receiver_key.public_address(),
+            5 * ROUND_VALUE_UNIT,
+            "",
+            NATIVE_ASSET,
+            spender_key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        let mut transaction = ProposedTransaction::new(spender_key);
+        transaction.add_spend(in_note, &witness).unwrap();
+        transaction.add_output(out_note.clone()).unwrap();
+
+        assert_eq!(
+            transaction.analyze_privacy(10),
+            vec![PrivacyFinding::SingleSpendSingleRoundOutput {
+                value: 5 * ROUND_VALUE_UNIT
+            }]
+        );
+
+        // A second output to the same address removes the round single
+        // output pattern, but links the outputs together
+        transaction.add_output(out_note).unwrap();
+        assert_eq!(
+            transaction.analyze_privacy(123_457),
+            vec![
+                PrivacyFinding::AddressReuse {
+                    address: receiver_key.public_address(),
+                    output_indices: vec![0, 1],
+                },
+                PrivacyFinding::DistinctiveFee { fee: 123_457 },
+            ]
+        );
+    }
+}
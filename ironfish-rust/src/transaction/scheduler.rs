@@ -0,0 +1,342 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A shared pool of workers posting transactions in order of urgency, so that
+//! an interactive send doesn't wait behind a background batch.
+//!
+//! Jobs run by priority, then by earliest deadline, then in the order they
+//! were submitted. A transaction is the unit of preemption: proofs being
+//! built aren't interrupted, but a batch submitted as many transactions
+//! yields to a more urgent job between any two of them. A job whose deadline
+//! has passed by the time a worker gets to it fails with
+//! [`IronfishError::ProvingDeadlineMissed`] instead of being proven late.
+
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, TryRecvError},
+        Arc, Condvar, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Instant,
+};
+
+use crate::{errors::IronfishError, keys::PublicAddress};
+
+use super::{ProposedTransaction, Transaction};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ProvingPriority {
+    /// Work nobody is waiting on, such as consolidating notes
+    Background,
+    Normal,
+    /// A user is waiting on the transaction
+    Interactive,
+}
+
+/// Posts transactions on a bounded pool of worker threads. Dropping the
+/// scheduler waits for the jobs in progress and cancels the queued ones.
+pub struct ProvingScheduler {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+/// The transaction of a job submitted to a [`ProvingScheduler`], once posted
+pub struct ProvingHandle {
+    receiver: mpsc::Receiver<Result<Transaction, IronfishError>>,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    available: Condvar,
+}
+
+struct Queue {
+    jobs: BinaryHeap<Job>,
+    next_sequence: u64,
+    shutting_down: bool,
+}
+
+struct Job {
+    priority: ProvingPriority,
+    deadline: Option<Instant>,
+    sequence: u64,
+    run: Box<dyn FnOnce() + Send>,
+}
+
+impl ProvingScheduler {
+    /// Start a scheduler with the given number of workers, at least one
+    pub fn new(worker_count: usize) -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                jobs: BinaryHeap::new(),
+                next_sequence: 0,
+                shutting_down: false,
+            }),
+            available: Condvar::new(),
+        });
+
+        let workers = (0..worker_count.max(1))
+            .map(|id| {
+                let shared = shared.clone();
+                thread::Builder::new()
+                    .name(format!("proving-{}", id))
+                    .spawn(move || work(&shared))
+                    .unwrap()
+            })
+            .collect();
+
+        ProvingScheduler { shared, workers }
+    }
+
+    /// Queue the transaction to be posted with the given change address and
+    /// fee, see [`ProposedTransaction::post`]
+    pub fn submit(
+        &self,
+        transaction: ProposedTransaction,
+        change_goes_to: Option<PublicAddress>,
+        intended_transaction_fee: u64,
+        priority: ProvingPriority,
+        deadline: Option<Instant>,
+    ) -> ProvingHandle {
+        let (sender, receiver) = mpsc::channel();
+
+        self.submit_job(
+            priority,
+            deadline,
+            Box::new(move || {
+                let result = match deadline {
+                    Some(deadline) if Instant::now() >= deadline => {
+                        Err(IronfishError::ProvingDeadlineMissed)
+                    }
+                    _ => transaction.post(change_goes_to, intended_transaction_fee),
+                };
+
+                // Nobody is waiting for the result if the handle was dropped
+                let _ = sender.send(result);
+            }),
+        );
+
+        ProvingHandle { receiver }
+    }
+
+    /// Number of jobs waiting for a worker
+    pub fn pending(&self) -> usize {
+        self.shared.queue.lock().unwrap().jobs.len()
+    }
+
+    fn submit_job(
+        &self,
+        priority: ProvingPriority,
+        deadline: Option<Instant>,
+        run: Box<dyn FnOnce() + Send>,
+    ) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let sequence = queue.next_sequence;
+        queue.next_sequence += 1;
+        queue.jobs.push(Job {
+            priority,
+            deadline,
+            sequence,
+            run,
+        });
+
+        self.shared.available.notify_one();
+    }
+}
+
+impl Drop for ProvingScheduler {
+    fn drop(&mut self) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.shutting_down = true;
+            queue.jobs.clear();
+        }
+        self.shared.available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl ProvingHandle {
+    /// Wait for the transaction to be posted. Fails with
+    /// [`IronfishError::ProvingCancelled`] if the scheduler was dropped
+    /// first.
+    pub fn wait(self) -> Result<Transaction, IronfishError> {
+        self.receiver
+            .recv()
+            .unwrap_or(Err(IronfishError::ProvingCancelled))
+    }
+
+    /// The outcome of the job if it's done, without waiting
+    pub fn try_wait(&self) -> Option<Result<Transaction, IronfishError>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(IronfishError::ProvingCancelled)),
+        }
+    }
+}
+
+fn work(shared: &Shared) {
+    loop {
+        let job = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if let Some(job) = queue.jobs.pop() {
+                    break job;
+                }
+                if queue.shutting_down {
+                    return;
+                }
+                queue = shared.available.wait(queue).unwrap();
+            }
+        };
+
+        // A panicking job drops its sender, which cancels it, and mustn't
+        // take the worker down with it
+        let _ = panic::catch_unwind(AssertUnwindSafe(job.run));
+    }
+}
+
+/// Jobs that should run first are greater, as [`BinaryHeap`] pops the
+/// greatest
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority
+            .cmp(&other.priority)
+            .then_with(|| match (self.deadline, other.deadline) {
+                (Some(deadline), Some(other_deadline)) => other_deadline.cmp(&deadline),
+                (Some(_), None) => Ordering::Greater,
+                (None, Some(_)) => Ordering::Less,
+                (None, None) => Ordering::Equal,
+            })
+            .then_with(|| other.sequence.cmp(&self.sequence))
+    }
+}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.sequence == other.sequence
+    }
+}
+
+impl Eq for Job {}
+
+#[cfg(test)]
+mod test {
+    use super::{ProvingPriority, ProvingScheduler};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, transaction::ProposedTransaction,
+    };
+    use std::{
+        sync::{mpsc, Arc, Mutex},
+        time::{Duration, Instant},
+    };
+
+    fn proposed_transaction() -> ProposedTransaction {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(note, &witness).unwrap();
+        transaction
+    }
+
+    #[test]
+    fn test_scheduler_order() {
+        let scheduler = ProvingScheduler::new(1);
+        let order = Arc::new(Mutex::new(vec![]));
+
+        // Hold the only worker until every job is queued
+        let (release, gate) = mpsc::channel::<()>();
+        scheduler.submit_job(
+            ProvingPriority::Background,
+            None,
+            Box::new(move || gate.recv().unwrap()),
+        );
+        while scheduler.pending() > 0 {
+            std::thread::yield_now();
+        }
+
+        let later = Instant::now() + Duration::from_secs(60);
+        let sooner = Instant::now() + Duration::from_secs(30);
+        let jobs = [
+            ("batch 1", ProvingPriority::Background, None),
+            ("batch 2", ProvingPriority::Background, None),
+            ("send later", ProvingPriority::Interactive, Some(later)),
+            ("normal", ProvingPriority::Normal, None),
+            ("send sooner", ProvingPriority::Interactive, Some(sooner)),
+        ];
+        let (done_sender, done) = mpsc::channel();
+        for (name, priority, deadline) in jobs {
+            let order = order.clone();
+            let done_sender = done_sender.clone();
+            scheduler.submit_job(
+                priority,
+                deadline,
+                Box::new(move || {
+                    order.lock().unwrap().push(name);
+                    done_sender.send(()).unwrap();
+                }),
+            );
+        }
+
+        release.send(()).unwrap();
+        for _ in 0..jobs.len() {
+            done.recv().unwrap();
+        }
+
+        assert_eq!(
+            *order.lock().unwrap(),
+            ["send sooner", "send later", "normal", "batch 1", "batch 2"]
+        );
+    }
+
+    #[test]
+    fn test_scheduler_posts_transactions() {
+        let scheduler = ProvingScheduler::new(2);
+
+        let posted = scheduler.submit(
+            proposed_transaction(),
+            None,
+            1,
+            ProvingPriority::Interactive,
+            Some(Instant::now() + Duration::from_secs(600)),
+        );
+        let missed = scheduler.submit(
+            proposed_transaction(),
+            None,
+            1,
+            ProvingPriority::Normal,
+            Some(Instant::now()),
+        );
+
+        let transaction = posted.wait().unwrap();
+        transaction.verify().unwrap();
+        assert_eq!(transaction.fee(), 1);
+        assert!(matches!(
+            missed.wait(),
+            Err(IronfishError::ProvingDeadlineMissed)
+        ));
+    }
+}
@@ -0,0 +1,170 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use group::GroupEncoding;
+use ironfish_zkp::{
+    constants::{SPENDING_KEY_GENERATOR, VALUE_COMMITMENT_RANDOMNESS_GENERATOR},
+    redjubjub::{self, BatchEntry, PublicKey, Signature},
+};
+use jubjub::{ExtendedPoint, SubgroupPoint};
+use rand::RngCore;
+
+use std::time::Instant;
+
+use crate::{errors::IronfishError, metrics::record_signatures};
+
+/// Signatures queued to be verified together, with a random linear
+/// combination, rather than one at a time. Spend and mint authorizing
+/// signatures are made with a different generator than binding signatures,
+/// so the two kinds are kept in separate batches. See
+/// [`super::batch_verify_transactions`].
+#[derive(Default)]
+pub(crate) struct SignatureBatch {
+    authorizing: QueuedSignatures,
+    binding: QueuedSignatures,
+}
+
+/// The key, message and signature of each queued signature. They're kept
+/// apart so the messages can be borrowed by the [`BatchEntry`]s that the
+/// keys and signatures are moved into.
+#[derive(Default)]
+struct QueuedSignatures {
+    keys: Vec<PublicKey>,
+    messages: Vec<[u8; 64]>,
+    signatures: Vec<Signature>,
+}
+
+impl QueuedSignatures {
+    fn queue(&mut self, key: ExtendedPoint, signature_hash: &[u8; 32], signature: &Signature) {
+        let mut message = [0; 64];
+        message[..32].copy_from_slice(&key.to_bytes());
+        message[32..].copy_from_slice(&signature_hash[..]);
+
+        self.keys.push(PublicKey(key));
+        self.messages.push(message);
+        self.signatures.push(*signature);
+    }
+
+    fn verify<R: RngCore>(self, rng: &mut R, generator: SubgroupPoint) -> bool {
+        let entries: Vec<_> = self
+            .keys
+            .into_iter()
+            .zip(self.messages.iter())
+            .zip(self.signatures)
+            .map(|((vk, msg), sig)| BatchEntry { vk, msg, sig })
+            .collect();
+
+        redjubjub::batch_verify(rng, &entries, generator)
+    }
+}
+
+impl SignatureBatch {
+    /// Queue the signature of a spend or mint, made with the randomized
+    /// public key of its transaction over the transaction's signature hash.
+    pub(crate) fn queue_authorizing(
+        &mut self,
+        randomized_public_key: &PublicKey,
+        signature_hash: &[u8; 32],
+        signature: &Signature,
+    ) -> Result<(), IronfishError> {
+        if randomized_public_key.0.is_small_order().into() {
+            return Err(IronfishError::IsSmallOrder);
+        }
+
+        self.authorizing
+            .queue(randomized_public_key.0, signature_hash, signature);
+
+        Ok(())
+    }
+
+    /// Queue the binding signature of a transaction, made with the key its
+    /// value balance commits to.
+    pub(crate) fn queue_binding(
+        &mut self,
+        value_balance: ExtendedPoint,
+        signature_hash: &[u8; 32],
+        signature: &Signature,
+    ) {
+        self.binding.queue(value_balance, signature_hash, signature);
+    }
+
+    /// Verify every queued signature. A failure doesn't say which signature
+    /// is invalid; verify each transaction on its own to find out.
+    pub(crate) fn verify<R: RngCore>(self, rng: &mut R) -> Result<(), IronfishError> {
+        let count = self.authorizing.keys.len() + self.binding.keys.len();
+
+        let start = Instant::now();
+        let is_valid = self.authorizing.verify(rng, SPENDING_KEY_GENERATOR)
+            && self
+                .binding
+                .verify(rng, VALUE_COMMITMENT_RANDOMNESS_GENERATOR);
+        record_signatures(count, start.elapsed(), is_valid);
+
+        if !is_valid {
+            return Err(IronfishError::VerificationFailed);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SignatureBatch;
+    use crate::{errors::IronfishError, test_util::make_posted_transaction, Transaction};
+    use ironfish_zkp::redjubjub::Signature;
+    use rand::rngs::OsRng;
+
+    fn queue_transaction(batch: &mut SignatureBatch, transaction: &Transaction) {
+        let signature_hash = transaction.transaction_signature_hash();
+        for spend in transaction.spends() {
+            batch
+                .queue_authorizing(
+                    transaction.randomized_public_key(),
+                    &signature_hash,
+                    &spend.authorizing_signature,
+                )
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_signature_batch() {
+        let first = make_posted_transaction();
+        let second = make_posted_transaction();
+
+        let mut batch = SignatureBatch::default();
+        queue_transaction(&mut batch, &first);
+        queue_transaction(&mut batch, &second);
+        batch.verify(&mut OsRng).unwrap();
+
+        // A signature over the wrong hash fails the whole batch
+        let mut batch = SignatureBatch::default();
+        queue_transaction(&mut batch, &first);
+        batch
+            .queue_authorizing(
+                second.randomized_public_key(),
+                &first.transaction_signature_hash(),
+                &second.spends()[0].authorizing_signature,
+            )
+            .unwrap();
+        assert!(matches!(
+            batch.verify(&mut OsRng),
+            Err(IronfishError::VerificationFailed)
+        ));
+
+        // So does a malformed signature
+        let mut batch = SignatureBatch::default();
+        queue_transaction(&mut batch, &first);
+        let garbage = Signature::read(&[0xff; 64][..]).unwrap();
+        batch
+            .queue_authorizing(
+                first.randomized_public_key(),
+                &first.transaction_signature_hash(),
+                &garbage,
+            )
+            .unwrap();
+        assert!(batch.verify(&mut OsRng).is_err());
+    }
+}
@@ -0,0 +1,304 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Limits on what the key of a hot wallet may sign, so that whoever takes
+//! over the application calling it can't empty the wallet in one
+//! transaction.
+//!
+//! A [`SigningContext`] holds the spender key and never hands it out.
+//! Transactions are built through it as [`GuardedTransaction`]s, which can
+//! spend, pay and burn but not mint, always send change back to the spender,
+//! and are checked against the [`SpendingLimits`] before anything is proven
+//! or signed. Outputs to the spender itself don't count against the limits.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    assets::asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+    errors::IronfishError,
+    keys::{PublicAddress, SaplingKey},
+    note::Note,
+    witness::WitnessTrait,
+};
+
+use super::{ProposedTransaction, Transaction};
+
+/// What a single transaction signed by a [`SigningContext`] may do. Limits
+/// left unset don't restrict anything.
+#[derive(Clone, Debug, Default)]
+pub struct SpendingLimits {
+    /// Most value of any one asset a transaction may take out of the
+    /// account: paid to others, burned and, in the native asset, paid as fee
+    pub max_value_per_transaction: Option<u64>,
+
+    /// Assets that may be paid to others or burned
+    pub allowed_assets: Option<Vec<AssetIdentifier>>,
+
+    /// Addresses other than the spender's that may be paid
+    pub allowed_recipients: Option<Vec<PublicAddress>>,
+}
+
+/// How a transaction goes beyond the [`SpendingLimits`] of its context, in
+/// [`IronfishError::SpendingLimitExceeded`]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SpendingLimitViolation {
+    AssetNotAllowed {
+        asset_id: AssetIdentifier,
+    },
+
+    RecipientNotAllowed {
+        recipient: PublicAddress,
+    },
+
+    /// The transaction takes `value` of the asset out of the account
+    ValueTooLarge {
+        asset_id: AssetIdentifier,
+        value: u64,
+        max_value: u64,
+    },
+}
+
+impl fmt::Display for SpendingLimitViolation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpendingLimitViolation::AssetNotAllowed { asset_id } => {
+                write!(f, "asset {:?} isn't allowed", asset_id)
+            }
+            SpendingLimitViolation::RecipientNotAllowed { recipient } => {
+                write!(
+                    f,
+                    "recipient {} isn't allowed",
+                    recipient.hex_public_address()
+                )
+            }
+            SpendingLimitViolation::ValueTooLarge {
+                asset_id,
+                value,
+                max_value,
+            } => write!(
+                f,
+                "{} of asset {:?} is more than the limit of {}",
+                value, asset_id, max_value
+            ),
+        }
+    }
+}
+
+/// A spender key that only signs transactions within its limits. See the
+/// [module documentation](self).
+pub struct SigningContext {
+    spender_key: SaplingKey,
+    limits: SpendingLimits,
+}
+
+impl SigningContext {
+    pub fn new(spender_key: SaplingKey, limits: SpendingLimits) -> Self {
+        SigningContext {
+            spender_key,
+            limits,
+        }
+    }
+
+    pub fn limits(&self) -> &SpendingLimits {
+        &self.limits
+    }
+
+    pub fn public_address(&self) -> PublicAddress {
+        self.spender_key.public_address()
+    }
+
+    /// Start a transaction spending from the key of this context
+    pub fn propose(&self) -> GuardedTransaction<'_> {
+        GuardedTransaction {
+            context: self,
+            proposed: ProposedTransaction::new(self.spender_key.clone()),
+        }
+    }
+
+    fn check(
+        &self,
+        proposed: &ProposedTransaction,
+        intended_transaction_fee: u64,
+    ) -> Result<(), SpendingLimitViolation> {
+        let spender = self.public_address();
+        let mut totals = HashMap::new();
+        totals.insert(NATIVE_ASSET, intended_transaction_fee);
+
+        let payments = proposed
+            .outputs
+            .iter()
+            .map(|output| {
+                (
+                    Some(output.note.owner()),
+                    *output.note.asset_id(),
+                    output.note.value(),
+                )
+            })
+            .filter(|(recipient, _, _)| *recipient != Some(spender));
+        let burns = proposed
+            .burns
+            .iter()
+            .map(|burn| (None, burn.asset_id, burn.value));
+
+        for (recipient, asset_id, value) in payments.chain(burns) {
+            if let Some(allowed_assets) = &self.limits.allowed_assets {
+                if !allowed_assets.contains(&asset_id) {
+                    return Err(SpendingLimitViolation::AssetNotAllowed { asset_id });
+                }
+            }
+            if let (Some(recipient), Some(allowed_recipients)) =
+                (recipient, &self.limits.allowed_recipients)
+            {
+                if !allowed_recipients.contains(&recipient) {
+                    return Err(SpendingLimitViolation::RecipientNotAllowed { recipient });
+                }
+            }
+
+            let total = totals.entry(asset_id).or_insert(0);
+            *total = total.saturating_add(value);
+        }
+
+        if let Some(max_value) = self.limits.max_value_per_transaction {
+            if let Some((asset_id, value)) =
+                totals.into_iter().find(|(_, value)| *value > max_value)
+            {
+                return Err(SpendingLimitViolation::ValueTooLarge {
+                    asset_id,
+                    value,
+                    max_value,
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// A transaction being built by a [`SigningContext`], only posted if it's
+/// within the limits of the context
+pub struct GuardedTransaction<'a> {
+    context: &'a SigningContext,
+    proposed: ProposedTransaction,
+}
+
+impl<'a> GuardedTransaction<'a> {
+    /// See [`ProposedTransaction::add_spend`]
+    pub fn add_spend(
+        &mut self,
+        note: Note,
+        witness: &dyn WitnessTrait,
+    ) -> Result<(), IronfishError> {
+        self.proposed.add_spend(note, witness)
+    }
+
+    /// See [`ProposedTransaction::add_output`]
+    pub fn add_output(&mut self, note: Note) -> Result<(), IronfishError> {
+        self.proposed.add_output(note)
+    }
+
+    /// See [`ProposedTransaction::add_burn`]
+    pub fn add_burn(&mut self, asset_id: AssetIdentifier, value: u64) -> Result<(), IronfishError> {
+        self.proposed.add_burn(asset_id, value)
+    }
+
+    /// See [`ProposedTransaction::set_expiration`]
+    pub fn set_expiration(&mut self, sequence: u32) {
+        self.proposed.set_expiration(sequence)
+    }
+
+    /// Check the transaction against the limits of its context, failing with
+    /// [`IronfishError::SpendingLimitExceeded`] before building any proof if
+    /// it goes beyond them, then post it with change going back to the
+    /// spender, see [`ProposedTransaction::post`]
+    pub fn post(mut self, intended_transaction_fee: u64) -> Result<Transaction, IronfishError> {
+        self.context
+            .check(&self.proposed, intended_transaction_fee)
+            .map_err(IronfishError::SpendingLimitExceeded)?;
+
+        self.proposed.post(None, intended_transaction_fee)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{SigningContext, SpendingLimitViolation, SpendingLimits};
+    use crate::{
+        assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::Note,
+        test_util::make_fake_witness,
+        PublicAddress, Transaction,
+    };
+
+    fn payment(context: &SigningContext, recipient: PublicAddress, value: u64) -> Note {
+        Note::new(recipient, value, "", NATIVE_ASSET, context.public_address())
+    }
+
+    fn violation(result: Result<Transaction, IronfishError>) -> SpendingLimitViolation {
+        match result {
+            Err(IronfishError::SpendingLimitExceeded(violation)) => violation,
+            _ => panic!("expected the transaction to exceed the limits"),
+        }
+    }
+
+    #[test]
+    fn test_signing_context_limits() {
+        let key = SaplingKey::generate_key();
+        let allowed = SaplingKey::generate_key().public_address();
+        let other = SaplingKey::generate_key().public_address();
+        let asset = Asset::new(key.public_address(), "coin", "").unwrap();
+
+        let context = SigningContext::new(
+            key,
+            SpendingLimits {
+                max_value_per_transaction: Some(50),
+                allowed_assets: Some(vec![NATIVE_ASSET]),
+                allowed_recipients: Some(vec![allowed]),
+            },
+        );
+        let in_note = payment(&context, context.public_address(), 100);
+        let witness = make_fake_witness(&in_note);
+
+        let propose = |outputs: &[Note]| {
+            let mut transaction = context.propose();
+            transaction.add_spend(in_note.clone(), &witness).unwrap();
+            for output in outputs {
+                transaction.add_output(output.clone()).unwrap();
+            }
+            transaction
+        };
+
+        assert_eq!(
+            violation(propose(&[payment(&context, other, 10)]).post(1)),
+            SpendingLimitViolation::RecipientNotAllowed { recipient: other }
+        );
+        assert_eq!(
+            violation(propose(&[payment(&context, allowed, 40)]).post(20)),
+            SpendingLimitViolation::ValueTooLarge {
+                asset_id: NATIVE_ASSET,
+                value: 60,
+                max_value: 50,
+            }
+        );
+
+        let mut burning = propose(&[]);
+        burning.add_burn(*asset.id(), 1).unwrap();
+        assert_eq!(
+            violation(burning.post(1)),
+            SpendingLimitViolation::AssetNotAllowed {
+                asset_id: *asset.id()
+            }
+        );
+
+        // Paying itself doesn't count against the limits
+        let posted = propose(&[
+            payment(&context, allowed, 40),
+            payment(&context, context.public_address(), 50),
+        ])
+        .post(10)
+        .unwrap();
+        posted.verify().unwrap();
+    }
+}
@@ -4,12 +4,15 @@
 
 use crate::{
     errors::IronfishError,
-    keys::SaplingKey,
+    keys::{SaplingKey, ViewKey},
     merkle_note::{position as witness_position, sapling_auth_path},
+    metrics::record_signatures,
     note::Note,
-    sapling_bls12::SAPLING,
+    proof_system::proof_system,
+    randomness::with_rng,
     serializing::{read_point, read_scalar},
-    witness::WitnessTrait,
+    tree_position::NotePosition,
+    witness::{TreeId, WitnessTrait},
 };
 
 use bellman::gadgets::multipack;
@@ -19,17 +22,27 @@ use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use ff::PrimeField;
 use group::{Curve, GroupEncoding};
 use ironfish_zkp::{
-    constants::SPENDING_KEY_GENERATOR,
+    constants::{PROOF_GENERATION_KEY_GENERATOR, SPENDING_KEY_GENERATOR},
     primitives::ValueCommitment,
     proofs::Spend,
     redjubjub::{self, Signature},
-    Nullifier,
+    Nullifier, ProofGenerationKey,
 };
 use jubjub::ExtendedPoint;
-use rand::thread_rng;
-use std::io;
+use std::{io, time::Instant};
 
-use super::{utils::verify_spend_proof, TRANSACTION_PUBLIC_KEY_SIZE};
+use super::{
+    outputs::PROOF_SIZE,
+    utils::{new_value_commitment, verify_spend_proof},
+    TRANSACTION_PUBLIC_KEY_SIZE, TRANSACTION_SIGNATURE_SIZE, TRANSACTION_VERSION,
+    TREE_ID_TRANSACTION_VERSION,
+};
+
+/// Size of a serialized [`SpendDescription`]: proof, value commitment, root
+/// hash, tree size, nullifier and authorizing signature. Transactions from
+/// [`TREE_ID_TRANSACTION_VERSION`] on add a byte for the tree id.
+pub const SPEND_DESCRIPTION_SIZE: usize =
+    PROOF_SIZE as usize + 32 + 32 + 4 + 32 + TRANSACTION_SIGNATURE_SIZE;
 
 /// Parameters used when constructing proof that the spender owns a note with
 /// a given value.
@@ -51,7 +64,11 @@ pub struct SpendBuilder {
     /// incorporated into the proof, but is supplied to help miners verify the
     /// root hash at the time of spend.
     pub(crate) tree_size: u32,
-    pub(crate) witness_position: u64,
+
+    /// The tree the root hash belongs to
+    pub(crate) tree_id: TreeId,
+
+    pub(crate) witness_position: NotePosition,
     pub(crate) auth_path: Vec<Option<(Scalar, bool)>>,
 }
 
@@ -62,14 +79,29 @@ impl SpendBuilder {
     /// This is the only time this API thinks about the merkle tree. The witness
     /// contains the root-hash at the time the witness was created and the path
     /// to verify the location of that note in the tree.
-    pub(crate) fn new(note: Note, witness: &dyn WitnessTrait) -> Self {
-        let value_commitment = ValueCommitment::new(note.value, note.asset_generator());
+    pub(crate) fn new(note: Note, witness: &dyn WitnessTrait) -> Result<Self, IronfishError> {
+        let value_commitment = new_value_commitment(note.value, note.asset_generator().point())?;
+
+        Ok(SpendBuilder::with_value_commitment(
+            note,
+            witness,
+            value_commitment,
+        ))
+    }
 
+    /// Create a new [`SpendBuilder`] that commits to the value of the note
+    /// with the given commitment.
+    pub(crate) fn with_value_commitment(
+        note: Note,
+        witness: &dyn WitnessTrait,
+        value_commitment: ValueCommitment,
+    ) -> Self {
         SpendBuilder {
             note,
             value_commitment,
             root_hash: witness.root_hash(),
             tree_size: witness.tree_size(),
+            tree_id: witness.tree_id(),
             witness_position: witness_position(witness),
             auth_path: sapling_auth_path(witness),
         }
@@ -93,12 +125,28 @@ impl SpendBuilder {
         spender_key: &SaplingKey,
         public_key_randomness: &jubjub::Fr,
         randomized_public_key: &redjubjub::PublicKey,
+    ) -> Result<UnsignedSpendDescription, IronfishError> {
+        self.build_with_keys(
+            &spender_key.sapling_proof_generation_key(),
+            &spender_key.view_key,
+            public_key_randomness,
+            randomized_public_key,
+        )
+    }
+
+    /// [`SpendBuilder::build`], with only the keys the proof needs
+    fn build_with_keys(
+        &self,
+        proof_generation_key: &ProofGenerationKey,
+        view_key: &ViewKey,
+        public_key_randomness: &jubjub::Fr,
+        randomized_public_key: &redjubjub::PublicKey,
     ) -> Result<UnsignedSpendDescription, IronfishError> {
         let value_commitment_point = self.value_commitment_point();
 
         let circuit = Spend {
             value_commitment: Some(self.value_commitment.clone()),
-            proof_generation_key: Some(spender_key.sapling_proof_generation_key()),
+            proof_generation_key: Some(proof_generation_key.clone()),
             payment_address: Some(self.note.owner.transmission_key),
             auth_path: self.auth_path.clone(),
             commitment_randomness: Some(self.note.randomness),
@@ -109,14 +157,11 @@ impl SpendBuilder {
 
         // Proof that the spend was valid and successful for the provided owner
         // and note.
-        let proof =
-            groth16::create_random_proof(circuit, &SAPLING.spend_params, &mut thread_rng())?;
+        let proof = proof_system().prove_spend(circuit)?;
 
         // Bytes to be placed into the nullifier set to verify whether this note
         // has been previously spent.
-        let nullifier = self
-            .note
-            .nullifier(&spender_key.view_key, self.witness_position);
+        let nullifier = self.note.nullifier(view_key, self.witness_position);
 
         let blank_signature = {
             let buf = [0u8; 64];
@@ -128,6 +173,7 @@ impl SpendBuilder {
             value_commitment: value_commitment_point,
             root_hash: self.root_hash,
             tree_size: self.tree_size,
+            tree_id: self.tree_id,
             nullifier,
             authorizing_signature: blank_signature,
         };
@@ -145,6 +191,58 @@ impl SpendBuilder {
     }
 }
 
+/// Everything that goes into the proof of a spend, for proving it away from
+/// the wallet that holds the spending key, on a prover that keeps no state
+/// between proofs. The randomness is picked by the wallet, which needs
+/// `public_key_randomness` again to sign the spend and
+/// `value_commitment_randomness` to sign the binding of the transaction.
+pub struct SpendInputs<'a> {
+    /// The note being spent, in plaintext
+    pub note: Note,
+
+    /// Where the note is in the tree, and the root it's spent from
+    pub witness: &'a dyn WitnessTrait,
+
+    /// See [`SaplingKey::proof_generation_key`]
+    pub proof_generation_key: ProofGenerationKey,
+
+    /// Randomizes the key the spend is signed with. Referred to as `ar` in
+    /// the literature.
+    pub public_key_randomness: jubjub::Fr,
+
+    /// Blinds the value of the note. Referred to as `rcv` in the literature.
+    pub value_commitment_randomness: jubjub::Fr,
+}
+
+/// Prove the spend described by the inputs. The description that comes out
+/// is the one [`crate::ProposedTransaction`] would build from the same
+/// inputs, and is signed by the holder of the spending key with
+/// [`UnsignedSpendDescription::sign`].
+pub fn prove_spend(inputs: &SpendInputs) -> Result<UnsignedSpendDescription, IronfishError> {
+    let value_commitment = ValueCommitment {
+        value: inputs.note.value,
+        randomness: inputs.value_commitment_randomness,
+        asset_generator: inputs.note.asset_generator().point(),
+    };
+    let spend =
+        SpendBuilder::with_value_commitment(inputs.note.clone(), inputs.witness, value_commitment);
+
+    let proof_generation_key = &inputs.proof_generation_key;
+    let view_key = ViewKey {
+        authorizing_key: proof_generation_key.ak,
+        nullifier_deriving_key: PROOF_GENERATION_KEY_GENERATOR * proof_generation_key.nsk,
+    };
+    let randomized_public_key = redjubjub::PublicKey(proof_generation_key.ak.into())
+        .randomize(inputs.public_key_randomness, SPENDING_KEY_GENERATOR);
+
+    spend.build_with_keys(
+        proof_generation_key,
+        &view_key,
+        &inputs.public_key_randomness,
+        &randomized_public_key,
+    )
+}
+
 pub struct UnsignedSpendDescription {
     /// Used to add randomness to signature generation without leaking the
     /// key. Referred to as `ar` in the literature.
@@ -155,6 +253,11 @@ pub struct UnsignedSpendDescription {
 }
 
 impl UnsignedSpendDescription {
+    /// The spend as it will be posted, but for its signature
+    pub fn description(&self) -> &SpendDescription {
+        &self.description
+    }
+
     pub fn sign(
         mut self,
         spender_key: &SaplingKey,
@@ -179,11 +282,9 @@ impl UnsignedSpendDescription {
             .copy_from_slice(&transaction_randomized_public_key.0.to_bytes());
         data_to_be_signed[32..].copy_from_slice(&signature_hash[..]);
 
-        self.description.authorizing_signature = randomized_private_key.sign(
-            &data_to_be_signed,
-            &mut thread_rng(),
-            SPENDING_KEY_GENERATOR,
-        );
+        self.description.authorizing_signature = with_rng(|rng| {
+            randomized_private_key.sign(&data_to_be_signed, rng, SPENDING_KEY_GENERATOR)
+        })?;
 
         Ok(self.description)
     }
@@ -212,6 +313,11 @@ pub struct SpendDescription {
     /// hash the client supplied is valid in the tree.
     pub(crate) tree_size: u32,
 
+    /// The tree the root hash belongs to. Only serialized from
+    /// [`TREE_ID_TRANSACTION_VERSION`] on, earlier spends always anchor to
+    /// [`TreeId::NOTES`].
+    pub(crate) tree_id: TreeId,
+
     /// Bytes to be placed into the nullifier set to verify whether this
     /// note has been previously spent.
     pub(crate) nullifier: Nullifier,
@@ -228,11 +334,24 @@ impl SpendDescription {
     /// Load a [`SpendDescription`] from a Read implementation (e.g: socket,
     /// file) This is the main entry-point when reconstructing a serialized
     /// transaction.
-    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+    pub fn read<R: io::Read>(reader: R) -> Result<Self, IronfishError> {
+        SpendDescription::read_for_version(reader, TRANSACTION_VERSION)
+    }
+
+    /// Load a [`SpendDescription`] as laid out in transactions of the given
+    /// version.
+    pub fn read_for_version<R: io::Read>(
+        mut reader: R,
+        version: u8,
+    ) -> Result<Self, IronfishError> {
         let proof = groth16::Proof::read(&mut reader)?;
         let value_commitment = read_point(&mut reader)?;
         let root_hash = read_scalar(&mut reader)?;
         let tree_size = reader.read_u32::<LittleEndian>()?;
+        let tree_id = match version >= TREE_ID_TRANSACTION_VERSION {
+            true => TreeId(reader.read_u8()?),
+            false => TreeId::NOTES,
+        };
         let mut nullifier = Nullifier([0; 32]);
         reader.read_exact(&mut nullifier.0)?;
         let authorizing_signature = redjubjub::Signature::read(&mut reader)?;
@@ -242,19 +361,41 @@ impl SpendDescription {
             value_commitment,
             root_hash,
             tree_size,
+            tree_id,
             nullifier,
             authorizing_signature,
         })
     }
 
     /// Stow the bytes of this [`SpendDescription`] in the given writer.
-    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
-        self.serialize_signature_fields(&mut writer)?;
+    pub fn write<W: io::Write>(&self, writer: W) -> Result<(), IronfishError> {
+        self.write_for_version(writer, TRANSACTION_VERSION)
+    }
+
+    /// Stow the bytes of this [`SpendDescription`] as laid out in
+    /// transactions of the given version. Fails if the version can't name
+    /// the tree the spend is anchored to.
+    pub fn write_for_version<W: io::Write>(
+        &self,
+        mut writer: W,
+        version: u8,
+    ) -> Result<(), IronfishError> {
+        self.serialize_signature_fields(&mut writer, version)?;
         self.authorizing_signature.write(&mut writer)?;
 
         Ok(())
     }
 
+    /// The serialized form of this [`SpendDescription`], as written by
+    /// [`SpendDescription::write`], without allocating. Fails like it if the
+    /// spend is anchored to a tree other than [`TreeId::NOTES`], which that
+    /// layout can't express.
+    pub fn to_bytes(&self) -> Result<[u8; SPEND_DESCRIPTION_SIZE], IronfishError> {
+        let mut bytes = [0; SPEND_DESCRIPTION_SIZE];
+        self.write(&mut bytes[..])?;
+        Ok(bytes)
+    }
+
     pub fn nullifier(&self) -> Nullifier {
         self.nullifier
     }
@@ -267,6 +408,10 @@ impl SpendDescription {
         self.tree_size
     }
 
+    pub fn tree_id(&self) -> TreeId {
+        self.tree_id
+    }
+
     /// Verify that the signature on this proof is signing the provided input
     /// with the randomized_public_key on this proof.
     pub fn verify_signature(
@@ -281,11 +426,15 @@ impl SpendDescription {
         data_to_be_signed[..32].copy_from_slice(&randomized_public_key.0.to_bytes());
         data_to_be_signed[32..].copy_from_slice(&signature_hash_value[..]);
 
-        if !randomized_public_key.verify(
+        let start = Instant::now();
+        let is_valid = randomized_public_key.verify(
             &data_to_be_signed,
             &self.authorizing_signature,
             SPENDING_KEY_GENERATOR,
-        ) {
+        );
+        record_signatures(1, start.elapsed(), is_valid);
+
+        if !is_valid {
             return Err(IronfishError::VerificationFailed);
         }
 
@@ -300,6 +449,10 @@ impl SpendDescription {
     pub fn partial_verify(&self) -> Result<(), IronfishError> {
         self.verify_not_small_order()?;
 
+        if !self.tree_id.is_known() {
+            return Err(IronfishError::InvalidTreeId);
+        }
+
         Ok(())
     }
 
@@ -335,17 +488,28 @@ impl SpendDescription {
     }
 
     /// Serialize the fields that are needed in calculating a signature to
-    /// the provided writer (probably a Blake2B writer)
+    /// the provided writer (probably a Blake2B writer), as laid out in
+    /// transactions of the given version
     pub(crate) fn serialize_signature_fields<W: io::Write>(
         &self,
         writer: W,
+        version: u8,
     ) -> Result<(), IronfishError> {
+        let tree_id = if version >= TREE_ID_TRANSACTION_VERSION {
+            Some(self.tree_id)
+        } else if self.tree_id == TreeId::NOTES {
+            None
+        } else {
+            return Err(IronfishError::InvalidTransactionVersion);
+        };
+
         serialize_signature_fields(
             writer,
             &self.proof,
             &self.value_commitment,
             &self.root_hash,
             self.tree_size,
+            tree_id,
             &self.nullifier,
         )
     }
@@ -359,12 +523,16 @@ fn serialize_signature_fields<W: io::Write>(
     value_commitment: &ExtendedPoint,
     root_hash: &Scalar,
     tree_size: u32,
+    tree_id: Option<TreeId>,
     nullifier: &Nullifier,
 ) -> Result<(), IronfishError> {
     proof.write(&mut writer)?;
     writer.write_all(&value_commitment.to_bytes())?;
     writer.write_all(root_hash.to_repr().as_ref())?;
     writer.write_u32::<LittleEndian>(tree_size)?;
+    if let Some(tree_id) = tree_id {
+        writer.write_u8(tree_id.0)?;
+    }
     writer.write_all(&nullifier.0)?;
 
     Ok(())
@@ -373,9 +541,13 @@ fn serialize_signature_fields<W: io::Write>(
 #[cfg(test)]
 mod test {
 
-    use super::{SpendBuilder, SpendDescription};
+    use super::{prove_spend, SpendBuilder, SpendDescription, SpendInputs, SPEND_DESCRIPTION_SIZE};
     use crate::assets::asset_identifier::NATIVE_ASSET;
-    use crate::transaction::utils::verify_spend_proof;
+    use crate::errors::IronfishError;
+    use crate::transaction::{
+        utils::verify_spend_proof, TRANSACTION_VERSION, TREE_ID_TRANSACTION_VERSION,
+    };
+    use crate::witness::TreeId;
     use crate::{keys::SaplingKey, note::Note, test_util::make_fake_witness};
     use ff::Field;
     use group::Curve;
@@ -401,7 +573,7 @@ mod test {
         );
         let witness = make_fake_witness(&note);
 
-        let spend = SpendBuilder::new(note, &witness);
+        let spend = SpendBuilder::new(note, &witness).unwrap();
 
         let public_key_randomness = jubjub::Fr::random(thread_rng());
         let randomized_public_key = redjubjub::PublicKey(key.view_key.authorizing_key.into())
@@ -457,4 +629,116 @@ mod test {
             .expect("should be able to serialize proof again");
         assert_eq!(serialized_proof, serialized_again);
     }
+
+    #[test]
+    fn test_spend_tree_id() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let public_key_randomness = jubjub::Fr::random(thread_rng());
+        let randomized_public_key = redjubjub::PublicKey(key.view_key.authorizing_key.into())
+            .randomize(public_key_randomness, SPENDING_KEY_GENERATOR);
+        let mut spend = SpendBuilder::new(note, &witness)
+            .unwrap()
+            .build(&key, &public_key_randomness, &randomized_public_key)
+            .unwrap()
+            .sign(&key, &[0; 32])
+            .unwrap();
+        assert_eq!(spend.tree_id(), TreeId::NOTES);
+
+        // Older transactions don't carry the tree id
+        let mut serialized = vec![];
+        spend
+            .write_for_version(&mut serialized, TRANSACTION_VERSION)
+            .unwrap();
+        assert_eq!(serialized.len(), SPEND_DESCRIPTION_SIZE);
+
+        spend.tree_id = TreeId(1);
+        assert!(!spend.tree_id().is_known());
+        assert!(matches!(
+            spend.partial_verify(),
+            Err(IronfishError::InvalidTreeId)
+        ));
+        assert!(matches!(
+            spend.write_for_version(vec![], TRANSACTION_VERSION),
+            Err(IronfishError::InvalidTransactionVersion)
+        ));
+        assert!(matches!(
+            spend.to_bytes(),
+            Err(IronfishError::InvalidTransactionVersion)
+        ));
+
+        let mut serialized = vec![];
+        spend
+            .write_for_version(&mut serialized, TREE_ID_TRANSACTION_VERSION)
+            .unwrap();
+        assert_eq!(serialized.len(), SPEND_DESCRIPTION_SIZE + 1);
+        let read_back =
+            SpendDescription::read_for_version(&serialized[..], TREE_ID_TRANSACTION_VERSION)
+                .unwrap();
+        assert_eq!(read_back.tree_id(), TreeId(1));
+        assert_eq!(read_back.nullifier(), spend.nullifier());
+    }
+
+    #[test]
+    fn test_prove_spend_from_explicit_inputs() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let inputs = SpendInputs {
+            note: note.clone(),
+            witness: &witness,
+            proof_generation_key: key.proof_generation_key(),
+            public_key_randomness: jubjub::Fr::random(thread_rng()),
+            value_commitment_randomness: jubjub::Fr::random(thread_rng()),
+        };
+        let unsigned = prove_spend(&inputs).unwrap();
+
+        let randomized_public_key = redjubjub::PublicKey(key.view_key.authorizing_key.into())
+            .randomize(inputs.public_key_randomness, SPENDING_KEY_GENERATOR);
+        let description = unsigned.description();
+        verify_spend_proof(
+            &description.proof,
+            &description.public_inputs(&randomized_public_key),
+        )
+        .unwrap();
+
+        // The same note spent by the builder has the same nullifier, and the
+        // value is committed to with the supplied randomness
+        let mut built = SpendBuilder::new(note.clone(), &witness).unwrap();
+        assert_eq!(
+            description.nullifier(),
+            note.nullifier(&key.view_key, built.witness_position)
+        );
+        built.value_commitment.randomness = inputs.value_commitment_randomness;
+        assert_eq!(description.value_commitment, built.value_commitment_point());
+
+        let signature_hash = [7; 32];
+        let spend = unsigned.sign(&key, &signature_hash).unwrap();
+        spend
+            .verify_signature(&signature_hash, &randomized_public_key)
+            .unwrap();
+
+        // Only the holder of the matching spending key can sign it
+        let other = SaplingKey::generate_key();
+        let unsigned = prove_spend(&inputs).unwrap();
+        assert!(matches!(
+            unsigned.sign(&other, &signature_hash),
+            Err(IronfishError::InvalidSigningKey)
+        ));
+    }
 }
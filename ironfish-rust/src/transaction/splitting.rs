@@ -0,0 +1,208 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Splitting a payment into several notes. Values are hidden inside notes,
+//! but a single large note still stands out when it's later spent alongside
+//! small ones, and it ties the whole amount to one output. Splitting the
+//! payment makes the notes look like any other notes of common values.
+
+use rand::Rng;
+
+use crate::{
+    errors::IronfishError,
+    note::Note,
+    randomness::expect_rng,
+    value_range::{check_value, ValueContext},
+};
+
+use super::{outputs::OutputBuilder, ProposedTransaction};
+
+/// Decides how a payment is split into note values. Implement this to plug
+/// in a custom strategy. The returned values must add up to the payment.
+pub trait SplitStrategy {
+    fn split(&self, value: u64) -> Vec<u64>;
+}
+
+/// Split the payment into notes whose values are powers of two, following the
+/// binary representation of the value. If that needs more than `max_notes`
+/// notes, the smallest powers are combined into a single remainder note. A
+/// payment of zero is a single note of zero.
+#[derive(Clone, Copy, Debug)]
+pub struct PowersOfTwo {
+    pub max_notes: usize,
+}
+
+impl SplitStrategy for PowersOfTwo {
+    fn split(&self, value: u64) -> Vec<u64> {
+        let mut values: Vec<u64> = (0..u64::BITS)
+            .rev()
+            .map(|bit| value & (1 << bit))
+            .filter(|power| *power != 0)
+            .collect();
+
+        if values.is_empty() {
+            return vec![0];
+        }
+
+        let max_notes = self.max_notes.max(1);
+        if values.len() > max_notes {
+            let remainder = values.split_off(max_notes - 1).iter().sum();
+            values.push(remainder);
+        }
+
+        values
+    }
+}
+
+/// Split the payment into `notes` notes at uniformly random cut points. Some
+/// of the notes may hold little or no value, and act as decoys.
+#[derive(Clone, Copy, Debug)]
+pub struct UniformDecoys {
+    pub notes: usize,
+}
+
+impl SplitStrategy for UniformDecoys {
+    fn split(&self, value: u64) -> Vec<u64> {
+        let mut cuts: Vec<u64> = expect_rng(|rng| {
+            (1..self.notes.max(1))
+                .map(|_| rng.gen_range(0..=value))
+                .collect()
+        });
+        cuts.sort_unstable();
+        cuts.push(value);
+
+        let mut previous = 0;
+        cuts.into_iter()
+            .map(|cut| {
+                let part = cut - previous;
+                previous = cut;
+                part
+            })
+            .collect()
+    }
+}
+
+impl ProposedTransaction {
+    /// Pay the owner of `note` its value, split into several notes by the
+    /// given strategy. Every note gets the owner, memo, asset and sender of
+    /// `note`. Either all of the notes are added or none are.
+    pub fn add_split_output(
+        &mut self,
+        note: Note,
+        strategy: &dyn SplitStrategy,
+    ) -> Result<(), IronfishError> {
+        let values = strategy.split(note.value);
+
+        let total = values
+            .iter()
+            .try_fold(0u64, |total, value| total.checked_add(*value));
+        if values.is_empty() || total != Some(note.value) {
+            return Err(IronfishError::InvalidBalance);
+        }
+
+        self.limits
+            .check_outputs(self.outputs.len() + values.len())?;
+
+        let value = check_value(note.value(), ValueContext::Note)?;
+
+        let outputs = values
+            .into_iter()
+            .map(|value| {
+                let part = Note::try_new(note.owner, value, note.memo, note.asset_id, note.sender)?;
+                OutputBuilder::new(part)
+            })
+            .collect::<Result<Vec<_>, IronfishError>>()?;
+
+        self.value_balances.subtract(note.asset_id(), value)?;
+        self.outputs.extend(outputs);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PowersOfTwo, SplitStrategy, UniformDecoys};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, test_util::make_fake_witness, transaction::limits::Limits, ProposedTransaction,
+    };
+
+    struct Broken;
+
+    impl SplitStrategy for Broken {
+        fn split(&self, value: u64) -> Vec<u64> {
+            vec![value, 1]
+        }
+    }
+
+    #[test]
+    fn test_powers_of_two() {
+        let strategy = PowersOfTwo { max_notes: 10 };
+        assert_eq!(strategy.split(13), vec![8, 4, 1]);
+        assert_eq!(strategy.split(0), vec![0]);
+
+        let capped = PowersOfTwo { max_notes: 2 };
+        assert_eq!(capped.split(15), vec![8, 7]);
+    }
+
+    #[test]
+    fn test_uniform_decoys() {
+        let strategy = UniformDecoys { notes: 5 };
+        let values = strategy.split(1000);
+        assert_eq!(values.len(), 5);
+        assert_eq!(values.iter().sum::<u64>(), 1000);
+    }
+
+    #[test]
+    fn test_add_split_output() {
+        let key = SaplingKey::generate_key();
+        let in_note = Note::new(
+            key.public_address(),
+            20,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let out_note = Note::new(
+            key.public_address(),
+            13,
+            "split",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        let limits = Limits {
+            max_outputs: 4,
+            ..Limits::default()
+        };
+        let mut transaction = ProposedTransaction::with_limits(key, limits).unwrap();
+        transaction.add_spend(in_note, &witness).unwrap();
+
+        assert!(matches!(
+            transaction.add_split_output(out_note.clone(), &Broken),
+            Err(IronfishError::InvalidBalance)
+        ));
+        assert!(matches!(
+            transaction.add_split_output(out_note.clone(), &UniformDecoys { notes: 5 }),
+            Err(IronfishError::TooManyOutputs)
+        ));
+        assert!(transaction.outputs.is_empty());
+
+        transaction
+            .add_split_output(out_note, &PowersOfTwo { max_notes: 4 })
+            .unwrap();
+        let values: Vec<u64> = transaction
+            .outputs
+            .iter()
+            .map(|output| output.note.value)
+            .collect();
+        assert_eq!(values, vec![8, 4, 1]);
+
+        // The change note takes the last output
+        let posted = transaction.post(None, 0).unwrap();
+        assert_eq!(posted.outputs().len(), 4);
+    }
+}
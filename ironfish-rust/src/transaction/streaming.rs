@@ -0,0 +1,516 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use std::mem;
+
+use byteorder::{LittleEndian, ReadBytesExt};
+use ironfish_zkp::redjubjub::{self, Signature};
+
+use crate::{
+    errors::IronfishError,
+    metrics::Circuit,
+    network::NetworkKind,
+    value_range::{check_posted_value, ValueContext},
+    OutputDescription, SpendDescription,
+};
+
+use super::{
+    burns::{BurnDescription, BURN_DESCRIPTION_SIZE},
+    extra_data::{
+        has_extra_data, read_entry_count, read_entry_header, ExtraData, ENTRY_COUNT_SIZE,
+        ENTRY_HEADER_SIZE,
+    },
+    is_verifiable_version,
+    mints::{MintDescription, MINT_DESCRIPTION_SIZE},
+    outputs::OUTPUT_DESCRIPTION_SIZE,
+    spends::SPEND_DESCRIPTION_SIZE,
+    utils::verify_proof_batch,
+    verification::VerifiedChecks,
+    Transaction, TRANSACTION_HEADER_SIZE, TRANSACTION_SIGNATURE_SIZE, TRANSACTION_VERSION,
+    TREE_ID_TRANSACTION_VERSION,
+};
+
+/// The part of the serialized transaction that the verifier expects next
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Stage {
+    Header,
+    Spends,
+    Outputs,
+    Mints,
+    Burns,
+    ExtraDataCount,
+    ExtraDataEntryHeader,
+    ExtraDataEntryValue,
+    BindingSignature,
+    Complete,
+}
+
+struct Header {
+    version: u8,
+    num_spends: u64,
+    num_outputs: u64,
+    num_mints: u64,
+    num_burns: u64,
+    fee: i64,
+    expiration: u32,
+    randomized_public_key: redjubjub::PublicKey,
+}
+
+/// Verifies a serialized transaction while its bytes are still arriving, so
+/// that downloading a block and verifying it overlap.
+///
+/// Each description is checked for structure as soon as all of its bytes
+/// have been pushed. The signatures cover the whole transaction, so they are
+/// checked once the last byte arrives. [`TransactionVerifier::finish`] then
+/// verifies the proofs of each circuit in a batch. Any error means the
+/// transaction is invalid, and the verifier should be dropped.
+pub struct TransactionVerifier {
+    network: NetworkKind,
+    /// Latest transaction version the node activated
+    activated_version: u8,
+    stage: Stage,
+
+    /// Bytes of the item currently being received
+    pending: Vec<u8>,
+
+    header: Option<Header>,
+    spends: Vec<SpendDescription>,
+    outputs: Vec<OutputDescription>,
+    mints: Vec<MintDescription>,
+    burns: Vec<BurnDescription>,
+
+    /// Bytes of the extra data received so far, parsed once they're all in
+    extra_data: Vec<u8>,
+    /// Extra data entries whose key and length haven't been received yet
+    extra_data_entries: u16,
+    /// Length of the value of the extra data entry being received
+    entry_value_size: usize,
+
+    /// Set once every byte has been received and the signatures checked
+    transaction: Option<Transaction>,
+}
+
+impl Default for TransactionVerifier {
+    fn default() -> Self {
+        TransactionVerifier::new()
+    }
+}
+
+impl TransactionVerifier {
+    pub fn new() -> Self {
+        TransactionVerifier::new_for_network(NetworkKind::Mainnet)
+    }
+
+    /// Verify a transaction whose minted assets belong to the given network.
+    /// See [`Transaction::read_for_network`].
+    pub fn new_for_network(network: NetworkKind) -> Self {
+        TransactionVerifier {
+            network,
+            activated_version: TRANSACTION_VERSION,
+            stage: Stage::Header,
+            pending: Vec::with_capacity(TRANSACTION_HEADER_SIZE),
+            header: None,
+            spends: vec![],
+            outputs: vec![],
+            mints: vec![],
+            burns: vec![],
+            extra_data: vec![],
+            extra_data_entries: 0,
+            entry_value_size: 0,
+            transaction: None,
+        }
+    }
+
+    /// Accept the transaction versions up to `activated_version`, instead of
+    /// only [`TRANSACTION_VERSION`]
+    pub fn with_activated_version(mut self, activated_version: u8) -> Self {
+        self.activated_version = activated_version;
+        self
+    }
+
+    /// Feed the next bytes of the transaction, returning how many of them
+    /// were used. Fewer bytes than given are used only once the transaction
+    /// is complete, and the rest belong to whatever follows it in the stream,
+    /// such as the next transaction of a block.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<usize, IronfishError> {
+        let mut remaining = bytes;
+
+        while self.stage != Stage::Complete && !remaining.is_empty() {
+            let needed = self.item_size() - self.pending.len();
+            let (chunk, rest) = remaining.split_at(needed.min(remaining.len()));
+            self.pending.extend_from_slice(chunk);
+            remaining = rest;
+
+            if self.pending.len() == self.item_size() {
+                let item = mem::take(&mut self.pending);
+                self.process(&item)?;
+            }
+        }
+
+        Ok(bytes.len() - remaining.len())
+    }
+
+    /// Returns true once every byte of the transaction has been pushed.
+    pub fn is_complete(&self) -> bool {
+        self.stage == Stage::Complete
+    }
+
+    /// The checks that have passed so far. Proofs are only verified by
+    /// [`TransactionVerifier::finish`].
+    pub fn checks(&self) -> VerifiedChecks {
+        VerifiedChecks {
+            structure: self.is_complete(),
+            signatures: self.is_complete(),
+            proofs: false,
+        }
+    }
+
+    /// Verify the queued proofs and return the fully verified transaction.
+    pub fn finish(self) -> Result<Transaction, IronfishError> {
+        let transaction = self.transaction.ok_or(IronfishError::InvalidTransaction)?;
+
+        let randomized_public_key = transaction.randomized_public_key();
+        let spend_proofs: Vec<_> = transaction
+            .spends
+            .iter()
+            .map(|spend| {
+                (
+                    &spend.proof,
+                    spend.public_inputs(randomized_public_key).to_vec(),
+                )
+            })
+            .collect();
+        let output_proofs: Vec<_> = transaction
+            .outputs
+            .iter()
+            .map(|output| {
+                (
+                    &output.proof,
+                    output.public_inputs(randomized_public_key).to_vec(),
+                )
+            })
+            .collect();
+        let mint_proofs: Vec<_> = transaction
+            .mints
+            .iter()
+            .map(|mint| {
+                (
+                    &mint.proof,
+                    mint.public_inputs(randomized_public_key).to_vec(),
+                )
+            })
+            .collect();
+
+        verify_proof_batch(transaction.version, Circuit::Spend, &spend_proofs)?;
+        verify_proof_batch(transaction.version, Circuit::Output, &output_proofs)?;
+        verify_proof_batch(transaction.version, Circuit::Mint, &mint_proofs)?;
+
+        Ok(transaction)
+    }
+
+    fn item_size(&self) -> usize {
+        match self.stage {
+            Stage::Header => TRANSACTION_HEADER_SIZE,
+            Stage::Spends if self.version() >= TREE_ID_TRANSACTION_VERSION => {
+                SPEND_DESCRIPTION_SIZE + 1
+            }
+            Stage::Spends => SPEND_DESCRIPTION_SIZE,
+            Stage::Outputs => OUTPUT_DESCRIPTION_SIZE,
+            Stage::Mints => MINT_DESCRIPTION_SIZE,
+            Stage::Burns => BURN_DESCRIPTION_SIZE,
+            Stage::ExtraDataCount => ENTRY_COUNT_SIZE,
+            Stage::ExtraDataEntryHeader => ENTRY_HEADER_SIZE,
+            Stage::ExtraDataEntryValue => self.entry_value_size,
+            Stage::BindingSignature => TRANSACTION_SIGNATURE_SIZE,
+            Stage::Complete => 0,
+        }
+    }
+
+    /// Version of the transaction, once its header has been received
+    fn version(&self) -> u8 {
+        self.header
+            .as_ref()
+            .map_or(TRANSACTION_VERSION, |header| header.version)
+    }
+
+    fn process(&mut self, mut item: &[u8]) -> Result<(), IronfishError> {
+        match self.stage {
+            Stage::Header => {
+                let version = item.read_u8()?;
+
+                if !is_verifiable_version(version, self.activated_version) {
+                    return Err(IronfishError::InvalidTransactionVersion);
+                }
+
+                self.header = Some(Header {
+                    version,
+                    num_spends: item.read_u64::<LittleEndian>()?,
+                    num_outputs: item.read_u64::<LittleEndian>()?,
+                    num_mints: item.read_u64::<LittleEndian>()?,
+                    num_burns: item.read_u64::<LittleEndian>()?,
+                    fee: item.read_i64::<LittleEndian>()?,
+                    expiration: item.read_u32::<LittleEndian>()?,
+                    randomized_public_key: redjubjub::PublicKey::read(&mut item)?,
+                });
+            }
+            Stage::Spends => {
+                let spend = SpendDescription::read_for_version(item, self.version())?;
+                spend.partial_verify()?;
+                self.spends.push(spend);
+            }
+            Stage::Outputs => {
+                let output = OutputDescription::read_for_version(item, self.version())?;
+                output.partial_verify()?;
+                self.outputs.push(output);
+            }
+            Stage::Mints => {
+                let mint = MintDescription::read_for_network(item, self.network)?;
+                mint.partial_verify()?;
+                check_posted_value(mint.value, ValueContext::Mint)?;
+                self.mints.push(mint);
+            }
+            Stage::Burns => {
+                let burn = BurnDescription::read(item)?;
+                check_posted_value(burn.value, ValueContext::Burn)?;
+                self.burns.push(burn);
+            }
+            Stage::ExtraDataCount => {
+                self.extra_data.extend_from_slice(item);
+                self.extra_data_entries = read_entry_count(item)?;
+            }
+            Stage::ExtraDataEntryHeader => {
+                // The size so far doesn't count this entry yet
+                let mut size = self.extra_data.len();
+                let (_, length) = read_entry_header(item, &mut size)?;
+                self.extra_data.extend_from_slice(item);
+
+                self.extra_data_entries -= 1;
+                self.entry_value_size = length;
+            }
+            Stage::ExtraDataEntryValue => {
+                self.extra_data.extend_from_slice(item);
+            }
+            Stage::BindingSignature => {
+                let binding_signature = Signature::read(item)?;
+                let header = self
+                    .header
+                    .take()
+                    .ok_or(IronfishError::InvalidTransaction)?;
+                // The entries have been delimited as they arrived, so the
+                // parse only checks their order
+                let extra_data = match has_extra_data(header.version) {
+                    true => ExtraData::read(&self.extra_data[..])?,
+                    false => ExtraData::new(),
+                };
+
+                let transaction = Transaction {
+                    version: header.version,
+                    fee: header.fee,
+                    spends: mem::take(&mut self.spends),
+                    outputs: mem::take(&mut self.outputs),
+                    mints: mem::take(&mut self.mints),
+                    burns: mem::take(&mut self.burns),
+                    binding_signature,
+                    expiration: header.expiration,
+                    randomized_public_key: header.randomized_public_key,
+                    extra_data,
+                };
+                transaction.verify_signatures()?;
+
+                self.transaction = Some(transaction);
+            }
+            Stage::Complete => return Err(IronfishError::InvalidTransaction),
+        }
+
+        self.advance();
+
+        Ok(())
+    }
+
+    /// Move to the next stage once the current one has all of its items,
+    /// skipping over stages with no items at all.
+    fn advance(&mut self) {
+        let header = match &self.header {
+            Some(header) => header,
+            // The header is only taken to build the transaction
+            None => {
+                self.stage = Stage::Complete;
+                return;
+            }
+        };
+
+        let stage_is_done = match self.stage {
+            Stage::Spends => self.spends.len() as u64 == header.num_spends,
+            Stage::Outputs => self.outputs.len() as u64 == header.num_outputs,
+            Stage::Mints => self.mints.len() as u64 == header.num_mints,
+            Stage::Burns => self.burns.len() as u64 == header.num_burns,
+            _ => true,
+        };
+        if !stage_is_done {
+            return;
+        }
+
+        loop {
+            self.stage = match self.stage {
+                Stage::Header => Stage::Spends,
+                Stage::Spends => Stage::Outputs,
+                Stage::Outputs => Stage::Mints,
+                Stage::Mints => Stage::Burns,
+                Stage::Burns if has_extra_data(header.version) => Stage::ExtraDataCount,
+                Stage::ExtraDataEntryHeader if self.entry_value_size > 0 => {
+                    Stage::ExtraDataEntryValue
+                }
+                Stage::ExtraDataCount
+                | Stage::ExtraDataEntryHeader
+                | Stage::ExtraDataEntryValue
+                    if self.extra_data_entries > 0 =>
+                {
+                    Stage::ExtraDataEntryHeader
+                }
+                _ => Stage::BindingSignature,
+            };
+
+            let stage_is_empty = match self.stage {
+                Stage::Spends => header.num_spends == 0,
+                Stage::Outputs => header.num_outputs == 0,
+                Stage::Mints => header.num_mints == 0,
+                Stage::Burns => header.num_burns == 0,
+                _ => false,
+            };
+            if !stage_is_empty {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TransactionVerifier;
+    use crate::{
+        assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+        errors::IronfishError,
+        keys::SaplingKey,
+        note::Note,
+        test_util::make_fake_witness,
+        transaction::{extra_data::ExtraData, EXTRA_DATA_TRANSACTION_VERSION},
+        ProposedTransaction,
+    };
+
+    #[test]
+    fn test_streaming_verification() {
+        let key = SaplingKey::generate_key();
+        let asset = Asset::new(key.public_address(), "testcoin", "").unwrap();
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(in_note, &witness).unwrap();
+        transaction.add_mint(asset, 5).unwrap();
+        transaction.add_burn(asset.id, 2).unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        let mut serialized = vec![];
+        posted.write(&mut serialized).unwrap();
+        let transaction_size = serialized.len();
+
+        // Bytes of the next transaction in the stream
+        serialized.extend_from_slice(&[1, 2, 3]);
+
+        let mut verifier = TransactionVerifier::new();
+        let mut consumed = 0;
+        for chunk in serialized.chunks(100) {
+            assert!(!verifier.is_complete());
+            consumed += verifier.push(chunk).unwrap();
+        }
+        assert_eq!(consumed, transaction_size);
+        assert!(verifier.checks().signatures);
+
+        let verified = verifier.finish().unwrap();
+        assert_eq!(
+            verified.transaction_signature_hash(),
+            posted.transaction_signature_hash()
+        );
+    }
+
+    #[test]
+    fn test_streaming_verification_extra_data() {
+        let key = SaplingKey::generate_key();
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        let mut extra_data = ExtraData::new();
+        extra_data.insert_raw(1, vec![]).unwrap();
+        extra_data.insert_raw(7, b"invoice 17".to_vec()).unwrap();
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(in_note, &witness).unwrap();
+        transaction
+            .set_version(EXTRA_DATA_TRANSACTION_VERSION)
+            .unwrap();
+        transaction.set_extra_data(extra_data.clone()).unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        let mut serialized = vec![];
+        posted.write(&mut serialized).unwrap();
+
+        // Not accepted before the version is activated
+        let mut verifier = TransactionVerifier::new();
+        assert!(matches!(
+            verifier.push(&serialized),
+            Err(IronfishError::InvalidTransactionVersion)
+        ));
+
+        // Small chunks, so the entries arrive split across pushes
+        let mut verifier =
+            TransactionVerifier::new().with_activated_version(EXTRA_DATA_TRANSACTION_VERSION);
+        for chunk in serialized.chunks(3) {
+            verifier.push(chunk).unwrap();
+        }
+        assert!(verifier.is_complete());
+
+        let verified = verifier.finish().unwrap();
+        assert_eq!(verified.extra_data(), &extra_data);
+    }
+
+    #[test]
+    fn test_streaming_verification_rejects_bad_signature() {
+        let key = SaplingKey::generate_key();
+        let in_note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&in_note);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(in_note, &witness).unwrap();
+        let posted = transaction.post(None, 1).unwrap();
+
+        let mut serialized = vec![];
+        posted.write(&mut serialized).unwrap();
+
+        // Tamper with the binding signature
+        let last = serialized.len() - 1;
+        serialized[last] ^= 1;
+
+        let mut verifier = TransactionVerifier::new();
+        assert!(verifier.push(&serialized).is_err());
+        assert!(verifier.finish().is_err());
+    }
+}
@@ -0,0 +1,311 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Templates for payments that are made over and over, such as payroll. A
+//! template holds everything about the payments except the notes that fund
+//! them, and is instantiated against fresh notes each time it's paid.
+
+use std::{collections::HashSet, io};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use crate::{
+    assets::asset_identifier::{AssetIdentifier, NATIVE_ASSET},
+    errors::IronfishError,
+    note::{Note, MEMO_SIZE},
+    spendable_note::SpendableNoteRecord,
+    util::str_to_array,
+    PublicAddress, SaplingKey,
+};
+
+use super::{
+    fee::{serialized_size_for, FEE_RATE_BYTES},
+    ProposedTransaction, Transaction,
+};
+
+pub const TRANSACTION_TEMPLATE_VERSION: u8 = 1;
+
+/// Replaced in memos by the number of the instance being paid, e.g.
+/// `"salary {n}"` becomes `"salary 12"` in the twelfth payment.
+pub const MEMO_INSTANCE_PLACEHOLDER: &str = "{n}";
+
+/// How the fee of each instance of a template is chosen
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeePolicy {
+    /// Always pay this many ore
+    Fixed(u64),
+
+    /// Pay this many ore per [`FEE_RATE_BYTES`] of the transaction, which
+    /// grows with the number of notes spent
+    Rate(u64),
+}
+
+/// A single payment made every time a template is instantiated
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateRecipient {
+    pub address: PublicAddress,
+    pub amount: u64,
+    pub asset_id: AssetIdentifier,
+
+    /// Memo of the note, which may contain [`MEMO_INSTANCE_PLACEHOLDER`]
+    pub memo: String,
+}
+
+impl TemplateRecipient {
+    /// The memo for the given instance, failing if it doesn't fit in a note
+    pub fn memo_for(&self, instance: u64) -> Result<String, IronfishError> {
+        let memo = self
+            .memo
+            .replace(MEMO_INSTANCE_PLACEHOLDER, &instance.to_string());
+        if memo.len() > MEMO_SIZE {
+            return Err(IronfishError::InvalidData);
+        }
+
+        Ok(memo)
+    }
+}
+
+/// Recipients and fee policy of a recurring payment
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionTemplate {
+    pub recipients: Vec<TemplateRecipient>,
+    pub fee_policy: FeePolicy,
+}
+
+impl TransactionTemplate {
+    pub fn new(fee_policy: FeePolicy) -> Self {
+        TransactionTemplate {
+            recipients: vec![],
+            fee_policy,
+        }
+    }
+
+    /// Add a payment to every instance of the template. The memo must fit
+    /// in a note without any placeholders replaced, and is checked again for
+    /// each instance.
+    pub fn add_recipient(
+        &mut self,
+        address: PublicAddress,
+        amount: u64,
+        asset_id: AssetIdentifier,
+        memo: &str,
+    ) -> Result<(), IronfishError> {
+        if memo.len() > MEMO_SIZE {
+            return Err(IronfishError::InvalidData);
+        }
+
+        self.recipients.push(TemplateRecipient {
+            address,
+            amount,
+            asset_id,
+            memo: memo.to_string(),
+        });
+
+        Ok(())
+    }
+
+    /// The fee paid by an instance funded by the given notes. The size used
+    /// for [`FeePolicy::Rate`] assumes a change note for every asset spent,
+    /// so the fee can be a little higher than the rate requires.
+    pub fn fee(&self, notes: &[SpendableNoteRecord]) -> u64 {
+        match self.fee_policy {
+            FeePolicy::Fixed(fee) => fee,
+            FeePolicy::Rate(rate) => {
+                let mut change_assets: HashSet<AssetIdentifier> =
+                    notes.iter().map(|record| record.note.asset_id()).collect();
+                change_assets.insert(NATIVE_ASSET);
+
+                let size = serialized_size_for(
+                    notes.len(),
+                    self.recipients.len() + change_assets.len(),
+                    0,
+                    0,
+                ) as u128;
+
+                let fee =
+                    (rate as u128 * size + FEE_RATE_BYTES as u128 - 1) / FEE_RATE_BYTES as u128;
+                fee.try_into().unwrap_or(u64::MAX)
+            }
+        }
+    }
+
+    /// Build the transaction for one instance of the template, spending all
+    /// of the given notes. `instance` replaces the placeholder in memos.
+    /// Returns the fee to post the transaction with alongside it, so that
+    /// the expiration can still be set before posting.
+    pub fn instantiate(
+        &self,
+        spender_key: SaplingKey,
+        notes: &[SpendableNoteRecord],
+        instance: u64,
+    ) -> Result<(ProposedTransaction, u64), IronfishError> {
+        let sender = spender_key.public_address();
+        let mut transaction = ProposedTransaction::new(spender_key);
+
+        for record in notes {
+            transaction.add_spendable_note(record)?;
+        }
+
+        for recipient in self.recipients.iter() {
+            transaction.add_output(Note::new(
+                recipient.address,
+                recipient.amount,
+                recipient.memo_for(instance)?,
+                recipient.asset_id,
+                sender,
+            ))?;
+        }
+
+        Ok((transaction, self.fee(notes)))
+    }
+
+    /// Build and post one instance of the template. Change goes back to the
+    /// spender unless `change_goes_to` is set.
+    pub fn post_instance(
+        &self,
+        spender_key: SaplingKey,
+        notes: &[SpendableNoteRecord],
+        instance: u64,
+        change_goes_to: Option<PublicAddress>,
+    ) -> Result<Transaction, IronfishError> {
+        let (mut transaction, fee) = self.instantiate(spender_key, notes, instance)?;
+        transaction.post(change_goes_to, fee)
+    }
+
+    /// Load a [`TransactionTemplate`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let version = reader.read_u8()?;
+        if version != TRANSACTION_TEMPLATE_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let fee_policy = match reader.read_u8()? {
+            0 => FeePolicy::Fixed(reader.read_u64::<LittleEndian>()?),
+            1 => FeePolicy::Rate(reader.read_u64::<LittleEndian>()?),
+            _ => return Err(IronfishError::InvalidData),
+        };
+
+        let mut template = TransactionTemplate::new(fee_policy);
+
+        let num_recipients = reader.read_u64::<LittleEndian>()?;
+        for _ in 0..num_recipients {
+            let address = PublicAddress::read(&mut reader)?;
+            let amount = reader.read_u64::<LittleEndian>()?;
+            let asset_id = AssetIdentifier::read(&mut reader)?;
+
+            let mut memo = [0; MEMO_SIZE];
+            reader.read_exact(&mut memo)?;
+            let memo = std::str::from_utf8(&memo)
+                .map_err(|_| IronfishError::InvalidData)?
+                .trim_end_matches('\0');
+
+            template.add_recipient(address, amount, asset_id, memo)?;
+        }
+
+        Ok(template)
+    }
+
+    /// Stow the bytes of this [`TransactionTemplate`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u8(TRANSACTION_TEMPLATE_VERSION)?;
+
+        match self.fee_policy {
+            FeePolicy::Fixed(fee) => {
+                writer.write_u8(0)?;
+                writer.write_u64::<LittleEndian>(fee)?;
+            }
+            FeePolicy::Rate(rate) => {
+                writer.write_u8(1)?;
+                writer.write_u64::<LittleEndian>(rate)?;
+            }
+        }
+
+        writer.write_u64::<LittleEndian>(self.recipients.len() as u64)?;
+        for recipient in self.recipients.iter() {
+            recipient.address.write(&mut writer)?;
+            writer.write_u64::<LittleEndian>(recipient.amount)?;
+            recipient.asset_id.write(&mut writer)?;
+            writer.write_all(&str_to_array::<MEMO_SIZE>(&recipient.memo))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{FeePolicy, TransactionTemplate};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET, errors::IronfishError, keys::SaplingKey,
+        note::Note, spendable_note::SpendableNoteRecord, test_util::make_fake_witness,
+        tree_position::NotePosition,
+    };
+
+    fn spendable_note(key: &SaplingKey, value: u64) -> SpendableNoteRecord {
+        let note = Note::new(
+            key.public_address(),
+            value,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+        SpendableNoteRecord {
+            note,
+            position: NotePosition::new(0),
+            witness,
+            confirmations: 1,
+        }
+    }
+
+    #[test]
+    fn test_template_round_trip() {
+        let recipient = SaplingKey::generate_key().public_address();
+
+        let mut template = TransactionTemplate::new(FeePolicy::Rate(2));
+        template
+            .add_recipient(recipient, 10, NATIVE_ASSET, "salary {n}")
+            .unwrap();
+        assert!(matches!(
+            template.add_recipient(recipient, 10, NATIVE_ASSET, &"x".repeat(33)),
+            Err(IronfishError::InvalidData)
+        ));
+
+        let mut serialized = vec![];
+        template.write(&mut serialized).unwrap();
+        let read_back = TransactionTemplate::read(&serialized[..]).unwrap();
+        assert_eq!(read_back, template);
+
+        assert_eq!(template.recipients[0].memo_for(12).unwrap(), "salary 12");
+    }
+
+    #[test]
+    fn test_template_instantiation() {
+        let key = SaplingKey::generate_key();
+        let recipient = SaplingKey::generate_key().public_address();
+
+        let mut template = TransactionTemplate::new(FeePolicy::Fixed(1));
+        template
+            .add_recipient(recipient, 10, NATIVE_ASSET, "payment {n}")
+            .unwrap();
+
+        // The same template pays out again against fresh notes
+        for instance in 1..=2 {
+            let notes = [spendable_note(&key, 20)];
+            let transaction = template
+                .post_instance(key.clone(), &notes, instance, None)
+                .unwrap();
+
+            assert_eq!(transaction.fee(), 1);
+            assert_eq!(transaction.spends().len(), 1);
+            // The payment and the change
+            assert_eq!(transaction.outputs().len(), 2);
+        }
+
+        // Not enough to pay the recipient and the fee
+        let notes = [spendable_note(&key, 10)];
+        assert!(template.post_instance(key, &notes, 3, None).is_err());
+    }
+}
@@ -6,14 +6,33 @@
 use super::{ProposedTransaction, Transaction};
 use crate::{
     assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+    errors::IronfishError,
     keys::SaplingKey,
-    merkle_note::NOTE_ENCRYPTION_MINER_KEYS,
+    merkle_note::{MerkleNote, NOTE_ENCRYPTION_MINER_KEYS},
     note::Note,
+    parameter_sets::{register_parameter_set, unregister_parameter_set},
     test_util::make_fake_witness,
-    transaction::{TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE, TRANSACTION_SIGNATURE_SIZE},
+    transaction::{
+        block_verifier::BlockVerifier,
+        burns::BurnDescription,
+        extra_data::{ExtraData, ENTRY_COUNT_SIZE, ENTRY_HEADER_SIZE, MAX_EXTRA_DATA_SIZE},
+        fee::{compare_by_priority, FEE_RATE_BYTES},
+        limits::{Limits, ReadLimits},
+        mints::MintDescription,
+        spends::SPEND_DESCRIPTION_SIZE,
+        streaming::TransactionVerifier,
+        verification::{FailedComponent, VerifyLevel},
+        EXTRA_DATA_TRANSACTION_VERSION, TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE,
+        TRANSACTION_SIGNATURE_SIZE, TRANSACTION_VERSION, TREE_ID_TRANSACTION_VERSION,
+    },
+    value_range::{ValueContext, ValueError},
+    witness::{TreeId, Witness, WitnessNode, WitnessTrait},
+    MerkleNoteHash, NetworkKind, OutputDescription, Sapling, SpendDescription,
 };
 
+use bls12_381::Scalar;
 use ironfish_zkp::redjubjub::Signature;
+use std::sync::Arc;
 
 #[test]
 fn test_transaction() {
@@ -183,9 +202,16 @@ fn test_transaction_simple() {
     assert_eq!(public_transaction.mints.len(), 0);
     assert_eq!(public_transaction.burns.len(), 0);
 
-    let received_note = public_transaction.outputs[1]
-        .merkle_note()
-        .decrypt_note_for_owner(&spender_key_clone.incoming_viewing_key)
+    // Outputs are sorted when posting, so the change can be either one
+    let received_note = public_transaction
+        .outputs
+        .iter()
+        .find_map(|output| {
+            output
+                .merkle_note()
+                .decrypt_note_for_owner(&spender_key_clone.incoming_viewing_key)
+                .ok()
+        })
         .unwrap();
     assert_eq!(received_note.sender, spender_key_clone.public_address());
 }
@@ -358,11 +384,913 @@ fn test_transaction_value_overflows() {
     assert!(tx.add_spend(note.clone(), &witness).is_err());
 
     // output
-    assert!(tx.add_output(note).is_err());
+    assert!(matches!(
+        tx.add_output(note),
+        Err(IronfishError::InvalidValue(ValueError::TooLarge {
+            context: ValueContext::Note,
+            ..
+        }))
+    ));
 
     // mint
     assert!(tx.add_mint(asset, overflow_value).is_err());
 
     // burn
     assert!(tx.add_burn(asset.id, overflow_value).is_err());
+
+    // Mints and burns of nothing can't be built
+    assert!(matches!(
+        tx.add_mint(asset, 0),
+        Err(IronfishError::InvalidValue(ValueError::Zero {
+            context: ValueContext::Mint
+        }))
+    ));
+    assert!(matches!(
+        tx.add_burn(asset.id, 0),
+        Err(IronfishError::InvalidValue(ValueError::Zero {
+            context: ValueContext::Burn
+        }))
+    ));
+
+    // Neither can a fee that's out of range
+    assert!(matches!(
+        tx.post(None, overflow_value),
+        Err(IronfishError::InvalidValue(ValueError::TooLarge {
+            context: ValueContext::Fee,
+            ..
+        }))
+    ));
+}
+
+#[test]
+fn test_transaction_verify_with_level() {
+    let spender_key = SaplingKey::generate_key();
+    let receiver_key = SaplingKey::generate_key();
+    let sender_key = SaplingKey::generate_key();
+
+    let in_note = Note::new(
+        spender_key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        sender_key.public_address(),
+    );
+    let out_note = Note::new(
+        receiver_key.public_address(),
+        40,
+        "",
+        NATIVE_ASSET,
+        spender_key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut transaction = ProposedTransaction::new(spender_key);
+    transaction.add_spend(in_note, &witness).unwrap();
+    transaction.add_output(out_note).unwrap();
+
+    let mut public_transaction = transaction
+        .post(None, 1)
+        .expect("should be able to post transaction");
+
+    let structural = public_transaction
+        .verify_with_level(VerifyLevel::Structural)
+        .expect("structure should be valid");
+    assert!(structural.structure);
+    assert!(!structural.signatures);
+    assert!(!structural.proofs);
+
+    let signatures = public_transaction
+        .verify_with_level(VerifyLevel::Signatures)
+        .expect("signatures should be valid");
+    assert!(signatures.signatures);
+    assert!(!signatures.is_fully_verified());
+
+    let full = public_transaction
+        .verify_with_level(VerifyLevel::Full)
+        .expect("transaction should be valid");
+    assert!(full.is_fully_verified());
+
+    // Tampering with the fee invalidates the signatures, but the transaction
+    // is still structurally sound
+    public_transaction.fee = 2;
+    public_transaction
+        .verify_with_level(VerifyLevel::Structural)
+        .expect("structure should still be valid");
+    public_transaction
+        .verify_with_level(VerifyLevel::Signatures)
+        .expect_err("signatures should no longer be valid");
+}
+
+#[test]
+fn test_transaction_limits() {
+    let key = SaplingKey::generate_key();
+    let asset = Asset::new(key.public_address(), "testcoin", "").unwrap();
+
+    let note = Note::new(
+        key.public_address(),
+        10,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&note);
+
+    let limits = Limits {
+        max_spends: 1,
+        max_outputs: 1,
+        max_mints: 1,
+        max_burns: 1,
+    };
+    let mut tx = ProposedTransaction::with_limits(key, limits).unwrap();
+
+    tx.add_spend(note.clone(), &witness).unwrap();
+    assert!(matches!(
+        tx.add_spend(note.clone(), &witness),
+        Err(IronfishError::TooManySpends)
+    ));
+
+    tx.add_output(note.clone()).unwrap();
+    assert!(matches!(
+        tx.add_output(note),
+        Err(IronfishError::TooManyOutputs)
+    ));
+
+    tx.add_mint(asset, 5).unwrap();
+    assert!(matches!(
+        tx.add_mint(asset, 5),
+        Err(IronfishError::TooManyMints)
+    ));
+
+    tx.add_burn(asset.id, 5).unwrap();
+    assert!(matches!(
+        tx.add_burn(asset.id, 5),
+        Err(IronfishError::TooManyBurns)
+    ));
+
+    // Rejected descriptions don't affect the balance, so the transaction
+    // posts with no change
+    let posted = tx
+        .post(None, 0)
+        .expect("should be able to post transaction");
+    assert!(Limits::default().check_transaction(&posted).is_ok());
+    assert!(limits.check_transaction(&posted).is_ok());
+
+    let stricter = Limits {
+        max_mints: 0,
+        ..limits
+    };
+    assert!(matches!(
+        stricter.check_transaction(&posted),
+        Err(IronfishError::TooManyMints)
+    ));
+}
+
+#[test]
+fn test_transaction_read_limits() {
+    let key = SaplingKey::generate_key();
+    let note = Note::new(
+        key.public_address(),
+        10,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&note);
+
+    let mut tx = ProposedTransaction::new(key);
+    tx.add_spend(note.clone(), &witness).unwrap();
+    tx.add_output(note).unwrap();
+    let posted = tx.post(None, 0).unwrap();
+
+    let mut serialized = vec![];
+    posted.write(&mut serialized).unwrap();
+
+    let limits = ReadLimits {
+        descriptions: Limits {
+            max_spends: 1,
+            max_outputs: 1,
+            max_mints: 0,
+            max_burns: 0,
+        },
+        max_bytes: serialized.len(),
+    };
+    let read_back =
+        Transaction::read_with_limits(&serialized[..], NetworkKind::Mainnet, &limits).unwrap();
+    assert_eq!(
+        read_back.transaction_signature_hash(),
+        posted.transaction_signature_hash()
+    );
+
+    let too_small = ReadLimits {
+        max_bytes: serialized.len() - 1,
+        ..limits
+    };
+    assert!(matches!(
+        Transaction::read_with_limits(&serialized[..], NetworkKind::Mainnet, &too_small),
+        Err(IronfishError::TransactionTooLarge)
+    ));
+
+    let no_spends = ReadLimits {
+        descriptions: Limits {
+            max_spends: 0,
+            ..limits.descriptions
+        },
+        ..limits
+    };
+    assert!(matches!(
+        Transaction::read_with_limits(&serialized[..], NetworkKind::Mainnet, &no_spends),
+        Err(IronfishError::TooManySpends)
+    ));
+
+    // A header claiming more spends than could ever be sent is refused
+    // before anything is allocated for them, even without limits
+    let mut forged = serialized[..1].to_vec();
+    forged.extend_from_slice(&u64::MAX.to_le_bytes());
+    forged.extend_from_slice(&serialized[9..]);
+    assert!(matches!(
+        Transaction::read_with_limits(&forged[..], NetworkKind::Mainnet, &ReadLimits::UNLIMITED),
+        Err(IronfishError::TransactionTooLarge)
+    ));
+}
+
+#[test]
+fn test_transaction_read_limits_for_version() {
+    let key = SaplingKey::generate_key();
+    let note = Note::new(
+        key.public_address(),
+        10,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&note);
+
+    // As large as the extra data of a transaction can get
+    let mut extra_data = ExtraData::new();
+    extra_data
+        .insert_raw(
+            1,
+            vec![7; MAX_EXTRA_DATA_SIZE - ENTRY_COUNT_SIZE - ENTRY_HEADER_SIZE],
+        )
+        .unwrap();
+    assert_eq!(extra_data.serialized_size(), MAX_EXTRA_DATA_SIZE);
+
+    let mut tx = ProposedTransaction::new(key);
+    tx.set_version(EXTRA_DATA_TRANSACTION_VERSION).unwrap();
+    tx.set_extra_data(extra_data).unwrap();
+    tx.add_spend(note.clone(), &witness).unwrap();
+    tx.add_output(note).unwrap();
+    let posted = tx.post(None, 0).unwrap();
+
+    let mut serialized = vec![];
+    posted.write(&mut serialized).unwrap();
+
+    // The spend carries its tree id and the extra data is at its maximum,
+    // so the transaction is exactly as large as its header allows
+    let limits = ReadLimits {
+        descriptions: Limits::UNLIMITED,
+        max_bytes: serialized.len(),
+    };
+    let read_back =
+        Transaction::read_with_limits(&serialized[..], NetworkKind::Mainnet, &limits).unwrap();
+    assert_eq!(
+        read_back.transaction_signature_hash(),
+        posted.transaction_signature_hash()
+    );
+
+    let too_small = ReadLimits {
+        max_bytes: serialized.len() - 1,
+        ..limits
+    };
+    assert!(matches!(
+        Transaction::read_with_limits(&serialized[..], NetworkKind::Mainnet, &too_small),
+        Err(IronfishError::TransactionTooLarge)
+    ));
+}
+
+#[test]
+fn test_transaction_burn_checks() {
+    let key = SaplingKey::generate_key();
+    let asset = Asset::new(key.public_address(), "testcoin", "").unwrap();
+
+    let note = Note::new(
+        key.public_address(),
+        10,
+        "",
+        *asset.id(),
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&note);
+
+    // The total burned per asset has to fit in a value balance
+    let mut tx = ProposedTransaction::new(key.clone());
+    tx.add_burn(asset.id, i64::MAX as u64).unwrap();
+    assert!(matches!(
+        tx.add_burn(asset.id, 1),
+        Err(IronfishError::BurnValueOverflow)
+    ));
+    assert!(matches!(
+        tx.add_burn(asset.id, u64::MAX),
+        Err(IronfishError::BurnValueOverflow)
+    ));
+
+    // Burning more than is spent and minted is reported as such
+    let mut tx = ProposedTransaction::new(key.clone());
+    tx.add_spend(note.clone(), &witness).unwrap();
+    tx.add_mint(asset, 5).unwrap();
+    tx.add_burn(asset.id, 16).unwrap();
+    assert!(matches!(
+        tx.post(None, 0),
+        Err(IronfishError::BurnExceedsBalance)
+    ));
+
+    let mut tx = ProposedTransaction::new(key);
+    tx.add_spend(note, &witness).unwrap();
+    tx.add_mint(asset, 5).unwrap();
+    tx.add_burn(asset.id, 15).unwrap();
+    assert!(tx.post(None, 0).is_ok());
+}
+
+#[test]
+fn test_posted_values_checked_at_every_level() {
+    let key = SaplingKey::generate_key();
+    let asset = Asset::new(key.public_address(), "testcoin", "").unwrap();
+
+    let mut tx = ProposedTransaction::new(key);
+    tx.add_mint(asset, 5).unwrap();
+    tx.add_burn(asset.id, 2).unwrap();
+    let posted = tx.post(None, 0).unwrap();
+
+    let is_too_large = |result: Result<(), IronfishError>, expected: ValueContext| {
+        matches!(
+            result,
+            Err(IronfishError::InvalidValue(ValueError::TooLarge { context, .. }))
+                if context == expected
+        )
+    };
+    let streamed = |transaction: &Transaction| {
+        let mut serialized = vec![];
+        transaction.write(&mut serialized).unwrap();
+        TransactionVerifier::new().push(&serialized).map(|_| ())
+    };
+
+    // A mint and a burn no transaction could be built with
+    let mut large_mint = posted.clone();
+    large_mint.mints[0].value = (i64::MAX as u64) + 1;
+    let mut large_burn = posted;
+    large_burn.burns[0].value = (i64::MAX as u64) + 1;
+
+    for (transaction, context, component) in [
+        (&large_mint, ValueContext::Mint, FailedComponent::Mint(0)),
+        (&large_burn, ValueContext::Burn, FailedComponent::Burn(0)),
+    ] {
+        assert!(is_too_large(transaction.verify(), context));
+        assert!(is_too_large(
+            BlockVerifier::unbounded().add_transaction(transaction),
+            context
+        ));
+        assert!(is_too_large(streamed(transaction), context));
+        assert!(transaction
+            .verify_collect()
+            .into_iter()
+            .any(|failure| failure.component == component
+                && is_too_large(Err(failure.error), context)));
+    }
+}
+
+#[test]
+fn test_transaction_verify_collect() {
+    let spender_key = SaplingKey::generate_key();
+    let receiver_key = SaplingKey::generate_key();
+
+    let in_note = Note::new(
+        spender_key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        receiver_key.public_address(),
+    );
+    let out_note = Note::new(
+        receiver_key.public_address(),
+        40,
+        "",
+        NATIVE_ASSET,
+        spender_key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut transaction = ProposedTransaction::new(spender_key);
+    transaction.add_spend(in_note, &witness).unwrap();
+    transaction.add_output(out_note).unwrap();
+
+    let mut public_transaction = transaction
+        .post(None, 1)
+        .expect("should be able to post transaction");
+
+    assert!(public_transaction.verify_collect().is_empty());
+
+    // Every check after the version keeps running, and the proofs don't
+    // depend on the fee, so only the signatures are reported
+    public_transaction.version = u8::MAX;
+    public_transaction.fee = 2;
+
+    let components: Vec<_> = public_transaction
+        .verify_collect()
+        .iter()
+        .map(|failure| failure.component)
+        .collect();
+    assert_eq!(
+        components,
+        vec![
+            FailedComponent::Version,
+            FailedComponent::Spend(0),
+            FailedComponent::BindingSignature,
+        ]
+    );
+}
+
+#[test]
+fn test_descriptions_to_and_from_bytes() {
+    let key = SaplingKey::generate_key();
+    let asset = Asset::new(key.public_address(), "testcoin", "").unwrap();
+
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut transaction = ProposedTransaction::new(key);
+    transaction.add_spend(in_note, &witness).unwrap();
+    transaction.add_mint(asset, 5).unwrap();
+    transaction.add_burn(asset.id, 2).unwrap();
+
+    let posted = transaction
+        .post(None, 1)
+        .expect("should be able to post transaction");
+
+    let spend = &posted.spends()[0];
+    let mut serialized = vec![];
+    spend.write(&mut serialized).unwrap();
+    let bytes = spend.to_bytes().unwrap();
+    assert_eq!(&bytes[..], &serialized[..]);
+    let parsed = SpendDescription::read(&bytes[..]).unwrap();
+    assert_eq!(parsed.to_bytes().unwrap(), bytes);
+
+    for output in posted.outputs() {
+        let mut serialized = vec![];
+        output.write(&mut serialized).unwrap();
+        assert_eq!(&output.to_bytes()[..], &serialized[..]);
+        let parsed = OutputDescription::read(&output.to_bytes()[..]).unwrap();
+        assert!(parsed.merkle_note() == output.merkle_note());
+        assert_eq!(
+            MerkleNote::read(&output.merkle_note().to_bytes()[..])
+                .unwrap()
+                .to_bytes(),
+            output.merkle_note().to_bytes()
+        );
+    }
+
+    let mint = &posted.mints()[0];
+    let mut serialized = vec![];
+    mint.write(&mut serialized).unwrap();
+    assert_eq!(&mint.to_bytes()[..], &serialized[..]);
+    let parsed = MintDescription::read(&mint.to_bytes()[..]).unwrap();
+    assert_eq!(parsed.to_bytes(), mint.to_bytes());
+
+    let burn = &posted.burns()[0];
+    let mut serialized = vec![];
+    burn.write(&mut serialized).unwrap();
+    assert_eq!(&burn.to_bytes()[..], &serialized[..]);
+    let parsed = BurnDescription::read(&burn.to_bytes()[..]).unwrap();
+    assert_eq!(parsed.to_bytes(), burn.to_bytes());
+}
+
+#[test]
+fn test_mint_and_burn_accessors() {
+    let key = SaplingKey::generate_key();
+    let asset = Asset::new(key.public_address(), "Testcoin", "").unwrap();
+    let other_asset = Asset::new(key.public_address(), "Othercoin", "").unwrap();
+
+    let mut transaction = ProposedTransaction::new(key.clone());
+    transaction.add_mint(asset, 5).unwrap();
+    transaction.add_mint(asset, 7).unwrap();
+    transaction.add_mint(other_asset, 1).unwrap();
+    transaction.add_burn(asset.id, 2).unwrap();
+    let posted = transaction.post(None, 0).unwrap();
+
+    let mint = posted.iter_mints().next().unwrap();
+    assert_eq!(mint.asset_id(), asset.id());
+    assert_eq!(mint.value(), 5);
+    assert!(mint.owner() == key.public_address());
+
+    let burn = posted.iter_burns().next().unwrap();
+    assert_eq!(burn.asset_id(), asset.id());
+    assert_eq!(burn.value(), 2);
+
+    let minted = posted.minted_by_asset();
+    assert_eq!(minted.len(), 2);
+    assert_eq!(minted[asset.id()], 12);
+    assert_eq!(minted[other_asset.id()], 1);
+    assert_eq!(posted.minted_value(asset.id()), 12);
+    assert_eq!(posted.minted_value(&NATIVE_ASSET), 0);
+
+    assert_eq!(posted.burned_by_asset()[asset.id()], 2);
+    assert_eq!(posted.burned_value(asset.id()), 2);
+    assert_eq!(posted.burned_value(other_asset.id()), 0);
+}
+
+#[test]
+fn test_add_mint_to() {
+    let key = SaplingKey::generate_key();
+    let recipient_key = SaplingKey::generate_key();
+    let asset = Asset::new(key.public_address(), "Testcoin", "").unwrap();
+
+    let mut transaction = ProposedTransaction::new(key.clone());
+    transaction
+        .add_mint_to(recipient_key.public_address(), asset, 5)
+        .unwrap();
+    let posted = transaction.post(None, 0).unwrap();
+    posted.verify().unwrap();
+
+    // Everything minted went to the recipient, so there's no change
+    assert_eq!(posted.minted_value(asset.id()), 5);
+    assert_eq!(posted.outputs().len(), 1);
+    let note = posted.outputs()[0]
+        .merkle_note()
+        .decrypt_note_for_owner(recipient_key.incoming_view_key())
+        .unwrap();
+    assert_eq!(note.value(), 5);
+    assert_eq!(note.asset_id(), asset.id());
+    assert!(note.sender() == key.public_address());
+
+    // Without room for the output, the mint isn't added either
+    let limits = Limits {
+        max_outputs: 0,
+        ..Limits::default()
+    };
+    let mut transaction = ProposedTransaction::with_limits(key, limits).unwrap();
+    assert!(matches!(
+        transaction.add_mint_to(recipient_key.public_address(), asset, 5),
+        Err(IronfishError::TooManyOutputs)
+    ));
+    assert!(transaction.post(None, 0).unwrap().mints().is_empty());
+}
+
+#[test]
+fn test_output_ephemeral_secrets() {
+    let key = SaplingKey::generate_key();
+    let receiver_key = SaplingKey::generate_key();
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let out_note = Note::new(
+        receiver_key.public_address(),
+        40,
+        "sent memo",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut proposed = ProposedTransaction::new(key.clone());
+    proposed.add_spend(in_note.clone(), &witness).unwrap();
+    proposed.add_output(out_note.clone()).unwrap();
+    let posted = proposed.post(None, 2).unwrap();
+
+    let secrets = proposed.output_ephemeral_secrets();
+    assert_eq!(secrets.len(), posted.outputs().len());
+
+    // The secret and the recipient's address are enough to read what was sent
+    let merkle_note = posted.outputs()[0].merkle_note();
+    let note = merkle_note
+        .decrypt_note_with_ephemeral_secret(&receiver_key.public_address(), &secrets[0])
+        .unwrap();
+    assert_eq!(note.memo(), out_note.memo());
+    assert!(matches!(
+        merkle_note.decrypt_note_with_ephemeral_secret(&key.public_address(), &secrets[0]),
+        Err(IronfishError::InvalidDecryptionKey)
+    ));
+    assert!(matches!(
+        merkle_note
+            .decrypt_note_with_ephemeral_secret(&receiver_key.public_address(), &jubjub::Fr::one()),
+        Err(IronfishError::InvalidDecryptionKey)
+    ));
+
+    // Rebuilding with the secret encrypts the note exactly as before
+    let mut proposed = ProposedTransaction::new(key);
+    proposed.add_spend(in_note, &witness).unwrap();
+    proposed
+        .add_output_with_ephemeral_secret(out_note, secrets[0])
+        .unwrap();
+    let resent = proposed.post(None, 2).unwrap();
+    let resent_note = resent.outputs()[0].merkle_note();
+    assert_eq!(
+        resent_note.ephemeral_public_key,
+        merkle_note.ephemeral_public_key
+    );
+    assert_eq!(resent_note.encrypted_note, merkle_note.encrypted_note);
+    posted.verify().unwrap();
+    resent.verify().unwrap();
+}
+
+#[test]
+fn test_transaction_fee_rate() {
+    let key = SaplingKey::generate_key();
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut transaction = ProposedTransaction::new(key.clone());
+    transaction.add_spend(in_note.clone(), &witness).unwrap();
+    let cheap = transaction.post(None, 1).unwrap();
+
+    let mut transaction = ProposedTransaction::new(key);
+    transaction.add_spend(in_note, &witness).unwrap();
+    let expensive = transaction.post(None, 10_000).unwrap();
+
+    let mut serialized = vec![];
+    cheap.write(&mut serialized).unwrap();
+    assert_eq!(cheap.serialized_size(), serialized.len());
+    assert_eq!(
+        expensive.fee_rate(),
+        10_000 * FEE_RATE_BYTES / serialized.len() as u64
+    );
+
+    let mut transactions = vec![cheap, expensive];
+    transactions.sort_by(compare_by_priority);
+    assert_eq!(transactions[0].fee(), 10_000);
+}
+
+/// A witness into a tree other than the note tree
+struct OtherTreeWitness(Witness);
+
+impl WitnessTrait for OtherTreeWitness {
+    fn verify(&self, my_hash: &MerkleNoteHash) -> bool {
+        self.0.verify(my_hash)
+    }
+
+    fn get_auth_path(&self) -> Vec<WitnessNode<Scalar>> {
+        self.0.get_auth_path()
+    }
+
+    fn root_hash(&self) -> Scalar {
+        self.0.root_hash()
+    }
+
+    fn tree_size(&self) -> u32 {
+        self.0.tree_size()
+    }
+
+    fn tree_id(&self) -> TreeId {
+        TreeId(1)
+    }
+}
+
+#[test]
+fn test_spends_of_other_trees_need_tree_ids() {
+    let key = SaplingKey::generate_key();
+    let note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = OtherTreeWitness(make_fake_witness(&note));
+
+    let mut transaction = ProposedTransaction::new(key);
+    assert!(matches!(
+        transaction.add_spend(note, &witness),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+    assert!(transaction.spends.is_empty());
+}
+
+#[test]
+fn test_transaction_tree_id_version() {
+    let key = SaplingKey::generate_key();
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let out_note = Note::new(
+        key.public_address(),
+        40,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut transaction = ProposedTransaction::new(key);
+    transaction
+        .set_version(TREE_ID_TRANSACTION_VERSION)
+        .unwrap();
+    transaction.add_spend(in_note, &witness).unwrap();
+    transaction.add_output(out_note).unwrap();
+    let posted = transaction.post(None, 1).unwrap();
+
+    // Only accepted once the node activates the version
+    assert!(matches!(
+        posted.verify(),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+    assert!(matches!(
+        BlockVerifier::unbounded().add_transaction(&posted),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+    assert!(!posted.verify_collect().is_empty());
+
+    posted
+        .verify_for_version(TREE_ID_TRANSACTION_VERSION)
+        .unwrap();
+    posted
+        .verify_for_version(EXTRA_DATA_TRANSACTION_VERSION)
+        .unwrap();
+    assert!(posted
+        .verify_collect_for_version(TREE_ID_TRANSACTION_VERSION)
+        .is_empty());
+    let mut verifier =
+        BlockVerifier::unbounded().with_activated_version(TREE_ID_TRANSACTION_VERSION);
+    verifier.add_transaction(&posted).unwrap();
+    verifier.finish().unwrap();
+
+    // The spends carry their tree id, so they're a byte longer than the
+    // layout of the first version
+    let mut serialized = vec![];
+    posted.write(&mut serialized).unwrap();
+    let read_back = Transaction::read(&serialized[..]).unwrap();
+    assert_eq!(read_back.version, TREE_ID_TRANSACTION_VERSION);
+    assert_eq!(read_back.spends()[0].tree_id(), TreeId::NOTES);
+    read_back
+        .verify_for_version(TREE_ID_TRANSACTION_VERSION)
+        .unwrap();
+
+    let mut serialized_spend = vec![];
+    posted.spends()[0]
+        .write_for_version(&mut serialized_spend, TREE_ID_TRANSACTION_VERSION)
+        .unwrap();
+    assert_eq!(serialized_spend.len(), SPEND_DESCRIPTION_SIZE + 1);
+}
+
+#[test]
+fn test_verify_with_registered_parameter_set() {
+    // A version transactions can't be built in, that no other test
+    // registers parameters for, as parameter sets are shared by every test
+    const OLD_VERSION: u8 = 210;
+
+    let key = SaplingKey::generate_key();
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut transaction = ProposedTransaction::new(key);
+    transaction.add_spend(in_note, &witness).unwrap();
+    transaction.version = OLD_VERSION;
+    let posted = transaction.post(None, 1).unwrap();
+    assert!(matches!(
+        posted.verify_for_version(OLD_VERSION),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+
+    // Parameters of a generation whose spend circuit is another one, which
+    // the spend wasn't proven with
+    let Sapling {
+        spend_params,
+        output_params,
+        mint_params,
+        ..
+    } = Sapling::load();
+    let swapped = Sapling::from_proving_params(output_params, spend_params, mint_params);
+    register_parameter_set(OLD_VERSION..=OLD_VERSION, Arc::new(swapped)).unwrap();
+    assert!(posted.verify_for_version(OLD_VERSION).is_err());
+    unregister_parameter_set(OLD_VERSION).unwrap();
+
+    // Parameters the spend was proven with
+    register_parameter_set(OLD_VERSION..=OLD_VERSION, Arc::new(Sapling::load())).unwrap();
+    posted.verify_for_version(OLD_VERSION).unwrap();
+    assert!(posted.verify_collect_for_version(OLD_VERSION).is_empty());
+
+    // Registered parameters don't activate their versions
+    assert!(matches!(
+        posted.verify(),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+    unregister_parameter_set(OLD_VERSION).unwrap();
+}
+
+#[test]
+fn test_transaction_memo_tag() {
+    let key = SaplingKey::generate_key();
+
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let memo_key = key.outgoing_view_key().memo_key(3);
+    let mut transaction = ProposedTransaction::new(key.clone());
+    transaction.add_spend(in_note, &witness).unwrap();
+    transaction.set_memo_key(&memo_key);
+    let posted = transaction.post(None, 1).unwrap();
+    posted.verify().unwrap();
+
+    // The sender can recompute the tag from its number alone
+    let tag = key.outgoing_view_key().memo_key(3).tag(key.view_key());
+    assert_eq!(posted.memo_tag(), tag);
+    assert_ne!(key.outgoing_view_key().memo_key(4).tag(key.view_key()), tag);
+
+    // Other senders' memo keys don't produce the tag
+    let other_key = SaplingKey::generate_key();
+    assert_ne!(
+        other_key
+            .outgoing_view_key()
+            .memo_key(3)
+            .tag(other_key.view_key()),
+        tag
+    );
+}
+
+#[test]
+fn test_transaction_extra_data() {
+    let key = SaplingKey::generate_key();
+    let in_note = Note::new(
+        key.public_address(),
+        42,
+        "",
+        NATIVE_ASSET,
+        key.public_address(),
+    );
+    let witness = make_fake_witness(&in_note);
+
+    let mut extra_data = ExtraData::new();
+    extra_data.insert_raw(1, b"invoice 17".to_vec()).unwrap();
+
+    let mut transaction = ProposedTransaction::new(key);
+    transaction.add_spend(in_note, &witness).unwrap();
+    assert!(matches!(
+        transaction.set_extra_data(extra_data.clone()),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+
+    transaction
+        .set_version(EXTRA_DATA_TRANSACTION_VERSION)
+        .unwrap();
+    transaction.set_extra_data(extra_data.clone()).unwrap();
+    assert!(matches!(
+        transaction.set_version(TRANSACTION_VERSION),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+
+    let posted = transaction.post(None, 1).unwrap();
+    assert_eq!(posted.extra_data(), &extra_data);
+    assert!(matches!(
+        posted.verify_for_version(TREE_ID_TRANSACTION_VERSION),
+        Err(IronfishError::InvalidTransactionVersion)
+    ));
+    posted
+        .verify_for_version(EXTRA_DATA_TRANSACTION_VERSION)
+        .unwrap();
+
+    let mut serialized = vec![];
+    posted.write(&mut serialized).unwrap();
+    let read_back = Transaction::read(&serialized[..]).unwrap();
+    assert_eq!(read_back.extra_data(), &extra_data);
+    assert_eq!(
+        read_back.transaction_signature_hash(),
+        posted.transaction_signature_hash()
+    );
+
+    // The extra data is signed along with the rest of the transaction
+    let mut tampered = posted.clone();
+    tampered.extra_data.insert_raw(2, vec![]).unwrap();
+    assert_ne!(
+        tampered.transaction_signature_hash(),
+        posted.transaction_signature_hash()
+    );
 }
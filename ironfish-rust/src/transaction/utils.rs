@@ -3,8 +3,14 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 use bellman::groth16;
 use bls12_381::Bls12;
+use ironfish_zkp::primitives::ValueCommitment;
 
-use crate::{errors::IronfishError, sapling_bls12::SAPLING};
+use crate::{
+    errors::IronfishError,
+    metrics::Circuit,
+    proof_system::{verify_proof, verify_proof_for_version, verify_proofs},
+    randomness::random_scalar,
+};
 
 /// Helper function for verifying spend proof internally. Note that this is not
 /// called by verifiers as part of transaction verification. See
@@ -13,9 +19,7 @@ pub(crate) fn verify_spend_proof(
     proof: &groth16::Proof<Bls12>,
     inputs: &[bls12_381::Scalar],
 ) -> Result<(), IronfishError> {
-    groth16::verify_proof(&SAPLING.spend_verifying_key, proof, inputs)?;
-
-    Ok(())
+    verify_proof(Circuit::Spend, proof, inputs)
 }
 
 /// Helper function for verifying output proof internally. Note that this is not
@@ -25,9 +29,7 @@ pub(crate) fn verify_output_proof(
     proof: &groth16::Proof<Bls12>,
     inputs: &[bls12_381::Scalar],
 ) -> Result<(), IronfishError> {
-    groth16::verify_proof(&SAPLING.output_verifying_key, proof, inputs)?;
-
-    Ok(())
+    verify_proof(Circuit::Output, proof, inputs)
 }
 
 /// Helper function for verifying mint proof internally. Note that this is not
@@ -37,7 +39,40 @@ pub(crate) fn verify_mint_proof(
     proof: &groth16::Proof<Bls12>,
     inputs: &[bls12_381::Scalar],
 ) -> Result<(), IronfishError> {
-    groth16::verify_proof(&SAPLING.mint_verifying_key, proof, inputs)?;
+    verify_proof(Circuit::Mint, proof, inputs)
+}
+
+/// Verify a proof of the circuit from a posted transaction of the given
+/// version, with the parameters of the version, see
+/// [`crate::parameter_sets`]
+pub(crate) fn verify_posted_proof(
+    version: u8,
+    circuit: Circuit,
+    proof: &groth16::Proof<Bls12>,
+    inputs: &[bls12_381::Scalar],
+) -> Result<(), IronfishError> {
+    verify_proof_for_version(version, circuit, proof, inputs)
+}
+
+/// Verify a batch of queued proofs of the circuit from posted transactions
+/// of the given version, as done by [`super::batch_verify_transactions`]
+pub(crate) fn verify_proof_batch(
+    version: u8,
+    circuit: Circuit,
+    proofs: &[(&groth16::Proof<Bls12>, Vec<bls12_381::Scalar>)],
+) -> Result<(), IronfishError> {
+    verify_proofs(version, circuit, proofs)
+}
 
-    Ok(())
+/// A commitment to the value with fresh randomness, like
+/// [`ValueCommitment::new`] but drawing from the randomness source in use
+pub(crate) fn new_value_commitment(
+    value: u64,
+    asset_generator: jubjub::ExtendedPoint,
+) -> Result<ValueCommitment, IronfishError> {
+    Ok(ValueCommitment {
+        value,
+        randomness: random_scalar()?,
+        asset_generator,
+    })
 }
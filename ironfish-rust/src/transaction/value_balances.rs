@@ -8,6 +8,7 @@ use crate::{
     errors::IronfishError,
 };
 
+#[derive(Clone)]
 pub struct ValueBalances {
     values: HashMap<AssetIdentifier, i64>,
 }
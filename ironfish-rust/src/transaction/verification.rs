@@ -0,0 +1,63 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+use crate::errors::IronfishError;
+
+/// How much of a transaction to verify. Levels are ordered, and each level
+/// includes all of the checks performed by the levels below it.
+///
+/// Verifying the zero-knowledge proofs is by far the most expensive part of
+/// validating a transaction, so a mempool can admit transactions after the
+/// cheaper checks and defer the proofs to block validation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum VerifyLevel {
+    /// Transaction version, and that every description is well formed: no
+    /// small order points and valid asset identifiers.
+    Structural,
+
+    /// Structural checks, plus the spend and mint authorizing signatures and
+    /// the binding signature.
+    Signatures,
+
+    /// Every check, including the spend, output and mint proofs. This is
+    /// equivalent to [`super::Transaction::verify`].
+    Full,
+}
+
+/// The checks that were performed, and passed, when verifying a transaction
+/// at a given [`VerifyLevel`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VerifiedChecks {
+    pub structure: bool,
+    pub signatures: bool,
+    pub proofs: bool,
+}
+
+impl VerifiedChecks {
+    /// Returns true if the checks cover everything required for the
+    /// transaction to be accepted into a block.
+    pub fn is_fully_verified(&self) -> bool {
+        self.structure && self.signatures && self.proofs
+    }
+}
+
+/// The part of a transaction that a [`VerificationFailure`] refers to.
+/// Descriptions are identified by their index within the transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FailedComponent {
+    Version,
+    Spend(usize),
+    Output(usize),
+    Mint(usize),
+    Burn(usize),
+    BindingSignature,
+}
+
+/// A single check that failed while verifying a transaction with
+/// [`super::Transaction::verify_collect`].
+#[derive(Debug)]
+pub struct VerificationFailure {
+    pub component: FailedComponent,
+    pub error: IronfishError,
+}
@@ -0,0 +1,248 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A cache of transactions that passed verification, so that a transaction
+//! verified when it entered the mempool isn't verified again when the block
+//! holding it is validated.
+//!
+//! Entries are keyed by [`Transaction::hash`], which covers every byte of the
+//! transaction, and remember the highest [`VerifyLevel`] the transaction
+//! passed: a transaction verified in full is also known to pass the lower
+//! levels, but one only checked structurally still has its signatures and
+//! proofs checked at a higher level. Only successes are cached. Whatever
+//! verification depends on besides the transaction, such as the proof
+//! system, is up to the caller: the cache has to be cleared, or the affected
+//! entries invalidated, when it changes.
+//!
+//! The cache holds a fixed number of transactions, evicting the least
+//! recently used, and can be shared between threads.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+use crate::errors::IronfishError;
+
+use super::{
+    verification::{VerifiedChecks, VerifyLevel},
+    Transaction,
+};
+
+/// Counts of the lookups made in a [`VerificationCache`] since it was created
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Transactions in the cache now
+    pub entries: usize,
+}
+
+pub struct VerificationCache {
+    capacity: usize,
+    inner: Mutex<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    /// The level each transaction passed, and when it was last used
+    entries: HashMap<[u8; 32], (VerifyLevel, u64)>,
+
+    /// Transactions by when they were last used, least recently used first
+    recency: BTreeMap<u64, [u8; 32]>,
+
+    next_use: u64,
+    stats: CacheStats,
+}
+
+impl VerificationCache {
+    /// A cache holding up to `capacity` transactions
+    pub fn new(capacity: usize) -> Self {
+        VerificationCache {
+            capacity,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// Verify the transaction up to the given level, see
+    /// [`Transaction::verify_with_level`], unless the cache shows it already
+    /// passed that level
+    pub fn verify(
+        &self,
+        transaction: &Transaction,
+        level: VerifyLevel,
+    ) -> Result<VerifiedChecks, IronfishError> {
+        let hash = transaction.hash();
+        if self.lookup(&hash, level) {
+            return Ok(checks_for(level));
+        }
+
+        let checks = transaction.verify_with_level(level)?;
+        self.insert(hash, level);
+        Ok(checks)
+    }
+
+    /// Whether the transaction with the given hash is known to pass the given
+    /// level, counted as a hit or a miss
+    pub fn lookup(&self, hash: &[u8; 32], level: VerifyLevel) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let use_count = inner.next_use;
+
+        let verified = match inner.entries.get_mut(hash) {
+            Some((verified, last_use)) if *verified >= level => {
+                let previous_use = *last_use;
+                *last_use = use_count;
+                Some(previous_use)
+            }
+            _ => None,
+        };
+
+        match verified {
+            Some(previous_use) => {
+                inner.recency.remove(&previous_use);
+                inner.recency.insert(use_count, *hash);
+                inner.next_use += 1;
+                inner.stats.hits += 1;
+                true
+            }
+            None => {
+                inner.stats.misses += 1;
+                false
+            }
+        }
+    }
+
+    /// Record that the transaction with the given hash passed the given level,
+    /// for example after verifying a batch of transactions together. A
+    /// higher level already recorded is kept.
+    pub fn insert(&self, hash: [u8; 32], level: VerifyLevel) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        let use_count = inner.next_use;
+        inner.next_use += 1;
+
+        let level = match inner.entries.remove(&hash) {
+            Some((verified, last_use)) => {
+                inner.recency.remove(&last_use);
+                verified.max(level)
+            }
+            None => level,
+        };
+
+        while inner.entries.len() >= self.capacity {
+            let (_, evicted) = inner.recency.pop_first().unwrap();
+            inner.entries.remove(&evicted);
+            inner.stats.evictions += 1;
+        }
+
+        inner.entries.insert(hash, (level, use_count));
+        inner.recency.insert(use_count, hash);
+    }
+
+    /// Forget the transaction with the given hash, returning whether it was
+    /// cached
+    pub fn invalidate(&self, hash: &[u8; 32]) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.entries.remove(hash) {
+            Some((_, last_use)) => {
+                inner.recency.remove(&last_use);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forget every transaction, for example after switching proof systems.
+    /// The stats keep counting.
+    pub fn clear(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.entries.clear();
+        inner.recency.clear();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock().unwrap();
+        CacheStats {
+            entries: inner.entries.len(),
+            ..inner.stats
+        }
+    }
+}
+
+/// The checks verifying at the given level performs
+fn checks_for(level: VerifyLevel) -> VerifiedChecks {
+    VerifiedChecks {
+        structure: true,
+        signatures: level >= VerifyLevel::Signatures,
+        proofs: level >= VerifyLevel::Full,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{CacheStats, VerificationCache};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        keys::SaplingKey,
+        note::Note,
+        test_util::make_fake_witness,
+        transaction::{verification::VerifyLevel, ProposedTransaction},
+    };
+
+    #[test]
+    fn test_verification_cache() {
+        let key = SaplingKey::generate_key();
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut proposed = ProposedTransaction::new(key);
+        proposed.add_spend(note, &witness).unwrap();
+        let transaction = proposed.post(None, 1).unwrap();
+        let hash = transaction.hash();
+
+        let cache = VerificationCache::new(2);
+        let checks = cache.verify(&transaction, VerifyLevel::Full).unwrap();
+        assert!(checks.is_fully_verified());
+
+        // Verified in full, so it passes every level
+        let checks = cache.verify(&transaction, VerifyLevel::Full).unwrap();
+        assert!(checks.is_fully_verified());
+        assert!(cache.lookup(&hash, VerifyLevel::Structural));
+        assert_eq!(
+            cache.stats(),
+            CacheStats {
+                hits: 2,
+                misses: 1,
+                evictions: 0,
+                entries: 1,
+            }
+        );
+
+        // Only checked structurally, so the proofs aren't known to be valid
+        cache.insert([1; 32], VerifyLevel::Structural);
+        assert!(!cache.lookup(&[1; 32], VerifyLevel::Signatures));
+
+        // The transaction was used last, so the other entry is evicted first
+        assert!(cache.lookup(&hash, VerifyLevel::Full));
+        cache.insert([2; 32], VerifyLevel::Full);
+        assert!(cache.lookup(&hash, VerifyLevel::Full));
+        assert!(!cache.lookup(&[1; 32], VerifyLevel::Structural));
+        assert_eq!(cache.stats().evictions, 1);
+
+        assert!(cache.invalidate(&hash));
+        assert!(!cache.lookup(&hash, VerifyLevel::Structural));
+        cache.clear();
+        assert_eq!(cache.stats().entries, 0);
+    }
+}
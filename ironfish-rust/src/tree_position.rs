@@ -0,0 +1,224 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Positions of notes in the note commitment tree, and sizes of the tree.
+//!
+//! Both used to be bare integers of different widths, which made it easy to
+//! pass a tree size where a position was expected, or the other way around,
+//! and to be off by one between the two. [`TreeSize`] is the number of leaves
+//! in a tree and [`NotePosition`] the zero based index of a leaf; converting
+//! between them and to other integer types is explicit and checked.
+
+use std::{fmt, num::TryFromIntError};
+
+use crate::witness::WitnessNode;
+
+/// Index of a leaf in the note commitment tree, starting at zero
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NotePosition(u64);
+
+impl NotePosition {
+    pub const fn new(position: u64) -> Self {
+        NotePosition(position)
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0
+    }
+
+    /// The position spelled out by an authentication path: at each level
+    /// the node is on the right exactly when that bit of the position is set.
+    pub fn from_auth_path<H: Clone + PartialEq + fmt::Debug>(auth_path: &[WitnessNode<H>]) -> Self {
+        let position = auth_path
+            .iter()
+            .enumerate()
+            .fold(0, |position, (level, node)| match node {
+                WitnessNode::Left(_) => position,
+                WitnessNode::Right(_) => position | (1 << level),
+            });
+
+        NotePosition(position)
+    }
+
+    /// Index of the ancestor of this leaf at the given level of the tree,
+    /// where the leaves are level zero
+    pub fn index_at(self, level: usize) -> u64 {
+        self.0 >> level
+    }
+
+    /// The position of the following leaf
+    pub fn next(self) -> Option<NotePosition> {
+        self.0.checked_add(1).map(NotePosition)
+    }
+}
+
+impl From<u32> for NotePosition {
+    fn from(position: u32) -> Self {
+        NotePosition(u64::from(position))
+    }
+}
+
+impl From<u64> for NotePosition {
+    fn from(position: u64) -> Self {
+        NotePosition(position)
+    }
+}
+
+impl From<NotePosition> for u64 {
+    fn from(position: NotePosition) -> Self {
+        position.0
+    }
+}
+
+impl TryFrom<usize> for NotePosition {
+    type Error = TryFromIntError;
+
+    fn try_from(position: usize) -> Result<Self, Self::Error> {
+        u64::try_from(position).map(NotePosition)
+    }
+}
+
+impl TryFrom<NotePosition> for usize {
+    type Error = TryFromIntError;
+
+    fn try_from(position: NotePosition) -> Result<Self, Self::Error> {
+        usize::try_from(position.0)
+    }
+}
+
+impl fmt::Display for NotePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+/// Number of leaves in a note commitment tree. Spends publish it as a 32 bit
+/// integer, so that's as large as a tree can get.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TreeSize(u32);
+
+impl TreeSize {
+    pub const EMPTY: TreeSize = TreeSize(0);
+
+    pub const MAX: TreeSize = TreeSize(u32::MAX);
+
+    pub const fn new(size: u32) -> Self {
+        TreeSize(size)
+    }
+
+    pub const fn as_u32(self) -> u32 {
+        self.0
+    }
+
+    pub const fn as_u64(self) -> u64 {
+        self.0 as u64
+    }
+
+    /// Whether the leaf at the given position is in a tree of this size
+    pub fn contains(self, position: NotePosition) -> bool {
+        position.as_u64() < self.as_u64()
+    }
+
+    /// The position of the next leaf added to a tree of this size
+    pub fn next_position(self) -> NotePosition {
+        NotePosition(self.as_u64())
+    }
+
+    /// The position of the last leaf of a tree of this size, if it's not
+    /// empty
+    pub fn last_position(self) -> Option<NotePosition> {
+        self.as_u64().checked_sub(1).map(NotePosition)
+    }
+
+    /// The size of the tree once `leaves` more leaves are added to it
+    pub fn checked_add(self, leaves: u32) -> Option<TreeSize> {
+        self.0.checked_add(leaves).map(TreeSize)
+    }
+
+    /// Number of nodes at the given level of a tree of this size, where the
+    /// leaves are level zero
+    pub fn level_size(self, level: usize) -> u64 {
+        (self.as_u64() + (1 << level) - 1) >> level
+    }
+}
+
+impl From<u32> for TreeSize {
+    fn from(size: u32) -> Self {
+        TreeSize(size)
+    }
+}
+
+impl From<TreeSize> for u32 {
+    fn from(size: TreeSize) -> Self {
+        size.0
+    }
+}
+
+impl From<TreeSize> for u64 {
+    fn from(size: TreeSize) -> Self {
+        size.as_u64()
+    }
+}
+
+impl TryFrom<u64> for TreeSize {
+    type Error = TryFromIntError;
+
+    fn try_from(size: u64) -> Result<Self, Self::Error> {
+        u32::try_from(size).map(TreeSize)
+    }
+}
+
+impl TryFrom<usize> for TreeSize {
+    type Error = TryFromIntError;
+
+    fn try_from(size: usize) -> Result<Self, Self::Error> {
+        u32::try_from(size).map(TreeSize)
+    }
+}
+
+impl TryFrom<TreeSize> for usize {
+    type Error = TryFromIntError;
+
+    fn try_from(size: TreeSize) -> Result<Self, Self::Error> {
+        usize::try_from(size.0)
+    }
+}
+
+impl fmt::Display for TreeSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{NotePosition, TreeSize};
+    use crate::witness::WitnessNode;
+
+    #[test]
+    fn test_tree_size_positions() {
+        let size = TreeSize::new(5);
+        assert!(size.contains(NotePosition::new(4)));
+        assert!(!size.contains(NotePosition::new(5)));
+        assert_eq!(size.next_position(), NotePosition::new(5));
+        assert_eq!(size.last_position(), Some(NotePosition::new(4)));
+        assert_eq!(TreeSize::EMPTY.last_position(), None);
+
+        assert_eq!(size.level_size(0), 5);
+        assert_eq!(size.level_size(1), 3);
+        assert_eq!(size.level_size(3), 1);
+
+        assert_eq!(TreeSize::MAX.checked_add(1), None);
+        assert!(TreeSize::try_from(u64::from(u32::MAX) + 1).is_err());
+
+        let auth_path = [
+            WitnessNode::Right(0),
+            WitnessNode::Left(0),
+            WitnessNode::Right(0),
+        ];
+        let position = NotePosition::from_auth_path(&auth_path);
+        assert_eq!(position, NotePosition::new(5));
+        assert_eq!(position.index_at(2), 1);
+    }
+}
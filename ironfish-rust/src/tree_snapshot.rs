@@ -0,0 +1,509 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Snapshots of the note commitment tree, for bootstrapping new nodes.
+//!
+//! A snapshot holds the leaves of the tree in fixed size chunks, each behind
+//! a BLAKE3 digest of its index and leaves, followed by the number of leaves
+//! and the root of the tree. [`TreeSnapshotReader`] checks every chunk as it's
+//! streamed in and folds the leaves into a [`TreeFrontier`], which only keeps
+//! one node per level, so the root is recomputed and checked at the end
+//! without holding the tree in memory. The caller should still compare the
+//! root with one it trusts, such as the note tree root of a block header.
+//!
+//! Since all chunks but the last are the same size, an import that was
+//! interrupted can be resumed from its [`SnapshotProgress`] by seeking
+//! straight to the next chunk.
+
+use std::io::{self, SeekFrom};
+
+use bls12_381::Scalar;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use ironfish_zkp::constants::TREE_DEPTH;
+
+use crate::{
+    errors::IronfishError,
+    serializing::read_scalar,
+    tree_position::{NotePosition, TreeSize},
+    MerkleNoteHash,
+};
+
+const TREE_SNAPSHOT_MAGIC: &[u8; 8] = b"IFNtSnap";
+const TREE_SNAPSHOT_VERSION: u8 = 1;
+
+/// Magic, version and chunk size
+const TREE_SNAPSHOT_HEADER_SIZE: u64 = TREE_SNAPSHOT_MAGIC.len() as u64 + 1 + 4;
+
+/// The right edge of a note tree: for each level, the node that is still
+/// waiting for a right sibling. That's all that's needed to append leaves and
+/// compute the root, so trees of any size fit in a few kilobytes.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TreeFrontier {
+    size: TreeSize,
+    pending: Vec<Option<Scalar>>,
+}
+
+impl TreeFrontier {
+    pub fn new() -> Self {
+        TreeFrontier {
+            size: TreeSize::EMPTY,
+            pending: vec![None; TREE_DEPTH],
+        }
+    }
+
+    /// Number of leaves in the tree
+    pub fn size(&self) -> TreeSize {
+        self.size
+    }
+
+    /// Append a leaf to the tree, returning its position. Fails once the tree
+    /// is as large as it can get.
+    pub fn append(&mut self, leaf: &MerkleNoteHash) -> Result<NotePosition, IronfishError> {
+        self.append_visiting(leaf, |_, _, _| {})
+    }
+
+    /// Append a leaf, calling `visit` with the level, index and hash of the
+    /// leaf and of every node the leaf completes, from the bottom up.
+    pub(crate) fn append_visiting(
+        &mut self,
+        leaf: &MerkleNoteHash,
+        mut visit: impl FnMut(usize, u64, &Scalar),
+    ) -> Result<NotePosition, IronfishError> {
+        let position = self.size.next_position();
+        self.size = self.size.checked_add(1).ok_or(IronfishError::InvalidData)?;
+
+        let mut node = leaf.0;
+        visit(0, position.as_u64(), &node);
+        for depth in 0..TREE_DEPTH {
+            match self.pending[depth].take() {
+                Some(left) => {
+                    node = MerkleNoteHash::combine_hash(depth, &left, &node);
+                    visit(depth + 1, position.index_at(depth + 1), &node);
+                }
+                None => {
+                    self.pending[depth] = Some(node);
+                    break;
+                }
+            }
+        }
+
+        Ok(position)
+    }
+
+    /// The root of the tree, with nodes that have no right sibling hashed
+    /// with themselves as in [`crate::witness::Witness::build_many`]. Empty
+    /// trees have no root.
+    pub fn root(&self) -> Option<Scalar> {
+        self.edge_node(TREE_DEPTH)
+    }
+
+    /// The hash of the node at the given level that holds the last leaf,
+    /// hashed the same way as the root. The node is incomplete unless the
+    /// size is a multiple of its width.
+    pub(crate) fn edge_node(&self, level: usize) -> Option<Scalar> {
+        let mut carry: Option<Scalar> = None;
+        for (depth, pending) in self.pending.iter().enumerate().take(level) {
+            carry = match (*pending, carry) {
+                (Some(left), Some(right)) => {
+                    Some(MerkleNoteHash::combine_hash(depth, &left, &right))
+                }
+                (Some(node), None) | (None, Some(node)) => {
+                    Some(MerkleNoteHash::combine_hash(depth, &node, &node))
+                }
+                (None, None) => None,
+            };
+        }
+
+        carry
+    }
+
+    /// Load a [`TreeFrontier`] from a Read implementation (e.g: socket, file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let size = TreeSize::try_from(reader.read_u64::<LittleEndian>()?)?;
+        let mut pending = Vec::with_capacity(TREE_DEPTH);
+        for depth in 0..TREE_DEPTH {
+            let node = match reader.read_u8()? {
+                0 => None,
+                1 => Some(read_scalar(&mut reader)?),
+                _ => return Err(IronfishError::InvalidData),
+            };
+
+            // A level has a pending node exactly when that bit of the size is set
+            if node.is_some() != ((size.as_u64() >> depth) & 1 == 1) {
+                return Err(IronfishError::InvalidData);
+            }
+            pending.push(node);
+        }
+
+        Ok(TreeFrontier { size, pending })
+    }
+
+    /// Stow the bytes of this [`TreeFrontier`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u64::<LittleEndian>(self.size.as_u64())?;
+        for node in self.pending.iter() {
+            match node {
+                None => writer.write_u8(0)?,
+                Some(node) => {
+                    writer.write_u8(1)?;
+                    writer.write_all(&node.to_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for TreeFrontier {
+    fn default() -> Self {
+        TreeFrontier::new()
+    }
+}
+
+fn chunk_digest(index: u64, leaves: &[MerkleNoteHash]) -> blake3::Hash {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&index.to_le_bytes());
+    for leaf in leaves {
+        hasher.update(&leaf.0.to_bytes());
+    }
+    hasher.finalize()
+}
+
+/// Size of a full chunk: leaf count, leaves and digest
+fn chunk_bytes(chunk_size: u32) -> u64 {
+    4 + chunk_size as u64 * 32 + blake3::OUT_LEN as u64
+}
+
+/// Streams the leaves of a tree into a snapshot.
+pub struct TreeSnapshotWriter<W: io::Write> {
+    writer: W,
+    chunk_size: u32,
+    chunk: Vec<MerkleNoteHash>,
+    chunk_index: u64,
+    frontier: TreeFrontier,
+}
+
+impl<W: io::Write> TreeSnapshotWriter<W> {
+    /// Start a snapshot with chunks of `chunk_size` leaves.
+    pub fn new(mut writer: W, chunk_size: u32) -> Result<Self, IronfishError> {
+        if chunk_size == 0 {
+            return Err(IronfishError::InvalidData);
+        }
+
+        writer.write_all(TREE_SNAPSHOT_MAGIC)?;
+        writer.write_u8(TREE_SNAPSHOT_VERSION)?;
+        writer.write_u32::<LittleEndian>(chunk_size)?;
+
+        Ok(TreeSnapshotWriter {
+            writer,
+            chunk_size,
+            chunk: Vec::with_capacity(chunk_size as usize),
+            chunk_index: 0,
+            frontier: TreeFrontier::new(),
+        })
+    }
+
+    /// Append the next leaf of the tree
+    pub fn append(&mut self, leaf: MerkleNoteHash) -> Result<(), IronfishError> {
+        self.frontier.append(&leaf)?;
+        self.chunk.push(leaf);
+
+        if self.chunk.len() == self.chunk_size as usize {
+            self.write_chunk()?;
+        }
+
+        Ok(())
+    }
+
+    fn write_chunk(&mut self) -> Result<(), IronfishError> {
+        self.writer
+            .write_u32::<LittleEndian>(self.chunk.len() as u32)?;
+        for leaf in self.chunk.iter() {
+            leaf.write(&mut self.writer)?;
+        }
+        self.writer
+            .write_all(chunk_digest(self.chunk_index, &self.chunk).as_bytes())?;
+
+        self.chunk.clear();
+        self.chunk_index += 1;
+
+        Ok(())
+    }
+
+    /// Write the last chunk and the root, returning the root. Fails for an
+    /// empty tree, which has no root.
+    pub fn finish(mut self) -> Result<Scalar, IronfishError> {
+        if !self.chunk.is_empty() {
+            self.write_chunk()?;
+        }
+        let root = self.frontier.root().ok_or(IronfishError::InvalidData)?;
+
+        // A chunk of no leaves marks the end
+        self.writer.write_u32::<LittleEndian>(0)?;
+        self.writer
+            .write_u64::<LittleEndian>(self.frontier.size().as_u64())?;
+        self.writer.write_all(&root.to_bytes())?;
+
+        Ok(root)
+    }
+}
+
+/// How far the import of a snapshot got, to resume it with
+/// [`TreeSnapshotReader::resume`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotProgress {
+    /// Index of the next chunk to read
+    pub next_chunk: u64,
+
+    /// The tree made of every leaf read so far
+    pub frontier: TreeFrontier,
+}
+
+impl SnapshotProgress {
+    /// Load a [`SnapshotProgress`] from a Read implementation (e.g: socket,
+    /// file)
+    pub fn read<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let next_chunk = reader.read_u64::<LittleEndian>()?;
+        let frontier = TreeFrontier::read(&mut reader)?;
+
+        Ok(SnapshotProgress {
+            next_chunk,
+            frontier,
+        })
+    }
+
+    /// Stow the bytes of this [`SnapshotProgress`] in the given writer.
+    pub fn write<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u64::<LittleEndian>(self.next_chunk)?;
+        self.frontier.write(&mut writer)
+    }
+}
+
+/// Reads the leaves of a snapshot chunk by chunk, checking each chunk and
+/// finally the root.
+pub struct TreeSnapshotReader<R: io::Read> {
+    reader: R,
+    chunk_size: u32,
+    progress: SnapshotProgress,
+    root: Option<Scalar>,
+}
+
+impl<R: io::Read> TreeSnapshotReader<R> {
+    /// Start reading a snapshot from the beginning.
+    pub fn new(mut reader: R) -> Result<Self, IronfishError> {
+        let chunk_size = read_header(&mut reader)?;
+
+        Ok(TreeSnapshotReader {
+            reader,
+            chunk_size,
+            progress: SnapshotProgress {
+                next_chunk: 0,
+                frontier: TreeFrontier::new(),
+            },
+            root: None,
+        })
+    }
+
+    /// Read the leaves of the next chunk, or `None` once every chunk was read
+    /// and the root of the snapshot was checked against its leaves. The leaves
+    /// are only returned once their digest was checked.
+    pub fn next_chunk(&mut self) -> Result<Option<Vec<MerkleNoteHash>>, IronfishError> {
+        if self.root.is_some() {
+            return Ok(None);
+        }
+
+        let count = self.reader.read_u32::<LittleEndian>()?;
+        if count == 0 {
+            return self.read_trailer().map(|_| None);
+        }
+        if count > self.chunk_size {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let mut leaves = Vec::with_capacity(count as usize);
+        for _ in 0..count {
+            leaves.push(MerkleNoteHash::read(&mut self.reader)?);
+        }
+        let mut digest = [0; blake3::OUT_LEN];
+        self.reader.read_exact(&mut digest)?;
+        if chunk_digest(self.progress.next_chunk, &leaves).as_bytes() != &digest {
+            return Err(IronfishError::InvalidTreeSnapshot);
+        }
+
+        for leaf in leaves.iter() {
+            self.progress.frontier.append(leaf)?;
+        }
+        self.progress.next_chunk += 1;
+
+        Ok(Some(leaves))
+    }
+
+    fn read_trailer(&mut self) -> Result<(), IronfishError> {
+        let size = self.reader.read_u64::<LittleEndian>()?;
+        let root = read_scalar(&mut self.reader)?;
+
+        if size != self.progress.frontier.size().as_u64()
+            || Some(root) != self.progress.frontier.root()
+        {
+            return Err(IronfishError::InvalidTreeSnapshot);
+        }
+        self.root = Some(root);
+
+        Ok(())
+    }
+
+    /// How far the import got, to persist along with the leaves read so far
+    pub fn progress(&self) -> &SnapshotProgress {
+        &self.progress
+    }
+
+    /// The checked root of the tree, once the whole snapshot was read
+    pub fn root(&self) -> Option<Scalar> {
+        self.root
+    }
+}
+
+impl<R: io::Read + io::Seek> TreeSnapshotReader<R> {
+    /// Resume reading a snapshot after the chunks that were already read by
+    /// an earlier import.
+    pub fn resume(mut reader: R, progress: SnapshotProgress) -> Result<Self, IronfishError> {
+        reader.seek(SeekFrom::Start(0))?;
+        let chunk_size = read_header(&mut reader)?;
+
+        // Only the last chunk may be partial, so the leaves read so far have
+        // to fill every chunk before the next one
+        if progress.frontier.size().as_u64() != progress.next_chunk * chunk_size as u64 {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let offset = TREE_SNAPSHOT_HEADER_SIZE + progress.next_chunk * chunk_bytes(chunk_size);
+        reader.seek(SeekFrom::Start(offset))?;
+
+        Ok(TreeSnapshotReader {
+            reader,
+            chunk_size,
+            progress,
+            root: None,
+        })
+    }
+}
+
+fn read_header<R: io::Read>(reader: &mut R) -> Result<u32, IronfishError> {
+    let mut magic = [0; TREE_SNAPSHOT_MAGIC.len()];
+    reader.read_exact(&mut magic)?;
+    if &magic != TREE_SNAPSHOT_MAGIC || reader.read_u8()? != TREE_SNAPSHOT_VERSION {
+        return Err(IronfishError::InvalidData);
+    }
+
+    let chunk_size = reader.read_u32::<LittleEndian>()?;
+    if chunk_size == 0 {
+        return Err(IronfishError::InvalidData);
+    }
+
+    Ok(chunk_size)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{TreeFrontier, TreeSnapshotReader, TreeSnapshotWriter};
+    use crate::{
+        errors::IronfishError, tree_position::NotePosition, witness::Witness, MerkleNoteHash,
+    };
+    use bls12_381::Scalar;
+    use rand::{thread_rng, Rng};
+    use std::io::Cursor;
+
+    fn random_leaves(count: usize) -> Vec<MerkleNoteHash> {
+        (0..count)
+            .map(|_| MerkleNoteHash(Scalar::from(thread_rng().gen::<u64>())))
+            .collect()
+    }
+
+    #[test]
+    fn test_frontier_root() {
+        let leaves = random_leaves(7);
+
+        let mut frontier = TreeFrontier::new();
+        assert_eq!(frontier.root(), None);
+        for (count, leaf) in leaves.iter().enumerate() {
+            let position = frontier.append(leaf).unwrap();
+            assert_eq!(position, NotePosition::try_from(count).unwrap());
+
+            let witness = &Witness::build_many(&leaves[..=count], &[position]).unwrap()[0];
+            assert_eq!(frontier.root(), Some(witness.root_hash));
+        }
+
+        let mut serialized = vec![];
+        frontier.write(&mut serialized).unwrap();
+        assert_eq!(TreeFrontier::read(&serialized[..]).unwrap(), frontier);
+    }
+
+    #[test]
+    fn test_tree_snapshot_round_trip() {
+        let leaves = random_leaves(11);
+
+        let mut snapshot = vec![];
+        let mut writer = TreeSnapshotWriter::new(&mut snapshot, 4).unwrap();
+        for leaf in leaves.iter() {
+            writer.append(leaf.clone()).unwrap();
+        }
+        let root = writer.finish().unwrap();
+        assert_eq!(
+            root,
+            Witness::build_many(&leaves, &[NotePosition::new(0)]).unwrap()[0].root_hash
+        );
+
+        let mut reader = TreeSnapshotReader::new(&snapshot[..]).unwrap();
+        let mut read = vec![];
+        while let Some(chunk) = reader.next_chunk().unwrap() {
+            read.extend(chunk);
+        }
+        assert_eq!(read, leaves);
+        assert_eq!(reader.root(), Some(root));
+
+        // Interrupted after the first two chunks, and resumed from there
+        let mut reader = TreeSnapshotReader::new(&snapshot[..]).unwrap();
+        reader.next_chunk().unwrap();
+        reader.next_chunk().unwrap();
+        let progress = reader.progress().clone();
+
+        let mut resumed = TreeSnapshotReader::resume(Cursor::new(&snapshot), progress).unwrap();
+        assert_eq!(resumed.next_chunk().unwrap().unwrap(), leaves[8..]);
+        assert!(resumed.next_chunk().unwrap().is_none());
+        assert_eq!(resumed.root(), Some(root));
+
+        // A flipped bit in a leaf fails the digest of its chunk
+        let mut corrupt = snapshot.clone();
+        corrupt[20] ^= 1;
+        let mut reader = TreeSnapshotReader::new(&corrupt[..]).unwrap();
+        assert!(reader.next_chunk().is_err());
+    }
+
+    #[test]
+    fn test_tree_snapshot_root_mismatch() {
+        let leaves = random_leaves(3);
+
+        let mut snapshot = vec![];
+        let mut writer = TreeSnapshotWriter::new(&mut snapshot, 2).unwrap();
+        for leaf in leaves.iter() {
+            writer.append(leaf.clone()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        // Replace the root with the root of another tree
+        let other_root =
+            Witness::build_many(&leaves[..2], &[NotePosition::new(0)]).unwrap()[0].root_hash;
+        let root_offset = snapshot.len() - 32;
+        snapshot[root_offset..].copy_from_slice(&other_root.to_bytes());
+
+        let mut reader = TreeSnapshotReader::new(&snapshot[..]).unwrap();
+        reader.next_chunk().unwrap();
+        reader.next_chunk().unwrap();
+        assert!(matches!(
+            reader.next_chunk(),
+            Err(IronfishError::InvalidTreeSnapshot)
+        ));
+    }
+}
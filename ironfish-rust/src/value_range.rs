@@ -0,0 +1,107 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The values notes, mints, burns and fees are allowed to have.
+//!
+//! Values are 64 bit unsigned integers in the circuits, but transactions
+//! balance them against each other as signed 64 bit integers, so nothing can
+//! be worth more than [`MAX_VALUE`]. Notes and fees may be zero, while mints
+//! and burns of nothing are rejected when building, since they only make the
+//! transaction larger.
+
+use std::fmt;
+
+/// Largest value of a note, mint, burn or fee
+pub const MAX_VALUE: u64 = i64::MAX as u64;
+
+/// What a value is the value of
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueContext {
+    Note,
+    Mint,
+    Burn,
+    Fee,
+}
+
+impl ValueContext {
+    /// Whether a value of zero can be built in this context
+    pub fn allows_zero(self) -> bool {
+        match self {
+            ValueContext::Note | ValueContext::Fee => true,
+            ValueContext::Mint | ValueContext::Burn => false,
+        }
+    }
+}
+
+/// Why a value is out of range
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueError {
+    /// The value is larger than [`MAX_VALUE`]
+    TooLarge { context: ValueContext, value: u64 },
+
+    /// The value is zero, which the context doesn't allow
+    Zero { context: ValueContext },
+}
+
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueError::TooLarge { context, value } => write!(
+                f,
+                "{:?} value {} is larger than the maximum of {}",
+                context, value, MAX_VALUE
+            ),
+            ValueError::Zero { context } => write!(f, "{:?} value can't be zero", context),
+        }
+    }
+}
+
+/// Check a value that is being added to a transaction, returning it as the
+/// signed value it's balanced with.
+pub fn check_value(value: u64, context: ValueContext) -> Result<i64, ValueError> {
+    if value == 0 && !context.allows_zero() {
+        return Err(ValueError::Zero { context });
+    }
+
+    check_posted_value(value, context)
+}
+
+/// Check a value of a posted transaction. Only the range is checked, so that
+/// transactions with zero mints or burns that are already on chain stay
+/// valid.
+pub fn check_posted_value(value: u64, context: ValueContext) -> Result<i64, ValueError> {
+    i64::try_from(value).map_err(|_| ValueError::TooLarge { context, value })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_posted_value, check_value, ValueContext, ValueError, MAX_VALUE};
+
+    #[test]
+    fn test_check_value() {
+        assert_eq!(check_value(MAX_VALUE, ValueContext::Note), Ok(i64::MAX));
+        assert_eq!(
+            check_value(MAX_VALUE + 1, ValueContext::Fee),
+            Err(ValueError::TooLarge {
+                context: ValueContext::Fee,
+                value: MAX_VALUE + 1
+            })
+        );
+
+        assert_eq!(check_value(0, ValueContext::Note), Ok(0));
+        assert_eq!(check_value(0, ValueContext::Fee), Ok(0));
+        assert_eq!(
+            check_value(0, ValueContext::Mint),
+            Err(ValueError::Zero {
+                context: ValueContext::Mint
+            })
+        );
+        assert!(check_value(0, ValueContext::Burn).is_err());
+
+        // Posted transactions may have zero mints and burns, but not values
+        // that are out of range
+        assert_eq!(check_posted_value(0, ValueContext::Burn), Ok(0));
+        assert!(check_posted_value(u64::MAX, ValueContext::Mint).is_err());
+    }
+}
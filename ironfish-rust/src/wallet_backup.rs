@@ -0,0 +1,310 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! A single-file, password-encrypted backup of a wallet: its accounts, how
+//! far each was scanned, and the notes each can spend.
+//!
+//! The file starts with a header in the clear:
+//! - the magic bytes [`WALLET_BACKUP_MAGIC`] and the format version
+//! - the Argon2id parameters and salt the key is derived from the password
+//!   with
+//!
+//! The rest is the contents, sealed with [`aead::seal`] under that key.
+//! Every backup is encrypted with a key derived from a fresh salt, so the
+//! key is only ever used once. Changing the parameters or the salt changes
+//! the key, and changing anything else fails the MAC tag, so a backup that
+//! imports is exactly the one that was exported.
+
+use std::io;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rand::RngCore;
+
+use crate::{
+    errors::IronfishError,
+    keys::AccountExport,
+    randomness::with_rng,
+    scan_cursor::ScanCursor,
+    serializing::aead::{self, AeadVersion},
+    spendable_note::SpendableNoteRecord,
+};
+
+pub const WALLET_BACKUP_MAGIC: &[u8; 4] = b"IFWB";
+pub const WALLET_BACKUP_VERSION: u8 = 1;
+
+const SALT_SIZE: usize = 16;
+
+/// Largest Argon2id memory cost accepted from a backup, in KiB, so that a
+/// crafted header can't make importing it exhaust the memory
+const MAX_MEMORY_KIB: u32 = 1 << 20;
+
+/// Largest Argon2id iteration count accepted from a backup
+const MAX_ITERATIONS: u32 = 64;
+
+/// Largest Argon2id parallelism accepted from a backup
+const MAX_PARALLELISM: u32 = 16;
+
+/// Cost of deriving the key of a backup from its password
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// The minimum recommended for Argon2id: 19 MiB of memory and 2
+    /// iterations on a single lane
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: 19 * 1024,
+            iterations: 2,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(
+        &self,
+        password: &[u8],
+        salt: &[u8; SALT_SIZE],
+    ) -> Result<[u8; 32], IronfishError> {
+        if self.memory_kib > MAX_MEMORY_KIB
+            || self.iterations > MAX_ITERATIONS
+            || self.parallelism > MAX_PARALLELISM
+        {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|_| IronfishError::InvalidData)?;
+
+        let mut key = [0; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(password, salt, &mut key)
+            .map_err(|_| IronfishError::InvalidData)?;
+
+        Ok(key)
+    }
+}
+
+/// An account with its scan progress and spendable notes
+pub struct AccountBackup {
+    pub account: AccountExport,
+
+    /// Where the last scan of the account stopped, if it was scanned
+    pub cursor: Option<ScanCursor>,
+
+    pub notes: Vec<SpendableNoteRecord>,
+}
+
+#[derive(Default)]
+pub struct WalletBackup {
+    pub accounts: Vec<AccountBackup>,
+}
+
+impl WalletBackup {
+    pub fn new(accounts: Vec<AccountBackup>) -> Self {
+        WalletBackup { accounts }
+    }
+
+    /// Encrypt the backup with a key derived from the password, and write it
+    /// to the given writer
+    pub fn export<W: io::Write>(
+        &self,
+        password: &[u8],
+        kdf_params: &KdfParams,
+        mut writer: W,
+    ) -> Result<(), IronfishError> {
+        let mut salt = [0; SALT_SIZE];
+        with_rng(|rng| rng.fill_bytes(&mut salt))?;
+        let key = kdf_params.derive_key(password, &salt)?;
+
+        let mut contents = vec![];
+        self.write_contents(&mut contents)?;
+        let sealed = aead::seal(AeadVersion::ChaCha20Poly1305, &key, &contents)?;
+
+        writer.write_all(WALLET_BACKUP_MAGIC)?;
+        writer.write_u8(WALLET_BACKUP_VERSION)?;
+        writer.write_u32::<LittleEndian>(kdf_params.memory_kib)?;
+        writer.write_u32::<LittleEndian>(kdf_params.iterations)?;
+        writer.write_u32::<LittleEndian>(kdf_params.parallelism)?;
+        writer.write_all(&salt)?;
+        writer.write_all(&sealed)?;
+
+        Ok(())
+    }
+
+    /// Load a backup written by [`WalletBackup::export`] from a Read
+    /// implementation (e.g: socket, file), decrypting it with the password.
+    /// A wrong password fails with [`IronfishError::InvalidDecryptionKey`].
+    pub fn import<R: io::Read>(mut reader: R, password: &[u8]) -> Result<Self, IronfishError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != WALLET_BACKUP_MAGIC || reader.read_u8()? != WALLET_BACKUP_VERSION {
+            return Err(IronfishError::InvalidData);
+        }
+
+        let kdf_params = KdfParams {
+            memory_kib: reader.read_u32::<LittleEndian>()?,
+            iterations: reader.read_u32::<LittleEndian>()?,
+            parallelism: reader.read_u32::<LittleEndian>()?,
+        };
+        let mut salt = [0; SALT_SIZE];
+        reader.read_exact(&mut salt)?;
+        let key = kdf_params.derive_key(password, &salt)?;
+
+        let mut sealed = vec![];
+        reader.read_to_end(&mut sealed)?;
+        let contents = aead::open(&key, &sealed)?;
+
+        let mut contents = &contents[..];
+        let backup = WalletBackup::read_contents(&mut contents)?;
+        if !contents.is_empty() {
+            return Err(IronfishError::InvalidData);
+        }
+
+        Ok(backup)
+    }
+
+    fn write_contents<W: io::Write>(&self, mut writer: W) -> Result<(), IronfishError> {
+        writer.write_u32::<LittleEndian>(u32::try_from(self.accounts.len())?)?;
+        for backup in self.accounts.iter() {
+            backup.account.write(&mut writer)?;
+
+            match &backup.cursor {
+                None => writer.write_u8(0)?,
+                Some(cursor) => {
+                    writer.write_u8(1)?;
+                    cursor.write(&mut writer)?;
+                }
+            }
+
+            writer.write_u32::<LittleEndian>(u32::try_from(backup.notes.len())?)?;
+            for note in backup.notes.iter() {
+                note.write(&mut writer)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_contents<R: io::Read>(mut reader: R) -> Result<Self, IronfishError> {
+        let account_count = reader.read_u32::<LittleEndian>()?;
+        let mut accounts = vec![];
+        for _ in 0..account_count {
+            let account = AccountExport::read(&mut reader)?;
+
+            let cursor = match reader.read_u8()? {
+                0 => None,
+                1 => Some(ScanCursor::read(&mut reader)?),
+                _ => return Err(IronfishError::InvalidData),
+            };
+
+            let note_count = reader.read_u32::<LittleEndian>()?;
+            let mut notes = vec![];
+            for _ in 0..note_count {
+                notes.push(SpendableNoteRecord::read(&mut reader)?);
+            }
+
+            accounts.push(AccountBackup {
+                account,
+                cursor,
+                notes,
+            });
+        }
+
+        Ok(WalletBackup { accounts })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AccountBackup, KdfParams, WalletBackup};
+    use crate::{
+        assets::asset_identifier::NATIVE_ASSET,
+        errors::IronfishError,
+        keys::{AccountExport, SaplingKey},
+        network::NetworkKind,
+        note::Note,
+        scan_cursor::ScanCursor,
+        spendable_note::SpendableNoteRecord,
+        test_util::make_fake_witness,
+        tree_position::{NotePosition, TreeSize},
+    };
+
+    /// Cheap enough for tests, far too cheap for real backups
+    const TEST_KDF_PARAMS: KdfParams = KdfParams {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn test_wallet_backup_round_trip() {
+        let key = SaplingKey::generate_key_for_network(NetworkKind::Testnet);
+        let note = Note::new(
+            key.public_address(),
+            42,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+        let position = NotePosition::from_auth_path(&witness.auth_path);
+        let record = SpendableNoteRecord::new(note, position, witness, 3).unwrap();
+
+        let watched = SaplingKey::generate_key();
+        let backup = WalletBackup::new(vec![
+            AccountBackup {
+                account: AccountExport::new(key.clone(), Some(120)),
+                cursor: Some(ScanCursor::new(130, TreeSize::new(500))),
+                notes: vec![record],
+            },
+            AccountBackup {
+                account: AccountExport::view_only(&watched, None),
+                cursor: None,
+                notes: vec![],
+            },
+        ]);
+
+        let mut exported = vec![];
+        backup
+            .export(b"correct horse", &TEST_KDF_PARAMS, &mut exported)
+            .unwrap();
+
+        let imported = WalletBackup::import(&exported[..], b"correct horse").unwrap();
+        assert_eq!(imported.accounts.len(), 2);
+
+        let spending = &imported.accounts[0];
+        assert_eq!(spending.account.network, NetworkKind::Testnet);
+        assert_eq!(spending.account.public_address(), key.public_address());
+        assert_eq!(spending.account.birthday(), Some(120));
+        assert_eq!(
+            spending.cursor,
+            Some(ScanCursor::new(130, TreeSize::new(500)))
+        );
+        assert_eq!(spending.notes.len(), 1);
+        assert_eq!(spending.notes[0].position, position);
+
+        let view_only = &imported.accounts[1];
+        assert!(view_only.account.is_view_only());
+        assert_eq!(view_only.account.public_address(), watched.public_address());
+        assert!(view_only.cursor.is_none());
+
+        assert!(matches!(
+            WalletBackup::import(&exported[..], b"wrong horse"),
+            Err(IronfishError::InvalidDecryptionKey)
+        ));
+
+        // Any change to the header or the contents is detected
+        for offset in [17, exported.len() - 1] {
+            let mut corrupted = exported.clone();
+            corrupted[offset] ^= 1;
+            assert!(WalletBackup::import(&corrupted[..], b"correct horse").is_err());
+        }
+    }
+}
@@ -0,0 +1,349 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! The byte layout of every struct this crate serializes, for parsers in
+//! other languages.
+//!
+//! Each layout lists the fields of a struct in the order they're written,
+//! with their size in bytes. Nothing is aligned or padded, and numbers are
+//! little endian: `u32` and `u64` counts and values, and the `i64` fee.
+//! Points and scalars are in their compressed 32 byte encoding.
+//!
+//! The layouts are checked against the size constants at compile time, and
+//! against fixed bytes in the tests, so that a change to the layout can't go
+//! unnoticed.
+
+use std::ops::Range;
+
+use crate::{
+    assets::asset::{ASSET_LENGTH, ID_LENGTH, METADATA_LENGTH, NAME_LENGTH},
+    keys::PUBLIC_ADDRESS_SIZE,
+    merkle_note::{
+        ENCRYPTED_NOTE_CIPHERTEXT_SIZE, EPHEMERAL_PUBLIC_KEY_SIZE, MERKLE_NOTE_SIZE,
+        NOTE_COMMITMENT_SIZE, NOTE_ENCRYPTION_KEY_SIZE, VALUE_COMMITMENT_SIZE,
+    },
+    note::{AMOUNT_VALUE_SIZE, MEMO_SIZE, SCALAR_SIZE},
+    transaction::{
+        burns::BURN_DESCRIPTION_SIZE, mints::MINT_DESCRIPTION_SIZE,
+        outputs::OUTPUT_DESCRIPTION_SIZE, outputs::PROOF_SIZE, spends::SPEND_DESCRIPTION_SIZE,
+        TRANSACTION_EXPIRATION_SIZE, TRANSACTION_FEE_SIZE, TRANSACTION_HEADER_SIZE,
+        TRANSACTION_PUBLIC_KEY_SIZE, TRANSACTION_SIGNATURE_SIZE,
+    },
+};
+
+/// A field of a serialized struct
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Field {
+    pub name: &'static str,
+    pub size: usize,
+}
+
+const fn field(name: &'static str, size: usize) -> Field {
+    Field { name, size }
+}
+
+/// Owner, name, metadata and the nonce the identifier was derived with
+pub const ASSET_LAYOUT: &[Field] = &[
+    field("owner", PUBLIC_ADDRESS_SIZE),
+    field("name", NAME_LENGTH),
+    field("metadata", METADATA_LENGTH),
+    field("nonce", 1),
+];
+
+/// A note in plaintext, as written by [`crate::note::Note::write`]
+pub const NOTE_LAYOUT: &[Field] = &[
+    field("owner", PUBLIC_ADDRESS_SIZE),
+    field("asset_id", ID_LENGTH),
+    field("value", AMOUNT_VALUE_SIZE),
+    field("randomness", SCALAR_SIZE),
+    field("memo", MEMO_SIZE),
+    field("sender", PUBLIC_ADDRESS_SIZE),
+];
+
+/// Spends of transactions before
+/// [`crate::transaction::TREE_ID_TRANSACTION_VERSION`]
+pub const SPEND_DESCRIPTION_LAYOUT: &[Field] = &[
+    field("proof", PROOF_SIZE as usize),
+    field("value_commitment", 32),
+    field("root_hash", 32),
+    field("tree_size", 4),
+    field("nullifier", 32),
+    field("authorizing_signature", TRANSACTION_SIGNATURE_SIZE),
+];
+
+/// Spends of transactions from
+/// [`crate::transaction::TREE_ID_TRANSACTION_VERSION`] on, which name the
+/// tree of their anchor
+pub const SPEND_DESCRIPTION_TREE_ID_LAYOUT: &[Field] = &[
+    field("proof", PROOF_SIZE as usize),
+    field("value_commitment", 32),
+    field("root_hash", 32),
+    field("tree_size", 4),
+    field("tree_id", 1),
+    field("nullifier", 32),
+    field("authorizing_signature", TRANSACTION_SIGNATURE_SIZE),
+];
+
+/// The proof followed by the merkle note
+pub const OUTPUT_DESCRIPTION_LAYOUT: &[Field] = &[
+    field("proof", PROOF_SIZE as usize),
+    field("value_commitment", VALUE_COMMITMENT_SIZE),
+    field("note_commitment", NOTE_COMMITMENT_SIZE),
+    field("ephemeral_public_key", EPHEMERAL_PUBLIC_KEY_SIZE),
+    field("encrypted_note", ENCRYPTED_NOTE_CIPHERTEXT_SIZE),
+    field("note_encryption_keys", NOTE_ENCRYPTION_KEY_SIZE),
+];
+
+pub const MINT_DESCRIPTION_LAYOUT: &[Field] = &[
+    field("proof", PROOF_SIZE as usize),
+    field("asset", ASSET_LENGTH),
+    field("value", 8),
+    field("authorizing_signature", TRANSACTION_SIGNATURE_SIZE),
+];
+
+pub const BURN_DESCRIPTION_LAYOUT: &[Field] = &[field("asset_id", ID_LENGTH), field("value", 8)];
+
+/// The start of a transaction. It's followed by the spends, outputs, mints
+/// and burns, as many of each as the header counts, and the binding
+/// signature of [`TRANSACTION_SIGNATURE_SIZE`] bytes.
+pub const TRANSACTION_HEADER_LAYOUT: &[Field] = &[
+    field("version", 1),
+    field("num_spends", 8),
+    field("num_outputs", 8),
+    field("num_mints", 8),
+    field("num_burns", 8),
+    field("fee", TRANSACTION_FEE_SIZE),
+    field("expiration", TRANSACTION_EXPIRATION_SIZE),
+    field("randomized_public_key", TRANSACTION_PUBLIC_KEY_SIZE),
+];
+
+/// Total size of the fields of a layout
+pub const fn layout_size(layout: &[Field]) -> usize {
+    let mut size = 0;
+    let mut i = 0;
+    while i < layout.len() {
+        size += layout[i].size;
+        i += 1;
+    }
+    size
+}
+
+/// Where the field with the given name is in the layout, if it has one
+pub fn field_range(layout: &[Field], name: &str) -> Option<Range<usize>> {
+    let mut start = 0;
+    for field in layout {
+        if field.name == name {
+            return Some(start..start + field.size);
+        }
+        start += field.size;
+    }
+
+    None
+}
+
+const _: () = assert!(layout_size(ASSET_LAYOUT) == ASSET_LENGTH);
+const _: () = assert!(layout_size(SPEND_DESCRIPTION_LAYOUT) == SPEND_DESCRIPTION_SIZE);
+const _: () = assert!(layout_size(SPEND_DESCRIPTION_TREE_ID_LAYOUT) == SPEND_DESCRIPTION_SIZE + 1);
+const _: () = assert!(
+    layout_size(OUTPUT_DESCRIPTION_LAYOUT) == PROOF_SIZE as usize + MERKLE_NOTE_SIZE
+        && layout_size(OUTPUT_DESCRIPTION_LAYOUT) == OUTPUT_DESCRIPTION_SIZE
+);
+const _: () = assert!(layout_size(MINT_DESCRIPTION_LAYOUT) == MINT_DESCRIPTION_SIZE);
+const _: () = assert!(layout_size(BURN_DESCRIPTION_LAYOUT) == BURN_DESCRIPTION_SIZE);
+const _: () = assert!(layout_size(TRANSACTION_HEADER_LAYOUT) == TRANSACTION_HEADER_SIZE);
+
+#[cfg(test)]
+mod test {
+    use super::{
+        field_range, layout_size, Field, ASSET_LAYOUT, BURN_DESCRIPTION_LAYOUT,
+        MINT_DESCRIPTION_LAYOUT, NOTE_LAYOUT, OUTPUT_DESCRIPTION_LAYOUT, SPEND_DESCRIPTION_LAYOUT,
+        TRANSACTION_HEADER_LAYOUT,
+    };
+    use crate::{
+        assets::{asset::Asset, asset_identifier::NATIVE_ASSET},
+        keys::SaplingKey,
+        note::Note,
+        serializing::{bytes_to_hex, hex_to_vec_bytes},
+        test_util::make_fake_witness,
+        transaction::{burns::BurnDescription, ProposedTransaction, TRANSACTION_SIGNATURE_SIZE},
+        PublicAddress,
+    };
+    use ff::PrimeField;
+    use group::GroupEncoding;
+
+    const ADDRESS: &str = "8a4685307f159e95418a0dd3d38a3245f488c1baf64bc914f53486efd370c563";
+
+    fn field<'a>(bytes: &'a [u8], layout: &[Field], name: &str) -> &'a [u8] {
+        &bytes[field_range(layout, name).unwrap()]
+    }
+
+    #[test]
+    fn test_asset_bytes() {
+        let owner = PublicAddress::from_hex(ADDRESS).unwrap();
+        let asset = Asset::new(owner, "wire", "layout").unwrap();
+        let bytes = asset.to_bytes();
+
+        // The nonce is found while deriving the identifier, so it's checked
+        // apart from the fixed bytes
+        let nonce = field_range(ASSET_LAYOUT, "nonce").unwrap();
+        assert_eq!(
+            bytes_to_hex(&bytes[..nonce.start]),
+            "8a4685307f159e95418a0dd3d38a3245f488c1baf64bc914f53486efd370c563\
+             7769726500000000000000000000000000000000000000000000000000000000\
+             6c61796f75740000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000\
+             0000000000000000000000000000000000000000000000000000000000000000"
+        );
+        assert_eq!(bytes[nonce], [asset.nonce()]);
+    }
+
+    #[test]
+    fn test_note_bytes() {
+        let golden = "8a4685307f159e95418a0dd3d38a3245f488c1baf64bc914f53486efd370c563\
+                      51f33a2f14f92735e562dc658a5639279ddca3d5079a6d1242b2a588a9cbf44c\
+                      0807060504030201\
+                      0500000000000000000000000000000000000000000000000000000000000000\
+                      7769726500000000000000000000000000000000000000000000000000000000\
+                      8a4685307f159e95418a0dd3d38a3245f488c1baf64bc914f53486efd370c563";
+        let bytes = hex_to_vec_bytes(golden).unwrap();
+        assert_eq!(bytes.len(), layout_size(NOTE_LAYOUT));
+
+        let note = Note::read(&bytes[..]).unwrap();
+        assert_eq!(note.value(), 0x0102030405060708);
+        assert_eq!(note.asset_id(), &NATIVE_ASSET);
+        assert_eq!(note.memo().0[..4], *b"wire");
+
+        let mut written = vec![];
+        note.write(&mut written).unwrap();
+        assert_eq!(bytes_to_hex(&written), golden);
+    }
+
+    #[test]
+    fn test_burn_bytes() {
+        let burn = BurnDescription {
+            asset_id: NATIVE_ASSET,
+            value: 0x0102030405060708,
+        };
+
+        assert_eq!(
+            bytes_to_hex(&burn.to_bytes()),
+            "51f33a2f14f92735e562dc658a5639279ddca3d5079a6d1242b2a588a9cbf44c\
+             0807060504030201"
+        );
+        assert_eq!(burn.to_bytes().len(), layout_size(BURN_DESCRIPTION_LAYOUT));
+    }
+
+    #[test]
+    fn test_transaction_bytes() {
+        let key = SaplingKey::generate_key();
+        let recipient = SaplingKey::generate_key().public_address();
+        let asset = Asset::new(key.public_address(), "wire", "layout").unwrap();
+
+        let note = Note::new(
+            key.public_address(),
+            10,
+            "",
+            NATIVE_ASSET,
+            key.public_address(),
+        );
+        let witness = make_fake_witness(&note);
+
+        let mut transaction = ProposedTransaction::new(key);
+        transaction.add_spend(note, &witness).unwrap();
+        transaction
+            .add_output(Note::new(recipient, 5, "", NATIVE_ASSET, recipient))
+            .unwrap();
+        transaction.add_mint(asset, 10).unwrap();
+        transaction.add_burn(*asset.id(), 3).unwrap();
+        transaction.set_expiration(0x01020304);
+
+        // Change of the native and the minted asset make three outputs
+        let posted = transaction.post(None, 1).unwrap();
+        let mut bytes = vec![];
+        posted.write(&mut bytes).unwrap();
+
+        let header_size = layout_size(TRANSACTION_HEADER_LAYOUT);
+        let key_start = field_range(TRANSACTION_HEADER_LAYOUT, "randomized_public_key")
+            .unwrap()
+            .start;
+        assert_eq!(
+            bytes_to_hex(&bytes[..key_start]),
+            "01\
+             0100000000000000\
+             0300000000000000\
+             0100000000000000\
+             0100000000000000\
+             0100000000000000\
+             04030201"
+        );
+        assert_eq!(
+            bytes[key_start..header_size],
+            posted.randomized_public_key().0.to_bytes()
+        );
+
+        let spend_size = layout_size(SPEND_DESCRIPTION_LAYOUT);
+        let output_size = layout_size(OUTPUT_DESCRIPTION_LAYOUT);
+        let mint_size = layout_size(MINT_DESCRIPTION_LAYOUT);
+        let burn_size = layout_size(BURN_DESCRIPTION_LAYOUT);
+        assert_eq!(
+            bytes.len(),
+            header_size
+                + spend_size
+                + 3 * output_size
+                + mint_size
+                + burn_size
+                + TRANSACTION_SIGNATURE_SIZE
+        );
+
+        let (spend, rest) = bytes[header_size..].split_at(spend_size);
+        let description = &posted.spends()[0];
+        assert_eq!(
+            field(spend, SPEND_DESCRIPTION_LAYOUT, "root_hash"),
+            description.root_hash().to_repr().as_ref()
+        );
+        assert_eq!(
+            field(spend, SPEND_DESCRIPTION_LAYOUT, "tree_size"),
+            description.tree_size().to_le_bytes()
+        );
+        assert_eq!(
+            field(spend, SPEND_DESCRIPTION_LAYOUT, "nullifier"),
+            description.nullifier().0
+        );
+
+        let (outputs, rest) = rest.split_at(3 * output_size);
+        for (output, description) in outputs.chunks(output_size).zip(posted.outputs()) {
+            let note_start = field_range(OUTPUT_DESCRIPTION_LAYOUT, "value_commitment")
+                .unwrap()
+                .start;
+            assert_eq!(output[note_start..], description.merkle_note().to_bytes());
+        }
+
+        let (mint, rest) = rest.split_at(mint_size);
+        assert_eq!(
+            field(mint, MINT_DESCRIPTION_LAYOUT, "asset"),
+            asset.to_bytes()
+        );
+        assert_eq!(
+            field(mint, MINT_DESCRIPTION_LAYOUT, "value"),
+            10u64.to_le_bytes()
+        );
+
+        let (burn, signature) = rest.split_at(burn_size);
+        assert_eq!(
+            field(burn, BURN_DESCRIPTION_LAYOUT, "asset_id"),
+            asset.id().as_bytes()
+        );
+        assert_eq!(
+            field(burn, BURN_DESCRIPTION_LAYOUT, "value"),
+            3u64.to_le_bytes()
+        );
+
+        let mut binding_signature = vec![];
+        posted
+            .binding_signature()
+            .write(&mut binding_signature)
+            .unwrap();
+        assert_eq!(signature, binding_signature);
+    }
+}
@@ -3,8 +3,15 @@
  * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
 
 use bls12_381::Scalar;
+use ironfish_zkp::constants::TREE_DEPTH;
+use rayon::prelude::*;
 
-use super::MerkleNoteHash;
+use super::{
+    errors::IronfishError,
+    tree_position::{NotePosition, TreeSize},
+    tree_snapshot::TreeFrontier,
+    MerkleNoteHash,
+};
 use std::fmt::{self, Debug};
 
 /// Witness to a specific node in an authentication path.
@@ -17,6 +24,24 @@ pub enum WitnessNode<H: Clone + PartialEq + Debug> {
     Right(H),
 }
 
+/// Identifier of a note commitment tree. Every note is in [`TreeId::NOTES`]
+/// for now; the identifier lets spends anchor to a parallel or replacement
+/// tree, such as one introduced with a new shielded pool, without changing
+/// the spend format again.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct TreeId(pub u8);
+
+impl TreeId {
+    /// The note commitment tree every output has been added to so far
+    pub const NOTES: TreeId = TreeId(0);
+
+    /// Whether verifiers know of this tree. Spends anchored to any other tree
+    /// are rejected.
+    pub fn is_known(&self) -> bool {
+        *self == TreeId::NOTES
+    }
+}
+
 /// Commitment that a leaf node exists in the tree, with an authentication path
 /// and the root_hash of the tree at the time the authentication_path was
 /// calculated.
@@ -31,12 +56,17 @@ pub trait WitnessTrait {
     fn root_hash(&self) -> Scalar;
 
     fn tree_size(&self) -> u32;
+
+    /// The tree the root hash belongs to
+    fn tree_id(&self) -> TreeId {
+        TreeId::NOTES
+    }
 }
 
 /// A Rust implementation of a WitnessTrait, used for testing Witness-related
 /// code within Rust.
 pub struct Witness {
-    pub tree_size: usize,
+    pub tree_size: TreeSize,
     pub root_hash: Scalar,
     pub auth_path: Vec<WitnessNode<Scalar>>,
 }
@@ -76,8 +106,241 @@ impl WitnessTrait for Witness {
     }
 
     fn tree_size(&self) -> u32 {
-        self.tree_size as u32
+        self.tree_size.as_u32()
+    }
+}
+
+impl Witness {
+    /// Build witnesses for the leaves at `positions` in a tree made of
+    /// `leaves`, in the same order as `positions`.
+    ///
+    /// Each level of the tree is hashed in parallel and then every
+    /// authentication path is extracted in parallel, so building witnesses for
+    /// many spends costs about the same as building one. As in the rest of
+    /// Iron Fish, a node without a right sibling is hashed with itself.
+    pub fn build_many(
+        leaves: &[MerkleNoteHash],
+        positions: &[NotePosition],
+    ) -> Result<Vec<Witness>, IronfishError> {
+        let tree_size = TreeSize::try_from(leaves.len())?;
+        if leaves.is_empty()
+            || positions
+                .iter()
+                .any(|position| !tree_size.contains(*position))
+        {
+            return Err(IronfishError::InconsistentWitness);
+        }
+        let indices = positions
+            .iter()
+            .map(|position| usize::try_from(*position))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut levels: Vec<Vec<Scalar>> = Vec::with_capacity(TREE_DEPTH + 1);
+        levels.push(leaves.par_iter().map(|leaf| leaf.0).collect());
+
+        for depth in 0..TREE_DEPTH {
+            let next_level = levels
+                .last()
+                .unwrap()
+                .par_chunks(2)
+                .map(|pair| {
+                    let right = pair.get(1).unwrap_or(&pair[0]);
+                    MerkleNoteHash::combine_hash(depth, &pair[0], right)
+                })
+                .collect();
+            levels.push(next_level);
+        }
+
+        let root_hash = levels[TREE_DEPTH][0];
+
+        Ok(indices
+            .par_iter()
+            .map(|position| {
+                let auth_path = levels
+                    .iter()
+                    .take(TREE_DEPTH)
+                    .enumerate()
+                    .map(|(depth, level)| {
+                        let index = position >> depth;
+
+                        if index % 2 == 0 {
+                            WitnessNode::Left(*level.get(index + 1).unwrap_or(&level[index]))
+                        } else {
+                            WitnessNode::Right(level[index - 1])
+                        }
+                    })
+                    .collect();
+
+                Witness {
+                    tree_size,
+                    root_hash,
+                    auth_path,
+                }
+            })
+            .collect())
+    }
+
+    /// Bring this witness for `leaf` up to date with the leaves appended to
+    /// the tree since, without rebuilding the tree. `frontier` is the
+    /// frontier of the tree at the size of this witness.
+    ///
+    /// Siblings on the left, and complete siblings on the right, can't change
+    /// as the tree grows. The only siblings to recompute are the ones the
+    /// appended leaves went into, which the frontier and those leaves are
+    /// enough for, so refreshing costs a few hashes per appended leaf.
+    pub fn refresh(
+        &self,
+        leaf: &MerkleNoteHash,
+        frontier: &TreeFrontier,
+        appended: &[MerkleNoteHash],
+    ) -> Result<Witness, IronfishError> {
+        check_auth_path(leaf, &self.auth_path, self.tree_size, &self.root_hash)?;
+        if frontier.size() != self.tree_size || frontier.root() != Some(self.root_hash) {
+            return Err(IronfishError::InconsistentWitness);
+        }
+
+        let position = NotePosition::from_auth_path(&self.auth_path);
+
+        // Right siblings completed by the appended leaves
+        let mut completed: Vec<Option<Scalar>> = vec![None; TREE_DEPTH];
+        let mut frontier = frontier.clone();
+        for appended_leaf in appended {
+            frontier.append_visiting(appended_leaf, |level, index, node| {
+                if level < TREE_DEPTH && index == position.index_at(level) ^ 1 {
+                    completed[level] = Some(*node);
+                }
+            })?;
+        }
+        let tree_size = frontier.size();
+
+        let mut cur_hash = leaf.0;
+        let mut auth_path = Vec::with_capacity(TREE_DEPTH);
+        for (level, node) in self.auth_path.iter().enumerate() {
+            let node = match node {
+                WitnessNode::Right(left_hash) => WitnessNode::Right(*left_hash),
+                WitnessNode::Left(right_hash) => {
+                    let sibling = position.index_at(level) + 1;
+                    let right_hash = if (sibling + 1) << level <= self.tree_size.as_u64() {
+                        *right_hash
+                    } else if let Some(completed) = completed[level] {
+                        completed
+                    } else if sibling << level >= tree_size.as_u64() {
+                        // Still no sibling, so the node is hashed with itself
+                        cur_hash
+                    } else {
+                        frontier
+                            .edge_node(level)
+                            .ok_or(IronfishError::InconsistentWitness)?
+                    };
+                    WitnessNode::Left(right_hash)
+                }
+            };
+
+            cur_hash = match &node {
+                WitnessNode::Left(right_hash) => {
+                    MerkleNoteHash::combine_hash(level, &cur_hash, right_hash)
+                }
+                WitnessNode::Right(left_hash) => {
+                    MerkleNoteHash::combine_hash(level, left_hash, &cur_hash)
+                }
+            };
+            auth_path.push(node);
+        }
+
+        let root_hash = frontier.root().ok_or(IronfishError::InconsistentWitness)?;
+        if cur_hash != root_hash {
+            return Err(IronfishError::InconsistentWitness);
+        }
+
+        Ok(Witness {
+            tree_size,
+            root_hash,
+            auth_path,
+        })
+    }
+}
+
+/// Why an authentication path doesn't prove that a leaf is in a tree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WitnessCheckFailure {
+    /// The path doesn't have exactly one node per level of the tree
+    WrongDepth { expected: usize, actual: usize },
+
+    /// The path places the leaf past the end of the tree
+    PositionOutOfRange {
+        position: NotePosition,
+        tree_size: TreeSize,
+    },
+
+    /// The node at this level has no right sibling in a tree of this size,
+    /// so it must be hashed with itself, but the path has another sibling
+    MissingSibling { level: usize },
+
+    /// Every level is consistent with the tree size, but the path leads to
+    /// a different root
+    RootMismatch { computed: Scalar },
+}
+
+/// Recompute the root from `leaf` and its authentication path and compare it
+/// with the claimed root, checking each level against the size of the tree
+/// along the way. This is cheap compared to proving a spend, so wallets can
+/// catch a stale or corrupt witness before starting a proof.
+pub fn check_auth_path(
+    leaf: &MerkleNoteHash,
+    auth_path: &[WitnessNode<Scalar>],
+    tree_size: TreeSize,
+    claimed_root: &Scalar,
+) -> Result<(), WitnessCheckFailure> {
+    if auth_path.len() != TREE_DEPTH {
+        return Err(WitnessCheckFailure::WrongDepth {
+            expected: TREE_DEPTH,
+            actual: auth_path.len(),
+        });
+    }
+
+    let position = NotePosition::from_auth_path(auth_path);
+    if !tree_size.contains(position) {
+        return Err(WitnessCheckFailure::PositionOutOfRange {
+            position,
+            tree_size,
+        });
     }
+
+    let mut cur_hash = leaf.0;
+    for (level, node) in auth_path.iter().enumerate() {
+        cur_hash = match node {
+            WitnessNode::Left(right_hash) => {
+                let index = position.index_at(level);
+                if index + 1 >= tree_size.level_size(level) && *right_hash != cur_hash {
+                    return Err(WitnessCheckFailure::MissingSibling { level });
+                }
+
+                MerkleNoteHash::combine_hash(level, &cur_hash, right_hash)
+            }
+            WitnessNode::Right(left_hash) => {
+                MerkleNoteHash::combine_hash(level, left_hash, &cur_hash)
+            }
+        };
+    }
+
+    if cur_hash != *claimed_root {
+        return Err(WitnessCheckFailure::RootMismatch { computed: cur_hash });
+    }
+
+    Ok(())
+}
+
+/// Check a witness for the given leaf, see [`check_auth_path`].
+pub fn check_witness(
+    witness: &dyn WitnessTrait,
+    leaf: &MerkleNoteHash,
+) -> Result<(), WitnessCheckFailure> {
+    check_auth_path(
+        leaf,
+        &witness.get_auth_path(),
+        TreeSize::from(witness.tree_size()),
+        &witness.root_hash(),
+    )
 }
 
 impl fmt::Debug for Witness {
@@ -95,3 +358,122 @@ impl fmt::Debug for Witness {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{check_witness, Witness, WitnessCheckFailure, WitnessNode, WitnessTrait};
+    use crate::{
+        errors::IronfishError,
+        tree_position::{NotePosition, TreeSize},
+        tree_snapshot::TreeFrontier,
+        MerkleNoteHash,
+    };
+    use bls12_381::Scalar;
+    use rand::{thread_rng, Rng};
+
+    #[test]
+    fn test_build_many_witnesses() {
+        let leaves: Vec<MerkleNoteHash> = (0..5)
+            .map(|_| MerkleNoteHash(Scalar::from(thread_rng().gen::<u64>())))
+            .collect();
+        let positions = [4, 0, 3].map(NotePosition::new);
+
+        let witnesses = Witness::build_many(&leaves, &positions).unwrap();
+        assert_eq!(witnesses.len(), positions.len());
+
+        for (witness, position) in witnesses.iter().zip(positions.iter()) {
+            assert!(witness.verify(&leaves[position.as_u64() as usize]));
+            assert_eq!(witness.tree_size(), 5);
+            assert_eq!(witness.root_hash(), witnesses[0].root_hash());
+        }
+
+        // The witness for one leaf doesn't prove another
+        assert!(!witnesses[0].verify(&leaves[3]));
+
+        assert!(Witness::build_many(&leaves, &[NotePosition::new(5)]).is_err());
+    }
+
+    #[test]
+    fn test_check_witness() {
+        let leaves: Vec<MerkleNoteHash> = (0..5)
+            .map(|_| MerkleNoteHash(Scalar::from(thread_rng().gen::<u64>())))
+            .collect();
+        let witnesses = Witness::build_many(&leaves, &[4, 1].map(NotePosition::new)).unwrap();
+
+        assert_eq!(check_witness(&witnesses[0], &leaves[4]), Ok(()));
+        assert_eq!(check_witness(&witnesses[1], &leaves[1]), Ok(()));
+        assert!(matches!(
+            check_witness(&witnesses[1], &leaves[2]),
+            Err(WitnessCheckFailure::RootMismatch { .. })
+        ));
+
+        // The last leaf has no sibling, so it's paired with itself
+        let mut tampered = Witness {
+            tree_size: witnesses[0].tree_size,
+            root_hash: witnesses[0].root_hash,
+            auth_path: witnesses[0].auth_path.clone(),
+        };
+        tampered.auth_path[0] = WitnessNode::Left(leaves[0].0);
+        assert_eq!(
+            check_witness(&tampered, &leaves[4]),
+            Err(WitnessCheckFailure::MissingSibling { level: 0 })
+        );
+
+        tampered.tree_size = TreeSize::new(4);
+        assert_eq!(
+            check_witness(&tampered, &leaves[4]),
+            Err(WitnessCheckFailure::PositionOutOfRange {
+                position: NotePosition::new(4),
+                tree_size: TreeSize::new(4)
+            })
+        );
+
+        tampered.auth_path.pop();
+        assert!(matches!(
+            check_witness(&tampered, &leaves[4]),
+            Err(WitnessCheckFailure::WrongDepth { actual: 31, .. })
+        ));
+    }
+
+    #[test]
+    fn test_refresh_witness() {
+        let leaves: Vec<MerkleNoteHash> = (0..12)
+            .map(|_| MerkleNoteHash(Scalar::from(thread_rng().gen::<u64>())))
+            .collect();
+
+        for old_size in 1..=9 {
+            let mut frontier = TreeFrontier::new();
+            for leaf in &leaves[..old_size] {
+                frontier.append(leaf).unwrap();
+            }
+
+            for position in 0..old_size {
+                let positions = [NotePosition::new(position as u64)];
+                let witness = Witness::build_many(&leaves[..old_size], &positions)
+                    .unwrap()
+                    .remove(0);
+
+                for new_size in old_size..=leaves.len() {
+                    let refreshed = witness
+                        .refresh(&leaves[position], &frontier, &leaves[old_size..new_size])
+                        .unwrap();
+                    let rebuilt = Witness::build_many(&leaves[..new_size], &positions).unwrap();
+                    assert_eq!(refreshed, rebuilt[0]);
+                }
+            }
+        }
+
+        // The frontier has to be of the tree the witness is for
+        let witness = Witness::build_many(&leaves[..4], &[NotePosition::new(1)])
+            .unwrap()
+            .remove(0);
+        let mut frontier = TreeFrontier::new();
+        for leaf in &leaves[..3] {
+            frontier.append(leaf).unwrap();
+        }
+        assert!(matches!(
+            witness.refresh(&leaves[1], &frontier, &leaves[4..]),
+            Err(IronfishError::InconsistentWitness)
+        ));
+    }
+}
@@ -0,0 +1,376 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at https://mozilla.org/MPL/2.0/. */
+
+//! Randomized sanity checks for anything that builds witnesses to the note
+//! tree, such as the wallet's tree or a light client's.
+//!
+//! A [`WitnessTree`] is grown one leaf at a time from a random insertion
+//! sequence, and after every leaf a few of its witnesses are checked: each
+//! has to verify for its leaf against the current root of the tree, and no
+//! copy with its root, a sibling or a side changed, or for another leaf, may
+//! verify. Insertion sequences come from a seed, so a failure can be replayed
+//! with [`check_witness_sanity`] and the seed it reports.
+
+use std::num::TryFromIntError;
+
+use bls12_381::Scalar;
+use ironfish_zkp::constants::TREE_DEPTH;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    errors::IronfishError,
+    tree_position::{NotePosition, TreeSize},
+    tree_snapshot::TreeFrontier,
+    witness::{check_auth_path, Witness, WitnessNode, WitnessTrait},
+    MerkleNoteHash,
+};
+
+/// A note tree that can give a witness for any of its leaves
+pub trait WitnessTree {
+    /// Append a leaf, returning its position
+    fn append(&mut self, leaf: MerkleNoteHash) -> Result<NotePosition, IronfishError>;
+
+    /// The root of the tree, or `None` while it's empty
+    fn root(&self) -> Option<Scalar>;
+
+    /// Witness for the leaf at the given position, against the current root
+    fn witness(&self, position: NotePosition) -> Result<Box<dyn WitnessTrait>, IronfishError>;
+}
+
+/// The tree as the crate builds it: the root from a [`TreeFrontier`] and
+/// witnesses from [`Witness::build_many`]
+#[derive(Default)]
+pub struct ReferenceTree {
+    leaves: Vec<MerkleNoteHash>,
+    frontier: TreeFrontier,
+}
+
+impl WitnessTree for ReferenceTree {
+    fn append(&mut self, leaf: MerkleNoteHash) -> Result<NotePosition, IronfishError> {
+        let position = self.frontier.append(&leaf)?;
+        self.leaves.push(leaf);
+        Ok(position)
+    }
+
+    fn root(&self) -> Option<Scalar> {
+        self.frontier.root()
+    }
+
+    fn witness(&self, position: NotePosition) -> Result<Box<dyn WitnessTrait>, IronfishError> {
+        let witness = Witness::build_many(&self.leaves, &[position])?.remove(0);
+        Ok(Box::new(witness))
+    }
+}
+
+/// How random insertion sequences are drawn
+#[derive(Clone, Copy, Debug)]
+pub struct InsertionStrategy {
+    /// Sequences have between one and this many leaves
+    pub max_leaves: usize,
+
+    /// Witnesses checked after every leaf, always including the newest leaf
+    pub witnesses_per_leaf: usize,
+}
+
+impl Default for InsertionStrategy {
+    /// Short sequences, which cover all the ragged edges a tree can have up
+    /// to a few levels and keep the checks cheap enough for unit tests
+    fn default() -> Self {
+        InsertionStrategy {
+            max_leaves: 16,
+            witnesses_per_leaf: 2,
+        }
+    }
+}
+
+impl InsertionStrategy {
+    /// Draw an insertion sequence. Some leaves repeat earlier ones, as
+    /// nothing stops the same commitment from being appended twice.
+    pub fn sample(&self, rng: &mut impl Rng) -> Vec<MerkleNoteHash> {
+        let count = rng.gen_range(1..=self.max_leaves.max(1));
+        let mut leaves: Vec<MerkleNoteHash> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let leaf = match leaves.is_empty() || rng.gen_ratio(7, 8) {
+                true => MerkleNoteHash::new(Scalar::from(rng.gen::<u64>())),
+                false => leaves[rng.gen_range(0..leaves.len())].clone(),
+            };
+            leaves.push(leaf);
+        }
+
+        leaves
+    }
+}
+
+/// A change to a valid witness that must make it invalid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mutation {
+    Root,
+    Sibling(usize),
+    Side(usize),
+    Leaf,
+}
+
+#[derive(Debug)]
+pub enum WitnessSanityFailure {
+    Tree(IronfishError),
+    WrongPosition {
+        expected: NotePosition,
+        actual: NotePosition,
+    },
+    /// The witness doesn't verify for its own leaf
+    Rejected {
+        position: NotePosition,
+        tree_size: TreeSize,
+    },
+    /// The witness isn't against the current root and size of the tree
+    StaleWitness {
+        position: NotePosition,
+        tree_size: TreeSize,
+    },
+    MutationAccepted {
+        position: NotePosition,
+        tree_size: TreeSize,
+        mutation: Mutation,
+    },
+}
+
+impl From<IronfishError> for WitnessSanityFailure {
+    fn from(error: IronfishError) -> Self {
+        WitnessSanityFailure::Tree(error)
+    }
+}
+
+impl From<TryFromIntError> for WitnessSanityFailure {
+    fn from(error: TryFromIntError) -> Self {
+        WitnessSanityFailure::Tree(error.into())
+    }
+}
+
+/// Grow a new tree from the insertion sequence drawn from `seed`, checking
+/// its witnesses after every leaf
+pub fn check_witness_sanity<T: WitnessTree>(
+    tree: &mut T,
+    strategy: &InsertionStrategy,
+    seed: u64,
+) -> Result<(), WitnessSanityFailure> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let leaves = strategy.sample(&mut rng);
+
+    for (index, leaf) in leaves.iter().enumerate() {
+        let expected = NotePosition::try_from(index)?;
+        let actual = tree.append(leaf.clone())?;
+        if actual != expected {
+            return Err(WitnessSanityFailure::WrongPosition { expected, actual });
+        }
+
+        let tree_size = TreeSize::try_from(index + 1)?;
+        let root = tree.root().ok_or(IronfishError::InconsistentWitness)?;
+
+        let mut positions = vec![index];
+        positions.extend((1..strategy.witnesses_per_leaf).map(|_| rng.gen_range(0..=index)));
+        for position in positions {
+            check_one(
+                tree,
+                &leaves,
+                NotePosition::try_from(position)?,
+                tree_size,
+                &root,
+                &mut rng,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Panic unless every tree made by `new_tree` passes
+/// [`check_witness_sanity`] for `cases` insertion sequences, naming the seed
+/// of the first that fails
+pub fn assert_witnesses_sane<T: WitnessTree>(
+    new_tree: impl Fn() -> T,
+    strategy: &InsertionStrategy,
+    cases: u64,
+) {
+    let first_seed: u64 = rand::thread_rng().gen();
+    for case in 0..cases {
+        let seed = first_seed.wrapping_add(case);
+        if let Err(failure) = check_witness_sanity(&mut new_tree(), strategy, seed) {
+            panic!("witness sanity failed with seed {}: {:?}", seed, failure);
+        }
+    }
+}
+
+fn check_one<T: WitnessTree>(
+    tree: &T,
+    leaves: &[MerkleNoteHash],
+    position: NotePosition,
+    tree_size: TreeSize,
+    root: &Scalar,
+    rng: &mut impl Rng,
+) -> Result<(), WitnessSanityFailure> {
+    let leaf = &leaves[usize::try_from(position)?];
+    let witness = tree.witness(position)?;
+
+    if !witness.verify(leaf) {
+        return Err(WitnessSanityFailure::Rejected {
+            position,
+            tree_size,
+        });
+    }
+
+    let auth_path = witness.get_auth_path();
+    if witness.root_hash() != *root
+        || witness.tree_size() != tree_size.as_u32()
+        || NotePosition::from_auth_path(&auth_path) != position
+        || check_auth_path(leaf, &auth_path, tree_size, root).is_err()
+    {
+        return Err(WitnessSanityFailure::StaleWitness {
+            position,
+            tree_size,
+        });
+    }
+
+    let witness = Witness {
+        tree_size,
+        root_hash: *root,
+        auth_path,
+    };
+    let level = rng.gen_range(0..TREE_DEPTH);
+
+    let mut mutations = vec![Mutation::Root, Mutation::Sibling(level)];
+    // Swapping the sides of two equal nodes doesn't change their parent
+    if sibling(&witness.auth_path[level]) != &node_at(&witness, leaf, level) {
+        mutations.push(Mutation::Side(level));
+    }
+    let other_leaf = leaves.iter().find(|other| *other != leaf);
+    if other_leaf.is_some() {
+        mutations.push(Mutation::Leaf);
+    }
+
+    for mutation in mutations {
+        let accepted = match (mutation, other_leaf) {
+            (Mutation::Leaf, Some(other_leaf)) => witness.verify(other_leaf),
+            _ => mutated(&witness, mutation).verify(leaf),
+        };
+
+        if accepted {
+            return Err(WitnessSanityFailure::MutationAccepted {
+                position,
+                tree_size,
+                mutation,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// A copy of the witness with the mutation applied. Mutating the leaf keeps
+/// the witness as it is, as it's verified for another leaf instead.
+fn mutated(witness: &Witness, mutation: Mutation) -> Witness {
+    let mut auth_path = witness.auth_path.clone();
+    let mut root_hash = witness.root_hash;
+
+    match mutation {
+        Mutation::Root => root_hash += Scalar::one(),
+        Mutation::Sibling(level) => {
+            auth_path[level] = match &auth_path[level] {
+                WitnessNode::Left(hash) => WitnessNode::Left(hash + Scalar::one()),
+                WitnessNode::Right(hash) => WitnessNode::Right(hash + Scalar::one()),
+            }
+        }
+        Mutation::Side(level) => {
+            auth_path[level] = match &auth_path[level] {
+                WitnessNode::Left(hash) => WitnessNode::Right(*hash),
+                WitnessNode::Right(hash) => WitnessNode::Left(*hash),
+            }
+        }
+        Mutation::Leaf => {}
+    }
+
+    Witness {
+        tree_size: witness.tree_size,
+        root_hash,
+        auth_path,
+    }
+}
+
+fn sibling(node: &WitnessNode<Scalar>) -> &Scalar {
+    match node {
+        WitnessNode::Left(hash) | WitnessNode::Right(hash) => hash,
+    }
+}
+
+/// Hash of the node on the path of the witness at the given level
+fn node_at(witness: &Witness, leaf: &MerkleNoteHash, level: usize) -> Scalar {
+    witness.auth_path[..level]
+        .iter()
+        .enumerate()
+        .fold(leaf.0, |node, (depth, path_node)| match path_node {
+            WitnessNode::Left(right) => MerkleNoteHash::combine_hash(depth, &node, right),
+            WitnessNode::Right(left) => MerkleNoteHash::combine_hash(depth, left, &node),
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        assert_witnesses_sane, check_witness_sanity, InsertionStrategy, ReferenceTree,
+        WitnessSanityFailure, WitnessTree,
+    };
+    use crate::{
+        errors::IronfishError,
+        tree_position::NotePosition,
+        witness::{Witness, WitnessNode, WitnessTrait},
+        MerkleNoteHash,
+    };
+    use bls12_381::Scalar;
+
+    #[test]
+    fn test_reference_tree_witnesses_are_sane() {
+        assert_witnesses_sane(ReferenceTree::default, &InsertionStrategy::default(), 4);
+    }
+
+    /// Gives witnesses whose first sibling is always a left sibling, wrong
+    /// for every leaf at an odd position
+    #[derive(Default)]
+    struct LeftLeaningTree(ReferenceTree);
+
+    impl WitnessTree for LeftLeaningTree {
+        fn append(&mut self, leaf: MerkleNoteHash) -> Result<NotePosition, IronfishError> {
+            self.0.append(leaf)
+        }
+
+        fn root(&self) -> Option<Scalar> {
+            self.0.root()
+        }
+
+        fn witness(&self, position: NotePosition) -> Result<Box<dyn WitnessTrait>, IronfishError> {
+            let mut witness = Witness::build_many(&self.0.leaves, &[position])?.remove(0);
+            if let WitnessNode::Right(hash) = witness.auth_path[0] {
+                witness.auth_path[0] = WitnessNode::Left(hash);
+            }
+            Ok(Box::new(witness))
+        }
+    }
+
+    #[test]
+    fn test_broken_tree_is_caught() {
+        let strategy = InsertionStrategy {
+            max_leaves: 4,
+            witnesses_per_leaf: 1,
+        };
+
+        // Some sequence soon has a second leaf, whose witness is checked
+        let failure = (0..32)
+            .find_map(|seed| {
+                check_witness_sanity(&mut LeftLeaningTree::default(), &strategy, seed).err()
+            })
+            .expect("broken witnesses should be caught");
+        assert!(matches!(
+            failure,
+            WitnessSanityFailure::Rejected { .. } | WitnessSanityFailure::StaleWitness { .. }
+        ));
+    }
+}
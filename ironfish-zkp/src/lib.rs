@@ -1,11 +1,12 @@
 mod circuits;
 pub mod constants;
+pub mod pedersen_hash;
 pub mod primitives;
 pub mod util;
 
 pub use zcash_primitives::sapling::{
-    group_hash::group_hash, pedersen_hash, redjubjub, Diversifier, Note as SaplingNote, Nullifier,
-    PaymentAddress, ProofGenerationKey, Rseed, ViewingKey,
+    group_hash::group_hash, redjubjub, Diversifier, Note as SaplingNote, Nullifier, PaymentAddress,
+    ProofGenerationKey, Rseed, ViewingKey,
 };
 
 pub mod proofs {
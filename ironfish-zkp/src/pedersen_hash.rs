@@ -0,0 +1,117 @@
+//! The Pedersen hash used for note commitments and for the note commitment
+//! tree, along with the generator tables it's computed with.
+//!
+//! This is the Sapling Pedersen hash. The input bits, prefixed with the 6
+//! bits of the [`Personalization`], are split into 3 bit chunks, and every
+//! run of [`PEDERSEN_HASH_CHUNKS_PER_GENERATOR`] chunks is encoded as a
+//! scalar and multiplied by the next of the [`PEDERSEN_HASH_GENERATORS`].
+//! The hash is the sum of those multiples. Outside of a circuit, the
+//! multiplications use [`PEDERSEN_HASH_EXP_TABLE`], which holds the multiples
+//! of each generator for every window of [`PEDERSEN_HASH_EXP_WINDOW_SIZE`]
+//! scalar bits. Inside the circuits, the lookups use
+//! [`PEDERSEN_CIRCUIT_GENERATORS`], the multiples of each chunk's base point
+//! in Montgomery coordinates.
+//!
+//! Note commitments hash the asset generator, value, owner and sender bytes
+//! with [`Personalization::NoteCommitment`], each byte taken least
+//! significant bit first; see [`pedersen_hash_bytes`]. Merkle tree nodes hash
+//! the bits of their two children with [`Personalization::MerkleTree`] and
+//! the depth of the node.
+
+use jubjub::SubgroupPoint;
+
+pub use zcash_primitives::constants::{
+    PEDERSEN_HASH_CHUNKS_PER_GENERATOR, PEDERSEN_HASH_EXP_TABLE, PEDERSEN_HASH_EXP_WINDOW_SIZE,
+    PEDERSEN_HASH_GENERATORS,
+};
+pub use zcash_primitives::sapling::pedersen_hash::{pedersen_hash, Personalization};
+pub use zcash_proofs::constants::PEDERSEN_CIRCUIT_GENERATORS;
+
+/// Hash the given bytes, taking the bits of each byte least significant bit
+/// first, in the same order note commitments do.
+pub fn pedersen_hash_bytes(personalization: Personalization, bytes: &[u8]) -> SubgroupPoint {
+    pedersen_hash(
+        personalization,
+        bytes
+            .iter()
+            .flat_map(|byte| (0..8).map(move |i| ((byte >> i) & 1) == 1)),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use group::GroupEncoding;
+
+    use super::{
+        pedersen_hash, pedersen_hash_bytes, Personalization, PEDERSEN_HASH_EXP_TABLE,
+        PEDERSEN_HASH_GENERATORS,
+    };
+    use crate::group_hash;
+
+    #[test]
+    fn test_generators() {
+        assert_eq!(
+            PEDERSEN_HASH_GENERATORS[0].to_bytes(),
+            [
+                202, 60, 36, 50, 212, 171, 191, 119, 50, 70, 78, 192, 139, 46, 71, 249, 94, 220,
+                126, 131, 107, 22, 201, 121, 87, 27, 82, 211, 162, 135, 158, 168
+            ]
+        );
+
+        for (index, generator) in PEDERSEN_HASH_GENERATORS.iter().enumerate() {
+            // Each generator is the first valid group hash of its index,
+            // followed by a counter byte
+            let mut tag = (index as u32).to_le_bytes().to_vec();
+            tag.push(0);
+            let expected = loop {
+                if let Some(point) = group_hash(&tag, b"Zcash_PH") {
+                    break point;
+                }
+                tag[4] += 1;
+            };
+            assert_eq!(*generator, expected);
+
+            // The first window of the table holds the small multiples
+            assert_eq!(PEDERSEN_HASH_EXP_TABLE[index][0][1], *generator);
+        }
+    }
+
+    #[test]
+    fn test_vectors() {
+        // Only the personalization bits are hashed
+        assert_eq!(
+            pedersen_hash(Personalization::NoteCommitment, vec![]).to_bytes(),
+            [
+                130, 201, 203, 16, 72, 13, 180, 93, 30, 209, 22, 142, 80, 85, 157, 139, 224, 196,
+                85, 30, 58, 57, 150, 164, 222, 240, 82, 102, 83, 15, 231, 188
+            ]
+        );
+
+        // The size of the contents of a note commitment
+        let note_contents: Vec<u8> = (0..104u32).map(|i| (i * 7 % 256) as u8).collect();
+        assert_eq!(
+            pedersen_hash_bytes(Personalization::NoteCommitment, &note_contents).to_bytes(),
+            [
+                136, 12, 38, 121, 226, 107, 184, 196, 169, 38, 207, 221, 94, 106, 99, 163, 50, 111,
+                155, 148, 10, 99, 122, 98, 218, 153, 219, 139, 245, 108, 60, 109
+            ]
+        );
+
+        // Long enough to use three generators
+        let children: Vec<u8> = (0..64).collect();
+        assert_eq!(
+            pedersen_hash_bytes(Personalization::MerkleTree(0), &children).to_bytes(),
+            [
+                234, 118, 47, 190, 111, 40, 191, 7, 24, 2, 122, 189, 170, 75, 73, 218, 226, 77,
+                101, 73, 183, 51, 140, 41, 0, 250, 169, 160, 255, 171, 57, 179
+            ]
+        );
+        assert_eq!(
+            pedersen_hash_bytes(Personalization::MerkleTree(31), &children).to_bytes(),
+            [
+                152, 187, 41, 216, 89, 114, 82, 139, 31, 132, 38, 180, 133, 210, 195, 215, 152, 32,
+                41, 193, 42, 85, 179, 233, 58, 163, 138, 159, 176, 65, 24, 182
+            ]
+        );
+    }
+}
@@ -3,12 +3,13 @@ use std::io::Write;
 use byteorder::{LittleEndian, WriteBytesExt};
 use ff::PrimeField;
 use group::{cofactor::CofactorGroup, Group, GroupEncoding};
-use zcash_primitives::{
-    constants::NOTE_COMMITMENT_RANDOMNESS_GENERATOR,
-    sapling::pedersen_hash::{pedersen_hash, Personalization},
-};
+use subtle::CtOption;
+use zcash_primitives::constants::NOTE_COMMITMENT_RANDOMNESS_GENERATOR;
 
-use crate::constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION;
+use crate::{
+    constants::VALUE_COMMITMENT_GENERATOR_PERSONALIZATION,
+    pedersen_hash::{pedersen_hash_bytes, Personalization},
+};
 
 /// Computes the note commitment with sender address, returning the full point.
 pub fn commitment_full_point(
@@ -43,33 +44,15 @@ pub fn commitment_full_point(
     );
 
     // Compute the Pedersen hash of the note contents
-    let hash_of_contents = pedersen_hash(
-        Personalization::NoteCommitment,
-        note_contents
-            .into_iter()
-            .flat_map(|byte| (0..8).map(move |i| ((byte >> i) & 1) == 1)),
-    );
+    let hash_of_contents = pedersen_hash_bytes(Personalization::NoteCommitment, &note_contents);
 
     // Compute final commitment
     (NOTE_COMMITMENT_RANDOMNESS_GENERATOR * rcm) + hash_of_contents
 }
 
 /// This is a lightly modified group_hash function, for use with the asset identifier/generator flow
-#[allow(clippy::assertions_on_constants)]
 pub fn asset_hash_to_point(tag: &[u8]) -> Option<jubjub::ExtendedPoint> {
-    assert_eq!(VALUE_COMMITMENT_GENERATOR_PERSONALIZATION.len(), 8);
-
-    // Check to see that scalar field is 255 bits
-    assert!(bls12_381::Scalar::NUM_BITS == 255);
-
-    let h = blake2s_simd::Params::new()
-        .hash_length(32)
-        .personal(VALUE_COMMITMENT_GENERATOR_PERSONALIZATION)
-        .to_state()
-        .update(tag)
-        .finalize();
-
-    let p = jubjub::ExtendedPoint::from_bytes(h.as_array());
+    let p = jubjub::ExtendedPoint::from_bytes(&asset_hash(tag));
     if p.is_some().into() {
         let p = p.unwrap();
 
@@ -87,3 +70,32 @@ pub fn asset_hash_to_point(tag: &[u8]) -> Option<jubjub::ExtendedPoint> {
         None
     }
 }
+
+/// Same as [`asset_hash_to_point`], without branching on the tag, for asset
+/// identifiers that are secret such as those of notes being decrypted. The
+/// point is the identity when the result is none.
+pub fn asset_hash_to_point_ct(tag: &[u8]) -> CtOption<jubjub::ExtendedPoint> {
+    let p = jubjub::ExtendedPoint::from_bytes(&asset_hash(tag));
+    let is_on_curve = p.is_some();
+    let p = p.unwrap_or(jubjub::ExtendedPoint::identity());
+
+    let prime = CofactorGroup::clear_cofactor(&p);
+
+    CtOption::new(p, is_on_curve & !prime.is_identity())
+}
+
+#[allow(clippy::assertions_on_constants)]
+fn asset_hash(tag: &[u8]) -> [u8; 32] {
+    assert_eq!(VALUE_COMMITMENT_GENERATOR_PERSONALIZATION.len(), 8);
+
+    // Check to see that scalar field is 255 bits
+    assert!(bls12_381::Scalar::NUM_BITS == 255);
+
+    *blake2s_simd::Params::new()
+        .hash_length(32)
+        .personal(VALUE_COMMITMENT_GENERATOR_PERSONALIZATION)
+        .to_state()
+        .update(tag)
+        .finalize()
+        .as_array()
+}